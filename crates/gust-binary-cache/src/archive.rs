@@ -0,0 +1,498 @@
+//! Artifact archives: a zstd-compressed tar of a build directory.
+//!
+//! The first entry, `.gust-manifest.json`, maps every regular file in the
+//! archive to its blake3 hash. Restoring extracts regular files in parallel
+//! and checks each against the manifest, so a corrupt entry is reported by
+//! name instead of surfacing later as a broken build. Archives with
+//! hardlinks, repeated paths, paths outside the destination or unusual
+//! entry types are extracted sequentially in archive order, which is what
+//! those need. Archives from before the manifest existed are extracted
+//! without verification.
+//!
+//! Packing is deterministic: entries are sorted by path, and mtimes,
+//! owners and permissions are normalized, so the same build output always
+//! yields the same bytes (and the same signature) on any machine.
+
+use crate::BinaryCacheError;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Name of the hash manifest entry.
+pub const MANIFEST_ENTRY: &str = ".gust-manifest.json";
+
+/// Relative path to blake3 hash of every regular file.
+type HashManifest = BTreeMap<String, String>;
+
+/// Archive and compress the contents of `source`.
+pub fn pack(source: &Path) -> Result<Vec<u8>, BinaryCacheError> {
+    let mut files = Vec::new();
+    {
+        let mut builder = tar::Builder::new(&mut files);
+        append_sorted(&mut builder, source, Path::new(""))?;
+        builder.finish()?;
+    }
+
+    // Hash what actually went into the archive, then put the manifest in
+    // front of it. Both are streams of 512-byte blocks, so the manifest
+    // entry can simply be prepended.
+    let manifest: HashManifest = plan(&files)?
+        .files
+        .par_iter()
+        .map(|f| {
+            (
+                f.key.clone(),
+                blake3::hash(f.data(&files)).to_hex().to_string(),
+            )
+        })
+        .collect();
+    let manifest = serde_json::to_vec(&manifest).map_err(std::io::Error::other)?;
+
+    let mut header = normalized_header();
+    header.set_path(MANIFEST_ENTRY)?;
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    let padding = (512 - manifest.len() % 512) % 512;
+    let mut tar_data = Vec::with_capacity(512 + manifest.len() + padding + files.len());
+    tar_data.extend_from_slice(header.as_bytes());
+    tar_data.extend_from_slice(&manifest);
+    tar_data.resize(tar_data.len() + padding, 0);
+    tar_data.extend_from_slice(&files);
+
+    // zstd level 1: fast to write, and decompression speed barely depends
+    // on the level
+    zstd::encode_all(tar_data.as_slice(), 1)
+        .map_err(|e| BinaryCacheError::DecompressionError(e.to_string()))
+}
+
+/// Append the contents of `dir` under `prefix` in sorted order, with
+/// normalized headers. Symlinks are followed, as `append_dir_all` did.
+fn append_sorted(
+    builder: &mut tar::Builder<&mut Vec<u8>>,
+    dir: &Path,
+    prefix: &Path,
+) -> Result<(), BinaryCacheError> {
+    let mut names: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<Result<_, _>>()?;
+    names.sort();
+
+    for name in names {
+        let path = dir.join(&name);
+        let key = path_key(&prefix.join(&name));
+        let metadata = fs::metadata(&path)?;
+        let mut header = normalized_header();
+        if metadata.is_dir() {
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            builder.append_data(&mut header, format!("{}/", key), std::io::empty())?;
+            append_sorted(builder, &path, &prefix.join(&name))?;
+        } else {
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(if is_executable(&metadata) {
+                0o755
+            } else {
+                0o644
+            });
+            header.set_size(metadata.len());
+            builder.append_data(&mut header, key, fs::File::open(&path)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// A header with no owner and a zero mtime.
+fn normalized_header() -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(0);
+    header
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Decompress and extract an archive into `dest`, verifying file hashes.
+pub fn unpack(compressed: &[u8], dest: &Path) -> Result<(), BinaryCacheError> {
+    let tar_data = zstd::decode_all(compressed)
+        .map_err(|e| BinaryCacheError::DecompressionError(e.to_string()))?;
+    fs::create_dir_all(dest)?;
+
+    let plan = plan(&tar_data)?;
+    if plan.sequential {
+        tracing::debug!("Archive needs ordered extraction, unpacking sequentially");
+        return unpack_sequential(&tar_data, dest, plan.manifest.as_ref());
+    }
+    unpack_parallel(&tar_data, dest, &plan)
+}
+
+/// A regular file and where its contents sit in the tar data.
+struct FileEntry {
+    /// Normalized relative path, `/`-separated
+    key: String,
+    path: PathBuf,
+    offset: usize,
+    len: usize,
+    mode: u32,
+    mtime: u64,
+}
+
+impl FileEntry {
+    fn data<'a>(&self, tar_data: &'a [u8]) -> &'a [u8] {
+        &tar_data[self.offset..self.offset + self.len]
+    }
+}
+
+/// What extraction has to do, from one pass over the headers.
+#[derive(Default)]
+struct Plan {
+    manifest: Option<HashManifest>,
+    dirs: Vec<PathBuf>,
+    files: Vec<FileEntry>,
+    /// Symlinks, created after the files in archive order
+    symlinks: Vec<(PathBuf, PathBuf)>,
+    /// Whether parallel extraction isn't safe for this archive
+    sequential: bool,
+}
+
+fn plan(tar_data: &[u8]) -> Result<Plan, BinaryCacheError> {
+    let mut plan = Plan::default();
+    let mut seen = HashSet::new();
+    let mut archive = tar::Archive::new(tar_data);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let Some(path) = normalize(&entry.path()?) else {
+            plan.sequential = true;
+            continue;
+        };
+        let key = path_key(&path);
+        if key.is_empty() {
+            continue;
+        }
+
+        let header = entry.header();
+        match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous if key == MANIFEST_ENTRY => {
+                let mut json = Vec::new();
+                std::io::Read::read_to_end(&mut entry, &mut json)?;
+                plan.manifest = serde_json::from_slice(&json).ok();
+            }
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                if !seen.insert(key.clone()) {
+                    plan.sequential = true;
+                }
+                plan.files.push(FileEntry {
+                    key,
+                    path,
+                    offset: entry.raw_file_position() as usize,
+                    len: entry.size() as usize,
+                    mode: header.mode().unwrap_or(0o644),
+                    mtime: header.mtime().unwrap_or(0),
+                });
+            }
+            tar::EntryType::Directory => plan.dirs.push(path),
+            tar::EntryType::Symlink => match entry.link_name()? {
+                Some(target) => plan.symlinks.push((path, target.into_owned())),
+                None => plan.sequential = true,
+            },
+            // Hardlinks need their target extracted first, and anything else
+            // is left to the tar crate
+            _ => plan.sequential = true,
+        }
+    }
+
+    Ok(plan)
+}
+
+/// `path` without `.` components, or `None` if it could escape the
+/// destination.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(normalized)
+}
+
+fn path_key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn unpack_parallel(tar_data: &[u8], dest: &Path, plan: &Plan) -> Result<(), BinaryCacheError> {
+    // Directories first, so files can be written in any order
+    let mut dirs: Vec<PathBuf> = plan.dirs.iter().map(|d| dest.join(d)).collect();
+    dirs.extend(
+        plan.files
+            .iter()
+            .filter_map(|f| dest.join(&f.path).parent().map(Path::to_path_buf)),
+    );
+    dirs.sort();
+    dirs.dedup();
+    for dir in &dirs {
+        fs::create_dir_all(dir)?;
+    }
+
+    plan.files.par_iter().try_for_each(|file| {
+        let data = file.data(tar_data);
+        if let Some(manifest) = &plan.manifest {
+            verify(manifest, &file.key, data)?;
+        }
+        write_file(&dest.join(&file.path), data, file.mode, file.mtime)
+    })?;
+
+    if let Some(manifest) = &plan.manifest {
+        let present: HashSet<&str> = plan.files.iter().map(|f| f.key.as_str()).collect();
+        if let Some((missing, expected)) = manifest
+            .iter()
+            .find(|(key, _)| !present.contains(key.as_str()))
+        {
+            return Err(corrupt(missing, expected, "missing"));
+        }
+    }
+
+    for (path, target) in &plan.symlinks {
+        let link = dest.join(path);
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _ = fs::remove_file(&link);
+        symlink(target, &link)?;
+    }
+
+    Ok(())
+}
+
+/// Extract entries one at a time with the tar crate, then check the result
+/// against the manifest.
+fn unpack_sequential(
+    tar_data: &[u8],
+    dest: &Path,
+    manifest: Option<&HashManifest>,
+) -> Result<(), BinaryCacheError> {
+    fs::create_dir_all(dest)?;
+    let mut archive = tar::Archive::new(tar_data);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if normalize(&entry.path()?).is_some_and(|p| path_key(&p) == MANIFEST_ENTRY) {
+            continue;
+        }
+        // Normalized archives have no mtimes; those files keep the time
+        // they were extracted
+        entry.set_preserve_mtime(entry.header().mtime().unwrap_or(0) != 0);
+        entry.unpack_in(dest)?;
+    }
+
+    if let Some(manifest) = manifest {
+        manifest
+            .par_iter()
+            .try_for_each(|(key, expected)| match fs::read(dest.join(key)) {
+                Ok(data) => verify(manifest, key, &data),
+                Err(_) => Err(corrupt(key, expected, "missing")),
+            })?;
+    }
+    Ok(())
+}
+
+fn verify(manifest: &HashManifest, key: &str, data: &[u8]) -> Result<(), BinaryCacheError> {
+    let actual = blake3::hash(data).to_hex().to_string();
+    match manifest.get(key) {
+        Some(expected) if *expected == actual => Ok(()),
+        Some(expected) => Err(corrupt(key, expected, &actual)),
+        None => Err(corrupt(key, "no entry in the manifest", &actual)),
+    }
+}
+
+fn corrupt(key: &str, expected: &str, actual: &str) -> BinaryCacheError {
+    BinaryCacheError::CorruptEntry {
+        path: key.to_string(),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+    }
+}
+
+fn write_file(path: &Path, data: &[u8], mode: u32, mtime: u64) -> Result<(), BinaryCacheError> {
+    // Replace rather than write through an existing symlink or hardlink
+    let _ = fs::remove_file(path);
+    let file = fs::File::create(path)?;
+    std::io::Write::write_all(&mut &file, data)?;
+    if mtime != 0 {
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A build directory with `count` files spread over a few modules.
+    fn synthetic_build(root: &Path, count: usize) {
+        for i in 0..count {
+            let dir = root.join(format!("debug/Module{}.build", i % 8));
+            fs::create_dir_all(&dir).unwrap();
+            let content: Vec<u8> = (0..16 * 1024).map(|b| ((b * 31 + i) % 251) as u8).collect();
+            fs::write(dir.join(format!("file{}.o", i)), content).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parallel_restore_of_many_files() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let source = tmp.path().join("source");
+        synthetic_build(&source, 400);
+
+        let compressed = pack(&source).unwrap();
+
+        let parallel = tmp.path().join("parallel");
+        unpack(&compressed, &parallel).unwrap();
+
+        let sequential = tmp.path().join("sequential");
+        let tar_data = zstd::decode_all(compressed.as_slice()).unwrap();
+        let manifest = plan(&tar_data).unwrap().manifest;
+        unpack_sequential(&tar_data, &sequential, manifest.as_ref()).unwrap();
+
+        assert_eq!(manifest.unwrap().len(), 400);
+        assert!(!parallel.join(MANIFEST_ENTRY).exists());
+        assert!(!sequential.join(MANIFEST_ENTRY).exists());
+        for i in [0, 199, 399] {
+            let rel = format!("debug/Module{}.build/file{}.o", i % 8, i);
+            let expected = fs::read(source.join(&rel)).unwrap();
+            assert_eq!(fs::read(parallel.join(&rel)).unwrap(), expected);
+            assert_eq!(fs::read(sequential.join(&rel)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_packing_is_deterministic() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let first = tmp.path().join("first");
+        let second = tmp.path().join("second");
+        synthetic_build(&first, 20);
+        // The same files, created in the opposite order a minute later
+        for i in (0..20).rev() {
+            let rel = format!("debug/Module{}.build/file{}.o", i % 8, i);
+            fs::create_dir_all(second.join(&rel).parent().unwrap()).unwrap();
+            fs::copy(first.join(&rel), second.join(&rel)).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(second.join(&rel))
+                .unwrap()
+                .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+                .unwrap();
+        }
+
+        let packed = pack(&first).unwrap();
+        assert_eq!(pack(&first).unwrap(), packed);
+        assert_eq!(pack(&second).unwrap(), packed);
+
+        // Restored files get the time they were extracted
+        let dest = tmp.path().join("dest");
+        unpack(&packed, &dest).unwrap();
+        let modified = fs::metadata(dest.join("debug/Module0.build/file0.o"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert!(modified > UNIX_EPOCH + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_named() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir_all(source.join("debug")).unwrap();
+        fs::write(source.join("debug/App"), b"executable contents").unwrap();
+        fs::write(source.join("debug/libCore.a"), b"library contents").unwrap();
+
+        // Flip a byte inside one file's data
+        let mut tar_data = zstd::decode_all(pack(&source).unwrap().as_slice()).unwrap();
+        let offset = plan(&tar_data)
+            .unwrap()
+            .files
+            .iter()
+            .find(|f| f.key == "debug/libCore.a")
+            .unwrap()
+            .offset;
+        tar_data[offset] ^= 0xff;
+        let corrupted = zstd::encode_all(tar_data.as_slice(), 1).unwrap();
+
+        match unpack(&corrupted, &tmp.path().join("dest")) {
+            Err(BinaryCacheError::CorruptEntry { path, .. }) => {
+                assert_eq!(path, "debug/libCore.a");
+            }
+            other => panic!("expected CorruptEntry, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hardlinks_fall_back_to_sequential() {
+        let mut tar_data = Vec::new();
+        {
+            let mut builder = tar::Builder::new(&mut tar_data);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "debug/App", &b"app!"[..])
+                .unwrap();
+            let mut link = tar::Header::new_gnu();
+            link.set_entry_type(tar::EntryType::Link);
+            link.set_size(0);
+            builder
+                .append_link(&mut link, "debug/App-link", "debug/App")
+                .unwrap();
+            builder.finish().unwrap();
+        }
+        assert!(plan(&tar_data).unwrap().sequential);
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let compressed = zstd::encode_all(tar_data.as_slice(), 1).unwrap();
+        unpack(&compressed, tmp.path()).unwrap();
+        assert_eq!(
+            fs::read(tmp.path().join("debug/App-link")).unwrap(),
+            b"app!"
+        );
+    }
+}
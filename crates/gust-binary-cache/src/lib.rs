@@ -3,12 +3,18 @@
 //! Caches compiled Swift modules and object files to skip redundant builds.
 //! Supports both local disk cache and remote artifact servers.
 
+mod archive;
+
+pub use archive::MANIFEST_ENTRY;
+pub use gust_cache::{CacheReport, SymlinkPolicy};
+
 use blake3::Hasher;
-use gust_types::BuildConfiguration;
+use gust_types::{BuildConfiguration, Target, TargetType};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -23,6 +29,26 @@ pub enum BinaryCacheError {
     InvalidSignature,
     #[error("Decompression error: {0}")]
     DecompressionError(String),
+    #[error("Corrupt entry {path} in cached artifact: expected hash {expected}, got {actual}")]
+    CorruptEntry {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Downloaded artifact {fingerprint} has digest {actual}, expected {expected}")]
+    DigestMismatch {
+        fingerprint: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Incomplete download of {fingerprint}: got {received} of {expected} bytes")]
+    IncompleteDownload {
+        fingerprint: String,
+        received: u64,
+        expected: u64,
+    },
+    #[error(transparent)]
+    Cache(#[from] gust_cache::CacheError),
 }
 
 /// Build fingerprint for cache lookup.
@@ -42,6 +68,18 @@ pub struct BuildFingerprint {
     pub build_config: BuildConfiguration,
     /// Compiler flags
     pub swift_flags: Vec<String>,
+    /// Enabled package features
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Environment variables passed to the build
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// SDK the build compiles against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk: Option<String>,
+    /// Extra compiler flags for individual dependencies, by dependency
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependency_flags: BTreeMap<String, Vec<String>>,
     /// Combined fingerprint
     pub fingerprint: String,
 }
@@ -78,9 +116,158 @@ impl BuildFingerprint {
             platform,
             build_config,
             swift_flags,
+            features: Vec::new(),
+            env: BTreeMap::new(),
+            sdk: None,
+            dependency_flags: BTreeMap::new(),
             fingerprint,
         }
     }
+
+    /// Include the enabled package features in the fingerprint.
+    ///
+    /// An empty feature set leaves the fingerprint unchanged.
+    pub fn with_features(mut self, mut features: Vec<String>) -> Self {
+        features.sort();
+        features.dedup();
+        if !features.is_empty() {
+            let mut hasher = Hasher::new();
+            hasher.update(self.fingerprint.as_bytes());
+            for feature in &features {
+                hasher.update(b"feature:");
+                hasher.update(feature.as_bytes());
+            }
+            self.fingerprint = hasher.finalize().to_hex().to_string();
+        }
+        self.features = features;
+        self
+    }
+
+    /// Include the build's environment variables in the fingerprint.
+    ///
+    /// An empty environment leaves the fingerprint unchanged.
+    pub fn with_env<'a>(mut self, env: impl IntoIterator<Item = (&'a String, &'a String)>) -> Self {
+        let env: BTreeMap<String, String> = env
+            .into_iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        if !env.is_empty() {
+            let mut hasher = Hasher::new();
+            hasher.update(self.fingerprint.as_bytes());
+            for (key, value) in &env {
+                hasher.update(b"env:");
+                hasher.update(key.as_bytes());
+                hasher.update(b"=");
+                hasher.update(value.as_bytes());
+            }
+            self.fingerprint = hasher.finalize().to_hex().to_string();
+        }
+        self.env = env;
+        self
+    }
+
+    /// Include the SDK the build compiles against in the fingerprint.
+    ///
+    /// No SDK leaves the fingerprint unchanged.
+    pub fn with_sdk(mut self, sdk: Option<&Path>) -> Self {
+        self.sdk = sdk.map(|path| path.display().to_string());
+        if let Some(sdk) = &self.sdk {
+            let mut hasher = Hasher::new();
+            hasher.update(self.fingerprint.as_bytes());
+            hasher.update(b"sdk:");
+            hasher.update(sdk.as_bytes());
+            self.fingerprint = hasher.finalize().to_hex().to_string();
+        }
+        self
+    }
+
+    /// Include the compiler flags set for individual dependencies in the
+    /// fingerprint.
+    ///
+    /// No flags leave the fingerprint unchanged.
+    pub fn with_dependency_flags(mut self, flags: BTreeMap<String, Vec<String>>) -> Self {
+        if !flags.is_empty() {
+            let mut hasher = Hasher::new();
+            hasher.update(self.fingerprint.as_bytes());
+            for (name, flags) in &flags {
+                hasher.update(b"dependency:");
+                hasher.update(name.as_bytes());
+                for flag in flags {
+                    hasher.update(b"flag:");
+                    hasher.update(flag.as_bytes());
+                }
+            }
+            self.fingerprint = hasher.finalize().to_hex().to_string();
+        }
+        self.dependency_flags = flags;
+        self
+    }
+
+    /// The inputs that make up the fingerprint, by name.
+    pub fn components(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("source_hash", self.source_hash.clone()),
+            ("manifest_hash", self.manifest_hash.clone()),
+            ("deps_hash", self.deps_hash.clone()),
+            ("swift_version", self.swift_version.clone()),
+            ("platform", self.platform.clone()),
+            ("build_config", self.build_config.to_string()),
+            ("swift_flags", format!("{:?}", self.swift_flags)),
+            ("features", format!("{:?}", self.features)),
+            ("env", format!("{:?}", self.env)),
+            ("sdk", self.sdk.clone().unwrap_or_default()),
+            ("dependency_flags", format!("{:?}", self.dependency_flags)),
+        ]
+    }
+
+    /// List the components that differ from a previous fingerprint.
+    pub fn diff(&self, previous: &BuildFingerprint) -> Vec<FingerprintChange> {
+        self.components()
+            .into_iter()
+            .zip(previous.components())
+            .filter(|((_, new), (_, old))| new != old)
+            .map(|((component, new), (_, old))| FingerprintChange {
+                component,
+                old,
+                new,
+            })
+            .collect()
+    }
+}
+
+/// A fingerprint component that changed between two builds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintChange {
+    /// Component name, as in [`BuildFingerprint::components`]
+    pub component: &'static str,
+    /// Value in the previous fingerprint
+    pub old: String,
+    /// Value in the current fingerprint
+    pub new: String,
+}
+
+impl FingerprintChange {
+    /// What the change means for the build.
+    pub fn reason(&self) -> &'static str {
+        match self.component {
+            "source_hash" => "source files differ",
+            "manifest_hash" => "manifest differs",
+            "deps_hash" => "lockfile differs",
+            "swift_version" => "Swift toolchain differs",
+            "platform" => "platform differs",
+            "build_config" => "build configuration differs",
+            "swift_flags" => "compiler flags differ",
+            "features" => "enabled features differ",
+            "dependency_flags" => "dependency compiler flags differ",
+            _ => "changed",
+        }
+    }
+}
+
+impl std::fmt::Display for FingerprintChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} changed: {}", self.component, self.reason())
+    }
 }
 
 /// Artifact metadata.
@@ -95,6 +282,31 @@ pub struct ArtifactInfo {
     pub compression: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub signature: Option<String>,
+    /// `blake3:<hex>` of the compressed archive, set by `push`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+/// Attempts per `pull` before giving up on an interrupted or corrupt
+/// download.
+const PULL_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry of a `pull`; it doubles for each one after.
+const PULL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// HEAD requests in flight at once when a server has no batch endpoint.
+const EXISTS_CONCURRENCY: usize = 16;
+
+/// Body of a batch existence check.
+#[derive(Serialize)]
+struct ExistsRequest<'a> {
+    fingerprints: &'a [String],
+}
+
+/// Reply to a batch existence check; fingerprints left out are missing.
+#[derive(Deserialize)]
+struct ExistsResponse {
+    artifacts: HashMap<String, bool>,
 }
 
 /// Binary cache client.
@@ -105,6 +317,10 @@ pub struct BinaryCacheClient {
     client: reqwest::Client,
     /// Authentication token
     auth_token: Option<String>,
+    /// Upload metadata even when the artifact is already in the cache
+    update_metadata: bool,
+    /// Wait before the first retry of a download
+    retry_backoff: Duration,
 }
 
 impl BinaryCacheClient {
@@ -113,6 +329,8 @@ impl BinaryCacheClient {
             base_url: base_url.into(),
             client: reqwest::Client::new(),
             auth_token: None,
+            update_metadata: false,
+            retry_backoff: PULL_BACKOFF,
         }
     }
 
@@ -121,6 +339,20 @@ impl BinaryCacheClient {
         self
     }
 
+    /// Wait `backoff` before retrying a failed download, doubling it for
+    /// each retry after that.
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Refresh an artifact's metadata when pushing a fingerprint the cache
+    /// already has, instead of leaving it alone.
+    pub fn with_metadata_updates(mut self, update: bool) -> Self {
+        self.update_metadata = update;
+        self
+    }
+
     /// Check if an artifact exists in the cache.
     pub async fn exists(&self, fingerprint: &str) -> Result<bool, BinaryCacheError> {
         let url = format!("{}/artifacts/{}", self.base_url, fingerprint);
@@ -128,6 +360,64 @@ impl BinaryCacheClient {
         Ok(resp.status().is_success())
     }
 
+    /// Check which of `fingerprints` exist in the cache.
+    ///
+    /// Asks with a single `POST /artifacts/exists` and falls back to a HEAD
+    /// per fingerprint, a few at a time, for servers without that endpoint.
+    pub async fn exists_many(
+        &self,
+        fingerprints: &[String],
+    ) -> Result<HashMap<String, bool>, BinaryCacheError> {
+        if fingerprints.is_empty() {
+            return Ok(HashMap::new());
+        }
+        if let Some(found) = self.exists_batch(fingerprints).await? {
+            return Ok(fingerprints
+                .iter()
+                .map(|fp| (fp.clone(), found.get(fp).copied().unwrap_or(false)))
+                .collect());
+        }
+
+        use futures::stream::{self, StreamExt, TryStreamExt};
+        stream::iter(fingerprints)
+            .map(|fp| async move { Ok((fp.clone(), self.exists(fp).await?)) })
+            .buffer_unordered(EXISTS_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// The batch endpoint's answer, or `None` if the server doesn't have one.
+    async fn exists_batch(
+        &self,
+        fingerprints: &[String],
+    ) -> Result<Option<HashMap<String, bool>>, BinaryCacheError> {
+        let url = format!("{}/artifacts/exists", self.base_url);
+        let mut req = self.client.post(&url).json(&ExistsRequest { fingerprints });
+        if let Some(token) = &self.auth_token {
+            req = req.bearer_auth(token);
+        }
+
+        let resp = req.send().await?;
+        let status = resp.status();
+        if matches!(
+            status,
+            reqwest::StatusCode::NOT_FOUND
+                | reqwest::StatusCode::METHOD_NOT_ALLOWED
+                | reqwest::StatusCode::NOT_IMPLEMENTED
+        ) {
+            tracing::debug!("No batch existence check at {}, falling back to HEAD", url);
+            return Ok(None);
+        }
+        let resp = resp.error_for_status()?;
+        match resp.json::<ExistsResponse>().await {
+            Ok(body) => Ok(Some(body.artifacts)),
+            Err(e) => {
+                tracing::debug!("Unexpected reply from {}: {}, falling back to HEAD", url, e);
+                Ok(None)
+            }
+        }
+    }
+
     /// Get artifact metadata.
     pub async fn get_info(&self, fingerprint: &str) -> Result<ArtifactInfo, BinaryCacheError> {
         let url = format!("{}/artifacts/{}.info", self.base_url, fingerprint);
@@ -142,50 +432,171 @@ impl BinaryCacheClient {
     }
 
     /// Download and extract an artifact.
+    ///
+    /// The archive is downloaded to `.<fingerprint>.part` next to `dest`. If
+    /// the transfer is interrupted and the server accepts byte ranges, the
+    /// next attempt (or the next `pull`) resumes from the end of that file
+    /// instead of starting over. Retries wait longer each time. The
+    /// download is checked against the digest in the artifact's metadata
+    /// before it is unpacked, and one that doesn't match is downloaded
+    /// again from scratch.
     pub async fn pull(&self, fingerprint: &str, dest: &Path) -> Result<(), BinaryCacheError> {
-        let url = format!("{}/artifacts/{}", self.base_url, fingerprint);
-        let resp = self.client.get(&url).send().await?;
-
-        if !resp.status().is_success() {
-            return Err(BinaryCacheError::CacheMiss(fingerprint.to_string()));
+        let parent = dest
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(std::env::temp_dir);
+        fs::create_dir_all(&parent)?;
+        let partial = parent.join(format!(".{}.part", fingerprint));
+        let info = self.get_info(fingerprint).await?;
+
+        let mut attempt = 1;
+        loop {
+            let downloaded = match self.download(fingerprint, &partial).await {
+                Ok(()) => verify_digest(fingerprint, &partial, &info),
+                Err(e) => Err(e),
+            };
+            match downloaded {
+                Ok(()) => break,
+                Err(BinaryCacheError::CacheMiss(f)) => return Err(BinaryCacheError::CacheMiss(f)),
+                Err(e) if attempt < PULL_ATTEMPTS => {
+                    let wait = self.retry_backoff * 2u32.pow(attempt - 1);
+                    tracing::warn!(
+                        "Download of artifact {} failed ({}), retrying in {:?}",
+                        fingerprint,
+                        e,
+                        wait
+                    );
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&partial);
+                    return Err(e);
+                }
+            }
         }
 
-        let bytes = resp.bytes().await?;
-
-        // Decompress zstd
-        let decompressed = zstd::decode_all(bytes.as_ref())
-            .map_err(|e| BinaryCacheError::DecompressionError(e.to_string()))?;
-
-        // Extract tar
-        let mut archive = tar::Archive::new(decompressed.as_slice());
-        archive.unpack(dest)?;
+        let unpacked = fs::read(&partial)
+            .map_err(BinaryCacheError::from)
+            .and_then(|bytes| archive::unpack(&bytes, dest));
+        // A corrupt archive mustn't be resumed from on the next pull either
+        let _ = fs::remove_file(&partial);
+        unpacked?;
 
         tracing::info!("Pulled artifact {} to {}", fingerprint, dest.display());
         Ok(())
     }
 
-    /// Push an artifact to the cache.
+    /// Download an artifact into `partial`, resuming from its current length
+    /// when possible, and check the final size against the server's.
+    async fn download(&self, fingerprint: &str, partial: &Path) -> Result<(), BinaryCacheError> {
+        use tokio::io::AsyncWriteExt;
+
+        let url = format!("{}/artifacts/{}", self.base_url, fingerprint);
+        let offset = fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+
+        let mut req = self.client.get(&url);
+        if offset > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let mut resp = req.send().await?;
+
+        let status = resp.status();
+        let (append, expected) = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+            let total = resp
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(content_range_total);
+            (true, total)
+        } else if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // Whatever is on disk doesn't match the artifact; start over
+            let _ = fs::remove_file(partial);
+            return Err(BinaryCacheError::IncompleteDownload {
+                fingerprint: fingerprint.to_string(),
+                received: offset,
+                expected: 0,
+            });
+        } else if status.is_success() {
+            // Ranges unsupported (or nothing to resume): full download
+            (false, resp.content_length())
+        } else {
+            return Err(BinaryCacheError::CacheMiss(fingerprint.to_string()));
+        };
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(partial)
+            .await?;
+        // Keep what arrived before an interruption so the retry can resume
+        let streamed = async {
+            while let Some(chunk) = resp.chunk().await? {
+                file.write_all(&chunk).await?;
+            }
+            Ok::<_, BinaryCacheError>(())
+        }
+        .await;
+        file.flush().await?;
+        streamed?;
+
+        let received = fs::metadata(partial)?.len();
+        match expected {
+            Some(expected) if expected != received => Err(BinaryCacheError::IncompleteDownload {
+                fingerprint: fingerprint.to_string(),
+                received,
+                expected,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Push an artifact to the cache, unless it's already there.
+    ///
+    /// Returns whether the artifact was uploaded. The upload is a
+    /// conditional PUT (`If-None-Match: *`), so when several pushes of a
+    /// fingerprint race, the server keeps the first and turns the others
+    /// away with `412 Precondition Failed`, which counts as already there.
     pub async fn push(
         &self,
         fingerprint: &str,
         source: &Path,
         info: &ArtifactInfo,
-    ) -> Result<(), BinaryCacheError> {
-        // Create tar archive
-        let mut tar_data = Vec::new();
-        {
-            let mut builder = tar::Builder::new(&mut tar_data);
-            builder.append_dir_all(".", source)?;
-            builder.finish()?;
+    ) -> Result<bool, BinaryCacheError> {
+        if self.exists(fingerprint).await? {
+            tracing::debug!("Artifact {} is already in the cache", fingerprint);
+            if self.update_metadata {
+                // Keep the digest recorded when the archive was uploaded
+                let info = match self.get_info(fingerprint).await {
+                    Ok(existing) if info.digest.is_none() => ArtifactInfo {
+                        file_size: existing.file_size,
+                        digest: existing.digest,
+                        ..info.clone()
+                    },
+                    _ => info.clone(),
+                };
+                self.push_info(fingerprint, &info).await?;
+            }
+            return Ok(false);
         }
 
-        // Compress with zstd level 1 (fast compression, ARM decompresses quickly regardless)
-        let compressed = zstd::encode_all(tar_data.as_slice(), 1)
-            .map_err(|e| BinaryCacheError::DecompressionError(e.to_string()))?;
+        let compressed = archive::pack(source)?;
+        let info = ArtifactInfo {
+            file_size: compressed.len() as u64,
+            digest: Some(archive_digest(&compressed)),
+            ..info.clone()
+        };
 
         // Upload
         let url = format!("{}/artifacts/{}", self.base_url, fingerprint);
-        let mut req = self.client.put(&url).body(compressed);
+        let mut req = self
+            .client
+            .put(&url)
+            .header(reqwest::header::IF_NONE_MATCH, "*")
+            .body(compressed);
 
         if let Some(token) = &self.auth_token {
             req = req.bearer_auth(token);
@@ -193,13 +604,27 @@ impl BinaryCacheClient {
 
         let resp = req.send().await?;
 
+        if resp.status() == reqwest::StatusCode::PRECONDITION_FAILED {
+            tracing::debug!("Artifact {} was pushed by someone else first", fingerprint);
+            return Ok(false);
+        }
         if !resp.status().is_success() {
             return Err(BinaryCacheError::NetworkError(
                 resp.error_for_status().unwrap_err(),
             ));
         }
 
-        // Upload metadata
+        self.push_info(fingerprint, &info).await?;
+        tracing::info!("Pushed artifact {}", fingerprint);
+        Ok(true)
+    }
+
+    /// Upload an artifact's metadata.
+    async fn push_info(
+        &self,
+        fingerprint: &str,
+        info: &ArtifactInfo,
+    ) -> Result<(), BinaryCacheError> {
         let info_url = format!("{}/artifacts/{}.info", self.base_url, fingerprint);
         let mut info_req = self.client.put(&info_url).json(info);
 
@@ -208,12 +633,65 @@ impl BinaryCacheClient {
         }
 
         info_req.send().await?;
-
-        tracing::info!("Pushed artifact {}", fingerprint);
         Ok(())
     }
 }
 
+/// The digest recorded in [`ArtifactInfo::digest`] for a compressed archive.
+fn archive_digest(compressed: &[u8]) -> String {
+    format!("blake3:{}", blake3::hash(compressed).to_hex())
+}
+
+/// Check the download in `partial` against the artifact's metadata,
+/// removing it if it doesn't match so the next attempt starts over.
+/// Artifacts pushed before digests were recorded are checked by size.
+fn verify_digest(
+    fingerprint: &str,
+    partial: &Path,
+    info: &ArtifactInfo,
+) -> Result<(), BinaryCacheError> {
+    let bytes = fs::read(partial)?;
+    let mismatch = match &info.digest {
+        Some(expected) => {
+            let actual = archive_digest(&bytes);
+            (&actual != expected).then(|| BinaryCacheError::DigestMismatch {
+                fingerprint: fingerprint.to_string(),
+                expected: expected.clone(),
+                actual,
+            })
+        }
+        None => (info.file_size != 0 && info.file_size != bytes.len() as u64).then(|| {
+            BinaryCacheError::IncompleteDownload {
+                fingerprint: fingerprint.to_string(),
+                received: bytes.len() as u64,
+                expected: info.file_size,
+            }
+        }),
+    };
+    match mismatch {
+        Some(e) => {
+            fs::remove_file(partial)?;
+            Err(e)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Total size from a `Content-Range: bytes <start>-<end>/<total>` header.
+fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// A product recorded with a cached build, so a cache hit reports the same
+/// products as the build that stored it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedProduct {
+    pub name: String,
+    pub target_type: TargetType,
+    /// Relative to the build directory that was cached
+    pub path: PathBuf,
+}
+
 /// Local binary cache for offline access.
 pub struct LocalBinaryCache {
     cache_dir: PathBuf,
@@ -227,16 +705,8 @@ impl LocalBinaryCache {
 
     /// Open the default local binary cache.
     pub fn open() -> Result<Self, BinaryCacheError> {
-        let cache_dir = directories::ProjectDirs::from("dev", "gust", "gust")
-            .map(|d| d.cache_dir().join("binary-cache"))
-            .ok_or_else(|| {
-                BinaryCacheError::IoError(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "Could not determine cache directory",
-                ))
-            })?;
-
-        fs::create_dir_all(&cache_dir)?;
+        let cache_dir = gust_cache::GlobalCache::default_cache_dir()?.join("binary-cache");
+        gust_cache::ensure_writable(&cache_dir)?;
         Ok(Self { cache_dir })
     }
 
@@ -264,13 +734,7 @@ impl LocalBinaryCache {
             .ok_or_else(|| BinaryCacheError::CacheMiss(fingerprint.to_string()))?;
 
         let compressed = fs::read(&archive_path)?;
-        let decompressed = zstd::decode_all(compressed.as_slice())
-            .map_err(|e| BinaryCacheError::DecompressionError(e.to_string()))?;
-
-        fs::create_dir_all(dest)?;
-
-        let mut archive = tar::Archive::new(decompressed.as_slice());
-        archive.unpack(dest)?;
+        archive::unpack(&compressed, dest)?;
 
         tracing::info!(
             "Restored cached artifacts {} to {}",
@@ -291,18 +755,7 @@ impl LocalBinaryCache {
             return Ok(());
         }
 
-        // Create tar and compress
-        let mut tar_data = Vec::new();
-        {
-            let mut builder = tar::Builder::new(&mut tar_data);
-            builder.append_dir_all(".", source)?;
-            builder.finish()?;
-        }
-
-        // Use zstd level 1 for faster compression on Apple Silicon
-        // ARM CPUs decompress fast regardless of level, so optimize for write speed
-        let compressed = zstd::encode_all(tar_data.as_slice(), 1)
-            .map_err(|e| BinaryCacheError::DecompressionError(e.to_string()))?;
+        let compressed = archive::pack(source)?;
 
         fs::write(&dest, &compressed)?;
 
@@ -315,6 +768,43 @@ impl LocalBinaryCache {
         Ok(())
     }
 
+    /// Record the products of the build cached as `fingerprint`.
+    pub fn store_products(
+        &self,
+        fingerprint: &str,
+        products: &[CachedProduct],
+    ) -> Result<(), BinaryCacheError> {
+        fs::create_dir_all(&self.cache_dir)?;
+        let json = serde_json::to_vec_pretty(products)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        fs::write(self.products_path(fingerprint), json)?;
+        Ok(())
+    }
+
+    /// Products recorded with the build cached as `fingerprint`, if any.
+    /// Entries stored before products were recorded have none.
+    pub fn products(&self, fingerprint: &str) -> Option<Vec<CachedProduct>> {
+        let json = fs::read(self.products_path(fingerprint)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    fn products_path(&self, fingerprint: &str) -> PathBuf {
+        self.cache_dir
+            .join(format!("{}.products.json", fingerprint))
+    }
+
+    /// Remove a cached artifact, if present.
+    pub fn remove(&self, fingerprint: &str) -> Result<(), BinaryCacheError> {
+        if let Some(path) = self.get(fingerprint) {
+            fs::remove_file(path)?;
+        }
+        let products = self.products_path(fingerprint);
+        if products.exists() {
+            fs::remove_file(products)?;
+        }
+        Ok(())
+    }
+
     /// Get cache statistics.
     pub fn stats(&self) -> Result<CacheStats, BinaryCacheError> {
         let mut count = 0;
@@ -338,6 +828,13 @@ impl LocalBinaryCache {
         Ok(CacheStats { count, total_size })
     }
 
+    /// Entry count, size and ages of the cached artifacts.
+    pub fn report(&self) -> Result<CacheReport, BinaryCacheError> {
+        Ok(CacheReport::scan(&self.cache_dir, &|path| {
+            path.extension().map(|e| e == "zst").unwrap_or(false)
+        })?)
+    }
+
     /// Clear all cached artifacts.
     pub fn clear(&self) -> Result<usize, BinaryCacheError> {
         let mut cleared = 0;
@@ -353,6 +850,12 @@ impl LocalBinaryCache {
                 {
                     fs::remove_file(entry.path())?;
                     cleared += 1;
+                } else if entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".products.json")
+                {
+                    fs::remove_file(entry.path())?;
                 }
             }
         }
@@ -388,41 +891,43 @@ impl CacheStats {
 
 /// Hash all Swift source files in a directory.
 /// Uses rayon for parallel file hashing - optimized for Apple Silicon's many cores.
+///
+/// Symlinked directories are followed, but each real directory is hashed
+/// once, so shared folders and symlink cycles are safe.
 pub fn hash_sources(dir: &Path) -> Result<String, BinaryCacheError> {
-    use rayon::prelude::*;
-
-    // First, collect all source file paths (single-threaded, fast)
-    let mut source_files: Vec<PathBuf> = Vec::new();
-
-    fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), BinaryCacheError> {
-        if !dir.is_dir() {
-            return Ok(());
-        }
-
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            let name = path.file_name().unwrap().to_string_lossy();
+    hash_sources_with(dir, SymlinkPolicy::Follow)
+}
 
-            // Skip hidden files and build directories
-            if name.starts_with('.') || name == "Package.resolved" {
-                continue;
-            }
+/// Hash all source files in a directory, following symlinked directories
+/// or not as `symlinks` says.
+pub fn hash_sources_with(dir: &Path, symlinks: SymlinkPolicy) -> Result<String, BinaryCacheError> {
+    hash_sources_filtered(dir, symlinks, &|_| true)
+}
 
-            if path.is_dir() {
-                collect_files(&path, files)?;
-            } else {
-                // Only hash Swift source files and important config
-                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-                if matches!(ext, "swift" | "h" | "c" | "cpp" | "m" | "mm") {
-                    files.push(path);
-                }
-            }
-        }
-        Ok(())
-    }
+/// Hash the source files in `dir` whose path relative to `dir` passes
+/// `include`.
+fn hash_sources_filtered(
+    dir: &Path,
+    symlinks: SymlinkPolicy,
+    include: &(dyn Fn(&Path) -> bool + Sync),
+) -> Result<String, BinaryCacheError> {
+    use rayon::prelude::*;
 
-    collect_files(dir, &mut source_files)?;
+    // First, collect all source file paths (single-threaded, fast),
+    // skipping hidden files and build directories
+    let mut source_files: Vec<PathBuf> = gust_cache::collect_files(dir, symlinks, &|name| {
+        name.starts_with('.') || name == "Package.resolved"
+    })?
+    .into_iter()
+    .map(|(_, path)| path)
+    .collect();
+
+    // Only hash Swift source files and important config
+    source_files.retain(|path| {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        matches!(ext, "swift" | "h" | "c" | "cpp" | "m" | "mm")
+    });
+    source_files.retain(|path| include(path.strip_prefix(dir).unwrap_or(path)));
 
     // Parallel hash all files using rayon + mmap (Apple Silicon optimization)
     // mmap provides zero-copy reads directly from the kernel page cache
@@ -463,39 +968,86 @@ pub fn hash_sources(dir: &Path) -> Result<String, BinaryCacheError> {
 }
 
 /// Hash a target's source files specifically.
+///
+/// Uses the target's `path` if set, otherwise the usual `Sources/<name>`
+/// layouts. Only files selected by the target's `sources` (when given) and
+/// not under one of its `exclude` entries are hashed.
 pub fn hash_target_sources(
     project_dir: &Path,
-    target_name: &str,
+    target: &Target,
 ) -> Result<String, BinaryCacheError> {
-    // Try common source directory patterns
-    let possible_dirs = [
-        project_dir.join("Sources").join(target_name),
-        project_dir.join("Source").join(target_name),
-        project_dir.join("src").join(target_name),
-        project_dir.join(target_name),
-    ];
-
-    for dir in &possible_dirs {
-        if dir.exists() && dir.is_dir() {
-            return hash_sources(dir);
-        }
+    match target_source_dir(project_dir, target) {
+        Some(dir) => hash_sources_filtered(&dir, SymlinkPolicy::Follow, &|rel: &Path| {
+            target_includes(target, rel)
+        }),
+        // Fallback: hash all sources
+        None => hash_sources(&project_dir.join("Sources")),
     }
+}
+
+/// Hash the project's sources the way its targets select them: files a
+/// target's `sources` leaves out or its `exclude` names are skipped, so
+/// editing them doesn't change the hash.
+///
+/// Hashes `Sources`, or the whole project when there is no such directory.
+pub fn hash_project_sources(
+    project_dir: &Path,
+    targets: &[Target],
+) -> Result<String, BinaryCacheError> {
+    let target_dirs: Vec<(PathBuf, &Target)> = targets
+        .iter()
+        .filter_map(|t| Some((target_source_dir(project_dir, t)?, t)))
+        .collect();
+    let hash = |dir: &Path| {
+        hash_sources_filtered(dir, SymlinkPolicy::Follow, &|rel: &Path| {
+            let path = dir.join(rel);
+            target_dirs.iter().all(|(target_dir, target)| {
+                path.strip_prefix(target_dir)
+                    .map_or(true, |rel| target_includes(target, rel))
+            })
+        })
+    };
+    hash(&project_dir.join("Sources")).or_else(|_| hash(project_dir))
+}
+
+/// Directory holding `target`'s sources: its `path` if set, otherwise the
+/// first of the usual `Sources/<name>` layouts that exists.
+fn target_source_dir(project_dir: &Path, target: &Target) -> Option<PathBuf> {
+    let possible_dirs = match &target.path {
+        Some(path) => vec![project_dir.join(path)],
+        // Try common source directory patterns
+        None => vec![
+            project_dir.join("Sources").join(&target.name),
+            project_dir.join("Source").join(&target.name),
+            project_dir.join("src").join(&target.name),
+            project_dir.join(&target.name),
+        ],
+    };
+    possible_dirs.into_iter().find(|dir| dir.is_dir())
+}
 
-    // Fallback: hash all sources
-    hash_sources(&project_dir.join("Sources"))
+/// Whether `rel`, relative to the target's source directory, is selected by
+/// the target's `sources` (when given) and not under one of its `exclude`
+/// entries.
+fn target_includes(target: &Target, rel: &Path) -> bool {
+    let included = target
+        .sources
+        .as_ref()
+        .is_none_or(|sources| sources.iter().any(|s| rel.starts_with(s)));
+    included && !target.exclude.iter().any(|e| rel.starts_with(e))
 }
 
 /// Create a complete build fingerprint for a target.
 pub fn compute_target_fingerprint(
     project_dir: &Path,
-    target_name: &str,
+    target: &Target,
     deps_hash: &str,
     swift_version: &str,
     platform: &str,
     config: BuildConfiguration,
     flags: &[String],
 ) -> Result<BuildFingerprint, BinaryCacheError> {
-    let source_hash = hash_target_sources(project_dir, target_name)?;
+    let source_hash = hash_target_sources(project_dir, target)?;
 
     // Hash the manifest too
     let manifest_path = project_dir.join("Package.swift");
@@ -522,3 +1074,473 @@ pub fn compute_target_fingerprint(
         flags.to_vec(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gust_testkit::{MockServer, Response};
+
+    fn fingerprint(swift_flags: Vec<String>) -> BuildFingerprint {
+        BuildFingerprint::compute(
+            "sources".to_string(),
+            "manifest".to_string(),
+            "deps".to_string(),
+            "5.9".to_string(),
+            "macos-arm64".to_string(),
+            BuildConfiguration::Debug,
+            swift_flags,
+        )
+    }
+
+    #[test]
+    fn test_fingerprint_diff_names_changed_component() {
+        let previous = fingerprint(Vec::new());
+        let current = fingerprint(vec!["-Onone".to_string()]);
+        assert_ne!(previous.fingerprint, current.fingerprint);
+
+        let changes = current.diff(&previous);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].component, "swift_flags");
+        assert_eq!(changes[0].new, "[\"-Onone\"]");
+        assert_eq!(
+            changes[0].to_string(),
+            "swift_flags changed: compiler flags differ"
+        );
+
+        assert!(current.diff(&current).is_empty());
+    }
+
+    #[test]
+    fn test_features_change_fingerprint() {
+        let plain = fingerprint(Vec::new());
+        let same = fingerprint(Vec::new()).with_features(Vec::new());
+        assert_eq!(plain.fingerprint, same.fingerprint);
+
+        let server = fingerprint(Vec::new()).with_features(vec!["server".to_string()]);
+        assert_ne!(plain.fingerprint, server.fingerprint);
+
+        // Order doesn't matter
+        let a = fingerprint(Vec::new()).with_features(vec!["a".to_string(), "b".to_string()]);
+        let b = fingerprint(Vec::new()).with_features(vec!["b".to_string(), "a".to_string()]);
+        assert_eq!(a.fingerprint, b.fingerprint);
+
+        let changes = server.diff(&plain);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].component, "features");
+    }
+
+    #[test]
+    fn test_dependency_flags_change_fingerprint() {
+        let plain = fingerprint(Vec::new());
+        let same = fingerprint(Vec::new()).with_dependency_flags(BTreeMap::new());
+        assert_eq!(plain.fingerprint, same.fingerprint);
+
+        let flags =
+            |flag: &str| BTreeMap::from([("swift-nio".to_string(), vec![flag.to_string()])]);
+        let quiet = fingerprint(Vec::new()).with_dependency_flags(flags("-suppress-warnings"));
+        let strict = fingerprint(Vec::new()).with_dependency_flags(flags("-warnings-as-errors"));
+        assert_ne!(plain.fingerprint, quiet.fingerprint);
+        assert_ne!(quiet.fingerprint, strict.fingerprint);
+
+        let changes = strict.diff(&quiet);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].to_string(),
+            "dependency_flags changed: dependency compiler flags differ"
+        );
+    }
+
+    #[test]
+    fn test_excluded_files_dont_affect_target_hash() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("Sources").join("Core");
+        fs::create_dir_all(dir.join("Generated")).unwrap();
+        fs::write(dir.join("Core.swift"), "struct Core {}").unwrap();
+        fs::write(dir.join("Generated").join("Stub.swift"), "// v1").unwrap();
+
+        let target = Target {
+            exclude: vec![PathBuf::from("Generated")],
+            ..Target::library("Core")
+        };
+        let before = hash_target_sources(tmp.path(), &target).unwrap();
+        let unfiltered = hash_target_sources(tmp.path(), &Target::library("Core")).unwrap();
+
+        fs::write(dir.join("Generated").join("Stub.swift"), "// v2").unwrap();
+        assert_eq!(hash_target_sources(tmp.path(), &target).unwrap(), before);
+        assert_ne!(
+            hash_target_sources(tmp.path(), &Target::library("Core")).unwrap(),
+            unfiltered
+        );
+
+        // With explicit sources, only those are hashed
+        let only_core = Target {
+            sources: Some(vec![PathBuf::from("Core.swift")]),
+            ..Target::library("Core")
+        };
+        let before = hash_target_sources(tmp.path(), &only_core).unwrap();
+        fs::write(dir.join("Generated").join("Stub.swift"), "// v3").unwrap();
+        assert_eq!(hash_target_sources(tmp.path(), &only_core).unwrap(), before);
+        fs::write(dir.join("Core.swift"), "struct Core { let x = 1 }").unwrap();
+        assert_ne!(hash_target_sources(tmp.path(), &only_core).unwrap(), before);
+    }
+
+    #[test]
+    fn test_excluded_files_dont_affect_project_hash() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let core = tmp.path().join("Sources").join("Core");
+        let app = tmp.path().join("Sources").join("App");
+        fs::create_dir_all(core.join("Generated")).unwrap();
+        fs::create_dir_all(&app).unwrap();
+        fs::write(core.join("Core.swift"), "struct Core {}").unwrap();
+        fs::write(core.join("Generated").join("Stub.swift"), "// v1").unwrap();
+        fs::write(app.join("main.swift"), "print(1)").unwrap();
+
+        let targets = [
+            Target {
+                exclude: vec![PathBuf::from("Generated")],
+                ..Target::library("Core")
+            },
+            Target::library("App"),
+        ];
+        let before = hash_project_sources(tmp.path(), &targets).unwrap();
+
+        fs::write(core.join("Generated").join("Stub.swift"), "// v2").unwrap();
+        assert_eq!(hash_project_sources(tmp.path(), &targets).unwrap(), before);
+
+        fs::write(app.join("main.swift"), "print(2)").unwrap();
+        assert_ne!(hash_project_sources(tmp.path(), &targets).unwrap(), before);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_sources_terminates_on_symlink_cycle() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let sources = tmp.path().join("Sources");
+        fs::create_dir_all(sources.join("App")).unwrap();
+        fs::write(sources.join("App/main.swift"), "print(1)\n").unwrap();
+        let plain = hash_sources(&sources).unwrap();
+
+        std::os::unix::fs::symlink(&sources, sources.join("App/Loop")).unwrap();
+        assert_eq!(hash_sources(&sources).unwrap(), plain);
+        assert_eq!(
+            hash_sources_with(&sources, SymlinkPolicy::Skip).unwrap(),
+            plain
+        );
+    }
+
+    #[test]
+    fn test_report_counts_sizes_and_ages() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("binary");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.tar.zst"), vec![0; 10]).unwrap();
+        fs::write(dir.join("b.tar.zst"), vec![0; 20]).unwrap();
+        fs::write(dir.join("notes.txt"), "not an artifact").unwrap();
+        let old = dir.join("c.tar.zst");
+        fs::write(&old, vec![0; 30]).unwrap();
+        let forty_days = std::time::Duration::from_secs(40 * 24 * 60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - forty_days)
+            .unwrap();
+
+        let report = LocalBinaryCache::new(dir.clone()).report().unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["location"], dir.display().to_string());
+        assert_eq!(json["entries"], 3);
+        assert_eq!(json["total_bytes"], 60);
+        assert_eq!(
+            json["age_histogram"],
+            serde_json::json!({
+                "under_1_day": 2,
+                "under_1_week": 0,
+                "under_30_days": 0,
+                "older": 1,
+            })
+        );
+        let (oldest, newest) = (report.oldest.unwrap(), report.newest.unwrap());
+        assert!(newest - oldest >= forty_days.as_secs() - 1);
+    }
+
+    #[tokio::test]
+    async fn test_pull_resumes_interrupted_download() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("module.swiftmodule"), "x".repeat(64 * 1024)).unwrap();
+        let mut tar_data = Vec::new();
+        {
+            let mut builder = tar::Builder::new(&mut tar_data);
+            builder.append_dir_all(".", &source).unwrap();
+            builder.finish().unwrap();
+        }
+        let artifact = zstd::encode_all(tar_data.as_slice(), 1).unwrap();
+        let half = artifact.len() / 2;
+        let info = serde_json::to_string(&ArtifactInfo {
+            digest: Some(archive_digest(&artifact)),
+            ..artifact_info("abc123")
+        })
+        .unwrap();
+
+        // First response drops the connection halfway, the second honors
+        // the Range header with the rest
+        let server = MockServer::start(move |request| {
+            if request.path.ends_with(".info") {
+                return Response::json(&info);
+            }
+            let Some(range) = request.header("range") else {
+                return Response::new(200)
+                    .with_header("accept-ranges", "bytes")
+                    .with_body(artifact.clone())
+                    .cut_off_after(half);
+            };
+            let start: usize = range
+                .trim_start_matches("bytes=")
+                .trim_end_matches('-')
+                .parse()
+                .unwrap();
+            Response::new(206)
+                .with_header("accept-ranges", "bytes")
+                .with_header(
+                    "content-range",
+                    format!("bytes {}-{}/{}", start, artifact.len() - 1, artifact.len()),
+                )
+                .with_body(artifact[start..].to_vec())
+        })
+        .await;
+
+        let dest = tmp.path().join("out");
+        let client =
+            BinaryCacheClient::new(server.url()).with_retry_backoff(Duration::from_millis(1));
+        client.pull("abc123", &dest).await.unwrap();
+
+        let ranges: Vec<_> = server
+            .requests()
+            .iter()
+            .filter(|r| !r.path.ends_with(".info"))
+            .map(|r| r.header("range").map(str::to_string))
+            .collect();
+        assert_eq!(ranges, vec![None, Some(format!("bytes={}-", half))]);
+        assert_eq!(
+            fs::read_to_string(dest.join("module.swiftmodule"))
+                .unwrap()
+                .len(),
+            64 * 1024
+        );
+        assert!(!tmp.path().join(".abc123.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_pull_rejects_artifact_not_matching_its_digest() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("lib.a"), b"artifact").unwrap();
+        let artifact = archive::pack(&source).unwrap();
+        let info = serde_json::to_string(&ArtifactInfo {
+            digest: Some(archive_digest(&artifact)),
+            ..artifact_info("abc123")
+        })
+        .unwrap();
+
+        // The first download arrives corrupted, with the right length
+        let downloads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server = MockServer::start({
+            let (artifact, downloads) = (artifact.clone(), downloads.clone());
+            move |request| {
+                if request.path.ends_with(".info") {
+                    return Response::json(&info);
+                }
+                let mut body = artifact.clone();
+                if downloads.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                    body.iter_mut().for_each(|b| *b = !*b);
+                }
+                Response::new(200).with_body(body)
+            }
+        })
+        .await;
+
+        let client =
+            BinaryCacheClient::new(server.url()).with_retry_backoff(Duration::from_millis(1));
+        let dest = tmp.path().join("out");
+        client.pull("abc123", &dest).await.unwrap();
+        assert_eq!(fs::read(dest.join("lib.a")).unwrap(), b"artifact");
+        assert_eq!(downloads.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // Never matching, it fails without leaving the download behind
+        let server = MockServer::start(|request| {
+            if request.path.ends_with(".info") {
+                Response::json(
+                    r#"{"fingerprint": "abc123", "package": "p", "version": "1", "platform": "x",
+                        "swift_version": "5.9", "file_size": 3, "compression": "zstd",
+                        "digest": "blake3:0000"}"#,
+                )
+            } else {
+                Response::new(200).with_body(b"abc".to_vec())
+            }
+        })
+        .await;
+        let client =
+            BinaryCacheClient::new(server.url()).with_retry_backoff(Duration::from_millis(1));
+        let err = client.pull("abc123", &dest).await.unwrap_err();
+        assert!(
+            matches!(err, BinaryCacheError::DigestMismatch { .. }),
+            "{}",
+            err
+        );
+        assert_eq!(server.requests().len(), 1 + PULL_ATTEMPTS as usize);
+        assert!(!tmp.path().join(".abc123.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_exists_many_uses_batch_endpoint() {
+        let server =
+            MockServer::start(
+                |request| match (request.method.as_str(), request.path.as_str()) {
+                    ("POST", "/artifacts/exists") => {
+                        Response::json(r#"{"artifacts": {"aaa": true, "bbb": false}}"#)
+                    }
+                    _ => Response::new(500),
+                },
+            )
+            .await;
+
+        let client = BinaryCacheClient::new(server.url());
+        let fingerprints = ["aaa", "bbb", "ccc"].map(String::from);
+        let found = client.exists_many(&fingerprints).await.unwrap();
+        assert_eq!(
+            found,
+            HashMap::from([
+                ("aaa".to_string(), true),
+                ("bbb".to_string(), false),
+                ("ccc".to_string(), false),
+            ])
+        );
+        assert_eq!(server.request_lines(), vec!["POST /artifacts/exists"]);
+    }
+
+    fn artifact_info(fingerprint: &str) -> ArtifactInfo {
+        ArtifactInfo {
+            fingerprint: fingerprint.to_string(),
+            package: "swift-log".to_string(),
+            version: "1.5.0".to_string(),
+            platform: "arm64-apple-macosx".to_string(),
+            swift_version: "5.9".to_string(),
+            file_size: 0,
+            compression: "zstd".to_string(),
+            signature: None,
+            digest: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_skips_existing_artifact() {
+        let server =
+            MockServer::start(
+                |request| match (request.method.as_str(), request.path.as_str()) {
+                    ("HEAD", "/artifacts/aaa") => Response::new(200),
+                    ("HEAD", _) => Response::new(404),
+                    ("PUT", _) => Response::new(201),
+                    _ => Response::new(500),
+                },
+            )
+            .await;
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("lib.a"), b"artifact").unwrap();
+
+        let client = BinaryCacheClient::new(server.url());
+        assert!(client
+            .push("bbb", tmp.path(), &artifact_info("bbb"))
+            .await
+            .unwrap());
+        assert!(!client
+            .push("aaa", tmp.path(), &artifact_info("aaa"))
+            .await
+            .unwrap());
+        assert_eq!(
+            server.request_lines(),
+            vec![
+                "HEAD /artifacts/bbb",
+                "PUT /artifacts/bbb",
+                "PUT /artifacts/bbb.info",
+                "HEAD /artifacts/aaa",
+            ]
+        );
+
+        // Metadata can still be refreshed
+        server.clear();
+        let client = client.with_metadata_updates(true);
+        assert!(!client
+            .push("aaa", tmp.path(), &artifact_info("aaa"))
+            .await
+            .unwrap());
+        assert_eq!(
+            server.request_lines(),
+            vec![
+                "HEAD /artifacts/aaa",
+                "GET /artifacts/aaa.info",
+                "PUT /artifacts/aaa.info"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_losing_a_race_is_not_an_error() {
+        // Another shard uploaded between the existence check and the PUT
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "HEAD" => Response::new(404),
+            _ => Response::new(412),
+        })
+        .await;
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("lib.a"), b"artifact").unwrap();
+
+        let client = BinaryCacheClient::new(server.url());
+        assert!(!client
+            .push("aaa", tmp.path(), &artifact_info("aaa"))
+            .await
+            .unwrap());
+        assert_eq!(
+            server.request_lines(),
+            vec!["HEAD /artifacts/aaa", "PUT /artifacts/aaa"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exists_many_falls_back_to_head() {
+        let server =
+            MockServer::start(
+                |request| match (request.method.as_str(), request.path.as_str()) {
+                    ("HEAD", "/artifacts/aaa") | ("HEAD", "/artifacts/ccc") => Response::new(200),
+                    _ => Response::new(404),
+                },
+            )
+            .await;
+
+        let client = BinaryCacheClient::new(server.url());
+        let fingerprints = ["aaa", "bbb", "ccc"].map(String::from);
+        let found = client.exists_many(&fingerprints).await.unwrap();
+        assert_eq!(
+            found,
+            HashMap::from([
+                ("aaa".to_string(), true),
+                ("bbb".to_string(), false),
+                ("ccc".to_string(), true),
+            ])
+        );
+
+        let mut seen = server.request_lines();
+        seen.sort();
+        assert_eq!(
+            seen,
+            vec![
+                "HEAD /artifacts/aaa",
+                "HEAD /artifacts/bbb",
+                "HEAD /artifacts/ccc",
+                "POST /artifacts/exists",
+            ]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Code coverage for `swift test --enable-code-coverage`.
+//!
+//! SwiftPM writes raw profiles to `<scratch>/debug/codecov` and merges them
+//! into `default.profdata`. The instrumented test binary lives inside the
+//! `<Package>PackageTests.xctest` bundle on macOS and *is* the `.xctest`
+//! file on Linux. `llvm-cov` is taken from the Swift toolchain, since the
+//! one on `PATH` (if any) may not read the toolchain's profile format.
+
+use crate::BuildError;
+use gust_platform::SwiftToolchain;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Line coverage for one source target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetCoverage {
+    /// Target name (the directory under `Sources/`)
+    pub name: String,
+    /// Lines executed at least once
+    pub lines_covered: u64,
+    /// Executable lines
+    pub lines_total: u64,
+}
+
+impl TargetCoverage {
+    /// Covered lines as a percentage; 100 for a target with no lines.
+    pub fn percent(&self) -> f64 {
+        if self.lines_total == 0 {
+            100.0
+        } else {
+            self.lines_covered as f64 * 100.0 / self.lines_total as f64
+        }
+    }
+}
+
+/// Coverage data left behind by a `swift test --enable-code-coverage` run.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// `llvm-cov` from the Swift toolchain
+    pub llvm_cov: PathBuf,
+    /// Merged profile data
+    pub profdata: PathBuf,
+    /// Instrumented test binary
+    pub binary: PathBuf,
+}
+
+impl Coverage {
+    /// Locate coverage data for `package` in `build_dir` (`<scratch>/debug`).
+    ///
+    /// If SwiftPM left only raw `.profraw` files, they are merged with the
+    /// toolchain's `llvm-profdata` first.
+    pub async fn locate(
+        toolchain: &SwiftToolchain,
+        build_dir: &Path,
+        package: &str,
+    ) -> Result<Self, BuildError> {
+        let llvm_cov = find_llvm_tool(toolchain, "llvm-cov").ok_or_else(|| {
+            BuildError::CoverageError("llvm-cov not found in the Swift toolchain".to_string())
+        })?;
+
+        let binary = test_binary_path(build_dir, package);
+        if !binary.is_file() {
+            return Err(BuildError::CoverageError(format!(
+                "test binary not found at {}",
+                binary.display()
+            )));
+        }
+
+        let codecov = build_dir.join("codecov");
+        let profdata = codecov.join("default.profdata");
+        if !profdata.is_file() {
+            merge_profraw(toolchain, &codecov, &profdata).await?;
+        }
+
+        Ok(Self {
+            llvm_cov,
+            profdata,
+            binary,
+        })
+    }
+
+    /// Per-target line coverage for sources under `project_dir/Sources`,
+    /// sorted by target name.
+    pub async fn summary(&self, project_dir: &Path) -> Result<Vec<TargetCoverage>, BuildError> {
+        let json = self.export(&["-summary-only"]).await?;
+        parse_export_summary(&json, project_dir)
+    }
+
+    /// The full report in LCOV format.
+    pub async fn lcov(&self) -> Result<String, BuildError> {
+        self.export(&["-format=lcov"]).await
+    }
+
+    async fn export(&self, extra: &[&str]) -> Result<String, BuildError> {
+        let output = Command::new(&self.llvm_cov)
+            .arg("export")
+            .args(extra)
+            .arg(format!("-instr-profile={}", self.profdata.display()))
+            // Dependencies and the tests themselves aren't interesting
+            .arg("-ignore-filename-regex=(\\.build|Tests)/")
+            .arg(&self.binary)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(BuildError::CoverageError(format!(
+                "llvm-cov export failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Find an LLVM tool shipped with the Swift toolchain.
+///
+/// swift.org toolchains keep it next to `swift`; with Xcode, `/usr/bin/swift`
+/// is a shim and `xcrun` knows where the real toolchain is.
+pub fn find_llvm_tool(toolchain: &SwiftToolchain, tool: &str) -> Option<PathBuf> {
+    let swift = std::fs::canonicalize(&toolchain.swift_path)
+        .unwrap_or_else(|_| toolchain.swift_path.clone());
+    let beside = swift.parent()?.join(tool);
+    if beside.is_file() {
+        return Some(beside);
+    }
+
+    if cfg!(target_os = "macos") {
+        let output = std::process::Command::new("xcrun")
+            .args(["--find", tool])
+            .output()
+            .ok()?;
+        if output.status.success() {
+            let path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+/// The instrumented test binary SwiftPM builds for `package`.
+pub fn test_binary_path(build_dir: &Path, package: &str) -> PathBuf {
+    let bundle = build_dir.join(format!("{}PackageTests.xctest", package));
+    if cfg!(target_os = "macos") {
+        bundle
+            .join("Contents")
+            .join("MacOS")
+            .join(format!("{}PackageTests", package))
+    } else {
+        bundle
+    }
+}
+
+async fn merge_profraw(
+    toolchain: &SwiftToolchain,
+    codecov: &Path,
+    profdata: &Path,
+) -> Result<(), BuildError> {
+    let raw: Vec<PathBuf> = std::fs::read_dir(codecov)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "profraw"))
+                .collect()
+        })
+        .unwrap_or_default();
+    if raw.is_empty() {
+        return Err(BuildError::CoverageError(format!(
+            "no coverage data in {}; were the tests run with --enable-code-coverage?",
+            codecov.display()
+        )));
+    }
+
+    let llvm_profdata = find_llvm_tool(toolchain, "llvm-profdata").ok_or_else(|| {
+        BuildError::CoverageError("llvm-profdata not found in the Swift toolchain".to_string())
+    })?;
+    let output = Command::new(llvm_profdata)
+        .args(["merge", "-sparse"])
+        .args(&raw)
+        .arg("-o")
+        .arg(profdata)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(BuildError::CoverageError(format!(
+            "llvm-profdata merge failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Group an `llvm-cov export -summary-only` report by source target.
+///
+/// Files outside `project_dir/Sources` are skipped.
+pub fn parse_export_summary(
+    json: &str,
+    project_dir: &Path,
+) -> Result<Vec<TargetCoverage>, BuildError> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| BuildError::CoverageError(format!("invalid llvm-cov output: {}", e)))?;
+    let sources = project_dir.join("Sources");
+    let sources = std::fs::canonicalize(&sources).unwrap_or(sources);
+
+    let mut targets: Vec<TargetCoverage> = Vec::new();
+    let files = value["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|d| d["files"].as_array().into_iter().flatten());
+
+    for file in files {
+        let Some(filename) = file["filename"].as_str() else {
+            continue;
+        };
+        let path = Path::new(filename);
+        let relative = path
+            .strip_prefix(&sources)
+            .or_else(|_| path.strip_prefix(project_dir.join("Sources")));
+        let Some(target) = relative
+            .ok()
+            .and_then(|r| r.components().next())
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+
+        let lines = &file["summary"]["lines"];
+        let covered = lines["covered"].as_u64().unwrap_or(0);
+        let total = lines["count"].as_u64().unwrap_or(0);
+
+        match targets.iter_mut().find(|t| t.name == target) {
+            Some(entry) => {
+                entry.lines_covered += covered;
+                entry.lines_total += total;
+            }
+            None => targets.push(TargetCoverage {
+                name: target,
+                lines_covered: covered,
+                lines_total: total,
+            }),
+        }
+    }
+
+    targets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_export_summary() {
+        let project = Path::new("/work/app");
+        let json = r#"{
+            "data": [{
+                "files": [
+                    {"filename": "/work/app/Sources/Core/A.swift", "summary": {"lines": {"count": 10, "covered": 8}}},
+                    {"filename": "/work/app/Sources/Core/B.swift", "summary": {"lines": {"count": 10, "covered": 2}}},
+                    {"filename": "/work/app/Sources/CLI/main.swift", "summary": {"lines": {"count": 4, "covered": 4}}},
+                    {"filename": "/work/other/Sources/Dep/C.swift", "summary": {"lines": {"count": 7, "covered": 0}}}
+                ]
+            }],
+            "type": "llvm.coverage.json.export",
+            "version": "2.0.1"
+        }"#;
+
+        let targets = parse_export_summary(json, project).unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                TargetCoverage {
+                    name: "CLI".to_string(),
+                    lines_covered: 4,
+                    lines_total: 4,
+                },
+                TargetCoverage {
+                    name: "Core".to_string(),
+                    lines_covered: 10,
+                    lines_total: 20,
+                },
+            ]
+        );
+        assert_eq!(targets[1].percent(), 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_coverage_for_trivial_package() {
+        // Needs a real Swift toolchain with llvm-cov
+        let Ok(toolchain) = SwiftToolchain::detect() else {
+            return;
+        };
+        if find_llvm_tool(&toolchain, "llvm-cov").is_none() {
+            return;
+        }
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path();
+        std::fs::write(
+            project.join("Package.swift"),
+            "// swift-tools-version:5.7\nimport PackageDescription\n\nlet package = Package(\n    name: \"Tiny\",\n    targets: [\n        .target(name: \"Tiny\"),\n        .testTarget(name: \"TinyTests\", dependencies: [\"Tiny\"]),\n    ]\n)\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(project.join("Sources/Tiny")).unwrap();
+        std::fs::write(
+            project.join("Sources/Tiny/Tiny.swift"),
+            "public func double(_ x: Int) -> Int { x * 2 }\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(project.join("Tests/TinyTests")).unwrap();
+        std::fs::write(
+            project.join("Tests/TinyTests/TinyTests.swift"),
+            "import XCTest\n@testable import Tiny\n\nfinal class TinyTests: XCTestCase {\n    func testDouble() { XCTAssertEqual(double(2), 4) }\n}\n",
+        )
+        .unwrap();
+
+        let status = Command::new(&toolchain.swift_path)
+            .args(["test", "--enable-code-coverage"])
+            .current_dir(project)
+            .status()
+            .await
+            .unwrap();
+        if !status.success() {
+            // No XCTest in this toolchain
+            return;
+        }
+
+        let coverage = Coverage::locate(&toolchain, &project.join(".build/debug"), "Tiny")
+            .await
+            .unwrap();
+        let summary = coverage.summary(project).await.unwrap();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].name, "Tiny");
+        assert!(summary[0].lines_covered > 0);
+        assert!(coverage.lcov().await.unwrap().contains("SF:"));
+    }
+}
@@ -2,14 +2,42 @@
 //!
 //! Supports binary artifact caching for near-instant rebuilds.
 
-use gust_binary_cache::{hash_sources, BuildFingerprint, LocalBinaryCache};
+mod coverage;
+mod products;
+mod test_results;
+
+pub use coverage::{Coverage, TargetCoverage};
+pub use products::{
+    dynamic_library_name, locate_products, parse_describe, static_library_name, BuildProduct,
+    ProductInfo, ProductKind,
+};
+pub use test_results::TestCounts;
+
+use gust_binary_cache::{hash_project_sources, BuildFingerprint, CachedProduct, LocalBinaryCache};
+use gust_manifest::{FeatureSet, FEATURES_ENV};
 use gust_platform::SwiftToolchain;
-use gust_types::{BuildConfiguration, Manifest};
+use gust_types::{BuildConfiguration, Manifest, TargetType};
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use thiserror::Error;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use tokio::process::{Child, Command};
+use tokio_util::sync::CancellationToken;
+
+/// File in the target directory holding the fingerprint of the most recent
+/// build.
+pub const LAST_FINGERPRINT_FILE: &str = "gust-last-fingerprint.json";
+
+/// Default directory for checkouts and build products, relative to the project.
+pub const DEFAULT_TARGET_DIR: &str = ".build";
+
+/// Resolve the target directory for a project.
+///
+/// A relative `custom` path is taken relative to the project directory.
+pub fn target_dir(project_dir: &Path, custom: Option<&Path>) -> PathBuf {
+    project_dir.join(custom.unwrap_or(Path::new(DEFAULT_TARGET_DIR)))
+}
 
 #[derive(Error, Debug)]
 pub enum BuildError {
@@ -19,10 +47,29 @@ pub enum BuildError {
     BuildFailed(String),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
-    #[error("Target not found: {0}")]
-    TargetNotFound(String),
+    #[error("Target not found: {name}")]
+    TargetNotFound {
+        name: String,
+        /// Closest existing target, if the name looks like a typo
+        suggestion: Option<String>,
+    },
+    #[error("Target '{name}' is a {kind} target and can't be built directly")]
+    TargetNotBuildable { name: String, kind: &'static str },
     #[error("Cache error: {0}")]
     CacheError(#[from] gust_binary_cache::BinaryCacheError),
+    #[error("Coverage failed: {0}")]
+    CoverageError(String),
+    #[error("SDK not found: {}", .0.display())]
+    SdkNotFound(PathBuf),
+    #[error("Build interrupted")]
+    Interrupted,
+    #[error("System library '{library}' for target '{target}' was not found")]
+    SystemLibraryMissing {
+        target: String,
+        library: String,
+        /// Commands that install the library on this platform
+        install: Vec<String>,
+    },
 }
 
 /// Build options.
@@ -36,6 +83,18 @@ pub struct BuildOptions {
     pub target: Option<String>,
     /// Extra Swift flags
     pub swift_flags: Vec<String>,
+    /// Enabled package features, passed as `FEATURE_*` compilation conditions
+    pub features: Vec<String>,
+    /// Environment variables for `swift build`, on top of the manifest's
+    /// `[build.env]`
+    pub env: HashMap<String, String>,
+    /// SDK to compile against, passed to the compiler as `-sdk`
+    pub sdk: Option<PathBuf>,
+    /// Treat compiler warnings as errors
+    pub warnings_as_errors: bool,
+    /// Stops the build when cancelled; [`ctrl_c_token`] gives one that
+    /// Ctrl-C cancels
+    pub cancel: CancellationToken,
     /// Show verbose output
     pub verbose: bool,
     /// Enable binary artifact caching
@@ -51,6 +110,11 @@ impl Default for BuildOptions {
             jobs: None,
             target: None,
             swift_flags: Vec::new(),
+            features: Vec::new(),
+            env: HashMap::new(),
+            sdk: None,
+            warnings_as_errors: false,
+            cancel: CancellationToken::new(),
             verbose: false,
             use_cache: true,
             force_rebuild: false,
@@ -61,8 +125,8 @@ impl Default for BuildOptions {
 /// Build result.
 #[derive(Debug)]
 pub struct BuildResult {
-    /// Path to built products
-    pub products: Vec<PathBuf>,
+    /// Built products
+    pub products: Vec<BuildProduct>,
     /// Build duration in seconds
     pub duration_secs: f64,
     /// Whether the build was restored from cache
@@ -71,10 +135,27 @@ pub struct BuildResult {
     pub fingerprint: Option<String>,
 }
 
+impl BuildResult {
+    /// Paths of the built products.
+    pub fn product_paths(&self) -> Vec<PathBuf> {
+        self.products.iter().map(|p| p.path.clone()).collect()
+    }
+
+    /// The executable named `name`, or the first executable if `name` is
+    /// `None`.
+    pub fn executable(&self, name: Option<&str>) -> Option<&BuildProduct> {
+        self.products.iter().find(|p| {
+            p.target_type == TargetType::Executable && name.is_none_or(|name| p.name == name)
+        })
+    }
+}
+
 /// The build orchestrator.
 pub struct Builder {
     /// Project root directory
     project_dir: PathBuf,
+    /// Directory for build products (SwiftPM's scratch path)
+    target_dir: PathBuf,
     /// Swift toolchain
     toolchain: SwiftToolchain,
     /// Binary artifact cache
@@ -88,12 +169,32 @@ impl Builder {
         let binary_cache = LocalBinaryCache::open().ok();
 
         Ok(Self {
+            target_dir: target_dir(&project_dir, None),
             project_dir,
             toolchain,
             binary_cache,
         })
     }
 
+    /// Build into `dir` instead of `.build`.
+    pub fn with_target_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.target_dir = target_dir(&self.project_dir, Some(dir.as_ref()));
+        self
+    }
+
+    /// The directory build products are written to.
+    pub fn target_dir(&self) -> &Path {
+        &self.target_dir
+    }
+
+    /// Pass the target directory to SwiftPM unless it's the default, so
+    /// toolchains without `--scratch-path` keep working.
+    fn scratch_path_args(&self, cmd: &mut Command) {
+        if self.target_dir != target_dir(&self.project_dir, None) {
+            cmd.arg("--scratch-path").arg(&self.target_dir);
+        }
+    }
+
     /// Get the current platform identifier.
     fn platform_id(&self) -> String {
         #[cfg(target_os = "macos")]
@@ -116,15 +217,15 @@ impl Builder {
     }
 
     /// Compute build fingerprint for cache lookup.
-    fn compute_fingerprint(
+    pub fn compute_fingerprint(
         &self,
         manifest: &Manifest,
         options: &BuildOptions,
     ) -> Result<BuildFingerprint, BuildError> {
-        // Hash all sources in the project
-        let source_hash = hash_sources(&self.project_dir.join("Sources"))
-            .or_else(|_| hash_sources(&self.project_dir))
-            .unwrap_or_default();
+        // Hash the sources the targets build, so excluded files don't
+        // invalidate the cache
+        let source_hash =
+            hash_project_sources(&self.project_dir, &manifest.targets).unwrap_or_default();
 
         // Hash manifest
         let manifest_path = self.project_dir.join("Package.swift");
@@ -144,8 +245,7 @@ impl Builder {
         // Hash dependencies from lockfile if present
         let lockfile_path = self.project_dir.join("Gust.lock");
         let deps_hash = if lockfile_path.exists() {
-            let content = std::fs::read(&lockfile_path)?;
-            blake3::hash(&content).to_hex().to_string()
+            lockfile_deps_hash(&lockfile_path)?
         } else {
             // Hash dependency names as fallback
             let deps: Vec<_> = manifest.dependencies.keys().collect();
@@ -153,15 +253,62 @@ impl Builder {
             blake3::hash(deps_str.as_bytes()).to_hex().to_string()
         };
 
+        // Macro plugins are compiled for and loaded by the host compiler, so
+        // artifacts from a different toolchain install can't be reused
+        let swift_version = if manifest
+            .targets
+            .iter()
+            .any(|t| t.target_type == gust_types::TargetType::Macro)
+        {
+            format!(
+                "{}+host:{}",
+                self.toolchain.version,
+                self.toolchain.swift_path.display()
+            )
+        } else {
+            self.toolchain.version.clone()
+        };
+
         Ok(BuildFingerprint::compute(
             source_hash,
             manifest_hash,
             deps_hash,
-            self.toolchain.version.clone(),
+            swift_version,
             self.platform_id(),
             options.configuration,
-            options.swift_flags.clone(),
-        ))
+            compiler_flags(options),
+        )
+        .with_features(options.features.clone())
+        .with_env(&build_env(manifest, options))
+        .with_sdk(options.sdk.as_deref())
+        .with_dependency_flags(dependency_flags(manifest)))
+    }
+
+    /// Path of the fingerprint recorded by the most recent build.
+    fn last_fingerprint_path(&self) -> PathBuf {
+        self.target_dir.join(LAST_FINGERPRINT_FILE)
+    }
+
+    /// The fingerprint recorded by the most recent build, if any.
+    pub fn last_fingerprint(&self) -> Option<BuildFingerprint> {
+        let content = std::fs::read(self.last_fingerprint_path()).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Record `fingerprint` as the last build's; failing to only warns.
+    fn record_last_fingerprint(&self, fingerprint: &BuildFingerprint) {
+        if let Err(e) = self.save_last_fingerprint(fingerprint) {
+            tracing::warn!("Failed to record build fingerprint: {}", e);
+        }
+    }
+
+    fn save_last_fingerprint(&self, fingerprint: &BuildFingerprint) -> std::io::Result<()> {
+        let path = self.last_fingerprint_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(fingerprint).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
     }
 
     /// Build the project.
@@ -174,25 +321,23 @@ impl Builder {
 
         // Verify target exists if specified
         if let Some(target_name) = &options.target {
-            if !manifest.targets.iter().any(|t| &t.name == target_name) {
-                return Err(BuildError::TargetNotFound(target_name.clone()));
+            validate_target(manifest, target_name)?;
+        }
+        if let Some(sdk) = &options.sdk {
+            if !sdk.is_dir() {
+                return Err(BuildError::SdkNotFound(sdk.clone()));
             }
         }
 
-        // Compute build fingerprint for cache
+        // Compute build fingerprint for cache; it's recorded as the last
+        // build's only once the build succeeds
         let fingerprint = if options.use_cache {
             Some(self.compute_fingerprint(manifest, options)?)
         } else {
             None
         };
 
-        let build_dir = self
-            .project_dir
-            .join(".build")
-            .join(match options.configuration {
-                BuildConfiguration::Debug => "debug",
-                BuildConfiguration::Release => "release",
-            });
+        let build_dir = self.build_dir(options.configuration);
 
         // Check cache for existing build
         if options.use_cache && !options.force_rebuild {
@@ -200,29 +345,39 @@ impl Builder {
                 if cache.contains(&fp.fingerprint) {
                     tracing::info!("Cache hit for fingerprint {}", &fp.fingerprint[..16]);
 
-                    // Restore from cache
-                    std::fs::create_dir_all(&build_dir)?;
-                    cache.restore(&fp.fingerprint, &build_dir)?;
-
-                    let duration = start.elapsed().as_secs_f64();
-                    let products = find_products(&build_dir, manifest)?;
+                    // Restore from cache, falling through to a real build if
+                    // the cached archive is missing any expected products
+                    if let Some(products) = restore_from_cache(
+                        cache,
+                        &fp.fingerprint,
+                        &build_dir,
+                        manifest,
+                        options.target.as_deref(),
+                    )? {
+                        let duration = start.elapsed().as_secs_f64();
+                        self.record_last_fingerprint(fp);
 
-                    return Ok(BuildResult {
-                        products,
-                        duration_secs: duration,
-                        cached: true,
-                        fingerprint: Some(fp.fingerprint.clone()),
-                    });
+                        return Ok(BuildResult {
+                            products,
+                            duration_secs: duration,
+                            cached: true,
+                            fingerprint: Some(fp.fingerprint.clone()),
+                        });
+                    }
                 } else {
                     tracing::debug!("Cache miss for fingerprint {}", &fp.fingerprint[..16]);
                 }
             }
         }
 
+        check_system_libraries(manifest, &build_env(manifest, options))?;
+
         // Build command
         let mut cmd = Command::new(&self.toolchain.swift_path);
         cmd.arg("build");
         cmd.current_dir(&self.project_dir);
+        cmd.envs(build_env(manifest, options));
+        self.scratch_path_args(&mut cmd);
 
         // Configuration
         match options.configuration {
@@ -242,15 +397,27 @@ impl Builder {
             cmd.arg("--target").arg(target);
         }
 
+        if let Some(sdk) = &options.sdk {
+            cmd.args(["-Xswiftc", "-sdk", "-Xswiftc"]).arg(sdk);
+        }
+
         // Extra flags
-        for flag in &options.swift_flags {
-            cmd.arg(flag);
+        cmd.args(compiler_flags(options));
+        for feature in &options.features {
+            cmd.arg("-Xswiftc")
+                .arg(format!("-D{}", FeatureSet::compilation_condition(feature)));
         }
 
         // Stream output
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
 
+        // In its own process group, so an interrupt reaches the whole
+        // build through us and nothing outlives it
+        #[cfg(unix)]
+        cmd.process_group(0);
+        cmd.kill_on_drop(true);
+
         tracing::info!("Running: swift build");
 
         let mut child = cmd.spawn()?;
@@ -258,33 +425,15 @@ impl Builder {
         // Collect stderr for error reporting
         let mut error_output = Vec::new();
 
-        // Stream stderr (where swift build outputs progress)
-        if let Some(stderr) = child.stderr.take() {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
-
-            while let Some(line) = lines.next_line().await? {
-                // Always collect lines for error reporting
-                error_output.push(line.clone());
-
-                if options.verbose {
-                    println!("{}", line);
-                } else {
-                    // Parse and display progress or errors
-                    if line.contains("Compiling")
-                        || line.contains("Linking")
-                        || line.contains("error:")
-                        || line.contains("warning:")
-                        || line.contains("note:")
-                        || line.starts_with("/")
-                    {
-                        println!("{}", line);
-                    }
-                }
+        let status = tokio::select! {
+            status = stream_build_output(&mut child, options.verbose, &mut error_output) => status?,
+            _ = options.cancel.cancelled() => {
+                tracing::info!("Build interrupted, stopping swift build");
+                terminate(&mut child).await;
+                // Whatever was built so far is left out of the cache
+                return Err(BuildError::Interrupted);
             }
-        }
-
-        let status = child.wait().await?;
+        };
 
         if !status.success() {
             // Include the last 50 lines of output in the error
@@ -297,27 +446,46 @@ impl Builder {
                 .collect::<Vec<_>>()
                 .join("\n");
 
+            let summary = if options.warnings_as_errors {
+                format!(
+                    "swift build failed with {} error(s), counting warnings as errors",
+                    count_errors(&error_output)
+                )
+            } else {
+                "swift build failed".to_string()
+            };
             return Err(BuildError::BuildFailed(format!(
-                "swift build failed:\n{}",
-                error_context
+                "{}:\n{}",
+                summary, error_context
             )));
         }
 
         let duration = start.elapsed().as_secs_f64();
 
         // Find built products
-        let products = find_products(&build_dir, manifest)?;
+        let products = self
+            .discover_products(&build_dir, manifest, options)
+            .await?;
 
         // Store in cache for next time
         if options.use_cache {
             if let (Some(ref fp), Some(ref cache)) = (&fingerprint, &self.binary_cache) {
-                if let Err(e) = cache.store(&fp.fingerprint, &build_dir) {
+                let stored = cache.store(&fp.fingerprint, &build_dir).and_then(|()| {
+                    match cached_products(&build_dir, &products) {
+                        Some(cached) => cache.store_products(&fp.fingerprint, &cached),
+                        None => Ok(()),
+                    }
+                });
+                if let Err(e) = stored {
                     tracing::warn!("Failed to cache build artifacts: {}", e);
                 } else {
                     tracing::info!("Cached build artifacts as {}", &fp.fingerprint[..16]);
                 }
             }
         }
+        if let Some(fp) = &fingerprint {
+            self.record_last_fingerprint(fp);
+        }
 
         Ok(BuildResult {
             products,
@@ -327,11 +495,74 @@ impl Builder {
         })
     }
 
+    /// Products of the last build, as reported by SwiftPM.
+    ///
+    /// Asks `swift build --show-bin-path` where products go and
+    /// `swift package describe` which products exist. Falls back to guessing
+    /// from target names in `build_dir` if either fails.
+    async fn discover_products(
+        &self,
+        build_dir: &Path,
+        manifest: &Manifest,
+        options: &BuildOptions,
+    ) -> Result<Vec<BuildProduct>, BuildError> {
+        let env = build_env(manifest, options);
+        let (Some(bin_dir), Some(described)) = (
+            self.show_bin_path(options.configuration, &env).await,
+            self.describe_products(&env).await,
+        ) else {
+            tracing::debug!("Could not query SwiftPM for products, guessing from targets");
+            return find_products(build_dir, manifest);
+        };
+        Ok(locate_products(&bin_dir, &described, std::env::consts::OS))
+    }
+
+    /// Output of `swift build --show-bin-path`.
+    async fn show_bin_path(
+        &self,
+        config: BuildConfiguration,
+        env: &HashMap<String, String>,
+    ) -> Option<PathBuf> {
+        let mut cmd = Command::new(&self.toolchain.swift_path);
+        cmd.args(["build", "--show-bin-path"]);
+        cmd.current_dir(&self.project_dir);
+        cmd.envs(env);
+        self.scratch_path_args(&mut cmd);
+        if config == BuildConfiguration::Release {
+            cmd.arg("-c").arg("release");
+        }
+
+        let output = cmd.output().await.ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        // Anything SwiftPM prints before the path (e.g. fetching) goes first
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let path = PathBuf::from(stdout.lines().last()?.trim());
+        path.is_dir().then_some(path)
+    }
+
+    /// Products declared by the package, per `swift package describe`.
+    async fn describe_products(&self, env: &HashMap<String, String>) -> Option<Vec<ProductInfo>> {
+        let mut cmd = Command::new(&self.toolchain.swift_path);
+        cmd.args(["package", "describe", "--type", "json"]);
+        cmd.current_dir(&self.project_dir);
+        cmd.envs(env);
+        self.scratch_path_args(&mut cmd);
+
+        let output = cmd.output().await.ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_describe(&String::from_utf8_lossy(&output.stdout))
+    }
+
     /// Clean build artifacts.
     pub async fn clean(&self) -> Result<(), BuildError> {
         let mut cmd = Command::new(&self.toolchain.swift_path);
         cmd.arg("package").arg("clean");
         cmd.current_dir(&self.project_dir);
+        self.scratch_path_args(&mut cmd);
 
         let status = cmd.status().await?;
 
@@ -346,7 +577,7 @@ impl Builder {
 
     /// Get the build directory for a configuration.
     pub fn build_dir(&self, config: BuildConfiguration) -> PathBuf {
-        self.project_dir.join(".build").join(config.to_string())
+        self.target_dir.join(config.to_string())
     }
 
     /// Get binary cache statistics.
@@ -370,13 +601,128 @@ pub fn get_cache_stats() -> Result<gust_binary_cache::CacheStats, BuildError> {
     Ok(cache.stats()?)
 }
 
+/// Get a binary cache report (standalone function for CLI use).
+pub fn get_cache_report() -> Result<gust_binary_cache::CacheReport, BuildError> {
+    let cache = LocalBinaryCache::open()?;
+    Ok(cache.report()?)
+}
+
 /// Clear the binary cache (standalone function for CLI use).
 pub fn clear_binary_cache() -> Result<usize, BuildError> {
     let cache = LocalBinaryCache::open()?;
     Ok(cache.clear()?)
 }
 
-fn find_products(build_dir: &Path, manifest: &Manifest) -> Result<Vec<PathBuf>, BuildError> {
+/// Restore cached artifacts and verify every expected product is present.
+///
+/// The products are the ones recorded when the entry was stored; entries
+/// without a record fall back to guessing from target names. Returns `None`
+/// when the archive is incomplete (e.g. a target was added after it was
+/// cached) or corrupt. The entry is evicted so the rebuild can replace it.
+fn restore_from_cache(
+    cache: &LocalBinaryCache,
+    fingerprint: &str,
+    build_dir: &Path,
+    manifest: &Manifest,
+    target: Option<&str>,
+) -> Result<Option<Vec<BuildProduct>>, BuildError> {
+    std::fs::create_dir_all(build_dir)?;
+    match cache.restore(fingerprint, build_dir) {
+        Ok(()) => {}
+        Err(e @ gust_binary_cache::BinaryCacheError::CorruptEntry { .. }) => {
+            tracing::warn!("{}, rebuilding", e);
+            cache.remove(fingerprint)?;
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    if let Some(recorded) = cache.products(fingerprint) {
+        let products: Vec<BuildProduct> = recorded
+            .into_iter()
+            .map(|p| BuildProduct {
+                name: p.name,
+                target_type: p.target_type,
+                path: build_dir.join(p.path),
+            })
+            .collect();
+        let missing: Vec<&str> = products
+            .iter()
+            .filter(|p| !p.path.exists())
+            .map(|p| p.name.as_str())
+            .collect();
+        if missing.is_empty() {
+            return Ok(Some(products));
+        }
+        tracing::info!(
+            "Cached artifacts {} are missing products ({}), rebuilding",
+            &fingerprint[..16],
+            missing.join(", ")
+        );
+        cache.remove(fingerprint)?;
+        return Ok(None);
+    }
+
+    let missing = missing_products(build_dir, manifest, target);
+    if !missing.is_empty() {
+        tracing::info!(
+            "Cached artifacts {} are missing products ({}), rebuilding",
+            &fingerprint[..16],
+            missing.join(", ")
+        );
+        cache.remove(fingerprint)?;
+        return Ok(None);
+    }
+
+    Ok(Some(find_products(build_dir, manifest)?))
+}
+
+/// `products` relative to `build_dir`, for recording with a cache entry.
+/// `None` if any lies outside it, as it wouldn't be in the archive.
+fn cached_products(build_dir: &Path, products: &[BuildProduct]) -> Option<Vec<CachedProduct>> {
+    // SwiftPM's bin path may reach the build directory through a symlink
+    let root = build_dir.canonicalize().ok()?;
+    products
+        .iter()
+        .map(|p| {
+            let path = p.path.canonicalize().ok()?;
+            Some(CachedProduct {
+                name: p.name.clone(),
+                target_type: p.target_type,
+                path: path.strip_prefix(&root).ok()?.to_path_buf(),
+            })
+        })
+        .collect()
+}
+
+/// Names of targets whose build products are absent from `build_dir`.
+///
+/// Libraries count as present if either a linked library or the compiled
+/// module exists, since SwiftPM only links libraries for dynamic products.
+fn missing_products(build_dir: &Path, manifest: &Manifest, target: Option<&str>) -> Vec<String> {
+    manifest
+        .targets
+        .iter()
+        .filter(|t| target.is_none_or(|name| t.name == name))
+        .filter(|t| match t.target_type {
+            gust_types::TargetType::Executable => !build_dir.join(&t.name).exists(),
+            gust_types::TargetType::Library => ![
+                static_library_name(&t.name, std::env::consts::OS),
+                dynamic_library_name(&t.name, std::env::consts::OS),
+                format!("{}.swiftmodule", t.name),
+                format!("Modules/{}.swiftmodule", t.name),
+            ]
+            .iter()
+            .any(|p| build_dir.join(p).exists()),
+            _ => false,
+        })
+        .map(|t| t.name.clone())
+        .collect()
+}
+
+/// Guess products from target names, for when SwiftPM can't be asked.
+fn find_products(build_dir: &Path, manifest: &Manifest) -> Result<Vec<BuildProduct>, BuildError> {
+    let os = std::env::consts::OS;
     let mut products = Vec::new();
 
     for target in &manifest.targets {
@@ -384,8 +730,8 @@ fn find_products(build_dir: &Path, manifest: &Manifest) -> Result<Vec<PathBuf>,
             gust_types::TargetType::Executable => build_dir.join(&target.name),
             gust_types::TargetType::Library => {
                 // Try both static and dynamic lib names
-                let static_lib = build_dir.join(format!("lib{}.a", target.name));
-                let dylib = build_dir.join(format!("lib{}.dylib", target.name));
+                let static_lib = build_dir.join(static_library_name(&target.name, os));
+                let dylib = build_dir.join(dynamic_library_name(&target.name, os));
                 if static_lib.exists() {
                     static_lib
                 } else {
@@ -396,21 +742,791 @@ fn find_products(build_dir: &Path, manifest: &Manifest) -> Result<Vec<PathBuf>,
         };
 
         if path.exists() {
-            products.push(path);
+            products.push(BuildProduct {
+                name: target.name.clone(),
+                target_type: target.target_type,
+                path,
+            });
         }
     }
 
     Ok(products)
 }
 
+/// Environment for `swift build`: the manifest's `[build.env]`, with
+/// `options.env` taking precedence, and the enabled features for the
+/// generated Package.swift.
+fn build_env(manifest: &Manifest, options: &BuildOptions) -> HashMap<String, String> {
+    let mut env = manifest
+        .build
+        .as_ref()
+        .map(|b| b.env.clone())
+        .unwrap_or_default();
+    env.insert(FEATURES_ENV.to_string(), options.features.join(","));
+    env.extend(options.env.clone());
+    env
+}
+
+/// Fail early, with install instructions, if a system library target's
+/// pkg-config package isn't installed. Skipped when pkg-config itself isn't
+/// available, leaving SwiftPM to report what it can.
+fn check_system_libraries(
+    manifest: &Manifest,
+    env: &HashMap<String, String>,
+) -> Result<(), BuildError> {
+    for target in &manifest.targets {
+        if target.target_type != TargetType::SystemLibrary {
+            continue;
+        }
+        let Some(library) = &target.pkg_config else {
+            continue;
+        };
+        let exists = std::process::Command::new("pkg-config")
+            .args(["--exists", library])
+            .envs(env)
+            .status();
+        match exists {
+            Ok(status) if !status.success() => {
+                return Err(BuildError::SystemLibraryMissing {
+                    target: target.name.clone(),
+                    library: library.clone(),
+                    install: install_commands(&target.providers, std::env::consts::OS),
+                });
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::debug!(
+                    "Not checking for {}: pkg-config unavailable: {}",
+                    library,
+                    e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Install commands from `providers` for `os`, or from all of them if none
+/// is for `os`.
+fn install_commands(providers: &[gust_types::SystemPackageProvider], os: &str) -> Vec<String> {
+    let for_os: Vec<String> = providers
+        .iter()
+        .filter(|p| p.applies_to(os))
+        .map(|p| p.install_command())
+        .collect();
+    if for_os.is_empty() {
+        providers.iter().map(|p| p.install_command()).collect()
+    } else {
+        for_os
+    }
+}
+
+/// Stream `swift build`'s stderr, where it reports progress, into `output`
+/// and wait for it to exit.
+async fn stream_build_output(
+    child: &mut Child,
+    verbose: bool,
+    output: &mut Vec<String>,
+) -> Result<std::process::ExitStatus, BuildError> {
+    if let Some(stderr) = child.stderr.take() {
+        let reader = BufReader::new(stderr);
+        let mut lines = reader.lines();
+
+        while let Some(line) = lines.next_line().await? {
+            // Always collect lines for error reporting
+            output.push(line.clone());
+
+            if verbose {
+                println!("{}", line);
+            } else {
+                // Parse and display progress or errors
+                if line.contains("Compiling")
+                    || line.contains("Linking")
+                    || line.contains("error:")
+                    || line.contains("warning:")
+                    || line.contains("note:")
+                    || line.starts_with("/")
+                {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+
+    Ok(child.wait().await?)
+}
+
+/// Flags passed through to `swift build`: the extra Swift flags, plus
+/// `-warnings-as-errors` when asked for. They're part of the fingerprint, so
+/// a build made without the flag isn't reused for one made with it.
+fn compiler_flags(options: &BuildOptions) -> Vec<String> {
+    let mut flags = options.swift_flags.clone();
+    if options.warnings_as_errors {
+        flags.extend(["-Xswiftc".to_string(), "-warnings-as-errors".to_string()]);
+    }
+    flags
+}
+
+/// Hash of the packages `Gust.lock` at `path` resolves to: each one's name,
+/// version and revision, or its checksum when it has no revision (such as a
+/// copied path package), sorted by name. Formatting and fields that don't
+/// change what gets built leave it alone. A lockfile that doesn't parse is
+/// hashed as it is.
+fn lockfile_deps_hash(path: &Path) -> std::io::Result<String> {
+    let Ok(lockfile) = gust_lockfile::Lockfile::load(path) else {
+        let content = std::fs::read(path)?;
+        return Ok(blake3::hash(&content).to_hex().to_string());
+    };
+    let mut packages: Vec<String> = lockfile
+        .packages
+        .iter()
+        .map(|p| {
+            let content = p.revision.as_ref().or(p.checksum.as_ref());
+            format!(
+                "{} {} {}",
+                p.name,
+                p.version,
+                content.map_or("", String::as_str)
+            )
+        })
+        .collect();
+    packages.sort();
+    Ok(blake3::hash(packages.join("\n").as_bytes())
+        .to_hex()
+        .to_string())
+}
+
+/// Compiler flags set for individual dependencies in
+/// `[dependencies.<name>.build]`, by dependency: Swift flags, then C flags
+/// as `-Xcc <flag>`.
+fn dependency_flags(manifest: &Manifest) -> BTreeMap<String, Vec<String>> {
+    manifest
+        .dependencies
+        .iter()
+        .filter_map(|(name, dep)| {
+            let build = dep.build.as_ref()?;
+            let mut flags = build.swift_flags.clone();
+            for flag in &build.c_flags {
+                flags.extend(["-Xcc".to_string(), flag.clone()]);
+            }
+            Some((name.clone(), flags))
+        })
+        .collect()
+}
+
+/// Distinct compiler errors (`file:line:col: error: ...`) in build output.
+/// SwiftPM can print the same diagnostic more than once.
+fn count_errors(output: &[String]) -> usize {
+    output
+        .iter()
+        .filter(|line| line.contains(": error: "))
+        .collect::<std::collections::HashSet<_>>()
+        .len()
+}
+
+/// A token cancelled by the first Ctrl-C, for [`BuildOptions::cancel`].
+///
+/// Listening for Ctrl-C replaces the default of exiting for the rest of the
+/// process, so the handler is installed once, on first use, and a second
+/// Ctrl-C exits with the usual status. Must be called within a Tokio
+/// runtime.
+pub fn ctrl_c_token() -> CancellationToken {
+    static TOKEN: std::sync::OnceLock<CancellationToken> = std::sync::OnceLock::new();
+    TOKEN
+        .get_or_init(|| {
+            let token = CancellationToken::new();
+            let cancel = token.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    cancel.cancel();
+                    if tokio::signal::ctrl_c().await.is_ok() {
+                        std::process::exit(130);
+                    }
+                }
+            });
+            token
+        })
+        .clone()
+}
+
+/// How long `swift build` gets to exit after being asked to before it's
+/// killed.
+const TERMINATE_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Stop `child` and everything it started, then reap it.
+async fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // The child leads its own process group, so this reaches the
+        // compiler processes it spawned too
+        // SAFETY: killpg only sends a signal; it doesn't touch our memory
+        unsafe { libc::killpg(pid as libc::pid_t, libc::SIGTERM) };
+        if tokio::time::timeout(TERMINATE_GRACE, child.wait())
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        // SAFETY: as above; the group is still ours, since the leader
+        // hasn't been reaped
+        unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) };
+    }
+    let _ = child.kill().await;
+}
+
+/// Check that `name` is a target `swift build --target` can build.
+fn validate_target(manifest: &Manifest, name: &str) -> Result<(), BuildError> {
+    let Some(target) = manifest.targets.iter().find(|t| t.name == name) else {
+        let suggestion =
+            gust_diagnostics::closest_match(name, manifest.targets.iter().map(|t| t.name.as_str()))
+                .map(String::from);
+        return Err(BuildError::TargetNotFound {
+            name: name.to_string(),
+            suggestion,
+        });
+    };
+
+    let kind = match target.target_type {
+        TargetType::Test => "test",
+        TargetType::Plugin => "plugin",
+        _ => return Ok(()),
+    };
+    Err(BuildError::TargetNotBuildable {
+        name: name.to_string(),
+        kind,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A builder for `project` whose `swift` is the shell `script`, written
+    /// next to it, and with no binary cache.
+    #[cfg(unix)]
+    fn fake_swift_builder(project: &Path, script: &str) -> Builder {
+        use std::os::unix::fs::PermissionsExt;
+
+        let swift = project.with_file_name("swift");
+        std::fs::write(&swift, script).unwrap();
+        std::fs::set_permissions(&swift, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Builder {
+            target_dir: target_dir(project, None),
+            project_dir: project.to_path_buf(),
+            toolchain: SwiftToolchain {
+                swift_path: swift,
+                version: "5.9".to_string(),
+                major_version: 5,
+                minor_version: 9,
+            },
+            binary_cache: None,
+        }
+    }
+
     #[test]
     fn test_build_options_default() {
         let opts = BuildOptions::default();
         assert_eq!(opts.configuration, BuildConfiguration::Debug);
         assert!(opts.target.is_none());
     }
+
+    #[test]
+    fn test_validate_target_suggests_near_miss() {
+        let mut tests = gust_types::Target::executable("MyAppTests");
+        tests.target_type = TargetType::Test;
+        let manifest = Manifest {
+            targets: vec![
+                gust_types::Target::executable("MyApp"),
+                gust_types::Target::library("Networking"),
+                tests,
+            ],
+            ..Default::default()
+        };
+
+        assert!(validate_target(&manifest, "MyApp").is_ok());
+
+        match validate_target(&manifest, "Netwroking") {
+            Err(BuildError::TargetNotFound { suggestion, .. }) => {
+                assert_eq!(suggestion.as_deref(), Some("Networking"));
+            }
+            other => panic!("expected TargetNotFound, got {:?}", other),
+        }
+
+        assert!(matches!(
+            validate_target(&manifest, "Database"),
+            Err(BuildError::TargetNotFound {
+                suggestion: None,
+                ..
+            })
+        ));
+        assert!(matches!(
+            validate_target(&manifest, "MyAppTests"),
+            Err(BuildError::TargetNotBuildable { kind: "test", .. })
+        ));
+    }
+
+    #[test]
+    fn test_incomplete_cache_entry_triggers_rebuild() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = LocalBinaryCache::new(tmp.path().join("cache"));
+        let fingerprint = blake3::hash(b"fingerprint").to_hex().to_string();
+
+        // Cache a build that only produced the first executable
+        let cached_build = tmp.path().join("cached");
+        std::fs::create_dir_all(&cached_build).unwrap();
+        std::fs::write(cached_build.join("App"), b"binary").unwrap();
+        cache.store(&fingerprint, &cached_build).unwrap();
+
+        // A second executable target was added since the build was cached
+        let manifest = Manifest {
+            targets: vec![
+                gust_types::Target::executable("App"),
+                gust_types::Target::executable("Tool"),
+            ],
+            ..Default::default()
+        };
+
+        let build_dir = tmp.path().join("build");
+        let restored =
+            restore_from_cache(&cache, &fingerprint, &build_dir, &manifest, None).unwrap();
+        assert!(restored.is_none());
+        assert!(!cache.contains(&fingerprint));
+
+        // Building only the cached target is still a complete hit
+        cache.store(&fingerprint, &cached_build).unwrap();
+        let restored =
+            restore_from_cache(&cache, &fingerprint, &build_dir, &manifest, Some("App")).unwrap();
+        assert_eq!(
+            restored.unwrap(),
+            vec![BuildProduct {
+                name: "App".to_string(),
+                target_type: TargetType::Executable,
+                path: build_dir.join("App"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_products_are_typed_from_targets() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let build_dir = tmp.path();
+        std::fs::write(build_dir.join("App"), b"binary").unwrap();
+        let library = static_library_name("Core", std::env::consts::OS);
+        std::fs::write(build_dir.join(&library), b"archive").unwrap();
+
+        let manifest = Manifest {
+            targets: vec![
+                gust_types::Target::executable("App"),
+                gust_types::Target::library("Core"),
+            ],
+            ..Default::default()
+        };
+        let result = BuildResult {
+            products: find_products(build_dir, &manifest).unwrap(),
+            duration_secs: 0.0,
+            cached: false,
+            fingerprint: None,
+        };
+
+        let typed: Vec<(&str, TargetType)> = result
+            .products
+            .iter()
+            .map(|p| (p.name.as_str(), p.target_type))
+            .collect();
+        assert_eq!(
+            typed,
+            vec![
+                ("App", TargetType::Executable),
+                ("Core", TargetType::Library)
+            ]
+        );
+        assert_eq!(
+            result.product_paths(),
+            vec![build_dir.join("App"), build_dir.join(library)]
+        );
+        assert_eq!(result.executable(None).unwrap().name, "App");
+        assert!(result.executable(Some("Core")).is_none());
+    }
+
+    #[test]
+    fn test_deps_hash_ignores_lockfile_formatting() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = tmp.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let written = write(
+            "written.lock",
+            r#"# This file is auto-generated by Gust
+version = 1
+generated-by = "gust 0.1.0"
+
+[[package]]
+name = "swift-log"
+version = "1.5.4"
+source = "git"
+git = "https://github.com/apple/swift-log.git"
+revision = "abc123"
+
+[[package]]
+name = "swift-nio"
+version = "2.60.0"
+source = "git"
+git = "https://github.com/apple/swift-nio.git"
+revision = "def456"
+"#,
+        );
+        // Another machine: packages and fields in another order, a
+        // comment and a different generator
+        let reformatted = write(
+            "reformatted.lock",
+            r#"generated-by = "gust 0.2.0"
+version = 1
+
+[[package]]
+revision = "def456"
+git = "https://github.com/apple/swift-nio.git"
+source = "git"
+version = "2.60.0"
+name = "swift-nio"
+
+# Logging
+[[package]]
+name    = "swift-log"
+version = "1.5.4"
+source  = "git"
+git     = "https://github.com/apple/swift-log.git"
+revision = "abc123"
+"#,
+        );
+        let moved = write(
+            "moved.lock",
+            &std::fs::read_to_string(&written)
+                .unwrap()
+                .replace("abc123", "abc124"),
+        );
+
+        let hash = lockfile_deps_hash(&written).unwrap();
+        assert_eq!(hash, lockfile_deps_hash(&reformatted).unwrap());
+        assert_ne!(hash, lockfile_deps_hash(&moved).unwrap());
+    }
+
+    #[test]
+    fn test_deps_hash_covers_checksums_of_packages_without_revisions() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, checksum: &str| {
+            let path = tmp.path().join(name);
+            std::fs::write(
+                &path,
+                format!(
+                    r#"version = 1
+generated-by = "gust 0.1.0"
+
+[[package]]
+name = "local-utils"
+version = "0.1.0"
+source = "path"
+checksum = "{}"
+"#,
+                    checksum
+                ),
+            )
+            .unwrap();
+            path
+        };
+
+        assert_ne!(
+            lockfile_deps_hash(&write("before.lock", "aaaa")).unwrap(),
+            lockfile_deps_hash(&write("after.lock", "bbbb")).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_custom_target_dir_receives_products() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+
+        // Stand-in for `swift build` that writes the executable into the
+        // scratch path it was given
+        let builder = fake_swift_builder(
+            &project,
+            "#!/bin/sh\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = --scratch-path ]; then scratch=$2; fi\n  shift\ndone\nmkdir -p \"$scratch/debug\" && touch \"$scratch/debug/MyApp\"\n",
+        )
+        .with_target_dir("../out");
+
+        let manifest = Manifest {
+            targets: vec![gust_types::Target::executable("MyApp")],
+            ..Default::default()
+        };
+        let options = BuildOptions {
+            use_cache: false,
+            ..Default::default()
+        };
+
+        let result = builder.build(&manifest, &options).await.unwrap();
+        assert_eq!(
+            result.product_paths(),
+            vec![project.join("../out").join("debug").join("MyApp")]
+        );
+        assert!(tmp.path().join("out/debug/MyApp").is_file());
+        assert!(!project.join(".build").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cache_hit_reports_the_products_swiftpm_found() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+
+        // Stand-in for SwiftPM whose one executable is renamed and built
+        // into a subdirectory, so no target name points at it
+        let builder = Builder {
+            binary_cache: Some(LocalBinaryCache::new(tmp.path().join("cache"))),
+            ..fake_swift_builder(
+                &project,
+                r#"#!/bin/sh
+case "$1 $2" in
+  "build --show-bin-path") echo "$PWD/.build/debug" ;;
+  "package describe") echo '{"name": "App", "products": [{"name": "app-cli", "type": {"executable": null}}]}' ;;
+  build*) mkdir -p .build/debug/tools && echo binary > .build/debug/tools/app-cli && touch built.txt ;;
+esac
+"#,
+            )
+        };
+        let manifest = Manifest {
+            targets: vec![gust_types::Target::executable("AppMain")],
+            ..Default::default()
+        };
+        let options = BuildOptions::default();
+
+        let built = builder.build(&manifest, &options).await.unwrap();
+        assert!(!built.cached);
+        let expected = vec![BuildProduct {
+            name: "app-cli".to_string(),
+            target_type: TargetType::Executable,
+            path: project.join(".build/debug/tools/app-cli"),
+        }];
+        assert_eq!(built.products, expected);
+
+        // Restored from the cache without running swift build, with the
+        // same products at the same paths
+        std::fs::remove_dir_all(project.join(".build")).unwrap();
+        std::fs::remove_file(project.join("built.txt")).unwrap();
+        let restored = builder.build(&manifest, &options).await.unwrap();
+        assert!(restored.cached);
+        assert_eq!(restored.products, expected);
+        assert!(expected[0].path.is_file());
+        assert!(!project.join("built.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_manifest_env_reaches_build() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[build.env]\nGUST_TEST_SDK = \"/opt/sdk\"\n",
+        )
+        .unwrap();
+        let manifest = gust_manifest::parse_gust_toml(&project.join("Gust.toml")).unwrap();
+
+        // Stand-in for `swift build` that records the variable it was given
+        let builder = fake_swift_builder(
+            &project,
+            "#!/bin/sh\nprintf %s \"$GUST_TEST_SDK\" > env.txt\n",
+        );
+        let options = BuildOptions {
+            use_cache: false,
+            ..Default::default()
+        };
+
+        builder.build(&manifest, &options).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.join("env.txt")).unwrap(),
+            "/opt/sdk"
+        );
+
+        let with_env = builder.compute_fingerprint(&manifest, &options).unwrap();
+        let mut without = manifest.clone();
+        without.build = None;
+        let plain = builder.compute_fingerprint(&without, &options).unwrap();
+        assert_ne!(with_env.fingerprint, plain.fingerprint);
+        assert_eq!(with_env.diff(&plain)[0].component, "env");
+
+        // Options override the manifest
+        let overridden = BuildOptions {
+            env: HashMap::from([("GUST_TEST_SDK".to_string(), "/other".to_string())]),
+            ..options
+        };
+        let other = builder.compute_fingerprint(&manifest, &overridden).unwrap();
+        assert_ne!(with_env.fingerprint, other.fingerprint);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancelled_build_stops_swift_and_is_not_cached() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(project.join(".build/debug")).unwrap();
+        std::fs::write(project.join(".build/debug/partial.o"), b"").unwrap();
+
+        // Stand-in for a `swift build` that never finishes on its own
+        let cache_dir = tmp.path().join("binary-cache");
+        let builder = Builder {
+            binary_cache: Some(LocalBinaryCache::new(cache_dir.clone())),
+            ..fake_swift_builder(&project, "#!/bin/sh\nsleep 30\necho done > finished.txt\n")
+        };
+        let options = BuildOptions::default();
+        let cancel = options.cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            cancel.cancel();
+        });
+
+        let start = std::time::Instant::now();
+        let result = builder.build(&Manifest::default(), &options).await;
+        assert!(
+            matches!(result, Err(BuildError::Interrupted)),
+            "{:?}",
+            result
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        assert!(!project.join("finished.txt").exists());
+
+        let stats = LocalBinaryCache::new(cache_dir).stats().unwrap();
+        assert_eq!(stats.count, 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_build_leaves_last_fingerprint_alone() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+
+        // Stand-in for `swift build` that fails while `fail` exists
+        let builder = fake_swift_builder(
+            &project,
+            "#!/bin/sh\nif [ -e fail ]; then echo 'error: broken' >&2; exit 1; fi\n",
+        );
+        let options = BuildOptions::default();
+
+        std::fs::write(project.join("fail"), "").unwrap();
+        assert!(builder.build(&Manifest::default(), &options).await.is_err());
+        assert!(builder.last_fingerprint().is_none());
+
+        std::fs::remove_file(project.join("fail")).unwrap();
+        let result = builder.build(&Manifest::default(), &options).await.unwrap();
+        assert_eq!(
+            builder.last_fingerprint().map(|fp| fp.fingerprint),
+            result.fingerprint
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sdk_reaches_build_and_fingerprint() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        let sdk = tmp.path().join("iPhoneSimulator.sdk");
+        std::fs::create_dir_all(&sdk).unwrap();
+
+        // Stand-in for `swift` that records how it was called
+        let builder = fake_swift_builder(
+            &project,
+            "#!/bin/sh
+echo \"$@\" >> args.txt
+",
+        );
+        let manifest = Manifest::default();
+        let options = BuildOptions {
+            use_cache: false,
+            sdk: Some(sdk.clone()),
+            ..Default::default()
+        };
+
+        builder.build(&manifest, &options).await.unwrap();
+        let args = std::fs::read_to_string(project.join("args.txt")).unwrap();
+        let build = args.lines().find(|l| l.starts_with("build ")).unwrap();
+        assert!(
+            build.contains(&format!("-Xswiftc -sdk -Xswiftc {}", sdk.display())),
+            "{}",
+            build
+        );
+
+        let with_sdk = builder.compute_fingerprint(&manifest, &options).unwrap();
+        let plain = builder
+            .compute_fingerprint(&manifest, &BuildOptions::default())
+            .unwrap();
+        assert_ne!(with_sdk.fingerprint, plain.fingerprint);
+        assert_eq!(with_sdk.diff(&plain)[0].component, "sdk");
+
+        // A missing SDK fails before anything runs
+        let missing = BuildOptions {
+            sdk: Some(tmp.path().join("Missing.sdk")),
+            ..options
+        };
+        assert!(matches!(
+            builder.build(&manifest, &missing).await,
+            Err(BuildError::SdkNotFound(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_warnings_as_errors_reach_build_and_fingerprint() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+
+        // Stand-in for `swift` that records how it was called, and fails on
+        // a warning when there's a WARN file
+        let builder = fake_swift_builder(
+            &project,
+            "#!/bin/sh
+echo \"$@\" >> args.txt
+if [ -e WARN ] && [ \"$1\" = build ]; then
+  echo \"/src/App.swift:3:9: error: initialization of 'x' was never used\" >&2
+  echo \"/src/App.swift:3:9: error: initialization of 'x' was never used\" >&2
+  exit 1
+fi
+",
+        );
+        let manifest = Manifest::default();
+        let options = BuildOptions {
+            use_cache: false,
+            warnings_as_errors: true,
+            ..Default::default()
+        };
+
+        builder.build(&manifest, &options).await.unwrap();
+        let args = std::fs::read_to_string(project.join("args.txt")).unwrap();
+        let build = args.lines().find(|l| l.starts_with("build ")).unwrap();
+        assert!(build.contains("-Xswiftc -warnings-as-errors"), "{}", build);
+
+        // A build with the flag gets its own cache entry
+        let strict = builder.compute_fingerprint(&manifest, &options).unwrap();
+        let plain = builder
+            .compute_fingerprint(&manifest, &BuildOptions::default())
+            .unwrap();
+        assert_ne!(strict.fingerprint, plain.fingerprint);
+        assert_eq!(strict.diff(&plain)[0].component, "swift_flags");
+
+        // Failures say the warnings were counted, each diagnostic once
+        std::fs::write(project.join("WARN"), "").unwrap();
+        let err = builder.build(&manifest, &options).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("failed with 1 error(s), counting warnings as errors"),
+            "{}",
+            err
+        );
+    }
 }
@@ -0,0 +1,274 @@
+//! Locating build products.
+//!
+//! SwiftPM names products independently of targets and puts them wherever
+//! `swift build --show-bin-path` says, so rather than guessing from target
+//! names the builder asks `swift package describe --type json` which
+//! products exist and looks for each one's platform-specific file name.
+
+use gust_types::TargetType;
+use std::path::{Path, PathBuf};
+
+/// How deep below the bin directory to look for a product.
+const SEARCH_DEPTH: usize = 3;
+
+/// The kind of a SwiftPM product.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductKind {
+    Executable,
+    StaticLibrary,
+    DynamicLibrary,
+    /// A library SwiftPM links statically or dynamically as it sees fit,
+    /// and usually not at all when building the package itself
+    AutomaticLibrary,
+    /// The `<Package>PackageTests.xctest` bundle
+    Test,
+}
+
+impl ProductKind {
+    /// The kind of target that builds this product.
+    pub fn target_type(self) -> TargetType {
+        match self {
+            ProductKind::Executable => TargetType::Executable,
+            ProductKind::StaticLibrary
+            | ProductKind::DynamicLibrary
+            | ProductKind::AutomaticLibrary => TargetType::Library,
+            ProductKind::Test => TargetType::Test,
+        }
+    }
+}
+
+/// A built product and where it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildProduct {
+    /// Product name; the target's name unless the manifest renames it
+    pub name: String,
+    /// Executable, library or test bundle
+    pub target_type: TargetType,
+    /// The built file
+    pub path: PathBuf,
+}
+
+/// A product declared by a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProductInfo {
+    pub name: String,
+    pub kind: ProductKind,
+}
+
+impl ProductInfo {
+    /// File names this product may have on `os` (as in
+    /// `std::env::consts::OS`), most likely first.
+    pub fn file_names(&self, os: &str) -> Vec<String> {
+        match self.kind {
+            ProductKind::Executable if os == "windows" => vec![format!("{}.exe", self.name)],
+            ProductKind::Executable => vec![self.name.clone()],
+            ProductKind::StaticLibrary => vec![static_library_name(&self.name, os)],
+            ProductKind::DynamicLibrary => vec![dynamic_library_name(&self.name, os)],
+            ProductKind::AutomaticLibrary => vec![
+                static_library_name(&self.name, os),
+                dynamic_library_name(&self.name, os),
+            ],
+            ProductKind::Test => vec![format!("{}.xctest", self.name)],
+        }
+    }
+}
+
+/// Static library file name for `name` on `os`.
+pub fn static_library_name(name: &str, os: &str) -> String {
+    if os == "windows" {
+        format!("{}.lib", name)
+    } else {
+        format!("lib{}.a", name)
+    }
+}
+
+/// Dynamic library file name for `name` on `os`.
+pub fn dynamic_library_name(name: &str, os: &str) -> String {
+    match os {
+        "macos" | "ios" => format!("lib{}.dylib", name),
+        "windows" => format!("{}.dll", name),
+        _ => format!("lib{}.so", name),
+    }
+}
+
+/// Products listed by `swift package describe --type json`, plus the test
+/// bundle if the package has test targets. `None` if the output can't be
+/// parsed.
+pub fn parse_describe(json: &str) -> Option<Vec<ProductInfo>> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let package = value["name"].as_str()?;
+
+    let mut products = Vec::new();
+    for product in value["products"].as_array()? {
+        let Some(name) = product["name"].as_str() else {
+            continue;
+        };
+        let ty = &product["type"];
+        let kind = if ty.get("executable").is_some() {
+            ProductKind::Executable
+        } else if let Some(library) = ty.get("library") {
+            match library.as_array().and_then(|l| l.first()?.as_str()) {
+                Some("static") => ProductKind::StaticLibrary,
+                Some("dynamic") => ProductKind::DynamicLibrary,
+                _ => ProductKind::AutomaticLibrary,
+            }
+        } else {
+            // Plugins and macros aren't products of the build itself
+            continue;
+        };
+        products.push(ProductInfo {
+            name: name.to_string(),
+            kind,
+        });
+    }
+
+    let has_tests = value["targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|t| t["type"] == "test");
+    if has_tests {
+        products.push(ProductInfo {
+            name: format!("{}PackageTests", package),
+            kind: ProductKind::Test,
+        });
+    }
+
+    Some(products)
+}
+
+/// The `products` that exist under `bin_dir`, searching a few levels of
+/// subdirectories for products SwiftPM didn't put at the top.
+pub fn locate_products(bin_dir: &Path, products: &[ProductInfo], os: &str) -> Vec<BuildProduct> {
+    products
+        .iter()
+        .filter_map(|product| {
+            let path = product
+                .file_names(os)
+                .iter()
+                .find_map(|file| find_file(bin_dir, file, SEARCH_DEPTH))?;
+            Some(BuildProduct {
+                name: product.name.clone(),
+                target_type: product.kind.target_type(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// `dir/name`, or the first match in a subdirectory up to `depth` levels
+/// down. Intermediate `<target>.build` directories hold object files and are
+/// skipped, as are bundles.
+fn find_file(dir: &Path, name: &str, depth: usize) -> Option<PathBuf> {
+    let direct = dir.join(name);
+    if direct.exists() {
+        return Some(direct);
+    }
+    if depth == 0 {
+        return None;
+    }
+
+    let mut subdirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.path())
+        .filter(|p| {
+            !p.extension()
+                .is_some_and(|ext| ext == "build" || ext == "xctest" || ext == "dSYM")
+        })
+        .collect();
+    subdirs.sort();
+    subdirs
+        .iter()
+        .find_map(|sub| find_file(sub, name, depth - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIBE: &str = r#"{
+        "name": "Tools",
+        "products": [
+            {"name": "mytool", "type": {"executable": null}, "targets": ["ToolMain"]},
+            {"name": "Net", "type": {"library": ["dynamic"]}, "targets": ["Networking"]},
+            {"name": "Core", "type": {"library": ["automatic"]}, "targets": ["Core"]},
+            {"name": "Lint", "type": {"plugin": null}, "targets": ["LintPlugin"]}
+        ],
+        "targets": [
+            {"name": "ToolMain", "type": "executable"},
+            {"name": "ToolTests", "type": "test"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_describe() {
+        let products = parse_describe(DESCRIBE).unwrap();
+        assert_eq!(
+            products,
+            vec![
+                ProductInfo {
+                    name: "mytool".to_string(),
+                    kind: ProductKind::Executable,
+                },
+                ProductInfo {
+                    name: "Net".to_string(),
+                    kind: ProductKind::DynamicLibrary,
+                },
+                ProductInfo {
+                    name: "Core".to_string(),
+                    kind: ProductKind::AutomaticLibrary,
+                },
+                ProductInfo {
+                    name: "ToolsPackageTests".to_string(),
+                    kind: ProductKind::Test,
+                },
+            ]
+        );
+        assert!(parse_describe("error: no Package.swift").is_none());
+    }
+
+    #[test]
+    fn test_locates_linux_shared_library_and_renamed_product() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bin = tmp.path();
+        // The executable is named after the product, not its target, and the
+        // shared library landed in a subdirectory
+        std::fs::write(bin.join("mytool"), b"").unwrap();
+        std::fs::create_dir_all(bin.join("lib")).unwrap();
+        std::fs::write(bin.join("lib/libNet.so"), b"").unwrap();
+        std::fs::create_dir_all(bin.join("ToolMain.build")).unwrap();
+        std::fs::write(bin.join("ToolMain.build/libCore.a"), b"").unwrap();
+        std::fs::write(bin.join("ToolsPackageTests.xctest"), b"").unwrap();
+
+        let products = parse_describe(DESCRIBE).unwrap();
+        let located: Vec<(String, TargetType, PathBuf)> = locate_products(bin, &products, "linux")
+            .into_iter()
+            .map(|p| (p.name, p.target_type, p.path))
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                (
+                    "mytool".to_string(),
+                    TargetType::Executable,
+                    bin.join("mytool")
+                ),
+                (
+                    "Net".to_string(),
+                    TargetType::Library,
+                    bin.join("lib/libNet.so")
+                ),
+                (
+                    "ToolsPackageTests".to_string(),
+                    TargetType::Test,
+                    bin.join("ToolsPackageTests.xctest")
+                ),
+            ]
+        );
+
+        // The same library is a .dylib on macOS
+        assert!(locate_products(bin, &products[1..2], "macos").is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Test counts from `swift test` output.
+//!
+//! XCTest ends with an `Executed N tests, with M failures` line for the
+//! whole run, while swift-testing reports each test on its own line, so the
+//! two are counted separately and added up.
+
+use std::fmt;
+
+/// How many tests passed, failed and were skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestCounts {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl TestCounts {
+    /// Counts from the complete output of `swift test`.
+    pub fn parse(output: &str) -> Self {
+        let xctest = output
+            .lines()
+            .rev()
+            .find_map(parse_executed)
+            .unwrap_or_default();
+
+        let mut counts = xctest;
+        for line in output.lines() {
+            let line = line.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+            if !line.starts_with("Test ") || line.starts_with("Test run") {
+                continue;
+            }
+            if line.contains(" passed after ") {
+                counts.passed += 1;
+            } else if line.contains(" failed after ") {
+                counts.failed += 1;
+            } else if line.ends_with(" skipped.") || line.contains(" skipped: ") {
+                counts.skipped += 1;
+            }
+        }
+        counts
+    }
+
+    /// Total number of tests.
+    pub fn total(&self) -> usize {
+        self.passed + self.failed + self.skipped
+    }
+
+    /// Add `other` to these counts.
+    pub fn add(&mut self, other: TestCounts) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+    }
+}
+
+impl fmt::Display for TestCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} passed, {} failed", self.passed, self.failed)?;
+        if self.skipped > 0 {
+            write!(f, ", {} skipped", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts from an XCTest summary line, such as
+/// `Executed 5 tests, with 1 test skipped and 2 failures (0 unexpected) in 0.1 (0.1) seconds`.
+fn parse_executed(line: &str) -> Option<TestCounts> {
+    let rest = line.trim().strip_prefix("Executed ")?;
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let executed: usize = words.first()?.parse().ok()?;
+
+    // The number before a word, e.g. "2" in "2 failures"
+    let count_before = |word: &str| {
+        words
+            .windows(2)
+            .find(|w| w[1].trim_end_matches(',').starts_with(word))
+            .and_then(|w| w[0].parse::<usize>().ok())
+            .unwrap_or(0)
+    };
+    let failed = count_before("failure");
+    let skipped = words
+        .windows(3)
+        .find(|w| w[2].starts_with("skipped"))
+        .and_then(|w| w[0].parse::<usize>().ok())
+        .unwrap_or(0);
+
+    Some(TestCounts {
+        passed: executed.saturating_sub(failed + skipped),
+        failed,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xctest_and_swift_testing() {
+        let xctest = "\
+Test Suite 'All tests' started at 2024-05-01 10:00:00.000
+Test Suite 'CoreTests' started at 2024-05-01 10:00:00.001
+Test Case 'CoreTests.testAdd' passed (0.001 seconds)
+Test Case 'CoreTests.testSub' failed (0.002 seconds)
+Test Suite 'CoreTests' failed at 2024-05-01 10:00:00.010
+\t Executed 4 tests, with 1 test skipped and 1 failure (0 unexpected) in 0.009 (0.009) seconds
+Test Suite 'All tests' failed at 2024-05-01 10:00:00.011
+\t Executed 4 tests, with 1 test skipped and 1 failure (0 unexpected) in 0.009 (0.010) seconds
+";
+        assert_eq!(
+            TestCounts::parse(xctest),
+            TestCounts {
+                passed: 2,
+                failed: 1,
+                skipped: 1,
+            }
+        );
+
+        let swift_testing = "\
+◇ Test run started.
+◇ Test parses() started.
+✔ Test parses() passed after 0.001 seconds.
+✘ Test rejects() recorded an issue at ParserTests.swift:12:5: Expectation failed
+✘ Test rejects() failed after 0.002 seconds with 1 issue.
+➜ Test later() skipped.
+✘ Test run with 3 tests failed after 0.003 seconds with 1 issue.
+";
+        let counts = TestCounts::parse(swift_testing);
+        assert_eq!(
+            counts,
+            TestCounts {
+                passed: 1,
+                failed: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(counts.to_string(), "1 passed, 1 failed, 1 skipped");
+        assert_eq!(
+            TestCounts::parse("error: build failed"),
+            TestCounts::default()
+        );
+    }
+}
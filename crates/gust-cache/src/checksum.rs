@@ -0,0 +1,105 @@
+//! The checksum of a checkout, as recorded in Gust.lock.
+//!
+//! Fetching computes it for what it just checked out, and the lockfile
+//! recomputes it to verify what's on disk, so both must go through this one
+//! function to agree.
+
+use crate::{collect_files, lfs_pointer, LfsPatterns, SymlinkPolicy};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Files up to this size are read; larger ones are memory-mapped.
+const MMAP_THRESHOLD: u64 = 4096;
+
+/// BLAKE3 checksum of every file in `path` except `.git`.
+///
+/// Symlinked directories are followed once per real directory, so a
+/// package that links a folder into itself (or in a cycle) still hashes.
+/// Git LFS files count as their pointers, whether or not their content was
+/// pulled.
+pub fn directory_checksum(path: &Path) -> io::Result<String> {
+    let files = collect_files(path, SymlinkPolicy::Follow, &|name| name == ".git")?;
+    let lfs = LfsPatterns::load(path);
+    let hash = |content: &[u8], key: &str| {
+        if lfs.matches(key) {
+            blake3::hash(&lfs_pointer(content))
+        } else {
+            blake3::hash(content)
+        }
+        .to_hex()
+        .to_string()
+    };
+
+    let file_hashes = files
+        .par_iter()
+        .map(|(key, path)| {
+            let file = fs::File::open(path)?;
+            let hash = if file.metadata()?.len() > MMAP_THRESHOLD {
+                // SAFETY: We only read the file, and it's not modified during hashing
+                let mmap = unsafe { memmap2::Mmap::map(&file)? };
+                hash(&mmap, key)
+            } else {
+                hash(&fs::read(path)?, key)
+            };
+            Ok((key.clone(), hash))
+        })
+        .collect::<io::Result<BTreeMap<String, String>>>()?;
+
+    let combined: String = file_hashes
+        .iter()
+        .map(|(k, v)| format!("{}:{}", k, v))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(blake3::hash(combined.as_bytes()).to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfs_files_hash_the_same_pulled_or_not() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        std::fs::create_dir_all(repo.join("Fixtures")).unwrap();
+        std::fs::write(repo.join(".gitattributes"), "*.bin filter=lfs -text\n").unwrap();
+        std::fs::write(repo.join("Package.swift"), "// swift-tools-version:5.9\n").unwrap();
+        let model = vec![7u8; 8192];
+        std::fs::write(repo.join("Fixtures/model.bin"), &model).unwrap();
+        let pulled = directory_checksum(&repo).unwrap();
+
+        // Left as a pointer, as on a machine without git-lfs
+        std::fs::write(repo.join("Fixtures/model.bin"), lfs_pointer(&model)).unwrap();
+        assert_eq!(directory_checksum(&repo).unwrap(), pulled);
+
+        // Different content is still a different checksum
+        std::fs::write(repo.join("Fixtures/model.bin"), vec![8u8; 8192]).unwrap();
+        assert_ne!(directory_checksum(&repo).unwrap(), pulled);
+
+        // Files outside LFS are hashed as they are
+        std::fs::write(repo.join("Fixtures/model.bin"), &model).unwrap();
+        std::fs::write(
+            repo.join("Package.swift"),
+            lfs_pointer(b"// swift-tools-version:5.9\n"),
+        )
+        .unwrap();
+        assert_ne!(directory_checksum(&repo).unwrap(), pulled);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_checksum_survives_symlink_cycles() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let pkg = tmp.path().join("pkg");
+        std::fs::create_dir_all(pkg.join("Sources")).unwrap();
+        std::fs::write(pkg.join("Sources/Lib.swift"), "let x = 1").unwrap();
+        let before = directory_checksum(&pkg).unwrap();
+
+        std::os::unix::fs::symlink(&pkg, pkg.join("Sources/loop")).unwrap();
+        assert_eq!(directory_checksum(&pkg).unwrap(), before);
+    }
+}
@@ -0,0 +1,123 @@
+//! Hashing Git LFS files the same way whether or not their content was
+//! pulled.
+//!
+//! Without git-lfs a checkout holds pointer files where the content would
+//! be. Checksums take every LFS file in its pointer form, which can be
+//! computed from the content, so a checkout hashes the same on every
+//! machine.
+
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::path::Path;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1\n";
+
+/// Pointer files are small; anything larger is content.
+const MAX_POINTER_LEN: usize = 1024;
+
+/// The files the `.gitattributes` at the root of a checkout sends through
+/// the Git LFS filter.
+#[derive(Debug, Clone, Default)]
+pub struct LfsPatterns {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl LfsPatterns {
+    /// Read the patterns from `dir/.gitattributes`; none if it's missing.
+    pub fn load(dir: &Path) -> Self {
+        let attributes = std::fs::read_to_string(dir.join(".gitattributes")).unwrap_or_default();
+        Self::parse(&attributes)
+    }
+
+    /// Patterns of the lines in `attributes` that set `filter=lfs`.
+    pub fn parse(attributes: &str) -> Self {
+        let patterns = attributes
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.starts_with('#') {
+                    return None;
+                }
+                let mut fields = line.split_whitespace();
+                let pattern = fields.next()?;
+                if !fields.any(|attr| attr == "filter=lfs") {
+                    return None;
+                }
+                // Without a slash a pattern matches a name at any depth
+                let pattern = match pattern.strip_prefix('/') {
+                    Some(rooted) => rooted.to_string(),
+                    None if pattern.contains('/') => pattern.to_string(),
+                    None => format!("**/{}", pattern),
+                };
+                glob::Pattern::new(&pattern).ok()
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the file at `key`, relative to the checkout with `/`
+    /// separators, is stored in LFS.
+    pub fn matches(&self, key: &str) -> bool {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.patterns.iter().any(|p| p.matches_with(key, options))
+    }
+}
+
+/// The Git LFS pointer for a file holding `content`. A pointer that was
+/// never pulled is returned as it is.
+pub fn lfs_pointer(content: &[u8]) -> Cow<'_, [u8]> {
+    if content.len() <= MAX_POINTER_LEN && content.starts_with(POINTER_VERSION.as_bytes()) {
+        return Cow::Borrowed(content);
+    }
+    let oid = Sha256::digest(content);
+    let hex: String = oid.iter().map(|b| format!("{:02x}", b)).collect();
+    Cow::Owned(
+        format!(
+            "{}oid sha256:{}\nsize {}\n",
+            POINTER_VERSION,
+            hex,
+            content.len()
+        )
+        .into_bytes(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfs_patterns_follow_gitattributes() {
+        let lfs = LfsPatterns::parse(
+            "# *.zip filter=lfs\n*.bin filter=lfs diff=lfs merge=lfs -text\n\
+             /Assets/*.png filter=lfs\nDocs/** filter=lfs\n*.swift text\n",
+        );
+        assert!(lfs.matches("model.bin"));
+        assert!(lfs.matches("Fixtures/deep/model.bin"));
+        assert!(lfs.matches("Assets/logo.png"));
+        assert!(!lfs.matches("Sources/Assets/logo.png"));
+        assert!(lfs.matches("Docs/guide/intro.md"));
+        assert!(!lfs.matches("archive.zip"));
+        assert!(!lfs.matches("Sources/App.swift"));
+        assert!(LfsPatterns::parse("*.swift text\n").is_empty());
+    }
+
+    #[test]
+    fn test_pointer_is_the_same_for_content_and_pointer() {
+        let pointer = lfs_pointer(b"hello\n").into_owned();
+        assert_eq!(
+            String::from_utf8(pointer.clone()).unwrap(),
+            "version https://git-lfs.github.com/spec/v1\n\
+             oid sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03\n\
+             size 6\n"
+        );
+        assert_eq!(lfs_pointer(&pointer), pointer);
+    }
+}
@@ -3,6 +3,18 @@
 //! Implements a pnpm-style global store that saves disk space
 //! by storing each unique file only once.
 
+mod checksum;
+mod lfs;
+mod migrate;
+mod report;
+mod walk;
+
+pub use checksum::directory_checksum;
+pub use lfs::{lfs_pointer, LfsPatterns};
+pub use migrate::StoreMigration;
+pub use report::{AgeHistogram, CacheReport};
+pub use walk::{collect_files, SymlinkPolicy};
+
 use blake3::Hasher;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
@@ -11,7 +23,13 @@ use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Environment variable that relocates every Gust cache.
+pub const CACHE_DIR_ENV: &str = "GUST_CACHE_DIR";
+
+/// Version of the store format, which names the `store/v<N>` directory.
+pub const STORE_VERSION: u32 = 1;
 
 #[derive(Error, Debug)]
 pub enum CacheError {
@@ -25,11 +43,21 @@ pub enum CacheError {
     LinkError(#[source] io::Error),
     #[error("Cache directory not found")]
     NoCacheDir,
+    #[error(
+        "Cache directory {} is not writable: {source}; set GUST_CACHE_DIR to a writable location",
+        path.display()
+    )]
+    NotWritable {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
     #[error("Package not in cache: {0}")]
     PackageNotFound(String),
 }
 
 /// The global package cache.
+#[derive(Debug, Clone)]
 pub struct GlobalCache {
     /// Root cache directory (~/.gust)
     root: PathBuf,
@@ -44,18 +72,44 @@ impl GlobalCache {
         Self::open_at(root)
     }
 
-    /// Open a cache at a specific location.
+    /// Open a cache at a specific location, migrating any stores left by
+    /// older versions.
+    ///
+    /// Fails with [`CacheError::NotWritable`] up front rather than on the
+    /// first store if the directory can't be written. A failed migration
+    /// only warns; `gust cache migrate` can retry it.
     pub fn open_at(root: PathBuf) -> Result<Self, CacheError> {
-        let cache = Self { root, version: 1 };
+        let cache = Self::open_unmigrated(root)?;
+        match cache.migrate() {
+            Ok(migration) if !migration.is_empty() => info!(
+                "Migrated {} packages from old cache stores",
+                migration.packages
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to migrate old cache stores: {}", e),
+        }
+        Ok(cache)
+    }
+
+    /// Open a cache at a specific location, leaving older stores as they
+    /// are.
+    pub fn open_unmigrated(root: PathBuf) -> Result<Self, CacheError> {
+        Self::open_version(root, STORE_VERSION)
+    }
+
+    fn open_version(root: PathBuf, version: u32) -> Result<Self, CacheError> {
+        ensure_writable(&root)?;
+        let cache = Self { root, version };
         cache.ensure_dirs()?;
         Ok(cache)
     }
 
-    /// Get the default cache directory.
+    /// Get the default cache directory: `GUST_CACHE_DIR` if set, then the
+    /// one passed to [`configure_cache_dir`], otherwise the platform cache
+    /// directory (which honors `XDG_CACHE_HOME`).
     pub fn default_cache_dir() -> Result<PathBuf, CacheError> {
-        ProjectDirs::from("dev", "gust", "gust")
-            .map(|dirs| dirs.cache_dir().to_path_buf())
-            .ok_or(CacheError::NoCacheDir)
+        let env = std::env::var_os(CACHE_DIR_ENV).filter(|d| !d.is_empty());
+        cache_dir_from(env.or_else(|| CONFIGURED_CACHE_DIR.get().map(|d| d.clone().into())))
     }
 
     /// Ensure all cache directories exist.
@@ -87,11 +141,102 @@ impl GlobalCache {
         self.root.join("git")
     }
 
+    /// Get the directory path dependencies are linked or copied into, one
+    /// entry per source directory.
+    pub fn paths_dir(&self) -> PathBuf {
+        self.root.join("paths")
+    }
+
+    /// Get the directory extracted binary target artifacts are stored in.
+    pub fn artifacts_dir(&self) -> PathBuf {
+        self.root.join("artifacts")
+    }
+
+    /// Get the directory the Swift Package Index list is cached in.
+    pub fn index_dir(&self) -> PathBuf {
+        self.root.join("index")
+    }
+
     /// Get the binary cache directory.
     pub fn binary_cache_dir(&self) -> PathBuf {
         self.root.join("binary-cache")
     }
 
+    /// Get the file listing the projects that install from this cache, one
+    /// directory per line.
+    pub fn projects_file(&self) -> PathBuf {
+        self.root.join("projects")
+    }
+
+    /// Record that the project at `dir` installs from this cache, so pruning
+    /// can tell what its lockfile still needs.
+    pub fn register_project(&self, dir: &Path) -> Result<(), CacheError> {
+        let dir = dir.canonicalize().map_err(CacheError::ReadError)?;
+        if self.projects().contains(&dir) {
+            return Ok(());
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.projects_file())
+            .map_err(CacheError::WriteError)?;
+        writeln!(file, "{}", dir.display()).map_err(CacheError::WriteError)
+    }
+
+    /// Projects recorded with [`GlobalCache::register_project`] that still
+    /// exist.
+    pub fn projects(&self) -> Vec<PathBuf> {
+        fs::read_to_string(self.projects_file())
+            .unwrap_or_default()
+            .lines()
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    /// Get the directory checkout checksums are kept in, one file per git
+    /// commit.
+    pub fn checksums_dir(&self) -> PathBuf {
+        self.root.join("checksums")
+    }
+
+    /// The checksum recorded for the checkout `dir` of the commit
+    /// `revision`, if none of its files were added, removed or modified
+    /// since.
+    ///
+    /// Comparing file sizes and modification times is much cheaper than
+    /// hashing the checkout again; `gust verify` still hashes it.
+    pub fn checkout_checksum(&self, revision: &str, dir: &Path) -> Option<String> {
+        if !is_commit_hash(revision) {
+            return None;
+        }
+        let recorded = fs::read_to_string(self.checksums_dir().join(revision)).ok()?;
+        let (signature, checksum) = recorded.trim().split_once(' ')?;
+        (signature == checkout_signature(dir).ok()? && !checksum.is_empty())
+            .then(|| checksum.to_string())
+    }
+
+    /// Record the checksum of the checkout `dir` of the commit `revision`
+    /// for [`GlobalCache::checkout_checksum`]. Anything but a full commit
+    /// hash is ignored.
+    pub fn record_checkout_checksum(
+        &self,
+        revision: &str,
+        dir: &Path,
+        checksum: &str,
+    ) -> Result<(), CacheError> {
+        if !is_commit_hash(revision) {
+            return Ok(());
+        }
+        let signature = checkout_signature(dir).map_err(CacheError::ReadError)?;
+        fs::create_dir_all(self.checksums_dir()).map_err(CacheError::CreateDirError)?;
+        fs::write(
+            self.checksums_dir().join(revision),
+            format!("{} {}", signature, checksum),
+        )
+        .map_err(CacheError::WriteError)
+    }
+
     /// Compute the BLAKE3 hash of a file.
     pub fn hash_file(path: &Path) -> Result<String, CacheError> {
         let mut file = File::open(path).map_err(CacheError::ReadError)?;
@@ -120,20 +265,27 @@ impl GlobalCache {
         self.files_dir().join(prefix).join(hash)
     }
 
-    /// Store a file in the content-addressed store.
+    /// Store a file in the content-addressed store, returning its key.
+    ///
+    /// The key is the content hash, followed by `.x` for an executable
+    /// file, so files with the same content but a different mode are
+    /// stored apart and each keeps its mode.
     pub fn store_file(&self, path: &Path) -> Result<String, CacheError> {
-        let hash = Self::hash_file(path)?;
-        let dest = self.content_path(&hash);
+        let metadata = fs::metadata(path).map_err(CacheError::ReadError)?;
+        let executable = is_executable(&metadata);
+        let key = blob_key(&Self::hash_file(path)?, executable);
+        let dest = self.content_path(&key);
 
         if !dest.exists() {
             if let Some(parent) = dest.parent() {
                 fs::create_dir_all(parent).map_err(CacheError::CreateDirError)?;
             }
             fs::copy(path, &dest).map_err(CacheError::WriteError)?;
-            debug!("Stored file {} -> {}", path.display(), hash);
+            set_mode(&dest, executable).map_err(CacheError::WriteError)?;
+            debug!("Stored file {} -> {}", path.display(), key);
         }
 
-        Ok(hash)
+        Ok(key)
     }
 
     /// Store bytes in the content-addressed store.
@@ -200,11 +352,106 @@ pub struct PackageMetadata {
     pub version: String,
     /// Map of file paths to content hashes
     pub files: HashMap<String, String>,
+    /// Map of symlink paths to their targets
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub links: HashMap<String, PathBuf>,
+    /// Git commit the files were checked out at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
     /// Total size in bytes
     pub total_size: u64,
 }
 
 impl PackageMetadata {
+    /// Store every regular file under `dir` in the content-addressed store
+    /// and save the resulting metadata, with `revision` being the commit
+    /// `dir` is a checkout of.
+    ///
+    /// `.git` directories are left out. Symlinks have no content of their
+    /// own, so only their targets are recorded.
+    pub fn store_dir(
+        cache: &GlobalCache,
+        name: &str,
+        version: &str,
+        revision: Option<&str>,
+        dir: &Path,
+    ) -> Result<Self, CacheError> {
+        let entries = walkdir::WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.file_name() != ".git");
+
+        let mut files = HashMap::new();
+        let mut links = HashMap::new();
+        let mut total_size = 0;
+        for entry in entries {
+            let entry = entry.map_err(|e| CacheError::ReadError(e.into()))?;
+            let Ok(relative) = entry.path().strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if entry.path_is_symlink() {
+                let target = fs::read_link(entry.path()).map_err(CacheError::ReadError)?;
+                links.insert(relative, target);
+            } else if entry.file_type().is_file() {
+                files.insert(relative, cache.store_file(entry.path())?);
+                total_size += entry
+                    .metadata()
+                    .map_err(|e| CacheError::ReadError(e.into()))?
+                    .len();
+            }
+        }
+
+        let metadata = Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            files,
+            links,
+            revision: revision.map(str::to_string),
+            total_size,
+        };
+        metadata.save(cache)?;
+        Ok(metadata)
+    }
+
+    /// Copy every file of this package from the store into `dest`, and
+    /// recreate its symlinks, replacing anything already there.
+    ///
+    /// Files are copied rather than hard-linked, since editing a linked file
+    /// would change it in the store and in every other package sharing it.
+    /// Where the filesystem supports it (APFS, btrfs, XFS) the copy is a
+    /// clone, so files shared with other packages still take up space once.
+    pub fn materialize(&self, cache: &GlobalCache, dest: &Path) -> Result<(), CacheError> {
+        let prepare = |relative: &str| -> Result<PathBuf, CacheError> {
+            let path = dest.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(CacheError::CreateDirError)?;
+            }
+            if path.symlink_metadata().is_ok() {
+                fs::remove_file(&path).map_err(CacheError::WriteError)?;
+            }
+            Ok(path)
+        };
+        for (relative, hash) in &self.files {
+            let source = cache.content_path(hash);
+            if !source.exists() {
+                return Err(CacheError::PackageNotFound(format!(
+                    "{}@{}: {}",
+                    self.name, self.version, relative
+                )));
+            }
+            fs::copy(source, prepare(relative)?).map_err(CacheError::WriteError)?;
+        }
+        for (relative, target) in &self.links {
+            symlink(target, &prepare(relative)?).map_err(CacheError::WriteError)?;
+        }
+        Ok(())
+    }
+
     /// Save metadata to the cache.
     pub fn save(&self, cache: &GlobalCache) -> Result<(), CacheError> {
         let dir = cache
@@ -234,6 +481,104 @@ impl PackageMetadata {
     }
 }
 
+/// A hash of the paths, sizes and modification times of the files of a
+/// checkout, leaving out `.git`.
+fn checkout_signature(dir: &Path) -> io::Result<String> {
+    let mut hasher = Hasher::new();
+    for (key, path) in collect_files(dir, SymlinkPolicy::Follow, &|name| name == ".git")? {
+        let metadata = fs::metadata(&path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.update(format!("{}:{}:{}\n", key, metadata.len(), modified.as_nanos()).as_bytes());
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Whether `revision` is a full SHA-1 or SHA-256 commit hash.
+fn is_commit_hash(revision: &str) -> bool {
+    matches!(revision.len(), 40 | 64) && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The store key of a file with content `hash`; see
+/// [`GlobalCache::store_file`].
+fn blob_key(hash: &str, executable: bool) -> String {
+    if executable {
+        format!("{}.x", hash)
+    } else {
+        hash.to_string()
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Give a stored file the mode git would check it out with.
+#[cfg(unix)]
+fn set_mode(path: &Path, executable: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = if executable { 0o755 } else { 0o644 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _executable: bool) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Cache directory from Gust's config, below `GUST_CACHE_DIR`.
+static CONFIGURED_CACHE_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// Use `dir` as the default cache directory when `GUST_CACHE_DIR` isn't
+/// set, such as `cache-dir` from Gust's config. Only the first call has an
+/// effect.
+pub fn configure_cache_dir(dir: PathBuf) {
+    let _ = CONFIGURED_CACHE_DIR.set(dir);
+}
+
+fn cache_dir_from(env_override: Option<std::ffi::OsString>) -> Result<PathBuf, CacheError> {
+    if let Some(dir) = env_override.filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    ProjectDirs::from("dev", "gust", "gust")
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .ok_or(CacheError::NoCacheDir)
+}
+
+/// Create `dir` if needed and check that files can be written to it by
+/// creating and removing a probe file.
+pub fn ensure_writable(dir: &Path) -> Result<(), CacheError> {
+    let not_writable = |source| CacheError::NotWritable {
+        path: dir.to_path_buf(),
+        source,
+    };
+
+    fs::create_dir_all(dir).map_err(not_writable)?;
+    let probe = dir.join(format!(".gust-write-test-{}", std::process::id()));
+    File::create(&probe).map_err(not_writable)?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,6 +603,120 @@ mod tests {
         assert_eq!(content, "test content");
     }
 
+    #[test]
+    fn test_old_store_is_migrated() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("cache");
+        let package = tmp.path().join("alpha");
+        fs::create_dir_all(&package).unwrap();
+        fs::write(package.join("Package.swift"), "// alpha").unwrap();
+
+        let old = GlobalCache::open_version(root.clone(), 1).unwrap();
+        let stored = PackageMetadata::store_dir(&old, "alpha", "1.0.0", None, &package).unwrap();
+        fs::create_dir_all(old.packages_dir().join("broken@0.1.0")).unwrap();
+        fs::write(
+            old.packages_dir().join("broken@0.1.0/metadata.json"),
+            r#"{"name":"broken","version":"0.1.0","files":{"a":"ffff"},"total_size":1}"#,
+        )
+        .unwrap();
+
+        let cache = GlobalCache::open_version(root.clone(), 2).unwrap();
+        // A newer store is not ours to touch
+        fs::create_dir_all(root.join("store/v3")).unwrap();
+        assert_eq!(cache.old_store_versions().unwrap(), vec![1]);
+
+        let migration = cache.migrate().unwrap();
+        assert_eq!(
+            migration,
+            StoreMigration {
+                versions: vec![1],
+                packages: 1,
+                dropped: 1,
+            }
+        );
+        assert!(!root.join("store/v1").exists());
+        assert!(root.join("store/v3").exists());
+
+        let migrated = PackageMetadata::load(&cache, "alpha", "1.0.0").unwrap();
+        assert_eq!(migrated.files, stored.files);
+        assert!(cache.contains(&migrated.files["Package.swift"]));
+        assert!(PackageMetadata::load(&cache, "broken", "0.1.0").is_err());
+        assert!(cache.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migration_keeps_files_shared_between_packages() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("cache");
+        let old = GlobalCache::open_version(root.clone(), 1).unwrap();
+        let mut stored = Vec::new();
+        for name in ["alpha", "beta"] {
+            let package = tmp.path().join(name);
+            fs::create_dir_all(&package).unwrap();
+            fs::write(package.join("LICENSE"), "MIT").unwrap();
+            fs::write(package.join("Package.swift"), format!("// {}", name)).unwrap();
+            stored.push(PackageMetadata::store_dir(&old, name, "1.0.0", None, &package).unwrap());
+        }
+        assert_eq!(stored[0].files["LICENSE"], stored[1].files["LICENSE"]);
+
+        let cache = GlobalCache::open_version(root, 2).unwrap();
+        let migration = cache.migrate().unwrap();
+        assert_eq!((migration.packages, migration.dropped), (2, 0));
+        for (name, stored) in ["alpha", "beta"].into_iter().zip(&stored) {
+            let migrated = PackageMetadata::load(&cache, name, "1.0.0").unwrap();
+            assert_eq!(migrated.files, stored.files);
+            assert!(migrated.files.values().all(|hash| cache.contains(hash)));
+        }
+    }
+
+    #[test]
+    fn test_projects_are_registered_once() {
+        let tmp = TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let (app, gone) = (tmp.path().join("app"), tmp.path().join("gone"));
+        fs::create_dir_all(&app).unwrap();
+        fs::create_dir_all(&gone).unwrap();
+
+        cache.register_project(&app).unwrap();
+        cache.register_project(&app).unwrap();
+        cache.register_project(&gone).unwrap();
+        fs::remove_dir(&gone).unwrap();
+
+        assert_eq!(cache.projects(), [app.canonicalize().unwrap()]);
+    }
+
+    #[test]
+    fn test_checkout_checksums_are_kept_by_commit() {
+        let tmp = TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let checkout = tmp.path().join("checkout");
+        fs::create_dir_all(checkout.join(".git")).unwrap();
+        fs::write(checkout.join("Package.swift"), "// swift-tools-version:5.9").unwrap();
+
+        assert_eq!(cache.checkout_checksum(commit, &checkout), None);
+        cache
+            .record_checkout_checksum(commit, &checkout, "feedface")
+            .unwrap();
+        assert_eq!(
+            cache.checkout_checksum(commit, &checkout).as_deref(),
+            Some("feedface")
+        );
+
+        // Git's own files don't matter, but the checkout's do
+        fs::write(checkout.join(".git/index"), "changed").unwrap();
+        assert!(cache.checkout_checksum(commit, &checkout).is_some());
+        fs::write(checkout.join("Injected.swift"), "").unwrap();
+        assert_eq!(cache.checkout_checksum(commit, &checkout), None);
+
+        // A branch or tag name can point anywhere, so it isn't a key
+        cache
+            .record_checkout_checksum("HEAD", &checkout, "feedface")
+            .unwrap();
+        assert_eq!(cache.checkout_checksum("HEAD", &checkout), None);
+        assert_eq!(cache.checkout_checksum("../projects", &checkout), None);
+    }
+
     #[test]
     fn test_link_file() {
         let tmp = TempDir::new().unwrap();
@@ -271,4 +730,165 @@ mod tests {
         let content = fs::read_to_string(dest).unwrap();
         assert_eq!(content, "linked content");
     }
+
+    #[test]
+    fn test_shared_file_stored_once() {
+        let tmp = TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+
+        // Two packages vendoring the same file, plus one file of their own
+        let package = |name: &str| {
+            let dir = tmp.path().join(name);
+            fs::create_dir_all(dir.join("Sources/Vendored")).unwrap();
+            fs::write(dir.join("Sources/Vendored/sqlite3.c"), "int sqlite;").unwrap();
+            fs::write(dir.join("Package.swift"), format!("// {}", name)).unwrap();
+            fs::create_dir_all(dir.join(".git")).unwrap();
+            fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+            dir
+        };
+        let alpha = package("alpha");
+        let beta = package("beta");
+
+        let alpha_meta =
+            PackageMetadata::store_dir(&cache, "alpha", "1.0.0", None, &alpha).unwrap();
+        let beta_meta = PackageMetadata::store_dir(&cache, "beta", "2.0.0", None, &beta).unwrap();
+        assert_eq!(alpha_meta.files.len(), 2);
+        assert_eq!(
+            alpha_meta.files["Sources/Vendored/sqlite3.c"],
+            beta_meta.files["Sources/Vendored/sqlite3.c"]
+        );
+        assert_eq!(alpha_meta.total_size, 11 + "// alpha".len() as u64);
+
+        // Three distinct contents in the store, not four
+        let stored = collect_files(&cache.files_dir(), SymlinkPolicy::Skip, &|_| false).unwrap();
+        assert_eq!(stored.len(), 3);
+
+        let loaded = PackageMetadata::load(&cache, "beta", "2.0.0").unwrap();
+        assert_eq!(loaded.files, beta_meta.files);
+    }
+
+    #[test]
+    fn test_materialized_files_are_not_shared_with_the_store() {
+        let tmp = TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let package = |name: &str| {
+            let dir = tmp.path().join(name);
+            fs::create_dir_all(dir.join("Sources/Vendored")).unwrap();
+            fs::write(dir.join("Sources/Vendored/sqlite3.c"), "int sqlite;").unwrap();
+            dir
+        };
+        let alpha = package("alpha");
+        let beta = package("beta");
+        let alpha_meta =
+            PackageMetadata::store_dir(&cache, "alpha", "1.0.0", None, &alpha).unwrap();
+        PackageMetadata::store_dir(&cache, "beta", "1.0.0", None, &beta)
+            .unwrap()
+            .materialize(&cache, &beta)
+            .unwrap();
+
+        // Editing one materialized copy leaves the store and the other
+        // package alone
+        alpha_meta.materialize(&cache, &alpha).unwrap();
+        fs::write(alpha.join("Sources/Vendored/sqlite3.c"), "int patched;").unwrap();
+        let hash = &alpha_meta.files["Sources/Vendored/sqlite3.c"];
+        assert_eq!(
+            fs::read_to_string(cache.get_path(hash).unwrap()).unwrap(),
+            "int sqlite;"
+        );
+        assert_eq!(
+            fs::read_to_string(beta.join("Sources/Vendored/sqlite3.c")).unwrap(),
+            "int sqlite;"
+        );
+
+        // Materializing somewhere new creates the directories it needs
+        let copy = tmp.path().join("copy");
+        alpha_meta.materialize(&cache, &copy).unwrap();
+        assert_eq!(
+            fs::read_to_string(copy.join("Sources/Vendored/sqlite3.c")).unwrap(),
+            "int sqlite;"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_materialized_files_keep_their_mode_and_links() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let package = |name: &str, mode: u32| {
+            let dir = tmp.path().join(name);
+            fs::create_dir_all(dir.join("scripts")).unwrap();
+            fs::write(dir.join("scripts/gen.sh"), "#!/bin/sh\n").unwrap();
+            fs::set_permissions(dir.join("scripts/gen.sh"), fs::Permissions::from_mode(mode))
+                .unwrap();
+            std::os::unix::fs::symlink("scripts", dir.join("bin")).unwrap();
+            dir
+        };
+        let plain =
+            PackageMetadata::store_dir(&cache, "plain", "1.0.0", None, &package("plain", 0o644))
+                .unwrap();
+        let tool = PackageMetadata::store_dir(
+            &cache,
+            "tool",
+            "1.0.0",
+            Some("0123456789abcdef0123456789abcdef01234567"),
+            &package("tool", 0o755),
+        )
+        .unwrap();
+
+        // Same content, different mode: stored apart
+        assert_ne!(plain.files["scripts/gen.sh"], tool.files["scripts/gen.sh"]);
+        assert_eq!(tool.links["bin"], PathBuf::from("scripts"));
+
+        let restored = tmp.path().join("restored");
+        PackageMetadata::load(&cache, "tool", "1.0.0")
+            .unwrap()
+            .materialize(&cache, &restored)
+            .unwrap();
+        let mode = fs::metadata(restored.join("scripts/gen.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(
+            fs::read_to_string(restored.join("bin/gen.sh")).unwrap(),
+            "#!/bin/sh\n"
+        );
+
+        // A file gone from the store fails the materialization
+        fs::remove_file(cache.get_path(&tool.files["scripts/gen.sh"]).unwrap()).unwrap();
+        assert!(tool.materialize(&cache, &tmp.path().join("again")).is_err());
+    }
+
+    #[test]
+    fn test_unwritable_cache_dir() {
+        let tmp = TempDir::new().unwrap();
+        // A regular file where the cache directory should be can't be
+        // written into, whatever the current user's permissions
+        let blocker = tmp.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        let root = blocker.join("cache");
+
+        let err = GlobalCache::open_at(root.clone()).err().unwrap();
+        assert!(matches!(err, CacheError::NotWritable { ref path, .. } if *path == root));
+        let message = err.to_string();
+        assert!(message.contains(&root.display().to_string()));
+        assert!(message.contains("GUST_CACHE_DIR"));
+    }
+
+    #[test]
+    fn test_cache_dir_env_override() {
+        let tmp = TempDir::new().unwrap();
+        let custom = tmp.path().join("custom");
+
+        let dir = cache_dir_from(Some(custom.clone().into_os_string())).unwrap();
+        assert_eq!(dir, custom);
+        let cache = GlobalCache::open_at(dir).unwrap();
+        assert!(cache.git_dir().starts_with(&custom));
+        assert!(custom.join("git").is_dir());
+
+        // An empty override falls back to the platform default
+        assert_ne!(cache_dir_from(Some("".into())).unwrap(), PathBuf::new());
+    }
 }
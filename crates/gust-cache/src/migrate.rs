@@ -0,0 +1,133 @@
+//! Moving packages out of stores left by older Gust versions.
+//!
+//! Each store format lives under `store/v<N>`. When the format changes,
+//! packages in older stores are carried over to the current one: their
+//! files are content-addressed, so migrating mostly means moving each file
+//! to where the current store expects it and saving the metadata again.
+//! The old store is removed afterwards. Stores from newer versions are left
+//! alone for the Gust that wrote them.
+
+use crate::{CacheError, GlobalCache, PackageMetadata};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// What a migration carried over and removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StoreMigration {
+    /// Old store versions that were found and removed
+    pub versions: Vec<u32>,
+    /// Packages carried over to the current store
+    pub packages: usize,
+    /// Packages dropped because their files were missing or unreadable
+    pub dropped: usize,
+}
+
+impl StoreMigration {
+    /// Whether there was nothing to migrate.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+}
+
+impl GlobalCache {
+    /// Store versions older than this cache's with a directory under
+    /// `store`, oldest first.
+    pub fn old_store_versions(&self) -> Result<Vec<u32>, CacheError> {
+        let entries = match fs::read_dir(self.root.join("store")) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(CacheError::ReadError(e)),
+        };
+
+        let mut versions: Vec<u32> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str()?.strip_prefix('v')?.parse().ok())
+            .filter(|&version| version < self.version)
+            .collect();
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    /// Carry packages from older stores over to this one and remove the old
+    /// stores.
+    pub fn migrate(&self) -> Result<StoreMigration, CacheError> {
+        let mut migration = StoreMigration::default();
+        for version in self.old_store_versions()? {
+            let old = self.root.join("store").join(format!("v{}", version));
+            info!("Migrating cache store v{} to v{}", version, self.version);
+
+            // Old hash to current hash of every file moved so far; packages
+            // share deduplicated files, which are gone from the old store
+            // once the first of them has been migrated
+            let mut moved = HashMap::new();
+            for metadata in old_packages(&old.join("packages")) {
+                match self.migrate_package(&old.join("files"), metadata, &mut moved) {
+                    Ok(()) => migration.packages += 1,
+                    Err(e) => {
+                        debug!("Dropping package from store v{}: {}", version, e);
+                        migration.dropped += 1;
+                    }
+                }
+            }
+
+            fs::remove_dir_all(&old).map_err(CacheError::WriteError)?;
+            migration.versions.push(version);
+        }
+        Ok(migration)
+    }
+
+    /// Move the files of one package from `old_files` into this store and
+    /// save its metadata here, recording each move in `moved`.
+    fn migrate_package(
+        &self,
+        old_files: &Path,
+        mut metadata: PackageMetadata,
+        moved: &mut HashMap<String, String>,
+    ) -> Result<(), CacheError> {
+        for hash in metadata.files.values_mut() {
+            if let Some(current) = moved.get(&*hash) {
+                *hash = current.clone();
+                continue;
+            }
+            let Some(prefix) = hash.get(..2) else {
+                return Err(CacheError::PackageNotFound(hash.clone()));
+            };
+            let old = old_files.join(prefix).join(&*hash);
+            // Hash again in case the format changed how content is addressed
+            let current = crate::blob_key(&Self::hash_file(&old)?, hash.ends_with(".x"));
+            let dest = self.content_path(&current);
+            if !dest.exists() {
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent).map_err(CacheError::CreateDirError)?;
+                }
+                if fs::rename(&old, &dest).is_err() {
+                    fs::copy(&old, &dest).map_err(CacheError::WriteError)?;
+                }
+            }
+            moved.insert(hash.clone(), current.clone());
+            *hash = current;
+        }
+        metadata.save(self)
+    }
+}
+
+/// Metadata of every package in an old store's `packages` directory,
+/// skipping any that can't be read.
+fn old_packages(dir: &Path) -> Vec<PackageMetadata> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join("metadata.json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| serde_json::from_str(&fs::read_to_string(path).ok()?).ok())
+        .collect()
+}
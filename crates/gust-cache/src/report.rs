@@ -0,0 +1,129 @@
+//! Size and age reports for cache directories.
+//!
+//! Each top-level entry of a cache directory (a cached repo, artifact or
+//! manifest) counts once, with the total size of the files under it and
+//! its modification time as its age. `gust cache stats --json` prints one
+//! report per cache so tooling can watch growth and decide when to clean.
+
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Entry count, size and ages of one cache directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheReport {
+    /// The cache directory
+    pub location: PathBuf,
+    /// Number of entries
+    pub entries: usize,
+    /// Size of every file under the entries, in bytes
+    pub total_bytes: u64,
+    /// Modification time of the oldest entry, in seconds since the Unix
+    /// epoch
+    pub oldest: Option<u64>,
+    /// Modification time of the newest entry, in seconds since the Unix
+    /// epoch
+    pub newest: Option<u64>,
+    /// How many entries fall in each age bracket
+    pub age_histogram: AgeHistogram,
+}
+
+/// Entry counts by time since last modified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct AgeHistogram {
+    /// Modified within the last day
+    pub under_1_day: usize,
+    /// Between a day and a week old
+    pub under_1_week: usize,
+    /// Between a week and 30 days old
+    pub under_30_days: usize,
+    /// 30 days old or more
+    pub older: usize,
+}
+
+impl AgeHistogram {
+    /// Count an entry of the given age.
+    pub fn record(&mut self, age: Duration) {
+        if age < DAY {
+            self.under_1_day += 1;
+        } else if age < DAY * 7 {
+            self.under_1_week += 1;
+        } else if age < DAY * 30 {
+            self.under_30_days += 1;
+        } else {
+            self.older += 1;
+        }
+    }
+}
+
+impl CacheReport {
+    /// Count the entries of `other` in this report too.
+    pub fn add(&mut self, other: &CacheReport) {
+        self.entries += other.entries;
+        self.total_bytes += other.total_bytes;
+        self.oldest = self.oldest.into_iter().chain(other.oldest).min();
+        self.newest = self.newest.into_iter().chain(other.newest).max();
+        let (ours, theirs) = (&mut self.age_histogram, other.age_histogram);
+        ours.under_1_day += theirs.under_1_day;
+        ours.under_1_week += theirs.under_1_week;
+        ours.under_30_days += theirs.under_30_days;
+        ours.older += theirs.older;
+    }
+
+    /// Report on the entries of `dir` that `include` accepts. A missing
+    /// directory is an empty cache.
+    pub fn scan(dir: &Path, include: &dyn Fn(&Path) -> bool) -> io::Result<Self> {
+        let now = SystemTime::now();
+        let mut report = Self {
+            location: dir.to_path_buf(),
+            ..Default::default()
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(report),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if !include(&path) {
+                continue;
+            }
+            let metadata = fs::symlink_metadata(&path)?;
+            report.entries += 1;
+            report.total_bytes += if metadata.is_dir() {
+                dir_size(&path)?
+            } else {
+                metadata.len()
+            };
+
+            let modified = metadata.modified()?;
+            let secs = modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            report.oldest = Some(report.oldest.map_or(secs, |t| t.min(secs)));
+            report.newest = Some(report.newest.map_or(secs, |t| t.max(secs)));
+            report
+                .age_histogram
+                .record(now.duration_since(modified).unwrap_or_default());
+        }
+        Ok(report)
+    }
+}
+
+/// Total size of the files under `dir`, not following symlinks.
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry.map_err(io::Error::other)?;
+        if entry.file_type().is_file() {
+            size += entry.metadata().map_err(io::Error::other)?.len();
+        }
+    }
+    Ok(size)
+}
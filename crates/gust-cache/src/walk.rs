@@ -0,0 +1,130 @@
+//! Collecting the files of a directory tree that may contain symlinks.
+//!
+//! Checkouts and projects can symlink directories into each other, so a
+//! naive walk may visit shared content twice or never finish on a cycle.
+//! Each real directory is visited at most once, in name order, which keeps
+//! hashes over the result deterministic.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What a walk does with symlinks to directories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Descend into them, unless the directory they resolve to was already
+    /// visited
+    #[default]
+    Follow,
+    /// Leave them out
+    Skip,
+}
+
+/// Files under `root` as `(relative path, full path)`, with `/` separating
+/// the components of the relative path.
+///
+/// Entries whose name `skip` returns true for are left out, and skipped
+/// directories aren't descended into. Symlinks to files are included;
+/// dangling symlinks aren't. A missing `root` has no files.
+pub fn collect_files(
+    root: &Path,
+    policy: SymlinkPolicy,
+    skip: &dyn Fn(&str) -> bool,
+) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    if !root.is_dir() {
+        return Ok(files);
+    }
+    let mut visited = HashSet::new();
+    visited.insert(fs::canonicalize(root)?);
+    walk(root, "", policy, skip, &mut visited, &mut files)?;
+    Ok(files)
+}
+
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    policy: SymlinkPolicy,
+    skip: &dyn Fn(&str) -> bool,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<(String, PathBuf)>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if skip(&name) {
+            continue;
+        }
+        let path = entry.path();
+        let key = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+
+        let file_type = entry.file_type()?;
+        let is_dir = if file_type.is_symlink() {
+            match fs::metadata(&path) {
+                Ok(target) if target.is_dir() => {
+                    if policy == SymlinkPolicy::Skip {
+                        continue;
+                    }
+                    true
+                }
+                Ok(_) => false,
+                // Dangling
+                Err(_) => continue,
+            }
+        } else {
+            file_type.is_dir()
+        };
+
+        if is_dir {
+            if visited.insert(fs::canonicalize(&path)?) {
+                walk(&path, &key, policy, skip, visited, files)?;
+            }
+        } else {
+            files.push((key, path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_cycles_and_shared_directories_visited_once() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().join("pkg");
+        fs::create_dir_all(root.join("Sources/Core")).unwrap();
+        fs::write(root.join("Sources/Core/Core.swift"), "").unwrap();
+        fs::create_dir_all(tmp.path().join("shared")).unwrap();
+        fs::write(tmp.path().join("shared/Util.swift"), "").unwrap();
+
+        // A cycle back to the root, the same directory linked twice, and a
+        // dangling link
+        symlink(&root, root.join("Sources/Core/loop")).unwrap();
+        symlink(tmp.path().join("shared"), root.join("Sources/A")).unwrap();
+        symlink(tmp.path().join("shared"), root.join("Sources/B")).unwrap();
+        symlink(tmp.path().join("missing"), root.join("Sources/gone")).unwrap();
+
+        let keys = |policy| {
+            collect_files(&root, policy, &|_| false)
+                .unwrap()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(SymlinkPolicy::Follow),
+            vec!["Sources/A/Util.swift", "Sources/Core/Core.swift"]
+        );
+        assert_eq!(keys(SymlinkPolicy::Skip), vec!["Sources/Core/Core.swift"]);
+    }
+}
@@ -35,6 +35,13 @@ pub enum GustError {
     )]
     PackageNotFound { name: String, suggestion: String },
 
+    #[error("Target not found: {name}")]
+    #[diagnostic(
+        code(gust::build::target_not_found),
+        help("Did you mean '{suggestion}'?")
+    )]
+    TargetNotFound { name: String, suggestion: String },
+
     #[error("Version conflict for {package}")]
     #[diagnostic(code(gust::resolve::version_conflict), help("{help}"))]
     VersionConflict {
@@ -66,7 +73,7 @@ pub enum GustError {
         available: String,
     },
 
-    #[error("Dependency cycle detected")]
+    #[error("Dependency cycle detected: {}", cycle.join(" -> "))]
     #[diagnostic(
         code(gust::resolve::cycle),
         help("Cycles are not allowed in Swift package dependencies")
@@ -90,6 +97,22 @@ pub enum GustError {
         span: Option<miette::SourceSpan>,
     },
 
+    #[error("System library '{library}' for target '{target}' was not found")]
+    #[diagnostic(code(gust::build::system_library_missing), help("{help}"))]
+    SystemLibraryMissing {
+        library: String,
+        target: String,
+        help: String,
+    },
+
+    #[error("Target '{target}' depends on unknown '{dependency}'")]
+    #[diagnostic(code(gust::manifest::unknown_target_dependency), help("{help}"))]
+    UnknownTargetDependency {
+        target: String,
+        dependency: String,
+        help: String,
+    },
+
     #[error("Swift toolchain not found")]
     #[diagnostic(
         code(gust::platform::no_toolchain),
@@ -129,6 +152,37 @@ impl GustError {
         }
     }
 
+    pub fn target_not_found(name: impl Into<String>, suggestion: impl Into<String>) -> Self {
+        Self::TargetNotFound {
+            name: name.into(),
+            suggestion: suggestion.into(),
+        }
+    }
+
+    pub fn system_library_missing(
+        library: impl Into<String>,
+        target: impl Into<String>,
+        help: impl Into<String>,
+    ) -> Self {
+        Self::SystemLibraryMissing {
+            library: library.into(),
+            target: target.into(),
+            help: help.into(),
+        }
+    }
+
+    pub fn unknown_target_dependency(
+        target: impl Into<String>,
+        dependency: impl Into<String>,
+        help: impl Into<String>,
+    ) -> Self {
+        Self::UnknownTargetDependency {
+            target: target.into(),
+            dependency: dependency.into(),
+            help: help.into(),
+        }
+    }
+
     pub fn cache(message: impl Into<String>) -> Self {
         Self::CacheError {
             message: message.into(),
@@ -181,17 +235,98 @@ impl GustError {
     }
 }
 
+/// Find the candidate closest to `name` by edit distance.
+///
+/// Returns `None` when nothing is close enough to be a plausible typo.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let lower = name.to_lowercase();
+
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(&lower, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
 /// Setup miette for pretty error output.
-pub fn setup() {
-    miette::set_hook(Box::new(|_| {
-        Box::new(
-            miette::MietteHandlerOpts::new()
-                .terminal_links(true)
-                .unicode(true)
-                .context_lines(2)
-                .tab_width(4)
-                .build(),
-        )
-    }))
-    .ok();
+///
+/// With `color` of `None`, color follows terminal support. `Some(false)`
+/// gives plain ASCII reports without ANSI colors or terminal links, so piped
+/// output and logs stay clean; `Some(true)` colors them even when stderr
+/// isn't a terminal.
+pub fn setup(color: Option<bool>) {
+    miette::set_hook(Box::new(move |_| Box::new(handler_opts(color).build()))).ok();
+}
+
+fn handler_opts(color: Option<bool>) -> miette::MietteHandlerOpts {
+    let enabled = color != Some(false);
+    let opts = miette::MietteHandlerOpts::new()
+        .terminal_links(enabled)
+        .unicode(enabled)
+        .context_lines(2)
+        .tab_width(4);
+    match color {
+        Some(true) => opts.color(true).force_graphical(true),
+        Some(false) => opts.color(false),
+        None => opts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders a report through a handler built from `handler_opts`.
+    struct Render<'a>(&'a dyn Diagnostic, Box<dyn miette::ReportHandler>);
+
+    impl std::fmt::Debug for Render<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            self.1.debug(self.0, f)
+        }
+    }
+
+    #[test]
+    fn test_no_color_reports_have_no_escapes() {
+        let err = GustError::target_not_found("MyAp", "MyApp");
+
+        let colored = handler_opts(Some(true)).build();
+        assert!(format!("{:?}", Render(&err, Box::new(colored))).contains('\x1b'));
+
+        let plain = handler_opts(Some(false)).force_graphical(true).build();
+        let output = format!("{:?}", Render(&err, Box::new(plain)));
+        assert!(output.contains("Did you mean 'MyApp'?"));
+        assert!(!output.contains('\x1b'));
+        assert!(output.is_ascii());
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let targets = ["MyApp", "MyAppCore", "Networking"];
+
+        assert_eq!(closest_match("MyAp", targets), Some("MyApp"));
+        assert_eq!(closest_match("networking", targets), Some("Networking"));
+        assert_eq!(closest_match("Database", targets), None);
+    }
 }
@@ -0,0 +1,209 @@
+//! Binary target downloads.
+//!
+//! Remote binary targets point at a `.xcframework.zip` and declare its
+//! SHA-256. The archive is streamed to disk while hashing, verified, and
+//! extracted into a directory named after the checksum so verified artifacts
+//! are shared between projects.
+
+use crate::{with_timeout, FetchError, Fetcher};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A downloaded and verified binary artifact.
+#[derive(Debug)]
+pub struct BinaryArtifact {
+    /// Binary target name
+    pub name: String,
+    /// Directory the archive was extracted into
+    pub path: PathBuf,
+    /// SHA-256 checksum of the archive
+    pub checksum: String,
+}
+
+impl Fetcher {
+    /// Download a binary target's archive, verify it and extract it.
+    ///
+    /// The archive lands in `cache_dir/<checksum>`; if that directory already
+    /// exists the download is skipped. A checksum mismatch is always an error,
+    /// and so is a checksum that isn't 64 hex digits, since it names a
+    /// directory.
+    pub async fn fetch_artifact(
+        &self,
+        name: &str,
+        url: &str,
+        checksum: &str,
+        cache_dir: &Path,
+    ) -> Result<BinaryArtifact, FetchError> {
+        let expected = checksum.to_lowercase();
+        if expected.len() != 64 || !expected.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(FetchError::InvalidChecksum {
+                package: name.to_string(),
+                checksum: checksum.to_string(),
+            });
+        }
+        let dest = cache_dir.join(&expected);
+
+        if !dest.is_dir() {
+            let staging = cache_dir.join(format!("{}.partial", expected));
+            let fetch = download_and_extract(name, url, &expected, &staging, &dest);
+            with_timeout(fetch, name, &staging, self.timeout).await?;
+        }
+
+        Ok(BinaryArtifact {
+            name: name.to_string(),
+            path: dest,
+            checksum: expected,
+        })
+    }
+}
+
+async fn download_and_extract(
+    name: &str,
+    url: &str,
+    expected: &str,
+    staging: &Path,
+    dest: &Path,
+) -> Result<(), FetchError> {
+    if staging.exists() {
+        tokio::fs::remove_dir_all(staging).await?;
+    }
+    tokio::fs::create_dir_all(staging).await?;
+
+    tracing::info!("Downloading binary target {} from {}", name, url);
+
+    let archive = staging.join("artifact.zip");
+    let actual = download(url, &archive).await?;
+    if actual != expected {
+        let _ = tokio::fs::remove_dir_all(staging).await;
+        return Err(FetchError::ChecksumMismatch {
+            package: name.to_string(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+
+    let contents = staging.join("contents");
+    let extract_to = contents.clone();
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&archive)?;
+        zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(&extract_to))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    })
+    .await
+    .map_err(|e| FetchError::FetchFailed {
+        package: name.to_string(),
+        message: format!("Task join error: {}", e),
+    })?
+    .map_err(|e| FetchError::FetchFailed {
+        package: name.to_string(),
+        message: format!("Failed to extract archive: {}", e),
+    })?;
+
+    tokio::fs::rename(&contents, dest).await?;
+    tokio::fs::remove_dir_all(staging).await?;
+    Ok(())
+}
+
+/// Stream `url` to `dest`, returning the hex SHA-256 of the bytes written.
+///
+/// `file://` URLs are read from disk.
+async fn download(url: &str, dest: &Path) -> Result<String, FetchError> {
+    let mut hasher = Sha256::new();
+    let mut out = tokio::fs::File::create(dest).await?;
+
+    if let Some(path) = url.strip_prefix("file://") {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut buffer = vec![0u8; 65536];
+        loop {
+            let n = file.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            out.write_all(&buffer[..n]).await?;
+        }
+    } else {
+        let response = reqwest::get(url).await?.error_for_status()?;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            out.write_all(&chunk).await?;
+        }
+    }
+
+    out.flush().await?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fetch_artifact_verifies_checksum() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let archive = tmp.path().join("Sdk.xcframework.zip");
+        let checksum = gust_testkit::write_xcframework_zip(&archive);
+        let url = format!("file://{}", archive.display());
+        let cache_dir = tmp.path().join("artifacts");
+        let fetcher = Fetcher::new();
+
+        // Wrong checksum is a hard error and leaves nothing behind
+        let wrong = "0".repeat(64);
+        let err = fetcher
+            .fetch_artifact("Sdk", &url, &wrong, &cache_dir)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, FetchError::ChecksumMismatch { ref actual, .. } if *actual == checksum)
+        );
+        assert!(!cache_dir.join(&wrong).exists());
+        assert!(!cache_dir.join(format!("{}.partial", wrong)).exists());
+
+        let artifact = fetcher
+            .fetch_artifact("Sdk", &url, &checksum.to_uppercase(), &cache_dir)
+            .await
+            .unwrap();
+        assert_eq!(artifact.checksum, checksum);
+        assert_eq!(artifact.path, cache_dir.join(&checksum));
+        assert!(artifact.path.join("Sdk.xcframework/Info.plist").is_file());
+
+        // Verified artifacts are reused without reading the URL again
+        std::fs::remove_file(&archive).unwrap();
+        let cached = fetcher
+            .fetch_artifact("Sdk", &url, &checksum, &cache_dir)
+            .await
+            .unwrap();
+        assert_eq!(cached.path, artifact.path);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_artifact_rejects_checksums_that_are_not_hashes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache").join("artifacts");
+        let fetcher = Fetcher::new();
+
+        let escape = format!("../../{}", "a".repeat(58));
+        for checksum in [escape.as_str(), "abc", &"g".repeat(64)] {
+            let err = fetcher
+                .fetch_artifact("Sdk", "file:///nonexistent.zip", checksum, &cache_dir)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, FetchError::InvalidChecksum { .. }),
+                "{}: {:?}",
+                checksum,
+                err
+            );
+        }
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+}
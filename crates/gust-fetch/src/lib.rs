@@ -2,17 +2,48 @@
 
 #![allow(clippy::ptr_arg)]
 
+mod artifact;
+
+pub use artifact::BinaryArtifact;
+
 use gust_types::Dependency;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::Semaphore;
 
+/// Default time allowed for fetching a single package.
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Extra attempts for git operations that failed with a transient error.
+const GIT_RETRIES: u32 = 2;
+
+/// Delay before the first retry; doubled for each further attempt.
+const GIT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 #[derive(Error, Debug)]
 pub enum FetchError {
     #[error("Failed to fetch {package}: {message}")]
     FetchFailed { package: String, message: String },
+    #[error("Timed out fetching {package} after {}s", timeout.as_secs())]
+    Timeout { package: String, timeout: Duration },
+    #[error("Checksum mismatch for {package}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        package: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Invalid checksum for {package}: '{checksum}' is not a SHA-256 in hex")]
+    InvalidChecksum { package: String, checksum: String },
+    #[error("Authentication required for {url}: {message}")]
+    AuthRequired { url: String, message: String },
+    #[error("Repository not found: {url}: {message}")]
+    NotFound { url: String, message: String },
+    #[error("Network error reaching {url}: {message}")]
+    Transient { url: String, message: String },
     #[error("Git error: {0}")]
     GitError(String),
     #[error("Network error: {0}")]
@@ -21,6 +52,89 @@ pub enum FetchError {
     IoError(#[from] std::io::Error),
 }
 
+impl FetchError {
+    /// Whether retrying the same operation might succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, FetchError::Transient { .. })
+    }
+}
+
+/// Classify a failed git command by its stderr.
+///
+/// Auth and not-found failures are permanent; connection problems are
+/// transient. Anything unrecognised falls back to [`FetchError::GitError`].
+pub fn classify_git_error(url: &str, command: &str, stderr: &str) -> FetchError {
+    const AUTH: &[&str] = &[
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "permission denied (publickey",
+        "invalid username or password",
+        "requested url returned error: 401",
+        "requested url returned error: 403",
+    ];
+    const NOT_FOUND: &[&str] = &[
+        "repository not found",
+        "does not appear to be a git repository",
+        "requested url returned error: 404",
+        "not found in upstream origin",
+    ];
+    const TRANSIENT: &[&str] = &[
+        "could not resolve host",
+        "timed out",
+        "connection refused",
+        "connection reset",
+        "failed to connect",
+        "network is unreachable",
+        "temporary failure in name resolution",
+        "the remote end hung up unexpectedly",
+        "early eof",
+        "rpc failed",
+        "requested url returned error: 429",
+        "requested url returned error: 5",
+    ];
+
+    let lower = stderr.to_lowercase();
+    let message = stderr.trim().to_string();
+    let url = url.to_string();
+    let matches = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+
+    if matches(AUTH) {
+        FetchError::AuthRequired { url, message }
+    } else if matches(NOT_FOUND) {
+        FetchError::NotFound { url, message }
+    } else if matches(TRANSIENT) {
+        FetchError::Transient { url, message }
+    } else {
+        FetchError::GitError(format!("{} failed: {}", command, message))
+    }
+}
+
+/// Run `op`, retrying with exponential backoff while it fails transiently.
+async fn retry_transient<T, F, Fut>(
+    retries: u32,
+    delay: Duration,
+    mut op: F,
+) -> Result<T, FetchError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, FetchError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if e.is_transient() && attempt < retries => {
+                let wait = delay * 2u32.pow(attempt);
+                tracing::warn!("{}; retrying in {:?}", e, wait);
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
 /// Result of fetching a package.
 #[derive(Debug)]
 pub struct FetchResult {
@@ -34,6 +148,17 @@ pub struct FetchResult {
     pub revision: Option<String>,
     /// Git tag used (if cloned with tag)
     pub tag: Option<String>,
+    /// Bytes downloaded, if known
+    pub bytes: Option<u64>,
+    /// Wall-clock time the fetch took
+    pub duration: Duration,
+}
+
+impl FetchResult {
+    fn timed(mut self, start: std::time::Instant) -> Self {
+        self.duration = start.elapsed();
+        self
+    }
 }
 
 /// Status updates during fetch operations.
@@ -41,16 +166,24 @@ pub struct FetchResult {
 pub enum FetchStatus {
     /// Fetch has started
     Started,
+    /// Bytes received so far, and the expected total if known
+    Progress { bytes: u64, total: Option<u64> },
     /// Fetch completed successfully
     Completed,
     /// Fetch failed with error message
     Failed(String),
+    /// Fetch was aborted after exceeding the timeout
+    TimedOut,
 }
 
 /// Fetch packages in parallel.
 pub struct Fetcher {
     /// Number of concurrent downloads
     concurrency: usize,
+    /// Time allowed for each package fetch
+    timeout: Duration,
+    /// Copy path dependencies instead of symlinking them
+    copy_paths: bool,
 }
 
 impl Default for Fetcher {
@@ -61,12 +194,13 @@ impl Default for Fetcher {
 
 impl Fetcher {
     pub fn new() -> Self {
-        // Use available parallelism (optimized for Apple Silicon's many cores)
-        // Falls back to 8 if detection fails
-        let concurrency = std::thread::available_parallelism()
-            .map(|n| n.get())
-            .unwrap_or(8);
-        Self { concurrency }
+        // One job per core (optimized for Apple Silicon's many cores),
+        // fewer when memory is tight
+        Self {
+            concurrency: gust_platform::Concurrency::detect(None).jobs,
+            timeout: DEFAULT_FETCH_TIMEOUT,
+            copy_paths: false,
+        }
     }
 
     pub fn with_concurrency(mut self, n: usize) -> Self {
@@ -74,13 +208,32 @@ impl Fetcher {
         self
     }
 
+    /// Set the time allowed for each package fetch.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Copy path dependencies into their destination instead of symlinking
+    /// them, so later edits to the source don't reach the checkout.
+    pub fn with_copy_paths(mut self, copy: bool) -> Self {
+        self.copy_paths = copy;
+        self
+    }
+
     /// Fetch a single dependency.
     pub async fn fetch(&self, dep: &Dependency, dest: &PathBuf) -> Result<FetchResult, FetchError> {
-        match dep.source_kind() {
-            gust_types::DependencySource::Git => self.fetch_git(dep, dest).await,
-            gust_types::DependencySource::Registry => self.fetch_registry(dep, dest).await,
-            gust_types::DependencySource::Path => self.fetch_path(dep, dest).await,
-        }
+        let fetch = async {
+            match dep.source_kind() {
+                gust_types::DependencySource::Git => self.fetch_git(dep, dest).await,
+                gust_types::DependencySource::Registry => self.fetch_registry(dep, dest).await,
+                gust_types::DependencySource::Path => self.fetch_path(dep, dest).await,
+            }
+        };
+
+        let start = std::time::Instant::now();
+        let result = with_timeout(fetch, &dep.name, dest, self.timeout).await;
+        result.map(|r| r.timed(start))
     }
 
     /// Fetch multiple dependencies in parallel.
@@ -100,6 +253,8 @@ impl Fetcher {
 
         let semaphore = Arc::new(Semaphore::new(self.concurrency));
         let on_progress = Arc::new(Mutex::new(on_progress));
+        let timeout = self.timeout;
+        let copy_paths = self.copy_paths;
 
         let tasks: Vec<_> = deps
             .into_iter()
@@ -117,23 +272,36 @@ impl Fetcher {
                         cb(&name, FetchStatus::Started);
                     }
 
-                    // Perform fetch
-                    let result = match dep.source_kind() {
-                        gust_types::DependencySource::Git => {
-                            Self::fetch_git_static(&dep, &dest).await
-                        }
-                        gust_types::DependencySource::Registry => {
-                            Self::fetch_registry_static(&dep, &dest).await
+                    let report = |bytes, total| {
+                        if let Ok(mut cb) = progress.lock() {
+                            cb(&name, FetchStatus::Progress { bytes, total });
                         }
-                        gust_types::DependencySource::Path => {
-                            Self::fetch_path_static(&dep, &dest).await
+                    };
+
+                    // Perform fetch
+                    let fetch = async {
+                        match dep.source_kind() {
+                            gust_types::DependencySource::Git => {
+                                Self::fetch_git_static(&dep, &dest, &report).await
+                            }
+                            gust_types::DependencySource::Registry => {
+                                Self::fetch_registry_static(&dep, &dest).await
+                            }
+                            gust_types::DependencySource::Path => {
+                                Self::fetch_path_static(&dep, &dest, copy_paths).await
+                            }
                         }
                     };
+                    let start = std::time::Instant::now();
+                    let result = with_timeout(fetch, &name, &dest, timeout)
+                        .await
+                        .map(|r| r.timed(start));
 
                     // Notify completion
                     if let Ok(mut cb) = progress.lock() {
                         match &result {
                             Ok(_) => cb(&name, FetchStatus::Completed),
+                            Err(FetchError::Timeout { .. }) => cb(&name, FetchStatus::TimedOut),
                             Err(e) => cb(&name, FetchStatus::Failed(e.to_string())),
                         }
                     }
@@ -149,7 +317,14 @@ impl Fetcher {
 
     /// Static version of fetch_git for use in spawned tasks.
     /// Uses git command for reliability with annotated tags.
-    async fn fetch_git_static(dep: &Dependency, dest: &PathBuf) -> Result<FetchResult, FetchError> {
+    ///
+    /// `on_bytes` is called with the bytes received as git reports them, and
+    /// once more with the final size.
+    async fn fetch_git_static(
+        dep: &Dependency,
+        dest: &PathBuf,
+        on_bytes: &(dyn Fn(u64, Option<u64>) + Send + Sync),
+    ) -> Result<FetchResult, FetchError> {
         let url = dep.git.as_ref().ok_or_else(|| FetchError::FetchFailed {
             package: dep.name.clone(),
             message: "No git URL".to_string(),
@@ -164,10 +339,29 @@ impl Fetcher {
         let tag = dep.tag.clone();
         let tag_for_result = dep.tag.clone();
         let name = dep.name.clone();
+        let lfs = dep.lfs;
 
-        // Use git command for better compatibility with annotated tags
-        let (revision, checksum) =
-            tokio::task::spawn_blocking(move || clone_with_git(&url, &dest_clone, branch, tag))
+        // Use git command for better compatibility with annotated tags,
+        // clearing any partial clone before a retry
+        let (revision, received) = retry_transient(GIT_RETRIES, GIT_RETRY_DELAY, || async {
+            if dest_clone.is_dir() && !dest_clone.is_symlink() {
+                std::fs::remove_dir_all(&dest_clone)?;
+            }
+            clone_with_git(&url, &dest_clone, branch.clone(), tag.clone(), on_bytes).await
+        })
+        .await?;
+
+        // Local and tiny clones don't report a size; fall back to what landed
+        // in the object store
+        let bytes = received.or_else(|| dir_size(&dest_clone.join(".git")).ok());
+        if let Some(bytes) = bytes {
+            on_bytes(bytes, Some(bytes));
+        }
+
+        pull_lfs(&name, &dest_clone, lfs).await?;
+
+        let checksum =
+            tokio::task::spawn_blocking(move || gust_cache::directory_checksum(&dest_clone))
                 .await
                 .map_err(|e| FetchError::GitError(format!("Task join error: {}", e)))??;
 
@@ -177,6 +371,8 @@ impl Fetcher {
             checksum,
             revision: Some(revision),
             tag: tag_for_result,
+            bytes,
+            duration: Duration::ZERO,
         })
     }
 
@@ -193,27 +389,48 @@ impl Fetcher {
     }
 
     /// Static version of fetch_path for use in spawned tasks.
+    ///
+    /// The source is symlinked at `dest`, or with `copy` copied there, and
+    /// the checksum covers what ends up at `dest`.
     async fn fetch_path_static(
         dep: &Dependency,
         dest: &PathBuf,
+        copy: bool,
     ) -> Result<FetchResult, FetchError> {
         let src = dep.path.as_ref().ok_or_else(|| FetchError::FetchFailed {
             package: dep.name.clone(),
             message: "No path specified".to_string(),
         })?;
 
-        // For path deps, we just symlink
-        if dest.exists() {
+        if dest.is_symlink() || dest.is_file() {
             std::fs::remove_file(dest)?;
+        } else if dest.is_dir() {
+            std::fs::remove_dir_all(dest)?;
+        }
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
         }
 
-        #[cfg(unix)]
-        std::os::unix::fs::symlink(src, dest)?;
+        let checksum = if copy {
+            let (src, dest) = (src.clone(), dest.clone());
+            tokio::task::spawn_blocking(move || {
+                copy_tree(&src, &dest)?;
+                Ok::<_, FetchError>(gust_cache::directory_checksum(&dest)?)
+            })
+            .await
+            .map_err(|e| FetchError::FetchFailed {
+                package: dep.name.clone(),
+                message: format!("Task join error: {}", e),
+            })??
+        } else {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(src, dest)?;
 
-        #[cfg(windows)]
-        std::os::windows::fs::symlink_dir(src, dest)?;
+            #[cfg(windows)]
+            std::os::windows::fs::symlink_dir(src, dest)?;
 
-        let checksum = compute_dir_hash(src)?;
+            gust_cache::directory_checksum(src)?
+        };
 
         Ok(FetchResult {
             name: dep.name.clone(),
@@ -221,12 +438,14 @@ impl Fetcher {
             checksum,
             revision: None,
             tag: None,
+            bytes: None,
+            duration: Duration::ZERO,
         })
     }
 
     async fn fetch_git(&self, dep: &Dependency, dest: &PathBuf) -> Result<FetchResult, FetchError> {
         // Delegate to static version which uses native gix
-        Self::fetch_git_static(dep, dest).await
+        Self::fetch_git_static(dep, dest, &|_, _| {}).await
     }
 
     async fn fetch_registry(
@@ -247,121 +466,118 @@ impl Fetcher {
         dep: &Dependency,
         dest: &PathBuf,
     ) -> Result<FetchResult, FetchError> {
-        let src = dep.path.as_ref().ok_or_else(|| FetchError::FetchFailed {
-            package: dep.name.clone(),
-            message: "No path specified".to_string(),
-        })?;
-
-        // For path deps, we just symlink
-        if dest.exists() {
-            std::fs::remove_file(dest)?;
-        }
-
-        #[cfg(unix)]
-        std::os::unix::fs::symlink(src, dest)?;
-
-        #[cfg(windows)]
-        std::os::windows::fs::symlink_dir(src, dest)?;
-
-        let checksum = compute_dir_hash(src)?;
-
-        Ok(FetchResult {
-            name: dep.name.clone(),
-            path: dest.clone(),
-            checksum,
-            revision: None,
-            tag: None,
-        })
+        Self::fetch_path_static(dep, dest, self.copy_paths).await
     }
 }
 
-fn compute_dir_hash(path: &Path) -> Result<String, FetchError> {
-    use rayon::prelude::*;
-    use std::collections::BTreeMap;
-    use std::fs;
-    use std::path::PathBuf;
-
-    // First, collect all file paths (single-threaded, fast)
-    let mut files: Vec<(String, PathBuf)> = Vec::new();
-
-    fn collect_files(
-        dir: &std::path::Path,
-        prefix: &str,
-        files: &mut Vec<(String, PathBuf)>,
-    ) -> Result<(), FetchError> {
-        if dir.is_dir() {
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                let name = path.file_name().unwrap().to_string_lossy();
-
-                // Skip .git directory
-                if name == ".git" {
-                    continue;
-                }
-
-                let key = if prefix.is_empty() {
-                    name.to_string()
-                } else {
-                    format!("{}/{}", prefix, name)
-                };
-
-                if path.is_dir() {
-                    collect_files(&path, &key, files)?;
-                } else {
-                    files.push((key, path));
-                }
+/// Run a fetch, giving up once `timeout` elapses.
+///
+/// Dropping the fetch future kills any git child it spawned (`kill_on_drop`),
+/// and the partial checkout is removed so it isn't mistaken for a cached one.
+async fn with_timeout<F, T>(
+    fetch: F,
+    name: &str,
+    dest: &Path,
+    timeout: Duration,
+) -> Result<T, FetchError>
+where
+    F: std::future::Future<Output = Result<T, FetchError>>,
+{
+    match tokio::time::timeout(timeout, fetch).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!("Fetching {} timed out after {:?}", name, timeout);
+            if dest.is_dir() && !dest.is_symlink() {
+                let _ = std::fs::remove_dir_all(dest);
             }
+            Err(FetchError::Timeout {
+                package: name.to_string(),
+                timeout,
+            })
         }
-        Ok(())
     }
+}
 
-    collect_files(path, "", &mut files)?;
-
-    // Parallel hash all files using rayon + mmap (Apple Silicon optimization)
-    // mmap provides zero-copy reads directly from the kernel page cache
-    let file_hashes: Result<BTreeMap<String, String>, FetchError> = files
-        .par_iter()
-        .map(|(key, path)| {
-            let file = fs::File::open(path)?;
-            let metadata = file.metadata()?;
-
-            // Use mmap for files > 4KB, regular read for small files
-            let hash = if metadata.len() > 4096 {
-                // SAFETY: We only read the file, and it's not modified during hashing
-                let mmap = unsafe { memmap2::Mmap::map(&file)? };
-                blake3::hash(&mmap).to_hex().to_string()
-            } else {
-                let content = fs::read(path)?;
-                blake3::hash(&content).to_hex().to_string()
-            };
+/// Copy the tree at `src` to `dest`, leaving out `.git` and `.build`.
+///
+/// Files are copied in parallel with `std::fs::copy`, which clones them on
+/// file systems that can (APFS, btrfs). The copy is assembled beside `dest`
+/// and renamed into place, so `dest` never holds half a tree.
+fn copy_tree(src: &Path, dest: &Path) -> Result<(), FetchError> {
+    use rayon::prelude::*;
 
-            Ok((key.clone(), hash))
-        })
-        .collect();
+    let files = gust_cache::collect_files(src, gust_cache::SymlinkPolicy::Follow, &|name| {
+        name == ".git" || name == ".build"
+    })?;
+    let mut staged = dest.as_os_str().to_owned();
+    staged.push(".partial");
+    let staged = PathBuf::from(staged);
+    if staged.exists() {
+        std::fs::remove_dir_all(&staged)?;
+    }
+    std::fs::create_dir_all(&staged)?;
 
-    let file_hashes = file_hashes?;
+    files.par_iter().try_for_each(|(key, path)| {
+        let target = staged.join(key);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(path, target).map(|_| ())
+    })?;
+    std::fs::rename(&staged, dest)?;
+    Ok(())
+}
 
-    // Hash all the file hashes together
-    let combined: String = file_hashes
-        .iter()
-        .map(|(k, v)| format!("{}:{}", k, v))
-        .collect::<Vec<_>>()
-        .join("\n");
+/// Total size of the files under `path`.
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
 
-    Ok(blake3::hash(combined.as_bytes()).to_hex().to_string())
+/// Bytes received so far from a `git clone --progress` line such as
+/// `Receiving objects:  45% (450/1000), 1.50 MiB | 1.00 MiB/s`.
+fn parse_git_received_bytes(line: &str) -> Option<u64> {
+    let rest = line.trim().strip_prefix("Receiving objects:")?;
+    let (_, size) = rest.split_once("), ")?;
+    let size = size.split(['|', ',']).next()?.trim();
+    let (amount, unit) = size.split_once(' ')?;
+    let amount: f64 = amount.parse().ok()?;
+    let scale = match unit {
+        "bytes" | "byte" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((amount * scale) as u64)
 }
 
 /// Clone a git repository using the git command.
 /// More reliable for annotated tags and complex scenarios.
-/// Returns (revision, checksum) on success.
-fn clone_with_git(
+/// Returns the checked-out revision and, if git reported it, the number of
+/// bytes received.
+///
+/// The git child is killed if the returned future is dropped, so a timed-out
+/// fetch doesn't leave an orphaned clone running.
+async fn clone_with_git(
     url: &str,
     dest: &std::path::Path,
     branch: Option<String>,
     tag: Option<String>,
-) -> Result<(String, String), FetchError> {
-    let mut args = vec!["clone", "--depth", "1"];
+    on_bytes: &(dyn Fn(u64, Option<u64>) + Send + Sync),
+) -> Result<(String, Option<u64>), FetchError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut args = vec!["clone", "--progress", "--depth", "1"];
 
     // Add branch or tag
     let ref_arg: String;
@@ -379,34 +595,151 @@ fn clone_with_git(
     let dest_str = dest.to_string_lossy();
     args.push(&dest_str);
 
-    let output = Command::new("git")
+    let mut child = tokio::process::Command::new("git")
         .args(&args)
-        .output()
+        // Fail instead of blocking on a credential prompt
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
         .map_err(|e| FetchError::GitError(format!("Failed to run git: {}", e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(FetchError::GitError(format!(
-            "git clone failed: {}",
-            stderr
-        )));
+    // Progress lines are separated by carriage returns, so split on those
+    // as well as newlines to see each update as it arrives
+    let mut stderr = Vec::new();
+    let mut received = None;
+    if let Some(mut pipe) = child.stderr.take() {
+        let mut buf = [0u8; 4096];
+        let mut line_start = 0;
+        loop {
+            let n = pipe.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            stderr.extend_from_slice(&buf[..n]);
+            while let Some(end) = stderr[line_start..]
+                .iter()
+                .position(|&b| b == b'\r' || b == b'\n')
+            {
+                let line = String::from_utf8_lossy(&stderr[line_start..line_start + end]);
+                if let Some(bytes) = parse_git_received_bytes(&line) {
+                    received = Some(bytes);
+                    on_bytes(bytes, None);
+                }
+                line_start += end + 1;
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| FetchError::GitError(format!("Failed to run git: {}", e)))?;
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(classify_git_error(url, "git clone", &stderr));
     }
 
     // Get the HEAD revision
-    let rev_output = Command::new("git")
+    let rev_output = tokio::process::Command::new("git")
         .args(["rev-parse", "HEAD"])
         .current_dir(dest)
+        .kill_on_drop(true)
         .output()
+        .await
         .map_err(|e| FetchError::GitError(format!("Failed to get revision: {}", e)))?;
 
     let revision = String::from_utf8_lossy(&rev_output.stdout)
         .trim()
         .to_string();
 
-    // Compute checksum
-    let checksum = compute_dir_hash(dest)?;
+    Ok((revision, received))
+}
 
-    Ok((revision, checksum))
+/// The commit checked out in the git checkout at `dir`, read from
+/// `.git/HEAD` without running git.
+pub fn checkout_revision(dir: &Path) -> Option<String> {
+    let git_dir = dir.join(".git");
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let Some(reference) = head.strip_prefix("ref: ") else {
+        return Some(head.to_string());
+    };
+    if let Ok(revision) = std::fs::read_to_string(git_dir.join(reference)) {
+        return Some(revision.trim().to_string());
+    }
+    std::fs::read_to_string(git_dir.join("packed-refs"))
+        .ok()?
+        .lines()
+        .find_map(|line| {
+            let (revision, name) = line.split_once(' ')?;
+            (name == reference).then(|| revision.to_string())
+        })
+}
+
+/// Whether the `.gitattributes` at the root of `dir` sends any files
+/// through the Git LFS filter.
+pub fn uses_lfs(dir: &Path) -> bool {
+    !gust_cache::LfsPatterns::load(dir).is_empty()
+}
+
+/// Replace the LFS pointer files in the checkout at `dest` with their
+/// content, returning whether `git lfs pull` ran.
+///
+/// `lfs` is the dependency's setting: `Some(false)` skips the pull, and
+/// `None` pulls only when `.gitattributes` declares LFS files. Without
+/// git-lfs installed that's an error for `Some(true)`; a detected
+/// dependency keeps its pointers with a warning, since many packages only
+/// keep test fixtures or docs in LFS.
+async fn pull_lfs(name: &str, dest: &Path, lfs: Option<bool>) -> Result<bool, FetchError> {
+    if !lfs.unwrap_or_else(|| uses_lfs(dest)) {
+        return Ok(false);
+    }
+
+    let available = tokio::process::Command::new("git")
+        .args(["lfs", "version"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success());
+    if !available && lfs == Some(true) {
+        return Err(FetchError::FetchFailed {
+            package: name.to_string(),
+            message: "it sets `lfs = true`, but git-lfs is not installed".to_string(),
+        });
+    }
+    if !available {
+        tracing::warn!(
+            "{} stores files in Git LFS, but git-lfs is not installed; those files are \
+             left as pointers. Install git-lfs and reinstall {} to fetch them",
+            name,
+            name
+        );
+        return Ok(false);
+    }
+
+    tracing::info!("Pulling Git LFS files for {}", name);
+    let output = tokio::process::Command::new("git")
+        .args(["lfs", "pull"])
+        .current_dir(dest)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| FetchError::GitError(format!("Failed to run git lfs: {}", e)))?;
+    if !output.status.success() {
+        return Err(FetchError::FetchFailed {
+            package: name.to_string(),
+            message: format!(
+                "git lfs pull failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(true)
 }
 
 /// Clone a git repository using native gix library.
@@ -468,7 +801,7 @@ fn clone_with_gix(
     let revision = head.to_string();
 
     // Compute checksum
-    let checksum = compute_dir_hash(dest)?;
+    let checksum = gust_cache::directory_checksum(dest)?;
 
     Ok((revision, checksum))
 }
@@ -480,31 +813,118 @@ pub struct GitTag {
     pub name: String,
     /// Parsed semver version (if valid)
     pub version: Option<semver::Version>,
-    /// Commit SHA
+    /// Commit SHA; for an annotated tag, the commit it points to rather
+    /// than the tag object
     pub sha: String,
 }
 
+/// Prefixes some packages put before the version in their tags.
+const TAG_PREFIXES: &[&str] = &["release-", "release/", "swift-"];
+
+/// The version a git tag names, if it names one.
+///
+/// Besides plain semver (`1.5.0`, `1.5.0-beta.1`) this accepts a leading
+/// `v`, a `release-` or `swift-` prefix, two-component versions (`1.5` is
+/// `1.5.0`) and `_` before a prerelease (`1.5.0_beta` is `1.5.0-beta`).
+/// Tags that don't start with at least `major.minor` aren't versions.
+pub fn parse_tag_version(tag: &str) -> Option<semver::Version> {
+    let mut tag = tag.trim();
+    for prefix in TAG_PREFIXES {
+        if tag.len() > prefix.len()
+            && tag
+                .get(..prefix.len())
+                .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+        {
+            tag = &tag[prefix.len()..];
+            break;
+        }
+    }
+    let tag = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+
+    let end = tag.find(['-', '+', '_']).unwrap_or(tag.len());
+    let (core, rest) = tag.split_at(end);
+    let components = core.split('.').count();
+    let core = match components {
+        2 => format!("{}.0", core),
+        3 => core.to_string(),
+        _ => return None,
+    };
+    let rest = match rest.strip_prefix('_') {
+        Some(prerelease) => format!("-{}", prerelease),
+        None => rest.to_string(),
+    };
+    semver::Version::parse(&format!("{}{}", core, rest.replace('_', "-"))).ok()
+}
+
 /// Fetch available tags from a remote git repository.
 /// Uses `git ls-remote --tags` for efficiency (no clone needed).
 pub async fn list_remote_tags(url: &str) -> Result<Vec<GitTag>, FetchError> {
-    let url = url.to_string();
+    retry_transient(GIT_RETRIES, GIT_RETRY_DELAY, || {
+        ls_remote_tags(url.to_string())
+    })
+    .await
+}
 
+/// Fetch the commit a remote branch points at, or the remote `HEAD` when
+/// `branch` is `None`. Returns `None` if the ref doesn't exist.
+pub async fn remote_branch_head(
+    url: &str,
+    branch: Option<&str>,
+) -> Result<Option<String>, FetchError> {
+    let reference = match branch {
+        Some(branch) => format!("refs/heads/{}", branch),
+        None => "HEAD".to_string(),
+    };
+    retry_transient(GIT_RETRIES, GIT_RETRY_DELAY, || {
+        ls_remote_ref(url.to_string(), reference.clone())
+    })
+    .await
+}
+
+async fn ls_remote_ref(url: String, reference: String) -> Result<Option<String>, FetchError> {
     tokio::task::spawn_blocking(move || {
         let output = Command::new("git")
-            .args(["ls-remote", "--tags", "--refs", &url])
+            .args(["ls-remote", &url, &reference])
+            .env("GIT_TERMINAL_PROMPT", "0")
             .output()
             .map_err(|e| FetchError::GitError(format!("Failed to run git ls-remote: {}", e)))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(FetchError::GitError(format!(
-                "git ls-remote failed: {}",
-                stderr
-            )));
+            return Err(classify_git_error(&url, "git ls-remote", &stderr));
+        }
+
+        // Format: "<sha>\t<ref>"
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .find(|(_, name)| *name == reference)
+            .map(|(sha, _)| sha.to_string()))
+    })
+    .await
+    .map_err(|e| FetchError::GitError(format!("Task join error: {}", e)))?
+}
+
+async fn ls_remote_tags(url: String) -> Result<Vec<GitTag>, FetchError> {
+    tokio::task::spawn_blocking(move || {
+        let output = Command::new("git")
+            .args(["ls-remote", "--tags", &url])
+            // Fail instead of blocking on a credential prompt
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|e| FetchError::GitError(format!("Failed to run git ls-remote: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(classify_git_error(&url, "git ls-remote", &stderr));
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
         let mut tags = Vec::new();
+        // Annotated tags are also listed peeled, as "<tag>^{}" with the
+        // commit they point to
+        let mut peeled = HashMap::new();
 
         for line in stdout.lines() {
             // Format: "<sha>\trefs/tags/<tag>"
@@ -521,10 +941,12 @@ pub async fn list_remote_tags(url: &str) -> Result<Vec<GitTag>, FetchError> {
                 .strip_prefix("refs/tags/")
                 .unwrap_or(ref_name)
                 .to_string();
+            if let Some(tag_name) = tag_name.strip_suffix("^{}") {
+                peeled.insert(tag_name.to_string(), sha);
+                continue;
+            }
 
-            // Try to parse as semver (strip 'v' prefix if present)
-            let version_str = tag_name.strip_prefix('v').unwrap_or(&tag_name);
-            let version = semver::Version::parse(version_str).ok();
+            let version = parse_tag_version(&tag_name);
 
             tags.push(GitTag {
                 name: tag_name,
@@ -533,6 +955,12 @@ pub async fn list_remote_tags(url: &str) -> Result<Vec<GitTag>, FetchError> {
             });
         }
 
+        for tag in &mut tags {
+            if let Some(sha) = peeled.remove(&tag.name) {
+                tag.sha = sha;
+            }
+        }
+
         // Sort by version (newest first), putting non-semver tags at the end
         tags.sort_by(|a, b| match (&b.version, &a.version) {
             (Some(v1), Some(v2)) => v1.cmp(v2),
@@ -546,3 +974,351 @@ pub async fn list_remote_tags(url: &str) -> Result<Vec<GitTag>, FetchError> {
     .await
     .map_err(|e| FetchError::GitError(format!("Task join error: {}", e)))?
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_fetch_times_out_on_unresponsive_host() {
+        // A server that accepts connections but never responds stands in
+        // for an unreachable git host without depending on the network.
+        let server = gust_testkit::MockServer::silent().await;
+        let url = format!("{}/repo.git", server.url());
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dest = tmp.path().join("repo");
+        let dep = Dependency::git("repo", &url);
+
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&statuses);
+
+        let fetcher = Fetcher::new().with_timeout(Duration::from_millis(500));
+        let start = std::time::Instant::now();
+        let results = fetcher
+            .fetch_many(vec![(dep, dest.clone())], move |_, status| {
+                recorded.lock().unwrap().push(status);
+            })
+            .await;
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(matches!(
+            results.as_slice(),
+            [Err(FetchError::Timeout { package, .. })] if package == "repo"
+        ));
+        assert!(matches!(
+            statuses.lock().unwrap().as_slice(),
+            [FetchStatus::Started, FetchStatus::TimedOut]
+        ));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_classify_git_errors() {
+        let url = "https://github.com/example/repo.git";
+        let classify = |stderr: &str| classify_git_error(url, "git clone", stderr);
+
+        assert!(matches!(
+            classify("remote: Invalid username or password.\nfatal: Authentication failed for 'https://github.com/example/repo.git/'"),
+            FetchError::AuthRequired { .. }
+        ));
+        assert!(matches!(
+            classify("fatal: could not read Username for 'https://github.com': terminal prompts disabled"),
+            FetchError::AuthRequired { .. }
+        ));
+        assert!(matches!(
+            classify("git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository."),
+            FetchError::AuthRequired { .. }
+        ));
+
+        assert!(matches!(
+            classify("remote: Repository not found.\nfatal: repository 'https://github.com/example/repo.git/' not found"),
+            FetchError::NotFound { .. }
+        ));
+        assert!(matches!(
+            classify("warning: Could not find remote branch 9.9.9 to clone.\nfatal: Remote branch 9.9.9 not found in upstream origin"),
+            FetchError::NotFound { .. }
+        ));
+
+        assert!(matches!(
+            classify("fatal: unable to access 'https://github.com/example/repo.git/': Could not resolve host: github.com"),
+            FetchError::Transient { .. }
+        ));
+        assert!(matches!(
+            classify("fatal: unable to access 'https://github.com/example/repo.git/': Failed to connect to github.com port 443 after 75001 ms: Operation timed out"),
+            FetchError::Transient { .. }
+        ));
+        assert!(matches!(
+            classify("error: RPC failed; HTTP 502 curl 22 The requested URL returned error: 502\nfatal: expected flush after ref listing"),
+            FetchError::Transient { .. }
+        ));
+
+        let fallback = classify(
+            "fatal: destination path 'repo' already exists and is not an empty directory.",
+        );
+        assert!(
+            matches!(fallback, FetchError::GitError(ref m) if m.starts_with("git clone failed"))
+        );
+        assert!(!fallback.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_only_transient_errors_are_retried() {
+        let attempts = Mutex::new(0);
+        let result: Result<(), _> = retry_transient(2, Duration::from_millis(1), || async {
+            *attempts.lock().unwrap() += 1;
+            Err(FetchError::Transient {
+                url: "u".to_string(),
+                message: "Could not resolve host".to_string(),
+            })
+        })
+        .await;
+        assert!(result.unwrap_err().is_transient());
+        assert_eq!(*attempts.lock().unwrap(), 3);
+
+        let attempts = Mutex::new(0);
+        let result: Result<(), _> = retry_transient(2, Duration::from_millis(1), || async {
+            *attempts.lock().unwrap() += 1;
+            Err(FetchError::NotFound {
+                url: "u".to_string(),
+                message: "Repository not found".to_string(),
+            })
+        })
+        .await;
+        assert!(matches!(result, Err(FetchError::NotFound { .. })));
+        assert_eq!(*attempts.lock().unwrap(), 1);
+
+        // Recovers once the transient failure clears
+        let attempts = Mutex::new(0);
+        let result = retry_transient(2, Duration::from_millis(1), || async {
+            let mut n = attempts.lock().unwrap();
+            *n += 1;
+            if *n < 2 {
+                Err(FetchError::Transient {
+                    url: "u".to_string(),
+                    message: "early EOF".to_string(),
+                })
+            } else {
+                Ok(*n)
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_parse_git_received_bytes() {
+        assert_eq!(
+            parse_git_received_bytes("Receiving objects:  45% (450/1000), 1.50 MiB | 1.00 MiB/s"),
+            Some(1_572_864)
+        );
+        assert_eq!(
+            parse_git_received_bytes(
+                "Receiving objects: 100% (12/12), 512 bytes | 512.00 KiB/s, done."
+            ),
+            Some(512)
+        );
+        assert_eq!(
+            parse_git_received_bytes("Receiving objects: 100% (3/3), done."),
+            None
+        );
+        assert_eq!(
+            parse_git_received_bytes("Resolving deltas: 100% (2/2), done."),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_tag_version() {
+        let cases = [
+            ("1.5.0", Some("1.5.0")),
+            ("v1.5.0", Some("1.5.0")),
+            ("V2.0.0", Some("2.0.0")),
+            ("1.5", Some("1.5.0")),
+            ("v1.5", Some("1.5.0")),
+            ("release-1.5.0", Some("1.5.0")),
+            ("Release-v1.5", Some("1.5.0")),
+            ("release/2.1.0", Some("2.1.0")),
+            ("swift-5.9", Some("5.9.0")),
+            ("swift-5.9-RELEASE", Some("5.9.0-RELEASE")),
+            ("1.5.0_beta", Some("1.5.0-beta")),
+            ("1.5.0_beta_2", Some("1.5.0-beta-2")),
+            ("1.5.0-beta.1", Some("1.5.0-beta.1")),
+            ("1.5-rc.1", Some("1.5.0-rc.1")),
+            ("1.5.0+build.7", Some("1.5.0+build.7")),
+            (" 1.5.0\n", Some("1.5.0")),
+            ("latest", None),
+            ("nightly-2024-01-01", None),
+            ("release-", None),
+            ("v", None),
+            ("1", None),
+            ("2024", None),
+            ("1.2.3.4", None),
+            ("1.x", None),
+            ("1.5.0_", None),
+            ("swifté1.0", None),
+            ("releasé-1.0", None),
+            ("", None),
+        ];
+        for (tag, expected) in cases {
+            assert_eq!(
+                parse_tag_version(tag).map(|v| v.to_string()).as_deref(),
+                expected,
+                "tag {:?}",
+                tag
+            );
+        }
+    }
+
+    #[test]
+    fn test_checkout_revision_reads_head() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        let head = gust_testkit::git_repo(&repo);
+        let git = |args: &[&str]| gust_testkit::git(&repo, args);
+
+        // On a branch, then with the branch packed, then detached
+        assert_eq!(checkout_revision(&repo).as_deref(), Some(head.as_str()));
+        git(&["pack-refs", "--all"]);
+        assert_eq!(checkout_revision(&repo).as_deref(), Some(head.as_str()));
+        git(&["checkout", "-q", "--detach"]);
+        assert_eq!(checkout_revision(&repo).as_deref(), Some(head.as_str()));
+
+        assert_eq!(checkout_revision(tmp.path()), None);
+    }
+
+    #[tokio::test]
+    async fn test_annotated_tags_resolve_to_commits() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        let first = gust_testkit::git_repo(&repo);
+        let git = |args: &[&str]| gust_testkit::git(&repo, args);
+        git(&["tag", "-a", "v1.0.0", "-m", "Release 1.0.0"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "second"]);
+        let second = git(&["rev-parse", "HEAD"]);
+        git(&["tag", "1.1.0"]);
+        assert_ne!(git(&["rev-parse", "v1.0.0"]), first);
+
+        let tags = list_remote_tags(&format!("file://{}", repo.display()))
+            .await
+            .unwrap();
+        let found: Vec<(&str, &str)> = tags
+            .iter()
+            .map(|t| (t.name.as_str(), t.sha.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("1.1.0", second.as_str()), ("v1.0.0", first.as_str())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lfs_is_detected_from_gitattributes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        std::fs::create_dir_all(&repo).unwrap();
+        assert!(!uses_lfs(&repo));
+
+        std::fs::write(
+            repo.join(".gitattributes"),
+            "# *.zip filter=lfs\n*.swift text\n",
+        )
+        .unwrap();
+        assert!(!uses_lfs(&repo));
+        std::fs::write(
+            repo.join(".gitattributes"),
+            "*.swift text\nFixtures/*.bin filter=lfs diff=lfs merge=lfs -text\n",
+        )
+        .unwrap();
+        assert!(uses_lfs(&repo));
+
+        // Turned off, nothing runs even though LFS files are declared
+        assert!(!pull_lfs("dep", &repo, Some(false)).await.unwrap());
+
+        // Auto-detected, a missing git-lfs only warns
+        let available = Command::new("git")
+            .args(["lfs", "version"])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !available {
+            assert!(!pull_lfs("dep", &repo, None).await.unwrap());
+
+            // Asked for explicitly, it's an error
+            let err = pull_lfs("dep", &repo, Some(true)).await.unwrap_err();
+            assert!(err.to_string().contains("git-lfs is not installed"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_reports_bytes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join("README.md"), "hello").unwrap();
+        gust_testkit::git_repo(&repo);
+
+        let dep = Dependency::git("dep", format!("file://{}", repo.display()));
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&statuses);
+
+        let results = Fetcher::new()
+            .fetch_many(vec![(dep, tmp.path().join("out"))], move |_, status| {
+                recorded.lock().unwrap().push(status);
+            })
+            .await;
+
+        let bytes = results[0].as_ref().unwrap().bytes.unwrap();
+        assert!(bytes > 0);
+
+        let statuses = statuses.lock().unwrap();
+        assert!(matches!(statuses.first(), Some(FetchStatus::Started)));
+        assert!(matches!(statuses.last(), Some(FetchStatus::Completed)));
+        // The final size is reported before completion
+        assert!(matches!(
+            statuses[statuses.len() - 2],
+            FetchStatus::Progress { bytes: b, total: Some(t) } if b == bytes && t == bytes
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_copied_path_dependency_is_a_snapshot() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let src = tmp.path().join("lib");
+        std::fs::create_dir_all(src.join("Sources/Lib")).unwrap();
+        std::fs::create_dir_all(src.join(".build/debug")).unwrap();
+        std::fs::write(src.join("Sources/Lib/Lib.swift"), "let a = 1").unwrap();
+        std::fs::write(src.join(".build/debug/big.o"), "object").unwrap();
+
+        let dep = Dependency::path("lib", &src);
+        let dest = tmp.path().join("cache/lib");
+        std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        let copied = Fetcher::new()
+            .with_copy_paths(true)
+            .fetch(&dep, &dest)
+            .await
+            .unwrap();
+        assert!(!dest.is_symlink());
+        assert!(!dest.join(".build").exists());
+        assert_eq!(
+            copied.checksum,
+            gust_cache::directory_checksum(&dest).unwrap()
+        );
+
+        // Editing the source leaves the copy as it was
+        std::fs::write(src.join("Sources/Lib/Lib.swift"), "let a = 2").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("Sources/Lib/Lib.swift")).unwrap(),
+            "let a = 1"
+        );
+        assert_eq!(
+            copied.checksum,
+            gust_cache::directory_checksum(&dest).unwrap()
+        );
+
+        // Symlinking again replaces the copy and follows the source
+        let linked = Fetcher::new().fetch(&dep, &dest).await.unwrap();
+        assert!(dest.is_symlink());
+        assert_ne!(linked.checksum, copied.checksum);
+    }
+}
@@ -0,0 +1,249 @@
+//! Manifest-vs-lockfile drift detection.
+//!
+//! Compares the root manifest's direct dependencies with a lockfile without
+//! touching the network or disk, so `install --locked`, `lock --check` and
+//! `doctor` can all tell whether the lockfile still matches the manifest.
+
+use crate::Lockfile;
+use gust_types::{Dependency, Manifest, Version};
+use std::collections::HashSet;
+use std::fmt;
+
+/// How a lockfile has drifted from its manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DriftReport {
+    /// Direct dependencies with no lockfile entry
+    pub missing: Vec<String>,
+    /// Root lockfile entries the manifest no longer depends on
+    pub stale: Vec<String>,
+    /// Direct dependencies whose constraint rejects the locked version
+    pub unsatisfied: Vec<UnsatisfiedDependency>,
+}
+
+/// A direct dependency whose locked version no longer fits the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsatisfiedDependency {
+    /// Dependency name
+    pub name: String,
+    /// The manifest's constraint, e.g. `^2.0` or `tag 1.2.0`
+    pub requirement: String,
+    /// Version recorded in the lockfile
+    pub locked: Version,
+}
+
+impl fmt::Display for UnsatisfiedDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is locked at {} but the manifest requires {}",
+            self.name, self.locked, self.requirement
+        )
+    }
+}
+
+impl DriftReport {
+    /// Check whether the lockfile matches the manifest.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty() && self.unsatisfied.is_empty()
+    }
+
+    /// One line per problem, for display.
+    pub fn messages(&self) -> Vec<String> {
+        let mut messages = Vec::new();
+        for name in &self.missing {
+            messages.push(format!("{} is in the manifest but not locked", name));
+        }
+        for name in &self.stale {
+            messages.push(format!("{} is locked but no longer in the manifest", name));
+        }
+        for dep in &self.unsatisfied {
+            messages.push(dep.to_string());
+        }
+        messages
+    }
+}
+
+/// Compare a manifest's direct dependencies with a lockfile.
+///
+/// Path dependencies are never locked and are ignored. Root lockfile entries
+/// are the ones no other locked package depends on.
+pub fn manifest_lockfile_drift(manifest: &Manifest, lockfile: &Lockfile) -> DriftReport {
+    let mut report = DriftReport::default();
+
+    let direct: Vec<&Dependency> = manifest
+        .dependencies
+        .values()
+        .chain(manifest.dev_dependencies.values())
+        .filter(|dep| dep.path.is_none())
+        .collect();
+    let direct_names: HashSet<&str> = manifest
+        .dependencies
+        .keys()
+        .chain(manifest.dev_dependencies.keys())
+        .map(String::as_str)
+        .collect();
+
+    for dep in &direct {
+        match lockfile.get(&dep.name) {
+            None => report.missing.push(dep.name.clone()),
+            Some(locked) => {
+                if let Some(requirement) = rejected_by(dep, &locked.version, &locked.revision) {
+                    report.unsatisfied.push(UnsatisfiedDependency {
+                        name: dep.name.clone(),
+                        requirement,
+                        locked: locked.version.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    let depended_on: HashSet<&str> = lockfile
+        .packages
+        .iter()
+        .flat_map(|p| p.dependencies.iter().map(String::as_str))
+        .collect();
+    for package in &lockfile.packages {
+        let name = package.name.as_str();
+        if !depended_on.contains(name) && !direct_names.contains(name) {
+            report.stale.push(package.name.clone());
+        }
+    }
+
+    report.missing.sort();
+    report.stale.sort();
+    report.unsatisfied.sort_by(|a, b| a.name.cmp(&b.name));
+    report
+}
+
+/// The dependency's constraint, if it rejects the locked version.
+///
+/// Branch dependencies can't be checked without fetching and always pass.
+fn rejected_by(dep: &Dependency, locked: &Version, revision: &Option<String>) -> Option<String> {
+    if dep.version.is_some() && !dep.accepts(locked) {
+        return dep.requirement();
+    }
+
+    if let Some(tag) = &dep.tag {
+        let tagged = tag.trim();
+        let tagged = tagged
+            .strip_prefix(['v', 'V'])
+            .unwrap_or(tagged)
+            .parse::<Version>();
+        if tagged.is_ok_and(|v| v != *locked) {
+            return Some(format!("tag {}", tag));
+        }
+    }
+
+    if let Some(wanted) = &dep.revision {
+        let matches = revision
+            .as_ref()
+            .is_some_and(|r| r.starts_with(wanted.as_str()) || wanted.starts_with(r.as_str()));
+        if !matches {
+            return Some(format!("revision {}", wanted));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LockedPackage;
+    use gust_types::VersionReq;
+
+    fn lockfile(packages: Vec<LockedPackage>) -> Lockfile {
+        Lockfile {
+            packages,
+            ..Default::default()
+        }
+    }
+
+    fn git(name: &str, version: Version) -> LockedPackage {
+        LockedPackage::git(
+            name,
+            version,
+            format!("https://example.com/{}.git", name),
+            "abc123",
+        )
+    }
+
+    #[test]
+    fn test_in_sync() {
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "log".to_string(),
+            Dependency::git("log", "https://example.com/log.git").with_tag("1.5.0"),
+        );
+        manifest
+            .dependencies
+            .insert("local".to_string(), Dependency::path("local", "../local"));
+
+        let mut log = git("log", Version::new(1, 5, 0));
+        log.dependencies = vec!["atomics".to_string()];
+        let lock = lockfile(vec![log, git("atomics", Version::new(1, 0, 0))]);
+
+        assert!(manifest_lockfile_drift(&manifest, &lock).is_empty());
+    }
+
+    #[test]
+    fn test_missing_dependency() {
+        let mut manifest = Manifest::default();
+        manifest.dev_dependencies.insert(
+            "testing".to_string(),
+            Dependency::git("testing", "https://example.com/testing.git"),
+        );
+
+        let report = manifest_lockfile_drift(&manifest, &lockfile(Vec::new()));
+        assert_eq!(report.missing, vec!["testing"]);
+        assert!(report.stale.is_empty());
+        assert!(report.unsatisfied.is_empty());
+    }
+
+    #[test]
+    fn test_stale_root_entry() {
+        let manifest = Manifest::default();
+        let mut old = git("old", Version::new(1, 0, 0));
+        old.dependencies = vec!["transitive".to_string()];
+        let lock = lockfile(vec![old, git("transitive", Version::new(2, 0, 0))]);
+
+        // Only the root is stale; its dependencies go with it
+        let report = manifest_lockfile_drift(&manifest, &lock);
+        assert_eq!(report.stale, vec!["old"]);
+        assert!(report.missing.is_empty());
+        assert_eq!(
+            report.messages(),
+            vec!["old is locked but no longer in the manifest"]
+        );
+    }
+
+    #[test]
+    fn test_unsatisfied_constraint() {
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "nio".to_string(),
+            Dependency::registry("nio", VersionReq::parse("^2.0").unwrap()),
+        );
+        manifest.dependencies.insert(
+            "log".to_string(),
+            Dependency::git("log", "https://example.com/log.git").with_tag("v1.6.0"),
+        );
+
+        let lock = lockfile(vec![
+            LockedPackage::registry("nio", Version::new(1, 9, 0), "blake3:00"),
+            git("log", Version::new(1, 5, 0)),
+        ]);
+
+        let report = manifest_lockfile_drift(&manifest, &lock);
+        assert!(report.missing.is_empty());
+        assert!(report.stale.is_empty());
+        assert_eq!(report.unsatisfied.len(), 2);
+        assert_eq!(report.unsatisfied[0].requirement, "tag v1.6.0");
+        assert_eq!(report.unsatisfied[1].name, "nio");
+        assert_eq!(
+            report.unsatisfied[1].to_string(),
+            "nio is locked at 1.9.0 but the manifest requires ^2.0"
+        );
+    }
+}
@@ -3,7 +3,14 @@
 //! Manages Gust.lock files for reproducible builds.
 //! Supports incremental updates to minimize I/O and diff computation.
 
-use gust_types::{DependencySource, Version};
+mod drift;
+mod listing;
+mod resolved;
+
+pub use drift::{manifest_lockfile_drift, DriftReport, UnsatisfiedDependency};
+pub use resolved::{PackageResolved, PinState, ResolvedPin, PACKAGE_RESOLVED_FILE};
+
+use gust_types::{DependencySource, ResolvedPackage, Version};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
@@ -22,6 +29,8 @@ pub enum LockfileError {
     SerializeError(#[from] toml::ser::Error),
     #[error("Async task error: {0}")]
     TaskError(String),
+    #[error("Failed to serialize Package.resolved: {0}")]
+    ResolvedError(#[from] serde_json::Error),
     #[error("Checksum mismatch for {package}: expected {expected}, got {actual}")]
     ChecksumMismatch {
         package: String,
@@ -41,6 +50,9 @@ pub struct Lockfile {
     /// Locked packages
     #[serde(rename = "package", default)]
     pub packages: Vec<LockedPackage>,
+    /// Binary target artifacts downloaded for the root package
+    #[serde(rename = "artifact", default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<LockedArtifact>,
 }
 
 impl Default for Lockfile {
@@ -49,6 +61,7 @@ impl Default for Lockfile {
             version: 1,
             generated_by: format!("gust {}", env!("CARGO_PKG_VERSION")),
             packages: Vec::new(),
+            artifacts: Vec::new(),
         }
     }
 }
@@ -104,6 +117,10 @@ impl Lockfile {
         let mut results = Vec::new();
 
         for pkg in &self.packages {
+            // A linked path package changes with its source
+            if pkg.source == DependencySource::Path {
+                continue;
+            }
             // Only verify packages with checksums
             let expected_checksum = match &pkg.checksum {
                 Some(c) if !c.is_empty() => c,
@@ -123,7 +140,7 @@ impl Lockfile {
             }
 
             // Compute actual checksum
-            match compute_directory_checksum(&pkg_dir) {
+            match gust_cache::directory_checksum(&pkg_dir) {
                 Ok(actual) => {
                     // Handle prefixed checksums (e.g., "blake3:abc123")
                     let expected_hash = expected_checksum
@@ -190,7 +207,7 @@ impl Lockfile {
         // Find added and updated packages
         for (name, pkg) in &other_map {
             if let Some(existing) = self_map.get(name) {
-                if pkg.revision != existing.revision || pkg.version != existing.version {
+                if existing.differs_from(pkg) {
                     updated.push((*pkg).clone());
                 } else {
                     unchanged.push((*name).clone());
@@ -243,6 +260,7 @@ impl Lockfile {
             version: self.version,
             generated_by: format!("gust {}", env!("CARGO_PKG_VERSION")),
             packages: new_packages,
+            artifacts: self.artifacts.clone(),
         }
     }
 
@@ -252,6 +270,7 @@ impl Lockfile {
             version: self.version,
             generated_by: format!("gust {}", env!("CARGO_PKG_VERSION")),
             packages: new_packages,
+            artifacts: self.artifacts.clone(),
         };
 
         let diff = self.diff(&new_lockfile);
@@ -289,7 +308,7 @@ impl Lockfile {
             match self_map.get(&pkg.name) {
                 None => return true,
                 Some(existing) => {
-                    if existing.revision != pkg.revision || existing.version != pkg.version {
+                    if existing.differs_from(pkg) {
                         return true;
                     }
                 }
@@ -406,9 +425,19 @@ pub struct LockedPackage {
     /// Git revision (for git dependencies)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub revision: Option<String>,
+    /// Upstream repository reported by the registry (for registry
+    /// dependencies)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
     /// Transitive dependencies
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub dependencies: Vec<String>,
+    /// Only required by the root package's dev-dependencies
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dev: bool,
+    /// Only required by the root package's dependencies in this group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
 }
 
 impl LockedPackage {
@@ -425,7 +454,10 @@ impl LockedPackage {
             checksum: Some(checksum.into()),
             git: None,
             revision: None,
+            repository: None,
             dependencies: Vec::new(),
+            dev: false,
+            group: None,
         }
     }
 
@@ -443,7 +475,66 @@ impl LockedPackage {
             checksum: None,
             git: Some(url.into()),
             revision: Some(revision.into()),
+            repository: None,
             dependencies: Vec::new(),
+            dev: false,
+            group: None,
+        }
+    }
+
+    /// Whether `other`, the same package resolved again, should replace
+    /// this entry. Path packages have no revision, so for them a new
+    /// checksum is what shows their files changed.
+    fn differs_from(&self, other: &LockedPackage) -> bool {
+        let edited = self.source == DependencySource::Path && self.checksum != other.checksum;
+        self.revision != other.revision
+            || self.version != other.version
+            || self.dev != other.dev
+            || self.group != other.group
+            || self.repository != other.repository
+            || edited
+    }
+}
+
+/// A locked binary target artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedArtifact {
+    /// Binary target name
+    pub name: String,
+    /// URL the archive was downloaded from
+    pub url: String,
+    /// SHA-256 checksum of the archive
+    pub checksum: String,
+}
+
+impl From<ResolvedPackage> for LockedPackage {
+    fn from(pkg: ResolvedPackage) -> Self {
+        Self {
+            name: pkg.name,
+            version: pkg.version,
+            source: pkg.source,
+            checksum: pkg.checksum,
+            git: pkg.git,
+            revision: pkg.revision,
+            repository: pkg.repository,
+            dependencies: pkg.dependencies,
+            dev: false,
+            group: None,
+        }
+    }
+}
+
+impl From<LockedPackage> for ResolvedPackage {
+    fn from(pkg: LockedPackage) -> Self {
+        Self {
+            name: pkg.name,
+            version: pkg.version,
+            source: pkg.source,
+            checksum: pkg.checksum,
+            git: pkg.git,
+            revision: pkg.revision,
+            repository: pkg.repository,
+            dependencies: pkg.dependencies,
         }
     }
 }
@@ -661,7 +752,10 @@ impl LockfileV2 {
                 checksum: pkg.checksum.clone(),
                 git: pkg.git.clone(),
                 revision: pkg.revision.clone(),
+                repository: None,
                 dependencies: pkg.dependencies.clone(),
+                dev: false,
+                group: None,
             })
             .collect();
 
@@ -669,6 +763,7 @@ impl LockfileV2 {
             version: 1,
             generated_by: self.metadata.generated_by.clone(),
             packages,
+            artifacts: Vec::new(),
         }
     }
 
@@ -894,58 +989,6 @@ fn sanitize_package_name(name: &str) -> String {
         .collect()
 }
 
-/// Compute BLAKE3 checksum of a directory.
-fn compute_directory_checksum(path: &Path) -> Result<String, std::io::Error> {
-    use std::collections::BTreeMap;
-
-    let mut file_hashes: BTreeMap<String, String> = BTreeMap::new();
-
-    fn collect_and_hash(
-        dir: &Path,
-        prefix: &str,
-        hashes: &mut BTreeMap<String, String>,
-    ) -> Result<(), std::io::Error> {
-        if dir.is_dir() {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                let name = path.file_name().unwrap().to_string_lossy();
-
-                // Skip .git directory
-                if name == ".git" {
-                    continue;
-                }
-
-                let key = if prefix.is_empty() {
-                    name.to_string()
-                } else {
-                    format!("{}/{}", prefix, name)
-                };
-
-                if path.is_dir() {
-                    collect_and_hash(&path, &key, hashes)?;
-                } else {
-                    let content = std::fs::read(&path)?;
-                    let hash = blake3::hash(&content).to_hex().to_string();
-                    hashes.insert(key, hash);
-                }
-            }
-        }
-        Ok(())
-    }
-
-    collect_and_hash(path, "", &mut file_hashes)?;
-
-    // Combine all hashes
-    let combined: String = file_hashes
-        .iter()
-        .map(|(k, v)| format!("{}:{}", k, v))
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    Ok(blake3::hash(combined.as_bytes()).to_hex().to_string())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1064,4 +1107,55 @@ revision = "abc123"
         assert_eq!(v1.version, 1);
         assert_eq!(v1.packages[0].source, DependencySource::Git);
     }
+
+    #[test]
+    fn test_resolved_package_round_trip() {
+        let mut git = LockedPackage::git(
+            "swift-log",
+            Version::new(1, 5, 4),
+            "https://github.com/apple/swift-log.git",
+            "abc123",
+        );
+        git.dependencies = vec!["swift-atomics".to_string()];
+
+        let registry = LockedPackage::registry("swift-nio", Version::new(2, 58, 0), "blake3:def");
+
+        for locked in [git, registry] {
+            let resolved = ResolvedPackage::from(locked.clone());
+            assert_eq!(resolved.checksum, locked.checksum);
+            assert_eq!(resolved.revision, locked.revision);
+
+            let back = LockedPackage::from(resolved);
+            assert_eq!(back.name, locked.name);
+            assert_eq!(back.version, locked.version);
+            assert_eq!(back.source, locked.source);
+            assert_eq!(back.checksum, locked.checksum);
+            assert_eq!(back.git, locked.git);
+            assert_eq!(back.revision, locked.revision);
+            assert_eq!(back.dependencies, locked.dependencies);
+            assert!(!back.dev);
+        }
+    }
+
+    #[test]
+    fn test_artifacts_round_trip() {
+        let mut lockfile = Lockfile::default();
+        assert!(!lockfile.to_string().unwrap().contains("[[artifact]]"));
+
+        lockfile.artifacts.push(LockedArtifact {
+            name: "Sdk".to_string(),
+            url: "https://example.com/Sdk.xcframework.zip".to_string(),
+            checksum: "abc123".to_string(),
+        });
+
+        let content = lockfile.to_string().unwrap();
+        assert!(content.contains("[[artifact]]"));
+
+        let parsed: Lockfile = toml::from_str(&content).unwrap();
+        assert_eq!(parsed.artifacts, lockfile.artifacts);
+
+        // Merging packages keeps the recorded artifacts
+        let (_, merged) = parsed.merge(Vec::new());
+        assert_eq!(merged.artifacts, lockfile.artifacts);
+    }
 }
@@ -0,0 +1,440 @@
+//! Flat listings and software bills of materials from a lockfile.
+//!
+//! `gust deps` prints every locked package once, sorted by name. The same
+//! entries can be exported as plain JSON, an SPDX 2.3 document or a
+//! CycloneDX 1.5 BOM, both in their JSON encodings.
+
+use crate::{LockedPackage, Lockfile};
+use gust_types::{DependencySource, Version};
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+impl Lockfile {
+    /// Locked packages sorted by name.
+    pub fn sorted_packages(&self) -> Vec<&LockedPackage> {
+        let mut packages: Vec<&LockedPackage> = self.packages.iter().collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        packages
+    }
+
+    /// One line per package: name, version, source kind and, for git
+    /// dependencies, `<url>#<revision>`.
+    pub fn flat_listing(&self) -> Vec<String> {
+        self.sorted_packages()
+            .into_iter()
+            .map(|p| {
+                let line = format!("{} {} {}", p.name, p.version, p.source);
+                match p.location() {
+                    Some(location) => format!("{} {}", line, location),
+                    None => line,
+                }
+            })
+            .collect()
+    }
+
+    /// The flat listing as a JSON array.
+    pub fn listing_json(&self) -> Value {
+        let packages: Vec<Value> = self
+            .sorted_packages()
+            .into_iter()
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "version": p.version.to_string(),
+                    "source": p.source.to_string(),
+                    "git": p.git,
+                    "revision": p.revision,
+                    "checksum": p.checksum,
+                    "dev": p.dev,
+                })
+            })
+            .collect();
+        Value::Array(packages)
+    }
+
+    /// An SPDX 2.3 document describing `root` and every locked package.
+    ///
+    /// `direct` names the root's own dependencies; the rest of the graph
+    /// comes from each locked package's dependencies.
+    pub fn to_spdx(
+        &self,
+        root: &str,
+        root_version: &Version,
+        direct: &[String],
+        created: SystemTime,
+    ) -> Value {
+        let root_id = spdx_id(root);
+        let mut packages = vec![json!({
+            "SPDXID": root_id,
+            "name": root,
+            "versionInfo": root_version.to_string(),
+            "downloadLocation": "NOASSERTION",
+            "filesAnalyzed": false,
+        })];
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": root_id,
+        })];
+
+        for p in self.sorted_packages() {
+            let mut package = json!({
+                "SPDXID": spdx_id(&p.name),
+                "name": p.name,
+                "versionInfo": p.version.to_string(),
+                "downloadLocation": p
+                    .git
+                    .as_ref()
+                    .map(|url| format!("git+{}", url))
+                    .unwrap_or_else(|| "NOASSERTION".to_string()),
+                "filesAnalyzed": false,
+            });
+            if let Some(checksum) = &p.checksum {
+                package["checksums"] =
+                    json!([{ "algorithm": "BLAKE3", "checksumValue": checksum }]);
+            }
+            if let Some(purl) = p.purl() {
+                package["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }]);
+            }
+            packages.push(package);
+            if direct.contains(&p.name) {
+                relationships.push(if p.dev {
+                    json!({
+                        "spdxElementId": spdx_id(&p.name),
+                        "relationshipType": "DEV_DEPENDENCY_OF",
+                        "relatedSpdxElement": root_id,
+                    })
+                } else {
+                    json!({
+                        "spdxElementId": root_id,
+                        "relationshipType": "DEPENDS_ON",
+                        "relatedSpdxElement": spdx_id(&p.name),
+                    })
+                });
+            }
+            for dep in p.dependencies.iter().filter(|d| self.get(d).is_some()) {
+                relationships.push(json!({
+                    "spdxElementId": spdx_id(&p.name),
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": spdx_id(dep),
+                }));
+            }
+        }
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": format!("{}-{}", root, root_version),
+            "documentNamespace": format!(
+                "https://spdx.org/spdxdocs/{}-{}-{}",
+                root,
+                root_version,
+                self.content_hash()
+            ),
+            "creationInfo": {
+                "created": rfc3339(created),
+                "creators": [format!("Tool: {}", self.generated_by)],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+
+    /// A CycloneDX 1.5 BOM describing `root` and every locked package, with
+    /// `direct` naming the root's own dependencies.
+    pub fn to_cyclonedx(
+        &self,
+        root: &str,
+        root_version: &Version,
+        direct: &[String],
+        created: SystemTime,
+    ) -> Value {
+        let components: Vec<Value> = self
+            .sorted_packages()
+            .into_iter()
+            .map(|p| {
+                let mut component = json!({
+                    "type": "library",
+                    "bom-ref": p.name,
+                    "name": p.name,
+                    "version": p.version.to_string(),
+                    "scope": if p.dev { "optional" } else { "required" },
+                });
+                if let Some(purl) = p.purl() {
+                    component["purl"] = json!(purl);
+                }
+                if let Some(checksum) = &p.checksum {
+                    component["hashes"] = json!([{ "alg": "BLAKE3", "content": checksum }]);
+                }
+                if let Some(url) = &p.git {
+                    component["externalReferences"] = json!([{ "type": "vcs", "url": url }]);
+                }
+                component
+            })
+            .collect();
+        let dependencies: Vec<Value> = std::iter::once(json!({
+            "ref": root,
+            "dependsOn": self
+                .sorted_packages()
+                .iter()
+                .map(|p| &p.name)
+                .filter(|name| direct.contains(name))
+                .collect::<Vec<_>>(),
+        }))
+        .chain(
+            self.sorted_packages()
+                .into_iter()
+                .map(|p| json!({ "ref": p.name, "dependsOn": p.dependencies })),
+        )
+        .collect();
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": rfc3339(created),
+                "tools": [{ "name": self.generated_by }],
+                "component": {
+                    "type": "application",
+                    "bom-ref": root,
+                    "name": root,
+                    "version": root_version.to_string(),
+                },
+            },
+            "components": components,
+            "dependencies": dependencies,
+        })
+    }
+
+    /// Short hash of the locked names and versions, so SPDX namespaces for
+    /// different dependency sets don't collide.
+    fn content_hash(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for p in self.sorted_packages() {
+            hasher.update(p.name.as_bytes());
+            hasher.update(p.version.to_string().as_bytes());
+        }
+        hasher.finalize().to_hex()[..16].to_string()
+    }
+}
+
+impl LockedPackage {
+    /// `<git url>#<revision>` for git dependencies.
+    pub fn location(&self) -> Option<String> {
+        let url = self.git.as_ref()?;
+        Some(match &self.revision {
+            Some(rev) => format!("{}#{}", url, rev),
+            None => url.clone(),
+        })
+    }
+
+    /// Package URL (`pkg:swift/...`), if the package has a public identity.
+    pub fn purl(&self) -> Option<String> {
+        match self.source {
+            DependencySource::Git => {
+                let url = self.git.as_ref()?;
+                let path = url
+                    .split_once("://")
+                    .map(|(_, rest)| rest)
+                    .unwrap_or(url)
+                    .trim_start_matches("git@")
+                    .replacen(':', "/", 1);
+                let path = path.trim_end_matches('/').trim_end_matches(".git");
+                Some(format!("pkg:swift/{}@{}", path, self.version))
+            }
+            DependencySource::Registry => Some(format!("pkg:swift/{}@{}", self.name, self.version)),
+            DependencySource::Path => None,
+        }
+    }
+}
+
+/// SPDX identifiers only allow letters, digits, `.` and `-`.
+///
+/// A name that had to be changed gets a hash of the original appended, so
+/// `a_b` and `a-b` don't share an identifier.
+fn spdx_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if id == name {
+        format!("SPDXRef-Package-{}", id)
+    } else {
+        let hash = blake3::hash(name.as_bytes()).to_hex();
+        format!("SPDXRef-Package-{}-{}", id, &hash[..8])
+    }
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` in UTC.
+fn rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn lockfile() -> Lockfile {
+        let mut lockfile = Lockfile::default();
+        let mut log = LockedPackage::git(
+            "swift-log",
+            Version::new(1, 5, 3),
+            "https://github.com/apple/swift-log.git",
+            "abc123",
+        );
+        log.checksum = Some("deadbeef".to_string());
+        lockfile.packages.push(LockedPackage::registry(
+            "swift-nio",
+            Version::new(2, 60, 0),
+            "cafef00d",
+        ));
+        lockfile.packages.push(log);
+        lockfile
+    }
+
+    #[test]
+    fn test_flat_listing_sorted_by_name() {
+        assert_eq!(
+            lockfile().flat_listing(),
+            vec![
+                "swift-log 1.5.3 git https://github.com/apple/swift-log.git#abc123",
+                "swift-nio 2.60.0 registry",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_listing_json() {
+        let json = lockfile().listing_json();
+        let packages = json.as_array().unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0]["name"], "swift-log");
+        assert_eq!(packages[0]["version"], "1.5.3");
+        assert_eq!(packages[0]["source"], "git");
+        assert_eq!(packages[0]["revision"], "abc123");
+        assert_eq!(packages[1]["source"], "registry");
+        assert_eq!(packages[1]["git"], Value::Null);
+        assert_eq!(packages[1]["checksum"], "cafef00d");
+    }
+
+    #[test]
+    fn test_sboms() {
+        let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let lockfile = lockfile();
+        let version = Version::new(0, 1, 0);
+
+        let direct = ["swift-log".to_string(), "swift-nio".to_string()];
+
+        let spdx = lockfile.to_spdx("app", &version, &direct, created);
+        assert_eq!(spdx["creationInfo"]["created"], "2023-11-14T22:13:20Z");
+        assert_eq!(spdx["packages"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            spdx["packages"][1]["externalRefs"][0]["referenceLocator"],
+            "pkg:swift/github.com/apple/swift-log@1.5.3"
+        );
+        assert_eq!(spdx["relationships"][1]["relationshipType"], "DEPENDS_ON");
+
+        let bom = lockfile.to_cyclonedx("app", &version, &direct, created);
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["components"][1]["hashes"][0]["content"], "cafef00d");
+        assert_eq!(
+            bom["dependencies"][0]["dependsOn"],
+            json!(["swift-log", "swift-nio"])
+        );
+    }
+
+    #[test]
+    fn test_sbom_relationships_follow_the_dependency_graph() {
+        let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut lockfile = lockfile();
+        // swift-nio depends on swift-atomics; swift-testing is a dev dependency
+        lockfile.packages[0].dependencies = vec!["swift-atomics".to_string()];
+        lockfile.packages.push(LockedPackage::registry(
+            "swift-atomics",
+            Version::new(1, 2, 0),
+            "0ff1ce",
+        ));
+        let mut testing = LockedPackage::registry("swift-testing", Version::new(0, 4, 0), "f00d");
+        testing.dev = true;
+        lockfile.packages.push(testing);
+        let direct = [
+            "swift-log".to_string(),
+            "swift-nio".to_string(),
+            "swift-testing".to_string(),
+        ];
+
+        let spdx = lockfile.to_spdx("app", &Version::new(0, 1, 0), &direct, created);
+        let relationships: Vec<(String, String, String)> = spdx["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["spdxElementId"].as_str().unwrap().to_string(),
+                    r["relationshipType"].as_str().unwrap().to_string(),
+                    r["relatedSpdxElement"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        let relationship = |from: &str, kind: &str, to: &str| {
+            (
+                format!("SPDXRef-Package-{}", from),
+                kind.to_string(),
+                format!("SPDXRef-Package-{}", to),
+            )
+        };
+        assert!(relationships.contains(&relationship("app", "DEPENDS_ON", "swift-nio")));
+        assert!(relationships.contains(&relationship("swift-nio", "DEPENDS_ON", "swift-atomics")));
+        assert!(relationships.contains(&relationship("swift-testing", "DEV_DEPENDENCY_OF", "app")));
+        assert!(!relationships.contains(&relationship("app", "DEPENDS_ON", "swift-atomics")));
+
+        let bom = lockfile.to_cyclonedx("app", &Version::new(0, 1, 0), &direct, created);
+        assert_eq!(
+            bom["dependencies"][0]["dependsOn"],
+            json!(["swift-log", "swift-nio", "swift-testing"])
+        );
+    }
+
+    #[test]
+    fn test_spdx_ids_stay_distinct_after_sanitizing() {
+        assert_eq!(spdx_id("swift-log"), "SPDXRef-Package-swift-log");
+        assert_ne!(spdx_id("a_b"), spdx_id("a-b"));
+        assert_ne!(spdx_id("a_b"), spdx_id("a+b"));
+        assert!(spdx_id("a_b").starts_with("SPDXRef-Package-a-b-"));
+    }
+}
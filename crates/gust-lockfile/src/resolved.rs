@@ -0,0 +1,203 @@
+//! SwiftPM `Package.resolved` generation.
+//!
+//! `gust build` hands the generated Package.swift to `swift build`, which
+//! would otherwise resolve versions on its own. Writing a `Package.resolved`
+//! (format version 3) from `Gust.lock` pins SwiftPM to Gust's resolution.
+
+use crate::{LockedPackage, Lockfile, LockfileError};
+use gust_types::{DependencySource, Manifest};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Name of SwiftPM's pin file.
+pub const PACKAGE_RESOLVED_FILE: &str = "Package.resolved";
+
+/// A `Package.resolved` file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageResolved {
+    /// Pinned packages, sorted by identity
+    pub pins: Vec<ResolvedPin>,
+    /// File format version
+    pub version: u32,
+}
+
+/// A single pin in `Package.resolved`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedPin {
+    /// SwiftPM package identity
+    pub identity: String,
+    /// `remoteSourceControl` or `registry`
+    pub kind: String,
+    /// Repository URL, empty for registry packages
+    pub location: String,
+    /// Pinned state
+    pub state: PinState,
+}
+
+/// The pinned revision, version or branch of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl PackageResolved {
+    /// Build pins for every locked git and registry package.
+    ///
+    /// The manifest tells which direct dependencies follow a branch or an
+    /// exact revision; everything else is pinned by version.
+    pub fn from_lockfile(lockfile: &Lockfile, manifest: &Manifest) -> Self {
+        let mut pins: Vec<ResolvedPin> = lockfile
+            .packages
+            .iter()
+            .filter_map(|package| ResolvedPin::from_locked(package, manifest))
+            .collect();
+        pins.sort_by(|a, b| a.identity.cmp(&b.identity));
+        pins.dedup_by(|a, b| a.identity == b.identity);
+
+        Self { pins, version: 3 }
+    }
+
+    /// Serialize to SwiftPM's JSON layout.
+    pub fn to_string(&self) -> Result<String, LockfileError> {
+        Ok(format!("{}\n", serde_json::to_string_pretty(self)?))
+    }
+
+    /// Write to `dir/Package.resolved`, leaving the file alone if unchanged.
+    pub fn write(&self, dir: &Path) -> Result<(), LockfileError> {
+        let path = dir.join(PACKAGE_RESOLVED_FILE);
+        let content = self.to_string()?;
+        if std::fs::read_to_string(&path).is_ok_and(|existing| existing == content) {
+            return Ok(());
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+impl ResolvedPin {
+    /// Pin a locked package, or `None` if it can't be pinned (path
+    /// dependencies, or git packages without a locked revision).
+    pub fn from_locked(package: &LockedPackage, manifest: &Manifest) -> Option<Self> {
+        match package.source {
+            DependencySource::Git => {
+                let url = package.git.as_ref()?;
+                let revision = package.revision.clone()?;
+                let dep = manifest
+                    .dependencies
+                    .get(&package.name)
+                    .or_else(|| manifest.dev_dependencies.get(&package.name));
+
+                let state = match dep {
+                    Some(dep) if dep.branch.is_some() => PinState {
+                        branch: dep.branch.clone(),
+                        revision: Some(revision),
+                        version: None,
+                    },
+                    Some(dep) if dep.revision.is_some() => PinState {
+                        branch: None,
+                        revision: Some(revision),
+                        version: None,
+                    },
+                    _ => PinState {
+                        branch: None,
+                        revision: Some(revision),
+                        version: Some(package.version.to_string()),
+                    },
+                };
+
+                Some(Self {
+                    identity: identity_from_url(url),
+                    kind: "remoteSourceControl".to_string(),
+                    location: url.clone(),
+                    state,
+                })
+            }
+            DependencySource::Registry => Some(Self {
+                identity: package.name.to_lowercase(),
+                kind: "registry".to_string(),
+                location: String::new(),
+                state: PinState {
+                    branch: None,
+                    revision: None,
+                    version: Some(package.version.to_string()),
+                },
+            }),
+            DependencySource::Path => None,
+        }
+    }
+}
+
+/// SwiftPM's identity for a repository URL: its last path component,
+/// lowercased, without a `.git` suffix.
+fn identity_from_url(url: &str) -> String {
+    let last = url.trim_end_matches('/').rsplit('/').next().unwrap_or(url);
+    last.strip_suffix(".git").unwrap_or(last).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gust_types::{Dependency, Version};
+
+    #[test]
+    fn test_locked_package_produces_pin() {
+        let lockfile = Lockfile {
+            packages: vec![LockedPackage::git(
+                "swift-log",
+                Version::new(1, 5, 3),
+                "https://github.com/apple/Swift-Log.git",
+                "e97a6fcb1ab07462881ac165fdbb37f067e205d5",
+            )],
+            ..Default::default()
+        };
+
+        let resolved = PackageResolved::from_lockfile(&lockfile, &Manifest::default());
+        let json: serde_json::Value = serde_json::from_str(&resolved.to_string().unwrap()).unwrap();
+
+        assert_eq!(json["version"], 3);
+        let pin = &json["pins"][0];
+        assert_eq!(pin["identity"], "swift-log");
+        assert_eq!(pin["kind"], "remoteSourceControl");
+        assert_eq!(pin["location"], "https://github.com/apple/Swift-Log.git");
+        assert_eq!(
+            pin["state"]["revision"],
+            "e97a6fcb1ab07462881ac165fdbb37f067e205d5"
+        );
+        assert_eq!(pin["state"]["version"], "1.5.3");
+        assert!(pin["state"].get("branch").is_none());
+    }
+
+    #[test]
+    fn test_branch_dependency_pins_branch() {
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "vapor".to_string(),
+            Dependency::git("vapor", "https://github.com/vapor/vapor").with_branch("main"),
+        );
+        let lockfile = Lockfile {
+            packages: vec![
+                LockedPackage::git(
+                    "vapor",
+                    Version::new(0, 0, 0),
+                    "https://github.com/vapor/vapor",
+                    "abc123",
+                ),
+                LockedPackage::registry("Yams", Version::new(5, 0, 6), "blake3:00"),
+            ],
+            ..Default::default()
+        };
+
+        let resolved = PackageResolved::from_lockfile(&lockfile, &manifest);
+        assert_eq!(resolved.pins.len(), 2);
+        let vapor = &resolved.pins[0];
+        assert_eq!(vapor.identity, "vapor");
+        assert_eq!(vapor.state.branch.as_deref(), Some("main"));
+        assert_eq!(vapor.state.version, None);
+        assert_eq!(resolved.pins[1].kind, "registry");
+    }
+}
@@ -1,10 +1,25 @@
 //! Manifest caching for fast repeated parsing.
 //!
 //! Caches parsed Package.swift results to avoid slow `swift package dump-package` calls.
+//! Entries are keyed by the manifest's content together with the
+//! `Package.resolved` beside it, so a change in resolution invalidates the
+//! cached dump.
+//!
+//! Entries live in directories named after the first two characters of
+//! their key, like the global cache's file store, so parallel parses don't
+//! all work in one directory. Nothing is locked: an entry is written to a
+//! temporary file and renamed into place, so a reader sees either no entry
+//! or a complete one.
 
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Version of the cache key scheme. Bumping it orphans every existing entry.
+///
+/// Version 2 added `Package.resolved` to the key.
+pub const CACHE_FORMAT_VERSION: u32 = 2;
 
 /// Manifest cache for storing parsed Package.swift results.
 pub struct ManifestCache {
@@ -14,69 +29,135 @@ pub struct ManifestCache {
 impl ManifestCache {
     /// Open or create the manifest cache.
     pub fn open() -> io::Result<Self> {
-        let cache_dir = directories::ProjectDirs::from("dev", "gust", "gust")
-            .map(|d| d.cache_dir().join("manifests"))
-            .unwrap_or_else(|| PathBuf::from("/tmp/gust-manifest-cache"));
+        let cache_dir = gust_cache::GlobalCache::default_cache_dir()
+            .map(|d| d.join("manifests"))
+            .unwrap_or_else(|_| PathBuf::from("/tmp/gust-manifest-cache"));
+
+        fs::create_dir_all(&cache_dir)?;
+        Ok(Self { cache_dir })
+    }
 
+    /// Open or create a manifest cache in `cache_dir`.
+    pub fn open_at(cache_dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let cache_dir = cache_dir.into();
         fs::create_dir_all(&cache_dir)?;
         Ok(Self { cache_dir })
     }
 
-    /// Get the cache key for a Package.swift file.
+    /// Get the cache key for a Package.swift file, including the
+    /// `Package.resolved` next to it if there is one.
     pub fn cache_key(path: &Path) -> io::Result<String> {
         let content = fs::read(path)?;
-        let hash = blake3::hash(&content);
-        Ok(hash.to_hex().to_string())
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(format!("v{}:", CACHE_FORMAT_VERSION).as_bytes());
+        hasher.update(&content);
+
+        let resolved = path.with_file_name("Package.resolved");
+        match fs::read(&resolved) {
+            Ok(resolved) => {
+                hasher.update(b"resolved:");
+                hasher.update(&resolved);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+
+    /// Where the entry for `key` is kept.
+    fn entry_path(&self, key: &str) -> PathBuf {
+        let prefix = key.get(..2).unwrap_or("_");
+        self.cache_dir.join(prefix).join(format!("{}.json", key))
+    }
+
+    /// The directories entries are kept in: each prefix directory, and the
+    /// cache directory itself for entries from before they were split up.
+    fn entry_dirs(&self) -> io::Result<Vec<PathBuf>> {
+        let mut dirs = vec![self.cache_dir.clone()];
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        Ok(dirs)
     }
 
     /// Get cached JSON for a Package.swift file.
     pub fn get(&self, key: &str) -> Option<String> {
-        let cache_path = self.cache_dir.join(format!("{}.json", key));
-        fs::read_to_string(cache_path).ok()
+        fs::read_to_string(self.entry_path(key)).ok()
     }
 
     /// Store parsed JSON in the cache.
+    ///
+    /// The JSON goes to a temporary file first and is renamed into place,
+    /// so concurrent readers never see a partial entry and concurrent
+    /// writers of the same key leave one complete entry.
     pub fn put(&self, key: &str, json: &str) -> io::Result<()> {
-        let cache_path = self.cache_dir.join(format!("{}.json", key));
-        fs::write(cache_path, json)
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+        let path = self.entry_path(key);
+        let dir = path.parent().unwrap_or(&self.cache_dir);
+        fs::create_dir_all(dir)?;
+        let temp = dir.join(format!(
+            ".{}.{}.{}.tmp",
+            key,
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, json)?;
+        fs::rename(&temp, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+    }
+
+    /// Remove a cache entry, if there is one.
+    pub fn remove(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.entry_path(key)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
     }
 
     /// Check if a cache entry exists.
     pub fn contains(&self, key: &str) -> bool {
-        self.cache_dir.join(format!("{}.json", key)).exists()
+        self.entry_path(key).exists()
     }
 
     /// Clear all cached manifests.
     pub fn clear(&self) -> io::Result<()> {
-        for entry in fs::read_dir(&self.cache_dir)? {
-            let entry = entry?;
-            if entry
-                .path()
-                .extension()
-                .map(|e| e == "json")
-                .unwrap_or(false)
-            {
-                fs::remove_file(entry.path())?;
+        for dir in self.entry_dirs()? {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if is_entry(&path) {
+                    fs::remove_file(path)?;
+                }
             }
         }
         Ok(())
     }
 
+    /// Entry count, size and ages of the cached manifests.
+    pub fn report(&self) -> io::Result<gust_cache::CacheReport> {
+        let mut report = gust_cache::CacheReport::scan(&self.cache_dir, &|path| is_entry(path))?;
+        for dir in self.entry_dirs()?.iter().skip(1) {
+            report.add(&gust_cache::CacheReport::scan(dir, &|path| is_entry(path))?);
+        }
+        Ok(report)
+    }
+
     /// Get cache statistics.
     pub fn stats(&self) -> io::Result<CacheStats> {
         let mut count = 0;
         let mut size = 0;
 
-        for entry in fs::read_dir(&self.cache_dir)? {
-            let entry = entry?;
-            if entry
-                .path()
-                .extension()
-                .map(|e| e == "json")
-                .unwrap_or(false)
-            {
-                count += 1;
-                size += entry.metadata()?.len();
+        for dir in self.entry_dirs()? {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                if is_entry(&entry.path()) {
+                    count += 1;
+                    size += entry.metadata()?.len();
+                }
             }
         }
 
@@ -84,9 +165,99 @@ impl ManifestCache {
     }
 }
 
+/// Whether `path` is a cached manifest rather than a directory or a
+/// temporary file.
+fn is_entry(path: &Path) -> bool {
+    path.extension().map(|e| e == "json").unwrap_or(false) && path.is_file()
+}
+
 /// Cache statistics.
 #[derive(Debug)]
 pub struct CacheStats {
     pub count: usize,
     pub size: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_follows_package_resolved() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let manifest = tmp.path().join("Package.swift");
+        fs::write(&manifest, "// swift-tools-version:5.9\n").unwrap();
+        let unresolved = ManifestCache::cache_key(&manifest).unwrap();
+
+        let resolved = tmp.path().join("Package.resolved");
+        fs::write(&resolved, r#"{"pins": [], "version": 2}"#).unwrap();
+        let first = ManifestCache::cache_key(&manifest).unwrap();
+        assert_ne!(first, unresolved);
+        assert_eq!(ManifestCache::cache_key(&manifest).unwrap(), first);
+
+        fs::write(
+            &resolved,
+            r#"{"pins": [{"identity": "yams", "state": {"version": "5.0.6"}}], "version": 2}"#,
+        )
+        .unwrap();
+        assert_ne!(ManifestCache::cache_key(&manifest).unwrap(), first);
+    }
+
+    #[test]
+    fn test_concurrent_gets_and_puts_keep_entries_whole() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = ManifestCache::open_at(tmp.path()).unwrap();
+        let key = |i: usize| blake3::hash(&i.to_le_bytes()).to_hex().to_string();
+        // Large enough that a torn write would show
+        let json = |i: usize, writer: usize| {
+            format!(
+                "{{\"key\": {}, \"writer\": {}, \"pad\": \"{}\"}}",
+                i,
+                writer,
+                "x".repeat(64 * 1024)
+            )
+        };
+
+        std::thread::scope(|scope| {
+            for writer in 0..8 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for i in 0..64 {
+                        // Distinct keys per writer, plus one key all share
+                        let i = if i == 0 { 0 } else { writer * 64 + i };
+                        cache.put(&key(i), &json(i, writer)).unwrap();
+                        let read = cache.get(&key(i)).unwrap();
+                        let value: serde_json::Value = serde_json::from_str(&read).unwrap();
+                        assert_eq!(value["key"], i);
+                    }
+                });
+            }
+        });
+
+        for writer in 0..8 {
+            for i in 1..64 {
+                let i = writer * 64 + i;
+                assert_eq!(cache.get(&key(i)).unwrap(), json(i, writer));
+            }
+        }
+        let shared = cache.get(&key(0)).unwrap();
+        assert!((0..8).any(|writer| shared == json(0, writer)));
+
+        // Entries are spread over prefix directories, with no temporary
+        // files left behind
+        assert_eq!(cache.stats().unwrap().count, 8 * 63 + 1);
+        assert_eq!(cache.report().unwrap().entries, 8 * 63 + 1);
+        assert!(cache
+            .entry_path(&key(1))
+            .starts_with(tmp.path().join(&key(1)[..2])));
+        for dir in cache.entry_dirs().unwrap() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let name = entry.unwrap().file_name();
+                assert!(!name.to_string_lossy().ends_with(".tmp"));
+            }
+        }
+
+        cache.clear().unwrap();
+        assert_eq!(cache.stats().unwrap().count, 0);
+    }
+}
@@ -0,0 +1,299 @@
+//! Format-preserving edits to Gust.toml.
+//!
+//! Used by `gust add`, `remove` and `update` so that comments, key order and
+//! table layout written by the user survive programmatic changes.
+
+use crate::ManifestError;
+use gust_types::Dependency;
+use std::path::Path;
+use toml_edit::{value, Array, DocumentMut, InlineTable, Item, Table};
+
+/// Dependency tables that can be edited.
+const DEPENDENCY_TABLES: [&str; 2] = ["dependencies", "dev-dependencies"];
+
+/// A Gust.toml document open for editing.
+pub struct ManifestEditor {
+    doc: DocumentMut,
+}
+
+impl ManifestEditor {
+    /// Parse manifest content for editing.
+    pub fn parse(content: &str) -> Result<Self, ManifestError> {
+        let doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| ManifestError::ValidationError(e.to_string()))?;
+        Ok(Self { doc })
+    }
+
+    /// Read a manifest file for editing.
+    pub fn open(path: &Path) -> Result<Self, ManifestError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Write the edited manifest back to disk.
+    pub fn save(&self, path: &Path) -> Result<(), ManifestError> {
+        std::fs::write(path, self.doc.to_string())?;
+        Ok(())
+    }
+
+    /// Check whether a dependency is declared in either dependency table.
+    pub fn contains_dependency(&self, name: &str) -> bool {
+        DEPENDENCY_TABLES.iter().any(|table| {
+            self.doc
+                .get(table)
+                .and_then(Item::as_table_like)
+                .is_some_and(|t| t.contains_key(name))
+        })
+    }
+
+    /// Add a dependency as an inline table, creating the table if needed.
+    ///
+    /// Fails if the dependency is already declared.
+    pub fn add_dependency(&mut self, dep: &Dependency, dev: bool) -> Result<(), ManifestError> {
+        if self.contains_dependency(&dep.name) {
+            return Err(ManifestError::ValidationError(format!(
+                "Dependency '{}' already exists",
+                dep.name
+            )));
+        }
+
+        let table = self.dependency_table(dev)?;
+        table.insert(&dep.name, value(dependency_to_inline(dep)));
+        Ok(())
+    }
+
+    /// Remove a dependency from whichever table declares it.
+    ///
+    /// Returns `false` if the dependency wasn't found.
+    pub fn remove_dependency(&mut self, name: &str) -> bool {
+        let mut removed = false;
+        for table in DEPENDENCY_TABLES {
+            if let Some(t) = self.doc.get_mut(table).and_then(Item::as_table_like_mut) {
+                removed |= t.remove(name).is_some();
+            }
+        }
+        removed
+    }
+
+    /// Set the `tag` of a git dependency, leaving its other keys intact.
+    ///
+    /// Returns `false` if the dependency wasn't found or isn't a table.
+    pub fn set_dependency_tag(&mut self, name: &str, tag: &str) -> bool {
+        for table in DEPENDENCY_TABLES {
+            let Some(entry) = self
+                .doc
+                .get_mut(table)
+                .and_then(Item::as_table_like_mut)
+                .and_then(|t| t.get_mut(name))
+            else {
+                continue;
+            };
+
+            if let Some(dep) = entry.as_table_like_mut() {
+                dep.insert("tag", value(tag));
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Set the `version` requirement of a dependency, leaving its other keys
+    /// intact.
+    ///
+    /// Returns `false` if the dependency wasn't found.
+    pub fn set_dependency_version(&mut self, name: &str, version: &str) -> bool {
+        for table in DEPENDENCY_TABLES {
+            let Some(entry) = self
+                .doc
+                .get_mut(table)
+                .and_then(Item::as_table_like_mut)
+                .and_then(|t| t.get_mut(name))
+            else {
+                continue;
+            };
+
+            if let Some(dep) = entry.as_table_like_mut() {
+                dep.insert("version", value(version));
+            } else {
+                *entry = value(version);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Add `member` (a feature or optional dependency) to the list a feature
+    /// enables, creating the feature and `[features]` if needed.
+    pub fn add_to_feature(&mut self, feature: &str, member: &str) -> Result<(), ManifestError> {
+        let features = self
+            .doc
+            .entry("features")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| ManifestError::ValidationError("[features] is not a table".into()))?;
+        let enables = features
+            .entry(feature)
+            .or_insert(value(Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| {
+                ManifestError::ValidationError(format!("Feature '{}' is not an array", feature))
+            })?;
+        if !enables.iter().any(|v| v.as_str() == Some(member)) {
+            enables.push(member);
+        }
+        Ok(())
+    }
+
+    fn dependency_table(&mut self, dev: bool) -> Result<&mut Table, ManifestError> {
+        let key = if dev {
+            "dev-dependencies"
+        } else {
+            "dependencies"
+        };
+        self.doc
+            .entry(key)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| ManifestError::ValidationError(format!("[{}] is not a table", key)))
+    }
+}
+
+impl std::fmt::Display for ManifestEditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+/// Render a dependency as it would be written by hand in Gust.toml.
+fn dependency_to_inline(dep: &Dependency) -> InlineTable {
+    let mut table = InlineTable::new();
+
+    if let Some(path) = &dep.path {
+        table.insert("path", path.display().to_string().into());
+    }
+    if let Some(git) = &dep.git {
+        table.insert("git", git.as_str().into());
+    }
+    if let Some(branch) = &dep.branch {
+        table.insert("branch", branch.as_str().into());
+    }
+    if let Some(tag) = &dep.tag {
+        table.insert("tag", tag.as_str().into());
+    }
+    if let Some(revision) = &dep.revision {
+        table.insert("revision", revision.as_str().into());
+    }
+    if let Some(version) = dep.requirement() {
+        table.insert("version", version.into());
+    }
+    if !dep.features.is_empty() {
+        let features: Array = dep.features.iter().map(String::as_str).collect();
+        table.insert("features", features.into());
+    }
+    if dep.optional {
+        table.insert("optional", true.into());
+    }
+    if let Some(group) = &dep.group {
+        table.insert("group", group.as_str().into());
+    }
+    if let Some(lfs) = dep.lfs {
+        table.insert("lfs", lfs.into());
+    }
+    if !dep.products.is_empty() {
+        let products: Array = dep.products.iter().map(String::as_str).collect();
+        table.insert("products", products.into());
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"# My app
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+# Logging backend
+swift-log = { git = "https://github.com/apple/swift-log.git", tag = "1.5.0" }
+swift-nio = { git = "https://github.com/apple/swift-nio.git", tag = "1.5.0" } # pinned
+
+[dev-dependencies]
+swift-testing = { git = "https://github.com/apple/swift-testing.git", tag = "0.1.0" }
+"#;
+
+    #[test]
+    fn test_set_tag_only_changes_target_dependency() {
+        let mut editor = ManifestEditor::parse(MANIFEST).unwrap();
+        assert!(editor.set_dependency_tag("swift-log", "1.6.0"));
+
+        let out = editor.to_string();
+        assert!(out.contains(
+            r#"swift-log = { git = "https://github.com/apple/swift-log.git", tag = "1.6.0" }"#
+        ));
+        // Same old tag on another dependency is untouched
+        assert!(out.contains(
+            r#"swift-nio = { git = "https://github.com/apple/swift-nio.git", tag = "1.5.0" } # pinned"#
+        ));
+        assert!(out.contains("# My app\n"));
+        assert!(out.contains("# Logging backend\n"));
+        assert!(!editor.set_dependency_tag("missing", "1.0.0"));
+    }
+
+    #[test]
+    fn test_add_and_remove_preserve_comments() {
+        let mut editor = ManifestEditor::parse(MANIFEST).unwrap();
+
+        let dep = Dependency::git("vapor", "https://github.com/vapor/vapor.git").with_tag("4.90.0");
+        editor.add_dependency(&dep, false).unwrap();
+        assert!(editor.add_dependency(&dep, true).is_err());
+
+        assert!(editor.remove_dependency("swift-testing"));
+        assert!(!editor.remove_dependency("swift-testing"));
+
+        let out = editor.to_string();
+        assert!(out
+            .contains(r#"vapor = { git = "https://github.com/vapor/vapor.git", tag = "4.90.0" }"#));
+        assert!(!out.contains("swift-testing ="));
+        assert!(out.contains("# Logging backend\n"));
+        assert!(out.contains("# pinned"));
+
+        // The result still parses as a manifest
+        let raw: crate::RawGustToml = toml::from_str(&out).unwrap();
+        assert_eq!(raw.dependencies.len(), 3);
+    }
+
+    #[test]
+    fn test_add_creates_missing_table() {
+        let mut editor = ManifestEditor::parse("[package]\nname = \"app\"\n").unwrap();
+        editor
+            .add_dependency(&Dependency::path("local", "../local"), true)
+            .unwrap();
+
+        assert!(editor
+            .to_string()
+            .contains("[dev-dependencies]\nlocal = { path = \"../local\" }\n"));
+    }
+
+    #[test]
+    fn test_add_optional_dependency_under_feature() {
+        let mut editor =
+            ManifestEditor::parse("[package]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+        let mut dep = Dependency::git("yams", "https://github.com/jpsim/Yams.git");
+        dep.optional = true;
+        editor.add_dependency(&dep, false).unwrap();
+        editor.add_to_feature("yaml", "yams").unwrap();
+        editor.add_to_feature("yaml", "yams").unwrap();
+
+        let out = editor.to_string();
+        assert!(out
+            .contains(r#"yams = { git = "https://github.com/jpsim/Yams.git", optional = true }"#));
+        assert!(out.contains("[features]\nyaml = [\"yams\"]\n"));
+
+        let raw: crate::RawGustToml = toml::from_str(&out).unwrap();
+        assert_eq!(raw.features["yaml"], vec!["yams".to_string()]);
+    }
+}
@@ -0,0 +1,287 @@
+//! Cargo-style features for the root package.
+//!
+//! A `[features]` table maps each feature to the optional dependencies and
+//! other features it turns on. `default` lists the features enabled unless
+//! `--no-default-features` is passed. An optional dependency's name can also
+//! be requested directly as a feature.
+//!
+//! Dependencies with a `group` (such as "bench") are left out unless their
+//! group is selected, along with the targets that need them.
+
+use crate::ManifestError;
+use gust_types::Manifest;
+use std::collections::BTreeSet;
+
+/// Name of the feature enabled by default.
+const DEFAULT_FEATURE: &str = "default";
+
+/// Environment variable carrying the enabled features, comma-separated, to
+/// the generated Package.swift, which adds optional dependencies by it.
+pub const FEATURES_ENV: &str = "GUST_FEATURES";
+
+/// The features and optional dependencies enabled for a build.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureSet {
+    /// Enabled features, excluding `default`
+    features: BTreeSet<String>,
+    /// Optional dependencies turned on by the enabled features
+    dependencies: BTreeSet<String>,
+    /// Selected dependency groups
+    groups: BTreeSet<String>,
+}
+
+impl FeatureSet {
+    /// Resolve requested features against the manifest's `[features]` table.
+    ///
+    /// Fails on a name that is neither a feature nor an optional dependency.
+    pub fn resolve(
+        manifest: &Manifest,
+        requested: &[String],
+        default_features: bool,
+    ) -> Result<Self, ManifestError> {
+        let mut set = Self::default();
+        let mut pending: Vec<String> = requested.to_vec();
+
+        if default_features && manifest.features.contains_key(DEFAULT_FEATURE) {
+            pending.push(DEFAULT_FEATURE.to_string());
+        }
+
+        while let Some(name) = pending.pop() {
+            if let Some(enables) = manifest.features.get(&name) {
+                if name == DEFAULT_FEATURE || set.features.insert(name.clone()) {
+                    pending.extend(enables.iter().cloned());
+                }
+                // An optional dependency may share its feature's name
+                if is_optional_dependency(manifest, &name) {
+                    set.dependencies.insert(name);
+                }
+            } else if is_optional_dependency(manifest, &name) {
+                set.features.insert(name.clone());
+                set.dependencies.insert(name);
+            } else {
+                return Err(ManifestError::ValidationError(format!(
+                    "Unknown feature '{}'",
+                    name
+                )));
+            }
+        }
+
+        Ok(set)
+    }
+
+    /// Also include the dependencies in `groups`.
+    ///
+    /// Fails on a group no dependency belongs to.
+    pub fn with_groups(
+        mut self,
+        manifest: &Manifest,
+        groups: &[String],
+    ) -> Result<Self, ManifestError> {
+        for group in groups {
+            let known = manifest
+                .dependencies
+                .values()
+                .any(|d| d.group.as_ref() == Some(group));
+            if !known {
+                return Err(ManifestError::ValidationError(format!(
+                    "Unknown dependency group '{}'",
+                    group
+                )));
+            }
+            self.groups.insert(group.clone());
+        }
+        Ok(self)
+    }
+
+    /// Enabled feature names, sorted.
+    pub fn features(&self) -> Vec<String> {
+        self.features.iter().cloned().collect()
+    }
+
+    /// Check whether no features are enabled.
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Swift compilation condition for a feature, e.g. `json-output`
+    /// becomes `FEATURE_JSON_OUTPUT`.
+    pub fn compilation_condition(feature: &str) -> String {
+        let name: String = feature
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("FEATURE_{}", name)
+    }
+
+    /// Compilation conditions for every enabled feature.
+    pub fn compilation_conditions(&self) -> Vec<String> {
+        self.features
+            .iter()
+            .map(|f| Self::compilation_condition(f))
+            .collect()
+    }
+
+    /// Copy of `manifest` without the grouped dependencies that aren't
+    /// selected, ready for Package.swift generation.
+    ///
+    /// Targets that need a group that isn't selected are left out, as are
+    /// targets depending on those. Optional dependencies all stay: the
+    /// generated Package.swift only adds each one while its feature is on.
+    pub fn apply(&self, manifest: &Manifest) -> Manifest {
+        self.without(manifest, BTreeSet::new())
+    }
+
+    /// Like [`apply`](Self::apply), also leaving out the optional
+    /// dependencies no enabled feature turns on, which are neither resolved
+    /// nor locked. Targets stop depending on them too.
+    pub fn select(&self, manifest: &Manifest) -> Manifest {
+        let disabled = manifest
+            .dependencies
+            .iter()
+            .filter(|(name, dep)| dep.optional && !self.dependencies.contains(*name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.without(manifest, disabled)
+    }
+
+    fn without(&self, manifest: &Manifest, disabled: BTreeSet<String>) -> Manifest {
+        let mut manifest = manifest.clone();
+        let mut unselected: BTreeSet<String> = manifest
+            .dependencies
+            .iter()
+            .filter(|(_, dep)| dep.group.as_ref().is_some_and(|g| !self.groups.contains(g)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        manifest
+            .dependencies
+            .retain(|name, _| !disabled.contains(name) && !unselected.contains(name));
+
+        loop {
+            let before = manifest.targets.len();
+            manifest.targets.retain(|target| {
+                let needs_group = target.dependencies.iter().any(|d| unselected.contains(d));
+                if needs_group {
+                    unselected.insert(target.name.clone());
+                }
+                !needs_group
+            });
+            if manifest.targets.len() == before {
+                break;
+            }
+        }
+        for target in &mut manifest.targets {
+            target.dependencies.retain(|d| !disabled.contains(d));
+        }
+        manifest
+    }
+}
+
+fn is_optional_dependency(manifest: &Manifest, name: &str) -> bool {
+    manifest.dependencies.get(name).is_some_and(|d| d.optional)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gust_types::Dependency;
+
+    fn manifest() -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "swift-log".to_string(),
+            Dependency::git("swift-log", "https://github.com/apple/swift-log.git"),
+        );
+        for name in ["swift-nio", "yams"] {
+            let mut dep = Dependency::git(name, format!("https://example.com/{}.git", name));
+            dep.optional = true;
+            manifest.dependencies.insert(name.to_string(), dep);
+        }
+        manifest
+            .features
+            .insert("default".to_string(), vec!["server".to_string()]);
+        manifest
+            .features
+            .insert("server".to_string(), vec!["swift-nio".to_string()]);
+        manifest.features.insert(
+            "full".to_string(),
+            vec!["server".to_string(), "yams".to_string()],
+        );
+        manifest
+    }
+
+    #[test]
+    fn test_resolve_features() {
+        let manifest = manifest();
+
+        let defaults = FeatureSet::resolve(&manifest, &[], true).unwrap();
+        // Optional dependencies count as features once enabled
+        assert_eq!(defaults.features(), vec!["server", "swift-nio"]);
+        let selected = defaults.select(&manifest);
+        assert!(selected.dependencies.contains_key("swift-log"));
+        assert!(selected.dependencies.contains_key("swift-nio"));
+        assert!(!selected.dependencies.contains_key("yams"));
+        // Package.swift keeps them all, guarded by their features
+        assert_eq!(defaults.apply(&manifest).dependencies.len(), 3);
+
+        let none = FeatureSet::resolve(&manifest, &[], false).unwrap();
+        assert!(none.is_empty());
+        assert_eq!(none.select(&manifest).dependencies.len(), 1);
+
+        let full = FeatureSet::resolve(&manifest, &["full".to_string()], false).unwrap();
+        assert_eq!(full.features(), vec!["full", "server", "swift-nio", "yams"]);
+        assert_eq!(full.select(&manifest).dependencies.len(), 3);
+
+        assert!(FeatureSet::resolve(&manifest, &["nope".to_string()], true).is_err());
+    }
+
+    #[test]
+    fn test_groups_left_out_unless_selected() {
+        let mut manifest = manifest();
+        let mut bench = Dependency::git("benchmark", "https://example.com/benchmark.git");
+        bench.group = Some("bench".to_string());
+        manifest.dependencies.insert("benchmark".to_string(), bench);
+        for (name, deps) in [
+            ("App", vec!["swift-log"]),
+            ("Benchmarks", vec!["App", "benchmark"]),
+            ("BenchmarkRunner", vec!["Benchmarks"]),
+        ] {
+            manifest.targets.push(gust_types::Target {
+                dependencies: deps.into_iter().map(String::from).collect(),
+                ..gust_types::Target::library(name)
+            });
+        }
+        let target_names =
+            |m: &Manifest| m.targets.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+
+        let defaults = FeatureSet::resolve(&manifest, &[], true).unwrap();
+        let applied = defaults.apply(&manifest);
+        assert!(!applied.dependencies.contains_key("benchmark"));
+        // Targets needing the group go too, including indirectly
+        assert_eq!(target_names(&applied), vec!["App"]);
+
+        let with_bench = defaults
+            .with_groups(&manifest, &["bench".to_string()])
+            .unwrap()
+            .apply(&manifest);
+        assert!(with_bench.dependencies.contains_key("benchmark"));
+        assert_eq!(target_names(&with_bench).len(), 3);
+
+        assert!(FeatureSet::default()
+            .with_groups(&manifest, &["docs".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn test_compilation_condition() {
+        assert_eq!(
+            FeatureSet::compilation_condition("json-output"),
+            "FEATURE_JSON_OUTPUT"
+        );
+    }
+}
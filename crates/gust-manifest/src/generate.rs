@@ -1,10 +1,43 @@
 //! Package.swift generation from Gust manifests.
 
-use gust_types::{Dependency, Manifest, Target, TargetType};
+use crate::features::{FeatureSet, FEATURES_ENV};
+use gust_types::{
+    CommandIntent, Dependency, DependencyBuildSettings, Manifest, NetworkScope, PluginCapability,
+    PluginPermission, ResourceLocalization, ResourceRule, SystemPackageProvider, Target,
+    TargetType,
+};
 use std::path::Path;
 
+/// Package providing the macro APIs that `.macro` targets build against.
+pub(crate) const SWIFT_SYNTAX: &str = "swift-syntax";
+const SWIFT_SYNTAX_URL: &str = "https://github.com/swiftlang/swift-syntax.git";
+const SWIFT_SYNTAX_VERSION: &str = "509.0.0";
+
+/// First line of the header on every Package.swift generated from
+/// Gust.toml, which is how a generated one is told from a hand-written one.
+pub const GENERATED_HEADER: &str = "// This file is auto-generated by Gust. Do not edit manually.";
+
 /// Generate a Package.swift file from a Gust manifest.
 pub fn generate_package_swift(manifest: &Manifest) -> String {
+    render_package_swift(manifest, true)
+}
+
+/// Whether a Package.swift's `content` was generated from Gust.toml, going
+/// by the [`GENERATED_HEADER`] right after the tools version line.
+pub fn is_generated_package_swift(content: &str) -> bool {
+    content
+        .lines()
+        .take(3)
+        .any(|line| line.trim_end() == GENERATED_HEADER)
+}
+
+/// Generate a Package.swift that is the package's own manifest rather than
+/// a copy of Gust.toml, so it has no "do not edit" header.
+pub fn generate_native_package_swift(manifest: &Manifest) -> String {
+    render_package_swift(manifest, false)
+}
+
+fn render_package_swift(manifest: &Manifest, generated: bool) -> String {
     let mut out = String::new();
 
     // Swift tools version
@@ -13,13 +46,29 @@ pub fn generate_package_swift(manifest: &Manifest) -> String {
         manifest.package.swift_tools_version
     ));
 
-    // Header comment
-    out.push_str("// This file is auto-generated by Gust. Do not edit manually.\n");
-    out.push_str("// To modify dependencies, edit Gust.toml instead.\n\n");
+    if generated {
+        out.push_str(GENERATED_HEADER);
+        out.push('\n');
+        out.push_str("// To modify dependencies, edit Gust.toml instead.\n");
+    }
+    out.push('\n');
 
-    out.push_str("import PackageDescription\n\n");
+    out.push_str("import PackageDescription\n");
+    if has_macro_targets(manifest) {
+        out.push_str("import CompilerPluginSupport\n");
+    }
+    out.push('\n');
     out.push_str("let package = Package(\n");
-    out.push_str(&format!("    name: \"{}\",\n", manifest.package.name));
+    out.push_str(&format!(
+        "    name: {},\n",
+        swift_string(&manifest.package.name)
+    ));
+    if let Some(localization) = &manifest.package.default_localization {
+        out.push_str(&format!(
+            "    defaultLocalization: {},\n",
+            swift_string(localization)
+        ));
+    }
 
     // Platforms (if specified)
     generate_platforms(&mut out, manifest);
@@ -34,6 +83,8 @@ pub fn generate_package_swift(manifest: &Manifest) -> String {
     generate_targets(&mut out, manifest);
 
     out.push_str(")\n");
+
+    generate_optional_dependencies(&mut out, manifest);
     out
 }
 
@@ -92,14 +143,16 @@ fn generate_products(out: &mut String, manifest: &Manifest) {
             match target.target_type {
                 TargetType::Library => {
                     out.push_str(&format!(
-                        "        .library(name: \"{}\", targets: [\"{}\"]),\n",
-                        target.name, target.name
+                        "        .library(name: {}, targets: [{}]),\n",
+                        swift_string(&target.name),
+                        swift_string(&target.name)
                     ));
                 }
                 TargetType::Executable => {
                     out.push_str(&format!(
-                        "        .executable(name: \"{}\", targets: [\"{}\"]),\n",
-                        target.name, target.name
+                        "        .executable(name: {}, targets: [{}]),\n",
+                        swift_string(&target.name),
+                        swift_string(&target.name)
                     ));
                 }
                 _ => {}
@@ -110,24 +163,53 @@ fn generate_products(out: &mut String, manifest: &Manifest) {
     }
 }
 
+fn has_macro_targets(manifest: &Manifest) -> bool {
+    manifest
+        .targets
+        .iter()
+        .any(|t| t.target_type == TargetType::Macro)
+}
+
+/// Whether swift-syntax must be added because macros need it but it isn't declared.
+fn needs_implicit_swift_syntax(manifest: &Manifest) -> bool {
+    has_macro_targets(manifest) && !manifest.dependencies.contains_key(SWIFT_SYNTAX)
+}
+
 fn generate_dependencies(out: &mut String, manifest: &Manifest) {
-    if manifest.dependencies.is_empty() {
+    let implicit_swift_syntax = needs_implicit_swift_syntax(manifest);
+    let required: Vec<&Dependency> = manifest
+        .dependencies
+        .values()
+        .filter(|dep| !dep.optional)
+        .collect();
+    if required.is_empty() && !implicit_swift_syntax {
         return;
     }
 
     out.push_str("    dependencies: [\n");
 
-    for dep in manifest.dependencies.values() {
+    for dep in required {
         out.push_str(&format_dependency(dep));
     }
 
+    if implicit_swift_syntax {
+        out.push_str(&format!(
+            "        .package(url: {}, from: {}),\n",
+            swift_string(SWIFT_SYNTAX_URL),
+            swift_string(SWIFT_SYNTAX_VERSION)
+        ));
+    }
+
     out.push_str("    ],\n");
 }
 
 fn format_dependency(dep: &Dependency) -> String {
     // Path dependency
     if let Some(path) = &dep.path {
-        return format!("        .package(path: \"{}\"),\n", path.display());
+        return format!(
+            "        .package(path: {}),\n",
+            swift_string(path.display())
+        );
     }
 
     // Git dependency
@@ -135,8 +217,9 @@ fn format_dependency(dep: &Dependency) -> String {
         // Exact revision
         if let Some(rev) = &dep.revision {
             return format!(
-                "        .package(url: \"{}\", revision: \"{}\"),\n",
-                git, rev
+                "        .package(url: {}, revision: {}),\n",
+                swift_string(git),
+                swift_string(rev)
             );
         }
 
@@ -145,29 +228,33 @@ fn format_dependency(dep: &Dependency) -> String {
             // If tag looks like a version, use "from:"
             if is_semver_like(tag) {
                 return format!(
-                    "        .package(url: \"{}\", from: \"{}\"),\n",
-                    git,
-                    tag.trim_start_matches('v')
+                    "        .package(url: {}, from: {}),\n",
+                    swift_string(git),
+                    swift_string(tag.trim_start_matches('v'))
                 );
             }
             // Otherwise use exact
             return format!(
-                "        .package(url: \"{}\", exact: \"{}\"),\n",
-                git,
-                tag.trim_start_matches('v')
+                "        .package(url: {}, exact: {}),\n",
+                swift_string(git),
+                swift_string(tag.trim_start_matches('v'))
             );
         }
 
         // Branch
         if let Some(branch) = &dep.branch {
             return format!(
-                "        .package(url: \"{}\", branch: \"{}\"),\n",
-                git, branch
+                "        .package(url: {}, branch: {}),\n",
+                swift_string(git),
+                swift_string(branch)
             );
         }
 
         // Default to main branch
-        return format!("        .package(url: \"{}\", branch: \"main\"),\n", git);
+        return format!(
+            "        .package(url: {}, branch: \"main\"),\n",
+            swift_string(git)
+        );
     }
 
     // Version requirement (for registry dependencies)
@@ -184,6 +271,40 @@ fn format_dependency(dep: &Dependency) -> String {
     format!("        // TODO: Add dependency for {}\n", dep.name)
 }
 
+/// Swift appended to a dependency's Package.swift that adds the flags from
+/// its `[dependencies.<name>.build]` table to each of the package's targets
+/// that is compiled.
+pub fn dependency_build_overlay(name: &str, settings: &DependencyBuildSettings) -> String {
+    let flags = |flags: &[String]| {
+        flags
+            .iter()
+            .map(swift_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut out = format!(
+        "\n// Added by Gust from [dependencies.{}.build] in the root Gust.toml\n",
+        name
+    );
+    out.push_str(
+        "for target in package.targets where ![.binary, .plugin, .system].contains(target.type) {\n",
+    );
+    if !settings.swift_flags.is_empty() {
+        out.push_str(&format!(
+            "    target.swiftSettings = (target.swiftSettings ?? []) + [.unsafeFlags([{}])]\n",
+            flags(&settings.swift_flags)
+        ));
+    }
+    if !settings.c_flags.is_empty() {
+        out.push_str(&format!(
+            "    target.cSettings = (target.cSettings ?? []) + [.unsafeFlags([{}])]\n",
+            flags(&settings.c_flags)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
 fn is_semver_like(s: &str) -> bool {
     let s = s.trim_start_matches('v');
     s.chars()
@@ -211,30 +332,89 @@ fn generate_target(out: &mut String, target: &Target, manifest: &Manifest) {
         TargetType::Plugin => ".plugin",
         TargetType::SystemLibrary => ".systemLibrary",
         TargetType::Binary => ".binaryTarget",
+        TargetType::Macro => ".macro",
     };
 
     out.push_str(&format!("        {}(\n", target_type));
-    out.push_str(&format!("            name: \"{}\"", target.name));
-
-    // Dependencies for this target
-    let target_deps: Vec<&String> = if target.dependencies.is_empty() {
-        // If no explicit target deps, use all package deps for non-test targets
-        if target.target_type != TargetType::Test {
-            manifest.dependencies.keys().collect()
-        } else {
-            // Test targets depend on the main target
-            let main_target = manifest.targets.iter().find(|t| {
-                t.target_type == TargetType::Library || t.target_type == TargetType::Executable
-            });
-            if let Some(main) = main_target {
-                vec![&main.name]
-            } else {
-                vec![]
-            }
+    out.push_str(&format!("            name: {}", swift_string(&target.name)));
+
+    // Binary targets only take a location
+    if target.target_type == TargetType::Binary {
+        if let (Some(url), Some(checksum)) = (&target.url, &target.checksum) {
+            out.push_str(&format!(",\n            url: {}", swift_string(url)));
+            out.push_str(&format!(
+                ",\n            checksum: {}",
+                swift_string(checksum)
+            ));
+        } else if let Some(path) = &target.path {
+            out.push_str(&format!(
+                ",\n            path: {}",
+                swift_string(path.display())
+            ));
         }
-    } else {
-        target.dependencies.iter().collect()
-    };
+        out.push_str("\n        ),\n");
+        return;
+    }
+
+    // System libraries only describe where the library comes from
+    if target.target_type == TargetType::SystemLibrary {
+        if let Some(path) = &target.path {
+            out.push_str(&format!(
+                ",\n            path: {}",
+                swift_string(path.display())
+            ));
+        }
+        if let Some(pkg_config) = &target.pkg_config {
+            out.push_str(&format!(
+                ",\n            pkgConfig: {}",
+                swift_string(pkg_config)
+            ));
+        }
+        if !target.providers.is_empty() {
+            let providers: Vec<String> = target
+                .providers
+                .iter()
+                .map(|provider| {
+                    let (manager, packages) = match provider {
+                        SystemPackageProvider::Brew(packages) => ("brew", packages),
+                        SystemPackageProvider::Apt(packages) => ("apt", packages),
+                    };
+                    let packages: Vec<String> = packages.iter().map(swift_string).collect();
+                    format!(".{}([{}])", manager, packages.join(", "))
+                })
+                .collect();
+            out.push_str(&format!(
+                ",\n            providers: [{}]",
+                providers.join(", ")
+            ));
+        }
+        out.push_str("\n        ),\n");
+        return;
+    }
+
+    if target.target_type == TargetType::Plugin {
+        let capability = target
+            .plugin_capability
+            .as_ref()
+            .unwrap_or(&PluginCapability::BuildTool);
+        out.push_str(&format!(
+            ",\n            capability: {}",
+            swift_plugin_capability(capability)
+        ));
+    }
+
+    // Dependencies for this target; optional ones are added by
+    // generate_optional_dependencies
+    let swift_syntax = SWIFT_SYNTAX.to_string();
+    let mut target_deps: Vec<&String> = target_dependency_names(target, manifest)
+        .into_iter()
+        .filter(|name| optional_package(name, manifest).is_none())
+        .collect();
+
+    // Macros always build against swift-syntax
+    if target.target_type == TargetType::Macro && !target_deps.contains(&&swift_syntax) {
+        target_deps.push(&swift_syntax);
+    }
 
     if !target_deps.is_empty() {
         out.push_str(",\n            dependencies: [\n");
@@ -242,13 +422,40 @@ fn generate_target(out: &mut String, target: &Target, manifest: &Manifest) {
             // Check if it's an internal target or external dependency
             let is_internal = manifest.targets.iter().any(|t| &t.name == dep_name);
             if is_internal {
-                out.push_str(&format!("                \"{}\",\n", dep_name));
+                out.push_str(&format!("                {},\n", swift_string(dep_name)));
+            } else if target.target_type == TargetType::Macro && *dep_name == SWIFT_SYNTAX {
+                for product in ["SwiftSyntaxMacros", "SwiftCompilerPlugin"] {
+                    out.push_str(&format!(
+                        "                .product(name: {}, package: {}),\n",
+                        swift_string(product),
+                        swift_string(SWIFT_SYNTAX)
+                    ));
+                }
+            } else if let Some(dep) = manifest.dependencies.get(dep_name.as_str()) {
+                for product in dependency_products(dep_name, dep) {
+                    out.push_str(&format!(
+                        "                .product(name: {}, package: {}),\n",
+                        swift_string(&product),
+                        swift_string(infer_package_name(dep_name))
+                    ));
+                }
+            } else if let Some((package, _)) = manifest
+                .dependencies
+                .iter()
+                .find(|(name, dep)| dependency_products(name, dep).contains(dep_name))
+            {
+                // A product of a dependency, named directly
+                out.push_str(&format!(
+                    "                .product(name: {}, package: {}),\n",
+                    swift_string(dep_name),
+                    swift_string(infer_package_name(package))
+                ));
             } else {
                 // External dependency - need to find product name
                 out.push_str(&format!(
-                    "                .product(name: \"{}\", package: \"{}\"),\n",
-                    infer_product_name(dep_name),
-                    infer_package_name(dep_name)
+                    "                .product(name: {}, package: {}),\n",
+                    swift_string(infer_product_name(dep_name)),
+                    swift_string(infer_package_name(dep_name))
                 ));
             }
         }
@@ -257,27 +464,241 @@ fn generate_target(out: &mut String, target: &Target, manifest: &Manifest) {
 
     // Path
     if let Some(path) = &target.path {
-        out.push_str(&format!(",\n            path: \"{}\"", path.display()));
+        out.push_str(&format!(
+            ",\n            path: {}",
+            swift_string(path.display())
+        ));
+    }
+
+    if !target.exclude.is_empty() {
+        out.push_str(&format!(
+            ",\n            exclude: [{}]",
+            quoted_paths(&target.exclude)
+        ));
+    }
+    if let Some(sources) = &target.sources {
+        out.push_str(&format!(
+            ",\n            sources: [{}]",
+            quoted_paths(sources)
+        ));
     }
 
     // Resources
     if !target.resources.is_empty() {
-        out.push_str(",\n            resources: [\n");
-        for resource in &target.resources {
+        let resources: Vec<String> = target
+            .resources
+            .iter()
+            .map(|resource| {
+                let path = resource.path.display();
+                match (resource.rule, resource.localization) {
+                    (ResourceRule::Copy, _) => format!(".copy({})", swift_string(&path)),
+                    (ResourceRule::EmbedInCode, _) => {
+                        format!(".embedInCode({})", swift_string(&path))
+                    }
+                    (ResourceRule::Process, None) => format!(".process({})", swift_string(&path)),
+                    (ResourceRule::Process, Some(ResourceLocalization::Default)) => {
+                        format!(".process({}, localization: .default)", swift_string(&path))
+                    }
+                    (ResourceRule::Process, Some(ResourceLocalization::Base)) => {
+                        format!(".process({}, localization: .base)", swift_string(&path))
+                    }
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            ",\n            resources: [\n                {}\n            ]",
+            resources.join(",\n                ")
+        ));
+    }
+
+    out.push_str("\n        ),\n");
+}
+
+/// Names a target depends on: its own list, or else every package
+/// dependency for a non-test target and the main target for a test target.
+fn target_dependency_names<'a>(target: &'a Target, manifest: &'a Manifest) -> Vec<&'a String> {
+    if !target.dependencies.is_empty() {
+        return target.dependencies.iter().collect();
+    }
+    if target.target_type != TargetType::Test {
+        return manifest.dependencies.keys().collect();
+    }
+    manifest
+        .targets
+        .iter()
+        .find(|t| t.target_type == TargetType::Library || t.target_type == TargetType::Executable)
+        .map(|main| vec![&main.name])
+        .unwrap_or_default()
+}
+
+/// The optional dependency `name` refers to, either by its own name or by
+/// one of its products.
+fn optional_package<'a>(name: &str, manifest: &'a Manifest) -> Option<(&'a str, &'a Dependency)> {
+    if manifest.targets.iter().any(|t| t.name == name) {
+        return None;
+    }
+    manifest
+        .dependencies
+        .iter()
+        .find(|(package, dep)| {
+            dep.optional
+                && (package.as_str() == name
+                    || dependency_products(package, dep).iter().any(|p| p == name))
+        })
+        .map(|(package, dep)| (package.as_str(), dep))
+}
+
+/// Swift that adds each optional dependency, and its products to the
+/// targets using them, while a feature turns it on.
+///
+/// The enabled features come from [`FEATURES_ENV`], which `gust build` sets;
+/// other tools opening the package get the default features.
+fn generate_optional_dependencies(out: &mut String, manifest: &Manifest) {
+    let mut optional: Vec<(&String, &Dependency)> = manifest
+        .dependencies
+        .iter()
+        .filter(|(_, dep)| dep.optional)
+        .collect();
+    if optional.is_empty() {
+        return;
+    }
+    optional.sort_by_key(|(name, _)| *name);
+
+    let defaults = FeatureSet::resolve(manifest, &[], true)
+        .map(|set| set.features())
+        .unwrap_or_default();
+    out.push_str(&format!(
+        "\nlet gustFeatures = Set(\n    (Context.environment[{}] ?? {}).split(separator: \",\").map(String.init)\n)\n",
+        swift_string(FEATURES_ENV),
+        swift_string(defaults.join(","))
+    ));
+
+    for (name, dep) in optional {
+        out.push_str(&format!(
+            "\nif gustFeatures.contains({}) {{\n",
+            swift_string(name)
+        ));
+        let package = format_dependency(dep);
+        match package.trim().strip_suffix(',') {
+            Some(package) => {
+                out.push_str(&format!("    package.dependencies.append({})\n", package))
+            }
+            None => out.push_str(&format!("    {}\n", package.trim())),
+        }
+
+        for target in &manifest.targets {
+            let products: Vec<String> = target_dependency_names(target, manifest)
+                .into_iter()
+                .filter(|d| optional_package(d, manifest).is_some_and(|(p, _)| p == name))
+                .flat_map(|d| {
+                    if d == name {
+                        dependency_products(name, dep)
+                    } else {
+                        vec![d.clone()]
+                    }
+                })
+                .collect();
+            if products.is_empty() {
+                continue;
+            }
             out.push_str(&format!(
-                "                .process(\"{}\")",
-                resource.display()
+                "    package.targets.first {{ $0.name == {} }}?.dependencies += [\n",
+                swift_string(&target.name)
             ));
+            for product in products {
+                out.push_str(&format!(
+                    "        .product(name: {}, package: {}),\n",
+                    swift_string(&product),
+                    swift_string(infer_package_name(name))
+                ));
+            }
+            out.push_str("    ]\n");
         }
-        out.push_str("\n            ]");
+        out.push_str("}\n");
     }
+}
 
-    out.push_str("\n        ),\n");
+/// `s` as a Swift string literal, quotes included, so names, paths and
+/// reasons from Gust.toml can't end the literal or interpolate.
+fn swift_string(s: impl std::fmt::Display) -> String {
+    let mut out = String::from("\"");
+    for c in s.to_string().chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `"a", "b"` for a Swift array literal.
+fn quoted_paths(paths: &[std::path::PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| swift_string(p.display()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A plugin capability as Package.swift source.
+fn swift_plugin_capability(capability: &PluginCapability) -> String {
+    let PluginCapability::Command(command) = capability else {
+        return ".buildTool()".to_string();
+    };
+    let intent = match &command.intent {
+        CommandIntent::DocumentationGeneration => ".documentationGeneration()".to_string(),
+        CommandIntent::SourceCodeFormatting => ".sourceCodeFormatting()".to_string(),
+        CommandIntent::Custom { verb, description } => format!(
+            ".custom(verb: {}, description: {})",
+            swift_string(verb),
+            swift_string(description)
+        ),
+    };
+    let permissions: Vec<String> = command
+        .permissions
+        .iter()
+        .map(|permission| match permission {
+            PluginPermission::WriteToPackageDirectory { reason } => {
+                format!(".writeToPackageDirectory(reason: {})", swift_string(reason))
+            }
+            PluginPermission::AllowNetworkConnections { scope, reason } => {
+                let scope = match scope {
+                    NetworkScope::None => ".none".to_string(),
+                    NetworkScope::Local(ports) => format!(
+                        ".local(ports: [{}])",
+                        ports
+                            .iter()
+                            .map(|p| p.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    NetworkScope::All => ".all()".to_string(),
+                    NetworkScope::Docker => ".docker".to_string(),
+                };
+                format!(
+                    ".allowNetworkConnections(scope: {}, reason: {})",
+                    scope,
+                    swift_string(reason)
+                )
+            }
+        })
+        .collect();
+    format!(
+        ".command(intent: {}, permissions: [{}])",
+        intent,
+        permissions.join(", ")
+    )
 }
 
 /// Infer the product name from a dependency name.
 /// e.g., "swift-log" -> "Logging", "vapor" -> "Vapor"
-fn infer_product_name(dep_name: &str) -> String {
+pub fn infer_product_name(dep_name: &str) -> String {
     // Common package name -> product name mappings
     match dep_name {
         "swift-log" => "Logging".to_string(),
@@ -305,15 +726,34 @@ fn infer_product_name(dep_name: &str) -> String {
     }
 }
 
+/// Products targets import from the dependency `name`: the ones it lists,
+/// or else its inferred primary product.
+pub fn dependency_products(name: &str, dep: &Dependency) -> Vec<String> {
+    if dep.products.is_empty() {
+        vec![infer_product_name(name)]
+    } else {
+        dep.products.clone()
+    }
+}
+
 /// Infer the package name from a dependency name (usually same as dep name).
 fn infer_package_name(dep_name: &str) -> String {
     dep_name.to_string()
 }
 
 /// Write a Package.swift file to the given directory.
+///
+/// Nothing is written when the file already holds exactly what would be
+/// generated. That keeps its mtime, so SwiftPM doesn't re-resolve on no-op
+/// builds, while a hand-edited or checked-out Package.swift is still
+/// replaced.
 pub fn write_package_swift(manifest: &Manifest, dir: &Path) -> std::io::Result<()> {
     let content = generate_package_swift(manifest);
-    std::fs::write(dir.join("Package.swift"), content)
+    let package_swift = dir.join("Package.swift");
+    if std::fs::read(&package_swift).is_ok_and(|current| current == content.as_bytes()) {
+        return Ok(());
+    }
+    std::fs::write(&package_swift, content)
 }
 
 #[cfg(test)]
@@ -340,6 +780,82 @@ mod tests {
         assert!(output.contains(".executableTarget"));
     }
 
+    #[test]
+    fn test_generated_package_swift_is_recognized() {
+        let manifest = Manifest {
+            package: Package {
+                name: "MyApp".to_string(),
+                version: Version::new(1, 0, 0),
+                swift_tools_version: "5.9".to_string(),
+                ..Default::default()
+            },
+            targets: vec![Target::executable("MyApp")],
+            ..Default::default()
+        };
+
+        let generated = generate_package_swift(&manifest);
+        assert!(generated.lines().nth(1) == Some(GENERATED_HEADER));
+        assert!(is_generated_package_swift(&generated));
+
+        // The package's own Package.swift, scaffolded or written by hand
+        assert!(!is_generated_package_swift(&generate_native_package_swift(
+            &manifest
+        )));
+        let hand_written = "// swift-tools-version:5.9\n\nimport PackageDescription\n\n// Gust.toml is only used for CI\nlet package = Package(name: \"MyApp\")\n";
+        assert!(!is_generated_package_swift(hand_written));
+    }
+
+    #[test]
+    fn test_unchanged_manifest_does_not_rewrite_package_swift() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut manifest = Manifest {
+            package: Package {
+                name: "MyApp".to_string(),
+                version: Version::new(1, 0, 0),
+                swift_tools_version: "5.9".to_string(),
+                ..Default::default()
+            },
+            targets: vec![Target::executable("MyApp")],
+            ..Default::default()
+        };
+        let package_swift = tmp.path().join("Package.swift");
+        let mtime = || {
+            std::fs::metadata(&package_swift)
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+
+        write_package_swift(&manifest, tmp.path()).unwrap();
+        // Backdate it so a rewrite would show even with coarse mtimes
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&package_swift)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        write_package_swift(&manifest, tmp.path()).unwrap();
+        assert_eq!(mtime(), old);
+
+        manifest.targets.push(Target::library("Core"));
+        write_package_swift(&manifest, tmp.path()).unwrap();
+        assert_ne!(mtime(), old);
+        assert!(std::fs::read_to_string(&package_swift)
+            .unwrap()
+            .contains("\"Core\""));
+
+        std::fs::remove_file(&package_swift).unwrap();
+        write_package_swift(&manifest, tmp.path()).unwrap();
+        assert!(package_swift.exists());
+
+        // An edit made outside Gust is overwritten
+        let generated = std::fs::read_to_string(&package_swift).unwrap();
+        std::fs::write(&package_swift, "// edited by hand\n").unwrap();
+        write_package_swift(&manifest, tmp.path()).unwrap();
+        assert_eq!(std::fs::read_to_string(&package_swift).unwrap(), generated);
+    }
+
     #[test]
     fn test_generate_with_dependencies() {
         let mut manifest = Manifest {
@@ -364,6 +880,140 @@ mod tests {
         assert!(output.contains("from: \"1.5.0\""));
     }
 
+    #[test]
+    fn test_optional_dependency_is_added_behind_its_feature() {
+        let mut manifest = Manifest {
+            targets: vec![Target::executable("MyApp")],
+            ..Default::default()
+        };
+        manifest.dependencies.insert(
+            "swift-log".to_string(),
+            Dependency::git("swift-log", "https://github.com/apple/swift-log.git")
+                .with_tag("1.5.0"),
+        );
+        let mut yams =
+            Dependency::git("yams", "https://github.com/jpsim/Yams.git").with_tag("5.0.0");
+        yams.optional = true;
+        manifest.dependencies.insert("yams".to_string(), yams);
+        manifest
+            .features
+            .insert("default".to_string(), vec!["yaml".to_string()]);
+        manifest
+            .features
+            .insert("yaml".to_string(), vec!["yams".to_string()]);
+
+        let output = generate_package_swift(&manifest);
+        let (package, guarded) = output.split_once("let gustFeatures").unwrap();
+        assert!(package.contains(".product(name: \"Logging\", package: \"swift-log\")"));
+        assert!(!package.contains("Yams"));
+        assert!(guarded.contains("Context.environment[\"GUST_FEATURES\"] ?? \"yaml,yams\""));
+        assert!(guarded.contains(
+            "if gustFeatures.contains(\"yams\") {\n    \
+             package.dependencies.append(.package(url: \"https://github.com/jpsim/Yams.git\", from: \"5.0.0\"))\n    \
+             package.targets.first { $0.name == \"MyApp\" }?.dependencies += [\n        \
+             .product(name: \"Yams\", package: \"yams\"),\n    ]\n}\n"
+        ));
+    }
+
+    #[test]
+    fn test_user_strings_are_escaped() {
+        let mut publish = Target {
+            target_type: TargetType::Plugin,
+            ..Target::library("Publish")
+        };
+        publish.plugin_capability = Some(PluginCapability::Command(
+            gust_types::CommandPluginCapability {
+                intent: CommandIntent::Custom {
+                    verb: "publish".to_string(),
+                    description: "Upload \"docs\" \\(now)\nfast".to_string(),
+                },
+                permissions: vec![PluginPermission::WriteToPackageDirectory {
+                    reason: "Writes C:\\docs".to_string(),
+                }],
+            },
+        ));
+        let mut sqlite = Target {
+            target_type: TargetType::SystemLibrary,
+            ..Target::library("CSQLite")
+        };
+        sqlite.pkg_config = Some("sqlite3\"), .target(name: \"X".to_string());
+        sqlite.providers = vec![SystemPackageProvider::Apt(vec!["lib\"sqlite".to_string()])];
+        let manifest = Manifest {
+            targets: vec![publish, sqlite],
+            ..Default::default()
+        };
+
+        let output = generate_package_swift(&manifest);
+        assert!(output
+            .contains(r#".custom(verb: "publish", description: "Upload \"docs\" \\(now)\nfast")"#));
+        assert!(output.contains(r#".writeToPackageDirectory(reason: "Writes C:\\docs")"#));
+        assert!(output.contains(r#"pkgConfig: "sqlite3\"), .target(name: \"X""#));
+        assert!(output.contains(r#"providers: [.apt(["lib\"sqlite"])]"#));
+    }
+
+    #[test]
+    fn test_local_network_scope_keeps_every_port() {
+        let json = serde_json::json!({
+            "name": "Tools",
+            "targets": [{
+                "name": "Serve",
+                "type": "plugin",
+                "pluginCapability": {
+                    "command": {
+                        "intent": { "custom": { "verb": "serve", "description": "Serve docs" } },
+                        "permissions": [{
+                            "allowNetworkConnections": {
+                                "scope": { "local": { "ports": [8080, 9000] } },
+                                "reason": "Serves"
+                            }
+                        }]
+                    }
+                }
+            }]
+        });
+        let manifest = crate::convert_spm_json(json).unwrap();
+
+        let output = generate_package_swift(&manifest);
+        assert!(output.contains(
+            ".allowNetworkConnections(scope: .local(ports: [8080, 9000]), reason: \"Serves\")"
+        ));
+    }
+
+    #[test]
+    fn test_recorded_product_is_imported() {
+        // As `gust add apple/swift-nio --product NIOCore` writes it
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut editor = crate::ManifestEditor::parse(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[[target]]\nname = \"app\"\ntype = \"executable\"\n",
+        )
+        .unwrap();
+        let mut nio = Dependency::git("swift-nio", "https://github.com/apple/swift-nio.git")
+            .with_tag("2.60.0");
+        nio.products = vec!["NIOCore".to_string()];
+        editor.add_dependency(&nio, false).unwrap();
+        editor
+            .add_dependency(
+                &Dependency::git("swift-log", "https://github.com/apple/swift-log.git")
+                    .with_tag("1.5.0"),
+                false,
+            )
+            .unwrap();
+        editor.save(&tmp.path().join("Gust.toml")).unwrap();
+
+        let manifest = crate::parse_gust_toml(&tmp.path().join("Gust.toml")).unwrap();
+        let output = generate_package_swift(&manifest);
+        assert!(output.contains(".product(name: \"NIOCore\", package: \"swift-nio\")"));
+        assert!(!output.contains(".product(name: \"NIO\""));
+        // Without products, the primary one
+        assert!(output.contains(".product(name: \"Logging\", package: \"swift-log\")"));
+
+        // A target naming the product directly gets it from its package
+        let mut manifest = manifest;
+        manifest.targets[0].dependencies = vec!["NIOCore".to_string()];
+        let output = generate_package_swift(&manifest);
+        assert!(output.contains(".product(name: \"NIOCore\", package: \"swift-nio\")"));
+    }
+
     #[test]
     fn test_generate_with_platforms() {
         let mut manifest = Manifest {
@@ -377,12 +1027,110 @@ mod tests {
             ..Default::default()
         };
 
-        manifest.platforms.insert("iOS".to_string(), "15.0".to_string());
-        manifest.platforms.insert("macOS".to_string(), "12.0".to_string());
+        manifest
+            .platforms
+            .insert("iOS".to_string(), "15.0".to_string());
+        manifest
+            .platforms
+            .insert("macOS".to_string(), "12.0".to_string());
 
         let output = generate_package_swift(&manifest);
         assert!(output.contains("platforms: ["));
         assert!(output.contains(".iOS(.v15_0)"));
         assert!(output.contains(".macOS(.v12_0)"));
     }
+
+    #[test]
+    fn test_dependency_build_flags() {
+        let settings = DependencyBuildSettings {
+            swift_flags: vec!["-suppress-warnings".to_string()],
+            c_flags: vec!["-Wno-deprecated".to_string()],
+        };
+        let mut quiet = Dependency::git("swift-nio", "https://github.com/apple/swift-nio.git")
+            .with_tag("2.60.0");
+        quiet.build = Some(settings.clone());
+        let plain = Dependency::git("swift-log", "https://github.com/apple/swift-log.git")
+            .with_tag("1.5.0");
+
+        // A tag name is never passed off as a revision
+        assert!(format_dependency(&quiet).contains("from: \"2.60.0\""));
+        assert!(format_dependency(&plain).contains("from: \"1.5.0\""));
+        quiet.revision = Some("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678".to_string());
+        assert!(format_dependency(&quiet)
+            .contains("revision: \"a1b2c3d4e5f60718293a4b5c6d7e8f9012345678\""));
+
+        let overlay = dependency_build_overlay("swift-nio", &settings);
+        assert!(overlay.contains("[dependencies.swift-nio.build]"));
+        assert!(overlay.contains(
+            "target.swiftSettings = (target.swiftSettings ?? []) + [.unsafeFlags([\"-suppress-warnings\"])]"
+        ));
+        assert!(overlay.contains(
+            "target.cSettings = (target.cSettings ?? []) + [.unsafeFlags([\"-Wno-deprecated\"])]"
+        ));
+    }
+
+    #[test]
+    fn test_generate_exclude_and_sources() {
+        let manifest = Manifest {
+            package: Package {
+                name: "App".to_string(),
+                version: Version::new(1, 0, 0),
+                ..Default::default()
+            },
+            targets: vec![Target {
+                exclude: vec!["Generated".into(), "README.md".into()],
+                sources: Some(vec!["Core".into()]),
+                ..Target::library("App")
+            }],
+            ..Default::default()
+        };
+
+        let output = generate_package_swift(&manifest);
+        assert!(output.contains("exclude: [\"Generated\", \"README.md\"]"));
+        assert!(output.contains("sources: [\"Core\"]"));
+    }
+
+    #[test]
+    fn test_generate_macro_target() {
+        let manifest = Manifest {
+            package: Package {
+                name: "MyMacros".to_string(),
+                version: Version::new(1, 0, 0),
+                swift_tools_version: "5.9".to_string(),
+                ..Default::default()
+            },
+            targets: vec![
+                Target {
+                    name: "MyMacrosPlugin".to_string(),
+                    target_type: TargetType::Macro,
+                    path: None,
+                    dependencies: Vec::new(),
+                    resources: Vec::new(),
+                    exclude: Vec::new(),
+                    sources: None,
+                    url: None,
+                    checksum: None,
+                    plugin_capability: None,
+                    pkg_config: None,
+                    providers: Vec::new(),
+                },
+                Target {
+                    dependencies: vec!["MyMacrosPlugin".to_string()],
+                    ..Target::library("MyMacros")
+                },
+            ],
+            ..Default::default()
+        };
+
+        let output = generate_package_swift(&manifest);
+        assert!(output.contains("import CompilerPluginSupport"));
+        assert!(output.contains(".macro(\n            name: \"MyMacrosPlugin\""));
+        assert!(output.contains("swift-syntax.git\", from: \"509.0.0\""));
+        assert!(output.contains(".product(name: \"SwiftSyntaxMacros\", package: \"swift-syntax\")"));
+        assert!(
+            output.contains(".product(name: \"SwiftCompilerPlugin\", package: \"swift-syntax\")")
+        );
+        // The library only depends on the macro target, not swift-syntax directly
+        assert!(output.contains("                \"MyMacrosPlugin\",\n"));
+    }
 }
@@ -4,19 +4,33 @@
 //! and can generate Package.swift from Gust.toml.
 
 mod cache;
+mod edit;
+mod features;
 mod generate;
-
-pub use cache::{CacheStats, ManifestCache};
-pub use generate::{generate_package_swift, write_package_swift};
+mod resolution;
+mod validate;
+
+pub use cache::{CacheStats, ManifestCache, CACHE_FORMAT_VERSION};
+pub use edit::ManifestEditor;
+pub use features::{FeatureSet, FEATURES_ENV};
+pub use generate::{
+    dependency_build_overlay, dependency_products, generate_native_package_swift,
+    generate_package_swift, infer_product_name, is_generated_package_swift, write_package_swift,
+    GENERATED_HEADER,
+};
 use gust_types::{
-    BinaryCacheConfig, BuildSettings, Dependency, Manifest, Package, Target, TargetType, Version,
-    VersionReq, WorkspaceConfig, WorkspacePackageDefaults,
+    BinaryCacheConfig, BuildSettings, CommandIntent, CommandPluginCapability, Dependency,
+    DependencyBuildSettings, GustSettings, Manifest, NetworkScope, Package, PluginCapability,
+    PluginPermission, Resource, ResourceLocalization, ResourceRule, SystemPackageProvider, Target,
+    TargetType, Version, VersionReq, WorkspaceConfig, WorkspacePackageDefaults,
 };
+pub use resolution::ResolutionSources;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use thiserror::Error;
+pub use validate::{validate_target_dependencies, ValidationIssue};
 
 #[derive(Error, Debug)]
 pub enum ManifestError {
@@ -28,8 +42,15 @@ pub enum ManifestError {
     TomlError(#[from] toml::de::Error),
     #[error("Failed to parse Package.swift: {0}")]
     SwiftParseError(String),
+    #[error(
+        "Swift is required to parse {}; convert the package to Gust.toml or install Swift",
+        .0.display()
+    )]
+    SwiftNotFound(PathBuf),
     #[error("Invalid manifest: {0}")]
     ValidationError(String),
+    #[error("{0}")]
+    InvalidResolutionOption(String),
 }
 
 /// The manifest file type.
@@ -83,6 +104,12 @@ struct RawGustToml {
     /// Platform requirements (e.g., macOS = "12.0", iOS = "15.0")
     #[serde(default)]
     platforms: HashMap<String, String>,
+    /// Tool behaviour settings
+    #[serde(default)]
+    gust: Option<GustSettings>,
+    /// Features and the optional dependencies they enable
+    #[serde(default)]
+    features: HashMap<String, Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -102,6 +129,8 @@ struct RawPackage {
     authors: Vec<String>,
     #[serde(default)]
     repository: Option<String>,
+    #[serde(default, rename = "default-localization")]
+    default_localization: Option<String>,
 }
 
 fn default_swift_tools_version() -> String {
@@ -114,24 +143,38 @@ enum RawDependency {
     /// Simple version string: "1.0"
     Version(String),
     /// Full dependency specification
-    Full {
-        #[serde(default)]
-        version: Option<String>,
-        #[serde(default)]
-        git: Option<String>,
-        #[serde(default)]
-        branch: Option<String>,
-        #[serde(default)]
-        tag: Option<String>,
-        #[serde(default)]
-        revision: Option<String>,
-        #[serde(default)]
-        path: Option<PathBuf>,
-        #[serde(default)]
-        features: Vec<String>,
-        #[serde(default)]
-        optional: bool,
-    },
+    Full(Box<RawDependencySpec>),
+}
+
+#[derive(Debug, Deserialize)]
+struct RawDependencySpec {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    git: Option<String>,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    tag: Option<String>,
+    #[serde(default)]
+    revision: Option<String>,
+    #[serde(default)]
+    path: Option<PathBuf>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    lfs: Option<bool>,
+    #[serde(default)]
+    build: Option<DependencyBuildSettings>,
+    /// Take the dependency from `[workspace.dependencies]`
+    #[serde(default)]
+    workspace: bool,
+    #[serde(default)]
+    products: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -143,6 +186,72 @@ struct RawTarget {
     path: Option<PathBuf>,
     #[serde(default)]
     dependencies: Vec<String>,
+    #[serde(default)]
+    resources: Vec<RawResource>,
+    #[serde(default)]
+    exclude: Vec<PathBuf>,
+    #[serde(default)]
+    sources: Option<Vec<PathBuf>>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    checksum: Option<String>,
+    #[serde(default, rename = "pkg-config")]
+    pkg_config: Option<String>,
+    #[serde(default)]
+    providers: Vec<SystemPackageProvider>,
+}
+
+/// A resource in Gust.toml: a path to process, or a table with a `rule`
+/// or `localization`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawResource {
+    Path(PathBuf),
+    Detailed(Resource),
+}
+
+impl From<RawResource> for Resource {
+    fn from(raw: RawResource) -> Self {
+        match raw {
+            RawResource::Path(path) => Resource::process(path),
+            RawResource::Detailed(resource) => resource,
+        }
+    }
+}
+
+/// Localized resources need the package's `default-localization`, which
+/// SwiftPM falls back to.
+fn validate_localization(package: &Package, targets: &[Target]) -> Result<(), ManifestError> {
+    if package.default_localization.is_some() {
+        return Ok(());
+    }
+    match targets
+        .iter()
+        .find(|t| t.resources.iter().any(Resource::is_localized))
+    {
+        Some(target) => Err(ManifestError::ValidationError(format!(
+            "Target '{}' has localized resources, but [package] sets no default-localization",
+            target.name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A binary target is either a local artifact (`path`) or a remote zip
+/// (`url` + `checksum`), never both.
+fn validate_binary_target(t: &RawTarget) -> Result<(), ManifestError> {
+    match (&t.path, &t.url, &t.checksum) {
+        (Some(_), None, None) | (None, Some(_), Some(_)) => Ok(()),
+        (None, Some(_), None) => Err(ManifestError::ValidationError(format!(
+            "Binary target '{}' has a url but no checksum",
+            t.name
+        ))),
+        _ => Err(ManifestError::ValidationError(format!(
+            "Binary target '{}' needs either a path or a url and checksum",
+            t.name
+        ))),
+    }
 }
 
 /// Raw workspace configuration
@@ -191,6 +300,7 @@ pub fn parse_gust_toml(path: &Path) -> Result<Manifest, ManifestError> {
         license: raw.package.license,
         authors: raw.package.authors,
         repository: raw.package.repository,
+        default_localization: raw.package.default_localization,
     };
 
     let dependencies = raw
@@ -220,6 +330,9 @@ pub fn parse_gust_toml(path: &Path) -> Result<Manifest, ManifestError> {
                 "library" => TargetType::Library,
                 "test" => TargetType::Test,
                 "plugin" => TargetType::Plugin,
+                "macro" => TargetType::Macro,
+                "binary" => TargetType::Binary,
+                "system-library" => TargetType::SystemLibrary,
                 other => {
                     return Err(ManifestError::ValidationError(format!(
                         "Unknown target type: {}",
@@ -227,15 +340,26 @@ pub fn parse_gust_toml(path: &Path) -> Result<Manifest, ManifestError> {
                     )))
                 }
             };
+            if target_type == TargetType::Binary {
+                validate_binary_target(&t)?;
+            }
             Ok(Target {
                 name: t.name,
                 target_type,
                 path: t.path,
                 dependencies: t.dependencies,
-                resources: Vec::new(),
+                resources: t.resources.into_iter().map(Resource::from).collect(),
+                exclude: t.exclude,
+                sources: t.sources,
+                url: t.url,
+                checksum: t.checksum,
+                plugin_capability: None,
+                pkg_config: t.pkg_config,
+                providers: t.providers,
             })
         })
         .collect::<Result<Vec<_>, ManifestError>>()?;
+    validate_localization(&package, &targets)?;
 
     // Convert workspace config if present
     let workspace = raw.workspace.map(|ws| {
@@ -275,27 +399,39 @@ pub fn parse_gust_toml(path: &Path) -> Result<Manifest, ManifestError> {
         overrides: raw.overrides,
         constraints: raw.constraints,
         workspace,
+        gust: raw.gust,
+        features: raw.features,
     })
 }
 
+/// A registry dependency on `requirement`, which may be compound.
+fn registry_dependency(name: &str, requirement: &str) -> Result<Dependency, ManifestError> {
+    let (version_req, excluded) =
+        gust_types::parse_version_requirement(requirement).map_err(|e| {
+            ManifestError::ValidationError(format!("Invalid version for {}: {}", name, e))
+        })?;
+    Ok(Dependency::registry(name, version_req).with_excluded_versions(excluded))
+}
+
 fn parse_raw_dependency(name: &str, raw: RawDependency) -> Result<Dependency, ManifestError> {
     match raw {
-        RawDependency::Version(v) => {
-            let version_req = VersionReq::parse(&v).map_err(|e| {
-                ManifestError::ValidationError(format!("Invalid version for {}: {}", name, e))
-            })?;
-            Ok(Dependency::registry(name, version_req))
-        }
-        RawDependency::Full {
-            version,
-            git,
-            branch,
-            tag,
-            revision,
-            path,
-            features,
-            optional,
-        } => {
+        RawDependency::Version(v) => registry_dependency(name, &v),
+        RawDependency::Full(spec) => {
+            let RawDependencySpec {
+                version,
+                git,
+                branch,
+                tag,
+                revision,
+                path,
+                features,
+                optional,
+                group,
+                lfs,
+                build,
+                workspace,
+                products,
+            } = *spec;
             let mut dep = if let Some(path) = path {
                 Dependency::path(name, path)
             } else if let Some(git_url) = git {
@@ -309,10 +445,14 @@ fn parse_raw_dependency(name: &str, raw: RawDependency) -> Result<Dependency, Ma
                 d.revision = revision;
                 d
             } else if let Some(v) = version {
-                let version_req = VersionReq::parse(&v).map_err(|e| {
-                    ManifestError::ValidationError(format!("Invalid version for {}: {}", name, e))
-                })?;
-                Dependency::registry(name, version_req)
+                registry_dependency(name, &v)?
+            } else if workspace {
+                // No source: filled in from [workspace.dependencies] when the
+                // workspace is loaded
+                Dependency {
+                    version: None,
+                    ..Dependency::registry(name, VersionReq::STAR)
+                }
             } else {
                 return Err(ManifestError::ValidationError(format!(
                     "Dependency {} must have version, git, or path",
@@ -322,6 +462,10 @@ fn parse_raw_dependency(name: &str, raw: RawDependency) -> Result<Dependency, Ma
 
             dep.features = features;
             dep.optional = optional;
+            dep.group = group;
+            dep.lfs = lfs;
+            dep.build = build.filter(|b| !b.is_empty());
+            dep.products = products;
             Ok(dep)
         }
     }
@@ -329,20 +473,45 @@ fn parse_raw_dependency(name: &str, raw: RawDependency) -> Result<Dependency, Ma
 
 /// Parse a Package.swift file by executing `swift package dump-package`.
 ///
-/// Results are cached based on the BLAKE3 hash of the Package.swift content,
-/// so repeated parsing of unchanged manifests is instant.
+/// Results are cached based on the BLAKE3 hash of the Package.swift content
+/// and any `Package.resolved` beside it, so repeated parsing of unchanged
+/// manifests is instant.
 pub fn parse_package_swift(path: &Path) -> Result<Manifest, ManifestError> {
-    // Try to use cache for fast path
     let cache = ManifestCache::open().ok();
+    parse_package_swift_with(path, cache.as_ref(), Path::new("swift"))
+}
+
+/// [`parse_package_swift`] with a given cache and `swift` executable.
+///
+/// A cache entry that can't be read back as a manifest, e.g. one truncated
+/// by a crash, is deleted and treated as a miss.
+fn parse_package_swift_with(
+    path: &Path,
+    cache: Option<&ManifestCache>,
+    swift: &Path,
+) -> Result<Manifest, ManifestError> {
     let cache_key = ManifestCache::cache_key(path).ok();
 
     // Check cache first
-    if let (Some(ref cache), Some(ref key)) = (&cache, &cache_key) {
+    if let (Some(cache), Some(key)) = (cache, &cache_key) {
         if let Some(cached_json) = cache.get(key) {
             tracing::debug!("Cache hit for {}", path.display());
-            let json: serde_json::Value = serde_json::from_str(&cached_json)
-                .map_err(|e| ManifestError::SwiftParseError(e.to_string()))?;
-            return convert_spm_json(json);
+            let cached = serde_json::from_str(&cached_json)
+                .map_err(|e| ManifestError::SwiftParseError(e.to_string()))
+                .and_then(convert_spm_json);
+            match cached {
+                Ok(manifest) => return Ok(manifest),
+                Err(e) => {
+                    tracing::warn!(
+                        "Ignoring corrupt manifest cache entry for {}: {}",
+                        path.display(),
+                        e
+                    );
+                    if let Err(e) = cache.remove(key) {
+                        tracing::warn!("Failed to remove corrupt cache entry: {}", e);
+                    }
+                }
+            }
         }
     }
 
@@ -351,23 +520,10 @@ pub fn parse_package_swift(path: &Path) -> Result<Manifest, ManifestError> {
         "Cache miss for {}, running swift package dump-package",
         path.display()
     );
-    let dir = path.parent().unwrap_or(Path::new("."));
-
-    let output = Command::new("swift")
-        .arg("package")
-        .arg("dump-package")
-        .current_dir(dir)
-        .output()?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(ManifestError::SwiftParseError(stderr.to_string()));
-    }
-
-    let json_str = String::from_utf8_lossy(&output.stdout);
+    let json_str = dump_package(path, swift)?;
 
     // Store in cache for next time
-    if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+    if let (Some(cache), Some(key)) = (cache, &cache_key) {
         if let Err(e) = cache.put(key, &json_str) {
             tracing::warn!("Failed to cache manifest: {}", e);
         }
@@ -391,6 +547,7 @@ fn convert_spm_json(json: serde_json::Value) -> Result<Manifest, ManifestError>
             .as_str()
             .unwrap_or("5.9")
             .to_string(),
+        default_localization: json["defaultLocalization"].as_str().map(String::from),
         ..Default::default()
     };
 
@@ -419,7 +576,24 @@ fn convert_spm_json(json: serde_json::Value) -> Result<Manifest, ManifestError>
             let tgt_type = match tgt["type"].as_str() {
                 Some("executable") => TargetType::Executable,
                 Some("test") => TargetType::Test,
-                _ => TargetType::Library,
+                Some("plugin") => TargetType::Plugin,
+                Some("system") => TargetType::SystemLibrary,
+                Some("binary") => TargetType::Binary,
+                Some("macro") => TargetType::Macro,
+                Some("regular") | None => TargetType::Library,
+                Some(other) => {
+                    tracing::debug!(
+                        "Treating target {} of unknown type '{}' as a library",
+                        tgt_name,
+                        other
+                    );
+                    TargetType::Library
+                }
+            };
+            let plugin_capability = if tgt_type == TargetType::Plugin {
+                parse_plugin_capability(&tgt["pluginCapability"])
+            } else {
+                None
             };
 
             let tgt_deps: Vec<String> = tgt["dependencies"]
@@ -442,7 +616,14 @@ fn convert_spm_json(json: serde_json::Value) -> Result<Manifest, ManifestError>
                 target_type: tgt_type,
                 path: tgt["path"].as_str().map(PathBuf::from),
                 dependencies: tgt_deps,
-                resources: Vec::new(),
+                resources: json_resources(&tgt["resources"]),
+                exclude: json_paths(&tgt["exclude"]).unwrap_or_default(),
+                sources: json_paths(&tgt["sources"]),
+                url: tgt["url"].as_str().map(String::from),
+                checksum: tgt["checksum"].as_str().map(String::from),
+                plugin_capability,
+                pkg_config: tgt["pkgConfig"].as_str().map(String::from),
+                providers: parse_system_providers(&tgt["providers"]),
             });
         }
     }
@@ -458,9 +639,183 @@ fn convert_spm_json(json: serde_json::Value) -> Result<Manifest, ManifestError>
         overrides: HashMap::new(),
         constraints: HashMap::new(),
         workspace: None,
+        gust: None,
+        features: HashMap::new(),
     })
 }
 
+/// A plugin's `pluginCapability` from `swift package dump-package` output.
+///
+/// SwiftPM encodes enum cases as single-key objects, e.g.
+/// `{"command": {"intent": {"sourceCodeFormatting": {}}, "permissions": []}}`.
+/// Older toolchains put unlabeled associated values under `_0`.
+fn parse_plugin_capability(value: &serde_json::Value) -> Option<PluginCapability> {
+    let (case, body) = enum_case(value)?;
+    match case {
+        "buildTool" => Some(PluginCapability::BuildTool),
+        "command" => {
+            let (intent, intent_body) = enum_case(&body["intent"])?;
+            let intent = match intent {
+                "documentationGeneration" => CommandIntent::DocumentationGeneration,
+                "sourceCodeFormatting" => CommandIntent::SourceCodeFormatting,
+                "custom" => CommandIntent::Custom {
+                    verb: intent_body["verb"].as_str().unwrap_or_default().to_string(),
+                    description: intent_body["description"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                },
+                _ => return None,
+            };
+            let permissions = body["permissions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(parse_plugin_permission)
+                .collect();
+            Some(PluginCapability::Command(CommandPluginCapability {
+                intent,
+                permissions,
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn parse_plugin_permission(value: &serde_json::Value) -> Option<PluginPermission> {
+    let (case, body) = enum_case(value)?;
+    let reason = body["reason"].as_str().unwrap_or_default().to_string();
+    match case {
+        "writeToPackageDirectory" => Some(PluginPermission::WriteToPackageDirectory { reason }),
+        "allowNetworkConnections" => {
+            let scope = match enum_case(&body["scope"]) {
+                Some(("all", _)) => NetworkScope::All,
+                Some(("docker", _)) => NetworkScope::Docker,
+                Some(("local", ports)) => NetworkScope::Local(
+                    ports["ports"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|p| u16::try_from(p.as_u64()?).ok())
+                        .collect(),
+                ),
+                _ => NetworkScope::None,
+            };
+            Some(PluginPermission::AllowNetworkConnections { scope, reason })
+        }
+        _ => None,
+    }
+}
+
+/// The case name and associated values of a SwiftPM-encoded enum.
+fn enum_case(value: &serde_json::Value) -> Option<(&str, &serde_json::Value)> {
+    let (case, body) = value.as_object()?.iter().next()?;
+    let body = body.get("_0").unwrap_or(body);
+    Some((case.as_str(), body))
+}
+
+/// System library providers from `swift package dump-package` output.
+fn parse_system_providers(value: &serde_json::Value) -> Vec<SystemPackageProvider> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|provider| {
+            let (case, body) = enum_case(provider)?;
+            let packages = body
+                .as_array()?
+                .iter()
+                .filter_map(|p| p.as_str().map(String::from))
+                .collect();
+            match case {
+                "brew" => Some(SystemPackageProvider::Brew(packages)),
+                "apt" => Some(SystemPackageProvider::Apt(packages)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// An array of path strings from `swift package dump-package` output.
+fn json_paths(value: &serde_json::Value) -> Option<Vec<PathBuf>> {
+    value.as_array().map(|paths| {
+        paths
+            .iter()
+            .filter_map(|p| p.as_str())
+            .map(PathBuf::from)
+            .collect()
+    })
+}
+
+/// A target's `resources` from `swift package dump-package` output, e.g.
+/// `[{"path": "Base.lproj", "rule": {"process": {"localization": "base"}}}]`.
+fn json_resources(value: &serde_json::Value) -> Vec<Resource> {
+    let Some(resources) = value.as_array() else {
+        return Vec::new();
+    };
+    resources
+        .iter()
+        .filter_map(|r| {
+            let path = r["path"].as_str()?;
+            let rule = &r["rule"];
+            let (rule, localization) = if rule.get("copy").is_some() {
+                (ResourceRule::Copy, None)
+            } else if rule.get("embedInCode").is_some() {
+                (ResourceRule::EmbedInCode, None)
+            } else {
+                let localization = match rule["process"]["localization"].as_str() {
+                    Some("default") => Some(ResourceLocalization::Default),
+                    Some("base") => Some(ResourceLocalization::Base),
+                    _ => None,
+                };
+                (ResourceRule::Process, localization)
+            };
+            Some(Resource {
+                path: PathBuf::from(path),
+                rule,
+                localization,
+            })
+        })
+        .collect()
+}
+
+/// A resource in Gust.toml: a bare path when it's processed without a
+/// localization, an inline table otherwise.
+fn resource_toml(resource: &Resource) -> String {
+    let path = format!("{:?}", resource.path.display().to_string());
+    let mut fields = Vec::new();
+    match resource.rule {
+        ResourceRule::Process => {}
+        ResourceRule::Copy => fields.push("rule = \"copy\"".to_string()),
+        ResourceRule::EmbedInCode => fields.push("rule = \"embed-in-code\"".to_string()),
+    }
+    match resource.localization {
+        None => {}
+        Some(ResourceLocalization::Default) => {
+            fields.push("localization = \"default\"".to_string())
+        }
+        Some(ResourceLocalization::Base) => fields.push("localization = \"base\"".to_string()),
+    }
+    if fields.is_empty() {
+        path
+    } else {
+        format!("{{ path = {}, {} }}", path, fields.join(", "))
+    }
+}
+
+/// The `type` of a target in Gust.toml.
+fn target_type_name(target_type: TargetType) -> &'static str {
+    match target_type {
+        TargetType::Executable => "executable",
+        TargetType::Library => "library",
+        TargetType::Test => "test",
+        TargetType::Plugin => "plugin",
+        TargetType::SystemLibrary => "system-library",
+        TargetType::Binary => "binary",
+        TargetType::Macro => "macro",
+    }
+}
+
 /// Generate a Gust.toml from a Manifest.
 pub fn generate_gust_toml(manifest: &Manifest) -> String {
     let mut out = String::new();
@@ -476,11 +831,14 @@ pub fn generate_gust_toml(manifest: &Manifest) -> String {
     if let Some(desc) = &manifest.package.description {
         out.push_str(&format!("description = \"{}\"\n", desc));
     }
+    if let Some(localization) = &manifest.package.default_localization {
+        out.push_str(&format!("default-localization = \"{}\"\n", localization));
+    }
 
     if !manifest.dependencies.is_empty() {
         out.push_str("\n[dependencies]\n");
         for (name, dep) in &manifest.dependencies {
-            if let Some(v) = &dep.version {
+            if let Some(v) = dep.requirement() {
                 out.push_str(&format!("{} = \"{}\"\n", name, v));
             } else if let Some(git) = &dep.git {
                 out.push_str(&format!("{} = {{ git = \"{}\" }}\n", name, git));
@@ -492,16 +850,87 @@ pub fn generate_gust_toml(manifest: &Manifest) -> String {
         for target in &manifest.targets {
             out.push_str("\n[[target]]\n");
             out.push_str(&format!("name = \"{}\"\n", target.name));
-            out.push_str(&format!("type = \"{:?}\"\n", target.target_type).to_lowercase());
+            out.push_str(&format!(
+                "type = \"{}\"\n",
+                target_type_name(target.target_type)
+            ));
+            if target.target_type == TargetType::Binary {
+                if let Some(path) = &target.path {
+                    out.push_str(&format!("path = \"{}\"\n", path.display()));
+                }
+                if let Some(url) = &target.url {
+                    out.push_str(&format!("url = \"{}\"\n", url));
+                }
+                if let Some(checksum) = &target.checksum {
+                    out.push_str(&format!("checksum = \"{}\"\n", checksum));
+                }
+            }
+            if target.target_type == TargetType::SystemLibrary {
+                if let Some(path) = &target.path {
+                    out.push_str(&format!("path = \"{}\"\n", path.display()));
+                }
+            }
+            if let Some(pkg_config) = &target.pkg_config {
+                out.push_str(&format!("pkg-config = \"{}\"\n", pkg_config));
+            }
+            if !target.providers.is_empty() {
+                let providers: Vec<String> = target
+                    .providers
+                    .iter()
+                    .map(|p| match p {
+                        SystemPackageProvider::Brew(packages) => {
+                            format!("{{ brew = {:?} }}", packages)
+                        }
+                        SystemPackageProvider::Apt(packages) => {
+                            format!("{{ apt = {:?} }}", packages)
+                        }
+                    })
+                    .collect();
+                out.push_str(&format!("providers = [{}]\n", providers.join(", ")));
+            }
             if !target.dependencies.is_empty() {
                 out.push_str(&format!("dependencies = {:?}\n", target.dependencies));
             }
+            if !target.resources.is_empty() {
+                let resources: Vec<String> = target.resources.iter().map(resource_toml).collect();
+                out.push_str(&format!("resources = [{}]\n", resources.join(", ")));
+            }
+            if !target.exclude.is_empty() {
+                out.push_str(&format!("exclude = {:?}\n", target.exclude));
+            }
+            if let Some(sources) = &target.sources {
+                out.push_str(&format!("sources = {:?}\n", sources));
+            }
         }
     }
 
     out
 }
 
+/// Output of `swift package dump-package` for the Package.swift at `path`,
+/// run with the `swift` executable.
+///
+/// Only this needs a toolchain, so a missing one is reported here rather
+/// than up front.
+fn dump_package(path: &Path, swift: &Path) -> Result<String, ManifestError> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let output = Command::new(swift)
+        .arg("package")
+        .arg("dump-package")
+        .current_dir(dir)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ManifestError::SwiftNotFound(path.to_path_buf()),
+            _ => ManifestError::ReadError(e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ManifestError::SwiftParseError(stderr.to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 /// Async version of parse_package_swift using tokio.
 pub async fn parse_package_swift_async(path: &Path) -> Result<Manifest, ManifestError> {
     let path = path.to_path_buf();
@@ -562,66 +991,68 @@ pub struct ParsedDependency {
     pub manifest: Manifest,
     /// Names of this package's dependencies
     pub dependency_names: Vec<String>,
+    /// Time spent parsing the manifest
+    pub parse_time: std::time::Duration,
+}
+
+/// Default cap on concurrent manifest parses, and so on concurrent
+/// `swift package dump-package` processes.
+pub const DEFAULT_MAX_MANIFEST_PARSES: usize = 8;
+
+/// Hard cap on concurrent manifest parses.
+///
+/// Reads `GUST_MAX_MANIFEST_PARSES`, falling back to
+/// [`DEFAULT_MAX_MANIFEST_PARSES`].
+pub fn max_manifest_parses() -> usize {
+    std::env::var("GUST_MAX_MANIFEST_PARSES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_MANIFEST_PARSES)
+}
+
+/// Parse the manifest in a package directory, preferring Package.swift.
+fn parse_manifest_dir(dir: &Path) -> Result<Manifest, ManifestError> {
+    let package_swift = dir.join("Package.swift");
+    if package_swift.exists() {
+        return parse_package_swift(&package_swift);
+    }
+
+    let gust_toml = dir.join("Gust.toml");
+    if gust_toml.exists() {
+        return parse_gust_toml(&gust_toml);
+    }
+
+    Err(ManifestError::NotFound(dir.to_path_buf()))
 }
 
 /// Parse transitive dependencies in parallel.
 ///
 /// Given a list of package directories, parses all their manifests in parallel
 /// and returns the parsed results along with discovered transitive dependencies.
+/// At most `concurrency` parses run at once, further capped by
+/// [`max_manifest_parses`].
 pub async fn parse_transitive_deps(
     package_dirs: Vec<(String, std::path::PathBuf)>,
     concurrency: usize,
 ) -> (Vec<ParsedDependency>, Vec<String>) {
-    use futures::stream::{self, StreamExt};
-    use std::sync::Arc;
-    use tokio::sync::Semaphore;
+    let limit = concurrency.clamp(1, max_manifest_parses());
+    let results = parse_dirs_bounded(package_dirs, limit, parse_manifest_dir).await;
 
-    let semaphore = Arc::new(Semaphore::new(concurrency));
     let mut parsed = Vec::new();
     let mut discovered_deps = Vec::new();
 
-    let results: Vec<_> = stream::iter(package_dirs)
-        .map(|(name, dir)| {
-            let sem = Arc::clone(&semaphore);
-            async move {
-                let _permit = sem.acquire().await.unwrap();
-
-                let package_swift = dir.join("Package.swift");
-                let start = std::time::Instant::now();
-
-                let result = if package_swift.exists() {
-                    parse_package_swift_async(&package_swift).await
-                } else {
-                    let gust_toml = dir.join("Gust.toml");
-                    if gust_toml.exists() {
-                        let toml_path = gust_toml.clone();
-                        match tokio::task::spawn_blocking(move || parse_gust_toml(&toml_path)).await
-                        {
-                            Ok(r) => r,
-                            Err(e) => {
-                                Err(ManifestError::SwiftParseError(format!("Task error: {}", e)))
-                            }
-                        }
-                    } else {
-                        Err(ManifestError::NotFound(dir.clone()))
-                    }
-                };
-
-                let elapsed = start.elapsed();
-                tracing::debug!("Parsed {} in {:?}", name, elapsed);
-
-                (name, dir, result)
-            }
-        })
-        .buffer_unordered(concurrency)
-        .collect()
-        .await;
-
-    for (name, dir, result) in results {
+    for (name, dir, result, parse_time) in results {
         match result {
             Ok(manifest) => {
-                // Collect dependency names for transitive resolution
-                let dep_names: Vec<String> = manifest.dependencies.keys().cloned().collect();
+                // Collect dependency names for transitive resolution;
+                // dependency groups are for the package's own use
+                let dep_names: Vec<String> = manifest
+                    .dependencies
+                    .iter()
+                    .filter(|(_, dep)| dep.group.is_none())
+                    .map(|(name, _)| name.clone())
+                    .collect();
 
                 for dep_name in &dep_names {
                     if !discovered_deps.contains(dep_name) {
@@ -634,6 +1065,7 @@ pub async fn parse_transitive_deps(
                     path: dir,
                     manifest,
                     dependency_names: dep_names,
+                    parse_time,
                 });
             }
             Err(e) => {
@@ -645,10 +1077,116 @@ pub async fn parse_transitive_deps(
     (parsed, discovered_deps)
 }
 
+/// Run `parse` over each directory with at most `limit` in flight.
+///
+/// The permit moves into the blocking task, so it's only released once the
+/// parse (and any Swift subprocess it spawned) has finished, even if the
+/// caller stops waiting.
+async fn parse_dirs_bounded<F>(
+    package_dirs: Vec<(String, PathBuf)>,
+    limit: usize,
+    parse: F,
+) -> Vec<(
+    String,
+    PathBuf,
+    Result<Manifest, ManifestError>,
+    std::time::Duration,
+)>
+where
+    F: Fn(&Path) -> Result<Manifest, ManifestError> + Send + Sync + 'static,
+{
+    use futures::stream::{self, StreamExt};
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+
+    let semaphore = Arc::new(Semaphore::new(limit));
+    let parse = Arc::new(parse);
+
+    stream::iter(package_dirs)
+        .map(|(name, dir)| {
+            let sem = Arc::clone(&semaphore);
+            let parse = Arc::clone(&parse);
+            async move {
+                let permit = sem.acquire_owned().await.unwrap();
+                let start = std::time::Instant::now();
+
+                let parse_dir = dir.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    let _permit = permit;
+                    parse(&parse_dir)
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    Err(ManifestError::SwiftParseError(format!("Task error: {}", e)))
+                });
+
+                let elapsed = start.elapsed();
+                tracing::debug!("Parsed {} in {:?}", name, elapsed);
+                (name, dir, result, elapsed)
+            }
+        })
+        .buffer_unordered(limit)
+        .collect()
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_missing_swift_reported_when_parsing_package_swift() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Package.swift");
+        std::fs::write(&path, "// swift-tools-version:5.9\n").unwrap();
+
+        let err = dump_package(&path, &tmp.path().join("no-swift-here")).unwrap_err();
+        assert!(matches!(&err, ManifestError::SwiftNotFound(p) if *p == path));
+        assert!(err
+            .to_string()
+            .contains("convert the package to Gust.toml or install Swift"));
+
+        // A Gust.toml project doesn't need Swift at all
+        std::fs::write(
+            tmp.path().join("Gust.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let (_, kind) = find_manifest(tmp.path()).unwrap();
+        assert_eq!(kind, ManifestType::GustToml);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_corrupt_cache_entry_is_replaced() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dump = r#"{"name": "App", "toolsVersion": {"_version": "5.9"}, "targets": []}"#;
+        let swift = tmp.path().join("swift");
+        std::fs::write(&swift, format!("#!/bin/sh\necho '{}'\n", dump)).unwrap();
+        std::fs::set_permissions(&swift, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let package = tmp.path().join("app");
+        std::fs::create_dir_all(&package).unwrap();
+        let path = package.join("Package.swift");
+        std::fs::write(&path, "// swift-tools-version:5.9\n").unwrap();
+
+        let cache = ManifestCache::open_at(tmp.path().join("manifests")).unwrap();
+        let key = ManifestCache::cache_key(&path).unwrap();
+        cache.put(&key, r#"{"name": "App", "targ"#).unwrap();
+
+        let manifest = parse_package_swift_with(&path, Some(&cache), &swift).unwrap();
+        assert_eq!(manifest.package.name, "App");
+        let recached: serde_json::Value = serde_json::from_str(&cache.get(&key).unwrap()).unwrap();
+        assert_eq!(recached["name"], "App");
+
+        // The fresh entry is used from now on
+        std::fs::remove_file(&swift).unwrap();
+        let manifest = parse_package_swift_with(&path, Some(&cache), &swift).unwrap();
+        assert_eq!(manifest.package.name, "App");
+    }
+
     #[test]
     fn test_parse_simple_toml() {
         let toml = r#"
@@ -677,14 +1215,53 @@ alamofire = { git = "https://github.com/Alamofire/Alamofire.git", tag = "5.8.0"
 "#;
         let raw: RawGustToml = toml::from_str(toml).unwrap();
         match &raw.dependencies["alamofire"] {
-            RawDependency::Full { git, tag, .. } => {
-                assert!(git.is_some());
-                assert_eq!(tag.as_deref(), Some("5.8.0"));
+            RawDependency::Full(spec) => {
+                assert!(spec.git.is_some());
+                assert_eq!(spec.tag.as_deref(), Some("5.8.0"));
             }
             _ => panic!("Expected full dependency"),
         }
     }
 
+    #[test]
+    fn test_parse_compound_version_requirement() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Gust.toml");
+        let write = |requirement: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nswift-log = \"{}\"\n",
+                    requirement
+                ),
+            )
+            .unwrap();
+        };
+
+        write(">=1.0, <2.0, !=1.4.3");
+        let manifest = parse_gust_toml(&path).unwrap();
+        let dep = &manifest.dependencies["swift-log"];
+        assert_eq!(dep.excluded_versions, vec![Version::new(1, 4, 3)]);
+        assert!(dep.accepts(&Version::new(1, 4, 2)));
+        assert!(!dep.accepts(&Version::new(1, 4, 3)));
+        assert!(!dep.accepts(&Version::new(2, 0, 0)));
+        assert_eq!(dep.requirement().as_deref(), Some(">=1.0, <2.0, !=1.4.3"));
+
+        write(">=1.0, <2.x.y, !=1.4.3");
+        let err = parse_gust_toml(&path).unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "Invalid version for swift-log: clause '<2.x.y' in '>=1.0, <2.x.y, !=1.4.3'"
+            ),
+            "{}",
+            err
+        );
+
+        write(">=1.0, !=1.4");
+        let err = parse_gust_toml(&path).unwrap_err().to_string();
+        assert!(err.contains("clause '!=1.4'"), "{}", err);
+    }
+
     #[test]
     fn test_parse_overrides_and_constraints() {
         let toml = r#"
@@ -760,4 +1337,374 @@ tvOS = "15.0"
         assert_eq!(raw.platforms.get("iOS"), Some(&"15.0".to_string()));
         assert_eq!(raw.platforms.get("tvOS"), Some(&"15.0".to_string()));
     }
+
+    #[test]
+    fn test_macro_target_round_trip() {
+        let manifest = Manifest {
+            package: Package {
+                name: "MyMacros".to_string(),
+                version: Version::new(1, 0, 0),
+                ..Default::default()
+            },
+            targets: vec![Target {
+                name: "MyMacrosPlugin".to_string(),
+                target_type: TargetType::Macro,
+                path: None,
+                dependencies: vec!["swift-syntax".to_string()],
+                resources: Vec::new(),
+                exclude: Vec::new(),
+                sources: None,
+                url: None,
+                checksum: None,
+                plugin_capability: None,
+                pkg_config: None,
+                providers: Vec::new(),
+            }],
+            ..Default::default()
+        };
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Gust.toml");
+        std::fs::write(&path, generate_gust_toml(&manifest)).unwrap();
+
+        let parsed = parse_gust_toml(&path).unwrap();
+        assert_eq!(parsed.targets.len(), 1);
+        assert_eq!(parsed.targets[0].target_type, TargetType::Macro);
+        assert_eq!(parsed.targets[0].dependencies, vec!["swift-syntax"]);
+
+        // SwiftPM's dump-package reports macros with type "macro"
+        let json = serde_json::json!({
+            "name": "MyMacros",
+            "targets": [{ "name": "MyMacrosPlugin", "type": "macro" }]
+        });
+        let converted = convert_spm_json(json).unwrap();
+        assert_eq!(converted.targets[0].target_type, TargetType::Macro);
+    }
+
+    #[test]
+    fn test_spm_json_target_kinds() {
+        let json = serde_json::json!({
+            "name": "Kinds",
+            "targets": [
+                { "name": "Core", "type": "regular" },
+                { "name": "Tool", "type": "executable" },
+                { "name": "CoreTests", "type": "test" },
+                { "name": "CSQLite", "type": "system" },
+                { "name": "Sdk", "type": "binary", "url": "https://example.com/Sdk.zip", "checksum": "abc" },
+                { "name": "Macros", "type": "macro" },
+                { "name": "Codegen", "type": "plugin", "pluginCapability": { "buildTool": {} } },
+                {
+                    "name": "Format",
+                    "type": "plugin",
+                    "pluginCapability": {
+                        "command": {
+                            "intent": { "sourceCodeFormatting": {} },
+                            "permissions": [
+                                { "writeToPackageDirectory": { "reason": "Formats sources" } }
+                            ]
+                        }
+                    }
+                },
+                {
+                    "name": "Publish",
+                    "type": "plugin",
+                    "pluginCapability": {
+                        "command": {
+                            "intent": { "custom": { "verb": "publish", "description": "Upload docs" } },
+                            "permissions": [
+                                {
+                                    "allowNetworkConnections": {
+                                        "scope": { "all": { "ports": [] } },
+                                        "reason": "Uploads"
+                                    }
+                                }
+                            ]
+                        }
+                    }
+                }
+            ]
+        });
+        let manifest = convert_spm_json(json).unwrap();
+        let kinds: Vec<(&str, TargetType)> = manifest
+            .targets
+            .iter()
+            .map(|t| (t.name.as_str(), t.target_type))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("Core", TargetType::Library),
+                ("Tool", TargetType::Executable),
+                ("CoreTests", TargetType::Test),
+                ("CSQLite", TargetType::SystemLibrary),
+                ("Sdk", TargetType::Binary),
+                ("Macros", TargetType::Macro),
+                ("Codegen", TargetType::Plugin),
+                ("Format", TargetType::Plugin),
+                ("Publish", TargetType::Plugin),
+            ]
+        );
+
+        assert_eq!(manifest.targets[0].plugin_capability, None);
+        assert_eq!(
+            manifest.targets[6].plugin_capability,
+            Some(PluginCapability::BuildTool)
+        );
+        assert_eq!(
+            manifest.targets[7].plugin_capability,
+            Some(PluginCapability::Command(CommandPluginCapability {
+                intent: CommandIntent::SourceCodeFormatting,
+                permissions: vec![PluginPermission::WriteToPackageDirectory {
+                    reason: "Formats sources".to_string()
+                }],
+            }))
+        );
+        assert_eq!(
+            manifest.targets[8].plugin_capability,
+            Some(PluginCapability::Command(CommandPluginCapability {
+                intent: CommandIntent::Custom {
+                    verb: "publish".to_string(),
+                    description: "Upload docs".to_string()
+                },
+                permissions: vec![PluginPermission::AllowNetworkConnections {
+                    scope: NetworkScope::All,
+                    reason: "Uploads".to_string()
+                }],
+            }))
+        );
+
+        let package_swift = generate::generate_package_swift(&manifest);
+        assert!(package_swift.contains(
+            ".plugin(\n            name: \"Format\",\n            capability: .command(intent: .sourceCodeFormatting(), permissions: [.writeToPackageDirectory(reason: \"Formats sources\")])"
+        ));
+        assert!(package_swift.contains("capability: .buildTool()"));
+    }
+
+    #[test]
+    fn test_system_library_round_trip() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Gust.toml");
+        std::fs::write(
+            &path,
+            r#"
+[package]
+name = "App"
+version = "1.0.0"
+
+[[target]]
+name = "CSQLite"
+type = "system-library"
+path = "Sources/CSQLite"
+pkg-config = "sqlite3"
+providers = [{ brew = ["sqlite"] }, { apt = ["libsqlite3-dev"] }]
+
+[[target]]
+name = "App"
+type = "executable"
+dependencies = ["CSQLite"]
+"#,
+        )
+        .unwrap();
+
+        let parsed = parse_gust_toml(&path).unwrap();
+        let system = &parsed.targets[0];
+        assert_eq!(system.target_type, TargetType::SystemLibrary);
+        assert_eq!(system.pkg_config.as_deref(), Some("sqlite3"));
+        assert_eq!(
+            system.providers,
+            vec![
+                SystemPackageProvider::Brew(vec!["sqlite".to_string()]),
+                SystemPackageProvider::Apt(vec!["libsqlite3-dev".to_string()]),
+            ]
+        );
+
+        // Gust.toml -> Manifest -> Gust.toml keeps everything
+        std::fs::write(&path, generate_gust_toml(&parsed)).unwrap();
+        let reparsed = parse_gust_toml(&path).unwrap();
+        assert_eq!(reparsed.targets[0].path, system.path);
+        assert_eq!(reparsed.targets[0].pkg_config, system.pkg_config);
+        assert_eq!(reparsed.targets[0].providers, system.providers);
+
+        let package_swift = generate::generate_package_swift(&parsed);
+        assert!(package_swift.contains(
+            ".systemLibrary(\n            name: \"CSQLite\",\n            path: \"Sources/CSQLite\",\n            pkgConfig: \"sqlite3\",\n            providers: [.brew([\"sqlite\"]), .apt([\"libsqlite3-dev\"])]\n        ),"
+        ));
+
+        // The same target as `swift package dump-package` describes it
+        let json = serde_json::json!({
+            "name": "App",
+            "targets": [{
+                "name": "CSQLite",
+                "type": "system",
+                "pkgConfig": "sqlite3",
+                "providers": [
+                    { "brew": { "_0": ["sqlite"] } },
+                    { "apt": { "_0": ["libsqlite3-dev"] } }
+                ]
+            }]
+        });
+        let converted = convert_spm_json(json).unwrap();
+        assert_eq!(converted.targets[0].pkg_config, system.pkg_config);
+        assert_eq!(converted.targets[0].providers, system.providers);
+    }
+
+    #[test]
+    fn test_default_localization_round_trip() {
+        let manifest = Manifest {
+            package: Package {
+                name: "App".to_string(),
+                version: Version::new(1, 0, 0),
+                default_localization: Some("en".to_string()),
+                ..Default::default()
+            },
+            targets: vec![Target {
+                resources: vec![
+                    Resource {
+                        localization: Some(ResourceLocalization::Default),
+                        ..Resource::process("Resources/Localizable.strings")
+                    },
+                    Resource {
+                        rule: ResourceRule::Copy,
+                        ..Resource::process("Resources/data.json")
+                    },
+                ],
+                ..Target::library("App")
+            }],
+            ..Default::default()
+        };
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Gust.toml");
+        std::fs::write(&path, generate_gust_toml(&manifest)).unwrap();
+        let parsed = parse_gust_toml(&path).unwrap();
+        assert_eq!(parsed.package.default_localization.as_deref(), Some("en"));
+        assert_eq!(parsed.targets[0].resources, manifest.targets[0].resources);
+
+        let package_swift = generate_package_swift(&parsed);
+        assert!(package_swift.contains("    name: \"App\",\n    defaultLocalization: \"en\",\n"));
+        assert!(package_swift.contains(
+            ".process(\"Resources/Localizable.strings\", localization: .default),\n                .copy(\"Resources/data.json\")"
+        ));
+
+        // SwiftPM's dump-package output carries the same information
+        let json = serde_json::json!({
+            "name": "App",
+            "defaultLocalization": "en",
+            "targets": [{
+                "name": "App",
+                "type": "regular",
+                "resources": [
+                    {"path": "Resources/Localizable.strings", "rule": {"process": {"localization": "default"}}},
+                    {"path": "Resources/data.json", "rule": {"copy": {}}}
+                ]
+            }]
+        });
+        let converted = convert_spm_json(json).unwrap();
+        assert_eq!(
+            converted.package.default_localization.as_deref(),
+            Some("en")
+        );
+        assert_eq!(
+            converted.targets[0].resources,
+            manifest.targets[0].resources
+        );
+    }
+
+    #[test]
+    fn test_localized_resources_need_default_localization() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Gust.toml");
+        std::fs::write(
+            &path,
+            r#"
+[package]
+name = "App"
+version = "1.0.0"
+
+[[target]]
+name = "App"
+type = "library"
+resources = ["Resources/data.json", "Resources/fr.lproj"]
+"#,
+        )
+        .unwrap();
+
+        let err = parse_gust_toml(&path).unwrap_err();
+        assert!(err.to_string().contains("default-localization"));
+    }
+
+    #[test]
+    fn test_binary_target_parsing() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("Gust.toml");
+
+        let manifest = Manifest {
+            package: Package {
+                name: "App".to_string(),
+                version: Version::new(1, 0, 0),
+                ..Default::default()
+            },
+            targets: vec![Target {
+                url: Some("https://example.com/Sdk.xcframework.zip".to_string()),
+                checksum: Some("abc123".to_string()),
+                ..Target::binary("Sdk")
+            }],
+            ..Default::default()
+        };
+        std::fs::write(&path, generate_gust_toml(&manifest)).unwrap();
+
+        let parsed = parse_gust_toml(&path).unwrap();
+        assert_eq!(parsed.targets[0].target_type, TargetType::Binary);
+        assert_eq!(
+            parsed.targets[0].url.as_deref(),
+            Some("https://example.com/Sdk.xcframework.zip")
+        );
+        assert_eq!(parsed.targets[0].checksum.as_deref(), Some("abc123"));
+
+        // A remote binary target without a checksum can't be verified
+        std::fs::write(
+            &path,
+            r#"
+[package]
+name = "App"
+version = "1.0.0"
+
+[[target]]
+name = "Sdk"
+type = "binary"
+url = "https://example.com/Sdk.xcframework.zip"
+"#,
+        )
+        .unwrap();
+        assert!(parse_gust_toml(&path).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parse_concurrency_is_bounded() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let (r, p) = (Arc::clone(&running), Arc::clone(&peak));
+        let mock = move |_: &Path| {
+            let now = r.fetch_add(1, Ordering::SeqCst) + 1;
+            p.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            r.fetch_sub(1, Ordering::SeqCst);
+            Ok(Manifest::default())
+        };
+
+        let dirs: Vec<(String, PathBuf)> = (0..40)
+            .map(|i| (format!("pkg-{}", i), PathBuf::from(format!("/pkg-{}", i))))
+            .collect();
+        let results = parse_dirs_bounded(dirs, 3, mock).await;
+
+        assert_eq!(results.len(), 40);
+        assert!(results.iter().all(|(_, _, r, _)| r.is_ok()));
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(peak.load(Ordering::SeqCst) > 1, "parses should overlap");
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+    }
 }
@@ -0,0 +1,188 @@
+//! Overrides and constraints from every source, merged into
+//! [`ResolutionOptions`].
+//!
+//! Besides Gust.toml's `[overrides]` and `[constraints]` tables, they can
+//! come from config defaults and from `--override pkg@ver` and
+//! `--constraint pkg@req` flags. For each package the precedence is: CLI >
+//! manifest > config.
+
+use crate::ManifestError;
+use gust_types::{Manifest, ResolutionOptions, VersionConstraint, VersionOverride, VersionReq};
+use std::collections::BTreeMap;
+
+/// Overrides and constraints from one source, as written: package name to
+/// version requirement.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolutionSources {
+    /// Versions to force, regardless of what dependents ask for
+    pub overrides: BTreeMap<String, String>,
+    /// Extra requirements on packages wherever they appear
+    pub constraints: BTreeMap<String, String>,
+}
+
+impl ResolutionSources {
+    /// The manifest's `[overrides]` and `[constraints]` tables.
+    pub fn from_manifest(manifest: &Manifest) -> Self {
+        Self {
+            overrides: manifest
+                .overrides
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            constraints: manifest
+                .constraints
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    /// Parse `--override` and `--constraint` values of the form
+    /// `pkg@requirement`. A package given twice keeps its last value.
+    pub fn from_flags(overrides: &[String], constraints: &[String]) -> Result<Self, ManifestError> {
+        let parse = |flag: &str, values: &[String]| {
+            values
+                .iter()
+                .map(|value| match value.split_once('@') {
+                    Some((name, req)) if !name.is_empty() && !req.is_empty() => {
+                        Ok((name.to_string(), req.to_string()))
+                    }
+                    _ => Err(ManifestError::InvalidResolutionOption(format!(
+                        "Expected PACKAGE@VERSION for {}, got '{}'",
+                        flag, value
+                    ))),
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()
+        };
+        Ok(Self {
+            overrides: parse("--override", overrides)?,
+            constraints: parse("--constraint", constraints)?,
+        })
+    }
+
+    /// Combine config defaults, the manifest and CLI flags, checking every
+    /// requirement parses.
+    ///
+    /// Each source is checked before merging, so an invalid requirement is
+    /// reported against where it was written even if another source
+    /// replaces it.
+    pub fn merge(
+        config: &Self,
+        manifest: &Manifest,
+        cli: &Self,
+    ) -> Result<ResolutionOptions, ManifestError> {
+        let layers = [
+            ("config", config.clone()),
+            ("Gust.toml", Self::from_manifest(manifest)),
+            ("the command line", cli.clone()),
+        ];
+
+        let mut overrides = BTreeMap::new();
+        let mut constraints = BTreeMap::new();
+        for (origin, layer) in layers {
+            for (name, req) in layer.overrides {
+                let version = parse_requirement("override", origin, &name, &req)?;
+                overrides.insert(name, version);
+            }
+            for (name, req) in layer.constraints {
+                let version = parse_requirement("constraint", origin, &name, &req)?;
+                constraints.insert(name, version);
+            }
+        }
+
+        Ok(ResolutionOptions {
+            overrides: overrides
+                .into_iter()
+                .map(|(package, version)| VersionOverride { package, version })
+                .collect(),
+            constraints: constraints
+                .into_iter()
+                .map(|(package, version)| VersionConstraint { package, version })
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+fn parse_requirement(
+    kind: &str,
+    origin: &str,
+    name: &str,
+    req: &str,
+) -> Result<VersionReq, ManifestError> {
+    VersionReq::parse(req).map_err(|e| {
+        ManifestError::InvalidResolutionOption(format!(
+            "Invalid {} '{}' for {} from {}: {}",
+            kind, req, name, origin, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cli_beats_manifest_beats_config() {
+        let mut manifest = Manifest::default();
+        manifest
+            .overrides
+            .insert("swift-log".to_string(), "1.5.0".to_string());
+        manifest
+            .overrides
+            .insert("swift-nio".to_string(), "2.60.0".to_string());
+        manifest
+            .constraints
+            .insert("swift-atomics".to_string(), ">=1.1".to_string());
+
+        let config = ResolutionSources {
+            overrides: BTreeMap::from([
+                ("swift-log".to_string(), "1.4.0".to_string()),
+                ("swift-collections".to_string(), "1.0.0".to_string()),
+            ]),
+            constraints: BTreeMap::from([("swift-atomics".to_string(), ">=1.0".to_string())]),
+        };
+        let cli =
+            ResolutionSources::from_flags(&strings(&["swift-nio@=2.62.0"]), &strings(&[])).unwrap();
+
+        let options = ResolutionSources::merge(&config, &manifest, &cli).unwrap();
+        let overrides: Vec<(&str, String)> = options
+            .overrides
+            .iter()
+            .map(|o| (o.package.as_str(), o.version.to_string()))
+            .collect();
+        assert_eq!(
+            overrides,
+            vec![
+                ("swift-collections", "^1.0.0".to_string()),
+                ("swift-log", "^1.5.0".to_string()),
+                ("swift-nio", "=2.62.0".to_string()),
+            ]
+        );
+        assert_eq!(options.constraints.len(), 1);
+        assert_eq!(options.constraints[0].version.to_string(), ">=1.1");
+    }
+
+    #[test]
+    fn test_invalid_cli_override_is_reported() {
+        let err = ResolutionSources::from_flags(&strings(&["swift-log"]), &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected PACKAGE@VERSION for --override, got 'swift-log'"
+        );
+
+        let cli = ResolutionSources::from_flags(&strings(&["swift-log@one"]), &[]).unwrap();
+        let err = ResolutionSources::merge(&Default::default(), &Manifest::default(), &cli)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Invalid override 'one' for swift-log from the command line"),
+            "{}",
+            err
+        );
+    }
+}
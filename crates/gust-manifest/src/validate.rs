@@ -0,0 +1,142 @@
+//! Checks on a parsed manifest beyond what parsing enforces.
+//!
+//! Target dependencies in Gust.toml are plain names that the generated
+//! Package.swift turns into target or product references. A typo there
+//! would only show up as a SwiftPM error at build time, so they're checked
+//! before generating.
+
+use crate::generate::{dependency_products, SWIFT_SYNTAX};
+use gust_types::{Manifest, TargetType};
+use std::fmt;
+
+/// A target dependency that names nothing in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// Target whose `dependencies` has the name
+    pub target: String,
+    /// The unknown name
+    pub dependency: String,
+    /// Closest known target, dependency or product, if it looks like a typo
+    pub suggestion: Option<String>,
+}
+
+impl ValidationIssue {
+    /// What to do about the issue.
+    pub fn help(&self) -> String {
+        match &self.suggestion {
+            Some(suggestion) => format!("Did you mean '{}'?", suggestion),
+            None => format!(
+                "Add '{}' to [dependencies] or as a [[target]]",
+                self.dependency
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Target '{}' depends on '{}', which is not a target or dependency",
+            self.target, self.dependency
+        )
+    }
+}
+
+/// Target dependencies that are neither another target of the manifest
+/// nor a declared dependency, by package or product name.
+pub fn validate_target_dependencies(manifest: &Manifest) -> Vec<ValidationIssue> {
+    let packages = manifest
+        .dependencies
+        .iter()
+        .chain(manifest.dev_dependencies.iter());
+    let known: Vec<String> = manifest
+        .targets
+        .iter()
+        .map(|t| t.name.clone())
+        .chain(packages.clone().map(|(name, _)| name.clone()))
+        .chain(packages.flat_map(|(name, dep)| dependency_products(name, dep)))
+        .collect();
+
+    let mut issues = Vec::new();
+    for target in &manifest.targets {
+        for dep in &target.dependencies {
+            // Macros get swift-syntax whether or not it's declared
+            let implicit = target.target_type == TargetType::Macro && dep == SWIFT_SYNTAX;
+            if implicit || known.contains(dep) {
+                continue;
+            }
+            issues.push(ValidationIssue {
+                target: target.name.clone(),
+                dependency: dep.clone(),
+                suggestion: gust_diagnostics::closest_match(dep, known.iter().map(String::as_str))
+                    .map(String::from),
+            });
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gust_types::{Dependency, Target};
+
+    fn target(name: &str, target_type: TargetType, dependencies: &[&str]) -> Target {
+        Target {
+            name: name.to_string(),
+            target_type,
+            path: None,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            resources: Vec::new(),
+            exclude: Vec::new(),
+            sources: None,
+            url: None,
+            checksum: None,
+            plugin_capability: None,
+            pkg_config: None,
+            providers: Vec::new(),
+        }
+    }
+
+    fn manifest(targets: Vec<Target>) -> Manifest {
+        let mut manifest = Manifest {
+            targets,
+            ..Default::default()
+        };
+        manifest.dependencies.insert(
+            "swift-log".to_string(),
+            Dependency::git("swift-log", "https://github.com/apple/swift-log.git"),
+        );
+        manifest
+    }
+
+    #[test]
+    fn test_known_target_dependencies_pass() {
+        let manifest = manifest(vec![
+            target("Core", TargetType::Library, &["swift-log"]),
+            target("App", TargetType::Executable, &["Core", "Logging"]),
+            target("Macros", TargetType::Macro, &["swift-syntax"]),
+        ]);
+        assert!(validate_target_dependencies(&manifest).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_target_dependency_suggests_near_miss() {
+        let manifest = manifest(vec![
+            target("Core", TargetType::Library, &["swift-lg"]),
+            target("App", TargetType::Executable, &["Database"]),
+        ]);
+
+        let issues = validate_target_dependencies(&manifest);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            issues[0].to_string(),
+            "Target 'Core' depends on 'swift-lg', which is not a target or dependency"
+        );
+        assert_eq!(issues[0].suggestion.as_deref(), Some("swift-log"));
+        assert_eq!(issues[0].help(), "Did you mean 'swift-log'?");
+        assert_eq!(issues[1].dependency, "Database");
+        assert_eq!(issues[1].suggestion, None);
+    }
+}
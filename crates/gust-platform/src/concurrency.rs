@@ -0,0 +1,212 @@
+//! How many packages to fetch and parse at once.
+//!
+//! Each job is a git clone plus a `swift package dump-package`, and the
+//! latter can take hundreds of megabytes. Scaling with CPU count alone
+//! gets small CI runners with many cores killed by the OOM killer, so the
+//! default is also capped by available memory.
+
+use std::fmt;
+
+/// Memory budgeted per concurrent fetch-and-parse job.
+pub const MEMORY_PER_JOB: u64 = 512 * 1024 * 1024;
+
+/// CPU count used when it can't be detected.
+const FALLBACK_CPUS: usize = 8;
+
+/// Number of concurrent jobs and why it was chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Concurrency {
+    /// Jobs to run at once (at least 1)
+    pub jobs: usize,
+    /// What limited `jobs`
+    pub reason: ConcurrencyReason,
+}
+
+/// What determined a [`Concurrency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrencyReason {
+    /// Set with `--jobs`
+    Explicit,
+    /// One job per CPU
+    Cpus,
+    /// Fewer jobs than CPUs because available memory is low
+    Memory {
+        /// CPU count
+        cpus: usize,
+        /// Available memory in bytes
+        available: u64,
+    },
+}
+
+impl Concurrency {
+    /// Concurrency for this machine. `explicit` (from `--jobs`) always wins.
+    pub fn detect(explicit: Option<usize>) -> Self {
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(FALLBACK_CPUS);
+        Self::compute(explicit, cpus, available_memory())
+    }
+
+    /// Concurrency for `cpus` CPUs and `available` bytes of free memory
+    /// (`None` if unknown).
+    pub fn compute(explicit: Option<usize>, cpus: usize, available: Option<u64>) -> Self {
+        if let Some(jobs) = explicit {
+            return Self {
+                jobs: jobs.max(1),
+                reason: ConcurrencyReason::Explicit,
+            };
+        }
+
+        let cpus = cpus.max(1);
+        if let Some(available) = available {
+            let by_memory = (available / MEMORY_PER_JOB).max(1) as usize;
+            if by_memory < cpus {
+                return Self {
+                    jobs: by_memory,
+                    reason: ConcurrencyReason::Memory { cpus, available },
+                };
+            }
+        }
+
+        Self {
+            jobs: cpus,
+            reason: ConcurrencyReason::Cpus,
+        }
+    }
+}
+
+impl fmt::Display for Concurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            ConcurrencyReason::Explicit => write!(f, "{} jobs (--jobs)", self.jobs),
+            ConcurrencyReason::Cpus => write!(f, "{} jobs (one per CPU)", self.jobs),
+            ConcurrencyReason::Memory { cpus, available } => write!(
+                f,
+                "{} jobs ({} CPUs, limited by {} MB of available memory)",
+                self.jobs,
+                cpus,
+                available / (1024 * 1024)
+            ),
+        }
+    }
+}
+
+/// Memory available to new processes, in bytes, if it can be determined.
+///
+/// Honors `GUST_MEMORY_LIMIT` (bytes) as an override, then what's left of
+/// a cgroup v2 limit on Linux (containers and CI runners), `MemAvailable`
+/// from `/proc/meminfo`, or free plus inactive pages from `vm_stat` on
+/// macOS.
+pub fn available_memory() -> Option<u64> {
+    if let Some(limit) = std::env::var("GUST_MEMORY_LIMIT")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+    {
+        return Some(limit);
+    }
+
+    if cfg!(target_os = "linux") {
+        let meminfo = std::fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|s| parse_meminfo(&s));
+        let cgroup = std::fs::read_to_string("/sys/fs/cgroup/memory.max")
+            .ok()
+            .and_then(|max| {
+                let current = std::fs::read_to_string("/sys/fs/cgroup/memory.current").ok();
+                parse_cgroup(&max, current.as_deref())
+            });
+        match (meminfo, cgroup) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    } else if cfg!(target_os = "macos") {
+        let output = std::process::Command::new("vm_stat").output().ok()?;
+        parse_vm_stat(&String::from_utf8_lossy(&output.stdout))
+    } else {
+        None
+    }
+}
+
+/// `MemAvailable` from `/proc/meminfo`, in bytes.
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// The part of a cgroup's `memory.max` not yet used according to
+/// `memory.current`, in bytes. `None` without a limit (`max`).
+fn parse_cgroup(max: &str, current: Option<&str>) -> Option<u64> {
+    let max: u64 = max.trim().parse().ok()?;
+    let current = current.and_then(|c| c.trim().parse().ok()).unwrap_or(0);
+    Some(max.saturating_sub(current))
+}
+
+/// Free, inactive and speculative pages from `vm_stat`, in bytes.
+fn parse_vm_stat(vm_stat: &str) -> Option<u64> {
+    // "Mach Virtual Memory Statistics: (page size of 16384 bytes)"
+    let page_size: u64 = vm_stat
+        .split("page size of ")
+        .nth(1)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+
+    let pages = |label: &str| -> u64 {
+        vm_stat
+            .lines()
+            .find(|l| l.starts_with(label))
+            .and_then(|l| l.rsplit(':').next())
+            .and_then(|v| v.trim().trim_end_matches('.').parse().ok())
+            .unwrap_or(0)
+    };
+    Some((pages("Pages free") + pages("Pages inactive") + pages("Pages speculative")) * page_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_low_memory_reduces_concurrency() {
+        let gib = 1024 * 1024 * 1024;
+
+        let plenty = Concurrency::compute(None, 16, Some(64 * gib));
+        assert_eq!(plenty.jobs, 16);
+        assert_eq!(plenty.reason, ConcurrencyReason::Cpus);
+
+        let tight = Concurrency::compute(None, 16, Some(2 * gib));
+        assert_eq!(tight.jobs, 4);
+        assert!(matches!(
+            tight.reason,
+            ConcurrencyReason::Memory { cpus: 16, .. }
+        ));
+        assert_eq!(
+            tight.to_string(),
+            "4 jobs (16 CPUs, limited by 2048 MB of available memory)"
+        );
+
+        // Never below one job, and --jobs always wins
+        assert_eq!(Concurrency::compute(None, 16, Some(1)).jobs, 1);
+        assert_eq!(Concurrency::compute(Some(12), 16, Some(1)).jobs, 12);
+    }
+
+    #[test]
+    fn test_parse_memory_sources() {
+        let meminfo = "MemTotal:       16315204 kB\nMemFree:          412344 kB\nMemAvailable:    8157602 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some(8157602 * 1024));
+
+        // A cgroup's limit counts what its processes already use
+        assert_eq!(
+            parse_cgroup("4294967296\n", Some("1073741824\n")),
+            Some(3 * 1024 * 1024 * 1024)
+        );
+        assert_eq!(parse_cgroup("1024\n", Some("2048\n")), Some(0));
+        assert_eq!(parse_cgroup("1024\n", None), Some(1024));
+        assert_eq!(parse_cgroup("max\n", Some("1024\n")), None);
+
+        let vm_stat = "Mach Virtual Memory Statistics: (page size of 16384 bytes)\nPages free:                               10000.\nPages active:                            50000.\nPages inactive:                          20000.\nPages speculative:                        1000.\n";
+        assert_eq!(parse_vm_stat(vm_stat), Some(31000 * 16384));
+    }
+}
@@ -1,5 +1,9 @@
 //! Platform and Swift toolchain detection for Gust.
 
+mod concurrency;
+
+pub use concurrency::{available_memory, Concurrency, ConcurrencyReason, MEMORY_PER_JOB};
+
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::process::Command;
@@ -98,6 +102,11 @@ impl SwiftToolchain {
         Err(PlatformError::VersionParseError(output.to_string()))
     }
 
+    /// `major.minor` of this toolchain, as written in `swift-tools-version`.
+    pub fn tools_version(&self) -> String {
+        format!("{}.{}", self.major_version, self.minor_version)
+    }
+
     /// Check if this toolchain meets the minimum version requirement.
     pub fn meets_requirement(&self, tools_version: &str) -> bool {
         let parts: Vec<u32> = tools_version
@@ -146,6 +155,7 @@ mod tests {
         };
 
         assert!(toolchain.meets_requirement("5.9"));
+        assert_eq!(toolchain.tools_version(), "5.9");
         assert!(toolchain.meets_requirement("5.8"));
         assert!(!toolchain.meets_requirement("5.10"));
         assert!(!toolchain.meets_requirement("6.0"));
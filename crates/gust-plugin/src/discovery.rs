@@ -48,11 +48,18 @@ pub fn discover_plugins(package_dir: &Path) -> Result<Vec<DiscoveredPlugin>, Plu
 
     for target in &manifest.targets {
         if target.target_type == TargetType::Plugin {
-            // Determine plugin kind from target configuration
-            // For now, assume all plugin targets are build tools
-            // Real implementation would parse Package.swift for @main attribute
-            let capability = PluginCapability::BuildTool;
-            let kind = PluginKind::BuildTool;
+            // Gust.toml doesn't record capabilities; plugins declared
+            // there are build tools
+            let capability = target
+                .plugin_capability
+                .clone()
+                .unwrap_or(PluginCapability::BuildTool);
+            let (kind, permissions) = match &capability {
+                PluginCapability::BuildTool => (PluginKind::BuildTool, Vec::new()),
+                PluginCapability::Command(command) => {
+                    (PluginKind::Command, command.permissions.clone())
+                }
+            };
 
             let source_path = target
                 .path
@@ -63,7 +70,7 @@ pub fn discover_plugins(package_dir: &Path) -> Result<Vec<DiscoveredPlugin>, Plu
                 name: target.name.clone(),
                 kind,
                 capability,
-                permissions: Vec::new(),
+                permissions,
                 source_path,
                 source_package: manifest.package.name.clone(),
             });
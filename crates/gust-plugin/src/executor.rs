@@ -196,11 +196,16 @@ impl PluginExecutor {
                         NetworkScope::All => {
                             profile.push_str("(allow network*)\n");
                         }
-                        NetworkScope::Local(port) => {
-                            profile.push_str(&format!(
-                                "(allow network-outbound (local ip \"*:{}\"))\n",
-                                port
-                            ));
+                        NetworkScope::Local(ports) if ports.is_empty() => {
+                            profile.push_str("(allow network-outbound (local ip \"*:*\"))\n");
+                        }
+                        NetworkScope::Local(ports) => {
+                            for port in ports {
+                                profile.push_str(&format!(
+                                    "(allow network-outbound (local ip \"*:{}\"))\n",
+                                    port
+                                ));
+                            }
                         }
                         NetworkScope::Docker => {
                             // Docker socket access
@@ -3,10 +3,79 @@
 //! Implements the Swift Package Registry Service API (SE-0292, SE-0321).
 //! https://github.com/apple/swift-package-manager/blob/main/Documentation/PackageRegistry/Registry.md
 
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// The default registry.
+pub const DEFAULT_REGISTRY_URL: &str = "https://packages.swift.org";
+
+/// Environment variable capping registry requests per second.
+pub const RATE_LIMIT_ENV: &str = "GUST_REGISTRY_RATE_LIMIT";
+
+/// Environment variable naming the scope of packages given without one.
+pub const SCOPE_ENV: &str = "GUST_REGISTRY_SCOPE";
+
+/// Registry URL and rate limit from Gust's config, below the environment.
+static CONFIGURED: std::sync::OnceLock<(Option<String>, Option<u32>)> = std::sync::OnceLock::new();
+
+/// Use `url` and `rate_limit` in [`RegistryClient::from_env`] when
+/// `GUST_REGISTRY_URL` or `GUST_REGISTRY_RATE_LIMIT` isn't set, such as the
+/// values from Gust's config. Only the first call has an effect.
+pub fn configure(url: Option<String>, rate_limit: Option<u32>) {
+    let _ = CONFIGURED.set((url, rate_limit));
+}
+
+/// Default scope from Gust's config, below the environment.
+static CONFIGURED_SCOPE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// Use `scope` in [`registry_identity`] when `GUST_REGISTRY_SCOPE` isn't
+/// set. Only the first call has an effect.
+pub fn configure_scope(scope: String) {
+    let _ = CONFIGURED_SCOPE.set(scope);
+}
+
+/// The registry identity of `package`: `scope.name` as written, or a bare
+/// name in the default scope from `GUST_REGISTRY_SCOPE` or the config.
+///
+/// Fails for a bare name when there's no default scope, rather than
+/// guessing one.
+pub fn registry_identity(package: &str) -> Result<PackageIdentifier, RegistryError> {
+    let default_scope = std::env::var(SCOPE_ENV)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .or_else(|| CONFIGURED_SCOPE.get().cloned());
+    identity_in(package, default_scope.as_deref())
+}
+
+fn identity_in(
+    package: &str,
+    default_scope: Option<&str>,
+) -> Result<PackageIdentifier, RegistryError> {
+    let (scope, name) = match package.split_once('.') {
+        Some((scope, name)) => (scope, name),
+        None => (
+            default_scope.ok_or_else(|| RegistryError::MissingScope(package.to_string()))?,
+            package,
+        ),
+    };
+    if scope.is_empty() || name.is_empty() {
+        return Err(RegistryError::MissingScope(package.to_string()));
+    }
+    Ok(PackageIdentifier {
+        scope: scope.to_string(),
+        name: name.to_string(),
+    })
+}
+
+/// How many times a rate-limited request is retried by default.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` waited out by default; longer ones are reported
+/// as [`RegistryError::RateLimited`] instead.
+const DEFAULT_MAX_RETRY_WAIT: Duration = Duration::from_secs(10);
+
 #[derive(Error, Debug)]
 pub enum RegistryError {
     #[error("Package not found: {0}")]
@@ -17,28 +86,230 @@ pub enum RegistryError {
     Network(#[from] reqwest::Error),
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    /// A registry package named without a scope, and no default scope set
+    #[error(
+        "Registry package '{0}' has no scope; write it as scope.name or set registry-scope (GUST_REGISTRY_SCOPE)"
+    )]
+    MissingScope(String),
+    /// The registry refused the request and said why, as RFC 7807 problem
+    /// details
+    #[error(
+        "Registry error {status} ({title}){}",
+        detail.as_deref().map(|d| format!(": {}", d)).unwrap_or_default()
+    )]
+    Problem {
+        status: u16,
+        title: String,
+        detail: Option<String>,
+    },
+    /// Too many requests; `retry_after` is how long the registry asked us
+    /// to wait
+    #[error(
+        "Rate limited by the registry{}",
+        retry_after.map(|d| format!(", retry after {}s", d.as_secs())).unwrap_or_default()
+    )]
+    RateLimited {
+        retry_after: Option<Duration>,
+        detail: Option<String>,
+    },
 }
 
 /// A Swift Package Registry client.
 pub struct RegistryClient {
     base_url: String,
     client: Client,
+    /// Bearer token sent with every request
+    auth_token: Option<String>,
+    /// Spaces requests out, if a rate is set
+    limiter: Option<RateLimiter>,
+    /// Retries of a request answered with 429
+    max_retries: u32,
+    /// Longest wait before a retry
+    max_retry_wait: Duration,
+}
+
+/// Spaces requests at least `interval` apart.
+struct RateLimiter {
+    interval: Duration,
+    /// When the next request may start
+    next: tokio::sync::Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn per_second(requests: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests,
+            next: tokio::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for this request's turn.
+    async fn acquire(&self) {
+        let start = {
+            let mut next = self.next.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + self.interval;
+            start
+        };
+        tokio::time::sleep_until(start.into()).await;
+    }
+}
+
+/// Result of a registry reachability check.
+#[derive(Debug, Clone)]
+pub struct RegistryHealth {
+    /// HTTP status the registry answered with
+    pub status: u16,
+    /// Round-trip time of the check
+    pub latency: Duration,
 }
 
 impl RegistryClient {
     /// Create a client for the default registry.
     pub fn new() -> Self {
-        Self::with_url("https://packages.swift.org")
+        Self::with_url(DEFAULT_REGISTRY_URL)
     }
 
     /// Create a client for a custom registry.
     pub fn with_url(url: impl Into<String>) -> Self {
         Self {
-            base_url: url.into(),
+            base_url: url.into().trim_end_matches('/').to_string(),
             client: Client::new(),
+            auth_token: None,
+            limiter: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_retry_wait: DEFAULT_MAX_RETRY_WAIT,
+        }
+    }
+
+    /// Create a client configured from `GUST_REGISTRY_URL`,
+    /// `GUST_REGISTRY_TOKEN` and `GUST_REGISTRY_RATE_LIMIT`, then from
+    /// [`configure`], falling back to the default registry.
+    pub fn from_env() -> Self {
+        let (configured_url, configured_rate) = CONFIGURED.get().cloned().unwrap_or_default();
+        let mut client = match std::env::var("GUST_REGISTRY_URL") {
+            Ok(url) if !url.is_empty() => Self::with_url(url),
+            _ => configured_url.map_or_else(Self::new, Self::with_url),
+        };
+        if let Ok(token) = std::env::var("GUST_REGISTRY_TOKEN") {
+            if !token.is_empty() {
+                client = client.with_auth(token);
+            }
+        }
+        let rate = match std::env::var(RATE_LIMIT_ENV).map(|rate| rate.parse()) {
+            Ok(Ok(rate)) => Some(rate),
+            Ok(Err(_)) => {
+                tracing::warn!("Ignoring invalid {}", RATE_LIMIT_ENV);
+                configured_rate
+            }
+            Err(_) => configured_rate,
+        };
+        match rate {
+            Some(rate) => client.with_rate_limit(rate),
+            None => client,
+        }
+    }
+
+    /// Authenticate requests with a bearer token.
+    pub fn with_auth(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Send at most `requests_per_second` requests; 0 removes the limit.
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.limiter =
+            (requests_per_second > 0).then(|| RateLimiter::per_second(requests_per_second));
+        self
+    }
+
+    /// Retry requests answered with 429 Too Many Requests up to
+    /// `max_retries` times, waiting as long as `Retry-After` asks if that's
+    /// no more than `max_wait`.
+    pub fn with_retries(mut self, max_retries: u32, max_wait: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.max_retry_wait = max_wait;
+        self
+    }
+
+    /// The registry's base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Check whether requests are authenticated.
+    pub fn has_auth(&self) -> bool {
+        self.auth_token.is_some()
+    }
+
+    fn get(&self, url: &str) -> RequestBuilder {
+        self.authorize(self.client.get(url))
+    }
+
+    /// Send `request` within the rate limit, retrying while the registry
+    /// answers 429 and the wait it asks for is acceptable.
+    ///
+    /// Without `Retry-After`, retries back off by a second per attempt. The
+    /// last 429 is returned for [`check_status`] to report.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, RegistryError> {
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+            let Some(this_try) = request.try_clone() else {
+                return Ok(request.send().await?);
+            };
+            let resp = this_try.send().await?;
+            if resp.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= self.max_retries {
+                return Ok(resp);
+            }
+
+            attempt += 1;
+            let wait = resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after)
+                .unwrap_or(Duration::from_secs(attempt.into()));
+            if wait > self.max_retry_wait {
+                return Ok(resp);
+            }
+            tracing::debug!(
+                "Rate limited by the registry, retrying in {}s ({}/{})",
+                wait.as_secs(),
+                attempt,
+                self.max_retries
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
         }
     }
 
+    /// Check that the registry is reachable with a HEAD to its base URL.
+    ///
+    /// Any HTTP response counts as reachable; the status is reported so
+    /// callers can flag auth or server errors.
+    pub async fn health(&self, timeout: Duration) -> Result<RegistryHealth, RegistryError> {
+        let start = Instant::now();
+        let resp = self
+            .authorize(self.client.head(&self.base_url))
+            .timeout(timeout)
+            .send()
+            .await?;
+
+        Ok(RegistryHealth {
+            status: resp.status().as_u16(),
+            latency: start.elapsed(),
+        })
+    }
+
     /// List all versions of a package.
     pub async fn list_versions(
         &self,
@@ -48,17 +319,17 @@ impl RegistryClient {
         let url = format!("{}/{}/{}", self.base_url, scope, name);
 
         let resp = self
-            .client
-            .get(&url)
-            .header("Accept", "application/vnd.swift.registry.v1+json")
-            .send()
+            .send(
+                self.get(&url)
+                    .header("Accept", "application/vnd.swift.registry.v1+json"),
+            )
             .await?;
 
         if resp.status() == 404 {
             return Err(RegistryError::NotFound(format!("{}/{}", scope, name)));
         }
 
-        let body: PackageVersions = resp.json().await?;
+        let body: PackageVersions = check_status(resp).await?.json().await?;
         Ok(body)
     }
 
@@ -72,10 +343,10 @@ impl RegistryClient {
         let url = format!("{}/{}/{}/{}", self.base_url, scope, name, version);
 
         let resp = self
-            .client
-            .get(&url)
-            .header("Accept", "application/vnd.swift.registry.v1+json")
-            .send()
+            .send(
+                self.get(&url)
+                    .header("Accept", "application/vnd.swift.registry.v1+json"),
+            )
             .await?;
 
         if resp.status() == 404 {
@@ -85,7 +356,7 @@ impl RegistryClient {
             ));
         }
 
-        let body: PackageRelease = resp.json().await?;
+        let body: PackageRelease = check_status(resp).await?.json().await?;
         Ok(body)
     }
 
@@ -102,10 +373,7 @@ impl RegistryClient {
         );
 
         let resp = self
-            .client
-            .get(&url)
-            .header("Accept", "text/x-swift")
-            .send()
+            .send(self.get(&url).header("Accept", "text/x-swift"))
             .await?;
 
         if resp.status() == 404 {
@@ -115,7 +383,7 @@ impl RegistryClient {
             ));
         }
 
-        let body = resp.text().await?;
+        let body = check_status(resp).await?.text().await?;
         Ok(body)
     }
 
@@ -129,10 +397,7 @@ impl RegistryClient {
         let url = format!("{}/{}/{}/{}.zip", self.base_url, scope, name, version);
 
         let resp = self
-            .client
-            .get(&url)
-            .header("Accept", "application/zip")
-            .send()
+            .send(self.get(&url).header("Accept", "application/zip"))
             .await?;
 
         if resp.status() == 404 {
@@ -142,7 +407,7 @@ impl RegistryClient {
             ));
         }
 
-        let bytes = resp.bytes().await?;
+        let bytes = check_status(resp).await?.bytes().await?;
         Ok(bytes.to_vec())
     }
 
@@ -155,21 +420,65 @@ impl RegistryClient {
         );
 
         let resp = self
-            .client
-            .get(&lookup_url)
-            .header("Accept", "application/vnd.swift.registry.v1+json")
-            .send()
+            .send(
+                self.get(&lookup_url)
+                    .header("Accept", "application/vnd.swift.registry.v1+json"),
+            )
             .await?;
 
         if resp.status() == 404 {
             return Ok(vec![]);
         }
 
-        let body: IdentifiersResponse = resp.json().await?;
+        let body: IdentifiersResponse = check_status(resp).await?.json().await?;
         Ok(body.identifiers)
     }
 }
 
+/// `resp` if it succeeded, otherwise the registry's explanation of why not.
+///
+/// Error bodies are read as `application/problem+json`; a registry that
+/// sends something else gets the status's standard reason as the title.
+async fn check_status(resp: Response) -> Result<Response, RegistryError> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+
+    let retry_after = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let problem: ProblemDetails = resp
+        .text()
+        .await
+        .ok()
+        .and_then(|body| serde_json::from_str(&body).ok())
+        .unwrap_or_default();
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(RegistryError::RateLimited {
+            retry_after,
+            detail: problem.detail,
+        });
+    }
+    Err(RegistryError::Problem {
+        status: status.as_u16(),
+        title: problem
+            .title
+            .or_else(|| status.canonical_reason().map(String::from))
+            .unwrap_or_else(|| "Unknown error".to_string()),
+        detail: problem.detail,
+    })
+}
+
+/// Seconds to wait from a `Retry-After` header. HTTP dates aren't
+/// supported.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
 impl Default for RegistryClient {
     fn default() -> Self {
         Self::new()
@@ -200,6 +509,16 @@ pub struct ReleaseProblem {
     pub detail: Option<String>,
 }
 
+/// An RFC 7807 problem details body, sent by registries with error
+/// responses.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ProblemDetails {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    detail: Option<String>,
+}
+
 /// Detailed release metadata.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageRelease {
@@ -210,6 +529,18 @@ pub struct PackageRelease {
     pub metadata: Option<ReleaseMetadata>,
 }
 
+impl PackageRelease {
+    /// The release's source repository, preferring an HTTPS URL over SSH
+    /// ones.
+    pub fn repository_url(&self) -> Option<&str> {
+        let urls = &self.metadata.as_ref()?.repository_urls;
+        urls.iter()
+            .find(|url| url.starts_with("https://"))
+            .or_else(|| urls.first())
+            .map(String::as_str)
+    }
+}
+
 /// A resource in a release.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReleaseResource {
@@ -291,6 +622,7 @@ mod urlencoding {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use gust_testkit::{MockServer, Response};
 
     #[test]
     fn test_package_identifier_display() {
@@ -301,6 +633,148 @@ mod tests {
         assert_eq!(id.to_string(), "apple.swift-argument-parser");
     }
 
+    #[test]
+    fn test_bare_name_needs_a_default_scope() {
+        let id = identity_in("apple.swift-log", None).unwrap();
+        assert_eq!(
+            (id.scope.as_str(), id.name.as_str()),
+            ("apple", "swift-log")
+        );
+        let id = identity_in("swift-log", Some("acme")).unwrap();
+        assert_eq!(id.to_string(), "acme.swift-log");
+
+        for package in ["swift-log", ".swift-log", "apple."] {
+            assert!(matches!(
+                identity_in(package, None),
+                Err(RegistryError::MissingScope(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_status() {
+        let server = MockServer::start(|request| {
+            if request.header("authorization") == Some("Bearer secret") {
+                Response::new(204)
+            } else {
+                Response::new(401)
+            }
+        })
+        .await;
+
+        let client = RegistryClient::with_url(format!("{}/", server.url())).with_auth("secret");
+        assert!(client.has_auth());
+        assert_eq!(client.base_url(), server.url());
+
+        let health = client.health(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(health.status, 204);
+    }
+
+    #[tokio::test]
+    async fn test_health_fails_when_unreachable() {
+        // Bind then drop to get a port nothing listens on
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = RegistryClient::with_url(format!("http://{}", addr));
+        assert!(client.health(Duration::from_secs(2)).await.is_err());
+    }
+
+    /// A registry that answers every request with `response`.
+    async fn serve(response: Response) -> (MockServer, RegistryClient) {
+        let server = MockServer::start(move |_| response.clone()).await;
+        let client = RegistryClient::with_url(server.url());
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn test_problem_details_become_structured_errors() {
+        let body = r#"{"type": "about:blank", "status": 403, "title": "Forbidden", "detail": "version 1.0.0 was yanked"}"#;
+        let (_server, client) = serve(
+            Response::new(403)
+                .with_header("content-type", "application/problem+json")
+                .with_body(body),
+        )
+        .await;
+        let err = client
+            .get_version("apple", "swift-log", "1.0.0")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            RegistryError::Problem { status: 403, title, detail: Some(detail) }
+                if title == "Forbidden" && detail == "version 1.0.0 was yanked"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Registry error 403 (Forbidden): version 1.0.0 was yanked"
+        );
+
+        let (_server, client) = serve(Response::new(429).with_header("retry-after", 30)).await;
+        let err = client
+            .list_versions("apple", "swift-log")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RegistryError::RateLimited {
+                retry_after: Some(d),
+                detail: None,
+            } if d == Duration::from_secs(30)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Rate limited by the registry, retry after 30s"
+        );
+
+        // Without a problem body the status's reason is the title
+        let (_server, client) = serve(Response::new(500)).await;
+        let err = client
+            .download_source("apple", "swift-log", "1.0.0")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Registry error 500 (Internal Server Error)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_is_retried() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // Rate limited the first time only
+        let limited = AtomicBool::new(false);
+        let server = MockServer::start(move |_| {
+            if limited.swap(true, Ordering::SeqCst) {
+                Response::json(r#"{"releases": {"1.5.0": {}}}"#)
+            } else {
+                Response::new(429).with_header("retry-after", 1)
+            }
+        })
+        .await;
+
+        let client = RegistryClient::with_url(server.url());
+        let start = Instant::now();
+        let versions = client.list_versions("apple", "swift-log").await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(versions.releases.contains_key("1.5.0"));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_spaces_requests() {
+        let limiter = RateLimiter::per_second(20);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        // The first goes right away, the other two 50ms apart
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(RegistryClient::new().with_rate_limit(0).limiter.is_none());
+    }
+
     #[test]
     fn test_url_encoding() {
         assert_eq!(
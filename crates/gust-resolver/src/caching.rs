@@ -0,0 +1,179 @@
+//! Memoizing wrapper for any [`PackageProvider`].
+//!
+//! PubGrub asks for the same package's versions and dependencies many times
+//! while backtracking, and a provider backed by the network or a subprocess
+//! pays for every call. [`CachingProvider`] remembers each successful answer,
+//! so the inner provider sees every unique query at most once. Errors are
+//! not cached, so a transient failure can succeed when asked again.
+
+use crate::error::ResolveError;
+use crate::provider::PackageProvider;
+use gust_types::{Dependency, Version};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A [`PackageProvider`] that caches the answers of another in memory.
+pub struct CachingProvider<P: PackageProvider> {
+    inner: P,
+    versions: Mutex<HashMap<String, Vec<Version>>>,
+    dependencies: Mutex<HashMap<(String, Version), Vec<Dependency>>>,
+}
+
+impl<P: PackageProvider> CachingProvider<P> {
+    /// Wrap `inner`, starting with an empty cache.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            versions: Mutex::new(HashMap::new()),
+            dependencies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Unwrap the provider, dropping the cache.
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+impl<P: PackageProvider> PackageProvider for CachingProvider<P> {
+    fn available_versions(&self, package: &str) -> Result<Vec<Version>, ResolveError> {
+        if let Some(versions) = self.versions.lock().unwrap().get(package) {
+            return Ok(versions.clone());
+        }
+        // Not holding the lock while the inner provider works; two threads
+        // asking at once may both query it, and either answer is the same
+        let versions = self.inner.available_versions(package)?;
+        self.versions
+            .lock()
+            .unwrap()
+            .insert(package.to_string(), versions.clone());
+        Ok(versions)
+    }
+
+    fn dependencies(
+        &self,
+        package: &str,
+        version: &Version,
+    ) -> Result<Vec<Dependency>, ResolveError> {
+        let key = (package.to_string(), version.clone());
+        if let Some(deps) = self.dependencies.lock().unwrap().get(&key) {
+            return Ok(deps.clone());
+        }
+        let deps = self.inner.dependencies(package, version)?;
+        self.dependencies.lock().unwrap().insert(key, deps.clone());
+        Ok(deps)
+    }
+
+    fn version_problem(&self, package: &str, version: &Version) -> Option<String> {
+        self.inner.version_problem(package, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MemoryProvider;
+    use crate::Resolver;
+    use gust_types::{Manifest, VersionReq};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the queries that reach a [`MemoryProvider`].
+    #[derive(Default)]
+    struct Counting {
+        inner: MemoryProvider,
+        versions: Mutex<HashMap<String, usize>>,
+        dependencies: Mutex<HashMap<(String, Version), usize>>,
+        failures: AtomicUsize,
+    }
+
+    impl PackageProvider for Counting {
+        fn available_versions(&self, package: &str) -> Result<Vec<Version>, ResolveError> {
+            *self
+                .versions
+                .lock()
+                .unwrap()
+                .entry(package.to_string())
+                .or_default() += 1;
+            let result = self.inner.available_versions(package);
+            if result.is_err() {
+                self.failures.fetch_add(1, Ordering::SeqCst);
+            }
+            result
+        }
+
+        fn dependencies(
+            &self,
+            package: &str,
+            version: &Version,
+        ) -> Result<Vec<Dependency>, ResolveError> {
+            *self
+                .dependencies
+                .lock()
+                .unwrap()
+                .entry((package.to_string(), version.clone()))
+                .or_default() += 1;
+            self.inner.dependencies(package, version)
+        }
+    }
+
+    #[test]
+    fn test_inner_provider_queried_once_per_unique_query() {
+        let mut inner = MemoryProvider::new();
+        let log = Dependency::registry("swift-log", VersionReq::parse("^1.4").unwrap());
+        inner.add_package("swift-log", Version::new(1, 4, 0), vec![]);
+        inner.add_package("swift-log", Version::new(1, 5, 4), vec![]);
+        inner.add_package("swift-nio", Version::new(2, 60, 0), vec![log.clone()]);
+        let provider = CachingProvider::new(Counting {
+            inner,
+            ..Default::default()
+        });
+
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "swift-nio".to_string(),
+            Dependency::registry("swift-nio", VersionReq::parse("^2.0").unwrap()),
+        );
+
+        // Same answers as the inner provider, however often it's resolved
+        let resolver = Resolver::new(&provider);
+        for _ in 0..3 {
+            let resolution = resolver.resolve(&manifest).unwrap();
+            assert_eq!(
+                resolution.packages["swift-log"].version,
+                Version::new(1, 5, 4)
+            );
+            assert_eq!(
+                resolution.packages["swift-nio"].version,
+                Version::new(2, 60, 0)
+            );
+        }
+        assert_eq!(
+            provider.available_versions("swift-log").unwrap(),
+            provider
+                .inner()
+                .inner
+                .available_versions("swift-log")
+                .unwrap()
+        );
+
+        let counting = provider.inner();
+        assert!(!counting.versions.lock().unwrap().is_empty());
+        assert!(counting.versions.lock().unwrap().values().all(|&n| n == 1));
+        assert!(counting
+            .dependencies
+            .lock()
+            .unwrap()
+            .values()
+            .all(|&n| n == 1));
+
+        // Failures reach the inner provider every time
+        assert!(provider.available_versions("missing").is_err());
+        assert!(provider.available_versions("missing").is_err());
+        assert_eq!(counting.failures.load(Ordering::SeqCst), 2);
+    }
+}
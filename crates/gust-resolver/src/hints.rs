@@ -94,6 +94,8 @@ pub enum ChoiceReason {
     Override,
     /// Version was the only one available
     OnlyOption,
+    /// Version was pinned by the manifest (tag, branch, revision or path)
+    Pinned,
 }
 
 impl std::fmt::Display for ChoiceReason {
@@ -104,6 +106,7 @@ impl std::fmt::Display for ChoiceReason {
             Self::LowestCompatible => write!(f, "lowest compatible"),
             Self::Override => write!(f, "override"),
             Self::OnlyOption => write!(f, "only option"),
+            Self::Pinned => write!(f, "pinned"),
         }
     }
 }
@@ -115,6 +118,8 @@ pub struct ResolutionTrace {
     choices: HashMap<String, (Version, ChoiceReason)>,
     /// Map of package name to the packages that required it
     required_by: HashMap<String, Vec<String>>,
+    /// Map of package name to a warning about the chosen version
+    warnings: HashMap<String, String>,
 }
 
 impl ResolutionTrace {
@@ -131,10 +136,10 @@ impl ResolutionTrace {
 
     /// Record that a package was required by another.
     pub fn record_requirement(&mut self, package: &str, required_by: &str) {
-        self.required_by
-            .entry(package.to_string())
-            .or_default()
-            .push(required_by.to_string());
+        let entry = self.required_by.entry(package.to_string()).or_default();
+        if !entry.iter().any(|r| r == required_by) {
+            entry.push(required_by.to_string());
+        }
     }
 
     /// Get the choice for a package.
@@ -150,6 +155,56 @@ impl ResolutionTrace {
             .unwrap_or(&[])
     }
 
+    /// Record a warning about the version chosen for a package, replacing
+    /// any earlier one.
+    pub fn record_warning(&mut self, package: &str, warning: String) {
+        self.warnings.insert(package.to_string(), warning);
+    }
+
+    /// Add the choices, requirements and warnings of `other`, such as a
+    /// trace of a separately resolved part of the graph.
+    pub fn merge(&mut self, other: ResolutionTrace) {
+        self.choices.extend(other.choices);
+        for (package, required_by) in other.required_by {
+            for by in required_by {
+                self.record_requirement(&package, &by);
+            }
+        }
+        self.warnings.extend(other.warnings);
+    }
+
+    /// Warnings about chosen versions, sorted by package name.
+    pub fn warnings(&self) -> Vec<&str> {
+        let mut names: Vec<&String> = self.warnings.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|n| self.warnings[n].as_str())
+            .collect()
+    }
+
+    /// Render the decision log: each package, its chosen version and why.
+    ///
+    /// One line per package, sorted by name, e.g.
+    /// `swift-log 1.4.0 (locked), required by my-app, swift-nio`.
+    pub fn explain(&self) -> String {
+        let mut names: Vec<&String> = self.choices.keys().collect();
+        names.sort();
+
+        let mut out = String::new();
+        for name in names {
+            let (version, reason) = &self.choices[name];
+            out.push_str(&format!("{} {} ({})", name, version, reason));
+
+            let required_by = self.get_required_by(name);
+            if !required_by.is_empty() {
+                out.push_str(&format!(", required by {}", required_by.join(", ")));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
     /// Convert to resolution metadata for the lockfile.
     pub fn to_metadata(&self, package: &str) -> gust_types::ResolutionMetadata {
         let required_by = self.required_by.get(package).cloned().unwrap_or_default();
@@ -5,25 +5,33 @@
 //! - Proper backtracking and conflict detection
 //! - Version overrides and constraints
 //! - Lockfile hints for fast re-resolution
+//! - Caching of provider answers across backtracking
 //! - Rich error messages with derivation trees
 
+pub mod caching;
 pub mod conflict;
 pub mod error;
 pub mod hints;
 pub mod package;
 pub mod provider;
+pub mod registry;
 
+pub use caching::CachingProvider;
 pub use error::ResolveError;
 pub use hints::{ChoiceReason, LockfileHints, ResolutionTrace};
 pub use package::GustPackage;
 pub use provider::{
     GustDependencyProvider, GustVersion, GustVersionSet, MemoryProvider, PackageProvider,
 };
+pub use registry::RegistryProvider;
 
-use gust_types::{Dependency, Manifest, ResolutionStrategy, Version};
+use gust_types::{
+    Dependency, DependencySource, Manifest, ResolutionOptions, ResolutionStrategy, ResolvedPackage,
+    Version, VersionReq,
+};
 use pubgrub::resolve as pubgrub_resolve;
 use pubgrub::{DefaultStringReporter, PubGrubError, Reporter};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
 /// A resolved dependency graph.
@@ -47,7 +55,10 @@ pub struct ResolvedDep {
 /// The resolved source of a package.
 #[derive(Debug, Clone)]
 pub enum ResolvedSource {
-    Registry,
+    Registry {
+        /// Upstream repository the registry reports for the release
+        repository: Option<String>,
+    },
     Git {
         url: String,
         revision: String,
@@ -58,6 +69,71 @@ pub enum ResolvedSource {
     },
 }
 
+impl From<ResolvedDep> for ResolvedPackage {
+    fn from(dep: ResolvedDep) -> Self {
+        let (source, git, revision, repository) = match dep.source {
+            ResolvedSource::Registry { repository } => {
+                (DependencySource::Registry, None, None, repository)
+            }
+            ResolvedSource::Git { url, revision, .. } => {
+                (DependencySource::Git, Some(url), Some(revision), None)
+            }
+            ResolvedSource::Path { .. } => (DependencySource::Path, None, None, None),
+        };
+
+        Self {
+            name: dep.name,
+            version: dep.version,
+            source,
+            checksum: None,
+            git,
+            revision,
+            repository,
+            dependencies: dep.dependencies,
+        }
+    }
+}
+
+impl TryFrom<ResolvedPackage> for ResolvedDep {
+    type Error = ResolveError;
+
+    /// Fails for git packages without a URL or revision, and for path
+    /// packages, whose location isn't recorded in a `ResolvedPackage`.
+    fn try_from(pkg: ResolvedPackage) -> Result<Self, Self::Error> {
+        let source = match pkg.source {
+            DependencySource::Registry => ResolvedSource::Registry {
+                repository: pkg.repository,
+            },
+            DependencySource::Git => {
+                let (Some(url), Some(revision)) = (pkg.git, pkg.revision) else {
+                    return Err(ResolveError::ProviderError(format!(
+                        "Git package {} is missing its URL or revision",
+                        pkg.name
+                    )));
+                };
+                ResolvedSource::Git {
+                    url,
+                    revision,
+                    tag: None,
+                }
+            }
+            DependencySource::Path => {
+                return Err(ResolveError::ProviderError(format!(
+                    "Path package {} has no recorded location",
+                    pkg.name
+                )))
+            }
+        };
+
+        Ok(Self {
+            name: pkg.name,
+            version: pkg.version,
+            source,
+            dependencies: pkg.dependencies,
+        })
+    }
+}
+
 /// The main dependency resolver.
 ///
 /// # Example
@@ -73,6 +149,7 @@ pub struct Resolver<P: PackageProvider> {
     provider: P,
     hints: LockfileHints,
     strategy: ResolutionStrategy,
+    options: Option<ResolutionOptions>,
 }
 
 impl<P: PackageProvider> Resolver<P> {
@@ -82,6 +159,7 @@ impl<P: PackageProvider> Resolver<P> {
             provider,
             hints: LockfileHints::new(),
             strategy: ResolutionStrategy::Highest,
+            options: None,
         }
     }
 
@@ -97,15 +175,39 @@ impl<P: PackageProvider> Resolver<P> {
         self
     }
 
+    /// Use merged resolution options, such as those from
+    /// `ResolutionSources::merge` in gust-manifest, including their
+    /// strategy.
+    pub fn with_options(mut self, options: ResolutionOptions) -> Self {
+        self.strategy = options.strategy;
+        self.options = Some(options);
+        self
+    }
+
     /// Resolve dependencies for a manifest.
     ///
     /// This uses the PubGrub algorithm for SAT-based resolution with
     /// proper backtracking and conflict detection.
     pub fn resolve(&self, manifest: &Manifest) -> Result<Resolution, ResolveError> {
+        self.resolve_with_trace(manifest)
+            .map(|(resolution, _trace)| resolution)
+    }
+
+    /// Resolve dependencies and return the trace of every version choice.
+    ///
+    /// The trace records the chosen version for each package along with its
+    /// [`ChoiceReason`]; use [`ResolutionTrace::explain`] to render it.
+    pub fn resolve_with_trace(
+        &self,
+        manifest: &Manifest,
+    ) -> Result<(Resolution, ResolutionTrace), ResolveError> {
         // Create the dependency provider
-        let dp = GustDependencyProvider::new(&self.provider, Arc::new(manifest.clone()))
+        let mut dp = GustDependencyProvider::new(&self.provider, Arc::new(manifest.clone()))
             .with_hints(self.hints.clone())
             .with_strategy(self.strategy);
+        if let Some(options) = &self.options {
+            dp = dp.with_options(options);
+        }
 
         // Run PubGrub resolution
         let root = GustPackage::Root;
@@ -142,14 +244,16 @@ impl<P: PackageProvider> Resolver<P> {
                             ResolvedDep {
                                 name,
                                 version: version.0,
-                                source: ResolvedSource::Registry, // TODO: Determine actual source
+                                // TODO: Determine actual source
+                                source: ResolvedSource::Registry { repository: None },
                                 dependencies: dep_names,
                             },
                         );
                     }
                 }
 
-                Ok(resolution)
+                let trace = dp.trace().clone();
+                Ok((resolution, trace))
             }
             Err(PubGrubError::NoSolution(derivation_tree)) => {
                 // Format the derivation tree into a user-friendly error
@@ -179,6 +283,151 @@ impl<P: PackageProvider> Resolver<P> {
             Err(PubGrubError::ErrorInShouldCancel(e)) => Err(e),
         }
     }
+
+    /// Add one dependency to an existing resolution without re-resolving it.
+    ///
+    /// Only `new_dep` is resolved; every package in `base` is preferred at
+    /// its resolved version through [`LockfileHints`] and keeps its entry
+    /// unchanged. If the dependency only fits by moving a locked package,
+    /// fails with [`ResolveError::VersionConflict`] naming that package and
+    /// the requirement on it that the locked version doesn't meet.
+    pub fn resolve_incremental(
+        &self,
+        base: &Resolution,
+        new_dep: &Dependency,
+    ) -> Result<Resolution, ResolveError> {
+        if let Some(existing) = base.packages.get(&new_dep.name) {
+            return match new_dep.requirement() {
+                Some(req) if !new_dep.accepts(&existing.version) => Err(locked_conflict(
+                    existing,
+                    error::ConflictingRequirement {
+                        from: "Gust.toml".to_string(),
+                        requirement: format!("{} {}", new_dep.name, req),
+                        dependency_chain: Vec::new(),
+                    },
+                )),
+                _ => Ok(base.clone()),
+            };
+        }
+
+        let mut hints = self.hints.clone();
+        for (name, dep) in &base.packages {
+            hints.add_preferred_version(name, dep.version.clone());
+        }
+        let mut manifest = Manifest::default();
+        manifest
+            .dependencies
+            .insert(new_dep.name.clone(), new_dep.clone());
+
+        let resolver = Resolver {
+            provider: &self.provider,
+            hints,
+            strategy: self.strategy,
+            options: self.options.clone(),
+        };
+        let resolved = resolver.resolve(&manifest)?;
+
+        // Locked versions are preferred, so one that moved had to
+        let mut moved: Vec<(&String, &ResolvedDep)> = base
+            .packages
+            .iter()
+            .filter(|(name, locked)| {
+                resolved
+                    .packages
+                    .get(*name)
+                    .is_some_and(|r| r.version != locked.version)
+            })
+            .collect();
+        moved.sort_by(|a, b| a.0.cmp(b.0));
+        if let Some((name, locked)) = moved.first() {
+            let requirement = self
+                .conflicting_requirement(&resolved, new_dep, locked)
+                .unwrap_or_else(|| error::ConflictingRequirement {
+                    from: new_dep.name.clone(),
+                    requirement: format!("{} {}", name, resolved.packages[*name].version),
+                    dependency_chain: Vec::new(),
+                });
+            return Err(locked_conflict(locked, requirement));
+        }
+
+        let mut resolution = base.clone();
+        for (name, dep) in resolved.packages {
+            if !resolution.packages.contains_key(&name) {
+                if let Some(metadata) = resolved.metadata.get(&name) {
+                    resolution.metadata.insert(name.clone(), metadata.clone());
+                }
+                resolution.packages.insert(name, dep);
+            }
+        }
+        Ok(resolution)
+    }
+
+    /// The requirement on `locked`'s package, by `new_dep` or a package it
+    /// pulled into `resolved`, that `locked`'s version doesn't meet, with the
+    /// chain of packages from `new_dep` to the one that imposes it.
+    fn conflicting_requirement(
+        &self,
+        resolved: &Resolution,
+        new_dep: &Dependency,
+        locked: &ResolvedDep,
+    ) -> Option<error::ConflictingRequirement> {
+        let mut queue = VecDeque::from([vec![new_dep.name.clone()]]);
+        let mut seen = HashSet::from([new_dep.name.clone()]);
+        while let Some(chain) = queue.pop_front() {
+            let name = chain.last()?;
+            let package = resolved.packages.get(name)?;
+            let dependencies = self
+                .provider
+                .dependencies(name, &package.version)
+                .unwrap_or_default();
+            if let Some(dep) = dependencies
+                .iter()
+                .find(|d| d.name == locked.name && !d.accepts(&locked.version))
+            {
+                return Some(error::ConflictingRequirement {
+                    from: name.clone(),
+                    requirement: format!("{} {}", dep.name, dep.requirement().unwrap_or_default()),
+                    dependency_chain: if chain.len() > 1 {
+                        chain.clone()
+                    } else {
+                        Vec::new()
+                    },
+                });
+            }
+            for child in &package.dependencies {
+                if seen.insert(child.clone()) {
+                    let mut next = chain.clone();
+                    next.push(child.clone());
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A requirement matching exactly `version`.
+fn exact_req(version: &Version) -> VersionReq {
+    VersionReq::parse(&format!("={}", version)).expect("a version is a valid exact requirement")
+}
+
+/// Conflict between a locked package and a new `requirement` on it.
+fn locked_conflict(
+    locked: &ResolvedDep,
+    requirement: error::ConflictingRequirement,
+) -> ResolveError {
+    ResolveError::VersionConflict {
+        package: locked.name.clone(),
+        conflicts: vec![
+            requirement,
+            error::ConflictingRequirement {
+                from: "the lockfile".to_string(),
+                requirement: format!("{} {}", locked.name, exact_req(&locked.version)),
+                dependency_chain: Vec::new(),
+            },
+        ],
+        derivation: None,
+    }
 }
 
 // Implement PackageProvider for references to providers
@@ -194,6 +443,10 @@ impl<P: PackageProvider> PackageProvider for &P {
     ) -> Result<Vec<Dependency>, ResolveError> {
         (*self).dependencies(package, version)
     }
+
+    fn version_problem(&self, package: &str, version: &Version) -> Option<String> {
+        (*self).version_problem(package, version)
+    }
 }
 
 /// A package version provider for the resolver (legacy trait).
@@ -243,6 +496,32 @@ mod tests {
         assert_eq!(resolved.version, Version::new(1, 5, 4));
     }
 
+    #[test]
+    fn test_compound_requirement_skips_excluded_version() {
+        let mut provider = MemoryProvider::new();
+        for (major, minor, patch) in [(1, 4, 2), (1, 4, 3), (2, 0, 0)] {
+            provider.add_package("swift-log", Version::new(major, minor, patch), vec![]);
+        }
+        let (req, excluded) =
+            gust_types::parse_version_requirement(">=1.0, <2.0, !=1.4.3").unwrap();
+        let log = Dependency::registry("swift-log", req).with_excluded_versions(excluded);
+        provider.add_package("app-core", Version::new(1, 0, 0), vec![log.clone()]);
+
+        // Directly and through another package
+        for dep in [
+            log,
+            Dependency::registry("app-core", VersionReq::parse("^1.0").unwrap()),
+        ] {
+            let mut manifest = Manifest::default();
+            manifest.dependencies.insert(dep.name.clone(), dep);
+            let resolution = Resolver::new(&provider).resolve(&manifest).unwrap();
+            assert_eq!(
+                resolution.packages["swift-log"].version,
+                Version::new(1, 4, 2)
+            );
+        }
+    }
+
     #[test]
     fn test_resolution_with_hints() {
         let mut provider = MemoryProvider::new();
@@ -266,6 +545,125 @@ mod tests {
         assert_eq!(resolved.version, Version::new(1, 4, 0));
     }
 
+    #[test]
+    fn test_options_override_manifest() {
+        let mut provider = MemoryProvider::new();
+        provider.add_package("swift-log", Version::new(1, 5, 4), vec![]);
+        provider.add_package("swift-log", Version::new(1, 4, 0), vec![]);
+
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "swift-log".to_string(),
+            Dependency::registry("swift-log", VersionReq::parse("^1.4").unwrap()),
+        );
+        manifest
+            .overrides
+            .insert("swift-log".to_string(), "=1.5.4".to_string());
+
+        let options = ResolutionOptions {
+            overrides: vec![gust_types::VersionOverride {
+                package: "swift-log".to_string(),
+                version: VersionReq::parse("=1.4.0").unwrap(),
+            }],
+            ..Default::default()
+        };
+        let resolution = Resolver::new(provider)
+            .with_options(options)
+            .resolve(&manifest)
+            .unwrap();
+        assert_eq!(
+            resolution.packages["swift-log"].version,
+            Version::new(1, 4, 0)
+        );
+    }
+
+    #[test]
+    fn test_resolve_with_trace_records_hint_reason() {
+        let mut provider = MemoryProvider::new();
+        provider.add_package("swift-log", Version::new(1, 5, 4), vec![]);
+        provider.add_package("swift-log", Version::new(1, 4, 0), vec![]);
+
+        let mut hints = LockfileHints::new();
+        hints.add_preferred_version("swift-log", Version::new(1, 4, 0));
+
+        let resolver = Resolver::new(provider).with_hints(hints);
+
+        let mut manifest = Manifest::default();
+        manifest.package.name = "my-app".to_string();
+        manifest.dependencies.insert(
+            "swift-log".to_string(),
+            Dependency::registry("swift-log", VersionReq::parse("^1.4").unwrap()),
+        );
+
+        let (resolution, trace) = resolver.resolve_with_trace(&manifest).unwrap();
+        assert_eq!(
+            resolution.packages["swift-log"].version,
+            Version::new(1, 4, 0)
+        );
+
+        let (version, reason) = trace.get_choice("swift-log").unwrap();
+        assert_eq!(*version, Version::new(1, 4, 0));
+        assert_eq!(reason, ChoiceReason::LockedHint);
+        assert_eq!(
+            trace.explain(),
+            "swift-log 1.4.0 (locked), required by my-app\n"
+        );
+    }
+
+    #[test]
+    fn test_resolved_dep_conversions() {
+        let git = ResolvedDep {
+            name: "swift-log".to_string(),
+            version: Version::new(1, 5, 4),
+            source: ResolvedSource::Git {
+                url: "https://github.com/apple/swift-log.git".to_string(),
+                revision: "abc123".to_string(),
+                tag: Some("1.5.4".to_string()),
+            },
+            dependencies: vec!["swift-atomics".to_string()],
+        };
+
+        let pkg = ResolvedPackage::from(git);
+        assert_eq!(pkg.source, DependencySource::Git);
+        assert_eq!(
+            pkg.git.as_deref(),
+            Some("https://github.com/apple/swift-log.git")
+        );
+        assert_eq!(pkg.revision.as_deref(), Some("abc123"));
+        assert_eq!(pkg.dependencies, vec!["swift-atomics"]);
+
+        let back = ResolvedDep::try_from(pkg.clone()).unwrap();
+        assert_eq!(ResolvedPackage::from(back), pkg);
+
+        let registry = ResolvedPackage {
+            name: "swift-nio".to_string(),
+            version: Version::new(2, 58, 0),
+            source: DependencySource::Registry,
+            checksum: Some("blake3:def".to_string()),
+            git: None,
+            revision: None,
+            repository: Some("https://github.com/apple/swift-nio.git".to_string()),
+            dependencies: Vec::new(),
+        };
+        let dep = ResolvedDep::try_from(registry.clone()).unwrap();
+        assert!(matches!(
+            &dep.source,
+            ResolvedSource::Registry { repository: Some(url) } if url.ends_with("swift-nio.git")
+        ));
+        assert_eq!(
+            ResolvedPackage::from(dep.clone()).repository,
+            registry.repository
+        );
+        assert_eq!(dep.version, Version::new(2, 58, 0));
+
+        // A git package without a revision can't be turned back into a source
+        let incomplete = ResolvedPackage {
+            revision: None,
+            ..pkg
+        };
+        assert!(ResolvedDep::try_from(incomplete).is_err());
+    }
+
     #[test]
     fn test_transitive_resolution() {
         let mut provider = MemoryProvider::new();
@@ -296,4 +694,138 @@ mod tests {
         assert!(resolution.packages.contains_key("swift-nio"));
         assert!(resolution.packages.contains_key("swift-log"));
     }
+
+    fn incremental_provider() -> MemoryProvider {
+        let mut provider = MemoryProvider::new();
+        provider.add_package("swift-log", Version::new(1, 4, 0), vec![]);
+        provider.add_package("swift-log", Version::new(1, 5, 4), vec![]);
+        provider.add_package("swift-log", Version::new(2, 0, 0), vec![]);
+        provider.add_package(
+            "swift-metrics",
+            Version::new(2, 4, 0),
+            vec![Dependency::registry(
+                "swift-log",
+                VersionReq::parse("^1.4").unwrap(),
+            )],
+        );
+        provider.add_package(
+            "swift-tracing",
+            Version::new(1, 0, 0),
+            vec![Dependency::registry(
+                "swift-log",
+                VersionReq::parse("^2.0").unwrap(),
+            )],
+        );
+        provider
+    }
+
+    fn base_resolution(provider: &MemoryProvider) -> Resolution {
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "swift-log".to_string(),
+            Dependency::registry("swift-log", VersionReq::parse("~1.4").unwrap()),
+        );
+        Resolver::new(provider).resolve(&manifest).unwrap()
+    }
+
+    #[test]
+    fn test_incremental_add_keeps_base() {
+        let provider = incremental_provider();
+        let base = base_resolution(&provider);
+        assert_eq!(base.packages["swift-log"].version, Version::new(1, 4, 0));
+
+        let resolver = Resolver::new(&provider);
+        let added = resolver
+            .resolve_incremental(
+                &base,
+                &Dependency::registry("swift-metrics", VersionReq::parse("^2.0").unwrap()),
+            )
+            .unwrap();
+
+        assert_eq!(added.packages.len(), 2);
+        assert_eq!(
+            added.packages["swift-metrics"].version,
+            Version::new(2, 4, 0)
+        );
+        // swift-metrics would accept 1.5.4, but the locked 1.4.0 stays
+        assert_eq!(added.packages["swift-log"].version, Version::new(1, 4, 0));
+    }
+
+    #[test]
+    fn test_incremental_add_conflict_names_locked_package() {
+        let provider = incremental_provider();
+        let base = base_resolution(&provider);
+
+        let resolver = Resolver::new(&provider);
+        let err = resolver
+            .resolve_incremental(
+                &base,
+                &Dependency::registry("swift-tracing", VersionReq::parse("^1.0").unwrap()),
+            )
+            .unwrap_err();
+
+        let ResolveError::VersionConflict {
+            package, conflicts, ..
+        } = err
+        else {
+            panic!("expected a version conflict, got {:?}", err);
+        };
+        assert_eq!(package, "swift-log");
+        assert_eq!(
+            conflicts[0].to_string(),
+            "swift-tracing requires swift-log ^2.0"
+        );
+        assert_eq!(
+            conflicts[1].to_string(),
+            "the lockfile requires swift-log =1.4.0"
+        );
+
+        // Re-adding a locked package with an incompatible requirement
+        let err = resolver
+            .resolve_incremental(
+                &base,
+                &Dependency::registry("swift-log", VersionReq::parse("^2.0").unwrap()),
+            )
+            .unwrap_err();
+        let ResolveError::VersionConflict {
+            package, conflicts, ..
+        } = err
+        else {
+            panic!("expected a version conflict, got {:?}", err);
+        };
+        assert_eq!(package, "swift-log");
+        assert_eq!(
+            conflicts[0].to_string(),
+            "Gust.toml requires swift-log ^2.0"
+        );
+    }
+
+    #[test]
+    fn test_incremental_add_conflict_names_transitive_requirer() {
+        let mut provider = incremental_provider();
+        provider.add_package(
+            "swift-otel",
+            Version::new(0, 9, 0),
+            vec![Dependency::registry(
+                "swift-tracing",
+                VersionReq::parse("^1.0").unwrap(),
+            )],
+        );
+        let base = base_resolution(&provider);
+
+        let err = Resolver::new(&provider)
+            .resolve_incremental(
+                &base,
+                &Dependency::registry("swift-otel", VersionReq::parse("^0.9").unwrap()),
+            )
+            .unwrap_err();
+
+        let ResolveError::VersionConflict { conflicts, .. } = err else {
+            panic!("expected a version conflict, got {:?}", err);
+        };
+        assert_eq!(
+            conflicts[0].to_string(),
+            "swift-tracing requires swift-log ^2.0 (via swift-otel -> swift-tracing)"
+        );
+    }
 }
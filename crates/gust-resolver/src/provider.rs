@@ -3,7 +3,9 @@
 use crate::error::ResolveError;
 use crate::hints::{ChoiceReason, LockfileHints, ResolutionTrace};
 use crate::package::GustPackage;
-use gust_types::{Dependency, Manifest, ResolutionStrategy, Version, VersionReq};
+use gust_types::{
+    Dependency, Manifest, ResolutionOptions, ResolutionStrategy, Version, VersionReq,
+};
 use pubgrub::{Dependencies, DependencyProvider, Map, PackageResolutionStatistics, VersionSet};
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -24,6 +26,13 @@ pub trait PackageProvider: Send + Sync {
         package: &str,
         version: &Version,
     ) -> Result<Vec<Dependency>, ResolveError>;
+
+    /// Why this version shouldn't be picked, such as a yanked release.
+    ///
+    /// Such a version is only chosen when a dependency requires exactly it.
+    fn version_problem(&self, _package: &str, _version: &Version) -> Option<String> {
+        None
+    }
 }
 
 /// Wrapper around semver::Version that implements pubgrub traits.
@@ -49,6 +58,8 @@ pub struct GustVersionSet {
     req: Option<VersionReq>,
     /// Explicit versions that are included (for overrides)
     included: Vec<Version>,
+    /// Versions `req` would match that are ruled out by `!=` clauses
+    excluded: Vec<Version>,
     /// Negated version sets
     negated: bool,
 }
@@ -59,6 +70,7 @@ impl GustVersionSet {
         Self {
             req: None,
             included: vec![],
+            excluded: vec![],
             negated: false,
         }
     }
@@ -68,15 +80,29 @@ impl GustVersionSet {
         Self {
             req: Some(req),
             included: vec![],
+            excluded: vec![],
             negated: false,
         }
     }
 
+    /// Create the set of versions `dep` accepts: its requirement minus its
+    /// excluded versions.
+    pub fn from_dependency(dep: &Dependency) -> Self {
+        match &dep.version {
+            Some(req) => Self {
+                excluded: dep.excluded_versions.clone(),
+                ..Self::from_req(req.clone())
+            },
+            None => Self::any(),
+        }
+    }
+
     /// Create a set that matches exactly one version.
     pub fn exact(version: Version) -> Self {
         Self {
             req: Some(VersionReq::parse(&format!("={}", version)).unwrap()),
             included: vec![version],
+            excluded: vec![],
             negated: false,
         }
     }
@@ -86,6 +112,7 @@ impl GustVersionSet {
         Self {
             req: None,
             included: vec![],
+            excluded: vec![],
             negated: true,
         }
     }
@@ -106,6 +133,7 @@ impl VersionSet for GustVersionSet {
         Self {
             req: self.req.clone(),
             included: self.included.clone(),
+            excluded: self.excluded.clone(),
             negated: !self.negated,
         }
     }
@@ -127,10 +155,26 @@ impl VersionSet for GustVersionSet {
             return self.clone(); // other is full
         }
 
-        // Both positive with requirements - intersect
-        if !self.negated && !other.negated {
-            // For simplicity, keep self's requirement (proper impl would combine)
-            self.clone()
+        // Both positive with requirements - a version must satisfy every
+        // comparator of both and be excluded by neither
+        if let (false, false, Some(a), Some(b)) =
+            (self.negated, other.negated, &self.req, &other.req)
+        {
+            let mut excluded = self.excluded.clone();
+            excluded.extend(other.excluded.iter().cloned());
+            Self {
+                req: Some(VersionReq {
+                    comparators: a
+                        .comparators
+                        .iter()
+                        .chain(&b.comparators)
+                        .cloned()
+                        .collect(),
+                }),
+                included: vec![],
+                excluded,
+                negated: false,
+            }
         } else if self.negated {
             other.clone()
         } else {
@@ -140,7 +184,7 @@ impl VersionSet for GustVersionSet {
 
     fn contains(&self, v: &Self::V) -> bool {
         let matches = match &self.req {
-            Some(req) => req.matches(&v.0),
+            Some(req) => req.matches(&v.0) && !self.excluded.contains(&v.0),
             None => true,
         };
         if self.negated {
@@ -208,9 +252,13 @@ impl Display for GustVersionSet {
             write!(f, "not ")?;
         }
         match &self.req {
-            Some(req) => write!(f, "{}", req),
-            None => write!(f, "*"),
+            Some(req) => write!(f, "{}", req)?,
+            None => write!(f, "*")?,
         }
+        for version in &self.excluded {
+            write!(f, ", !={}", version)?;
+        }
+        Ok(())
     }
 }
 
@@ -243,6 +291,9 @@ pub struct GustDependencyProvider<'a, P: PackageProvider> {
     /// Cache of dependencies (package@version -> deps)
     /// Prevents re-fetching during PubGrub backtracking
     dependency_cache: RefCell<HashMap<(String, Version), Vec<Dependency>>>,
+
+    /// Packages some dependency requires an exact version of
+    pinned: RefCell<HashMap<String, Version>>,
 }
 
 impl<'a, P: PackageProvider> GustDependencyProvider<'a, P> {
@@ -276,6 +327,7 @@ impl<'a, P: PackageProvider> GustDependencyProvider<'a, P> {
             trace: RefCell::new(ResolutionTrace::new()),
             version_cache: RefCell::new(HashMap::new()),
             dependency_cache: RefCell::new(HashMap::new()),
+            pinned: RefCell::new(HashMap::new()),
         }
     }
 
@@ -291,6 +343,19 @@ impl<'a, P: PackageProvider> GustDependencyProvider<'a, P> {
         self
     }
 
+    /// Apply merged overrides and constraints, replacing the manifest's for
+    /// the same package.
+    pub fn with_options(mut self, options: &ResolutionOptions) -> Self {
+        for o in &options.overrides {
+            self.overrides.insert(o.package.clone(), o.version.clone());
+        }
+        for c in &options.constraints {
+            self.constraints
+                .insert(c.package.clone(), c.version.clone());
+        }
+        self
+    }
+
     /// Get the resolution trace.
     pub fn trace(&self) -> std::cell::Ref<'_, ResolutionTrace> {
         self.trace.borrow()
@@ -312,6 +377,42 @@ impl<'a, P: PackageProvider> GustDependencyProvider<'a, P> {
         Ok(versions)
     }
 
+    /// Whether `version` may be chosen: it has no problem, or a dependency
+    /// pins exactly it, in which case the problem becomes a warning.
+    fn usable(&self, package: &str, version: &Version) -> bool {
+        let Some(problem) = self.provider.version_problem(package, version) else {
+            return true;
+        };
+        if self.pinned.borrow().get(package) != Some(version) {
+            return false;
+        }
+        self.trace.borrow_mut().record_warning(
+            package,
+            format!(
+                "{} {} is pinned but has a problem: {}",
+                package, version, problem
+            ),
+        );
+        true
+    }
+
+    /// The versions `dep` allows, narrowed by any constraint on its
+    /// package, whether the root or another package depends on it.
+    fn constrained_range(&self, dep: &Dependency) -> GustVersionSet {
+        let range = GustVersionSet::from_dependency(dep);
+        match self.constraints.get(&dep.name) {
+            Some(constraint) => range.intersection(&GustVersionSet::from_req(constraint.clone())),
+            None => range,
+        }
+    }
+
+    /// Remember `dep` if it requires an exact version.
+    fn record_pin(&self, dep: &Dependency) {
+        if let Some(version) = dep.version.as_ref().and_then(exact_version) {
+            self.pinned.borrow_mut().insert(dep.name.clone(), version);
+        }
+    }
+
     /// Get dependencies for a package version (cached).
     /// This prevents re-fetching during PubGrub backtracking.
     fn get_dependencies_cached(
@@ -390,7 +491,7 @@ impl<'a, P: PackageProvider> DependencyProvider for GustDependencyProvider<'a, P
                     let versions = self.get_versions(name)?;
                     let matching: Vec<_> = versions
                         .into_iter()
-                        .filter(|v| override_req.matches(v))
+                        .filter(|v| override_req.matches(v) && self.usable(name, v))
                         .collect();
 
                     if let Some(version) = matching.into_iter().max() {
@@ -407,7 +508,7 @@ impl<'a, P: PackageProvider> DependencyProvider for GustDependencyProvider<'a, P
                 let versions = self.get_versions(name)?;
                 let matching: Vec<_> = versions
                     .into_iter()
-                    .filter(|v| range.contains(&GustVersion(v.clone())))
+                    .filter(|v| range.contains(&GustVersion(v.clone())) && self.usable(name, v))
                     .collect();
 
                 // Check lockfile hints first
@@ -460,17 +561,17 @@ impl<'a, P: PackageProvider> DependencyProvider for GustDependencyProvider<'a, P
                 );
 
                 for (name, dep) in &self.manifest.dependencies {
+                    if !self.manifest.package.name.is_empty() {
+                        self.trace
+                            .borrow_mut()
+                            .record_requirement(name, &self.manifest.package.name);
+                    }
+
+                    self.record_pin(dep);
                     let pkg = GustPackage::named(name);
-                    let range = if let Some(version) = &dep.version {
-                        #[cfg(test)]
-                        eprintln!("  Adding dep {} with version {:?}", name, version);
-                        GustVersionSet::from_req(version.clone())
-                    } else {
-                        #[cfg(test)]
-                        eprintln!("  Adding dep {} with any version", name);
-                        GustVersionSet::any()
-                    };
-                    deps.insert(pkg, range);
+                    #[cfg(test)]
+                    eprintln!("  Adding dep {} with version {:?}", name, dep.requirement());
+                    deps.insert(pkg, self.constrained_range(dep));
                 }
 
                 #[cfg(test)]
@@ -491,20 +592,9 @@ impl<'a, P: PackageProvider> DependencyProvider for GustDependencyProvider<'a, P
                 let mut pubgrub_deps = Map::default();
 
                 for dep in deps {
+                    self.record_pin(&dep);
                     let pkg = GustPackage::named(&dep.name);
-                    let mut range = if let Some(version_req) = &dep.version {
-                        GustVersionSet::from_req(version_req.clone())
-                    } else {
-                        GustVersionSet::any()
-                    };
-
-                    // Apply additional constraints
-                    if let Some(constraint) = self.constraints.get(&dep.name) {
-                        let constraint_set = GustVersionSet::from_req(constraint.clone());
-                        range = range.intersection(&constraint_set);
-                    }
-
-                    pubgrub_deps.insert(pkg, range);
+                    pubgrub_deps.insert(pkg, self.constrained_range(&dep));
                 }
 
                 Ok(Dependencies::Available(pubgrub_deps))
@@ -513,6 +603,23 @@ impl<'a, P: PackageProvider> DependencyProvider for GustDependencyProvider<'a, P
     }
 }
 
+/// The version `req` requires exactly, if it's a single `=x.y.z`.
+fn exact_version(req: &VersionReq) -> Option<Version> {
+    let [comparator] = req.comparators.as_slice() else {
+        return None;
+    };
+    if comparator.op != semver::Op::Exact {
+        return None;
+    }
+    Some(Version {
+        major: comparator.major,
+        minor: comparator.minor?,
+        patch: comparator.patch?,
+        pre: comparator.pre.clone(),
+        build: Default::default(),
+    })
+}
+
 /// A simple in-memory provider for testing.
 #[derive(Default)]
 pub struct MemoryProvider {
@@ -584,4 +691,25 @@ mod tests {
         assert!(set.contains(&GustVersion(Version::new(1, 4, 0))));
         assert!(!set.contains(&GustVersion(Version::new(2, 0, 0))));
     }
+
+    #[test]
+    fn test_version_set_excludes_versions() {
+        let dep = Dependency::registry("swift-log", VersionReq::parse("^1.4").unwrap())
+            .with_excluded_versions(vec![Version::new(1, 4, 3)]);
+        let set = GustVersionSet::from_dependency(&dep);
+        assert!(set.contains(&GustVersion(Version::new(1, 4, 2))));
+        assert!(!set.contains(&GustVersion(Version::new(1, 4, 3))));
+        assert!(set
+            .complement()
+            .contains(&GustVersion(Version::new(1, 4, 3))));
+        assert_eq!(set.to_string(), "^1.4, !=1.4.3");
+
+        // Intersecting keeps the exclusion and both requirements
+        let narrowed = set.intersection(&GustVersionSet::from_req(
+            VersionReq::parse("<1.5").unwrap(),
+        ));
+        assert!(narrowed.contains(&GustVersion(Version::new(1, 4, 2))));
+        assert!(!narrowed.contains(&GustVersion(Version::new(1, 4, 3))));
+        assert!(!narrowed.contains(&GustVersion(Version::new(1, 5, 0))));
+    }
 }
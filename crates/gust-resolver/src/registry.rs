@@ -0,0 +1,150 @@
+//! [`PackageProvider`] backed by a Swift package registry.
+//!
+//! Resolution is synchronous, so each package's release list is fetched up
+//! front with [`RegistryProvider::fetch`]. A release list doesn't say what a
+//! release depends on, so registry packages resolve without dependencies of
+//! their own. Releases the registry reports a problem for, such as yanked
+//! ones, are only chosen when a dependency requires exactly that version.
+
+use crate::error::ResolveError;
+use crate::provider::PackageProvider;
+use gust_registry::{PackageVersions, RegistryClient, RegistryError, ReleaseInfo};
+use gust_types::{Dependency, Version};
+use std::collections::HashMap;
+
+/// A [`PackageProvider`] over registry release lists, keyed by `scope.name`.
+#[derive(Debug, Clone, Default)]
+pub struct RegistryProvider {
+    releases: HashMap<String, Vec<(Version, ReleaseInfo)>>,
+}
+
+impl RegistryProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch the release lists of `packages` (`scope.name`) from `client`.
+    pub async fn fetch(
+        client: &RegistryClient,
+        packages: impl IntoIterator<Item = &str>,
+    ) -> Result<Self, RegistryError> {
+        let mut provider = Self::new();
+        for package in packages {
+            let id = gust_registry::registry_identity(package)?;
+            let releases = client.list_versions(&id.scope, &id.name).await?;
+            provider.add_releases(package, releases);
+        }
+        Ok(provider)
+    }
+
+    /// Add a package's release list; releases whose version doesn't parse
+    /// are left out.
+    pub fn add_releases(&mut self, package: impl Into<String>, releases: PackageVersions) {
+        let parsed = releases
+            .releases
+            .into_iter()
+            .filter_map(|(v, info)| Version::parse(&v).ok().map(|v| (v, info)))
+            .collect();
+        self.releases.insert(package.into(), parsed);
+    }
+
+    fn release(&self, package: &str, version: &Version) -> Result<&ReleaseInfo, ResolveError> {
+        self.releases
+            .get(package)
+            .and_then(|releases| releases.iter().find(|(v, _)| v == version))
+            .map(|(_, info)| info)
+            .ok_or_else(|| ResolveError::PackageNotFound {
+                name: package.to_string(),
+                suggestions: vec![],
+            })
+    }
+}
+
+impl PackageProvider for RegistryProvider {
+    fn available_versions(&self, package: &str) -> Result<Vec<Version>, ResolveError> {
+        self.releases
+            .get(package)
+            .map(|releases| releases.iter().map(|(v, _)| v.clone()).collect())
+            .ok_or_else(|| ResolveError::PackageNotFound {
+                name: package.to_string(),
+                suggestions: vec![],
+            })
+    }
+
+    fn dependencies(
+        &self,
+        package: &str,
+        version: &Version,
+    ) -> Result<Vec<Dependency>, ResolveError> {
+        self.release(package, version).map(|_| Vec::new())
+    }
+
+    fn version_problem(&self, package: &str, version: &Version) -> Option<String> {
+        let problem = self.release(package, version).ok()?.problem.as_ref()?;
+        Some(problem.title.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Resolver;
+    use gust_registry::ReleaseProblem;
+    use gust_types::{Manifest, VersionReq};
+
+    fn provider() -> RegistryProvider {
+        let release = |problem: Option<&str>| ReleaseInfo {
+            url: None,
+            problem: problem.map(|title| ReleaseProblem {
+                status: "410".to_string(),
+                title: title.to_string(),
+                detail: None,
+            }),
+        };
+        let mut provider = RegistryProvider::new();
+        provider.add_releases(
+            "apple.swift-log",
+            PackageVersions {
+                releases: [
+                    ("1.4.0".to_string(), release(None)),
+                    ("1.5.0".to_string(), release(Some("Release yanked"))),
+                ]
+                .into_iter()
+                .collect(),
+            },
+        );
+        provider
+    }
+
+    fn manifest(requirement: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "apple.swift-log".to_string(),
+            Dependency::registry("apple.swift-log", VersionReq::parse(requirement).unwrap()),
+        );
+        manifest
+    }
+
+    #[test]
+    fn test_yanked_release_is_skipped_unless_pinned() {
+        let provider = provider();
+        let resolver = Resolver::new(&provider);
+
+        let (resolution, trace) = resolver.resolve_with_trace(&manifest("^1.4")).unwrap();
+        assert_eq!(
+            resolution.packages["apple.swift-log"].version,
+            Version::new(1, 4, 0)
+        );
+        assert!(trace.warnings().is_empty());
+
+        let (resolution, trace) = resolver.resolve_with_trace(&manifest("=1.5.0")).unwrap();
+        assert_eq!(
+            resolution.packages["apple.swift-log"].version,
+            Version::new(1, 5, 0)
+        );
+        assert_eq!(
+            trace.warnings(),
+            ["apple.swift-log 1.5.0 is pinned but has a problem: Release yanked"]
+        );
+    }
+}
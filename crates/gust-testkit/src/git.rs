@@ -0,0 +1,42 @@
+//! Local git repositories standing in for remote dependencies.
+//!
+//! Commits are made as a fixed identity, so they work on machines with no
+//! git config.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Run `git args` in `dir` and return its trimmed stdout, panicking if it
+/// fails.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=gust", "-c", "user.email=gust@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Make `dir` a repository with everything in it committed, creating it if
+/// needed. Returns the commit.
+pub fn git_repo(dir: &Path) -> String {
+    std::fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "-q"]);
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// [`git_repo`], with the commit tagged `tag`.
+pub fn tagged_repo(dir: &Path, tag: &str) -> String {
+    let commit = git_repo(dir);
+    git(dir, &["tag", tag]);
+    commit
+}
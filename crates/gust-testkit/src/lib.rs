@@ -0,0 +1,32 @@
+//! Test fixtures shared by Gust's crates.
+//!
+//! Only ever a dev-dependency.
+
+mod git;
+mod http;
+
+pub use git::{git, git_repo, tagged_repo};
+pub use http::{MockServer, Request, Response};
+
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+
+/// Write a zipped `Sdk.xcframework` to `path`, as a remote binary target
+/// would be published, and return its SHA-256 in lowercase hex.
+pub fn write_xcframework_zip(path: &Path) -> String {
+    let file = std::fs::File::create(path).unwrap();
+    let mut zip = zip::ZipWriter::new(file);
+    zip.start_file(
+        "Sdk.xcframework/Info.plist",
+        zip::write::SimpleFileOptions::default(),
+    )
+    .unwrap();
+    zip.write_all(b"<plist/>").unwrap();
+    zip.finish().unwrap();
+
+    Sha256::digest(std::fs::read(path).unwrap())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -5,7 +5,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub use semver::{Version, VersionReq};
 
@@ -30,6 +30,10 @@ pub struct Package {
     /// Repository URL
     #[serde(default)]
     pub repository: Option<String>,
+    /// Language of the package's resources when no localization matches,
+    /// e.g. `en`; required once any resource is localized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_localization: Option<String>,
 }
 
 impl Default for Package {
@@ -42,6 +46,7 @@ impl Default for Package {
             license: None,
             authors: Vec::new(),
             repository: None,
+            default_localization: None,
         }
     }
 }
@@ -53,6 +58,9 @@ pub struct Dependency {
     pub name: String,
     /// Version requirement
     pub version: Option<VersionReq>,
+    /// Versions the requirement rules out with `!=` clauses
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_versions: Vec<Version>,
     /// Git repository URL
     pub git: Option<String>,
     /// Git branch
@@ -69,6 +77,40 @@ pub struct Dependency {
     /// Is this an optional dependency?
     #[serde(default)]
     pub optional: bool,
+    /// Dependency group (e.g. "bench") this belongs to; grouped
+    /// dependencies are only installed when their group is asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Whether to pull Git LFS files after cloning; `None` detects it from
+    /// the repository's `.gitattributes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lfs: Option<bool>,
+    /// Extra compiler flags for this dependency's own targets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<DependencyBuildSettings>,
+    /// Products of the package that targets import; empty means the
+    /// package's primary product
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub products: Vec<String>,
+}
+
+/// Compiler flags for one dependency's targets, from
+/// `[dependencies.<name>.build]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyBuildSettings {
+    /// Extra Swift compiler flags
+    #[serde(default)]
+    pub swift_flags: Vec<String>,
+    /// Extra C compiler flags
+    #[serde(default)]
+    pub c_flags: Vec<String>,
+}
+
+impl DependencyBuildSettings {
+    /// Whether no flags are set.
+    pub fn is_empty(&self) -> bool {
+        self.swift_flags.is_empty() && self.c_flags.is_empty()
+    }
 }
 
 impl Dependency {
@@ -84,6 +126,11 @@ impl Dependency {
             path: None,
             features: Vec::new(),
             optional: false,
+            group: None,
+            lfs: None,
+            excluded_versions: Vec::new(),
+            build: None,
+            products: Vec::new(),
         }
     }
 
@@ -99,6 +146,11 @@ impl Dependency {
             path: None,
             features: Vec::new(),
             optional: false,
+            group: None,
+            lfs: None,
+            excluded_versions: Vec::new(),
+            build: None,
+            products: Vec::new(),
         }
     }
 
@@ -114,6 +166,11 @@ impl Dependency {
             path: Some(path.into()),
             features: Vec::new(),
             optional: false,
+            group: None,
+            lfs: None,
+            excluded_versions: Vec::new(),
+            build: None,
+            products: Vec::new(),
         }
     }
 
@@ -129,6 +186,34 @@ impl Dependency {
         self
     }
 
+    /// Set the versions the requirement rules out.
+    pub fn with_excluded_versions(mut self, versions: Vec<Version>) -> Self {
+        self.excluded_versions = versions;
+        self
+    }
+
+    /// Whether `version` satisfies the version requirement and isn't
+    /// excluded. Without a requirement every version does.
+    pub fn accepts(&self, version: &Version) -> bool {
+        self.version.as_ref().is_none_or(|req| req.matches(version))
+            && !self.excluded_versions.contains(version)
+    }
+
+    /// The version requirement as written, exclusions included, e.g.
+    /// `>=1.0.0, <2.0.0, !=1.4.3`.
+    pub fn requirement(&self) -> Option<String> {
+        let req = self.version.as_ref()?;
+        let mut clauses = vec![req.to_string()];
+        clauses.extend(self.excluded_versions.iter().map(|v| format!("!={}", v)));
+        Some(clauses.join(", "))
+    }
+
+    /// Set the features to enable on this dependency.
+    pub fn with_features(mut self, features: Vec<String>) -> Self {
+        self.features = features;
+        self
+    }
+
     /// Returns the source kind of this dependency.
     pub fn source_kind(&self) -> DependencySource {
         if self.path.is_some() {
@@ -141,6 +226,30 @@ impl Dependency {
     }
 }
 
+/// Parse a compound version requirement such as `>=1.0, <2.0, !=1.4.3`.
+///
+/// Clauses are comma-separated and must all hold. `!=` clauses, which
+/// semver requirements can't express, name exact versions to exclude and
+/// are returned separately. An error names the clause that failed to parse.
+pub fn parse_version_requirement(requirement: &str) -> Result<(VersionReq, Vec<Version>), String> {
+    let mut comparators = Vec::new();
+    let mut excluded = Vec::new();
+    for clause in requirement.split(',').map(str::trim) {
+        let invalid =
+            |e: &dyn std::fmt::Display| format!("clause '{}' in '{}': {}", clause, requirement, e);
+        if clause.is_empty() {
+            return Err(invalid(&"empty clause"));
+        }
+        if let Some(version) = clause.strip_prefix("!=") {
+            excluded.push(Version::parse(version.trim()).map_err(|e| invalid(&e))?);
+        } else {
+            let req = VersionReq::parse(clause).map_err(|e| invalid(&e))?;
+            comparators.extend(req.comparators);
+        }
+    }
+    Ok((VersionReq { comparators }, excluded))
+}
+
 /// The source type of a dependency.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -153,6 +262,16 @@ pub enum DependencySource {
     Path,
 }
 
+impl std::fmt::Display for DependencySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencySource::Registry => write!(f, "registry"),
+            DependencySource::Git => write!(f, "git"),
+            DependencySource::Path => write!(f, "path"),
+        }
+    }
+}
+
 /// A build target (executable, library, test, etc).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Target {
@@ -169,7 +288,117 @@ pub struct Target {
     pub dependencies: Vec<String>,
     /// Resources to include
     #[serde(default)]
-    pub resources: Vec<PathBuf>,
+    pub resources: Vec<Resource>,
+    /// Files and directories under the target's path to leave out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<PathBuf>,
+    /// Files and directories to compile, instead of everything under the
+    /// target's path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<PathBuf>>,
+    /// Download URL of a remote binary target's `.xcframework.zip`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// SHA-256 checksum of the zip at `url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// What a plugin target does, for plugin targets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_capability: Option<PluginCapability>,
+    /// pkg-config name of the library a system library target wraps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkg_config: Option<String>,
+    /// Packages that install the library a system library target wraps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<SystemPackageProvider>,
+}
+
+/// A file or directory a target bundles as a resource.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resource {
+    /// Path relative to the target's directory
+    pub path: PathBuf,
+    /// How SwiftPM bundles it
+    #[serde(default)]
+    pub rule: ResourceRule,
+    /// Localization of a processed resource outside an `.lproj` directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localization: Option<ResourceLocalization>,
+}
+
+impl Resource {
+    /// A resource processed the default way.
+    pub fn process(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            rule: ResourceRule::Process,
+            localization: None,
+        }
+    }
+
+    /// Whether the resource is localized: it has a `localization`, or lies
+    /// in (or is) an `.lproj` directory.
+    pub fn is_localized(&self) -> bool {
+        self.localization.is_some()
+            || self
+                .path
+                .components()
+                .any(|c| Path::new(c.as_os_str()).extension() == Some("lproj".as_ref()))
+    }
+}
+
+/// How SwiftPM bundles a resource.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResourceRule {
+    /// Optimized for the platform, e.g. asset catalogs compiled
+    #[default]
+    Process,
+    /// Copied as is, keeping directory structure
+    Copy,
+    /// Embedded into the binary as a byte array
+    EmbedInCode,
+}
+
+/// Which localization a processed resource belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceLocalization {
+    /// The package's `default_localization`
+    Default,
+    /// The base internationalization, for Interface Builder files
+    Base,
+}
+
+/// A system package manager package that provides a system library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemPackageProvider {
+    /// Homebrew packages (macOS)
+    Brew(Vec<String>),
+    /// apt packages (Debian, Ubuntu)
+    Apt(Vec<String>),
+}
+
+impl SystemPackageProvider {
+    /// Whether this provider's package manager is used on `os` (as in
+    /// `std::env::consts::OS`).
+    pub fn applies_to(&self, os: &str) -> bool {
+        match self {
+            SystemPackageProvider::Brew(_) => os == "macos",
+            SystemPackageProvider::Apt(_) => os == "linux",
+        }
+    }
+
+    /// Command that installs the packages.
+    pub fn install_command(&self) -> String {
+        match self {
+            SystemPackageProvider::Brew(packages) => format!("brew install {}", packages.join(" ")),
+            SystemPackageProvider::Apt(packages) => {
+                format!("apt-get install {}", packages.join(" "))
+            }
+        }
+    }
 }
 
 impl Target {
@@ -181,6 +410,13 @@ impl Target {
             path: None,
             dependencies: Vec::new(),
             resources: Vec::new(),
+            exclude: Vec::new(),
+            sources: None,
+            url: None,
+            checksum: None,
+            plugin_capability: None,
+            pkg_config: None,
+            providers: Vec::new(),
         }
     }
 
@@ -192,6 +428,31 @@ impl Target {
             path: None,
             dependencies: Vec::new(),
             resources: Vec::new(),
+            exclude: Vec::new(),
+            sources: None,
+            url: None,
+            checksum: None,
+            plugin_capability: None,
+            pkg_config: None,
+            providers: Vec::new(),
+        }
+    }
+
+    /// Create a new binary target.
+    pub fn binary(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            target_type: TargetType::Binary,
+            path: None,
+            dependencies: Vec::new(),
+            resources: Vec::new(),
+            exclude: Vec::new(),
+            sources: None,
+            url: None,
+            checksum: None,
+            plugin_capability: None,
+            pkg_config: None,
+            providers: Vec::new(),
         }
     }
 
@@ -203,6 +464,13 @@ impl Target {
             path: None,
             dependencies: Vec::new(),
             resources: Vec::new(),
+            exclude: Vec::new(),
+            sources: None,
+            url: None,
+            checksum: None,
+            plugin_capability: None,
+            pkg_config: None,
+            providers: Vec::new(),
         }
     }
 }
@@ -223,6 +491,8 @@ pub enum TargetType {
     SystemLibrary,
     /// A binary target (pre-built)
     Binary,
+    /// A compiler macro (SE-0382), built for the host
+    Macro,
 }
 
 /// A complete package manifest with all dependencies and targets.
@@ -257,6 +527,13 @@ pub struct Manifest {
     /// Workspace configuration (only present at workspace root)
     #[serde(default)]
     pub workspace: Option<WorkspaceConfig>,
+    /// Tool behaviour settings from the `[gust]` table
+    #[serde(default)]
+    pub gust: Option<GustSettings>,
+    /// Feature name to the optional dependencies and features it enables;
+    /// `default` lists the features enabled unless opted out
+    #[serde(default)]
+    pub features: HashMap<String, Vec<String>>,
 }
 
 /// Binary cache configuration.
@@ -276,6 +553,15 @@ fn default_true() -> bool {
     true
 }
 
+/// Tool behaviour settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GustSettings {
+    /// Run `gust install` after `gust add` edits the manifest
+    #[serde(default)]
+    pub auto_install: bool,
+}
+
 /// Build settings.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BuildSettings {
@@ -294,10 +580,19 @@ pub struct BuildSettings {
     /// Extra linker flags
     #[serde(default)]
     pub link_flags: Vec<String>,
+    /// Environment variables set for `swift build`
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// SDK to build against, relative to the package root unless absolute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk: Option<PathBuf>,
+    /// Fail the build on compiler warnings
+    #[serde(default, rename = "warnings-as-errors")]
+    pub warnings_as_errors: bool,
 }
 
 /// A resolved package in the dependency graph.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ResolvedPackage {
     /// Package name
     pub name: String,
@@ -311,6 +606,8 @@ pub struct ResolvedPackage {
     pub git: Option<String>,
     /// Git revision (for git deps)
     pub revision: Option<String>,
+    /// Upstream repository reported by the registry (for registry deps)
+    pub repository: Option<String>,
     /// Resolved dependencies
     pub dependencies: Vec<String>,
 }
@@ -545,8 +842,8 @@ pub enum PluginPermission {
 pub enum NetworkScope {
     /// No network access
     None,
-    /// Local connections only (with port)
-    Local(u16),
+    /// Local connections only, to these ports (any port if empty)
+    Local(Vec<u16>),
     /// All outgoing connections
     All,
     /// Docker connections
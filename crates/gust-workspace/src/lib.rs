@@ -14,8 +14,8 @@ pub use discovery::{find_workspace_root, WorkspaceDiscovery};
 pub use loader::{LoadedWorkspace, WorkspaceLoader};
 pub use resolver::WorkspaceResolver;
 
-use gust_types::{Dependency, Manifest, WorkspaceConfig};
-use std::collections::HashMap;
+use gust_types::{Dependency, Manifest, Version, VersionReq, WorkspaceConfig};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
@@ -44,6 +44,32 @@ pub enum WorkspaceError {
 
     #[error("Resolver error: {0}")]
     ResolverError(#[from] Box<gust_resolver::ResolveError>),
+
+    #[error("Workspace members disagree on dependencies:\n{}", conflicts.iter().map(|c| format!("  {}", c)).collect::<Vec<_>>().join("\n"))]
+    DependencyConflicts { conflicts: Vec<DependencyConflict> },
+}
+
+/// Two workspace members declaring the same external dependency in ways a
+/// single resolution can't satisfy: different sources, different pins, or
+/// version requirements no version meets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyConflict {
+    /// Dependency name
+    pub name: String,
+    /// The member declaring it first and its spec
+    pub first: (String, String),
+    /// The member it conflicts with and its spec
+    pub second: (String, String),
+}
+
+impl std::fmt::Display for DependencyConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} wants {}, {} wants {}",
+            self.name, self.first.0, self.first.1, self.second.0, self.second.1
+        )
+    }
 }
 
 /// Represents a loaded workspace.
@@ -111,6 +137,62 @@ impl Workspace {
             .collect()
     }
 
+    /// Members affected by changes to `changed` files, given relative to
+    /// the workspace root.
+    ///
+    /// A file belongs to the innermost member whose directory contains it.
+    /// A change to the root `Gust.toml` or `Gust.lock` can move any shared
+    /// dependency, so it affects every member. Members depending on an
+    /// affected member, directly or through others, are affected too.
+    /// Members are returned in declaration order.
+    pub fn affected_members(&self, changed: &[PathBuf]) -> Vec<&WorkspaceMember> {
+        let shared_changed = changed
+            .iter()
+            .any(|file| file == Path::new("Gust.toml") || file == Path::new("Gust.lock"));
+        if shared_changed {
+            return self.members.iter().collect();
+        }
+
+        let mut affected: HashSet<&str> = changed
+            .iter()
+            .filter_map(|file| {
+                self.members
+                    .iter()
+                    .filter_map(|m| {
+                        let dir = m.path.strip_prefix(&self.root).unwrap_or(&m.path);
+                        file.starts_with(dir)
+                            .then_some((dir.components().count(), m))
+                    })
+                    .max_by_key(|(depth, _)| *depth)
+                    .map(|(_, m)| m.name.as_str())
+            })
+            .collect();
+
+        // Expand to dependents until nothing changes
+        loop {
+            let dependents: Vec<&str> = self
+                .members
+                .iter()
+                .filter(|m| !affected.contains(m.name.as_str()))
+                .filter(|m| {
+                    m.workspace_deps
+                        .iter()
+                        .any(|d| affected.contains(d.as_str()))
+                })
+                .map(|m| m.name.as_str())
+                .collect();
+            if dependents.is_empty() {
+                break;
+            }
+            affected.extend(dependents);
+        }
+
+        self.members
+            .iter()
+            .filter(|m| affected.contains(m.name.as_str()))
+            .collect()
+    }
+
     /// Get the topological order for building members.
     ///
     /// Members with no dependencies on other workspace members come first.
@@ -159,19 +241,166 @@ impl Workspace {
     }
 
     /// Collect all external dependencies across all workspace members.
-    pub fn all_external_dependencies(&self) -> HashMap<String, Dependency> {
-        let mut all_deps = HashMap::new();
+    ///
+    /// A dependency declared by several members is merged into one entry
+    /// whose version requirement is all of theirs together. Declarations
+    /// that can't be merged are returned as
+    /// [`WorkspaceError::DependencyConflicts`], each naming both members.
+    pub fn all_external_dependencies(&self) -> Result<HashMap<String, Dependency>, WorkspaceError> {
+        // Dependency and the member it was first seen in
+        let mut all_deps: HashMap<String, (Dependency, &str)> = HashMap::new();
+        let mut conflicts = Vec::new();
 
         for member in &self.members {
-            for (name, dep) in &member.manifest.dependencies {
+            let mut deps: Vec<_> = member.manifest.dependencies.iter().collect();
+            deps.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, dep) in deps {
                 // Skip workspace members
-                if !self.members.iter().any(|m| m.name == *name) {
-                    all_deps.insert(name.clone(), dep.clone());
+                if self.members.iter().any(|m| m.name == *name) {
+                    continue;
+                }
+                match all_deps.get_mut(name) {
+                    None => {
+                        all_deps.insert(name.clone(), (dep.clone(), &member.name));
+                    }
+                    Some((merged, first)) => {
+                        if let Err(()) = merge_dependency(merged, dep) {
+                            conflicts.push(DependencyConflict {
+                                name: name.clone(),
+                                first: (first.to_string(), describe_dependency(merged)),
+                                second: (member.name.clone(), describe_dependency(dep)),
+                            });
+                        }
+                    }
                 }
             }
         }
 
-        all_deps
+        if !conflicts.is_empty() {
+            return Err(WorkspaceError::DependencyConflicts { conflicts });
+        }
+        Ok(all_deps
+            .into_iter()
+            .map(|(name, (dep, _))| (name, dep))
+            .collect())
+    }
+}
+
+/// Fold `other` into `merged`, or `Err` if they can't both hold. `merged`
+/// is left as it was on error.
+fn merge_dependency(merged: &mut Dependency, other: &Dependency) -> Result<(), ()> {
+    if merged.source_kind() != other.source_kind()
+        || merged.git.as_deref().map(normalize_git_url)
+            != other.git.as_deref().map(normalize_git_url)
+        || merged.path != other.path
+    {
+        return Err(());
+    }
+    let branch = merge_pin(&merged.branch, &other.branch)?;
+    let tag = merge_pin(&merged.tag, &other.tag)?;
+    let revision = merge_pin(&merged.revision, &other.revision)?;
+    let version = match (&merged.version, &other.version) {
+        (Some(a), Some(b)) => {
+            let mut both = a.clone();
+            both.comparators.extend(
+                b.comparators
+                    .iter()
+                    .filter(|c| !a.comparators.contains(c))
+                    .cloned(),
+            );
+            if !satisfiable(&both) {
+                return Err(());
+            }
+            Some(both)
+        }
+        (a, b) => a.clone().or_else(|| b.clone()),
+    };
+
+    merged.version = version;
+    merged.branch = branch;
+    merged.tag = tag;
+    merged.revision = revision;
+    for v in &other.excluded_versions {
+        if !merged.excluded_versions.contains(v) {
+            merged.excluded_versions.push(v.clone());
+        }
+    }
+    for feature in &other.features {
+        if !merged.features.contains(feature) {
+            merged.features.push(feature.clone());
+        }
+    }
+    for product in &other.products {
+        if !merged.products.contains(product) {
+            merged.products.push(product.clone());
+        }
+    }
+    merged.optional &= other.optional;
+    Ok(())
+}
+
+/// The pin both sides agree on; one side leaving it unset agrees with any.
+fn merge_pin(a: &Option<String>, b: &Option<String>) -> Result<Option<String>, ()> {
+    match (a, b) {
+        (Some(a), Some(b)) if a != b => Err(()),
+        _ => Ok(a.clone().or_else(|| b.clone())),
+    }
+}
+
+/// A git URL without the parts that don't change the repository.
+fn normalize_git_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url)
+}
+
+/// Whether some release version meets every comparator of `req`.
+///
+/// The versions meeting a set of comparators form a range, so if any does,
+/// the lowest one does: the highest lower bound, or just above it when the
+/// bound is exclusive. Those are the only candidates tried.
+fn satisfiable(req: &VersionReq) -> bool {
+    use semver::Op;
+
+    let mut candidates = vec![Version::new(0, 0, 0)];
+    for c in &req.comparators {
+        let (minor, patch) = (c.minor.unwrap_or(0), c.patch.unwrap_or(0));
+        candidates.push(Version::new(c.major, minor, patch));
+        if c.op == Op::Greater {
+            candidates.push(match (c.minor, c.patch) {
+                (Some(_), Some(_)) => Version::new(c.major, minor, patch + 1),
+                (Some(_), None) => Version::new(c.major, minor + 1, 0),
+                _ => Version::new(c.major + 1, 0, 0),
+            });
+        }
+    }
+    candidates.iter().any(|v| req.matches(v))
+}
+
+/// A dependency's spec as it would be written, for conflict messages.
+fn describe_dependency(dep: &Dependency) -> String {
+    let mut parts = Vec::new();
+    if let Some(git) = &dep.git {
+        parts.push(format!("git {}", git));
+    }
+    if let Some(path) = &dep.path {
+        parts.push(format!("path {}", path.display()));
+    }
+    for (key, value) in [
+        ("branch", &dep.branch),
+        ("tag", &dep.tag),
+        ("rev", &dep.revision),
+    ] {
+        if let Some(value) = value {
+            parts.push(format!("{} {}", key, value));
+        }
+    }
+    if let Some(requirement) = dep.requirement() {
+        parts.push(requirement);
+    }
+    if parts.is_empty() {
+        "any version".to_string()
+    } else {
+        parts.join(", ")
     }
 }
 
@@ -206,4 +435,111 @@ mod tests {
         assert_eq!(ws.filter_members("core").len(), 1);
         assert_eq!(ws.filter_members("cli").len(), 1);
     }
+
+    #[test]
+    fn test_affected_members_follow_dependents() {
+        let member = |name: &str, deps: &[&str]| WorkspaceMember {
+            path: PathBuf::from("/workspace/packages").join(name),
+            name: name.to_string(),
+            manifest: Manifest::default(),
+            workspace_deps: deps.iter().map(|d| d.to_string()).collect(),
+        };
+        let ws = Workspace {
+            root: PathBuf::from("/workspace"),
+            root_manifest: Manifest::default(),
+            config: WorkspaceConfig::default(),
+            members: vec![
+                member("core", &[]),
+                member("net", &["core"]),
+                member("app", &["net"]),
+                member("docs", &[]),
+            ],
+            shared_dependencies: HashMap::new(),
+        };
+        let affected = |files: &[&str]| {
+            let files: Vec<PathBuf> = files.iter().map(PathBuf::from).collect();
+            ws.affected_members(&files)
+                .into_iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            affected(&["packages/core/Sources/Core/Core.swift"]),
+            ["core", "net", "app"]
+        );
+        assert_eq!(affected(&["packages/app/Gust.toml"]), ["app"]);
+        assert_eq!(
+            affected(&["packages/docs/README.md", "README.md"]),
+            ["docs"]
+        );
+        // `packages/core-extras` is not inside `packages/core`
+        assert!(affected(&["packages/core-extras/x.swift"]).is_empty());
+        // Shared dependencies live in the root manifest
+        assert_eq!(affected(&["Gust.lock"]).len(), 4);
+    }
+
+    #[test]
+    fn test_conflicting_member_dependencies_are_reported() {
+        let member = |name: &str, deps: Vec<Dependency>| {
+            let mut manifest = Manifest::default();
+            for dep in deps {
+                manifest.dependencies.insert(dep.name.clone(), dep);
+            }
+            WorkspaceMember {
+                path: PathBuf::from("/workspace").join(name),
+                name: name.to_string(),
+                manifest,
+                workspace_deps: vec![],
+            }
+        };
+        let req = |r: &str| VersionReq::parse(r).unwrap();
+        let workspace = |members| Workspace {
+            root: PathBuf::from("/workspace"),
+            root_manifest: Manifest::default(),
+            config: WorkspaceConfig::default(),
+            members,
+            shared_dependencies: HashMap::new(),
+        };
+
+        // Compatible requirements merge into one
+        let ws = workspace(vec![
+            member("core", vec![Dependency::registry("log", req("^1.4"))]),
+            member("cli", vec![Dependency::registry("log", req("<1.6"))]),
+        ]);
+        let deps = ws.all_external_dependencies().unwrap();
+        let log = &deps["log"];
+        assert!(log.accepts(&Version::new(1, 5, 0)));
+        assert!(!log.accepts(&Version::new(1, 6, 0)));
+        assert!(!log.accepts(&Version::new(1, 3, 0)));
+
+        // Different repositories and disjoint requirements are both reported
+        let ws = workspace(vec![
+            member(
+                "core",
+                vec![
+                    Dependency::git("nio", "https://github.com/apple/swift-nio.git"),
+                    Dependency::registry("log", req("^1.4")),
+                ],
+            ),
+            member(
+                "cli",
+                vec![
+                    Dependency::git("nio", "https://github.com/fork/swift-nio.git"),
+                    Dependency::registry("log", req("^2.0")),
+                ],
+            ),
+        ]);
+        let Err(WorkspaceError::DependencyConflicts { conflicts }) = ws.all_external_dependencies()
+        else {
+            panic!("expected conflicts");
+        };
+        assert_eq!(conflicts.len(), 2);
+        let log = conflicts.iter().find(|c| c.name == "log").unwrap();
+        assert_eq!(log.first, ("core".to_string(), "^1.4".to_string()));
+        assert_eq!(log.second, ("cli".to_string(), "^2.0".to_string()));
+        let nio = conflicts.iter().find(|c| c.name == "nio").unwrap();
+        assert_eq!(nio.first.0, "core");
+        assert!(nio.second.1.contains("fork/swift-nio"));
+    }
 }
@@ -70,6 +70,7 @@ impl WorkspaceLoader {
             let member = self.load_member(&member_path, &shared_dependencies, &member_names)?;
             members.push(member);
         }
+        link_members(&mut members);
 
         Ok(Workspace {
             root: root.to_path_buf(),
@@ -90,9 +91,6 @@ impl WorkspaceLoader {
         let (mut manifest, _) = find_manifest(path)?;
         let name = manifest.package.name.clone();
 
-        // Track which dependencies are on other workspace members
-        let mut workspace_deps = Vec::new();
-
         // Process dependencies with workspace inheritance
         if self.resolve_inheritance {
             let mut resolved_deps = HashMap::new();
@@ -100,7 +98,6 @@ impl WorkspaceLoader {
             for (dep_name, dep) in manifest.dependencies.drain() {
                 // Check if this dependency is on another workspace member
                 if all_member_names.contains(&dep_name) {
-                    workspace_deps.push(dep_name.clone());
                     // Keep the dependency as-is - it will be resolved as a workspace member
                     // The resolver handles workspace member detection separately
                     resolved_deps.insert(dep_name, dep);
@@ -126,7 +123,8 @@ impl WorkspaceLoader {
             path: path.to_path_buf(),
             name,
             manifest,
-            workspace_deps,
+            // Filled in by `link_members` once every member is loaded
+            workspace_deps: Vec::new(),
         })
     }
 
@@ -147,6 +145,42 @@ impl Clone for WorkspaceLoader {
     }
 }
 
+/// Fill each member's `workspace_deps` with the members it depends on.
+///
+/// A dependency refers to a member if it has the member's name, or if it's a
+/// path dependency pointing at the member's directory.
+fn link_members(members: &mut [WorkspaceMember]) {
+    let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+
+    let by_path: HashMap<PathBuf, String> = members
+        .iter()
+        .map(|m| (canonical(&m.path), m.name.clone()))
+        .collect();
+    let names: Vec<String> = members.iter().map(|m| m.name.clone()).collect();
+
+    for member in members.iter_mut() {
+        let mut deps = Vec::new();
+
+        for (dep_name, dep) in &member.manifest.dependencies {
+            let target = dep
+                .path
+                .as_ref()
+                .and_then(|p| by_path.get(&canonical(&member.path.join(p))))
+                .cloned()
+                .or_else(|| names.contains(dep_name).then(|| dep_name.clone()));
+
+            if let Some(target) = target {
+                if target != member.name && !deps.contains(&target) {
+                    deps.push(target);
+                }
+            }
+        }
+
+        deps.sort();
+        member.workspace_deps = deps;
+    }
+}
+
 /// Extension trait for Dependency to check workspace inheritance.
 trait DependencyExt {
     fn is_workspace_inherited(&self) -> bool;
@@ -183,7 +217,59 @@ mod tests {
             path: None,
             features: vec![],
             optional: false,
+            group: None,
+            lfs: None,
+            excluded_versions: vec![],
+            build: None,
+            products: vec![],
         };
         assert!(bare.is_workspace_inherited());
     }
+
+    fn write_manifest(dir: &Path, content: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("Gust.toml"), content).unwrap();
+    }
+
+    #[test]
+    fn test_load_links_member_dependencies() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path();
+
+        write_manifest(
+            root,
+            "[package]\nname = \"root\"\nversion = \"0.1.0\"\n\n[workspace]\nmembers = [\"packages/*\"]\n",
+        );
+        write_manifest(
+            &root.join("packages/cli"),
+            "[package]\nname = \"cli\"\nversion = \"0.1.0\"\n\n[dependencies]\ncore = { path = \"../core\" }\n",
+        );
+        write_manifest(
+            &root.join("packages/core"),
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\n\n[dependencies]\nbase = { path = \"../util\" }\n",
+        );
+        write_manifest(
+            &root.join("packages/util"),
+            "[package]\nname = \"util\"\nversion = \"0.1.0\"\n",
+        );
+
+        for loader in [
+            WorkspaceLoader::new(),
+            WorkspaceLoader::new().without_inheritance(),
+        ] {
+            let ws = loader.load(root).unwrap();
+
+            assert_eq!(ws.get_member("cli").unwrap().workspace_deps, vec!["core"]);
+            // Matched by path even though the dependency key differs
+            assert_eq!(ws.get_member("core").unwrap().workspace_deps, vec!["util"]);
+
+            let order: Vec<&str> = ws
+                .build_order()
+                .unwrap()
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect();
+            assert_eq!(order, vec!["util", "core", "cli"]);
+        }
+    }
 }
@@ -43,7 +43,7 @@ impl<P: PackageProvider> WorkspaceResolver<P> {
     /// from all workspace members.
     pub fn resolve(&self, workspace: &Workspace) -> Result<WorkspaceResolution, WorkspaceError> {
         // Collect all external dependencies from all members
-        let all_deps = workspace.all_external_dependencies();
+        let all_deps = workspace.all_external_dependencies()?;
 
         // Create a synthetic root manifest that depends on everything
         let mut root_manifest = Manifest::default();
@@ -182,7 +182,7 @@ mod tests {
     #[test]
     fn test_workspace_collects_all_deps() {
         let workspace = create_test_workspace();
-        let all_deps = workspace.all_external_dependencies();
+        let all_deps = workspace.all_external_dependencies().unwrap();
 
         assert!(all_deps.contains_key("swift-log"));
         assert!(all_deps.contains_key("swift-argument-parser"));
@@ -1,18 +1,40 @@
 //! Core CLI command implementations.
 
-use crate::commands::ui::{self, dim, green, pkg, separator};
-use crate::commands::version::{check_all_for_updates, filter_breaking};
-use crate::install::{InstallOptions, Installer};
+use crate::commands::template;
+use crate::commands::ui::{self, dim, green, pkg, separator, ManifestFormat};
+use crate::commands::version::{
+    check_all_for_updates, filter_breaking, tracked_branches, BranchTracked,
+};
+use crate::commands::workspace::effective_manifest;
+use crate::config::{self, GustConfig};
+use crate::install::{
+    sanitize_name, write_package_resolved, InstallOptions, InstallResult, Installer, PathMode,
+    PruneScope,
+};
+use crate::reporter::{Event, ReporterKind};
 use console::style;
-use gust_build::{BuildOptions, Builder};
+use gust_build::{
+    BuildError, BuildOptions, BuildProduct, BuildResult, Builder, Coverage, TargetCoverage,
+};
 use gust_cache::GlobalCache;
-use gust_manifest::{find_manifest, generate_gust_toml, write_package_swift, ManifestType};
-use gust_types::{BuildConfiguration, Manifest, Package, Target, TargetType, Version};
+use gust_diagnostics::GustError;
+use gust_manifest::{
+    dependency_products, find_manifest, generate_gust_toml, generate_native_package_swift,
+    is_generated_package_swift, validate_target_dependencies, write_package_swift, FeatureSet,
+    ManifestEditor, ManifestType, ResolutionSources,
+};
+use gust_platform::SwiftToolchain;
+use gust_types::{
+    BuildConfiguration, Dependency, DependencySource, Manifest, Package, Target, TargetType,
+    Version, VersionReq,
+};
 use miette::{IntoDiagnostic, Result};
+use std::collections::{BTreeSet, HashSet};
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
 /// Well-known Swift package GitHub organizations for auto-discovery
 const KNOWN_ORGS: &[(&str, &str)] = &[
@@ -42,24 +64,77 @@ const KNOWN_ORGS: &[(&str, &str)] = &[
 ];
 
 /// Create a new package.
-pub async fn new_package(name: &str, pkg_type: &str, no_git: bool) -> Result<()> {
+pub async fn new_package(
+    name: &str,
+    pkg_type: &str,
+    template: Option<&str>,
+    no_git: bool,
+    install: bool,
+    format: ManifestFormat,
+) -> Result<()> {
     let path = env::current_dir().into_diagnostic()?.join(name);
 
     if path.exists() {
         return Err(miette::miette!("Directory {} already exists", name));
     }
 
-    fs::create_dir_all(&path).into_diagnostic()?;
+    if let Some(spec) = template {
+        return new_from_template(name, &path, spec, no_git, install).await;
+    }
 
-    let target_type = match pkg_type {
-        "executable" | "exe" => TargetType::Executable,
-        "library" | "lib" => TargetType::Library,
-        _ => return Err(miette::miette!("Unknown package type: {}", pkg_type)),
-    };
+    let target_type = parse_target_type(pkg_type)?;
+    scaffold_package(&path, name, target_type, format)?;
 
-    let manifest = create_manifest(name, target_type);
-    let toml = generate_gust_toml(&manifest);
-    fs::write(path.join("Gust.toml"), toml).into_diagnostic()?;
+    if !no_git {
+        init_git_repo(&path);
+    }
+
+    println!(
+        "{} Created package {} at {}",
+        style("✓").green().bold(),
+        style(name).cyan(),
+        path.display()
+    );
+
+    println!("\n{}", style("Next steps:").bold());
+    println!("  cd {}", name);
+    println!("  gust build");
+    if target_type == TargetType::Executable {
+        println!("  gust run");
+    }
+
+    Ok(())
+}
+
+fn parse_target_type(pkg_type: &str) -> Result<TargetType> {
+    match pkg_type {
+        "executable" | "exe" => Ok(TargetType::Executable),
+        "library" | "lib" => Ok(TargetType::Library),
+        _ => Err(miette::miette!("Unknown package type: {}", pkg_type)),
+    }
+}
+
+/// The manifest for a new package in `format`: its file name and contents.
+fn scaffold_manifest(manifest: &Manifest, format: ManifestFormat) -> (&'static str, String) {
+    match format {
+        ManifestFormat::Toml => ("Gust.toml", generate_gust_toml(manifest)),
+        ManifestFormat::Swift => ("Package.swift", generate_native_package_swift(manifest)),
+    }
+}
+
+/// Write a new package named `name` into `path`, which must not exist.
+fn scaffold_package(
+    path: &Path,
+    name: &str,
+    target_type: TargetType,
+    format: ManifestFormat,
+) -> Result<()> {
+    fs::create_dir_all(path).into_diagnostic()?;
+
+    let tools_version = scaffold_tools_version(SwiftToolchain::detect().ok().as_ref());
+    let manifest = create_manifest(name, target_type, &tools_version);
+    let (file_name, content) = scaffold_manifest(&manifest, format);
+    fs::write(path.join(file_name), content).into_diagnostic()?;
 
     // Create source directory
     let sources_dir = path.join("Sources").join(name);
@@ -97,28 +172,42 @@ final class {}Tests: XCTestCase {{
     );
     fs::write(test_file, test_content).into_diagnostic()?;
 
-    // Create .gitignore
-    let gitignore = r#".build/
+    // Create .gitignore; a generated Package.swift isn't committed
+    let mut gitignore = String::from(
+        r#".build/
 .swiftpm/
 *.xcodeproj
 *.xcworkspace
 DerivedData/
-Package.swift
-"#;
+"#,
+    );
+    if format == ManifestFormat::Toml {
+        gitignore.push_str("Package.swift\n");
+    }
     fs::write(path.join(".gitignore"), gitignore).into_diagnostic()?;
 
-    // Initialize git repository
+    Ok(())
+}
+
+/// Create a package at `path` from a template, then optionally install it.
+async fn new_from_template(
+    name: &str,
+    path: &Path,
+    spec: &str,
+    no_git: bool,
+    install: bool,
+) -> Result<()> {
+    let source = template::TemplateSource::parse(spec);
+    ui::info(format!("Creating {} from template {}", name, spec));
+
+    let vars = template::TemplateVars::for_package(name);
+    if let Err(err) = template::scaffold(&source, path, &vars).await {
+        let _ = fs::remove_dir_all(path);
+        return Err(err);
+    }
+
     if !no_git {
-        let git_result = Command::new("git")
-            .args(["init", "-q"])
-            .current_dir(&path)
-            .status();
-
-        if let Ok(status) = git_result {
-            if status.success() {
-                println!("{} Initialized git repository", style("✓").green());
-            }
-        }
+        init_git_repo(path);
     }
 
     println!(
@@ -128,38 +217,57 @@ Package.swift
         path.display()
     );
 
+    if install {
+        println!();
+        let installer = Installer::new(path.to_path_buf(), default_install_options(None, None))?;
+        installer.install().await?;
+        let (manifest, manifest_type) = find_manifest(path).into_diagnostic()?;
+        if manifest_type == ManifestType::GustToml {
+            write_default_package_swift(&manifest, path)?;
+        }
+    }
+
     println!("\n{}", style("Next steps:").bold());
     println!("  cd {}", name);
-    println!("  gust build");
-    if target_type == TargetType::Executable {
-        println!("  gust run");
+    if !install {
+        println!("  gust install");
     }
+    println!("  gust build");
 
     Ok(())
 }
 
+/// Initialize a git repository in `path`, reporting success.
+fn init_git_repo(path: &Path) {
+    let git_result = Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(path)
+        .status();
+
+    if let Ok(status) = git_result {
+        if status.success() {
+            println!("{} Initialized git repository", style("✓").green());
+        }
+    }
+}
+
 /// Initialize a package in the current directory.
-pub async fn init(name: Option<&str>, pkg_type: &str) -> Result<()> {
+pub async fn init(name: Option<&str>, pkg_type: &str, format: ManifestFormat) -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
     let pkg_name = name
         .map(String::from)
         .or_else(|| cwd.file_name().map(|n| n.to_string_lossy().to_string()))
         .unwrap_or_else(|| "MyPackage".to_string());
 
-    let manifest_path = cwd.join("Gust.toml");
+    let target_type = parse_target_type(pkg_type)?;
+    let tools_version = scaffold_tools_version(SwiftToolchain::detect().ok().as_ref());
+    let manifest = create_manifest(&pkg_name, target_type, &tools_version);
+    let (file_name, content) = scaffold_manifest(&manifest, format);
+    let manifest_path = cwd.join(file_name);
     if manifest_path.exists() {
-        return Err(miette::miette!("Gust.toml already exists"));
+        return Err(miette::miette!("{} already exists", file_name));
     }
-
-    let target_type = match pkg_type {
-        "executable" | "exe" => TargetType::Executable,
-        "library" | "lib" => TargetType::Library,
-        _ => return Err(miette::miette!("Unknown package type: {}", pkg_type)),
-    };
-
-    let manifest = create_manifest(&pkg_name, target_type);
-    let toml = generate_gust_toml(&manifest);
-    fs::write(&manifest_path, toml).into_diagnostic()?;
+    fs::write(&manifest_path, content).into_diagnostic()?;
 
     // Create source directory if it doesn't exist
     let sources_dir = cwd.join("Sources").join(&pkg_name);
@@ -209,12 +317,23 @@ DerivedData/
     Ok(())
 }
 
-fn create_manifest(name: &str, target_type: TargetType) -> Manifest {
+/// Tools version of new packages when no Swift toolchain is found.
+const FALLBACK_TOOLS_VERSION: &str = "5.9";
+
+/// `swift-tools-version` for a new package: `major.minor` of the installed
+/// toolchain, or [`FALLBACK_TOOLS_VERSION`] without one.
+fn scaffold_tools_version(toolchain: Option<&SwiftToolchain>) -> String {
+    toolchain
+        .map(SwiftToolchain::tools_version)
+        .unwrap_or_else(|| FALLBACK_TOOLS_VERSION.to_string())
+}
+
+fn create_manifest(name: &str, target_type: TargetType, tools_version: &str) -> Manifest {
     Manifest {
         package: Package {
             name: name.to_string(),
             version: Version::new(0, 1, 0),
-            swift_tools_version: "5.9".to_string(),
+            swift_tools_version: tools_version.to_string(),
             ..Default::default()
         },
         targets: vec![Target {
@@ -223,37 +342,94 @@ fn create_manifest(name: &str, target_type: TargetType) -> Manifest {
             path: Some(format!("Sources/{}", name).into()),
             dependencies: Vec::new(),
             resources: Vec::new(),
+            exclude: Vec::new(),
+            sources: None,
+            url: None,
+            checksum: None,
+            plugin_capability: None,
+            pkg_config: None,
+            providers: Vec::new(),
         }],
         ..Default::default()
     }
 }
 
+/// What to build, as `gust build` was asked.
+#[derive(Debug, Clone, Default)]
+pub struct BuildArgs {
+    pub release: bool,
+    /// Build only this target
+    pub target: Option<String>,
+    pub jobs: Option<usize>,
+    pub no_cache: bool,
+    /// Print the fingerprint breakdown before building
+    pub explain_cache: bool,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub groups: Vec<String>,
+    /// Overrides `[build] sdk`
+    pub sdk: Option<PathBuf>,
+    /// On top of `[build] warnings-as-errors`
+    pub warnings_as_errors: bool,
+    pub target_dir: Option<PathBuf>,
+}
+
 /// Build the package.
-pub async fn build(
-    release: bool,
-    target: Option<&str>,
-    jobs: Option<usize>,
-    no_cache: bool,
-) -> Result<()> {
+pub async fn build(args: &BuildArgs) -> Result<BuildResult> {
     let cwd = env::current_dir().into_diagnostic()?;
-    let (manifest, manifest_type) = find_manifest(&cwd).into_diagnostic()?;
+    build_in(&cwd, args).await
+}
 
-    // Auto-generate Package.swift from Gust.toml if needed
+/// Build the package in `dir`.
+pub async fn build_in(dir: &Path, args: &BuildArgs) -> Result<BuildResult> {
+    let cwd = dir.to_path_buf();
+    let (manifest, manifest_type) = effective_manifest(&cwd)?;
+    let feature_set = FeatureSet::resolve(&manifest, &args.features, !args.no_default_features)
+        .and_then(|set| set.with_groups(&manifest, &args.groups))
+        .into_diagnostic()?;
+
+    // Auto-generate Package.swift from Gust.toml if needed, leaving out
+    // unselected dependency groups; optional dependencies are added by
+    // Package.swift itself for the features the build enables
     if manifest_type == ManifestType::GustToml {
-        write_package_swift(&manifest, &cwd).into_diagnostic()?;
+        if let Some(issue) = validate_target_dependencies(&manifest).into_iter().next() {
+            return Err(GustError::unknown_target_dependency(
+                issue.target.clone(),
+                issue.dependency.clone(),
+                issue.help(),
+            )
+            .into());
+        }
+        write_project_package_swift(feature_set.apply(&manifest), &cwd)?;
+        write_package_resolved(&cwd, &manifest)?;
     }
 
-    let builder = Builder::new(cwd).into_diagnostic()?;
+    let mut builder = Builder::new(cwd.clone()).into_diagnostic()?;
+    if let Some(dir) = &args.target_dir {
+        builder = builder.with_target_dir(dir);
+    }
 
     let options = BuildOptions {
-        configuration: if release {
+        configuration: if args.release {
             BuildConfiguration::Release
         } else {
             BuildConfiguration::Debug
         },
-        target: target.map(String::from),
-        jobs,
-        use_cache: !no_cache,
+        target: args.target.clone(),
+        jobs: args.jobs,
+        use_cache: !args.no_cache,
+        features: feature_set.features(),
+        sdk: args
+            .sdk
+            .clone()
+            .or_else(|| manifest.build.as_ref().and_then(|b| b.sdk.clone()))
+            .map(|sdk| cwd.join(sdk)),
+        warnings_as_errors: args.warnings_as_errors
+            || manifest
+                .build
+                .as_ref()
+                .is_some_and(|b| b.warnings_as_errors),
+        cancel: gust_build::ctrl_c_token(),
         ..Default::default()
     };
 
@@ -263,8 +439,40 @@ pub async fn build(
         style(&manifest.package.name).cyan(),
         options.configuration
     );
+    if !feature_set.is_empty() {
+        println!(
+            "  {} Features: {}",
+            style("→").dim(),
+            feature_set.features().join(", ")
+        );
+    }
+
+    if args.explain_cache {
+        explain_fingerprint(&builder, &manifest, &options)?;
+    }
 
-    let result = builder.build(&manifest, &options).await.into_diagnostic()?;
+    let result = match builder.build(&manifest, &options).await {
+        Err(BuildError::TargetNotFound {
+            name,
+            suggestion: Some(suggestion),
+        }) => return Err(GustError::target_not_found(name, suggestion).into()),
+        Err(BuildError::SystemLibraryMissing {
+            target,
+            library,
+            install,
+        }) => {
+            let help = if install.is_empty() {
+                format!(
+                    "Install the package that provides '{}' and make sure pkg-config can find it",
+                    library
+                )
+            } else {
+                format!("Install it with: {}", install.join(" or "))
+            };
+            return Err(GustError::system_library_missing(library, target, help).into());
+        }
+        result => result.into_diagnostic()?,
+    };
 
     if result.cached {
         println!(
@@ -289,45 +497,117 @@ pub async fn build(
     }
 
     for product in &result.products {
-        println!("  {} {}", style("•").dim(), product.display());
+        println!("  {} {}", style("•").dim(), product.path.display());
+    }
+
+    Ok(result)
+}
+
+/// Print the fingerprint breakdown and how it differs from the last build.
+fn explain_fingerprint(
+    builder: &Builder,
+    manifest: &Manifest,
+    options: &BuildOptions,
+) -> Result<()> {
+    let current = builder
+        .compute_fingerprint(manifest, options)
+        .into_diagnostic()?;
+
+    println!(
+        "  {} {}",
+        style("Fingerprint").bold(),
+        dim(&current.fingerprint[..16])
+    );
+    for (component, value) in current.components() {
+        println!("    {:<14} {}", component, dim(value));
+    }
+
+    match builder.last_fingerprint() {
+        None => println!("  {}", dim("No previous build fingerprint recorded")),
+        Some(previous) if previous.fingerprint == current.fingerprint => {
+            println!("  {}", dim("Unchanged since the last build"))
+        }
+        Some(previous) => {
+            println!("  {}", style("Changed since the last build:").bold());
+            for change in current.diff(&previous) {
+                println!("    {} {}", style("~").yellow(), change);
+            }
+        }
     }
+    println!();
+
+    Ok(())
+}
 
+/// Print the manifest `gust build` works from instead of building.
+pub fn print_manifest(
+    format: ui::ManifestOutput,
+    features: &[String],
+    no_default_features: bool,
+    groups: &[String],
+) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let rendered = render_manifest(&cwd, format, features, no_default_features, groups)?;
+    println!("{}", rendered.trim_end());
     Ok(())
 }
 
+/// The effective manifest of the package at `dir` with the selected
+/// features and groups applied, keys sorted so output is stable.
+fn render_manifest(
+    dir: &Path,
+    format: ui::ManifestOutput,
+    features: &[String],
+    no_default_features: bool,
+    groups: &[String],
+) -> Result<String> {
+    let (manifest, _) = effective_manifest(dir)?;
+    let manifest = FeatureSet::resolve(&manifest, features, !no_default_features)
+        .and_then(|set| set.with_groups(&manifest, groups))
+        .into_diagnostic()?
+        .select(&manifest);
+    match format {
+        ui::ManifestOutput::Json => {
+            let value = serde_json::to_value(&manifest).into_diagnostic()?;
+            serde_json::to_string_pretty(&value).into_diagnostic()
+        }
+        ui::ManifestOutput::Toml => {
+            let value = toml::Value::try_from(&manifest).into_diagnostic()?;
+            toml::to_string_pretty(&value).into_diagnostic()
+        }
+    }
+}
+
 /// Run the executable.
-pub async fn run(target: Option<&str>, args: &[String]) -> Result<()> {
+///
+/// The executable runs in the project root with `env` added to the
+/// inherited environment; the build itself doesn't see `env`.
+pub async fn run(
+    target: Option<&str>,
+    release: bool,
+    env: &[(String, String)],
+    args: &[String],
+    target_dir: Option<&Path>,
+) -> Result<()> {
     // First build (with cache)
-    build(false, target, None, false).await?;
+    let result = build(&BuildArgs {
+        release,
+        target: target.map(String::from),
+        target_dir: target_dir.map(Path::to_path_buf),
+        ..Default::default()
+    })
+    .await?;
 
     let cwd = env::current_dir().into_diagnostic()?;
-    let (manifest, _) = find_manifest(&cwd).into_diagnostic()?;
-
-    // Find executable target
-    let exe_target = if let Some(name) = target {
-        manifest
-            .targets
-            .iter()
-            .find(|t| t.name == name && t.target_type == TargetType::Executable)
-            .ok_or_else(|| miette::miette!("Executable target '{}' not found", name))?
-    } else {
-        manifest
-            .targets
-            .iter()
-            .find(|t| t.target_type == TargetType::Executable)
-            .ok_or_else(|| miette::miette!("No executable target found"))?
-    };
-
-    let exe_path = cwd.join(".build").join("debug").join(&exe_target.name);
+    let exe = run_product(&result, target)?;
 
     println!(
         "{} Running {}",
         style("→").blue().bold(),
-        style(&exe_target.name).cyan()
+        style(&exe.name).cyan()
     );
 
-    let status = tokio::process::Command::new(&exe_path)
-        .args(args)
+    let status = run_command(&exe.path, args, env, &cwd)
         .status()
         .await
         .into_diagnostic()?;
@@ -339,8 +619,66 @@ pub async fn run(target: Option<&str>, args: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// The executable `gust run` starts: the built product named `target`, or
+/// the only executable built. Its path comes from the build, so a renamed
+/// product or one outside the top of the build directory is found too.
+fn run_product<'a>(result: &'a BuildResult, target: Option<&str>) -> Result<&'a BuildProduct> {
+    let executables: Vec<&BuildProduct> = result
+        .products
+        .iter()
+        .filter(|p| p.target_type == TargetType::Executable)
+        .collect();
+    let names = || {
+        executables
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match (target, executables.as_slice()) {
+        (Some(name), _) => result.executable(Some(name)).ok_or_else(|| {
+            if executables.is_empty() {
+                miette::miette!("Executable target '{}' not found", name)
+            } else {
+                miette::miette!(
+                    help = format!("Executables: {}", names()),
+                    "Executable target '{}' not found",
+                    name
+                )
+            }
+        }),
+        (None, []) => Err(miette::miette!("No executable target found")),
+        (None, [only]) => Ok(only),
+        (None, _) => Err(miette::miette!(
+            help = "Name the one to run, e.g. `gust run <name>`",
+            "Several executables were built: {}",
+            names()
+        )),
+    }
+}
+
+/// Command running `exe` with `args` in `dir`, with `env` on top of the
+/// inherited environment.
+fn run_command(
+    exe: &Path,
+    args: &[String],
+    env: &[(String, String)],
+    dir: &Path,
+) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new(exe);
+    cmd.args(args)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .current_dir(dir);
+    cmd
+}
+
 /// Run tests.
-pub async fn test(target: Option<&str>, filter: Option<&str>) -> Result<()> {
+pub async fn test(
+    target: Option<&str>,
+    filter: Option<&str>,
+    coverage: Option<ui::CoverageFormat>,
+    target_dir: Option<&Path>,
+) -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
 
     println!("{} Running tests", style("→").blue().bold());
@@ -348,6 +686,13 @@ pub async fn test(target: Option<&str>, filter: Option<&str>) -> Result<()> {
     let mut cmd = tokio::process::Command::new("swift");
     cmd.arg("test");
     cmd.current_dir(&cwd);
+    if let Some(dir) = target_dir {
+        cmd.arg("--scratch-path")
+            .arg(gust_build::target_dir(&cwd, Some(dir)));
+    }
+    if coverage.is_some() {
+        cmd.arg("--enable-code-coverage");
+    }
 
     if let Some(t) = target {
         cmd.arg("--filter").arg(t);
@@ -364,14 +709,70 @@ pub async fn test(target: Option<&str>, filter: Option<&str>) -> Result<()> {
         return Err(miette::miette!("Tests failed"));
     }
 
+    if let Some(format) = coverage {
+        report_coverage(&cwd, target_dir, format).await?;
+    }
+
+    Ok(())
+}
+
+/// Summarize or export the coverage data left by `swift test`.
+async fn report_coverage(
+    cwd: &Path,
+    target_dir: Option<&Path>,
+    format: ui::CoverageFormat,
+) -> Result<()> {
+    let (manifest, _) = find_manifest(cwd).into_diagnostic()?;
+    let toolchain = SwiftToolchain::detect().into_diagnostic()?;
+    let scratch = gust_build::target_dir(cwd, target_dir);
+    let coverage = Coverage::locate(&toolchain, &scratch.join("debug"), &manifest.package.name)
+        .await
+        .into_diagnostic()?;
+
+    match format {
+        ui::CoverageFormat::Lcov => {
+            let lcov = coverage.lcov().await.into_diagnostic()?;
+            let path = scratch.join("coverage").join("lcov.info");
+            fs::create_dir_all(path.parent().unwrap()).into_diagnostic()?;
+            fs::write(&path, lcov).into_diagnostic()?;
+            ui::success(format!("Wrote coverage to {}", path.display()));
+        }
+        ui::CoverageFormat::Text => {
+            let targets = coverage.summary(cwd).await.into_diagnostic()?;
+            println!("\n{}", style("Coverage:").bold());
+            let width = targets.iter().map(|t| t.name.len()).max().unwrap_or(0);
+            for target in &targets {
+                println!(
+                    "  {:<width$}  {:>6.1}%  ({}/{} lines)",
+                    target.name,
+                    target.percent(),
+                    target.lines_covered,
+                    target.lines_total,
+                );
+            }
+            let total = TargetCoverage {
+                name: "total".to_string(),
+                lines_covered: targets.iter().map(|t| t.lines_covered).sum(),
+                lines_total: targets.iter().map(|t| t.lines_total).sum(),
+            };
+            println!(
+                "  {:<width$}  {:>6.1}%  ({}/{} lines)",
+                total.name,
+                total.percent(),
+                total.lines_covered,
+                total.lines_total,
+            );
+        }
+    }
+
     Ok(())
 }
 
 /// Clean build artifacts.
-pub async fn clean(deps: bool) -> Result<()> {
+pub async fn clean(deps: bool, target_dir: Option<&Path>) -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
 
-    let build_dir = cwd.join(".build");
+    let build_dir = gust_build::target_dir(&cwd, target_dir);
     if build_dir.exists() {
         fs::remove_dir_all(&build_dir).into_diagnostic()?;
         println!(
@@ -413,15 +814,192 @@ fn resolve_package_url(package: &str) -> Option<String> {
     None
 }
 
+/// The requirement a `package@<spec>` version asks for: a full version
+/// means exactly that version, anything shorter (`1.0`, `^1.2`) a range.
+/// `None` when the spec isn't a version at all, so it's taken as a tag name.
+fn add_requirement(spec: &str) -> Option<VersionReq> {
+    let spec = spec.trim_start_matches('v');
+    match Version::parse(spec) {
+        Ok(version) => VersionReq::parse(&format!("={}", version)).ok(),
+        Err(_) => VersionReq::parse(spec).ok(),
+    }
+}
+
+/// Whether `version` satisfies `requirement`. Without `allow_prerelease` a
+/// prerelease only counts when the requirement names it, as in
+/// `foo@1.3.0-beta.1`; with it a prerelease also counts when its release
+/// would.
+fn admits(requirement: Option<&VersionReq>, version: &Version, allow_prerelease: bool) -> bool {
+    if !version.pre.is_empty() && !allow_prerelease {
+        return requirement.is_some_and(|r| r.matches(version));
+    }
+    let Some(requirement) = requirement else {
+        return true;
+    };
+    let mut release = version.clone();
+    release.pre = semver::Prerelease::EMPTY;
+    requirement.matches(version) || (allow_prerelease && requirement.matches(&release))
+}
+
+/// The highest version `requirement` admits, skipping prereleases unless
+/// `allow_prerelease`.
+fn select_version<'a>(
+    versions: impl IntoIterator<Item = &'a Version>,
+    requirement: Option<&VersionReq>,
+    allow_prerelease: bool,
+) -> Option<&'a Version> {
+    versions
+        .into_iter()
+        .filter(|v| admits(requirement, v, allow_prerelease))
+        .max()
+}
+
+/// Error for a `gust add` where no version fits, pointing at
+/// `--allow-prerelease` when a prerelease would have.
+fn no_matching_version(
+    name: &str,
+    spec: Option<&str>,
+    versions: &[&Version],
+    requirement: Option<&VersionReq>,
+) -> miette::Report {
+    let wanted = spec.map_or_else(|| "any release".to_string(), |s| format!("'{}'", s));
+    let help = if select_version(versions.iter().copied(), requirement, true).is_some() {
+        "only prereleases match; pass --allow-prerelease to use one".to_string()
+    } else {
+        let mut newest: Vec<&Version> = versions.to_vec();
+        newest.sort_unstable_by(|a, b| b.cmp(a));
+        let newest: Vec<String> = newest.iter().take(5).map(|v| v.to_string()).collect();
+        if newest.is_empty() {
+            "it has no versioned releases".to_string()
+        } else {
+            format!("available versions include: {}", newest.join(", "))
+        }
+    };
+    miette::miette!(help = help, "No version of {} matches {}", name, wanted)
+}
+
+/// The tag `gust add` pins a git dependency to: the highest tag matching
+/// the `@` spec. A spec that isn't a version is used as the tag as is, and
+/// without a spec a repository with no versioned tags is left unpinned.
+async fn select_git_tag(
+    name: &str,
+    url: &str,
+    spec: Option<&str>,
+    allow_prerelease: bool,
+) -> Result<Option<String>> {
+    let requirement = match spec {
+        Some(spec) => match add_requirement(spec) {
+            Some(requirement) => Some(requirement),
+            None => return Ok(Some(spec.to_string())),
+        },
+        None => None,
+    };
+    let tags = match gust_fetch::list_remote_tags(url).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            ui::warn(format!("Could not list versions of {}: {}", name, e));
+            return Ok(spec.map(str::to_string));
+        }
+    };
+    let versions: Vec<&Version> = tags.iter().filter_map(|t| t.version.as_ref()).collect();
+    if spec.is_none() && versions.is_empty() {
+        return Ok(None);
+    }
+    let Some(version) = select_version(
+        versions.iter().copied(),
+        requirement.as_ref(),
+        allow_prerelease,
+    ) else {
+        return Err(no_matching_version(
+            name,
+            spec,
+            &versions,
+            requirement.as_ref(),
+        ));
+    };
+    Ok(tags
+        .iter()
+        .find(|t| t.version.as_ref() == Some(version))
+        .map(|t| t.name.clone()))
+}
+
+/// The registry version `gust add` requires for a `scope.name` package:
+/// compatible with the highest release matching the `@` spec.
+async fn select_registry_version(
+    name: &str,
+    spec: Option<&str>,
+    allow_prerelease: bool,
+) -> Result<VersionReq> {
+    let requirement = match spec {
+        Some(spec) => Some(
+            add_requirement(spec)
+                .ok_or_else(|| miette::miette!("'{}' is not a version requirement", spec))?,
+        ),
+        None => None,
+    };
+    let id = gust_registry::registry_identity(name).into_diagnostic()?;
+    let releases = gust_registry::RegistryClient::from_env()
+        .list_versions(&id.scope, &id.name)
+        .await
+        .into_diagnostic()?
+        .releases;
+    let parsed: Vec<Version> = releases
+        .iter()
+        .filter(|(_, info)| info.problem.is_none())
+        .filter_map(|(v, _)| Version::parse(v).ok())
+        .collect();
+    let versions: Vec<&Version> = parsed.iter().collect();
+    let Some(version) = select_version(
+        versions.iter().copied(),
+        requirement.as_ref(),
+        allow_prerelease,
+    ) else {
+        return Err(no_matching_version(
+            name,
+            spec,
+            &versions,
+            requirement.as_ref(),
+        ));
+    };
+    VersionReq::parse(&format!("^{}", version)).into_diagnostic()
+}
+
+/// How to add a dependency, as `gust add` was asked.
+#[derive(Debug, Clone, Default)]
+pub struct AddArgs {
+    pub git: Option<String>,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    /// Let a version be picked from prereleases too
+    pub allow_prerelease: bool,
+    pub path: Option<PathBuf>,
+    pub features: Vec<String>,
+    pub products: Vec<String>,
+    /// Add to `[dev-dependencies]`
+    pub dev: bool,
+    /// Make it optional, turned on by the named feature or by its own name
+    pub optional: Option<Option<String>>,
+    /// Install afterwards; without a value the manifest's `[gust]
+    /// auto-install` decides
+    pub install: Option<bool>,
+    pub jobs: Option<usize>,
+    pub target_dir: Option<PathBuf>,
+}
+
 /// Add a dependency.
-pub async fn add(
-    package: &str,
-    git: Option<&str>,
-    branch: Option<&str>,
-    tag: Option<&str>,
-    path: Option<&Path>,
-    dev: bool,
-) -> Result<()> {
+pub async fn add(package: &str, args: &AddArgs) -> Result<()> {
+    let AddArgs {
+        allow_prerelease,
+        dev,
+        ..
+    } = *args;
+    let (git, branch, tag, path) = (
+        args.git.as_deref(),
+        args.branch.as_deref(),
+        args.tag.as_deref(),
+        args.path.as_deref(),
+    );
+    let optional = args.optional.as_ref().map(Option::as_deref);
     let cwd = env::current_dir().into_diagnostic()?;
     let manifest_path = cwd.join("Gust.toml");
 
@@ -467,21 +1045,38 @@ pub async fn add(
         None
     };
 
-    // Read existing manifest
-    let content = fs::read_to_string(&manifest_path).into_diagnostic()?;
-
-    // Build the dependency line
-    let dep_line = if let Some(ref git_url) = resolved_git {
-        let mut parts = vec![format!("git = \"{}\"", git_url)];
+    // Build the dependency entry
+    let dep = if let Some(ref git_url) = resolved_git {
+        let mut dep = Dependency::git(name, git_url);
+        let tag = match (tag, branch) {
+            (Some(t), _) => Some(t.to_string()),
+            (None, Some(_)) => version.map(str::to_string),
+            (None, None) => {
+                let selected = select_git_tag(name, git_url, version, allow_prerelease).await?;
+                if let Some(t) = &selected {
+                    println!("  {} Selected {}", style("→").dim(), style(t).green());
+                }
+                selected
+            }
+        };
         if let Some(b) = branch {
-            parts.push(format!("branch = \"{}\"", b));
+            dep = dep.with_branch(b);
         }
-        if let Some(t) = tag.or(version) {
-            parts.push(format!("tag = \"{}\"", t));
+        if let Some(t) = tag {
+            dep = dep.with_tag(t);
         }
-        format!("{} = {{ {} }}", name, parts.join(", "))
+        dep
     } else if let Some(p) = path {
-        format!("{} = {{ path = \"{}\" }}", name, p.display())
+        Dependency::path(name, p)
+    } else if pkg_spec.contains('.') {
+        // A `scope.name` registry identity
+        let requirement = select_registry_version(pkg_spec, version, allow_prerelease).await?;
+        println!(
+            "  {} Selected {}",
+            style("→").dim(),
+            style(&requirement).green()
+        );
+        Dependency::registry(pkg_spec, requirement)
     } else {
         // No git URL found and no path - error with helpful message
         return Err(miette::miette!(
@@ -496,58 +1091,10 @@ pub async fn add(
         ));
     };
 
-    // Find or create the dependencies section
-    let section = if dev {
-        "[dev-dependencies]"
-    } else {
-        "[dependencies]"
-    };
-
-    let new_content = if content.contains(section) {
-        // Add to existing section
-        let mut lines: Vec<&str> = content.lines().collect();
-        let mut insert_idx = None;
-        let mut in_section = false;
-
-        for (i, line) in lines.iter().enumerate() {
-            if line.trim() == section {
-                in_section = true;
-                continue;
-            }
-            if in_section {
-                // Check if we've hit another section
-                if line.starts_with('[') && line.ends_with(']') {
-                    insert_idx = Some(i);
-                    break;
-                }
-                // Check if this dep already exists
-                if line.starts_with(name) && (line.contains('=') || line.contains(" =")) {
-                    return Err(miette::miette!(
-                        "Dependency '{}' already exists. Use 'gust update' to change it.",
-                        name
-                    ));
-                }
-            }
-        }
-
-        // If we're still in the section at the end, insert at end
-        if in_section && insert_idx.is_none() {
-            insert_idx = Some(lines.len());
-        }
-
-        if let Some(idx) = insert_idx {
-            lines.insert(idx, &dep_line);
-            lines.join("\n")
-        } else {
-            // Section exists but couldn't find insert point, append to end
-            format!("{}\n{}\n", content.trim_end(), dep_line)
-        }
-    } else {
-        // Add new section
-        format!("{}\n\n{}\n{}\n", content.trim_end(), section, dep_line)
-    };
-
-    fs::write(&manifest_path, new_content).into_diagnostic()?;
+    let mut dep = dep.with_features(args.features.clone());
+    dep.products = args.products.clone();
+    dep.optional = optional.is_some();
+    let original = add_to_manifest(&manifest_path, &dep, dev, optional.flatten())?;
 
     println!(
         "{} Added {} to {}",
@@ -560,24 +1107,142 @@ pub async fn add(
         }
     );
 
-    // Offer to install
+    if let Some(feature) = optional {
+        println!(
+            "  {} Optional; installed with {}",
+            style("→").dim(),
+            style(format!("--features {}", feature.unwrap_or(name))).cyan()
+        );
+    }
+
+    let install = args.install.unwrap_or_else(|| {
+        find_manifest(&cwd)
+            .ok()
+            .and_then(|(manifest, _)| manifest.gust)
+            .is_some_and(|settings| settings.auto_install)
+    });
+
+    if !install {
+        println!(
+            "\n{} Run {} to install",
+            style("→").dim(),
+            style("gust install").cyan()
+        );
+        return Ok(());
+    }
+
+    println!();
+    let installer = Installer::new(
+        cwd.clone(),
+        default_install_options(args.target_dir.as_deref(), args.jobs),
+    )?;
+    let result = install_or_revert(&installer, &cwd, &original).await?;
+    let (manifest, _) = find_manifest(&cwd).into_diagnostic()?;
+    write_default_package_swift(&manifest, &cwd)?;
+
     println!(
-        "\n{} Run {} to install",
-        style("→").dim(),
-        style("gust install").cyan()
+        "\n{} Installed {} packages",
+        style("✓").green().bold(),
+        style(result.installed).cyan()
     );
 
     Ok(())
 }
 
-/// Remove a dependency.
-pub async fn remove(package: &str) -> Result<()> {
-    let cwd = env::current_dir().into_diagnostic()?;
-    let manifest_path = cwd.join("Gust.toml");
-
-    if !manifest_path.exists() {
-        return Err(miette::miette!("No Gust.toml found"));
-    }
+/// Write Package.swift for `manifest` with its default features enabled.
+fn write_default_package_swift(manifest: &Manifest, dir: &Path) -> Result<()> {
+    let feature_set = FeatureSet::resolve(manifest, &[], true).into_diagnostic()?;
+    write_project_package_swift(feature_set.apply(manifest), dir)
+}
+
+/// Write Package.swift for `manifest`, with git dependencies that have
+/// `[dependencies.<name>.build]` flags pinned to their commit in Gust.lock.
+///
+/// SwiftPM refuses unsafe flags in a dependency required by version, so the
+/// build overlay only takes effect for one required by revision.
+fn write_project_package_swift(mut manifest: Manifest, dir: &Path) -> Result<()> {
+    let lockfile = gust_lockfile::Lockfile::load(&dir.join("Gust.lock")).ok();
+    for (name, dep) in &mut manifest.dependencies {
+        if dep.build.is_none() || dep.git.is_none() || dep.revision.is_some() {
+            continue;
+        }
+        match lockfile
+            .as_ref()
+            .and_then(|l| l.get(name)?.revision.clone())
+        {
+            Some(revision) => dep.revision = Some(revision),
+            None => tracing::warn!(
+                "{} isn't locked to a commit yet, so SwiftPM won't apply its build flags",
+                name
+            ),
+        }
+    }
+    write_package_swift(&manifest, dir).into_diagnostic()
+}
+
+/// Add a dependency to a Gust.toml, returning the manifest's previous
+/// contents so the edit can be reverted.
+fn add_to_manifest(
+    manifest_path: &Path,
+    dep: &Dependency,
+    dev: bool,
+    feature: Option<&str>,
+) -> Result<String> {
+    let original = fs::read_to_string(manifest_path).into_diagnostic()?;
+
+    let mut editor = ManifestEditor::parse(&original).into_diagnostic()?;
+    if editor.contains_dependency(&dep.name) {
+        return Err(miette::miette!(
+            "Dependency '{}' already exists. Use 'gust update' to change it.",
+            dep.name
+        ));
+    }
+    editor.add_dependency(dep, dev).into_diagnostic()?;
+    if let Some(feature) = feature {
+        editor
+            .add_to_feature(feature, &dep.name)
+            .into_diagnostic()?;
+    }
+    editor.save(manifest_path).into_diagnostic()?;
+
+    Ok(original)
+}
+
+/// Install after a manifest edit, restoring the previous Gust.toml and
+/// Gust.lock if resolution or fetching fails.
+async fn install_or_revert(
+    installer: &Installer,
+    project_dir: &Path,
+    original_manifest: &str,
+) -> Result<InstallResult> {
+    let lockfile_path = project_dir.join("Gust.lock");
+    let original_lockfile = fs::read(&lockfile_path).ok();
+
+    match installer.install().await {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            fs::write(project_dir.join("Gust.toml"), original_manifest).into_diagnostic()?;
+            match original_lockfile {
+                Some(content) => fs::write(&lockfile_path, content).into_diagnostic()?,
+                None if lockfile_path.exists() => {
+                    fs::remove_file(&lockfile_path).into_diagnostic()?
+                }
+                None => {}
+            }
+            ui::warn("Install failed, reverted Gust.toml");
+            Err(e)
+        }
+    }
+}
+
+/// Remove a dependency.
+pub async fn remove(package: &str) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let manifest_path = cwd.join("Gust.toml");
+
+    if !manifest_path.exists() {
+        return Err(miette::miette!("No Gust.toml found"));
+    }
 
     println!(
         "{} Removing {}",
@@ -585,31 +1250,11 @@ pub async fn remove(package: &str) -> Result<()> {
         style(package).cyan()
     );
 
-    let content = fs::read_to_string(&manifest_path).into_diagnostic()?;
-    let mut lines: Vec<&str> = content.lines().collect();
-    let mut removed = false;
-    let mut i = 0;
-
-    while i < lines.len() {
-        let line = lines[i].trim();
-        // Check if line starts with the package name followed by = or space
-        if let Some(rest) = line.strip_prefix(package) {
-            if rest.starts_with(' ') || rest.starts_with('=') {
-                lines.remove(i);
-                removed = true;
-                continue;
-            }
-        }
-        i += 1;
-    }
-
-    if !removed {
+    let mut editor = ManifestEditor::open(&manifest_path).into_diagnostic()?;
+    if !editor.remove_dependency(package) {
         return Err(miette::miette!("Dependency '{}' not found", package));
     }
-
-    // Clean up empty sections
-    let new_content = lines.join("\n");
-    fs::write(&manifest_path, new_content).into_diagnostic()?;
+    editor.save(&manifest_path).into_diagnostic()?;
 
     println!(
         "{} Removed {}",
@@ -620,41 +1265,386 @@ pub async fn remove(package: &str) -> Result<()> {
     Ok(())
 }
 
+/// Install options for a normal, non-frozen install.
+///
+/// Concurrency is `jobs` if given, otherwise one per CPU core capped by
+/// available memory. Overrides and constraints start from the config's.
+fn default_install_options(target_dir: Option<&Path>, jobs: Option<usize>) -> InstallOptions {
+    let concurrency = gust_platform::Concurrency::detect(jobs);
+    tracing::info!("Installing with {}", concurrency);
+
+    // An invalid config was already reported at startup
+    let config = env::current_dir()
+        .ok()
+        .and_then(|cwd| GustConfig::load(&cwd).ok())
+        .unwrap_or_default();
+
+    InstallOptions {
+        concurrency: concurrency.jobs,
+        target_dir: target_dir.map(Path::to_path_buf),
+        resolution_defaults: config.resolution_sources(),
+        ..Default::default()
+    }
+}
+
+/// How to resolve, as `gust resolve` was asked.
+#[derive(Debug, Clone, Default)]
+pub struct ResolveArgs {
+    /// Fail instead of changing Gust.lock
+    pub frozen: bool,
+    pub offline: bool,
+    pub no_dev: bool,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub groups: Vec<String>,
+    /// `--override` values, `PACKAGE@VERSION`
+    pub overrides: Vec<String>,
+    /// `--constraint` values, `PACKAGE@REQ`
+    pub constraints: Vec<String>,
+    pub jobs: Option<usize>,
+}
+
+/// Resolve dependencies and write Gust.lock, printing what changed,
+/// without fetching into or linking the project.
+pub async fn resolve(args: &ResolveArgs) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let options = InstallOptions {
+        frozen: args.frozen,
+        offline: args.offline,
+        no_dev: args.no_dev,
+        features: args.features.clone(),
+        no_default_features: args.no_default_features,
+        groups: args.groups.clone(),
+        resolution_flags: ResolutionSources::from_flags(&args.overrides, &args.constraints)
+            .into_diagnostic()?,
+        ..default_install_options(None, args.jobs)
+    };
+
+    let Some(diff) = Installer::new(cwd, options)?.lock().await? else {
+        ui::success("Gust.lock is up to date");
+        return Ok(());
+    };
+    for locked in &diff.added {
+        println!("  {} {} {}", green("+"), pkg(&locked.name), locked.version);
+    }
+    for locked in &diff.updated {
+        println!(
+            "  {} {} {}",
+            style("~").yellow(),
+            pkg(&locked.name),
+            locked.version
+        );
+    }
+    for name in &diff.removed {
+        println!("  {} {}", style("-").red(), pkg(name));
+    }
+    ui::success(format!("Updated Gust.lock ({})", diff.summary()));
+    Ok(())
+}
+
+/// How to install, as `gust install` was asked.
+#[derive(Debug, Clone, Default)]
+pub struct InstallArgs {
+    /// Fail instead of changing Gust.lock
+    pub frozen: bool,
+    pub no_dev: bool,
+    /// Say why each package was installed
+    pub explain: bool,
+    /// Print phase timings in this format
+    pub timings: Option<ui::OutputFormat>,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub groups: Vec<String>,
+    /// Install what can be fetched instead of stopping at a failure
+    pub keep_going: bool,
+    pub reporter: Option<ReporterKind>,
+    pub prune: Option<PruneScope>,
+    pub path_mode: PathMode,
+    pub max_depth: Option<usize>,
+    pub strict_depth: bool,
+    /// `--override` values, `PACKAGE@VERSION`
+    pub overrides: Vec<String>,
+    /// `--constraint` values, `PACKAGE@REQ`
+    pub constraints: Vec<String>,
+    pub jobs: Option<usize>,
+    pub target_dir: Option<PathBuf>,
+}
+
 /// Install dependencies.
-pub async fn install(frozen: bool) -> Result<()> {
+pub async fn install(args: &InstallArgs) -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
+    let reporter = args.reporter;
+    let timings = args.timings;
+    let json_reporter = reporter == Some(ReporterKind::Json);
+
+    let options = InstallOptions {
+        frozen: args.frozen,
+        no_dev: args.no_dev,
+        explain: args.explain,
+        features: args.features.clone(),
+        no_default_features: args.no_default_features,
+        groups: args.groups.clone(),
+        keep_going: args.keep_going,
+        reporter,
+        prune: args.prune,
+        path_mode: args.path_mode,
+        max_depth: args.max_depth,
+        strict_depth: args.strict_depth,
+        resolution_flags: ResolutionSources::from_flags(&args.overrides, &args.constraints)
+            .into_diagnostic()?,
+        ..default_install_options(args.target_dir.as_deref(), args.jobs)
+    };
+
+    let mut installer = Installer::new(cwd.clone(), options)?;
+    if timings == Some(ui::OutputFormat::Json) && !json_reporter {
+        // Keep stdout to the timings JSON
+        let kind = reporter.unwrap_or_else(ReporterKind::detect);
+        installer = installer.with_reporter(kind.stderr_reporter());
+    }
+    let result = installer.install().await;
+
+    // Auto-generate Package.swift from Gust.toml, with the same features
+    // and groups; with --keep-going this covers what did get installed
+    if result.is_err() && !args.keep_going {
+        return result.map(|_| ());
+    }
+    let (manifest, manifest_type) = find_manifest(&cwd).into_diagnostic()?;
+    if manifest_type == ManifestType::GustToml {
+        let feature_set = FeatureSet::resolve(&manifest, &args.features, !args.no_default_features)
+            .and_then(|set| set.with_groups(&manifest, &args.groups))
+            .into_diagnostic()?;
+        write_project_package_swift(feature_set.apply(&manifest), &cwd)?;
+    }
+    let result = result?;
+
+    // Through the reporter, so `--reporter json` keeps stdout to JSON lines
+    let reporter = installer.reporter();
+    reporter.report(Event::Installed {
+        packages: result.installed,
+        links: result.links,
+    });
+    match timings {
+        Some(ui::OutputFormat::Json) if !json_reporter => {
+            let json = result.timings.to_json(SLOWEST_FETCHES);
+            println!("{}", serde_json::to_string_pretty(&json).into_diagnostic()?);
+        }
+        Some(_) => reporter.report(Event::Timings {
+            timings: &result.timings,
+            slowest: SLOWEST_FETCHES,
+        }),
+        None => {}
+    }
 
-    // Scale concurrency with CPU cores (optimized for Apple Silicon Pro/Max chips)
-    let concurrency = std::thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(8);
+    Ok(())
+}
+
+/// Install every package found under the current directory.
+///
+/// Unlike a workspace install there is no shared lockfile: each package is
+/// installed on its own and keeps its own `Gust.lock`, but all of them use
+/// one package cache, so a dependency they have in common is fetched once.
+/// Every package is attempted even after a failure; the command fails if
+/// any did.
+pub async fn install_recursive(
+    frozen: bool,
+    no_dev: bool,
+    no_default_features: bool,
+    keep_going: bool,
+    reporter: Option<ReporterKind>,
+    jobs: Option<usize>,
+    target_dir: Option<&Path>,
+) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let roots = find_package_roots(&cwd, target_dir);
+    if roots.is_empty() {
+        return Err(miette::miette!(
+            "No Gust.toml or Package.swift found under {}",
+            cwd.display()
+        ));
+    }
 
     let options = InstallOptions {
         frozen,
-        concurrency,
+        no_dev,
+        no_default_features,
+        keep_going,
+        reporter,
+        ..default_install_options(target_dir, jobs)
     };
+    let cache = GlobalCache::open().into_diagnostic()?;
+    let results = install_roots(&roots, &options, &cache).await;
+    report_recursive_install(&cwd, &results)
+}
 
-    let installer = Installer::new(cwd.clone(), options)?;
-    let result = installer.install().await?;
+/// Directories under `dir` (including `dir` itself) that hold a `Gust.toml`
+/// or `Package.swift`, in path order.
+///
+/// Hidden directories are skipped, which covers `.build` and the
+/// dependency checkouts inside it, as is a custom `target_dir`.
+fn find_package_roots(dir: &Path, target_dir: Option<&Path>) -> Vec<PathBuf> {
+    let skip = target_dir.map(|t| gust_build::target_dir(dir, Some(t)));
+    let mut roots = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        if current.join("Gust.toml").exists() || current.join("Package.swift").exists() {
+            roots.push(current.clone());
+        }
+        let Ok(entries) = fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden || skip.as_ref() == Some(&path) {
+                continue;
+            }
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pending.push(path);
+            }
+        }
+    }
+    roots.sort();
+    roots
+}
 
-    // Auto-generate Package.swift from Gust.toml
-    let (manifest, manifest_type) = find_manifest(&cwd).into_diagnostic()?;
+/// Install each of `roots` with the shared `cache`.
+async fn install_roots(
+    roots: &[PathBuf],
+    options: &InstallOptions,
+    cache: &GlobalCache,
+) -> Vec<(PathBuf, Result<InstallResult>)> {
+    let mut results = Vec::new();
+    for root in roots {
+        println!(
+            "\n{} Installing {}",
+            style("→").blue().bold(),
+            pkg(root.display())
+        );
+        let installer = Installer::with_cache(root.clone(), options.clone(), cache.clone());
+        let result = match installer.install().await {
+            Ok(result) => generate_package_swift(root, options.no_default_features).map(|_| result),
+            Err(e) => Err(e),
+        };
+        results.push((root.clone(), result));
+    }
+    results
+}
+
+/// Regenerate `Package.swift` for a Gust.toml project after an install.
+fn generate_package_swift(dir: &Path, no_default_features: bool) -> Result<()> {
+    let (manifest, manifest_type) = find_manifest(dir).into_diagnostic()?;
     if manifest_type == ManifestType::GustToml {
-        write_package_swift(&manifest, &cwd).into_diagnostic()?;
+        let feature_set =
+            FeatureSet::resolve(&manifest, &[], !no_default_features).into_diagnostic()?;
+        write_project_package_swift(feature_set.apply(&manifest), dir)?;
     }
+    Ok(())
+}
 
+/// Print per-package results of a recursive install and fail if any
+/// package failed.
+fn report_recursive_install(
+    base: &Path,
+    results: &[(PathBuf, Result<InstallResult>)],
+) -> Result<()> {
+    println!();
     println!(
-        "\n{} Installed {} packages",
-        style("✓").green().bold(),
-        style(result.installed).cyan()
+        "{:<40} {:<10} {:<8} {}",
+        style("Package").bold(),
+        style("Installed").bold(),
+        style("Fetched").bold(),
+        style("Result").bold()
     );
+    separator(70);
+
+    let mut failed = Vec::new();
+    for (root, result) in results {
+        let name = match root.strip_prefix(base) {
+            Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+            Ok(rel) => rel.display().to_string(),
+            Err(_) => root.display().to_string(),
+        };
+        match result {
+            Ok(result) => println!(
+                "{:<40} {:<10} {:<8} {}",
+                pkg(&name),
+                result.installed,
+                result.timings.fetches.len(),
+                green("ok")
+            ),
+            Err(e) => {
+                println!(
+                    "{:<40} {:<10} {:<8} {} {}",
+                    pkg(&name),
+                    "-",
+                    "-",
+                    style("FAILED").red().bold(),
+                    dim(e)
+                );
+                failed.push(name);
+            }
+        }
+    }
+    println!();
 
+    if !failed.is_empty() {
+        return Err(miette::miette!(
+            "Install failed in {} of {} packages: {}",
+            failed.len(),
+            results.len(),
+            failed.join(", ")
+        ));
+    }
+    ui::success(format!("Installed {} packages", results.len()));
     Ok(())
 }
 
+/// How many of the slowest fetches `install --timings` lists.
+const SLOWEST_FETCHES: usize = 5;
+
 /// Update dependencies.
-pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
+pub async fn update(
+    package: Option<&str>,
+    breaking: bool,
+    precise: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
+    let Some(precise) = precise else {
+        return update_project(&cwd, package, breaking, dry_run).await;
+    };
+
+    let (name, version) = match (package, precise.split_once('@')) {
+        (None, Some((name, version))) => (name, version),
+        (Some(name), None) => (name, precise),
+        (Some(_), Some(_)) => {
+            return Err(miette::miette!(
+            "Give the package either as an argument or as --precise <package>@<version>, not both"
+        ))
+        }
+        (None, None) => {
+            return Err(miette::miette!(
+                help = "gust update <package> --precise <version>",
+                "--precise needs a package"
+            ))
+        }
+    };
+    let version = Version::parse(version.trim_start_matches('v'))
+        .map_err(|e| miette::miette!("Invalid version '{}': {}", version, e))?;
+    let cache = GlobalCache::open().into_diagnostic()?;
+    update_precise(&cwd, &cache, name, &version, dry_run).await
+}
+
+/// Update dependencies of the project in `cwd`.
+///
+/// With `dry_run`, only prints the planned updates; the manifest, lockfile
+/// and git cache are left untouched.
+async fn update_project(
+    cwd: &Path,
+    package: Option<&str>,
+    breaking: bool,
+    dry_run: bool,
+) -> Result<()> {
     let manifest_path = cwd.join("Gust.toml");
     let lockfile_path = cwd.join("Gust.lock");
 
@@ -671,7 +1661,7 @@ pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
     }
 
     let lockfile = gust_lockfile::Lockfile::load(&lockfile_path).into_diagnostic()?;
-    let mut manifest_content = fs::read_to_string(&manifest_path).into_diagnostic()?;
+    let mut editor = ManifestEditor::open(&manifest_path).into_diagnostic()?;
 
     // Filter packages to update
     let packages_to_check: Vec<_> = lockfile
@@ -694,9 +1684,19 @@ pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
     ));
 
     // Check for updates using shared helper
-    let all_updates = check_all_for_updates(&packages_to_check).await;
+    let branches = find_manifest(cwd)
+        .map(|(manifest, _)| tracked_branches(&manifest))
+        .unwrap_or_default();
+    let report = check_all_for_updates(&packages_to_check, &branches).await;
+    let all_updates = report.outdated;
+    // Branch-tracked dependencies are refetched instead of retagged
+    let moved: Vec<BranchTracked> = report
+        .branches
+        .into_iter()
+        .filter(BranchTracked::has_new_commit)
+        .collect();
 
-    if all_updates.is_empty() {
+    if all_updates.is_empty() && moved.is_empty() {
         ui::success("All dependencies are up to date");
         return Ok(());
     }
@@ -704,7 +1704,7 @@ pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
     // Filter by breaking changes
     let updates = filter_breaking(all_updates, breaking);
 
-    if updates.is_empty() {
+    if updates.is_empty() && moved.is_empty() {
         ui::success("No non-breaking updates available");
         println!(
             "  Run {} to include breaking changes",
@@ -729,16 +1729,42 @@ pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
             dim(&u.current),
             green(&u.latest_tag)
         );
-        update_manifest_tag(&mut manifest_content, &u.name, &u.latest_tag);
+        editor.set_dependency_tag(&u.name, &u.latest_tag);
+    }
+    for b in &moved {
+        println!(
+            "{:<30} {:<15} {}",
+            pkg(&b.name),
+            dim(b.current.as_deref().map(short_rev).unwrap_or("-")),
+            green(format!(
+                "{} ({})",
+                b.latest.as_deref().map(short_rev).unwrap_or("-"),
+                b.branch_name()
+            ))
+        );
+    }
+    let count = updates.len() + moved.len();
+
+    if dry_run {
+        println!();
+        ui::info(format!(
+            "Would update {} package(s) (dry run, nothing written)",
+            count
+        ));
+        return Ok(());
     }
 
     // Write updated manifest
-    fs::write(&manifest_path, &manifest_content).into_diagnostic()?;
+    editor.save(&manifest_path).into_diagnostic()?;
 
     // Clear the cache for updated packages so they get re-fetched
     let cache = GlobalCache::open().into_diagnostic()?;
-    for u in &updates {
-        let cache_path = cache.git_dir().join(&u.name);
+    let names = updates
+        .iter()
+        .map(|u| &u.name)
+        .chain(moved.iter().map(|b| &b.name));
+    for name in names {
+        let cache_path = cache.git_dir().join(name);
         if cache_path.exists() {
             let _ = fs::remove_dir_all(&cache_path);
         }
@@ -748,7 +1774,7 @@ pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
     let _ = fs::remove_file(&lockfile_path);
 
     println!();
-    ui::success(format!("Updated {} package(s)", updates.len()));
+    ui::success(format!("Updated {} package(s)", count));
     ui::hint(format!(
         "Run {} to install the updates",
         pkg("gust install")
@@ -757,53 +1783,434 @@ pub async fn update(package: Option<&str>, breaking: bool) -> Result<()> {
     Ok(())
 }
 
-/// Update a tag in the manifest content for a given package.
-fn update_manifest_tag(content: &mut String, name: &str, new_tag: &str) {
-    let patterns = [
-        format!("{} = {{ git =", name),
-        format!("{} = {{git =", name),
-    ];
-
-    for pattern in &patterns {
-        if let Some(start_idx) = content.find(pattern) {
-            let line_start = content[..start_idx].rfind('\n').map_or(0, |i| i + 1);
-            let line_end = content[start_idx..]
-                .find('\n')
-                .map_or(content.len(), |i| start_idx + i);
-            let line = &content[line_start..line_end];
-
-            if let Some(tag_start) = line.find("tag = \"") {
-                let tag_value_start = tag_start + 7;
-                if let Some(tag_end) = line[tag_value_start..].find('"') {
-                    let old_line = line.to_string();
-                    let new_line = format!(
-                        "{}{}{}",
-                        &line[..tag_value_start],
-                        new_tag,
-                        &line[tag_value_start + tag_end..]
-                    );
-                    *content = content.replace(&old_line, &new_line);
-                }
+/// Move `name` to exactly `version` in the project in `cwd`.
+///
+/// The version must exist upstream and satisfy what other locked packages
+/// require. The manifest is updated if its constraint doesn't admit the
+/// version, the lockfile entry is rewritten, and the package and the
+/// dependencies only it pulls in are dropped from the cache and lockfile so
+/// the next install resolves them again. Everything else stays locked.
+async fn update_precise(
+    cwd: &Path,
+    cache: &GlobalCache,
+    name: &str,
+    version: &Version,
+    dry_run: bool,
+) -> Result<()> {
+    let manifest_path = cwd.join("Gust.toml");
+    let lockfile_path = cwd.join("Gust.lock");
+
+    if !manifest_path.exists() {
+        return Err(miette::miette!(
+            "No Gust.toml found. Run 'gust init' first."
+        ));
+    }
+    if !lockfile_path.exists() {
+        return Err(miette::miette!(
+            "No Gust.lock found. Run 'gust install' first."
+        ));
+    }
+
+    let mut lockfile = gust_lockfile::Lockfile::load(&lockfile_path).into_diagnostic()?;
+    let (manifest, _) = find_manifest(cwd).into_diagnostic()?;
+    let Some(locked) = lockfile.get(name).cloned() else {
+        return Err(miette::miette!("Package '{}' not found in lockfile", name));
+    };
+    let Some(direct) = manifest
+        .dependencies
+        .get(name)
+        .or_else(|| manifest.dev_dependencies.get(name))
+    else {
+        return Err(miette::miette!(
+            help = format!("add it with `gust add {}` to choose its version", name),
+            "'{}' is not a direct dependency",
+            name
+        ));
+    };
+    if let Some(pin) = direct.branch.as_ref().or(direct.revision.as_ref()) {
+        return Err(miette::miette!(
+            "'{}' is pinned to {} in Gust.toml; remove the pin to select a version",
+            name,
+            pin
+        ));
+    }
+
+    // The version must exist
+    let tag = match locked.source {
+        gust_types::DependencySource::Git => {
+            let url = locked.git.as_deref().unwrap_or_default();
+            let tags = gust_fetch::list_remote_tags(url).await.into_diagnostic()?;
+            let Some(tag) = tags.iter().find(|t| t.version.as_ref() == Some(version)) else {
+                let available: Vec<&str> = tags
+                    .iter()
+                    .filter(|t| t.version.is_some())
+                    .take(5)
+                    .map(|t| t.name.as_str())
+                    .collect();
+                return Err(miette::miette!(
+                    help = format!("available versions include: {}", available.join(", ")),
+                    "{} has no version {}",
+                    name,
+                    version
+                ));
+            };
+            Some(tag.clone())
+        }
+        gust_types::DependencySource::Registry => {
+            let id = gust_registry::registry_identity(name).into_diagnostic()?;
+            gust_registry::RegistryClient::from_env()
+                .get_version(&id.scope, &id.name, &version.to_string())
+                .await
+                .into_diagnostic()?;
+            None
+        }
+        gust_types::DependencySource::Path => {
+            return Err(miette::miette!(
+                "'{}' is a path dependency and has no versions",
+                name
+            ));
+        }
+    };
+
+    // Other packages' constraints must admit it
+    let conflicts = precise_conflicts(cache, &lockfile, name, version);
+    if !conflicts.is_empty() {
+        return Err(miette::miette!(
+            "Cannot use {} {}: {}",
+            name,
+            version,
+            conflicts.join("; ")
+        ));
+    }
+
+    let subtree = exclusive_subtree(&manifest, &lockfile, name);
+
+    println!(
+        "{:<30} {:<15} {}",
+        pkg(name),
+        dim(locked.version.to_string()),
+        green(version.to_string())
+    );
+    if dry_run {
+        println!();
+        ui::info("Would update 1 package(s) (dry run, nothing written)");
+        return Ok(());
+    }
+
+    // Widen the manifest constraint only if it excludes the version
+    let mut editor = ManifestEditor::open(&manifest_path).into_diagnostic()?;
+    if let Some(tag) = &tag {
+        if direct.tag.is_some() || direct.version.is_none() {
+            editor.set_dependency_tag(name, &tag.name);
+        }
+    }
+    if direct.version.is_some() && !direct.accepts(version) {
+        editor.set_dependency_version(name, &version.to_string());
+    }
+    editor.save(&manifest_path).into_diagnostic()?;
+
+    lockfile.packages.retain(|p| !subtree.contains(&p.name));
+    if let Some(entry) = lockfile.packages.iter_mut().find(|p| p.name == name) {
+        entry.version = version.clone();
+        entry.checksum = None;
+        if let Some(tag) = &tag {
+            entry.revision = Some(tag.sha.clone());
+        }
+    }
+    lockfile.save(&lockfile_path).into_diagnostic()?;
+
+    for stale in subtree.iter().map(String::as_str).chain([name]) {
+        let cache_path = cache.git_dir().join(sanitize_name(stale));
+        if cache_path.exists() {
+            let _ = fs::remove_dir_all(&cache_path);
+        }
+    }
+
+    println!();
+    ui::success(format!("Updated {} to {}", name, version));
+    ui::hint(format!("Run {} to install the update", pkg("gust install")));
+
+    Ok(())
+}
+
+/// Requirements on `name` from other locked packages that `version`
+/// doesn't satisfy, read from their cached manifests.
+fn precise_conflicts(
+    cache: &GlobalCache,
+    lockfile: &gust_lockfile::Lockfile,
+    name: &str,
+    version: &Version,
+) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for parent in &lockfile.packages {
+        if parent.name == name || !parent.dependencies.iter().any(|d| d == name) {
+            continue;
+        }
+        let dir = cache.git_dir().join(sanitize_name(&parent.name));
+        let Ok((parent_manifest, _)) = find_manifest(&dir) else {
+            tracing::debug!("No cached manifest for {}", parent.name);
+            continue;
+        };
+        let Some(dep) = parent_manifest.dependencies.get(name) else {
+            continue;
+        };
+        if let Some(req) = dep.requirement().filter(|_| !dep.accepts(version)) {
+            conflicts.push(format!("{} requires {} {}", parent.name, name, req));
+        } else if let Some(tag) = &dep.tag {
+            let pinned = gust_fetch::parse_tag_version(tag);
+            if pinned.as_ref() != Some(version) {
+                conflicts.push(format!("{} pins {} to {}", parent.name, name, tag));
             }
         }
     }
+    conflicts
+}
+
+/// Locked packages reachable only through `name`, which change along with
+/// it.
+fn exclusive_subtree(
+    manifest: &Manifest,
+    lockfile: &gust_lockfile::Lockfile,
+    name: &str,
+) -> std::collections::HashSet<String> {
+    let reachable = |roots: Vec<&str>, skip: Option<&str>| {
+        let mut seen = std::collections::HashSet::new();
+        let mut stack = roots;
+        while let Some(current) = stack.pop() {
+            if Some(current) == skip || !seen.insert(current.to_string()) {
+                continue;
+            }
+            if let Some(locked) = lockfile.get(current) {
+                stack.extend(locked.dependencies.iter().map(String::as_str));
+            }
+        }
+        seen
+    };
+
+    let roots: Vec<&str> = manifest
+        .dependencies
+        .keys()
+        .chain(manifest.dev_dependencies.keys())
+        .map(String::as_str)
+        .collect();
+    let elsewhere = reachable(roots, Some(name));
+    let mut subtree = reachable(vec![name], None);
+    subtree.remove(name);
+    subtree.retain(|p| !elsewhere.contains(p));
+    subtree
 }
 
 /// Show dependency tree.
-pub async fn tree(_depth: Option<usize>, _duplicates: bool) -> Result<()> {
+///
+/// With `target`, only the packages that target's dependencies reach are
+/// shown. Transitive dependencies come from Gust.lock, when there is one.
+pub async fn tree(depth: Option<usize>, _duplicates: bool, target: Option<&str>) -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
     let (manifest, _) = find_manifest(&cwd).into_diagnostic()?;
+    let lockfile_path = cwd.join("Gust.lock");
+    let lockfile = if lockfile_path.exists() {
+        Some(gust_lockfile::Lockfile::load(&lockfile_path).into_diagnostic()?)
+    } else {
+        None
+    };
+
+    let roots = match target {
+        Some(name) => target_packages(&manifest, name)?,
+        None => manifest.dependencies.keys().cloned().collect(),
+    };
+    match target {
+        Some(name) => println!(
+            "{} v{} {}",
+            manifest.package.name,
+            manifest.package.version,
+            dim(format!("(target {})", name))
+        ),
+        None => println!("{} v{}", manifest.package.name, manifest.package.version),
+    }
+    for (prefix, name, version) in tree_lines(&manifest, lockfile.as_ref(), &roots, depth) {
+        println!("{}{} {}", prefix, name, dim(version));
+    }
 
-    println!("{} v{}", manifest.package.name, manifest.package.version);
+    Ok(())
+}
 
-    for (name, dep) in &manifest.dependencies {
-        let version = dep
-            .version
-            .as_ref()
-            .map(|v| v.to_string())
-            .unwrap_or_else(|| "git".to_string());
-        println!("├── {} {}", name, style(version).dim());
+/// Packages `target` depends on, directly or through other targets of the
+/// package.
+///
+/// A target dependency names another target, a package, or a product,
+/// which is matched to the package it's inferred to come from. As in the
+/// generated Package.swift, a target without dependencies uses every
+/// package (or, for tests, the main target).
+fn target_packages(manifest: &Manifest, target: &str) -> Result<BTreeSet<String>> {
+    let Some(start) = manifest.targets.iter().find(|t| t.name == target) else {
+        let names = manifest.targets.iter().map(|t| t.name.as_str());
+        return Err(match gust_diagnostics::closest_match(target, names) {
+            Some(suggestion) => miette::miette!(
+                help = format!("did you mean `{}`?", suggestion),
+                "{} is not a target",
+                target
+            ),
+            None => miette::miette!("{} is not a target", target),
+        });
+    };
+
+    let mut packages = BTreeSet::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![start];
+    while let Some(target) = pending.pop() {
+        if !visited.insert(target.name.as_str()) {
+            continue;
+        }
+        let deps: Vec<&str> = if !target.dependencies.is_empty() {
+            target.dependencies.iter().map(String::as_str).collect()
+        } else if target.target_type == TargetType::Test {
+            manifest
+                .targets
+                .iter()
+                .find(|t| matches!(t.target_type, TargetType::Library | TargetType::Executable))
+                .map(|t| vec![t.name.as_str()])
+                .unwrap_or_default()
+        } else {
+            manifest.dependencies.keys().map(String::as_str).collect()
+        };
+
+        for dep in deps {
+            if let Some(internal) = manifest.targets.iter().find(|t| t.name == dep) {
+                pending.push(internal);
+            } else if manifest.dependencies.contains_key(dep) {
+                packages.insert(dep.to_string());
+            } else if let Some((package, _)) = manifest
+                .dependencies
+                .iter()
+                .find(|(name, d)| dependency_products(name, d).iter().any(|p| p == dep))
+            {
+                packages.insert(package.clone());
+            } else {
+                tracing::debug!("{} depends on unknown {}", target.name, dep);
+            }
+        }
     }
+    Ok(packages)
+}
+
+/// Lines of the tree under `roots`, as (prefix, package, version), down to
+/// `depth` levels. A package that depends on one of its ancestors isn't
+/// expanded again.
+fn tree_lines(
+    manifest: &Manifest,
+    lockfile: Option<&gust_lockfile::Lockfile>,
+    roots: &BTreeSet<String>,
+    depth: Option<usize>,
+) -> Vec<(String, String, String)> {
+    let mut tree = TreeWalk {
+        manifest,
+        lockfile,
+        depth,
+        ancestors: Vec::new(),
+        lines: Vec::new(),
+    };
+    tree.walk(roots.iter().cloned().collect(), "", 1);
+    tree.lines
+}
+
+struct TreeWalk<'a> {
+    manifest: &'a Manifest,
+    lockfile: Option<&'a gust_lockfile::Lockfile>,
+    depth: Option<usize>,
+    /// Packages on the path to the one being listed
+    ancestors: Vec<String>,
+    lines: Vec<(String, String, String)>,
+}
+
+impl TreeWalk<'_> {
+    fn walk(&mut self, names: Vec<String>, indent: &str, level: usize) {
+        let count = names.len();
+        for (i, name) in names.into_iter().enumerate() {
+            let last = i + 1 == count;
+            let locked = self.lockfile.and_then(|l| l.get(&name));
+            let branch = if last { "└── " } else { "├── " };
+            self.lines.push((
+                format!("{}{}", indent, branch),
+                name.clone(),
+                self.version(&name),
+            ));
+
+            let Some(locked) = locked else {
+                continue;
+            };
+            if self.depth.is_some_and(|max| level >= max) || self.ancestors.contains(&name) {
+                continue;
+            }
+            let mut children = locked.dependencies.clone();
+            children.sort();
+            children.dedup();
+            self.ancestors.push(name);
+            let child_indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            self.walk(children, &child_indent, level + 1);
+            self.ancestors.pop();
+        }
+    }
+
+    /// The locked version of `name`, or what the manifest asks for.
+    fn version(&self, name: &str) -> String {
+        if let Some(locked) = self.lockfile.and_then(|l| l.get(name)) {
+            return locked.version.to_string();
+        }
+        let Some(dep) = self.manifest.dependencies.get(name) else {
+            return String::new();
+        };
+        match dep.source_kind() {
+            DependencySource::Registry => dep
+                .version
+                .as_ref()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            DependencySource::Git => "git".to_string(),
+            DependencySource::Path => "path".to_string(),
+        }
+    }
+}
+
+/// List every locked package, or export them as an SBOM.
+pub async fn deps(format: ui::DepsFormat) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let (manifest, _) = find_manifest(&cwd).into_diagnostic()?;
+    let lockfile_path = cwd.join("Gust.lock");
+
+    if !lockfile_path.exists() {
+        return Err(miette::miette!(
+            "No Gust.lock found. Run 'gust install' first."
+        ));
+    }
+    let lockfile = gust_lockfile::Lockfile::load(&lockfile_path).into_diagnostic()?;
+
+    let name = &manifest.package.name;
+    let version = &manifest.package.version;
+    let direct: Vec<String> = manifest
+        .dependencies
+        .keys()
+        .chain(manifest.dev_dependencies.keys())
+        .cloned()
+        .collect();
+    let now = std::time::SystemTime::now();
+    let json = match format {
+        ui::DepsFormat::Text => {
+            for package in lockfile.sorted_packages() {
+                println!(
+                    "{} {:<12} {:<9} {}",
+                    pkg(format!("{:<30}", package.name)),
+                    package.version.to_string(),
+                    package.source.to_string(),
+                    dim(package.location().unwrap_or_default())
+                );
+            }
+            return Ok(());
+        }
+        ui::DepsFormat::Json => lockfile.listing_json(),
+        ui::DepsFormat::Spdx => lockfile.to_spdx(name, version, &direct, now),
+        ui::DepsFormat::Cyclonedx => lockfile.to_cyclonedx(name, version, &direct, now),
+    };
+    println!("{}", serde_json::to_string_pretty(&json).into_diagnostic()?);
 
     Ok(())
 }
@@ -828,8 +2235,12 @@ pub async fn outdated() -> Result<()> {
         return Ok(());
     }
 
+    let branches = find_manifest(&cwd)
+        .map(|(manifest, _)| tracked_branches(&manifest))
+        .unwrap_or_default();
     let packages: Vec<_> = lockfile.packages.iter().collect();
-    let outdated_deps = check_all_for_updates(&packages).await;
+    let report = check_all_for_updates(&packages, &branches).await;
+    let outdated_deps = report.outdated;
 
     if outdated_deps.is_empty() {
         ui::success("All dependencies are up to date");
@@ -851,12 +2262,110 @@ pub async fn outdated() -> Result<()> {
                 green(&dep.latest)
             );
         }
-
-        println!();
-        ui::warn(format!("{} package(s) can be updated", outdated_deps.len()));
-        println!("  Run {} to update all", pkg("gust update"));
+
+        println!();
+        ui::warn(format!("{} package(s) can be updated", outdated_deps.len()));
+        println!("  Run {} to update all", pkg("gust update"));
+    }
+    print_branch_tracked(&report.branches);
+
+    Ok(())
+}
+
+/// List branch-tracked dependencies, which have no versions to compare.
+fn print_branch_tracked(branches: &[BranchTracked]) {
+    if branches.is_empty() {
+        return;
+    }
+    println!();
+    for b in branches {
+        let status = match &b.latest {
+            Some(latest) if b.has_new_commit() => {
+                green(format!("new commit {}", short_rev(latest))).to_string()
+            }
+            Some(_) => dim("up to date").to_string(),
+            None => dim("branch not found").to_string(),
+        };
+        println!(
+            "{} tracking {}, no version comparison ({})",
+            pkg(format!("{:<30}", b.name)),
+            b.branch_name(),
+            status
+        );
+    }
+}
+
+fn short_rev(rev: &str) -> &str {
+    &rev[..rev.len().min(7)]
+}
+
+/// Check cached checkouts against the checksums recorded in Gust.lock.
+pub async fn verify() -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let cache = GlobalCache::open().into_diagnostic()?;
+    verify_project(&cwd, &cache)
+}
+
+/// Check the checkouts in `cache` against the checksums in the Gust.lock of
+/// the project at `dir`, hashing each one.
+fn verify_project(dir: &Path, cache: &GlobalCache) -> Result<()> {
+    use gust_lockfile::VerificationStatus;
+
+    let lockfile_path = dir.join("Gust.lock");
+    if !lockfile_path.exists() {
+        return Err(miette::miette!(
+            "No Gust.lock found. Run 'gust install' first."
+        ));
+    }
+
+    let lockfile = gust_lockfile::Lockfile::load(&lockfile_path).into_diagnostic()?;
+    let results = lockfile
+        .verify_checksums(&cache.git_dir())
+        .into_diagnostic()?;
+
+    let mut mismatched = 0;
+    for result in &results {
+        match result.status {
+            VerificationStatus::Verified => {
+                println!("{} {}", style("✓").green(), pkg(&result.package));
+            }
+            VerificationStatus::Mismatch => {
+                mismatched += 1;
+                println!(
+                    "{} {} expected {}, got {}",
+                    style("✗").red(),
+                    pkg(&result.package),
+                    result.expected,
+                    result.actual.as_deref().unwrap_or("-")
+                );
+            }
+            VerificationStatus::Missing => {
+                println!(
+                    "{} {} {}",
+                    dim("-"),
+                    pkg(&result.package),
+                    dim("not cached")
+                );
+            }
+            VerificationStatus::Error => {
+                mismatched += 1;
+                println!(
+                    "{} {} could not be read",
+                    style("✗").red(),
+                    pkg(&result.package)
+                );
+            }
+        }
     }
 
+    if mismatched > 0 {
+        return Err(miette::miette!(
+            help = "Run `gust cache clean` and `gust install` to refetch them",
+            "{} package(s) don't match Gust.lock",
+            mismatched
+        ));
+    }
+    ui::success(format!("{} package(s) verified", results.len()));
     Ok(())
 }
 
@@ -878,12 +2387,32 @@ pub async fn cache_list() -> Result<()> {
 }
 
 /// Show cache statistics.
-pub async fn cache_stats() -> Result<()> {
+///
+/// With `json`, print a report per cache instead: location, entry count,
+/// total bytes, oldest and newest entry, and an age histogram. Caches that
+/// can't be opened are `null`.
+pub async fn cache_stats(json: bool) -> Result<()> {
+    let cache = GlobalCache::open().into_diagnostic()?;
+    if json {
+        let report = serde_json::json!({
+            "packages": gust_cache::CacheReport::scan(&cache.git_dir(), &|_| true)
+                .into_diagnostic()?,
+            "binary": gust_build::get_cache_report().ok(),
+            "manifests": gust_manifest::ManifestCache::open()
+                .and_then(|c| c.report())
+                .ok(),
+        });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).into_diagnostic()?
+        );
+        return Ok(());
+    }
+
     println!("{}", style("Cache Statistics").bold().underlined());
     println!();
 
     // Package cache
-    let cache = GlobalCache::open().into_diagnostic()?;
     println!("{}", style("Package Cache:").bold());
     println!("  Location: {}", cache.git_dir().display());
 
@@ -962,6 +2491,12 @@ pub async fn cache_clean(all: bool, binary_only: bool) -> Result<()> {
             fs::remove_dir_all(&git_dir).into_diagnostic()?;
             fs::create_dir_all(&git_dir).into_diagnostic()?;
         }
+        // Links to and copies of path dependencies; removing a link leaves
+        // its source alone
+        let paths_dir = cache.paths_dir();
+        if paths_dir.exists() {
+            fs::remove_dir_all(&paths_dir).into_diagnostic()?;
+        }
 
         // Clear binary cache
         if let Ok(count) = gust_build::clear_binary_cache() {
@@ -1023,6 +2558,39 @@ pub async fn cache_clean(all: bool, binary_only: bool) -> Result<()> {
     Ok(())
 }
 
+/// Carry packages from older cache stores over to the current one.
+pub async fn cache_migrate() -> Result<()> {
+    let root = GlobalCache::default_cache_dir().into_diagnostic()?;
+    let cache = GlobalCache::open_unmigrated(root).into_diagnostic()?;
+    let migration = cache.migrate().into_diagnostic()?;
+    if migration.is_empty() {
+        ui::success(format!(
+            "Cache store is up to date (v{})",
+            gust_cache::STORE_VERSION
+        ));
+        return Ok(());
+    }
+
+    let versions: Vec<String> = migration
+        .versions
+        .iter()
+        .map(|v| format!("v{}", v))
+        .collect();
+    ui::success(format!(
+        "Migrated {} packages from store {} to v{}",
+        migration.packages,
+        versions.join(", "),
+        gust_cache::STORE_VERSION
+    ));
+    if migration.dropped > 0 {
+        ui::info(format!(
+            "Dropped {} packages with missing files; they'll be fetched again when needed",
+            migration.dropped
+        ));
+    }
+    Ok(())
+}
+
 /// Print cache path.
 pub async fn cache_path() -> Result<()> {
     let cache = GlobalCache::open().into_diagnostic()?;
@@ -1032,6 +2600,61 @@ pub async fn cache_path() -> Result<()> {
     Ok(())
 }
 
+/// Print the effective value of a config key.
+pub async fn config_get(key: &str) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    match GustConfig::load(&cwd)?.get(key)? {
+        Some(value) => println!("{}", value),
+        None => return Err(miette::miette!("{} is not set", key)),
+    }
+    Ok(())
+}
+
+/// Set (or with `None`, unset) a config key in the user config, or in the
+/// project's `.gust/config.toml` with `project`.
+pub async fn config_set(key: &str, value: Option<&str>, project: bool) -> Result<()> {
+    let path = if project {
+        GustConfig::project_path(&env::current_dir().into_diagnostic()?)
+    } else {
+        GustConfig::user_path().ok_or_else(|| miette::miette!("No home directory"))?
+    };
+
+    let mut config = GustConfig::read(&path)?;
+    config.set(key, value)?;
+    config.write(&path)?;
+
+    match value {
+        Some(value) => ui::success(format!("Set {} = {} in {}", key, value, path.display())),
+        None => ui::success(format!("Unset {} in {}", key, path.display())),
+    }
+    Ok(())
+}
+
+/// List every config key with its effective value.
+pub async fn config_list() -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let config = GustConfig::load(&cwd)?;
+    for (key, description) in config::KEYS {
+        // Per-package keys list each package set
+        if let Some((table, _)) = config::package_key(key) {
+            let entries = config.table(table);
+            if entries.is_empty() {
+                println!("{} {}", pkg(format!("{:<14}", key)), dim(description));
+            }
+            for (package, req) in entries {
+                let key = format!("{}.{}", table, package);
+                println!("{} = {}", pkg(format!("{:<14}", key)), req);
+            }
+            continue;
+        }
+        match config.get(key)? {
+            Some(value) => println!("{} = {}", pkg(format!("{:<14}", key)), value),
+            None => println!("{} {}", pkg(format!("{:<14}", key)), dim(description)),
+        }
+    }
+    Ok(())
+}
+
 /// Migrate Package.swift to Gust.toml.
 pub async fn migrate() -> Result<()> {
     let cwd = env::current_dir().into_diagnostic()?;
@@ -1081,7 +2704,7 @@ pub async fn generate() -> Result<()> {
     );
 
     let (manifest, _) = find_manifest(&cwd).into_diagnostic()?;
-    write_package_swift(&manifest, &cwd).into_diagnostic()?;
+    write_default_package_swift(&manifest, &cwd)?;
 
     println!("{} Generated Package.swift", style("✓").green().bold());
 
@@ -1089,18 +2712,16 @@ pub async fn generate() -> Result<()> {
 }
 
 /// Show package info.
-pub async fn info(package: &str) -> Result<()> {
+///
+/// A bare name is looked up with `scope`, or else in whichever registry
+/// scope publishes a repository of that name.
+pub async fn info(package: &str, scope: Option<&str>) -> Result<()> {
     println!("{} Looking up {}...", style("→").blue().bold(), package);
 
-    // Parse scope.name format
-    let (scope, name) = if let Some(idx) = package.find('.') {
-        (&package[..idx], &package[idx + 1..])
-    } else {
-        // Try common scopes
-        ("apple", package)
-    };
-
-    let client = gust_registry::RegistryClient::new();
+    let client = gust_registry::RegistryClient::from_env();
+    let identity = package_identity(&client, package, scope).await?;
+    let (scope, name) = (identity.scope.as_str(), identity.name.as_str());
+    println!("{} Querying {}", style("→").blue().bold(), pkg(&identity));
 
     match client.list_versions(scope, name).await {
         Ok(versions) => {
@@ -1127,6 +2748,15 @@ pub async fn info(package: &str) -> Result<()> {
                     version_list.len() - 5
                 );
             }
+
+            // Point at the source for anyone who wants to edit it
+            if let Some(latest) = version_list.first() {
+                if let Ok(release) = client.get_version(scope, name, latest).await {
+                    if let Some(url) = release.repository_url() {
+                        println!("{} {}", style("Repository:").bold(), url);
+                    }
+                }
+            }
         }
         Err(e) => {
             println!(
@@ -1145,8 +2775,89 @@ pub async fn info(package: &str) -> Result<()> {
     Ok(())
 }
 
+/// The registry identity to query for `package`.
+///
+/// `scope.name` is used as given. A bare name takes `scope` if there is
+/// one; otherwise the package index is searched for repositories of that
+/// name, see [`published_identity`].
+async fn package_identity(
+    client: &gust_registry::RegistryClient,
+    package: &str,
+    scope: Option<&str>,
+) -> Result<gust_registry::PackageIdentifier> {
+    let identity = |scope: &str, name: &str| gust_registry::PackageIdentifier {
+        scope: scope.to_string(),
+        name: name.to_string(),
+    };
+    if let Some((scope, name)) = package.split_once('.') {
+        return Ok(identity(scope, name));
+    }
+    if let Some(scope) = scope {
+        return Ok(identity(scope, package));
+    }
+
+    let mut urls = crate::package_index::fetch_package_list(false)
+        .await
+        .unwrap_or_default();
+    urls.extend(
+        KNOWN_ORGS
+            .iter()
+            .map(|(name, org)| format!("https://github.com/{}/{}.git", org, name)),
+    );
+    published_identity(client, package, &urls).await
+}
+
+/// The identity publishing the repositories in `urls` named `package`,
+/// asking the registry for each. More than one is an error listing them.
+async fn published_identity(
+    client: &gust_registry::RegistryClient,
+    package: &str,
+    urls: &[String],
+) -> Result<gust_registry::PackageIdentifier> {
+    let mut candidates = std::collections::BTreeSet::new();
+    let repositories = crate::package_index::rank_packages(urls, package);
+    for repo in repositories
+        .iter()
+        .filter(|r| r.name.eq_ignore_ascii_case(package))
+    {
+        for id in client.lookup_by_url(&repo.url).await.into_diagnostic()? {
+            if id.name.eq_ignore_ascii_case(package) {
+                candidates.insert((id.scope, id.name));
+            }
+        }
+    }
+
+    let candidates: Vec<(String, String)> = candidates.into_iter().collect();
+    match candidates.as_slice() {
+        [(scope, name)] => Ok(gust_registry::PackageIdentifier {
+            scope: scope.clone(),
+            name: name.clone(),
+        }),
+        [] => Err(miette::miette!(
+            help = "Qualify the name as scope.name, or pass --scope",
+            "No registry package named {} was found",
+            package
+        )),
+        _ => {
+            let names: Vec<String> = candidates
+                .iter()
+                .map(|(scope, name)| format!("{}.{}", scope, name))
+                .collect();
+            Err(miette::miette!(
+                help = format!(
+                    "Qualify the name, e.g. `gust info {}`, or pass --scope",
+                    names[0]
+                ),
+                "{} is published under several scopes: {}",
+                package,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
 /// Search for packages.
-pub async fn search(query: &str, limit: usize) -> Result<()> {
+pub async fn search(query: &str, limit: usize, refresh: bool) -> Result<()> {
     use crate::package_index;
 
     println!(
@@ -1155,15 +2866,12 @@ pub async fn search(query: &str, limit: usize) -> Result<()> {
         style(query).cyan()
     );
 
-    // Try to get package count for context
-    let count_info = match package_index::package_count().await {
-        Ok(count) => format!(" ({} packages indexed)", count),
-        Err(_) => String::new(),
-    };
-
-    // Search in Swift Package Index
-    let matches = match package_index::search_packages(query, limit * 2).await {
-        Ok(m) => m,
+    // Search in Swift Package Index, fetching it only once
+    let (matches, indexed) = match package_index::fetch_package_list(refresh).await {
+        Ok(packages) => (
+            package_index::rank_packages(&packages, query),
+            Some(packages.len()),
+        ),
         Err(e) => {
             // Fall back to known packages if index fetch fails
             println!(
@@ -1171,18 +2879,13 @@ pub async fn search(query: &str, limit: usize) -> Result<()> {
                 style("!").yellow(),
                 e
             );
-            println!("{} Searching local cache...\n", style("→").blue().bold());
+            println!("{} Searching known packages...\n", style("→").blue().bold());
 
-            let query_lower = query.to_lowercase();
-            let local_matches: Vec<_> = KNOWN_ORGS
+            let known: Vec<String> = KNOWN_ORGS
                 .iter()
-                .filter(|(name, _)| name.to_lowercase().contains(&query_lower))
-                .map(|(name, org)| crate::package_index::IndexedPackage {
-                    name: name.to_string(),
-                    owner: org.to_string(),
-                    url: format!("https://github.com/{}/{}.git", org, name),
-                })
+                .map(|(name, org)| format!("https://github.com/{}/{}.git", org, name))
                 .collect();
+            let local_matches = package_index::rank_packages(&known, query);
 
             if local_matches.is_empty() {
                 println!(
@@ -1192,10 +2895,15 @@ pub async fn search(query: &str, limit: usize) -> Result<()> {
                 );
                 return Ok(());
             }
-            local_matches
+            (local_matches, None)
         }
     };
 
+    let count_info = match indexed {
+        Some(count) => format!(" ({} packages indexed)", count),
+        None => String::new(),
+    };
+
     if matches.is_empty() {
         println!(
             "\n{} No packages found matching '{}'{}",
@@ -1393,7 +3101,7 @@ pub async fn xcode_generate(open: bool) -> Result<()> {
             "{} Generating Package.swift from Gust.toml",
             style("→").blue().bold()
         );
-        write_package_swift(&manifest, &cwd).into_diagnostic()?;
+        write_default_package_swift(&manifest, &cwd)?;
     }
 
     if !package_path.exists() {
@@ -1433,6 +3141,9 @@ pub async fn xcode_generate(open: bool) -> Result<()> {
     Ok(())
 }
 
+/// How long doctor waits on each network check.
+const DOCTOR_NETWORK_TIMEOUT: Duration = Duration::from_secs(3);
+
 /// Check environment and diagnose issues.
 pub async fn doctor() -> Result<()> {
     println!("{}", style("Gust Doctor").bold().underlined());
@@ -1502,9 +3213,50 @@ pub async fn doctor() -> Result<()> {
         }
     }
 
+    // Check registry reachability
+    let registry = gust_registry::RegistryClient::from_env();
+    print!("{} Registry... ", style("Checking").dim());
+    let auth = if registry.has_auth() {
+        "auth configured"
+    } else {
+        "no auth"
+    };
+    match registry.health(DOCTOR_NETWORK_TIMEOUT).await {
+        Ok(health) if health.status < 500 => {
+            println!(
+                "{} {} (HTTP {}, {}ms, {})",
+                style("✓").green(),
+                registry.base_url(),
+                health.status,
+                health.latency.as_millis(),
+                auth
+            );
+        }
+        Ok(health) => {
+            println!(
+                "{} {} returned HTTP {} ({})",
+                style("✗").red(),
+                registry.base_url(),
+                health.status,
+                auth
+            );
+            issues += 1;
+        }
+        Err(e) => {
+            println!(
+                "{} {} unreachable: {}",
+                style("✗").red(),
+                registry.base_url(),
+                e
+            );
+            issues += 1;
+        }
+    }
+
     // Check current project
     print!("{} Current project... ", style("Checking").dim());
     let cwd = env::current_dir().into_diagnostic()?;
+    let mut project = None;
     if cwd.join("Gust.toml").exists() {
         let (manifest, _) = find_manifest(&cwd).into_diagnostic()?;
         println!(
@@ -1513,6 +3265,34 @@ pub async fn doctor() -> Result<()> {
             manifest.package.name,
             manifest.package.version
         );
+        project = Some(manifest);
+
+        // Gust.toml wins, so a hand-written Package.swift next to it is
+        // silently ignored
+        let package_swift = cwd.join("Package.swift");
+        if package_swift.exists() {
+            print!("{} Package.swift... ", style("Checking").dim());
+            match fs::read_to_string(&package_swift) {
+                Ok(content) if is_generated_package_swift(&content) => {
+                    println!("{} generated from Gust.toml", style("✓").green());
+                }
+                Ok(_) => {
+                    println!(
+                        "{} hand-written, and ignored because Gust.toml takes precedence",
+                        style("✗").red()
+                    );
+                    println!(
+                        "    Delete Package.swift to keep Gust.toml, or delete Gust.toml and run {}",
+                        style("gust migrate").cyan()
+                    );
+                    issues += 1;
+                }
+                Err(e) => {
+                    println!("{} {}", style("✗").red(), e);
+                    issues += 1;
+                }
+            }
+        }
     } else if cwd.join("Package.swift").exists() {
         println!(
             "{} Package.swift found (run {} to convert)",
@@ -1523,6 +3303,68 @@ pub async fn doctor() -> Result<()> {
         println!("{} no package found", style("→").dim());
     }
 
+    // Check the lockfile still matches the manifest
+    if let Some(manifest) = &project {
+        let lock_path = cwd.join("Gust.lock");
+        print!("{} Lockfile... ", style("Checking").dim());
+        match gust_lockfile::Lockfile::load(&lock_path) {
+            Ok(lockfile) => {
+                let drift = gust_lockfile::manifest_lockfile_drift(manifest, &lockfile);
+                if drift.is_empty() {
+                    println!("{} in sync with Gust.toml", style("✓").green());
+                } else {
+                    println!(
+                        "{} out of date (run {})",
+                        style("✗").red(),
+                        style("gust install").cyan()
+                    );
+                    for message in drift.messages() {
+                        println!("    {}", message);
+                    }
+                    issues += 1;
+                }
+            }
+            Err(_) if !lock_path.exists() => {
+                println!("{} no Gust.lock yet", style("→").dim());
+            }
+            Err(e) => {
+                println!("{} {}", style("✗").red(), e);
+                issues += 1;
+            }
+        }
+    }
+
+    // Check the project's remote binary cache
+    if let Some(config) = project.and_then(|m| m.binary_cache) {
+        print!("{} Binary cache server... ", style("Checking").dim());
+        let client = gust_binary_cache::BinaryCacheClient::new(&config.url);
+        let start = Instant::now();
+        // Any answer for a fingerprint that can't exist proves reachability
+        match tokio::time::timeout(DOCTOR_NETWORK_TIMEOUT, client.exists(&"0".repeat(64))).await {
+            Ok(Ok(_)) => {
+                println!(
+                    "{} {} ({}ms)",
+                    style("✓").green(),
+                    config.url,
+                    start.elapsed().as_millis()
+                );
+            }
+            Ok(Err(e)) => {
+                println!("{} {} unreachable: {}", style("✗").red(), config.url, e);
+                issues += 1;
+            }
+            Err(_) => {
+                println!(
+                    "{} {} timed out after {}s",
+                    style("✗").red(),
+                    config.url,
+                    DOCTOR_NETWORK_TIMEOUT.as_secs()
+                );
+                issues += 1;
+            }
+        }
+    }
+
     println!();
     if issues == 0 {
         println!("{} All checks passed!", style("✓").green().bold());
@@ -1532,3 +3374,779 @@ pub async fn doctor() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gust_testkit::git;
+
+    /// A registry answering identifier lookups for `repos`, given as
+    /// `(owner/name in the URL, published scope)`. Other requests get a 404.
+    async fn identifiers_registry(repos: &[(&str, &str)]) -> gust_registry::RegistryClient {
+        let repos: Vec<(String, String)> = repos
+            .iter()
+            .map(|(repo, scope)| (repo.replace('/', "%2F"), scope.to_string()))
+            .collect();
+        let server = gust_testkit::MockServer::start(move |request| {
+            match repos
+                .iter()
+                .find(|(repo, _)| request.path.contains(repo.as_str()))
+            {
+                Some((repo, scope)) => {
+                    let name = repo.rsplit("%2F").next().unwrap();
+                    gust_testkit::Response::json(format!(
+                        r#"{{"identifiers": [{{"scope": "{}", "name": "{}"}}]}}"#,
+                        scope, name
+                    ))
+                }
+                None => gust_testkit::Response::new(404),
+            }
+        })
+        .await;
+        gust_registry::RegistryClient::with_url(server.url())
+    }
+
+    #[tokio::test]
+    async fn test_info_bare_name_with_several_scopes_is_ambiguous() {
+        let urls = vec![
+            "https://github.com/apple/swift-log.git".to_string(),
+            "https://github.com/someone/swift-log.git".to_string(),
+            "https://github.com/apple/swift-nio.git".to_string(),
+        ];
+        let client = identifiers_registry(&[
+            ("apple/swift-log", "apple"),
+            ("someone/swift-log", "someone"),
+        ])
+        .await;
+
+        let err = published_identity(&client, "swift-log", &urls)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "swift-log is published under several scopes: apple.swift-log, someone.swift-log"
+        );
+
+        // --scope settles it, and a name only one scope publishes needs none
+        let id = package_identity(&client, "swift-log", Some("someone"))
+            .await
+            .unwrap();
+        assert_eq!(id.to_string(), "someone.swift-log");
+        let client = identifiers_registry(&[("apple/swift-log", "apple")]).await;
+        let id = published_identity(&client, "swift-log", &urls)
+            .await
+            .unwrap();
+        assert_eq!(id.to_string(), "apple.swift-log");
+        assert!(published_identity(&client, "swift-nio", &urls)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_info_qualified_name_is_used_as_given() {
+        // Nothing listens here, so any lookup would fail
+        let client = gust_registry::RegistryClient::with_url("http://127.0.0.1:9");
+        let id = package_identity(&client, "vapor.swift-log", Some("apple"))
+            .await
+            .unwrap();
+        assert_eq!(id.to_string(), "vapor.swift-log");
+    }
+
+    #[test]
+    fn test_scaffold_uses_installed_tools_version() {
+        let toolchain = SwiftToolchain {
+            swift_path: PathBuf::from("/usr/bin/swift"),
+            version: "6.0.3".to_string(),
+            major_version: 6,
+            minor_version: 0,
+        };
+        let tools_version = scaffold_tools_version(Some(&toolchain));
+        assert_eq!(tools_version, "6.0");
+        let manifest = create_manifest("foo", TargetType::Library, &tools_version);
+        assert_eq!(manifest.package.swift_tools_version, "6.0");
+        let (_, content) = scaffold_manifest(&manifest, ManifestFormat::Toml);
+        assert!(content.contains("swift-tools-version = \"6.0\""));
+
+        assert_eq!(scaffold_tools_version(None), "5.9");
+    }
+
+    #[test]
+    fn test_new_with_swift_manifest_format() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("foo");
+        scaffold_package(&path, "foo", TargetType::Executable, ManifestFormat::Swift).unwrap();
+
+        assert!(!path.join("Gust.toml").exists());
+        assert!(path.join("Sources/foo/main.swift").exists());
+        let package_swift = fs::read_to_string(path.join("Package.swift")).unwrap();
+        let tools_version = scaffold_tools_version(SwiftToolchain::detect().ok().as_ref());
+        assert!(package_swift.starts_with(&format!("// swift-tools-version:{}\n", tools_version)));
+        assert!(!package_swift.contains("auto-generated"));
+        assert!(package_swift.contains(".executableTarget("));
+        assert!(!fs::read_to_string(path.join(".gitignore"))
+            .unwrap()
+            .contains("Package.swift"));
+
+        // Parsing it back needs a toolchain
+        if Command::new("swift").arg("--version").output().is_err() {
+            return;
+        }
+        let (manifest, manifest_type) = find_manifest(&path).unwrap();
+        assert_eq!(manifest_type, ManifestType::PackageSwift);
+        let expected = create_manifest("foo", TargetType::Executable, &tools_version);
+        assert_eq!(manifest.package.name, expected.package.name);
+        assert_eq!(manifest.package.swift_tools_version, tools_version);
+        let targets: Vec<_> = manifest
+            .targets
+            .iter()
+            .map(|t| (t.name.as_str(), t.target_type))
+            .collect();
+        assert_eq!(targets, vec![("foo", TargetType::Executable)]);
+    }
+
+    #[test]
+    fn test_tree_scoped_to_target() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(
+            tmp.path().join("Gust.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+swift-log = "1.5"
+swift-argument-parser = "1.3"
+
+[[target]]
+name = "Server"
+type = "executable"
+dependencies = ["Core"]
+
+[[target]]
+name = "Core"
+type = "library"
+dependencies = ["swift-log"]
+
+[[target]]
+name = "Cli"
+type = "executable"
+dependencies = ["ArgumentParser"]
+"#,
+        )
+        .unwrap();
+        let (manifest, _) = find_manifest(tmp.path()).unwrap();
+
+        let mut log =
+            gust_lockfile::LockedPackage::registry("swift-log", Version::new(1, 5, 4), "sha");
+        log.dependencies = vec!["swift-atomics".to_string()];
+        let lockfile = gust_lockfile::Lockfile {
+            packages: vec![
+                log,
+                gust_lockfile::LockedPackage::registry(
+                    "swift-atomics",
+                    Version::new(1, 2, 0),
+                    "sha",
+                ),
+                gust_lockfile::LockedPackage::registry(
+                    "swift-argument-parser",
+                    Version::new(1, 3, 0),
+                    "sha",
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let lines = |target: &str, depth| {
+            let roots = target_packages(&manifest, target).unwrap();
+            tree_lines(&manifest, Some(&lockfile), &roots, depth)
+                .into_iter()
+                .map(|(prefix, name, version)| format!("{}{} {}", prefix, name, version))
+                .collect::<Vec<_>>()
+        };
+        // Server reaches swift-log through Core, and nothing else
+        assert_eq!(
+            lines("Server", None),
+            vec!["└── swift-log 1.5.4", "    └── swift-atomics 1.2.0"]
+        );
+        assert_eq!(lines("Server", Some(1)), vec!["└── swift-log 1.5.4"]);
+        // Cli names a product, which comes from swift-argument-parser
+        assert_eq!(lines("Cli", None), vec!["└── swift-argument-parser 1.3.0"]);
+
+        let err = target_packages(&manifest, "Sever").unwrap_err();
+        assert!(format!("{:?}", err).contains("did you mean `Server`?"));
+    }
+
+    #[test]
+    fn test_tree_and_add_work_without_swift() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dep = tmp.path().join("dep");
+        fs::create_dir_all(&dep).unwrap();
+        fs::write(
+            dep.join("Gust.toml"),
+            "[package]\nname = \"dep\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+        let project = tmp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("Gust.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let empty_path = tmp.path().join("bin");
+        fs::create_dir_all(&empty_path).unwrap();
+
+        // Rerun this test binary for just the test below, from the project
+        // and with nothing on PATH
+        let output = Command::new(env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "commands::core::tests::tree_and_add_without_swift",
+                "--ignored",
+            ])
+            .env("PATH", &empty_path)
+            .env(gust_cache::CACHE_DIR_ENV, tmp.path().join("cache"))
+            .env("GUST_TEST_DEP", &dep)
+            .current_dir(&project)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success() && stdout.contains("1 passed"),
+            "{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(fs::read_to_string(project.join("Gust.toml"))
+            .unwrap()
+            .contains("[dependencies]\ndep = "));
+        assert!(project.join(".build/checkouts/dep").is_symlink());
+    }
+
+    #[tokio::test]
+    #[ignore = "run with swift unavailable by test_tree_and_add_work_without_swift"]
+    async fn tree_and_add_without_swift() {
+        let Some(dep) = env::var_os("GUST_TEST_DEP") else {
+            return;
+        };
+        assert!(Command::new("swift").arg("--version").output().is_err());
+
+        let dep = PathBuf::from(dep);
+        add(
+            "dep",
+            &AddArgs {
+                path: Some(dep),
+                install: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        tree(None, false, None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_dry_run_leaves_project_untouched() {
+        let tmp = tempfile::TempDir::new().unwrap();
+
+        // A local repository with a newer tag than the locked one
+        let repo = tmp.path().join("dep");
+        gust_testkit::tagged_repo(&repo, "1.0.0");
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "next"]);
+        git(&repo, &["tag", "1.1.0"]);
+        let url = format!("file://{}", repo.display());
+
+        let project = tmp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        let manifest = format!(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ndep = {{ git = \"{}\", tag = \"1.0.0\" }}\n",
+            url
+        );
+        fs::write(project.join("Gust.toml"), &manifest).unwrap();
+
+        let lockfile = gust_lockfile::Lockfile {
+            packages: vec![gust_lockfile::LockedPackage::git(
+                "dep",
+                Version::new(1, 0, 0),
+                &url,
+                "0000000",
+            )],
+            ..Default::default()
+        };
+        lockfile.save(&project.join("Gust.lock")).unwrap();
+        let locked = fs::read_to_string(project.join("Gust.lock")).unwrap();
+
+        update_project(&project, None, false, true).await.unwrap();
+
+        assert_eq!(
+            fs::read_to_string(project.join("Gust.toml")).unwrap(),
+            manifest
+        );
+        assert_eq!(
+            fs::read_to_string(project.join("Gust.lock")).unwrap(),
+            locked
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tagless_repo_reported_as_branch_tracked() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q", "-b", "main"]);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+        git(&repo, &["tag", "nightly"]);
+        let url = format!("file://{}", repo.display());
+        let head = git(&repo, &["rev-parse", "HEAD"]);
+
+        let locked =
+            gust_lockfile::LockedPackage::git("dep", Version::new(0, 0, 0), &url, "0000000");
+        let report = check_all_for_updates(&[&locked], &Default::default()).await;
+        assert!(report.outdated.is_empty());
+        assert_eq!(
+            report.branches,
+            vec![BranchTracked {
+                name: "dep".to_string(),
+                branch: None,
+                current: Some("0000000".to_string()),
+                latest: Some(head.clone()),
+            }]
+        );
+        assert!(report.branches[0].has_new_commit());
+
+        // A branch from the manifest is followed even if tags exist
+        git(&repo, &["tag", "1.0.0"]);
+        let branches = [("dep".to_string(), "main".to_string())].into();
+        let report = check_all_for_updates(&[&locked], &branches).await;
+        assert!(report.outdated.is_empty());
+        assert_eq!(report.branches[0].branch.as_deref(), Some("main"));
+        assert_eq!(report.branches[0].latest.as_deref(), Some(head.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_update_precise_upgrades_and_downgrades() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q"]);
+        for tag in ["1.0.0", "1.1.0", "1.2.0"] {
+            git(&repo, &["commit", "-q", "--allow-empty", "-m", tag]);
+            git(&repo, &["tag", tag]);
+        }
+        let url = format!("file://{}", repo.display());
+        let tag_sha = |tag: &str| git(&repo, &["rev-parse", tag]);
+
+        // `other` also depends on `dep`; `child` is only needed by `dep`
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let other_dir = cache.git_dir().join("other");
+        fs::create_dir_all(&other_dir).unwrap();
+        let write_other = |req: &str| {
+            fs::write(
+                other_dir.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"other\"\nversion = \"1.0.0\"\n\n[dependencies]\ndep = \"{}\"\n",
+                    req
+                ),
+            )
+            .unwrap();
+        };
+        write_other(">=1.0.0");
+        fs::create_dir_all(cache.git_dir().join("dep")).unwrap();
+        fs::create_dir_all(cache.git_dir().join("child")).unwrap();
+
+        let project = tmp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ndep = {{ git = \"{url}\", tag = \"1.1.0\" }}\nother = {{ git = \"{url}\", tag = \"1.0.0\" }}\n"
+            ),
+        )
+        .unwrap();
+        let mut dep =
+            gust_lockfile::LockedPackage::git("dep", Version::new(1, 1, 0), &url, tag_sha("1.1.0"));
+        dep.dependencies = vec!["child".to_string()];
+        let mut other =
+            gust_lockfile::LockedPackage::git("other", Version::new(1, 0, 0), &url, "0000000");
+        other.dependencies = vec!["dep".to_string()];
+        let child =
+            gust_lockfile::LockedPackage::git("child", Version::new(2, 0, 0), &url, "0000000");
+        gust_lockfile::Lockfile {
+            packages: vec![dep, other, child],
+            ..Default::default()
+        }
+        .save(&project.join("Gust.lock"))
+        .unwrap();
+
+        // Upgrade: manifest, lockfile and the package's subtree follow
+        update_precise(&project, &cache, "dep", &Version::new(1, 2, 0), false)
+            .await
+            .unwrap();
+        let manifest = fs::read_to_string(project.join("Gust.toml")).unwrap();
+        assert!(manifest.contains("tag = \"1.2.0\""));
+        let lockfile = gust_lockfile::Lockfile::load(&project.join("Gust.lock")).unwrap();
+        let locked = lockfile.get("dep").unwrap();
+        assert_eq!(locked.version, Version::new(1, 2, 0));
+        assert_eq!(locked.revision.as_deref(), Some(tag_sha("1.2.0").as_str()));
+        assert!(lockfile.get("child").is_none());
+        assert!(lockfile.get("other").is_some());
+        assert!(!cache.git_dir().join("dep").exists());
+        assert!(!cache.git_dir().join("child").exists());
+        assert!(other_dir.exists());
+
+        // Downgrade past another package's constraint fails and names it
+        write_other("^1.1");
+        let err = update_precise(&project, &cache, "dep", &Version::new(1, 0, 0), false)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("other requires dep ^1.1"),
+            "{}",
+            err
+        );
+
+        // Downgrade within every constraint succeeds
+        write_other(">=1.0.0");
+        update_precise(&project, &cache, "dep", &Version::new(1, 0, 0), false)
+            .await
+            .unwrap();
+        let manifest = fs::read_to_string(project.join("Gust.toml")).unwrap();
+        assert!(manifest.contains("tag = \"1.0.0\""));
+        let lockfile = gust_lockfile::Lockfile::load(&project.join("Gust.lock")).unwrap();
+        assert_eq!(lockfile.get("dep").unwrap().version, Version::new(1, 0, 0));
+
+        // Versions that don't exist are rejected
+        let err = update_precise(&project, &cache, "dep", &Version::new(1, 5, 0), false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has no version 1.5.0"), "{}", err);
+    }
+
+    #[test]
+    fn test_verify_checks_checkouts_against_lockfile() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let checkout = cache.git_dir().join("alpha");
+        fs::create_dir_all(&checkout).unwrap();
+        fs::write(checkout.join("Package.swift"), "// alpha\n").unwrap();
+        let checksum = gust_cache::directory_checksum(&checkout).unwrap();
+
+        let project = tmp.path().join("app");
+        write_project(&project);
+        assert!(verify_project(&project, &cache).is_err());
+
+        let mut alpha = gust_lockfile::LockedPackage::git(
+            "alpha",
+            gust_types::Version::new(1, 0, 0),
+            "https://example.com/alpha.git",
+            "0123456789abcdef0123456789abcdef01234567",
+        );
+        alpha.checksum = Some(format!("blake3:{}", checksum));
+        let mut lockfile = gust_lockfile::Lockfile::default();
+        lockfile.packages.push(alpha);
+        lockfile.save(&project.join("Gust.lock")).unwrap();
+        verify_project(&project, &cache).unwrap();
+
+        fs::write(checkout.join("Package.swift"), "// tampered\n").unwrap();
+        let err = verify_project(&project, &cache).unwrap_err();
+        assert_eq!(err.to_string(), "1 package(s) don't match Gust.lock");
+
+        // A checkout that isn't cached isn't a mismatch
+        fs::remove_dir_all(&checkout).unwrap();
+        verify_project(&project, &cache).unwrap();
+    }
+
+    fn write_project(dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(
+            dir.join("Gust.toml"),
+            "# app manifest\n[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_add_without_install_only_edits_manifest() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        write_project(&project);
+
+        let dep = Dependency::path("lib", tmp.path().join("lib"))
+            .with_features(vec!["json".to_string(), "tls".to_string()]);
+        let original = add_to_manifest(&project.join("Gust.toml"), &dep, false, None).unwrap();
+        assert!(original.starts_with("# app manifest"));
+
+        let edited = fs::read_to_string(project.join("Gust.toml")).unwrap();
+        assert!(edited.starts_with("# app manifest"));
+        assert!(edited.contains("lib = { path ="));
+        assert!(edited.contains("features = [\"json\", \"tls\"]"));
+        assert!(!project.join("Gust.lock").exists());
+        assert!(!project.join(".build").exists());
+
+        // Adding the same dependency twice is refused
+        assert!(add_to_manifest(&project.join("Gust.toml"), &dep, true, None).is_err());
+    }
+
+    #[test]
+    fn test_printed_manifest_applies_workspace_and_overrides() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(
+            tmp.path().join("Gust.toml"),
+            r#"[package]
+name = "root"
+version = "0.1.0"
+
+[workspace]
+members = ["core"]
+
+[workspace.dependencies]
+swift-log = { git = "https://github.com/apple/swift-log.git", tag = "1.5.0" }
+
+[overrides]
+swift-nio = "2.60.0"
+"#,
+        )
+        .unwrap();
+        let member = tmp.path().join("core");
+        fs::create_dir_all(&member).unwrap();
+        fs::write(
+            member.join("Gust.toml"),
+            r#"[package]
+name = "core"
+version = "0.1.0"
+
+[dependencies]
+swift-log = { workspace = true }
+"#,
+        )
+        .unwrap();
+
+        let json = render_manifest(&member, ui::ManifestOutput::Json, &[], false, &[]).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            manifest["dependencies"]["swift-log"]["git"],
+            "https://github.com/apple/swift-log.git"
+        );
+        assert_eq!(manifest["dependencies"]["swift-log"]["tag"], "1.5.0");
+        assert_eq!(manifest["overrides"]["swift-nio"], "2.60.0");
+
+        let toml = render_manifest(&member, ui::ManifestOutput::Toml, &[], false, &[]).unwrap();
+        assert!(toml.contains("swift-nio = \"2.60.0\""));
+        assert_eq!(
+            json,
+            render_manifest(&member, ui::ManifestOutput::Json, &[], false, &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_add_selects_latest_stable_or_prerelease() {
+        let versions: Vec<Version> = ["1.2.0", "1.3.0-beta.1", "1.2.5", "2.0.0-rc.1", "0.9.0"]
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        let select = |spec: Option<&str>, allow_prerelease| {
+            let requirement = spec.map(|s| add_requirement(s).unwrap());
+            select_version(&versions, requirement.as_ref(), allow_prerelease).map(|v| v.to_string())
+        };
+
+        // Stable releases only by default
+        assert_eq!(select(None, false).as_deref(), Some("1.2.5"));
+        assert_eq!(select(Some("1.0"), false).as_deref(), Some("1.2.5"));
+        assert_eq!(select(Some("0.9"), false).as_deref(), Some("0.9.0"));
+
+        // Prereleases count once allowed, within the requested range
+        assert_eq!(select(None, true).as_deref(), Some("2.0.0-rc.1"));
+        assert_eq!(select(Some("1.0"), true).as_deref(), Some("1.3.0-beta.1"));
+
+        // A full version is exact, and a tag name isn't a requirement
+        assert_eq!(select(Some("v1.2.0"), true).as_deref(), Some("1.2.0"));
+        assert!(add_requirement("main").is_none());
+
+        // A prerelease the spec names needs no flag
+        assert_eq!(
+            select(Some("1.3.0-beta.1"), false).as_deref(),
+            Some("1.3.0-beta.1")
+        );
+        assert_eq!(
+            select(Some(">=1.3.0-beta.1"), false).as_deref(),
+            Some("1.3.0-beta.1")
+        );
+
+        // Nothing matching is an error that points at the flag if it'd help
+        let refs: Vec<&Version> = versions.iter().collect();
+        assert_eq!(select(Some("2.0"), false), None);
+        let err = no_matching_version("dep", Some("2.0"), &refs, add_requirement("2.0").as_ref());
+        assert!(format!("{:?}", err).contains("--allow-prerelease"));
+        assert_eq!(select(Some("3.0"), true), None);
+    }
+
+    #[tokio::test]
+    async fn test_add_with_install_reverts_on_failure() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        write_project(&project);
+
+        let lib = tmp.path().join("lib");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(
+            lib.join("Gust.toml"),
+            "[package]\nname = \"lib\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+
+        let installer = Installer::with_cache(
+            project.clone(),
+            InstallOptions {
+                concurrency: 2,
+                ..Default::default()
+            },
+            GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+        );
+        let manifest_path = project.join("Gust.toml");
+
+        // Edit + install links the new dependency
+        let dep = Dependency::path("lib", &lib);
+        let original = add_to_manifest(&manifest_path, &dep, false, None).unwrap();
+        install_or_revert(&installer, &project, &original)
+            .await
+            .unwrap();
+        assert!(project.join(".build/checkouts/lib").is_symlink());
+        let installed = fs::read_to_string(&manifest_path).unwrap();
+        assert!(installed.contains("lib = { path ="));
+
+        // A dependency that can't be installed leaves the manifest as it was
+        let broken = Dependency::git(
+            "missing",
+            format!("file://{}", tmp.path().join("nope").display()),
+        );
+        let before = add_to_manifest(&manifest_path, &broken, false, None).unwrap();
+        assert!(install_or_revert(&installer, &project, &before)
+            .await
+            .is_err());
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), installed);
+    }
+
+    #[tokio::test]
+    async fn test_recursive_install_fetches_shared_dependency_once() {
+        let tmp = tempfile::TempDir::new().unwrap();
+
+        let repo = tmp.path().join("shared");
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(
+            repo.join("Gust.toml"),
+            "[package]\nname = \"shared\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+        gust_testkit::tagged_repo(&repo, "1.0.0");
+
+        // Two independent packages and a checkout that must not be picked up
+        let root = tmp.path().join("repo");
+        for name in ["api", "cli"] {
+            let dir = root.join("packages").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\nshared = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                    name,
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        }
+        let checkout = root.join("packages/api/.build/checkouts/old");
+        fs::create_dir_all(&checkout).unwrap();
+        fs::write(checkout.join("Package.swift"), "").unwrap();
+
+        let roots = find_package_roots(&root, None);
+        assert_eq!(
+            roots,
+            vec![root.join("packages/api"), root.join("packages/cli")]
+        );
+
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let options = InstallOptions {
+            concurrency: 2,
+            ..Default::default()
+        };
+        let results = install_roots(&roots, &options, &cache).await;
+
+        let fetches: usize = results
+            .iter()
+            .map(|(_, r)| r.as_ref().unwrap().timings.fetches.len())
+            .sum();
+        assert_eq!(fetches, 1);
+        for root in &roots {
+            assert!(root.join("Gust.lock").exists());
+            assert!(root.join("Package.swift").exists());
+        }
+        assert!(report_recursive_install(&root, &results).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_env_reaches_executable() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let status = run_command(
+            Path::new("/bin/sh"),
+            &[
+                "-c".to_string(),
+                "printf %s \"$GREETING\" > seen.txt".to_string(),
+            ],
+            &[("GREETING".to_string(), "hello".to_string())],
+            tmp.path(),
+        )
+        .status()
+        .await
+        .unwrap();
+        assert!(status.success());
+
+        // Written relative to the project root the command runs in
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("seen.txt")).unwrap(),
+            "hello"
+        );
+        assert!(env::var("GREETING").is_err());
+    }
+
+    #[test]
+    fn test_run_uses_the_built_product_path() {
+        let product = |name: &str, target_type, path: &str| BuildProduct {
+            name: name.to_string(),
+            target_type,
+            path: PathBuf::from(path),
+        };
+        let mut result = BuildResult {
+            products: vec![
+                product("Core", TargetType::Library, "build/debug/libCore.a"),
+                // Renamed in the manifest and built into a subdirectory
+                product("app", TargetType::Executable, "build/debug/tools/app-cli"),
+            ],
+            duration_secs: 0.0,
+            cached: true,
+            fingerprint: None,
+        };
+
+        for target in [None, Some("app")] {
+            assert_eq!(
+                run_product(&result, target).unwrap().path,
+                Path::new("build/debug/tools/app-cli")
+            );
+        }
+        assert!(run_product(&result, Some("Core")).is_err());
+
+        result.products.push(product(
+            "server",
+            TargetType::Executable,
+            "build/debug/server",
+        ));
+        let err = run_product(&result, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Several executables were built: app, server"
+        );
+        assert_eq!(
+            run_product(&result, Some("server")).unwrap().path,
+            Path::new("build/debug/server")
+        );
+    }
+}
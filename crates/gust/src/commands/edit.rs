@@ -0,0 +1,314 @@
+//! `gust edit` and `gust unedit`: swap a dependency for a local checkout.
+
+use crate::commands::ui::{self, pkg};
+use crate::edits::{Edits, EDITS_DIR};
+use crate::install::{checkout_dir, relink, sanitize_name};
+use gust_cache::GlobalCache;
+use gust_lockfile::Lockfile;
+use gust_manifest::find_manifest;
+use miette::{IntoDiagnostic, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Link `package` to a local copy until `gust unedit`.
+///
+/// With `path` the copy already exists; otherwise the package is cloned
+/// into `edits/<package>` at its locked revision.
+pub async fn edit(package: &str, path: Option<&Path>, target_dir: Option<&Path>) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let local = edit_package(&cwd, target_dir, package, path)?;
+    ui::success(format!("Editing {} at {}", pkg(package), local.display()));
+    ui::hint(format!(
+        "Run `gust unedit {}` to go back to the locked revision",
+        package
+    ));
+    Ok(())
+}
+
+/// Link `package` back to its cached checkout.
+pub async fn unedit(package: &str, target_dir: Option<&Path>) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let cache = GlobalCache::open().into_diagnostic()?;
+    let local = unedit_package(&cwd, target_dir, &cache, package)?;
+    ui::success(format!("Stopped editing {}", pkg(package)));
+    ui::info(format!(
+        "The local copy at {} was left in place",
+        local.display()
+    ));
+    Ok(())
+}
+
+/// Record and link the local copy of `package`, returning its path as
+/// stored in `.gust/edits.toml`.
+fn edit_package(
+    project_dir: &Path,
+    target_dir: Option<&Path>,
+    package: &str,
+    path: Option<&Path>,
+) -> Result<PathBuf> {
+    let mut edits = Edits::load(project_dir)?;
+    if let Some(existing) = edits.packages.get(package) {
+        return Err(miette::miette!(
+            help = format!("Run `gust unedit {}` first", package),
+            "{} is already being edited at {}",
+            package,
+            existing.display()
+        ));
+    }
+
+    let (git, revision) = locate_dependency(project_dir, package)?;
+    let local = match path {
+        Some(path) => {
+            if !project_dir.join(path).is_dir() {
+                return Err(miette::miette!("{} is not a directory", path.display()));
+            }
+            path.to_path_buf()
+        }
+        None => {
+            let Some(url) = git else {
+                return Err(miette::miette!(
+                    help = "Pass --path to use an existing copy",
+                    "{} has no git URL to clone",
+                    package
+                ));
+            };
+            let relative = Path::new(EDITS_DIR).join(sanitize_name(package));
+            let dest = project_dir.join(&relative);
+            if !dest.exists() {
+                clone(&url, revision.as_deref(), &dest)?;
+            }
+            relative
+        }
+    };
+
+    let checkouts = gust_build::target_dir(project_dir, target_dir).join("checkouts");
+    std::fs::create_dir_all(&checkouts).into_diagnostic()?;
+    relink(&checkouts.join(package), &project_dir.join(&local)).into_diagnostic()?;
+
+    edits.packages.insert(package.to_string(), local.clone());
+    edits.save(project_dir)?;
+    Ok(local)
+}
+
+/// Stop editing `package` and link its cached checkout again, returning
+/// the local copy it was using.
+fn unedit_package(
+    project_dir: &Path,
+    target_dir: Option<&Path>,
+    cache: &GlobalCache,
+    package: &str,
+) -> Result<PathBuf> {
+    let mut edits = Edits::load(project_dir)?;
+    let Some(local) = edits.packages.remove(package) else {
+        return Err(miette::miette!("{} is not being edited", package));
+    };
+
+    let link = gust_build::target_dir(project_dir, target_dir)
+        .join("checkouts")
+        .join(package);
+    let dep = find_manifest(project_dir).ok().and_then(|(manifest, _)| {
+        manifest
+            .dependencies
+            .get(package)
+            .or_else(|| manifest.dev_dependencies.get(package))
+            .cloned()
+    });
+    let cached = match dep {
+        Some(dep) => checkout_dir(cache, package, &dep),
+        None => cache.git_dir().join(sanitize_name(package)),
+    };
+    if cached.exists() {
+        relink(&link, &cached).into_diagnostic()?;
+    } else {
+        if link.is_symlink() {
+            std::fs::remove_file(&link).into_diagnostic()?;
+        }
+        ui::hint(format!(
+            "{} isn't cached; run `gust install` to fetch it",
+            package
+        ));
+    }
+
+    edits.save(project_dir)?;
+    Ok(local)
+}
+
+/// Git URL and revision of the dependency `package`, preferring what
+/// Gust.lock pinned over the manifest.
+fn locate_dependency(
+    project_dir: &Path,
+    package: &str,
+) -> Result<(Option<String>, Option<String>)> {
+    let lockfile_path = project_dir.join("Gust.lock");
+    if lockfile_path.exists() {
+        let lockfile = Lockfile::load(&lockfile_path).into_diagnostic()?;
+        if let Some(locked) = lockfile.packages.iter().find(|p| p.name == package) {
+            return Ok((locked.git.clone(), locked.revision.clone()));
+        }
+    }
+
+    let (manifest, _) = find_manifest(project_dir).into_diagnostic()?;
+    let dep = manifest
+        .dependencies
+        .get(package)
+        .or_else(|| manifest.dev_dependencies.get(package));
+    match dep {
+        Some(dep) => Ok((
+            dep.git.clone(),
+            dep.revision.clone().or_else(|| dep.tag.clone()),
+        )),
+        None => {
+            let names = manifest
+                .dependencies
+                .keys()
+                .chain(manifest.dev_dependencies.keys())
+                .map(String::as_str);
+            match gust_diagnostics::closest_match(package, names) {
+                Some(suggestion) => Err(miette::miette!(
+                    help = format!("did you mean `{}`?", suggestion),
+                    "{} is not a dependency",
+                    package
+                )),
+                None => Err(miette::miette!("{} is not a dependency", package)),
+            }
+        }
+    }
+}
+
+/// Clone `url` into `dest` and check out `revision`, if given.
+fn clone(url: &str, revision: Option<&str>, dest: &Path) -> Result<()> {
+    let status = Command::new("git")
+        .args(["clone", "-q", url])
+        .arg(dest)
+        .status()
+        .into_diagnostic()?;
+    if !status.success() {
+        return Err(miette::miette!("Failed to clone {}", url));
+    }
+    if let Some(revision) = revision {
+        let status = Command::new("git")
+            .args(["checkout", "-q", revision])
+            .current_dir(dest)
+            .status()
+            .into_diagnostic()?;
+        if !status.success() {
+            return Err(miette::miette!(
+                "Cloned {} but couldn't check out {}",
+                url,
+                revision
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::{InstallOptions, Installer};
+    use gust_testkit::git;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_edit_links_local_copy_until_unedit() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dep = tmp.path().join("alpha");
+        fs::create_dir_all(&dep).unwrap();
+        fs::write(
+            dep.join("Gust.toml"),
+            "[package]\nname = \"alpha\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+        let project = tmp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                dep.display()
+            ),
+        )
+        .unwrap();
+        let local = tmp.path().join("alpha-dev");
+        fs::create_dir_all(&local).unwrap();
+
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let installer = || {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    ..Default::default()
+                },
+                cache.clone(),
+            )
+        };
+        installer().install().await.unwrap();
+        let link = project.join(".build/checkouts/alpha");
+        let cached = fs::read_link(&link).unwrap();
+        assert!(cached.starts_with(cache.paths_dir()));
+
+        edit_package(&project, None, "alpha", Some(&local)).unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), local);
+        assert!(edit_package(&project, None, "alpha", Some(&local)).is_err());
+        assert!(edit_package(&project, None, "alpah", Some(&local)).is_err());
+
+        // Later installs keep the local copy, and the manifest is untouched
+        installer().install().await.unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), local);
+        assert!(!fs::read_to_string(project.join("Gust.toml"))
+            .unwrap()
+            .contains("alpha-dev"));
+
+        assert_eq!(
+            unedit_package(&project, None, &cache, "alpha").unwrap(),
+            local
+        );
+        assert_eq!(fs::read_link(&link).unwrap(), cached);
+        assert!(!Edits::path(&project).exists());
+        assert!(unedit_package(&project, None, &cache, "alpha").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_edit_clones_locked_revision() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        let locked = gust_testkit::git_repo(&repo);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "newer"]);
+        let url = format!("file://{}", repo.display());
+
+        let project = tmp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        let lockfile = Lockfile {
+            packages: vec![gust_lockfile::LockedPackage::git(
+                "dep",
+                gust_types::Version::new(1, 0, 0),
+                &url,
+                &locked,
+            )],
+            ..Default::default()
+        };
+        lockfile.save(&project.join("Gust.lock")).unwrap();
+
+        let local = edit_package(&project, None, "dep", None).unwrap();
+        assert_eq!(local, Path::new("edits/dep"));
+        let clone = project.join("edits/dep");
+        assert_eq!(git(&clone, &["rev-parse", "HEAD"]), locked);
+        assert_eq!(
+            fs::read_link(project.join(".build/checkouts/dep")).unwrap(),
+            clone
+        );
+        assert_eq!(
+            Edits::load(&project).unwrap().packages["dep"],
+            Path::new("edits/dep")
+        );
+
+        // With nothing cached the link is dropped until the next install,
+        // and the clone is kept
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        unedit_package(&project, None, &cache, "dep").unwrap();
+        assert!(!project.join(".build/checkouts/dep").is_symlink());
+        assert!(clone.join(".git").exists());
+    }
+}
@@ -4,12 +4,21 @@
 //! Commands are organized into submodules by functionality.
 
 mod core;
+mod edit;
+mod selftest;
+mod template;
 pub mod ui;
 pub mod version;
+mod workspace;
 
 // Re-export command functions from core
 pub use core::{
-    add, build, cache_clean, cache_list, cache_path, cache_stats, clean, doctor, generate, info,
-    init, install, migrate, new_package, outdated, remove, run, search, swift_current,
-    swift_install, swift_list, swift_use, test, tree, update, xcode_generate,
+    add, build, cache_clean, cache_list, cache_migrate, cache_path, cache_stats, clean, config_get,
+    config_list, config_set, deps, doctor, generate, info, init, install, install_recursive,
+    migrate, new_package, outdated, print_manifest, remove, resolve, run, search, swift_current,
+    swift_install, swift_list, swift_use, test, tree, update, verify, xcode_generate, AddArgs,
+    BuildArgs, InstallArgs, ResolveArgs,
 };
+pub use edit::{edit, unedit};
+pub use selftest::self_test;
+pub use workspace::{build_workspace, effective_manifest, test_workspace};
@@ -0,0 +1,212 @@
+//! End-to-end smoke test of the install and build pipeline.
+//!
+//! Runs against the real toolchain, network and caches so that environment
+//! problems (missing swift, unwritable cache, no git) show up as a failing
+//! stage rather than a confusing error deep inside a real project.
+
+use crate::commands::ui::{self, dim};
+use crate::install::{InstallOptions, Installer};
+use console::style;
+use gust_build::{BuildOptions, Builder};
+use gust_cache::GlobalCache;
+use gust_lockfile::Lockfile;
+use gust_manifest::{find_manifest, write_package_swift};
+use gust_platform::SwiftToolchain;
+use gust_types::BuildConfiguration;
+use miette::{IntoDiagnostic, Result};
+use std::future::Future;
+use std::path::Path;
+use std::time::Instant;
+
+/// Name of the throwaway package created by the self-test.
+const PACKAGE_NAME: &str = "GustSelfTest";
+
+/// Small, dependency-free package fetched during the self-test.
+const DEPENDENCY_NAME: &str = "swift-log";
+const DEPENDENCY_URL: &str = "https://github.com/apple/swift-log.git";
+const DEPENDENCY_TAG: &str = "1.5.4";
+
+/// Run the self-test, returning an error naming the first failing stage.
+pub async fn self_test() -> Result<()> {
+    ui::info("Running gust self-test");
+    println!();
+
+    let total = Instant::now();
+
+    stage("toolchain", async {
+        let toolchain = SwiftToolchain::detect().into_diagnostic()?;
+        Ok(format!("swift {}", toolchain.version))
+    })
+    .await?;
+
+    stage("cache", async {
+        let cache = GlobalCache::open().into_diagnostic()?;
+        let hash = cache.store_bytes(b"gust self-test").into_diagnostic()?;
+        if !cache.contains(&hash) {
+            return Err(miette::miette!("Stored probe is missing from the cache"));
+        }
+        gust_binary_cache::LocalBinaryCache::open().into_diagnostic()?;
+        Ok(cache.files_dir().display().to_string())
+    })
+    .await?;
+
+    let tmp = tempfile::Builder::new()
+        .prefix("gust-selftest-")
+        .tempdir()
+        .into_diagnostic()?;
+    let project = tmp.path().to_path_buf();
+
+    stage("scaffold", async {
+        scaffold(&project)?;
+        Ok(project.display().to_string())
+    })
+    .await?;
+
+    stage("install", async {
+        let options = InstallOptions {
+            concurrency: 4,
+            ..Default::default()
+        };
+        let installer = Installer::new(project.clone(), options)?;
+        let result = installer.install().await?;
+
+        if !project
+            .join(".build")
+            .join("checkouts")
+            .join(DEPENDENCY_NAME)
+            .exists()
+        {
+            return Err(miette::miette!("{} was not linked", DEPENDENCY_NAME));
+        }
+
+        let lockfile = Lockfile::load(&project.join("Gust.lock")).into_diagnostic()?;
+        if lockfile.get(DEPENDENCY_NAME).is_none() {
+            return Err(miette::miette!(
+                "{} missing from Gust.lock",
+                DEPENDENCY_NAME
+            ));
+        }
+
+        Ok(format!("{} packages", result.installed))
+    })
+    .await?;
+
+    let (manifest, _) = find_manifest(&project).into_diagnostic()?;
+    write_package_swift(&manifest, &project).into_diagnostic()?;
+    let builder = Builder::new(project.clone()).into_diagnostic()?;
+    let options = BuildOptions {
+        cancel: gust_build::ctrl_c_token(),
+        ..Default::default()
+    };
+
+    stage("build", async {
+        let result = builder.build(&manifest, &options).await.into_diagnostic()?;
+        if result.products.is_empty() {
+            return Err(miette::miette!("Build produced no products"));
+        }
+        Ok(if result.cached {
+            "restored from cache".to_string()
+        } else {
+            format!("{} products", result.products.len())
+        })
+    })
+    .await?;
+
+    stage("cache-restore", async {
+        let build_dir = builder.build_dir(BuildConfiguration::Debug);
+        if build_dir.is_symlink() {
+            std::fs::remove_file(&build_dir).into_diagnostic()?;
+        } else if build_dir.exists() {
+            std::fs::remove_dir_all(&build_dir).into_diagnostic()?;
+        }
+
+        let result = builder.build(&manifest, &options).await.into_diagnostic()?;
+        if !result.cached {
+            return Err(miette::miette!(
+                "Rebuild did not restore from the binary cache"
+            ));
+        }
+        if let Some(missing) = result.products.iter().find(|p| !p.path.exists()) {
+            return Err(miette::miette!(
+                "Restored product is missing: {}",
+                missing.path.display()
+            ));
+        }
+        Ok(format!("{} products", result.products.len()))
+    })
+    .await?;
+
+    println!();
+    ui::success(format!(
+        "Self-test passed in {:.2}s",
+        total.elapsed().as_secs_f64()
+    ));
+
+    Ok(())
+}
+
+/// Run a single stage, printing its outcome and duration.
+async fn stage<F>(name: &str, run: F) -> Result<()>
+where
+    F: Future<Output = Result<String>>,
+{
+    let start = Instant::now();
+    let result = run.await;
+    let elapsed = dim(format!("{:.2}s", start.elapsed().as_secs_f64()));
+
+    match result {
+        Ok(detail) => {
+            println!(
+                "{} {:<14} {} {}",
+                style("✓").green().bold(),
+                name,
+                elapsed,
+                dim(detail)
+            );
+            Ok(())
+        }
+        Err(e) => {
+            println!("{} {:<14} {}", style("✗").red().bold(), name, elapsed);
+            Err(miette::miette!(
+                "Self-test failed at stage '{}': {}",
+                name,
+                e
+            ))
+        }
+    }
+}
+
+/// Write a minimal executable package that depends on [`DEPENDENCY_NAME`].
+fn scaffold(dir: &Path) -> Result<()> {
+    let manifest = format!(
+        r#"[package]
+name = "{name}"
+version = "0.1.0"
+swift-tools-version = "5.9"
+
+[dependencies]
+{dep} = {{ git = "{url}", tag = "{tag}" }}
+
+[[target]]
+name = "{name}"
+type = "executable"
+path = "Sources/{name}"
+dependencies = ["{dep}"]
+"#,
+        name = PACKAGE_NAME,
+        dep = DEPENDENCY_NAME,
+        url = DEPENDENCY_URL,
+        tag = DEPENDENCY_TAG,
+    );
+    std::fs::write(dir.join("Gust.toml"), manifest).into_diagnostic()?;
+
+    let sources = dir.join("Sources").join(PACKAGE_NAME);
+    std::fs::create_dir_all(&sources).into_diagnostic()?;
+    std::fs::write(
+        sources.join("main.swift"),
+        "import Logging\n\nLogger(label: \"gust.selftest\").info(\"ok\")\n",
+    )
+    .into_diagnostic()?;
+
+    Ok(())
+}
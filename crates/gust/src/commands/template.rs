@@ -0,0 +1,276 @@
+//! Scaffolding new packages from template repositories.
+//!
+//! A template is any package layout whose file contents and names may
+//! contain `{{PackageName}}` and `{{Author}}`. It can be a built-in name, a
+//! git URL or a local directory.
+
+use gust_fetch::Fetcher;
+use gust_types::Dependency;
+use miette::{IntoDiagnostic, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Built-in template names and the repositories they clone.
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("vapor", "https://github.com/vapor/template.git"),
+    (
+        "hummingbird",
+        "https://github.com/hummingbird-project/template.git",
+    ),
+];
+
+/// Where a template comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateSource {
+    /// A git repository to clone
+    Git(String),
+    /// A directory to copy
+    Local(PathBuf),
+}
+
+impl TemplateSource {
+    /// Interpret `--template`: a built-in name, an existing directory, or
+    /// otherwise a git URL.
+    pub fn parse(spec: &str) -> Self {
+        if let Some((_, url)) = BUILTIN_TEMPLATES.iter().find(|(name, _)| *name == spec) {
+            return TemplateSource::Git(url.to_string());
+        }
+        let path = Path::new(spec);
+        if path.is_dir() {
+            return TemplateSource::Local(path.to_path_buf());
+        }
+        TemplateSource::Git(spec.to_string())
+    }
+}
+
+/// Values substituted for template placeholders.
+#[derive(Debug, Clone)]
+pub struct TemplateVars {
+    pub package_name: String,
+    pub author: String,
+}
+
+impl TemplateVars {
+    /// Variables for `package_name`, with the author from git config or
+    /// `$USER`.
+    pub fn for_package(package_name: &str) -> Self {
+        let author = Command::new("git")
+            .args(["config", "user.name"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_default();
+        Self {
+            package_name: package_name.to_string(),
+            author,
+        }
+    }
+
+    fn substitute(&self, text: &str) -> String {
+        text.replace("{{PackageName}}", &self.package_name)
+            .replace("{{Author}}", &self.author)
+    }
+}
+
+/// Create a package at `dest` from `source`, substituting `vars`.
+///
+/// The template's `.git` directory is not kept. Fails if the result has
+/// neither a `Gust.toml` nor a `Package.swift`.
+pub async fn scaffold(source: &TemplateSource, dest: &Path, vars: &TemplateVars) -> Result<()> {
+    match source {
+        TemplateSource::Git(url) => {
+            let dep = Dependency::git(&vars.package_name, url);
+            Fetcher::new()
+                .fetch(&dep, &dest.to_path_buf())
+                .await
+                .map_err(|e| miette::miette!("Failed to clone template {}: {}", url, e))?;
+        }
+        TemplateSource::Local(dir) => copy_dir(dir, dest).into_diagnostic()?,
+    }
+
+    let git_dir = dest.join(".git");
+    if git_dir.exists() {
+        fs::remove_dir_all(&git_dir).into_diagnostic()?;
+    }
+
+    render(dest, vars).into_diagnostic()?;
+
+    if !dest.join("Gust.toml").exists() && !dest.join("Package.swift").exists() {
+        return Err(miette::miette!(
+            "Template has no Gust.toml or Package.swift"
+        ));
+    }
+    Ok(())
+}
+
+/// Copy `src` into `dest`, skipping `.git`. Symlinks are copied as
+/// symlinks, never followed.
+fn copy_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let target = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dest)
+}
+
+#[cfg(windows)]
+fn copy_symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let link = fs::read_link(src)?;
+    if src.is_dir() {
+        std::os::windows::fs::symlink_dir(link, dest)
+    } else {
+        std::os::windows::fs::symlink_file(link, dest)
+    }
+}
+
+/// Substitute placeholders in the contents and names of everything under
+/// `dir`. Files that aren't UTF-8 are left as they are, and symlinks are
+/// renamed but not followed.
+fn render(dir: &Path, vars: &TemplateVars) -> std::io::Result<()> {
+    // Renaming while iterating may make read_dir skip or repeat entries
+    let entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    for entry in entries {
+        let mut path = entry.path();
+        let file_type = entry.file_type()?;
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rendered = vars.substitute(&name);
+        if rendered != name {
+            let renamed = dir.join(&rendered);
+            fs::rename(&path, &renamed)?;
+            path = renamed;
+        }
+
+        if file_type.is_symlink() {
+            continue;
+        } else if file_type.is_dir() {
+            render(&path, vars)?;
+        } else if let Ok(content) = fs::read_to_string(&path) {
+            let substituted = vars.substitute(&content);
+            if substituted != content {
+                fs::write(&path, substituted)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scaffold_substitutes_placeholders() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let template = tmp.path().join("template");
+        let sources = template.join("Sources").join("{{PackageName}}");
+        fs::create_dir_all(&sources).unwrap();
+        fs::create_dir_all(template.join(".git")).unwrap();
+        fs::write(
+            template.join("Gust.toml"),
+            "[package]\nname = \"{{PackageName}}\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            sources.join("{{PackageName}}.swift"),
+            "// Created by {{Author}}\npublic struct {{PackageName}} {}\n",
+        )
+        .unwrap();
+
+        let source = TemplateSource::parse(template.to_str().unwrap());
+        assert_eq!(source, TemplateSource::Local(template.clone()));
+
+        let dest = tmp.path().join("Greeter");
+        let vars = TemplateVars {
+            package_name: "Greeter".to_string(),
+            author: "Ada".to_string(),
+        };
+        scaffold(&source, &dest, &vars).await.unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dest.join("Gust.toml")).unwrap(),
+            "[package]\nname = \"Greeter\"\nversion = \"0.1.0\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("Sources/Greeter/Greeter.swift")).unwrap(),
+            "// Created by Ada\npublic struct Greeter {}\n"
+        );
+        assert!(!dest.join(".git").exists());
+        assert!(!dest.join("Sources/{{PackageName}}").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_scaffold_keeps_symlinks_as_links() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let outside = tmp.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("notes.txt"), "{{PackageName}}").unwrap();
+
+        let template = tmp.path().join("template");
+        fs::create_dir_all(template.join("Sources")).unwrap();
+        fs::write(template.join("Package.swift"), "// {{PackageName}}\n").unwrap();
+        std::os::unix::fs::symlink(&outside, template.join("Shared")).unwrap();
+        std::os::unix::fs::symlink("..", template.join("Sources/{{PackageName}}Root")).unwrap();
+
+        let dest = tmp.path().join("Greeter");
+        let vars = TemplateVars {
+            package_name: "Greeter".to_string(),
+            author: "Ada".to_string(),
+        };
+        scaffold(&TemplateSource::Local(template), &dest, &vars)
+            .await
+            .unwrap();
+
+        assert!(fs::symlink_metadata(dest.join("Shared"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_link(dest.join("Sources/GreeterRoot")).unwrap(),
+            Path::new("..")
+        );
+        // Content behind a link is never rewritten
+        assert_eq!(
+            fs::read_to_string(outside.join("notes.txt")).unwrap(),
+            "{{PackageName}}"
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("Package.swift")).unwrap(),
+            "// Greeter\n"
+        );
+    }
+
+    #[test]
+    fn test_builtin_and_url_templates() {
+        assert_eq!(
+            TemplateSource::parse("vapor"),
+            TemplateSource::Git("https://github.com/vapor/template.git".to_string())
+        );
+        assert_eq!(
+            TemplateSource::parse("https://example.com/t.git"),
+            TemplateSource::Git("https://example.com/t.git".to_string())
+        );
+    }
+}
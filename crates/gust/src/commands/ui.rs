@@ -3,6 +3,177 @@
 #![allow(dead_code)]
 
 use console::{style, StyledObject};
+use indicatif::ProgressStyle;
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal
+    #[default]
+    Auto,
+    /// Always color, even when output is piped
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    /// `Some(true)` to force color, `Some(false)` to turn it off, `None` to
+    /// follow terminal support.
+    pub fn forced(self) -> Option<bool> {
+        match self {
+            Self::Auto => None,
+            Self::Always => Some(true),
+            Self::Never => Some(false),
+        }
+    }
+}
+
+/// When output should be colored, given the `--color` and `--no-color`
+/// flags and the `color` config setting.
+///
+/// Flags win. Otherwise a non-empty `NO_COLOR` environment variable turns
+/// color off and a `CLICOLOR_FORCE` other than `0` turns it on.
+pub fn color_choice(flag: ColorChoice, no_color: bool, config: Option<bool>) -> ColorChoice {
+    let set = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    if no_color {
+        ColorChoice::Never
+    } else if flag != ColorChoice::Auto {
+        flag
+    } else if set("NO_COLOR").is_some() {
+        ColorChoice::Never
+    } else if set("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+        ColorChoice::Always
+    } else if config == Some(false) {
+        ColorChoice::Never
+    } else {
+        ColorChoice::Auto
+    }
+}
+
+/// Turn ANSI styling for stdout and stderr on or off as `choice` says.
+///
+/// With `auto`, `console` keeps detecting terminal support itself.
+pub fn init_colors(choice: ColorChoice) {
+    if let Some(enabled) = choice.forced() {
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+}
+
+/// Format for reports that scripts may want to consume.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON on stdout
+    Json,
+}
+
+/// Format of `gust deps` output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DepsFormat {
+    /// One line per package
+    #[default]
+    Text,
+    /// JSON array of packages
+    Json,
+    /// SPDX 2.3 SBOM (JSON)
+    Spdx,
+    /// CycloneDX 1.5 SBOM (JSON)
+    Cyclonedx,
+}
+
+/// Manifest that `gust new` and `gust init` scaffold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ManifestFormat {
+    /// Gust.toml, with Package.swift generated from it
+    #[default]
+    Toml,
+    /// A native Package.swift
+    Swift,
+}
+
+/// Format of `gust build --print-manifest` output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ManifestOutput {
+    /// Pretty-printed JSON
+    #[default]
+    Json,
+    /// TOML, in the shape of the parsed manifest rather than Gust.toml
+    Toml,
+}
+
+/// Format of `gust test --coverage` output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CoverageFormat {
+    /// Per-target summary table
+    #[default]
+    Text,
+    /// LCOV tracefile written to the target directory
+    Lcov,
+}
+
+/// Style for progress spinners; plain ASCII when color is off.
+pub fn spinner_style() -> ProgressStyle {
+    if console::colors_enabled_stderr() {
+        ProgressStyle::default_spinner()
+            .template("{spinner:.blue} {msg}")
+            .unwrap()
+    } else {
+        ProgressStyle::default_spinner()
+            .template("{spinner} {msg}")
+            .unwrap()
+            .tick_chars("-\\|/ ")
+    }
+}
+
+/// Style for progress bars; plain ASCII when color is off.
+pub fn bar_style() -> ProgressStyle {
+    if console::colors_enabled_stderr() {
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} ETA {eta} {wide_msg}")
+            .unwrap()
+            .progress_chars("█▓░")
+    } else {
+        ProgressStyle::default_bar()
+            .template("[{bar:40}] {pos}/{len} ETA {eta} {wide_msg}")
+            .unwrap()
+            .progress_chars("#>-")
+    }
+}
+
+/// Human-readable byte count, e.g. `12.3 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// Total transferred and average rate, e.g. `12.3 MB at 2.1 MB/s`.
+pub fn transfer_summary(bytes: u64, elapsed: std::time::Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return format_bytes(bytes);
+    }
+    format!(
+        "{} at {}/s",
+        format_bytes(bytes),
+        format_bytes((bytes as f64 / secs) as u64)
+    )
+}
 
 /// Print a success message with green checkmark.
 pub fn success(msg: impl std::fmt::Display) {
@@ -54,3 +225,60 @@ pub fn header(title: impl std::fmt::Display) {
 pub fn separator(width: usize) {
     println!("{}", style("─".repeat(width)).dim());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Color is global, so tests that change it take turns.
+    static COLORS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn build_line() -> String {
+        format!(
+            "{} Building {} ({})\n{} Built in {}s",
+            style("→").blue().bold(),
+            pkg("app"),
+            dim("debug"),
+            style("✓").green().bold(),
+            num("0.42"),
+        )
+    }
+
+    #[test]
+    fn test_no_color_output_has_no_escapes() {
+        let _colors = COLORS.lock().unwrap();
+        console::set_colors_enabled(true);
+        assert!(build_line().contains('\x1b'));
+
+        init_colors(color_choice(ColorChoice::Auto, true, None));
+        let output = build_line();
+        assert!(!output.contains('\x1b'));
+        assert!(output.contains("Building app (debug)"));
+        assert!(!console::colors_enabled_stderr());
+    }
+
+    #[test]
+    fn test_color_always_colors_redirected_output() {
+        let _colors = COLORS.lock().unwrap();
+        // Colored whether or not stdout is a terminal, and over the config
+        init_colors(color_choice(ColorChoice::Always, false, Some(false)));
+        assert!(build_line().contains('\x1b'));
+        assert!(console::colors_enabled_stderr());
+        assert_eq!(
+            color_choice(ColorChoice::Always, true, None),
+            ColorChoice::Never
+        );
+    }
+
+    #[test]
+    fn test_transfer_summary() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1_500), "1.5 KB");
+        assert_eq!(format_bytes(12_345_678), "12.3 MB");
+        assert_eq!(
+            transfer_summary(4_200_000, std::time::Duration::from_secs(2)),
+            "4.2 MB at 2.1 MB/s"
+        );
+        assert_eq!(transfer_summary(10, std::time::Duration::ZERO), "10 B");
+    }
+}
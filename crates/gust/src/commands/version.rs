@@ -1,7 +1,9 @@
 //! Version checking utilities for update and outdated commands.
 
 use gust_lockfile::LockedPackage;
+use gust_types::Manifest;
 use semver::Version;
+use std::collections::HashMap;
 
 /// Information about an outdated package.
 #[derive(Debug, Clone)]
@@ -12,37 +14,100 @@ pub struct OutdatedPackage {
     pub latest_tag: String,
 }
 
+/// A git dependency that follows a branch rather than version tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchTracked {
+    pub name: String,
+    /// Branch from the manifest; `None` for the remote's default branch
+    pub branch: Option<String>,
+    /// Locked revision
+    pub current: Option<String>,
+    /// Commit the branch points at now
+    pub latest: Option<String>,
+}
+
+impl BranchTracked {
+    /// Whether the branch has moved past the locked revision.
+    pub fn has_new_commit(&self) -> bool {
+        match (&self.current, &self.latest) {
+            (Some(current), Some(latest)) => current != latest,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Branch name for display.
+    pub fn branch_name(&self) -> &str {
+        self.branch.as_deref().unwrap_or("default branch")
+    }
+}
+
+/// Outcome of checking one package for updates.
+#[derive(Debug, Clone)]
+pub enum UpdateCheck {
+    /// A newer version tag exists
+    Outdated(OutdatedPackage),
+    /// Tracks a branch, so versions aren't compared
+    Branch(BranchTracked),
+}
+
+/// Updates found across a set of packages.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateReport {
+    pub outdated: Vec<OutdatedPackage>,
+    pub branches: Vec<BranchTracked>,
+}
+
+/// Branches the manifest's git dependencies track, by package name.
+pub fn tracked_branches(manifest: &Manifest) -> HashMap<String, String> {
+    manifest
+        .dependencies
+        .iter()
+        .chain(&manifest.dev_dependencies)
+        .filter_map(|(name, dep)| Some((name.clone(), dep.branch.clone()?)))
+        .collect()
+}
+
 /// Check a locked package for available updates.
-/// Returns None if up-to-date or no valid tags found.
-pub async fn check_for_update(pkg: &LockedPackage) -> Option<OutdatedPackage> {
+///
+/// Packages that track `branch`, or whose repository has no semver tags,
+/// are reported as [`UpdateCheck::Branch`] with the branch's latest commit.
+/// Returns None if up-to-date or the remote can't be reached.
+pub async fn check_for_update(pkg: &LockedPackage, branch: Option<&str>) -> Option<UpdateCheck> {
     let git_url = pkg.git.as_ref()?;
     let name = pkg.name.clone();
     let current_version = pkg.version.to_string();
 
+    if branch.is_some() {
+        return check_branch(pkg, git_url, branch).await;
+    }
+
     match gust_fetch::list_remote_tags(git_url).await {
         Ok(tags) => {
             // Find the latest semver tag
-            let latest = tags.iter().find(|t| t.version.is_some())?;
+            let Some(latest) = tags.iter().find(|t| t.version.is_some()) else {
+                return check_branch(pkg, git_url, None).await;
+            };
             let latest_version = latest.version.as_ref()?;
             let current = Version::parse(&current_version).ok();
 
             if let Some(ref curr) = current {
                 if latest_version > curr {
-                    return Some(OutdatedPackage {
+                    return Some(UpdateCheck::Outdated(OutdatedPackage {
                         name,
                         current: current_version,
                         latest: latest_version.to_string(),
                         latest_tag: latest.name.clone(),
-                    });
+                    }));
                 }
             } else {
                 // Current version isn't semver, show latest anyway
-                return Some(OutdatedPackage {
+                return Some(UpdateCheck::Outdated(OutdatedPackage {
                     name,
                     current: current_version,
                     latest: latest_version.to_string(),
                     latest_tag: latest.name.clone(),
-                });
+                }));
             }
             None
         }
@@ -53,27 +118,56 @@ pub async fn check_for_update(pkg: &LockedPackage) -> Option<OutdatedPackage> {
     }
 }
 
+async fn check_branch(
+    pkg: &LockedPackage,
+    git_url: &str,
+    branch: Option<&str>,
+) -> Option<UpdateCheck> {
+    let latest = match gust_fetch::remote_branch_head(git_url, branch).await {
+        Ok(latest) => latest,
+        Err(e) => {
+            tracing::warn!("Failed to check {} for new commits: {}", pkg.name, e);
+            None
+        }
+    };
+    Some(UpdateCheck::Branch(BranchTracked {
+        name: pkg.name.clone(),
+        branch: branch.map(str::to_string),
+        current: pkg.revision.clone(),
+        latest,
+    }))
+}
+
 /// Check multiple packages for updates in parallel.
-pub async fn check_all_for_updates(packages: &[&LockedPackage]) -> Vec<OutdatedPackage> {
+///
+/// `branches` maps packages to the branch they track (see
+/// [`tracked_branches`]).
+pub async fn check_all_for_updates(
+    packages: &[&LockedPackage],
+    branches: &HashMap<String, String>,
+) -> UpdateReport {
     let mut tasks = Vec::new();
 
     for pkg in packages {
         if pkg.git.is_some() {
             let pkg_clone = (*pkg).clone();
-            tasks.push(tokio::spawn(
-                async move { check_for_update(&pkg_clone).await },
-            ));
+            let branch = branches.get(&pkg.name).cloned();
+            tasks.push(tokio::spawn(async move {
+                check_for_update(&pkg_clone, branch.as_deref()).await
+            }));
         }
     }
 
-    let mut outdated = Vec::new();
+    let mut report = UpdateReport::default();
     for task in tasks {
-        if let Ok(Some(info)) = task.await {
-            outdated.push(info);
+        match task.await {
+            Ok(Some(UpdateCheck::Outdated(info))) => report.outdated.push(info),
+            Ok(Some(UpdateCheck::Branch(info))) => report.branches.push(info),
+            _ => {}
         }
     }
 
-    outdated
+    report
 }
 
 /// Filter updates by semver compatibility.
@@ -0,0 +1,580 @@
+//! Commands that run across every member of a workspace.
+
+use super::core::BuildArgs;
+use crate::commands::ui::{self, dim, green, pkg, separator};
+use console::style;
+use gust_build::TestCounts;
+use gust_manifest::{find_manifest, ManifestType};
+use gust_types::{Manifest, TargetType};
+use gust_workspace::{find_workspace_root, Workspace, WorkspaceLoader, WorkspaceMember};
+use miette::{IntoDiagnostic, Result};
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// The manifest of the package at `dir` as Gust sees it, and the kind of
+/// file it came from: for a workspace member, with `workspace = true`
+/// dependencies taken from the root and the root's overrides and
+/// constraints added under the member's own.
+///
+/// Outside a workspace, a `workspace = true` dependency is an error.
+pub fn effective_manifest(dir: &Path) -> Result<(Manifest, ManifestType)> {
+    let (manifest, manifest_type) = find_manifest(dir).into_diagnostic()?;
+    let Ok(root) = find_workspace_root(dir) else {
+        return standalone(manifest, manifest_type);
+    };
+    let workspace = WorkspaceLoader::new().load(&root).into_diagnostic()?;
+    let dir = dir.canonicalize().into_diagnostic()?;
+    let Some(member) = workspace
+        .members
+        .iter()
+        .find(|m| m.path.canonicalize().is_ok_and(|p| p == dir))
+    else {
+        return standalone(manifest, manifest_type);
+    };
+
+    let mut manifest = member.manifest.clone();
+    for (name, req) in &workspace.root_manifest.overrides {
+        manifest
+            .overrides
+            .entry(name.clone())
+            .or_insert_with(|| req.clone());
+    }
+    for (name, req) in &workspace.root_manifest.constraints {
+        manifest
+            .constraints
+            .entry(name.clone())
+            .or_insert_with(|| req.clone());
+    }
+    Ok((manifest, manifest_type))
+}
+
+/// `manifest` as it is, unless a dependency needs a workspace to take its
+/// source from.
+fn standalone(manifest: Manifest, manifest_type: ManifestType) -> Result<(Manifest, ManifestType)> {
+    let mut inherited: Vec<&str> = manifest
+        .dependencies
+        .values()
+        .filter(|d| d.version.is_none() && d.git.is_none() && d.path.is_none())
+        .map(|d| d.name.as_str())
+        .collect();
+    if !inherited.is_empty() {
+        inherited.sort_unstable();
+        return Err(miette::miette!(
+            help = "Give each a version, git or path, or add the package to a workspace's members",
+            "{} uses `workspace = true` outside a workspace for: {}",
+            manifest.package.name,
+            inherited.join(", ")
+        ));
+    }
+    Ok((manifest, manifest_type))
+}
+
+/// Files changed since the git ref `since`, relative to the workspace
+/// `root`, including untracked files that aren't ignored. A renamed file
+/// counts as changed at both its old and new path. Files outside the root
+/// are left out.
+async fn changed_files(root: &Path, since: &str) -> Result<Vec<PathBuf>> {
+    let output = tokio::process::Command::new("git")
+        .args([
+            "diff",
+            "--name-only",
+            "--no-renames",
+            "--relative",
+            since,
+            "--",
+        ])
+        .current_dir(root)
+        .output()
+        .await
+        .into_diagnostic()?;
+    if !output.status.success() {
+        return Err(miette::miette!(
+            help = "--since takes a branch, tag or commit, such as origin/main",
+            "git diff against '{}' failed: {}",
+            since,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let untracked = tokio::process::Command::new("git")
+        .args(["ls-files", "--others", "--exclude-standard"])
+        .current_dir(root)
+        .output()
+        .await
+        .into_diagnostic()?;
+    if !untracked.status.success() {
+        return Err(miette::miette!(
+            "git ls-files failed: {}",
+            String::from_utf8_lossy(&untracked.stderr).trim()
+        ));
+    }
+
+    let mut files = Vec::new();
+    for output in [&output.stdout, &untracked.stdout] {
+        for line in String::from_utf8_lossy(output).lines() {
+            let file = PathBuf::from(line);
+            if !line.is_empty() && !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The members to operate on, in build order.
+///
+/// `members` is a [`Workspace::filter_members`] pattern; it is an error for
+/// it to match nothing. With `since`, only members affected by files
+/// changed since that git ref are kept, as decided by
+/// [`Workspace::affected_members`]; that may leave none.
+async fn select_members<'a>(
+    workspace: &'a Workspace,
+    members: Option<&str>,
+    since: Option<&str>,
+) -> Result<Vec<&'a WorkspaceMember>> {
+    let mut selected: HashSet<&str> = workspace
+        .filter_members(members.unwrap_or("*"))
+        .into_iter()
+        .map(|m| m.name.as_str())
+        .collect();
+    if selected.is_empty() {
+        return Err(miette::miette!(
+            help = format!("members: {}", workspace.member_names().join(", ")),
+            "No workspace members match '{}'",
+            members.unwrap_or("*")
+        ));
+    }
+
+    if let Some(since) = since {
+        let changed = changed_files(&workspace.root, since).await?;
+        let affected: HashSet<&str> = workspace
+            .affected_members(&changed)
+            .into_iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        selected.retain(|name| affected.contains(name));
+        if selected.is_empty() {
+            ui::info(format!("No workspace members changed since {}", since));
+        }
+    }
+
+    Ok(workspace
+        .build_order()
+        .into_diagnostic()?
+        .into_iter()
+        .filter(|m| selected.contains(m.name.as_str()))
+        .collect())
+}
+
+/// Build each workspace member, in build order, stopping at the first
+/// failure.
+///
+/// With `since`, only members affected by changes since that git ref are
+/// built.
+pub async fn build_workspace(since: Option<&str>, args: &BuildArgs) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let root = find_workspace_root(&cwd).into_diagnostic()?;
+    let workspace = WorkspaceLoader::new().load(&root).into_diagnostic()?;
+    let selected = select_members(&workspace, None, since).await?;
+    if selected.is_empty() {
+        return Ok(());
+    }
+
+    for member in &selected {
+        super::core::build_in(&member.path, args).await?;
+    }
+    ui::success(format!(
+        "Built {} of {} workspace members",
+        selected.len(),
+        workspace.members.len()
+    ));
+    Ok(())
+}
+
+/// How testing one workspace member went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MemberOutcome {
+    /// The member declares no test targets
+    NoTests,
+    /// `swift test` ran
+    Ran { success: bool, counts: TestCounts },
+}
+
+/// Test results for one workspace member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MemberTests {
+    name: String,
+    outcome: MemberOutcome,
+}
+
+/// Run `swift test` in each workspace member, in build order, and report
+/// per-member results.
+///
+/// `package` selects members with [`Workspace::filter_members`], `since`
+/// narrows them to those affected by changes since a git ref, and
+/// `test_filter` is passed to each run as `--filter`. Every selected member
+/// is tested even after a failure; the command fails if any did.
+pub async fn test_workspace(
+    package: Option<&str>,
+    since: Option<&str>,
+    test_filter: Option<&str>,
+    target_dir: Option<&Path>,
+) -> Result<()> {
+    let cwd = env::current_dir().into_diagnostic()?;
+    let root = find_workspace_root(&cwd).into_diagnostic()?;
+    let toolchain = gust_platform::SwiftToolchain::detect().into_diagnostic()?;
+
+    let results = run_workspace_tests(
+        &root,
+        package,
+        since,
+        test_filter,
+        target_dir,
+        &toolchain.swift_path,
+    )
+    .await?;
+    report(&results)
+}
+
+/// Test the selected members of the workspace at `root` with `swift`.
+async fn run_workspace_tests(
+    root: &Path,
+    package: Option<&str>,
+    since: Option<&str>,
+    test_filter: Option<&str>,
+    target_dir: Option<&Path>,
+    swift: &Path,
+) -> Result<Vec<MemberTests>> {
+    let workspace = WorkspaceLoader::new().load(root).into_diagnostic()?;
+
+    let mut results = Vec::new();
+    for member in select_members(&workspace, package, since).await? {
+        let has_tests = member
+            .manifest
+            .targets
+            .iter()
+            .any(|t| t.target_type == TargetType::Test);
+        if !has_tests {
+            ui::info(format!("Skipping {} (no test targets)", member.name));
+            results.push(MemberTests {
+                name: member.name.clone(),
+                outcome: MemberOutcome::NoTests,
+            });
+            continue;
+        }
+
+        println!("{} Testing {}", style("→").blue().bold(), pkg(&member.name));
+        let mut cmd = tokio::process::Command::new(swift);
+        cmd.arg("test").current_dir(&member.path);
+        if let Some(dir) = target_dir {
+            cmd.arg("--scratch-path")
+                .arg(gust_build::target_dir(&member.path, Some(dir)));
+        }
+        if let Some(filter) = test_filter {
+            cmd.arg("--filter").arg(filter);
+        }
+        let (success, output) = run_streaming(cmd).await?;
+
+        results.push(MemberTests {
+            name: member.name.clone(),
+            outcome: MemberOutcome::Ran {
+                success,
+                counts: TestCounts::parse(&output),
+            },
+        });
+    }
+    Ok(results)
+}
+
+/// Run `cmd`, echoing its stdout while keeping a copy to parse.
+async fn run_streaming(mut cmd: tokio::process::Command) -> Result<(bool, String)> {
+    let mut child = cmd.stdout(Stdio::piped()).spawn().into_diagnostic()?;
+    let mut output = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await.into_diagnostic()? {
+            println!("{}", line);
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    let status = child.wait().await.into_diagnostic()?;
+    Ok((status.success(), output))
+}
+
+/// Print a per-member summary and fail if any member's tests failed.
+fn report(results: &[MemberTests]) -> Result<()> {
+    println!();
+    println!(
+        "{:<30} {:<8} {:<8} {:<8} {}",
+        style("Member").bold(),
+        style("Passed").bold(),
+        style("Failed").bold(),
+        style("Skipped").bold(),
+        style("Result").bold()
+    );
+    separator(70);
+
+    let mut total = TestCounts::default();
+    let mut failed = Vec::new();
+    for result in results {
+        match &result.outcome {
+            MemberOutcome::NoTests => println!(
+                "{:<30} {:<8} {:<8} {:<8} {}",
+                pkg(&result.name),
+                "-",
+                "-",
+                "-",
+                dim("no tests")
+            ),
+            MemberOutcome::Ran { success, counts } => {
+                total.add(*counts);
+                let verdict = if *success {
+                    green("ok").to_string()
+                } else {
+                    failed.push(result.name.as_str());
+                    style("FAILED").red().bold().to_string()
+                };
+                println!(
+                    "{:<30} {:<8} {:<8} {:<8} {}",
+                    pkg(&result.name),
+                    counts.passed,
+                    counts.failed,
+                    counts.skipped,
+                    verdict
+                );
+            }
+        }
+    }
+    println!();
+
+    if !failed.is_empty() {
+        return Err(miette::miette!(
+            "Tests failed in {} member(s): {} ({})",
+            failed.len(),
+            failed.join(", "),
+            total
+        ));
+    }
+    ui::success(format!("Tests passed ({})", total));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gust_testkit::git;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_workspace_runs_every_member_and_fails_if_one_fails() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().join("repo");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Gust.toml"),
+            "[package]\nname = \"repo\"\nversion = \"0.1.0\"\n\n[workspace]\nmembers = [\"packages/*\"]\n",
+        )
+        .unwrap();
+        let member = |name: &str, deps: &str, tests: bool| {
+            let dir = root.join("packages").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            let mut manifest = format!(
+                "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}\n\n[[target]]\nname = \"{name}\"\ntype = \"library\"\n"
+            );
+            if tests {
+                manifest.push_str(&format!(
+                    "\n[[target]]\nname = \"{name}Tests\"\ntype = \"test\"\n"
+                ));
+            }
+            fs::write(dir.join("Gust.toml"), manifest).unwrap();
+            dir
+        };
+        member("core", "", true);
+        let app = member("app", "core = { path = \"../core\" }", true);
+        member("docs", "", false);
+        fs::write(app.join("FAIL"), "").unwrap();
+
+        // Stand-in for `swift test` that logs where it ran and how, and
+        // fails in members containing a FAIL file
+        let log = tmp.path().join("runs.log");
+        let args = tmp.path().join("args.log");
+        let swift = tmp.path().join("swift");
+        fs::write(
+            &swift,
+            format!(
+                "#!/bin/sh\nbasename \"$PWD\" >> {log}\necho \"$@\" >> {args}\nif [ -e FAIL ]; then\n  echo \"Test Case 'T.testA' passed (0.001 seconds)\"\n  echo \"\t Executed 3 tests, with 2 failures (0 unexpected) in 0.1 (0.1) seconds\"\n  exit 1\nfi\necho \"\t Executed 4 tests, with 0 failures (0 unexpected) in 0.1 (0.1) seconds\"\n",
+                log = log.display(),
+                args = args.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&swift, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let results = run_workspace_tests(&root, None, None, None, None, &swift)
+            .await
+            .unwrap();
+
+        // Dependencies are tested first, and the failure didn't stop the run
+        assert_eq!(fs::read_to_string(&log).unwrap(), "core\napp\n");
+        assert_eq!(
+            results,
+            vec![
+                MemberTests {
+                    name: "core".to_string(),
+                    outcome: MemberOutcome::Ran {
+                        success: true,
+                        counts: TestCounts {
+                            passed: 4,
+                            failed: 0,
+                            skipped: 0,
+                        },
+                    },
+                },
+                MemberTests {
+                    name: "app".to_string(),
+                    outcome: MemberOutcome::Ran {
+                        success: false,
+                        counts: TestCounts {
+                            passed: 1,
+                            failed: 2,
+                            skipped: 0,
+                        },
+                    },
+                },
+                MemberTests {
+                    name: "docs".to_string(),
+                    outcome: MemberOutcome::NoTests,
+                },
+            ]
+        );
+        let err = report(&results).unwrap_err();
+        assert!(err.to_string().contains("1 member(s): app"), "{}", err);
+
+        // --package narrows the members, and --filter reaches swift test
+        let results = run_workspace_tests(&root, Some("core"), None, Some("testA"), None, &swift)
+            .await
+            .unwrap();
+        assert!(fs::read_to_string(&args)
+            .unwrap()
+            .ends_with("test --filter testA\n"));
+        assert_eq!(results.len(), 1);
+        assert!(report(&results).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_since_selects_changed_members_and_their_dependents() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().join("repo");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Gust.toml"),
+            "[package]\nname = \"repo\"\nversion = \"0.1.0\"\n\n[workspace]\nmembers = [\"packages/*\"]\n",
+        )
+        .unwrap();
+        let member = |name: &str, deps: &str| {
+            let dir = root.join("packages").join(name);
+            fs::create_dir_all(dir.join("Sources")).unwrap();
+            fs::write(
+                dir.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}\n\n[[target]]\nname = \"{name}Tests\"\ntype = \"test\"\n"
+                ),
+            )
+            .unwrap();
+            fs::write(dir.join("Sources").join("lib.swift"), "").unwrap();
+            dir
+        };
+        let core = member("core", "");
+        member("app", "core = { path = \"../core\" }");
+        member("util", "");
+        gust_testkit::git_repo(&root);
+
+        let log = tmp.path().join("runs.log");
+        let swift = tmp.path().join("swift");
+        fs::write(
+            &swift,
+            format!("#!/bin/sh\nbasename \"$PWD\" >> {}\n", log.display()),
+        )
+        .unwrap();
+        fs::set_permissions(&swift, fs::Permissions::from_mode(0o755)).unwrap();
+        let tested = |results: Vec<MemberTests>| {
+            let runs = fs::read_to_string(&log).unwrap_or_default();
+            fs::remove_file(&log).ok();
+            assert_eq!(
+                runs.lines().collect::<Vec<_>>(),
+                results.iter().map(|r| r.name.as_str()).collect::<Vec<_>>()
+            );
+            results.into_iter().map(|r| r.name).collect::<Vec<_>>()
+        };
+
+        // Nothing changed yet
+        let results = run_workspace_tests(&root, None, Some("HEAD"), None, None, &swift)
+            .await
+            .unwrap();
+        assert!(tested(results).is_empty());
+
+        // A change in core reaches app, which depends on it, but not util
+        fs::write(core.join("Sources").join("lib.swift"), "// changed\n").unwrap();
+        let results = run_workspace_tests(&root, None, Some("HEAD"), None, None, &swift)
+            .await
+            .unwrap();
+        assert_eq!(tested(results), ["core", "app"]);
+
+        // --package narrows the affected members further
+        let results = run_workspace_tests(&root, Some("app"), Some("HEAD"), None, None, &swift)
+            .await
+            .unwrap();
+        assert_eq!(tested(results), ["app"]);
+
+        // The root manifest holds shared dependencies, so every member is
+        // affected when it changes
+        git(&root, &["commit", "-qam", "core"]);
+        let manifest = fs::read_to_string(root.join("Gust.toml")).unwrap();
+        fs::write(
+            root.join("Gust.toml"),
+            format!("{manifest}\n[workspace.dependencies]\nlog = \"1.0\"\n"),
+        )
+        .unwrap();
+        let results = run_workspace_tests(&root, None, Some("HEAD"), None, None, &swift)
+            .await
+            .unwrap();
+        assert_eq!(tested(results).len(), 3);
+
+        let err = run_workspace_tests(&root, None, Some("no-such-ref"), None, None, &swift)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no-such-ref"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_changed_files_include_renames_and_untracked_files() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("core")).unwrap();
+        fs::create_dir_all(root.join("util")).unwrap();
+        fs::write(root.join("core/Moved.swift"), "struct Moved {}\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        gust_testkit::git_repo(root);
+
+        // A file moved between members, a new file nobody has added yet, and
+        // one git ignores
+        git(root, &["mv", "core/Moved.swift", "util/Moved.swift"]);
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(root.join("app/New.swift"), "").unwrap();
+        fs::write(root.join("app/build.log"), "").unwrap();
+
+        let mut changed = changed_files(root, "HEAD").await.unwrap();
+        changed.sort();
+        assert_eq!(
+            changed,
+            ["app/New.swift", "core/Moved.swift", "util/Moved.swift"].map(PathBuf::from)
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Persistent settings from `config.toml` files.
+//!
+//! Settings are read from `~/.gust/config.toml` and then from
+//! `.gust/config.toml` in the project, whose values win. For each setting
+//! the precedence is: CLI flag > environment variable > project config >
+//! user config > built-in default.
+
+use gust_manifest::ResolutionSources;
+use miette::{IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the config file inside a `.gust` directory.
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Every key `gust config` knows, with a one-line description.
+pub const KEYS: &[(&str, &str)] = &[
+    (
+        "jobs",
+        "Default number of parallel jobs (--jobs, GUST_JOBS)",
+    ),
+    ("cache-dir", "Global cache directory (GUST_CACHE_DIR)"),
+    ("registry", "Package registry URL (GUST_REGISTRY_URL)"),
+    (
+        "registry-rate-limit",
+        "Most registry requests per second (GUST_REGISTRY_RATE_LIMIT)",
+    ),
+    (
+        "registry-scope",
+        "Registry scope of packages named without one (GUST_REGISTRY_SCOPE)",
+    ),
+    (
+        "color",
+        "Colored output; false is like --no-color (NO_COLOR)",
+    ),
+    (
+        "auto-install",
+        "Install after `gust add` unless --no-install is given",
+    ),
+    (
+        "overrides.<package>",
+        "Version to force for a package, below Gust.toml's [overrides]",
+    ),
+    (
+        "constraints.<package>",
+        "Extra version requirement, below Gust.toml's [constraints]",
+    ),
+];
+
+/// Settings from a `config.toml` file. Unset keys fall through to the next
+/// layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct GustConfig {
+    /// Number of packages to fetch and parse at once, used when neither
+    /// `--jobs` nor `GUST_JOBS` is given. By default this follows CPU count
+    /// and available memory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+
+    /// Where the global package and binary caches live. `GUST_CACHE_DIR`
+    /// takes precedence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Registry used for `gust search`, `gust publish` and registry
+    /// dependencies. `GUST_REGISTRY_URL` takes precedence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+
+    /// Most requests per second sent to the registry; unlimited by default.
+    /// `GUST_REGISTRY_RATE_LIMIT` takes precedence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry_rate_limit: Option<u32>,
+
+    /// Scope of registry packages named without one, such as `apple` for
+    /// `swift-log`. Without it they must be written `scope.name`.
+    /// `GUST_REGISTRY_SCOPE` takes precedence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry_scope: Option<String>,
+
+    /// Whether to color output. `--no-color` and `NO_COLOR` take
+    /// precedence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<bool>,
+
+    /// Whether `gust add` installs right away. `--install`/`--no-install`
+    /// take precedence. Only `true` has an effect: it beats the manifest's
+    /// `[gust] auto-install`, while `false` leaves the choice to the
+    /// manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_install: Option<bool>,
+
+    /// Versions to force, by package, for projects that don't say
+    /// otherwise. Gust.toml's `[overrides]` and `--override` take
+    /// precedence.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, String>,
+
+    /// Extra version requirements, by package, for projects that don't say
+    /// otherwise. Gust.toml's `[constraints]` and `--constraint` take
+    /// precedence.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub constraints: BTreeMap<String, String>,
+}
+
+impl GustConfig {
+    /// `~/.gust/config.toml`.
+    pub fn user_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".gust").join(CONFIG_FILE))
+    }
+
+    /// `<project>/.gust/config.toml`.
+    pub fn project_path(project_dir: &Path) -> PathBuf {
+        project_dir.join(".gust").join(CONFIG_FILE)
+    }
+
+    /// User config overlaid with the config of the project in `project_dir`.
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        let user = match Self::user_path() {
+            Some(path) => Self::read(&path)?,
+            None => Self::default(),
+        };
+        Ok(user.overlay(Self::read(&Self::project_path(project_dir))?))
+    }
+
+    /// Parse the config file at `path`; a missing file is an empty config.
+    pub fn read(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| miette::miette!("Invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).into_diagnostic(),
+        }
+    }
+
+    /// Write this config to `path`, creating its directory.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).into_diagnostic()?;
+        }
+        let content = toml::to_string_pretty(self).into_diagnostic()?;
+        fs::write(path, content).into_diagnostic()
+    }
+
+    /// This config with every key set in `over` replaced. Overrides and
+    /// constraints are replaced package by package.
+    pub fn overlay(mut self, over: Self) -> Self {
+        self.overrides.extend(over.overrides);
+        self.constraints.extend(over.constraints);
+        Self {
+            jobs: over.jobs.or(self.jobs),
+            cache_dir: over.cache_dir.or(self.cache_dir),
+            registry: over.registry.or(self.registry),
+            registry_rate_limit: over.registry_rate_limit.or(self.registry_rate_limit),
+            registry_scope: over.registry_scope.or(self.registry_scope),
+            color: over.color.or(self.color),
+            auto_install: over.auto_install.or(self.auto_install),
+            overrides: self.overrides,
+            constraints: self.constraints,
+        }
+    }
+
+    /// The overrides and constraints, as the lowest layer of a project's.
+    pub fn resolution_sources(&self) -> ResolutionSources {
+        ResolutionSources {
+            overrides: self.overrides.clone(),
+            constraints: self.constraints.clone(),
+        }
+    }
+
+    /// The value of `key` as text, or `None` if unset.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        if let Some((table, package)) = package_key(key) {
+            return Ok(self.table(table).get(package).cloned());
+        }
+        Ok(match key {
+            "jobs" => self.jobs.map(|v| v.to_string()),
+            "cache-dir" => self.cache_dir.as_ref().map(|v| v.display().to_string()),
+            "registry" => self.registry.clone(),
+            "registry-rate-limit" => self.registry_rate_limit.map(|v| v.to_string()),
+            "registry-scope" => self.registry_scope.clone(),
+            "color" => self.color.map(|v| v.to_string()),
+            "auto-install" => self.auto_install.map(|v| v.to_string()),
+            _ => return Err(unknown_key(key)),
+        })
+    }
+
+    /// Set `key` from text, or clear it when `value` is `None`.
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        if let Some((table, package)) = package_key(key) {
+            if let Some(req) = value {
+                gust_types::VersionReq::parse(req)
+                    .map_err(|e| miette::miette!("Invalid value '{}' for {}: {}", req, key, e))?;
+            }
+            let table = self.table_mut(table);
+            match value {
+                Some(req) => table.insert(package.to_string(), req.to_string()),
+                None => table.remove(package),
+            };
+            return Ok(());
+        }
+        match key {
+            "jobs" => self.jobs = value.map(|v| parse(key, v)).transpose()?,
+            "cache-dir" => self.cache_dir = value.map(PathBuf::from),
+            "registry" => self.registry = value.map(String::from),
+            "registry-rate-limit" => {
+                self.registry_rate_limit = value.map(|v| parse(key, v)).transpose()?
+            }
+            "registry-scope" => self.registry_scope = value.map(String::from),
+            "color" => self.color = value.map(|v| parse(key, v)).transpose()?,
+            "auto-install" => self.auto_install = value.map(|v| parse(key, v)).transpose()?,
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// The `overrides` or `constraints` table.
+    pub fn table(&self, table: &str) -> &BTreeMap<String, String> {
+        match table {
+            "overrides" => &self.overrides,
+            _ => &self.constraints,
+        }
+    }
+
+    fn table_mut(&mut self, table: &str) -> &mut BTreeMap<String, String> {
+        match table {
+            "overrides" => &mut self.overrides,
+            _ => &mut self.constraints,
+        }
+    }
+}
+
+/// `overrides.<package>` or `constraints.<package>`, split in two.
+pub fn package_key(key: &str) -> Option<(&str, &str)> {
+    key.split_once('.').filter(|(table, package)| {
+        matches!(*table, "overrides" | "constraints") && !package.is_empty()
+    })
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| miette::miette!("Invalid value '{}' for {}: {}", value, key, e))
+}
+
+fn unknown_key(key: &str) -> miette::Report {
+    let known = KEYS.iter().map(|(k, _)| *k);
+    match gust_diagnostics::closest_match(key, known) {
+        Some(suggestion) => miette::miette!(
+            help = format!("did you mean `{}`?", suggestion),
+            "Unknown config key '{}'",
+            key
+        ),
+        None => miette::miette!("Unknown config key '{}'", key),
+    }
+}
+
+/// The first of a CLI flag, environment variable and config value that is
+/// set.
+pub fn resolve<T>(flag: Option<T>, env: Option<T>, config: Option<T>) -> Option<T> {
+    flag.or(env).or(config)
+}
+
+/// An environment variable, treating an empty value as unset.
+pub fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Hand config values to the lower crates that use them, below their
+/// environment variables. Call before opening the cache or a registry
+/// client.
+pub fn configure_crates(config: &GustConfig) {
+    if let Some(dir) = &config.cache_dir {
+        gust_cache::configure_cache_dir(dir.clone());
+    }
+    gust_registry::configure(config.registry.clone(), config.registry_rate_limit);
+    if let Some(scope) = &config.registry_scope {
+        gust_registry::configure_scope(scope.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence() {
+        let user = GustConfig {
+            jobs: Some(2),
+            registry: Some("https://user.example".to_string()),
+            color: Some(false),
+            ..Default::default()
+        };
+        let project = GustConfig {
+            jobs: Some(6),
+            ..Default::default()
+        };
+        let config = user.overlay(project);
+
+        // Project beats user, user beats default
+        assert_eq!(config.jobs, Some(6));
+        assert_eq!(config.registry.as_deref(), Some("https://user.example"));
+        assert_eq!(resolve(None, None, config.jobs), Some(6));
+        assert_eq!(resolve(None, None, config.auto_install), None);
+
+        // Env beats config, flag beats env
+        assert_eq!(
+            resolve(
+                None,
+                Some("https://env.example".to_string()),
+                config.registry.clone()
+            )
+            .as_deref(),
+            Some("https://env.example")
+        );
+        assert_eq!(resolve(Some(1), Some(3), config.jobs), Some(1));
+    }
+
+    #[test]
+    fn test_set_get_round_trip() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = GustConfig::project_path(tmp.path());
+
+        let mut config = GustConfig::read(&path).unwrap();
+        assert_eq!(config, GustConfig::default());
+        config.set("jobs", Some("4")).unwrap();
+        config.set("auto-install", Some("true")).unwrap();
+        config.write(&path).unwrap();
+
+        let mut config = GustConfig::read(&path).unwrap();
+        assert_eq!(config.get("jobs").unwrap().as_deref(), Some("4"));
+        assert_eq!(config.get("auto-install").unwrap().as_deref(), Some("true"));
+        assert_eq!(config.get("registry").unwrap(), None);
+
+        config.set("jobs", None).unwrap();
+        assert_eq!(config.get("jobs").unwrap(), None);
+        assert!(config.set("jobs", Some("many")).is_err());
+        assert!(config.get("job").is_err());
+    }
+
+    #[test]
+    fn test_overrides_merge_per_package() {
+        let mut user = GustConfig::default();
+        user.set("overrides.swift-log", Some("=1.5.4")).unwrap();
+        user.set("overrides.swift-nio", Some("=2.60.0")).unwrap();
+        user.set("constraints.swift-atomics", Some(">=1.1"))
+            .unwrap();
+        assert!(user.set("overrides.swift-log", Some("newest")).is_err());
+        assert!(user.set("overrides.", Some("1.0.0")).is_err());
+
+        let mut project = GustConfig::default();
+        project.set("overrides.swift-nio", Some("=2.62.0")).unwrap();
+        let config = user.overlay(project);
+        assert_eq!(
+            config.get("overrides.swift-log").unwrap().as_deref(),
+            Some("=1.5.4")
+        );
+        assert_eq!(
+            config.get("overrides.swift-nio").unwrap().as_deref(),
+            Some("=2.62.0")
+        );
+
+        let sources = config.resolution_sources();
+        assert_eq!(sources.overrides.len(), 2);
+        assert_eq!(sources.constraints["swift-atomics"], ">=1.1");
+
+        // Written as tables
+        let toml = toml::to_string_pretty(&config).unwrap();
+        assert!(toml.contains("[overrides]"), "{}", toml);
+        assert_eq!(toml::from_str::<GustConfig>(&toml).unwrap(), config);
+    }
+}
@@ -0,0 +1,68 @@
+//! Dependencies being edited in place, from `.gust/edits.toml`.
+//!
+//! `gust edit` points a dependency's `.build/checkouts` link at a local copy
+//! and records it here, so later installs keep linking the local copy until
+//! `gust unedit`. Gust.toml and Gust.lock are left alone, which is what lets
+//! `gust unedit` go back to the locked revision.
+
+use miette::{IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the edits file inside a project's `.gust` directory.
+pub const EDITS_FILE: &str = "edits.toml";
+
+/// Directory `gust edit` clones into when no `--path` is given.
+pub const EDITS_DIR: &str = "edits";
+
+/// Packages being edited, each with the local copy that replaces it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Edits {
+    /// Package name to local copy, relative to the project unless absolute
+    #[serde(default)]
+    pub packages: BTreeMap<String, PathBuf>,
+}
+
+impl Edits {
+    /// `<project>/.gust/edits.toml`.
+    pub fn path(project_dir: &Path) -> PathBuf {
+        project_dir.join(".gust").join(EDITS_FILE)
+    }
+
+    /// Edits of the project in `project_dir`; none if the file is missing.
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        let path = Self::path(project_dir);
+        match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| miette::miette!("Invalid {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).into_diagnostic(),
+        }
+    }
+
+    /// Write these edits for the project in `project_dir`, removing the file
+    /// once nothing is being edited.
+    pub fn save(&self, project_dir: &Path) -> Result<()> {
+        let path = Self::path(project_dir);
+        if self.packages.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).into_diagnostic(),
+                _ => Ok(()),
+            };
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).into_diagnostic()?;
+        }
+        let content = toml::to_string_pretty(self).into_diagnostic()?;
+        fs::write(path, content).into_diagnostic()
+    }
+
+    /// Absolute path of the local copy replacing `name`, if it's being
+    /// edited.
+    pub fn local_copy(&self, project_dir: &Path, name: &str) -> Option<PathBuf> {
+        self.packages.get(name).map(|path| project_dir.join(path))
+    }
+}
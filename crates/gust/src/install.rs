@@ -2,18 +2,28 @@
 //!
 //! Coordinates: manifest → resolve → fetch → cache → link
 
+use crate::commands::{effective_manifest, ui};
+use crate::edits::Edits;
+use crate::reporter::{Event, Reporter, ReporterKind};
 use console::style;
 use gust_cache::GlobalCache;
-use gust_fetch::{FetchResult, FetchStatus, Fetcher};
-use gust_lockfile::{LockedPackage, Lockfile, LockfileDiff};
-use gust_manifest::{find_manifest, parse_transitive_deps};
-use gust_resolver::{Resolution, ResolvedDep};
-use gust_types::{Dependency, DependencySource, Manifest, Version};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use gust_diagnostics::GustError;
+use gust_fetch::{BinaryArtifact, FetchResult, FetchStatus, Fetcher};
+use gust_lockfile::{LockedArtifact, LockedPackage, Lockfile, LockfileDiff, PackageResolved};
+use gust_manifest::{parse_transitive_deps, FeatureSet, ManifestType, ResolutionSources};
+use gust_resolver::{
+    ChoiceReason, LockfileHints, RegistryProvider, Resolution, ResolutionTrace, ResolvedDep,
+    Resolver,
+};
+use gust_types::{
+    Dependency, DependencyBuildSettings, DependencySource, Manifest, ResolutionOptions,
+    ResolvedPackage, TargetType, Version,
+};
 use miette::{IntoDiagnostic, Result};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Installation options.
 #[derive(Debug, Clone, Default)]
@@ -22,6 +32,62 @@ pub struct InstallOptions {
     pub frozen: bool,
     /// Number of parallel downloads
     pub concurrency: usize,
+    /// Skip the root package's dev-dependencies
+    pub no_dev: bool,
+    /// Print why each package version was chosen
+    pub explain: bool,
+    /// Directory for checkouts and artifacts instead of `.build`
+    pub target_dir: Option<PathBuf>,
+    /// Features whose optional dependencies to install, on top of `default`
+    pub features: Vec<String>,
+    /// Don't enable the `default` feature
+    pub no_default_features: bool,
+    /// Dependency groups to install along with the regular dependencies
+    pub groups: Vec<String>,
+    /// Install what can be installed when some packages fail to fetch, and
+    /// report every failure at the end
+    pub keep_going: bool,
+    /// Don't fetch anything; git dependencies must already be in the global
+    /// cache
+    pub offline: bool,
+    /// How progress is shown; `None` picks from whether stdout is a terminal
+    pub reporter: Option<ReporterKind>,
+    /// Remove cached git checkouts no lockfile needs after installing
+    pub prune: Option<PruneScope>,
+    /// How path dependencies are placed in the global cache
+    pub path_mode: PathMode,
+    /// Levels of transitive dependencies to resolve; `None` means
+    /// [`DEFAULT_MAX_DEPTH`]
+    pub max_depth: Option<usize>,
+    /// Fail instead of warning when dependencies are left past `max_depth`
+    pub strict_depth: bool,
+    /// `--override` and `--constraint` values, which beat Gust.toml's
+    pub resolution_flags: ResolutionSources,
+    /// Overrides and constraints from config, which Gust.toml's beat
+    pub resolution_defaults: ResolutionSources,
+}
+
+/// Levels of transitive dependencies resolved unless `--max-depth` says
+/// otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 20;
+
+/// Which cached git checkouts `--prune` may remove.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneScope {
+    /// Packages this project's lockfile dropped
+    Project,
+    /// Every checkout in the global cache
+    Global,
+}
+
+/// How a path dependency gets into the global cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PathMode {
+    /// Link to the source directory, so edits show up right away
+    #[default]
+    Symlink,
+    /// Copy the source directory, so the install is a snapshot
+    Copy,
 }
 
 /// The package installer.
@@ -32,44 +98,114 @@ pub struct Installer {
     cache: GlobalCache,
     /// Package fetcher
     fetcher: Fetcher,
+    /// Registry that registry dependencies resolve against
+    registry: gust_registry::RegistryClient,
     /// Installation options
     options: InstallOptions,
+    /// Where progress goes
+    reporter: Arc<dyn Reporter>,
 }
 
 impl Installer {
     /// Create a new installer for the given project.
     pub fn new(project_dir: PathBuf, options: InstallOptions) -> Result<Self> {
         let cache = GlobalCache::open().into_diagnostic()?;
-        let fetcher = Fetcher::new().with_concurrency(options.concurrency);
+        Ok(Self::with_cache(project_dir, options, cache))
+    }
 
-        Ok(Self {
+    /// Create an installer that uses a specific package cache.
+    pub fn with_cache(project_dir: PathBuf, options: InstallOptions, cache: GlobalCache) -> Self {
+        let fetcher = Fetcher::new()
+            .with_concurrency(options.concurrency)
+            .with_copy_paths(options.path_mode == PathMode::Copy);
+        let reporter = options
+            .reporter
+            .unwrap_or_else(ReporterKind::detect)
+            .reporter();
+
+        Self {
             project_dir,
             cache,
             fetcher,
+            registry: gust_registry::RegistryClient::from_env(),
             options,
-        })
+            reporter,
+        }
+    }
+
+    /// Show progress with `reporter` instead of the one `options` picks.
+    pub fn with_reporter(mut self, reporter: Arc<dyn Reporter>) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Where this installer's progress goes.
+    pub fn reporter(&self) -> &dyn Reporter {
+        self.reporter.as_ref()
+    }
+
+    /// Record the project with the cache after a successful install, then
+    /// prune the cache if asked to. Neither failing fails the install.
+    fn finish_cache(&self, previous: Option<&Lockfile>, current: &Lockfile) {
+        if let Err(e) = self.cache.register_project(&self.project_dir) {
+            tracing::debug!("Failed to register project with the cache: {}", e);
+        }
+        let Some(scope) = self.options.prune else {
+            return;
+        };
+        match prune_cache(&self.cache, &self.project_dir, previous, current, scope) {
+            Ok(pruned) => {
+                for name in &pruned {
+                    self.reporter
+                        .report(Event::Detail(&format!("  Pruned {}", name)));
+                }
+                self.reporter.report(Event::Finished(&format!(
+                    "Pruned {} cached checkouts",
+                    style(pruned.len()).cyan()
+                )));
+            }
+            Err(e) => self
+                .reporter
+                .report(Event::Info(&format!("Skipped pruning the cache: {}", e))),
+        }
+    }
+
+    /// Whether the cache entry at `dest` can be used as it is.
+    ///
+    /// A path dependency is only reused when it's a link in symlink mode;
+    /// copy mode takes a fresh snapshot on every install.
+    fn is_cached(&self, is_path: bool, dest: &Path) -> bool {
+        if is_path {
+            self.options.path_mode == PathMode::Symlink && dest.is_symlink()
+        } else {
+            dest.exists()
+        }
+    }
+
+    fn checkout_dir(&self, name: &str, dep: &Dependency) -> PathBuf {
+        checkout_dir(&self.cache, name, dep)
+    }
+
+    /// Directory checkouts and artifacts are linked into.
+    fn target_dir(&self) -> PathBuf {
+        gust_build::target_dir(&self.project_dir, self.options.target_dir.as_deref())
     }
 
     /// Run the full installation flow.
     pub async fn install(&self) -> Result<InstallResult> {
-        let mp = MultiProgress::new();
+        let mut timings = InstallTimings::default();
+        let mut phase_start = Instant::now();
 
         // Step 1: Parse manifest
-        let spinner = mp.add(ProgressBar::new_spinner());
-        spinner.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.blue} {msg}")
-                .unwrap(),
-        );
-        spinner.set_message("Reading manifest...");
-        spinner.enable_steady_tick(std::time::Duration::from_millis(100));
-
-        let (manifest, _manifest_type) = find_manifest(&self.project_dir).into_diagnostic()?;
-        spinner.finish_with_message(format!(
-            "{} Read manifest for {}",
-            style("✓").green(),
+        self.reporter.report(Event::Started("Reading manifest..."));
+
+        let (manifest, manifest_type) = effective_manifest(&self.project_dir)?;
+        let manifest = self.selected_manifest(&manifest)?;
+        self.reporter.report(Event::Finished(&format!(
+            "Read manifest for {}",
             style(&manifest.package.name).cyan()
-        ));
+        )));
+        timings.record_phase("manifest", &mut phase_start);
 
         // Step 2: Check lockfile
         let lockfile_path = self.project_dir.join("Gust.lock");
@@ -78,118 +214,416 @@ impl Installer {
         } else {
             None
         };
+        self.refresh_changed_dependencies(&manifest, existing_lockfile.as_ref())?;
 
-        // Step 3: Resolve dependencies (with parallel transitive parsing)
-        let resolution = self
-            .resolve(&manifest, existing_lockfile.as_ref(), &mp)
-            .await?;
+        // Packages that failed to fetch under --keep-going, with the error
+        let mut failures = BTreeMap::new();
+        // Packages past --max-depth, which keep Gust.lock from being written
+        let mut unresolved = BTreeSet::new();
+
+        // Step 3: Resolve dependencies (with parallel transitive parsing),
+        // or with --frozen take them from the lockfile as they are
+        let (mut resolution, trace, depth) = if self.options.frozen {
+            let Some(lockfile) = &existing_lockfile else {
+                return Err(miette::miette!(
+                    "No lockfile found but --frozen was specified"
+                ));
+            };
+            let (resolution, trace) = self.locked_resolution(&manifest, lockfile)?;
+            (resolution, trace, None)
+        } else {
+            let (resolution, trace, depth) = self
+                .resolve(
+                    &manifest,
+                    existing_lockfile.as_ref(),
+                    &mut timings,
+                    &mut failures,
+                    &mut unresolved,
+                )
+                .await?;
+            (resolution, trace, Some(depth))
+        };
         let pkg_count = resolution.packages.len();
+        timings.record_phase("resolve", &mut phase_start);
 
-        println!(
-            "{} Resolved {} total packages",
-            style("✓").green(),
-            style(pkg_count).cyan()
-        );
+        self.reporter.report(Event::Finished(&match depth {
+            Some(depth) if pkg_count > 0 => format!(
+                "Resolved {} total packages (depth {})",
+                style(pkg_count).cyan(),
+                depth
+            ),
+            _ => format!("Resolved {} total packages", style(pkg_count).cyan()),
+        }));
 
-        if pkg_count == 0 {
-            println!("{} No dependencies to install", style("✓").green().bold());
-            return Ok(InstallResult { installed: 0 });
+        for warning in trace.warnings() {
+            self.reporter.report(Event::Info(warning));
         }
 
-        // Step 4: Fetch packages
-        let fetch_results = self.fetch_packages(&mp, &resolution).await?;
+        if self.options.explain && pkg_count > 0 {
+            self.reporter.report(Event::Detail(
+                &style("Resolution trace:").bold().to_string(),
+            ));
+            for line in trace.explain().lines() {
+                self.reporter.report(Event::Detail(&format!("  {}", line)));
+            }
+        }
 
-        // Step 5: Link packages to project
-        let spinner = mp.add(ProgressBar::new_spinner());
-        spinner.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.blue} {msg}")
-                .unwrap(),
-        );
-        spinner.set_message("Linking packages...");
-        spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+        if pkg_count == 0 && locked_artifacts(&manifest).is_empty() {
+            let checkouts_dir = self.target_dir().join("checkouts");
+            prune_checkouts(&checkouts_dir, &HashMap::new()).into_diagnostic()?;
+            if !failures.is_empty() {
+                return Err(failures_error(&failures));
+            }
+            self.finish_cache(existing_lockfile.as_ref(), &Lockfile::default());
+            self.reporter
+                .report(Event::Finished("No dependencies to install"));
+            return Ok(InstallResult {
+                installed: 0,
+                links: LinkStats::default(),
+                timings,
+            });
+        }
+
+        // Step 4: Fetch packages and binary target artifacts
+        let fetch_results = self
+            .fetch_packages(&resolution, &mut timings, &mut failures)
+            .await?;
+        resolution
+            .packages
+            .retain(|name, _| !failures.contains_key(name));
+        let checksums = checkout_checksums(&self.cache, &resolution, &fetch_results)?;
+        if let Some(lockfile) = &existing_lockfile {
+            verify_locked_checksums(lockfile, &resolution, &fetch_results, &checksums)?;
+        }
+        // Checkouts not yet in the content-addressed store are added to it.
+        // Path dependencies are the user's own files and are left out.
+        for (name, resolved) in &resolution.packages {
+            if let (Some(fetched), gust_resolver::ResolvedSource::Git { .. }) =
+                (fetch_results.get(name), &resolved.source)
+            {
+                if let Some(revision) = fetched_revision(fetched) {
+                    self.store_checkout(fetched, &resolved.version, &revision)
+                        .await;
+                }
+            }
+        }
+        let artifacts = self.fetch_artifacts(&manifest).await?;
+        timings.record_phase("fetch", &mut phase_start);
 
-        let linked = self.link_packages(&resolution, &fetch_results)?;
+        // Step 5: Link packages to project
+        self.reporter.report(Event::Started("Linking packages..."));
 
-        spinner.finish_with_message(format!(
-            "{} Linked {} packages",
-            style("✓").green(),
-            style(linked).cyan()
-        ));
+        let edits = Edits::load(&self.project_dir)?;
+        let links = self.link_packages(&manifest, &resolution, &fetch_results, &edits)?;
+        self.link_artifacts(&manifest, &artifacts)?;
 
-        // Step 6: Update lockfile (incremental, async)
-        match self
-            .update_lockfile(
-                &lockfile_path,
-                &resolution,
-                &fetch_results,
-                existing_lockfile.as_ref(),
-            )
-            .await?
-        {
-            Some(diff) if diff.has_changes() => {
-                let summary = diff.summary();
-                println!(
-                    "{} Updated lockfile ({})",
-                    style("✓").green(),
-                    style(summary).dim()
-                );
-            }
-            Some(_) => {
-                println!("{} Lockfile unchanged", style("✓").green());
+        self.reporter.report(Event::Finished(&format!(
+            "Linked {} packages",
+            style(links.total()).cyan()
+        )));
+        for (name, local) in &edits.packages {
+            if fetch_results.contains_key(name) {
+                self.reporter.report(Event::Info(&format!(
+                    "{} is being edited at {}",
+                    ui::pkg(name),
+                    local.display()
+                )));
             }
-            None => {
-                tracing::debug!("Lockfile already up to date");
+        }
+        timings.record_phase("link", &mut phase_start);
+
+        // Step 6: Update lockfile (incremental, async), unless the
+        // resolution is missing packages it stopped short of
+        if !unresolved.is_empty() {
+            self.reporter.report(Event::Warning(
+                "Gust.lock was left as it was, since the resolution is incomplete",
+            ));
+        } else {
+            match self
+                .update_lockfile(
+                    &lockfile_path,
+                    &LockInputs {
+                        manifest: &manifest,
+                        resolution: &resolution,
+                        fetch_results: &fetch_results,
+                        checksums: &checksums,
+                        existing_lockfile: existing_lockfile.as_ref(),
+                        failures: &failures,
+                    },
+                )
+                .await?
+            {
+                Some(diff) if diff.has_changes() => {
+                    let summary = diff.summary();
+                    self.reporter.report(Event::Finished(&format!(
+                        "Updated lockfile ({})",
+                        style(summary).dim()
+                    )));
+                }
+                Some(_) => {
+                    self.reporter.report(Event::Finished("Lockfile unchanged"));
+                }
+                None => {
+                    tracing::debug!("Lockfile already up to date");
+                }
             }
         }
 
+        // Pin SwiftPM to the lockfile; Package.swift projects keep their own
+        if manifest_type == ManifestType::GustToml {
+            write_package_resolved(&self.project_dir, &manifest)?;
+        }
+        timings.record_phase("lockfile", &mut phase_start);
+
+        if !failures.is_empty() {
+            return Err(failures_error(&failures));
+        }
+        let current = Lockfile::load(&lockfile_path).unwrap_or_default();
+        self.finish_cache(existing_lockfile.as_ref(), &current);
+
         Ok(InstallResult {
             installed: fetch_results.len(),
+            links,
+            timings,
         })
     }
 
+    /// Resolve dependencies and write Gust.lock, without fetching into or
+    /// linking the project; the resolve half of [`Installer::install`].
+    ///
+    /// Git dependencies are still cloned into the global cache to read their
+    /// manifests, unless `offline`. With `frozen`, fails instead of writing
+    /// when the lockfile would change. Returns the changes, if any.
+    pub async fn lock(&self) -> Result<Option<LockfileDiff>> {
+        let (manifest, _) = effective_manifest(&self.project_dir)?;
+        let manifest = self.selected_manifest(&manifest)?;
+        let lockfile_path = self.project_dir.join("Gust.lock");
+        let existing_lockfile = if lockfile_path.exists() {
+            Some(Lockfile::load(&lockfile_path).into_diagnostic()?)
+        } else {
+            None
+        };
+        if self.options.frozen && existing_lockfile.is_none() {
+            return Err(miette::miette!(
+                "No lockfile found but --frozen was specified"
+            ));
+        }
+        self.refresh_changed_dependencies(&manifest, existing_lockfile.as_ref())?;
+
+        let mut failures = BTreeMap::new();
+        let mut unresolved = BTreeSet::new();
+        let (resolution, _, _) = self
+            .resolve(
+                &manifest,
+                existing_lockfile.as_ref(),
+                &mut InstallTimings::default(),
+                &mut failures,
+                &mut unresolved,
+            )
+            .await?;
+        if !unresolved.is_empty() {
+            return Err(miette::miette!(
+                help = "Raise --max-depth to resolve them",
+                "Gust.lock was not written, since dependencies past --max-depth were left unresolved"
+            ));
+        }
+
+        // Resolving leaves git dependencies in the cache, which is all the
+        // lockfile needs from them
+        let checkouts: HashMap<String, FetchResult> = resolution
+            .packages
+            .iter()
+            .filter_map(|(name, resolved)| {
+                let gust_resolver::ResolvedSource::Git { tag, .. } = &resolved.source else {
+                    return None;
+                };
+                let path = self.cache.git_dir().join(sanitize_name(name));
+                path.exists().then(|| {
+                    let result = FetchResult {
+                        name: name.clone(),
+                        path,
+                        checksum: String::new(),
+                        revision: None,
+                        tag: tag.clone(),
+                        bytes: None,
+                        duration: Duration::ZERO,
+                    };
+                    (name.clone(), result)
+                })
+            })
+            .collect();
+        let checksums = checkout_checksums(&self.cache, &resolution, &checkouts)?;
+
+        let inputs = LockInputs {
+            manifest: &manifest,
+            resolution: &resolution,
+            fetch_results: &checkouts,
+            checksums: &checksums,
+            existing_lockfile: existing_lockfile.as_ref(),
+            failures: &failures,
+        };
+        if let (true, Some(existing)) = (self.options.frozen, &existing_lockfile) {
+            let packages = self.locked_packages(&inputs);
+            let (diff, _) = existing.merge(packages);
+            if diff.has_changes() || existing.artifacts != locked_artifacts(&manifest) {
+                return Err(miette::miette!(
+                    help = "Run `gust resolve` without --frozen to update it",
+                    "Gust.lock is out of date ({})",
+                    diff.summary()
+                ));
+            }
+            return Ok(None);
+        }
+
+        self.update_lockfile(&lockfile_path, &inputs).await
+    }
+
+    /// `manifest` without the optional dependencies no enabled feature asks
+    /// for and the groups nobody asked for, which are neither fetched nor
+    /// locked.
+    fn selected_manifest(&self, manifest: &Manifest) -> Result<Manifest> {
+        Ok(FeatureSet::resolve(
+            manifest,
+            &self.options.features,
+            !self.options.no_default_features,
+        )
+        .and_then(|set| set.with_groups(manifest, &self.options.groups))
+        .into_diagnostic()?
+        .select(manifest))
+    }
+
+    /// Drop the cached checkouts of direct dependencies whose Gust.toml
+    /// requirement the lockfile no longer satisfies, e.g. after a tag was
+    /// edited, so resolving fetches them again instead of reusing the old
+    /// version. With `frozen`, such changes are an error instead.
+    fn refresh_changed_dependencies(
+        &self,
+        manifest: &Manifest,
+        lockfile: Option<&Lockfile>,
+    ) -> Result<()> {
+        let Some(lockfile) = lockfile else {
+            return Ok(());
+        };
+        let drift = gust_lockfile::manifest_lockfile_drift(manifest, lockfile);
+        if drift.unsatisfied.is_empty() {
+            return Ok(());
+        }
+
+        let changes: Vec<String> = drift.unsatisfied.iter().map(|d| d.to_string()).collect();
+        if self.options.frozen {
+            return Err(miette::miette!(
+                help = "Run without --frozen to resolve them again",
+                "Gust.lock is out of date with Gust.toml: {}",
+                changes.join("; ")
+            ));
+        }
+        if self.options.offline {
+            return Err(miette::miette!(
+                help = "Run without --offline to fetch them",
+                "Gust.toml changed since Gust.lock was written: {}",
+                changes.join("; ")
+            ));
+        }
+
+        for (dep, change) in drift.unsatisfied.iter().zip(&changes) {
+            self.reporter
+                .report(Event::Info(&format!("{}; resolving it again", change)));
+            let checkout = self.cache.git_dir().join(sanitize_name(&dep.name));
+            if checkout.exists() {
+                std::fs::remove_dir_all(&checkout).into_diagnostic()?;
+            }
+        }
+        Ok(())
+    }
+
     /// Resolve dependencies including transitive ones.
     ///
     /// This performs iterative resolution:
     /// 1. Fetch direct dependencies
     /// 2. Parse their manifests in parallel to discover transitive deps
     /// 3. Repeat until all dependencies are resolved
+    ///
+    /// Alongside the resolution, returns a trace of why each version was chosen
+    /// and the deepest level resolved, direct dependencies being level 1.
+    /// With `keep_going`, dependencies that can't be fetched are added to
+    /// `failures` and left out instead of failing the resolution.
+    ///
+    /// Dependencies deeper than `max_depth` are left out with a warning and
+    /// added to `unresolved`, or with `strict_depth` fail the resolution,
+    /// naming them either way.
+    ///
+    /// Overrides and constraints from config, Gust.toml and flags choose
+    /// registry versions; git packages are fetched at the tag they name, so
+    /// they're only checked against them.
     async fn resolve(
         &self,
         manifest: &Manifest,
         existing_lockfile: Option<&Lockfile>,
-        mp: &MultiProgress,
-    ) -> Result<Resolution> {
-        // If we have a lockfile and frozen mode, use it directly
-        if self.options.frozen {
-            if let Some(lockfile) = existing_lockfile {
-                return self.resolution_from_lockfile(lockfile);
-            } else {
-                return Err(miette::miette!(
-                    "No lockfile found but --frozen was specified"
-                ));
-            }
+        timings: &mut InstallTimings,
+        failures: &mut BTreeMap<String, String>,
+        unresolved: &mut BTreeSet<String>,
+    ) -> Result<(Resolution, ResolutionTrace, usize)> {
+        let options = ResolutionSources::merge(
+            &self.options.resolution_defaults,
+            manifest,
+            &self.options.resolution_flags,
+        )
+        .into_diagnostic()?;
+        let mut trace = ResolutionTrace::new();
+        let mut packages: HashMap<String, ResolvedDep> = HashMap::new();
+        let mut pending_deps = root_dependencies(manifest, !self.options.no_dev);
+        for (name, _) in &pending_deps {
+            trace.record_requirement(name, &manifest.package.name);
         }
 
-        let mut packages: HashMap<String, ResolvedDep> = HashMap::new();
-        let mut pending_deps: Vec<(String, Dependency)> = manifest
-            .dependencies
-            .iter()
-            .map(|(name, dep)| (name.clone(), dep.clone()))
-            .collect();
+        // Registry packages have no checkout to read dependencies from, so
+        // they resolve against the registry's release lists instead
+        let (registry_deps, rest): (Vec<_>, Vec<_>) = pending_deps
+            .into_iter()
+            .partition(|(_, dep)| dep.source_kind() == DependencySource::Registry);
+        pending_deps = rest;
+        if !registry_deps.is_empty() {
+            packages.extend(
+                self.resolve_registry(
+                    manifest,
+                    &registry_deps,
+                    existing_lockfile,
+                    &options,
+                    &mut trace,
+                )
+                .await?,
+            );
+        }
 
-        let mut iteration = 0;
-        const MAX_ITERATIONS: usize = 20; // Prevent infinite loops
+        // Checkouts the lockfile pins can be rebuilt from the package store
+        // instead of fetched, unless Gust.toml now asks for something else
+        let locked_checkouts: HashMap<&str, &LockedPackage> = existing_lockfile
+            .map(|lockfile| {
+                let drift = gust_lockfile::manifest_lockfile_drift(manifest, lockfile);
+                lockfile
+                    .packages
+                    .iter()
+                    .filter(|p| p.source == DependencySource::Git && p.revision.is_some())
+                    .filter(|p| !drift.unsatisfied.iter().any(|d| d.name == p.name))
+                    .map(|p| (p.name.as_str(), p))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Revisions of the checkouts rebuilt that way, which have no `.git`
+        // to read them from
+        let mut restored: HashMap<String, String> = HashMap::new();
 
-        while !pending_deps.is_empty() && iteration < MAX_ITERATIONS {
-            iteration += 1;
+        let max_depth = self.options.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        let mut iteration = 0;
 
+        loop {
             // Filter out already resolved deps
             pending_deps.retain(|(name, _)| !packages.contains_key(name));
 
-            if pending_deps.is_empty() {
+            if pending_deps.is_empty() || iteration >= max_depth {
                 break;
             }
+            iteration += 1;
 
             let count = pending_deps.len();
             let depth_msg = if iteration == 1 {
@@ -201,40 +635,75 @@ impl Installer {
                 )
             };
 
-            let spinner = mp.add(ProgressBar::new_spinner());
-            spinner.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.blue} {msg}")
-                    .unwrap(),
-            );
-            spinner.set_message(depth_msg.clone());
-            spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+            self.reporter.report(Event::Started(&depth_msg));
 
             // Build list of packages to fetch
-            let to_fetch: Vec<(Dependency, PathBuf)> = pending_deps
-                .iter()
-                .filter_map(|(name, dep)| {
-                    let dest = self.cache.git_dir().join(sanitize_name(name));
-                    if dest.exists() {
-                        None // Already cached
-                    } else {
-                        Some((dep.clone(), dest))
+            let mut to_fetch: Vec<(Dependency, PathBuf)> = Vec::new();
+            for (name, dep) in &pending_deps {
+                let dest = self.checkout_dir(name, dep);
+                if self.is_cached(dep.path.is_some(), &dest) {
+                    continue;
+                }
+                let locked = locked_checkouts
+                    .get(name.as_str())
+                    .filter(|locked| dep.git.is_some() && locked.git == dep.git);
+                if let Some(locked) = locked {
+                    let revision = locked.revision.as_deref().unwrap_or_default();
+                    if self
+                        .restore_checkout(name, &locked.version, revision, &dest)
+                        .await
+                    {
+                        restored.insert(name.clone(), revision.to_string());
+                        continue;
                     }
-                })
-                .collect();
+                }
+                to_fetch.push((dep.clone(), dest));
+            }
+
+            if self.options.offline && !to_fetch.is_empty() {
+                self.reporter.report(Event::Cancelled);
+                let names: Vec<&str> = to_fetch.iter().map(|(d, _)| d.name.as_str()).collect();
+                return Err(miette::miette!(
+                    help = "Run without --offline to fetch them",
+                    "Not in the package cache: {}",
+                    names.join(", ")
+                ));
+            }
 
-            // Fetch packages in parallel
+            // Fetch packages in parallel. A direct dependency that can't be
+            // fetched can't be resolved, so that fails the install, as it
+            // must when `gust add --install` adds one; a transitive one is
+            // left out with a warning
             if !to_fetch.is_empty() {
-                let _results = self.fetcher.fetch_many(to_fetch, |_name, _status| {}).await;
+                let names: Vec<String> = to_fetch.iter().map(|(d, _)| d.name.clone()).collect();
+                let results = self.fetcher.fetch_many(to_fetch, |_name, _status| {}).await;
+                let mut fetched = Vec::new();
+                for (name, result) in names.into_iter().zip(results) {
+                    match result {
+                        Ok(r) => fetched.push(r),
+                        Err(err) if self.options.keep_going => {
+                            failures.insert(name, err.to_string());
+                        }
+                        Err(err) if iteration == 1 => {
+                            self.reporter.report(Event::Cancelled);
+                            return Err(err).into_diagnostic();
+                        }
+                        Err(err) => self.reporter.report(Event::Info(&format!(
+                            "Couldn't fetch {}, leaving it out: {}",
+                            name, err
+                        ))),
+                    }
+                }
+                pending_deps.retain(|(name, _)| !failures.contains_key(name));
+                for r in fetched {
+                    timings.fetches.insert(r.name, r.duration);
+                }
             }
 
             // Collect paths for parsing
             let parse_dirs: Vec<(String, PathBuf)> = pending_deps
                 .iter()
-                .map(|(name, _)| {
-                    let path = self.cache.git_dir().join(sanitize_name(name));
-                    (name.clone(), path)
-                })
+                .map(|(name, dep)| (name.clone(), self.checkout_dir(name, dep)))
                 .collect();
 
             // Parse all fetched manifests in parallel
@@ -243,22 +712,51 @@ impl Installer {
 
             // Add resolved packages
             for parsed_dep in &parsed {
+                timings
+                    .parses
+                    .insert(parsed_dep.name.clone(), parsed_dep.parse_time);
                 let dep = pending_deps
                     .iter()
                     .find(|(n, _)| n == &parsed_dep.name)
                     .map(|(_, d)| d.clone());
 
+                let reason = match &dep {
+                    Some(d)
+                        if d.tag.is_some()
+                            || d.branch.is_some()
+                            || d.revision.is_some()
+                            || d.path.is_some() =>
+                    {
+                        ChoiceReason::Pinned
+                    }
+                    _ => ChoiceReason::HighestCompatible,
+                };
+                trace.record_choice(
+                    &parsed_dep.name,
+                    &parsed_dep.manifest.package.version,
+                    reason,
+                );
+                for child in &parsed_dep.dependency_names {
+                    trace.record_requirement(child, &parsed_dep.name);
+                }
+
                 let source = if let Some(ref d) = dep {
                     match d.source_kind() {
                         DependencySource::Git => gust_resolver::ResolvedSource::Git {
                             url: d.git.clone().unwrap_or_default(),
-                            revision: d.revision.clone().unwrap_or_else(|| "HEAD".to_string()),
+                            revision: d
+                                .revision
+                                .clone()
+                                .or_else(|| restored.get(&parsed_dep.name).cloned())
+                                .unwrap_or_else(|| "HEAD".to_string()),
                             tag: d.tag.clone(),
                         },
                         DependencySource::Path => gust_resolver::ResolvedSource::Path {
                             path: d.path.clone().unwrap_or_default(),
                         },
-                        DependencySource::Registry => gust_resolver::ResolvedSource::Registry,
+                        DependencySource::Registry => {
+                            gust_resolver::ResolvedSource::Registry { repository: None }
+                        }
                     }
                 } else {
                     // For discovered transitive deps, try to find git URL from their manifest
@@ -305,52 +803,236 @@ impl Installer {
                 }
             }
 
-            spinner.finish_with_message(format!("{} {}", style("✓").green(), depth_msg));
+            self.reporter.report(Event::Finished(&depth_msg));
         }
 
-        if iteration >= MAX_ITERATIONS {
-            tracing::warn!("Reached maximum resolution depth, some transitive deps may be missing");
+        if !pending_deps.is_empty() {
+            unresolved.extend(pending_deps.iter().map(|(name, _)| name.clone()));
+            let names = unresolved.iter().cloned().collect::<Vec<_>>().join(", ");
+            if self.options.strict_depth {
+                self.reporter.report(Event::Cancelled);
+                return Err(miette::miette!(
+                    help = "Raise --max-depth to resolve them",
+                    "Dependencies deeper than {} levels were not resolved: {}",
+                    max_depth,
+                    names
+                ));
+            }
+            self.reporter.report(Event::Warning(&format!(
+                "Stopped at --max-depth {}; left unresolved: {}",
+                max_depth, names
+            )));
         }
 
-        Ok(Resolution {
-            packages,
-            metadata: HashMap::new(),
-        })
+        // Packages already resolved aren't queued again, so a cycle ends the
+        // loop above like any other graph; SwiftPM rejects it, so we do too
+        if let Some(cycle) = find_cycle(&packages) {
+            self.reporter.report(Event::Cancelled);
+            return Err(GustError::dependency_cycle(cycle).into());
+        }
+        if let Err(err) = check_pinned_tags(&packages, &options) {
+            self.reporter.report(Event::Cancelled);
+            return Err(err);
+        }
+
+        if !self.options.offline {
+            backfill_registry_repositories(
+                &mut packages,
+                existing_lockfile,
+                &self.registry,
+                &self.cache,
+                self.options.concurrency,
+            )
+            .await;
+        }
+
+        Ok((
+            Resolution {
+                packages,
+                metadata: HashMap::new(),
+            },
+            trace,
+            iteration,
+        ))
+    }
+
+    /// Resolve the registry dependencies `deps` of `manifest` against their
+    /// release lists, preferring locked versions.
+    ///
+    /// Releases the registry reports a problem for, such as yanked ones, are
+    /// only chosen when a dependency requires exactly that version; `trace`
+    /// then carries a warning naming the problem.
+    async fn resolve_registry(
+        &self,
+        manifest: &Manifest,
+        deps: &[(String, Dependency)],
+        lockfile: Option<&Lockfile>,
+        options: &ResolutionOptions,
+        trace: &mut ResolutionTrace,
+    ) -> Result<HashMap<String, ResolvedDep>> {
+        let names: Vec<&str> = deps.iter().map(|(name, _)| name.as_str()).collect();
+        if self.options.offline {
+            return Err(miette::miette!(
+                help = "Run without --offline to fetch their release lists",
+                "Registry packages can't be resolved offline: {}",
+                names.join(", ")
+            ));
+        }
+        let provider = RegistryProvider::fetch(&self.registry, names.iter().copied())
+            .await
+            .into_diagnostic()?;
+
+        let mut hints = LockfileHints::new();
+        for (name, _) in deps {
+            if let Some(locked) = lockfile.and_then(|l| l.get(name)) {
+                hints.add_preferred_version(name.as_str(), locked.version.clone());
+            }
+        }
+        let mut registry_manifest = Manifest::default();
+        registry_manifest.package.name = manifest.package.name.clone();
+        registry_manifest.dependencies = deps.iter().cloned().collect();
+
+        let (resolution, registry_trace) = Resolver::new(&provider)
+            .with_hints(hints)
+            .with_options(options.clone())
+            .resolve_with_trace(&registry_manifest)
+            .into_diagnostic()?;
+        trace.merge(registry_trace);
+        Ok(resolution.packages)
+    }
+
+    /// The resolution `lockfile` records, with a trace crediting every
+    /// choice to it.
+    fn locked_resolution(
+        &self,
+        manifest: &Manifest,
+        lockfile: &Lockfile,
+    ) -> Result<(Resolution, ResolutionTrace)> {
+        let mut trace = ResolutionTrace::new();
+        let resolution = self.resolution_from_lockfile(manifest, lockfile)?;
+        for (name, resolved) in &resolution.packages {
+            trace.record_choice(name, &resolved.version, ChoiceReason::LockedHint);
+            for dep in &resolved.dependencies {
+                trace.record_requirement(dep, name);
+            }
+        }
+        Ok((resolution, trace))
     }
 
     /// Create resolution from existing lockfile.
-    fn resolution_from_lockfile(&self, lockfile: &Lockfile) -> Result<Resolution> {
+    ///
+    /// The lockfile doesn't record where path packages live, so they're
+    /// taken from `manifest`, or for one that only another path package
+    /// depends on, from that package's manifest. One neither says where to
+    /// find is an error.
+    ///
+    /// A git entry without a revision, as older lockfiles may have, isn't
+    /// taken as locked: it's resolved again from its tag, with a warning.
+    fn resolution_from_lockfile(
+        &self,
+        manifest: &Manifest,
+        lockfile: &Lockfile,
+    ) -> Result<Resolution> {
         let mut packages = HashMap::new();
+        let mut unplaced = Vec::new();
 
         for pkg in &lockfile.packages {
-            // For locked packages, derive tag from version
-            let version_tag = if pkg.version != Version::new(0, 0, 0) {
-                Some(pkg.version.to_string())
-            } else {
-                None
-            };
-
-            let source = match pkg.source {
-                DependencySource::Git => gust_resolver::ResolvedSource::Git {
-                    url: pkg.git.clone().unwrap_or_default(),
-                    revision: pkg.revision.clone().unwrap_or_default(),
-                    tag: version_tag,
-                },
-                DependencySource::Path => gust_resolver::ResolvedSource::Path {
-                    path: PathBuf::new(), // Would need to store path in lockfile
-                },
-                DependencySource::Registry => gust_resolver::ResolvedSource::Registry,
-            };
-
-            packages.insert(
-                pkg.name.clone(),
-                ResolvedDep {
+            if pkg.dev && self.options.no_dev {
+                continue;
+            }
+            if pkg
+                .group
+                .as_ref()
+                .is_some_and(|g| !self.options.groups.contains(g))
+            {
+                continue;
+            }
+            if pkg.source == DependencySource::Path {
+                let path = manifest
+                    .dependencies
+                    .get(&pkg.name)
+                    .or_else(|| manifest.dev_dependencies.get(&pkg.name))
+                    .and_then(|dep| dep.path.clone());
+                let Some(path) = path else {
+                    unplaced.push(pkg);
+                    continue;
+                };
+                let resolved = ResolvedDep {
                     name: pkg.name.clone(),
                     version: pkg.version.clone(),
-                    source,
+                    source: gust_resolver::ResolvedSource::Path { path },
                     dependencies: pkg.dependencies.clone(),
-                },
-            );
+                };
+                packages.insert(pkg.name.clone(), resolved);
+                continue;
+            }
+
+            if pkg.source == DependencySource::Git && pkg.revision.is_none() {
+                if let Some(resolved) = self.unlocked_git_package(manifest, pkg) {
+                    packages.insert(pkg.name.clone(), resolved);
+                }
+                continue;
+            }
+
+            let mut resolved = ResolvedDep::try_from(ResolvedPackage::from(pkg.clone()))
+                .map_err(|e| miette::miette!("Invalid lockfile entry: {}", e))?;
+
+            // For locked packages, derive tag from version
+            if let gust_resolver::ResolvedSource::Git { tag, .. } = &mut resolved.source {
+                if pkg.version != Version::new(0, 0, 0) {
+                    *tag = Some(pkg.version.to_string());
+                }
+            }
+
+            packages.insert(pkg.name.clone(), resolved);
+        }
+
+        // Nested path packages are placed level by level, each through a
+        // path package already placed that depends on it
+        let mut manifests: HashMap<PathBuf, Option<Manifest>> = HashMap::new();
+        while !unplaced.is_empty() {
+            let before = unplaced.len();
+            unplaced.retain(|pkg| {
+                let path = packages.values().find_map(|parent| {
+                    let gust_resolver::ResolvedSource::Path { path: dir } = &parent.source else {
+                        return None;
+                    };
+                    if !parent.dependencies.contains(&pkg.name) {
+                        return None;
+                    }
+                    let manifest = manifests
+                        .entry(dir.clone())
+                        .or_insert_with(|| effective_manifest(dir).ok().map(|(m, _)| m));
+                    let path = manifest
+                        .as_ref()?
+                        .dependencies
+                        .get(&pkg.name)?
+                        .path
+                        .clone()?;
+                    Some(dir.join(path))
+                });
+                let Some(path) = path else {
+                    return true;
+                };
+                packages.insert(
+                    pkg.name.clone(),
+                    ResolvedDep {
+                        name: pkg.name.clone(),
+                        version: pkg.version.clone(),
+                        source: gust_resolver::ResolvedSource::Path { path },
+                        dependencies: pkg.dependencies.clone(),
+                    },
+                );
+                false
+            });
+            if unplaced.len() == before {
+                return Err(miette::miette!(
+                    help = "Run without --frozen, or list it in Gust.toml with its path",
+                    "Gust.lock has path package '{}', which neither Gust.toml nor the path \
+                     packages depending on it say where to find",
+                    unplaced[0].name
+                ));
+            }
         }
 
         Ok(Resolution {
@@ -359,14 +1041,52 @@ impl Installer {
         })
     }
 
+    /// Resolve a git package the lockfile has no revision for from its tag,
+    /// or leave it out if not even its URL is known.
+    fn unlocked_git_package(
+        &self,
+        manifest: &Manifest,
+        pkg: &LockedPackage,
+    ) -> Option<ResolvedDep> {
+        let dep = manifest
+            .dependencies
+            .get(&pkg.name)
+            .or_else(|| manifest.dev_dependencies.get(&pkg.name));
+        let Some(url) = pkg.git.clone().or_else(|| dep?.git.clone()) else {
+            self.reporter.report(Event::Info(&format!(
+                "Gust.lock has neither a URL nor a revision for {}; leaving it out",
+                pkg.name
+            )));
+            return None;
+        };
+        self.reporter.report(Event::Info(&format!(
+            "Gust.lock has no revision for {}; resolving it again",
+            pkg.name
+        )));
+        let tag = dep
+            .and_then(|d| d.tag.clone())
+            .or_else(|| (pkg.version != Version::new(0, 0, 0)).then(|| pkg.version.to_string()));
+        Some(ResolvedDep {
+            name: pkg.name.clone(),
+            version: pkg.version.clone(),
+            source: gust_resolver::ResolvedSource::Git {
+                url,
+                revision: dep
+                    .and_then(|d| d.revision.clone())
+                    .unwrap_or_else(|| "HEAD".to_string()),
+                tag,
+            },
+            dependencies: pkg.dependencies.clone(),
+        })
+    }
+
     /// Fetch all packages in parallel.
     async fn fetch_packages(
         &self,
-        mp: &MultiProgress,
         resolution: &Resolution,
+        timings: &mut InstallTimings,
+        failures: &mut BTreeMap<String, String>,
     ) -> Result<HashMap<String, FetchResult>> {
-        use std::sync::atomic::{AtomicUsize, Ordering};
-
         // Separate packages into cached and need-to-fetch
         let mut already_cached: HashMap<String, FetchResult> = HashMap::new();
         let mut to_fetch: Vec<(Dependency, PathBuf)> = Vec::new();
@@ -382,24 +1102,33 @@ impl Installer {
                 gust_resolver::ResolvedSource::Path { path } => {
                     (Dependency::path(name, path), None)
                 }
-                gust_resolver::ResolvedSource::Registry => {
+                gust_resolver::ResolvedSource::Registry { .. } => {
                     // Skip registry deps for now
                     continue;
                 }
             };
 
-            let dest = self.cache.git_dir().join(sanitize_name(name));
+            let dest = self.checkout_dir(name, &dep);
 
-            // Check if already in cache
-            if dest.exists() {
+            // Check if already in cache, or in the package store
+            let restored = match (&resolved.source, dest.exists()) {
+                (gust_resolver::ResolvedSource::Git { revision, .. }, false) => self
+                    .restore_checkout(name, &resolved.version, revision, &dest)
+                    .await
+                    .then(|| revision.clone()),
+                _ => None,
+            };
+            if restored.is_some() || self.is_cached(dep.path.is_some(), &dest) {
                 already_cached.insert(
                     name.clone(),
                     FetchResult {
                         name: name.clone(),
                         path: dest,
                         checksum: String::new(),
-                        revision: None,
+                        revision: restored,
                         tag,
+                        bytes: None,
+                        duration: Duration::ZERO,
                     },
                 );
             } else {
@@ -411,72 +1140,48 @@ impl Installer {
         let fetch_count = to_fetch.len();
 
         if cached_count > 0 {
-            println!(
-                "{} {} packages already cached",
-                style("✓").green(),
+            self.reporter.report(Event::Finished(&format!(
+                "{} packages already cached",
                 cached_count
-            );
+            )));
         }
 
         if fetch_count == 0 {
             return Ok(already_cached);
         }
 
-        // Create progress bar for fetching
-        let pb = mp.add(ProgressBar::new(fetch_count as u64));
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {wide_msg}")
-                .unwrap()
-                .progress_chars("█▓░"),
-        );
-
-        // Track active fetches for display
-        let active_fetches: Arc<std::sync::Mutex<Vec<String>>> =
-            Arc::new(std::sync::Mutex::new(Vec::new()));
-        let completed = Arc::new(AtomicUsize::new(0));
-
-        let active_clone = Arc::clone(&active_fetches);
-        let completed_clone = Arc::clone(&completed);
-        let pb_clone = pb.clone();
-
-        // Progress callback
+        self.reporter
+            .report(Event::FetchStarted { count: fetch_count });
+        let started_at = Instant::now();
+        let reporter = Arc::clone(&self.reporter);
         let on_progress = move |name: &str, status: FetchStatus| {
-            let mut active = active_clone.lock().unwrap();
-            match status {
-                FetchStatus::Started => {
-                    active.push(name.to_string());
-                    let msg = if active.len() <= 3 {
-                        active.join(", ")
-                    } else {
-                        format!("{} and {} more", active[..3].join(", "), active.len() - 3)
-                    };
-                    pb_clone.set_message(format!("Fetching: {}", msg));
-                }
-                FetchStatus::Completed => {
-                    active.retain(|n| n != name);
-                    completed_clone.fetch_add(1, Ordering::SeqCst);
-                    pb_clone.inc(1);
-                }
-                FetchStatus::Failed(_) => {
-                    active.retain(|n| n != name);
-                    pb_clone.inc(1);
-                }
-            }
+            reporter.report(Event::Fetch {
+                name,
+                status: &status,
+            });
         };
 
         // Fetch all packages in parallel!
+        let names: Vec<String> = to_fetch.iter().map(|(d, _)| d.name.clone()).collect();
         let fetch_results = self.fetcher.fetch_many(to_fetch, on_progress).await;
 
         // Collect results
         let mut results = already_cached;
         let mut errors = Vec::new();
+        let mut fetched_bytes = 0;
 
-        for result in fetch_results {
+        for (name, result) in names.into_iter().zip(fetch_results) {
             match result {
                 Ok(fetch_result) => {
+                    fetched_bytes += fetch_result.bytes.unwrap_or(0);
+                    timings
+                        .fetches
+                        .insert(fetch_result.name.clone(), fetch_result.duration);
                     results.insert(fetch_result.name.clone(), fetch_result);
                 }
+                Err(e) if self.options.keep_going => {
+                    failures.insert(name, e.to_string());
+                }
                 Err(e) => {
                     errors.push(e.to_string());
                 }
@@ -484,53 +1189,248 @@ impl Installer {
         }
 
         if !errors.is_empty() {
-            pb.abandon_with_message(format!("{} errors during fetch", errors.len()));
+            self.reporter.report(Event::FetchFailed {
+                errors: errors.len(),
+            });
             return Err(miette::miette!("Fetch errors: {}", errors.join(", ")));
         }
 
-        pb.finish_with_message(format!(
-            "{} Fetched {} packages in parallel",
-            style("✓").green(),
-            fetch_count
-        ));
+        let size = if fetched_bytes > 0 {
+            format!(
+                " ({})",
+                ui::transfer_summary(fetched_bytes, started_at.elapsed())
+            )
+        } else {
+            String::new()
+        };
+        self.reporter.report(Event::FetchFinished(&format!(
+            "Fetched {} packages in parallel{}",
+            fetch_count, size
+        )));
 
         Ok(results)
     }
 
+    /// Add a checkout's files to the content-addressed store, so
+    /// [`Installer::restore_checkout`] can rebuild it without fetching.
+    /// Checkouts already stored at this version and revision are skipped,
+    /// and since the checkout works either way, failures are only logged.
+    async fn store_checkout(&self, fetched: &FetchResult, version: &Version, revision: &str) {
+        let cache = self.cache.clone();
+        let name = sanitize_name(&fetched.name);
+        let version = version.to_string();
+        let revision = revision.to_string();
+        let path = fetched.path.clone();
+        let stored = tokio::task::spawn_blocking(move || {
+            let existing = gust_cache::PackageMetadata::load(&cache, &name, &version);
+            if existing.is_ok_and(|m| m.revision.as_deref() == Some(revision.as_str())) {
+                return Ok(());
+            }
+            gust_cache::PackageMetadata::store_dir(&cache, &name, &version, Some(&revision), &path)
+                .map(|_| ())
+        })
+        .await
+        .unwrap_or_else(|e| Err(gust_cache::CacheError::ReadError(std::io::Error::other(e))));
+        if let Err(e) = stored {
+            tracing::warn!(
+                "Could not store {} in the package store: {}",
+                fetched.name,
+                e
+            );
+        }
+    }
+
+    /// Rebuild the missing checkout `dest` of `name` from the
+    /// content-addressed store, if it holds `version` at `revision`.
+    ///
+    /// The files are assembled beside `dest` and renamed into place, so a
+    /// failed restore leaves nothing behind and the checkout is fetched as
+    /// usual. Returns whether the checkout was restored.
+    async fn restore_checkout(
+        &self,
+        name: &str,
+        version: &Version,
+        revision: &str,
+        dest: &Path,
+    ) -> bool {
+        let cache = self.cache.clone();
+        let stored_name = sanitize_name(name);
+        let version = version.to_string();
+        let revision = revision.to_string();
+        let dest = dest.to_path_buf();
+        let restored = tokio::task::spawn_blocking(move || {
+            let metadata = gust_cache::PackageMetadata::load(&cache, &stored_name, &version)?;
+            if metadata.revision.as_deref() != Some(revision.as_str()) {
+                return Ok(false);
+            }
+            let staging = dest.with_extension("restoring");
+            if staging.exists() {
+                std::fs::remove_dir_all(&staging).map_err(gust_cache::CacheError::WriteError)?;
+            }
+            let restored = metadata.materialize(&cache, &staging).and_then(|()| {
+                std::fs::rename(&staging, &dest).map_err(gust_cache::CacheError::WriteError)
+            });
+            if restored.is_err() {
+                let _ = std::fs::remove_dir_all(&staging);
+            }
+            restored.map(|()| true)
+        })
+        .await
+        .unwrap_or_else(|e| Err(gust_cache::CacheError::ReadError(std::io::Error::other(e))));
+        match restored {
+            Ok(restored) => restored,
+            Err(e) => {
+                tracing::debug!("Could not restore {} from the package store: {}", name, e);
+                false
+            }
+        }
+    }
+
     /// Link packages from cache to project.
+    ///
+    /// Entries in `.build/checkouts` for packages that are no longer part of
+    /// the resolution are removed first. Links that already point at the
+    /// right checkout are left alone, and stale ones are replaced in a
+    /// single rename so a concurrent build never sees them missing.
+    /// Packages being edited are linked to their local copy instead.
+    ///
+    /// A git dependency with `[dependencies.<name>.build]` flags is linked to
+    /// a build overlay of its checkout instead; see
+    /// [`Installer::overlay_checkout`].
     fn link_packages(
         &self,
-        _resolution: &Resolution,
+        manifest: &Manifest,
+        resolution: &Resolution,
         fetch_results: &HashMap<String, FetchResult>,
-    ) -> Result<usize> {
-        let checkouts_dir = self.project_dir.join(".build").join("checkouts");
+        edits: &Edits,
+    ) -> Result<LinkStats> {
+        let checkouts_dir = self.target_dir().join("checkouts");
         std::fs::create_dir_all(&checkouts_dir).into_diagnostic()?;
 
-        let mut linked = 0;
+        let pruned = prune_checkouts(&checkouts_dir, fetch_results).into_diagnostic()?;
+        if pruned > 0 {
+            tracing::debug!("Pruned {} stale checkouts", pruned);
+        }
+
+        // Overlays are rebuilt below, so ones for dependencies that lost
+        // their flags don't linger
+        let overlays_dir = self.target_dir().join("build-overlays");
+        if overlays_dir.exists() {
+            std::fs::remove_dir_all(&overlays_dir).into_diagnostic()?;
+        }
 
+        let mut stats = LinkStats::default();
         for (name, result) in fetch_results {
             let link_path = checkouts_dir.join(name);
-
-            // Remove existing link/dir
-            if link_path.exists() {
-                if link_path.is_symlink() || link_path.is_file() {
-                    std::fs::remove_file(&link_path).into_diagnostic()?;
-                } else {
-                    std::fs::remove_dir_all(&link_path).into_diagnostic()?;
+            let build = manifest
+                .dependencies
+                .get(name)
+                .and_then(|dep| dep.build.as_ref())
+                .filter(|_| {
+                    matches!(
+                        resolution.packages.get(name).map(|r| &r.source),
+                        Some(gust_resolver::ResolvedSource::Git { .. })
+                    )
+                });
+            let target = match (edits.local_copy(&self.project_dir, name), build) {
+                (Some(local), _) => local,
+                (None, Some(_)) if !result.path.join("Package.swift").exists() => {
+                    tracing::warn!(
+                        "{} has no Package.swift to add its build flags to; they are ignored",
+                        name
+                    );
+                    result.path.clone()
                 }
+                (None, Some(build)) => self
+                    .overlay_checkout(name, &result.path, build)
+                    .into_diagnostic()?,
+                (None, None) => result.path.clone(),
+            };
+            match relink(&link_path, &target).into_diagnostic()? {
+                LinkChange::Created => stats.created += 1,
+                LinkChange::Updated => stats.updated += 1,
+                LinkChange::Unchanged => stats.unchanged += 1,
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// A copy of `checkout` at `<target_dir>/build-overlays/<name>` whose
+    /// Package.swift, and any `Package@swift-*.swift` SwiftPM may pick
+    /// instead, adds `build`'s flags to the package's targets.
+    ///
+    /// Files are hard links into the checkout, except the manifests, which
+    /// are written anew so the shared checkout in the cache is left alone.
+    fn overlay_checkout(
+        &self,
+        name: &str,
+        checkout: &Path,
+        build: &DependencyBuildSettings,
+    ) -> std::io::Result<PathBuf> {
+        let overlay = self.target_dir().join("build-overlays").join(name);
+        link_tree(checkout, &overlay)?;
+
+        let addition = gust_manifest::dependency_build_overlay(name, build);
+        for entry in std::fs::read_dir(&overlay)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let is_manifest = file_name == "Package.swift"
+                || (file_name.starts_with("Package@swift-") && file_name.ends_with(".swift"));
+            if !is_manifest || !entry.file_type()?.is_file() {
+                continue;
+            }
+            let manifest_path = entry.path();
+            let mut manifest = std::fs::read_to_string(&manifest_path)?;
+            manifest.push_str(&addition);
+            std::fs::remove_file(&manifest_path)?;
+            std::fs::write(&manifest_path, manifest)?;
+        }
+        Ok(overlay)
+    }
+
+    /// Download and verify the root package's remote binary targets.
+    async fn fetch_artifacts(&self, manifest: &Manifest) -> Result<Vec<BinaryArtifact>> {
+        let artifacts_dir = self.cache.artifacts_dir();
+        std::fs::create_dir_all(&artifacts_dir).into_diagnostic()?;
+
+        let mut artifacts = Vec::new();
+        for locked in locked_artifacts(manifest) {
+            let artifact = self
+                .fetcher
+                .fetch_artifact(&locked.name, &locked.url, &locked.checksum, &artifacts_dir)
+                .await
+                .into_diagnostic()?;
+            artifacts.push(artifact);
+        }
+        Ok(artifacts)
+    }
+
+    /// Link artifacts into `.build/artifacts/<package>/<target>`, where
+    /// SwiftPM looks for downloaded binary targets.
+    fn link_artifacts(&self, manifest: &Manifest, artifacts: &[BinaryArtifact]) -> Result<()> {
+        let package_dir = self
+            .target_dir()
+            .join("artifacts")
+            .join(manifest.package.name.to_lowercase());
+
+        for artifact in artifacts {
+            std::fs::create_dir_all(&package_dir).into_diagnostic()?;
+            let link_path = package_dir.join(&artifact.name);
+            if link_path.is_symlink() || link_path.is_file() {
+                std::fs::remove_file(&link_path).into_diagnostic()?;
+            } else if link_path.exists() {
+                std::fs::remove_dir_all(&link_path).into_diagnostic()?;
             }
 
-            // Create symlink to cached package
             #[cfg(unix)]
-            std::os::unix::fs::symlink(&result.path, &link_path).into_diagnostic()?;
+            std::os::unix::fs::symlink(&artifact.path, &link_path).into_diagnostic()?;
 
             #[cfg(windows)]
-            std::os::windows::fs::symlink_dir(&result.path, &link_path).into_diagnostic()?;
-
-            linked += 1;
+            std::os::windows::fs::symlink_dir(&artifact.path, &link_path).into_diagnostic()?;
         }
 
-        Ok(linked)
+        Ok(())
     }
 
     /// Update the lockfile incrementally.
@@ -539,82 +1439,35 @@ impl Installer {
     async fn update_lockfile(
         &self,
         lockfile_path: &Path,
-        resolution: &Resolution,
-        fetch_results: &HashMap<String, FetchResult>,
-        existing_lockfile: Option<&Lockfile>,
+        inputs: &LockInputs<'_>,
     ) -> Result<Option<LockfileDiff>> {
-        // Build the new package list
-        let mut new_packages: Vec<LockedPackage> = Vec::new();
-
-        for (name, resolved) in &resolution.packages {
-            let fetch_result = fetch_results.get(name);
-
-            let locked = match &resolved.source {
-                gust_resolver::ResolvedSource::Git { url, revision, tag } => {
-                    // Try to get version from tag, fallback to manifest version
-                    let version = tag
-                        .as_ref()
-                        .or_else(|| fetch_result.and_then(|r| r.tag.as_ref()))
-                        .and_then(|t| parse_version_from_tag(t))
-                        .unwrap_or_else(|| resolved.version.clone());
-
-                    let mut pkg = LockedPackage::git(
-                        name,
-                        version,
-                        url,
-                        fetch_result
-                            .and_then(|r| r.revision.clone())
-                            .unwrap_or_else(|| revision.clone()),
-                    );
-                    pkg.dependencies = resolved.dependencies.clone();
-                    pkg
-                }
-                gust_resolver::ResolvedSource::Registry => {
-                    let mut pkg = LockedPackage::registry(
-                        name,
-                        resolved.version.clone(),
-                        fetch_result
-                            .map(|r| format!("blake3:{}", r.checksum))
-                            .unwrap_or_default(),
-                    );
-                    pkg.dependencies = resolved.dependencies.clone();
-                    pkg
-                }
-                gust_resolver::ResolvedSource::Path { .. } => {
-                    // Don't lock path dependencies
-                    continue;
-                }
-            };
-
-            new_packages.push(locked);
-        }
-
-        // Sort for deterministic output
-        new_packages.sort_by(|a, b| a.name.cmp(&b.name));
+        let new_packages = self.locked_packages(inputs);
+        let artifacts = locked_artifacts(inputs.manifest);
 
         // Check if we need to update
-        if let Some(existing) = existing_lockfile {
-            if !existing.needs_update(&new_packages) {
+        if let Some(existing) = inputs.existing_lockfile {
+            let artifacts_changed = existing.artifacts != artifacts;
+            if !existing.needs_update(&new_packages) && !artifacts_changed {
                 tracing::debug!("Lockfile is up to date, skipping write");
                 return Ok(None);
             }
 
             // Compute and apply incremental diff
-            let (diff, merged) = existing.merge(new_packages);
+            let (diff, mut merged) = existing.merge(new_packages);
+            merged.artifacts = artifacts;
 
-            if diff.has_changes() {
+            if diff.has_changes() || artifacts_changed {
                 // Write asynchronously
                 let path = lockfile_path.to_path_buf();
                 merged.save_async(path).await.into_diagnostic()?;
-                return Ok(Some(diff));
-            } else {
-                return Ok(None);
             }
+            return Ok(diff.has_changes().then_some(diff));
         }
 
         // No existing lockfile, create new one
         let lockfile = Lockfile {
             packages: new_packages,
+            artifacts,
             ..Default::default()
         };
 
@@ -630,44 +1483,2624 @@ impl Installer {
 
         Ok(Some(diff))
     }
-}
 
-/// Sanitize a package name for use as a directory name.
-fn sanitize_name(name: &str) -> String {
-    name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '-' || c == '_' {
-                c
-            } else {
-                '_'
+    /// The packages Gust.lock should list for `resolution`, sorted by name.
+    ///
+    /// Entries the resolution left out on purpose (dev packages with
+    /// `no_dev`, unselected groups, packages that failed) keep what
+    /// `existing_lockfile` had.
+    fn locked_packages(&self, inputs: &LockInputs<'_>) -> Vec<LockedPackage> {
+        let LockInputs {
+            manifest,
+            resolution,
+            fetch_results,
+            checksums,
+            existing_lockfile,
+            failures,
+        } = *inputs;
+        // Build the new package list
+        let mut new_packages: Vec<LockedPackage> = Vec::new();
+        let dev_only = dev_only_packages(manifest, &resolution.packages);
+        let group_only = group_only_packages(manifest, &resolution.packages);
+
+        for (name, resolved) in &resolution.packages {
+            let fetch_result = fetch_results.get(name);
+
+            let mut pkg = ResolvedPackage::from(resolved.clone());
+            match &resolved.source {
+                gust_resolver::ResolvedSource::Git { tag, .. } => {
+                    // Try to get version from tag, fallback to manifest version
+                    if let Some(version) = tag
+                        .as_ref()
+                        .or_else(|| fetch_result.and_then(|r| r.tag.as_ref()))
+                        .and_then(|t| gust_fetch::parse_tag_version(t))
+                    {
+                        pkg.version = version;
+                    }
+                    if let Some(revision) = fetch_result.and_then(fetched_revision) {
+                        pkg.revision = Some(revision);
+                    }
+                    pkg.checksum = checksums.get(name).cloned();
+                }
+                gust_resolver::ResolvedSource::Registry { .. } => {
+                    pkg.checksum = Some(
+                        fetch_result
+                            .map(|r| format!("blake3:{}", r.checksum))
+                            .unwrap_or_default(),
+                    );
+                }
+                gust_resolver::ResolvedSource::Path { .. } => {
+                    pkg.checksum = checksums.get(name).cloned();
+                }
             }
+            let locked = LockedPackage::from(pkg);
+
+            new_packages.push(LockedPackage {
+                dev: dev_only.contains(name),
+                group: group_only.get(name).cloned(),
+                ..locked
+            });
+        }
+
+        // A --no-dev install didn't resolve dev packages; keep their entries
+        if self.options.no_dev {
+            if let Some(existing) = existing_lockfile {
+                new_packages.extend(
+                    existing
+                        .packages
+                        .iter()
+                        .filter(|p| p.dev && !resolution.packages.contains_key(&p.name))
+                        .cloned(),
+                );
+            }
+        }
+
+        // Likewise for groups this install didn't select
+        if let Some(existing) = existing_lockfile {
+            new_packages.extend(
+                existing
+                    .packages
+                    .iter()
+                    .filter(|p| {
+                        p.group
+                            .as_ref()
+                            .is_some_and(|g| !self.options.groups.contains(g))
+                            && !resolution.packages.contains_key(&p.name)
+                    })
+                    .cloned(),
+            );
+        }
+
+        // And for packages that failed to install, which keep what they had
+        if let Some(existing) = existing_lockfile {
+            new_packages.extend(
+                existing
+                    .packages
+                    .iter()
+                    .filter(|p| {
+                        failures.contains_key(&p.name)
+                            && !new_packages.iter().any(|n| n.name == p.name)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        // Sort for deterministic output
+        new_packages.sort_by(|a, b| a.name.cmp(&b.name));
+        new_packages
+    }
+}
+
+/// Write `Package.resolved` from the project's `Gust.lock` so `swift build`
+/// uses Gust's pins instead of resolving again. Does nothing without a
+/// lockfile.
+pub fn write_package_resolved(project_dir: &Path, manifest: &Manifest) -> Result<()> {
+    let lockfile_path = project_dir.join("Gust.lock");
+    if !lockfile_path.exists() {
+        return Ok(());
+    }
+
+    let lockfile = Lockfile::load(&lockfile_path).into_diagnostic()?;
+    PackageResolved::from_lockfile(&lockfile, manifest)
+        .write(project_dir)
+        .into_diagnostic()
+}
+
+/// Lockfile entries for the root package's remote binary targets.
+///
+/// The manifest checksum is authoritative: the download is verified against
+/// it before anything is linked.
+fn locked_artifacts(manifest: &Manifest) -> Vec<LockedArtifact> {
+    let mut artifacts: Vec<LockedArtifact> = manifest
+        .targets
+        .iter()
+        .filter(|t| t.target_type == TargetType::Binary)
+        .filter_map(|t| {
+            Some(LockedArtifact {
+                name: t.name.clone(),
+                url: t.url.clone()?,
+                checksum: t.checksum.as_ref()?.to_lowercase(),
+            })
         })
+        .collect();
+    artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+    artifacts
+}
+
+/// Dependencies that seed resolution for the root package.
+///
+/// Dev-dependencies only apply to the root; transitive packages contribute
+/// their regular dependencies alone, matching SwiftPM.
+fn root_dependencies(manifest: &Manifest, include_dev: bool) -> Vec<(String, Dependency)> {
+    let mut deps: Vec<(String, Dependency)> = manifest
+        .dependencies
+        .iter()
+        .map(|(name, dep)| (name.clone(), dep.clone()))
+        .collect();
+
+    if include_dev {
+        deps.extend(
+            manifest
+                .dev_dependencies
+                .iter()
+                .filter(|(name, _)| !manifest.dependencies.contains_key(*name))
+                .map(|(name, dep)| (name.clone(), dep.clone())),
+        );
+    }
+
+    deps
+}
+
+/// Resolved packages that are unreachable from the root's regular dependencies.
+fn dev_only_packages(
+    manifest: &Manifest,
+    packages: &HashMap<String, ResolvedDep>,
+) -> HashSet<String> {
+    let mut reachable: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = manifest.dependencies.keys().map(String::as_str).collect();
+
+    while let Some(name) = stack.pop() {
+        if !reachable.insert(name) {
+            continue;
+        }
+        if let Some(resolved) = packages.get(name) {
+            stack.extend(resolved.dependencies.iter().map(String::as_str));
+        }
+    }
+
+    packages
+        .keys()
+        .filter(|name| !reachable.contains(name.as_str()))
+        .cloned()
         .collect()
 }
 
-/// Parse a version from a git tag.
-/// Handles formats like "1.5.0", "v1.5.0", "1.5.0-beta.1"
-fn parse_version_from_tag(tag: &str) -> Option<Version> {
-    let tag = tag.trim();
+/// Record the upstream repository of each registry package, from the
+/// lockfile when it has one for the same version and otherwise from the
+/// registry, asking about up to `concurrency` releases at once. A failed
+/// lookup leaves the repository unknown.
+///
+/// Releases never change, so ones the registry has no repository for are
+/// remembered in `cache`, per registry, and not asked about again.
+async fn backfill_registry_repositories(
+    packages: &mut HashMap<String, ResolvedDep>,
+    lockfile: Option<&Lockfile>,
+    client: &gust_registry::RegistryClient,
+    cache: &GlobalCache,
+    concurrency: usize,
+) {
+    use futures::StreamExt;
 
-    // Try parsing directly
-    if let Ok(v) = Version::parse(tag) {
-        return Some(v);
+    let registry = GlobalCache::hash_bytes(client.base_url().as_bytes());
+    let without_repository = cache
+        .index_dir()
+        .join(format!("registry-no-repository-{}", &registry[..16]));
+    let mut known: BTreeSet<String> = std::fs::read_to_string(&without_repository)
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect();
+
+    let mut lookups = Vec::new();
+    for resolved in packages.values_mut() {
+        let gust_resolver::ResolvedSource::Registry { repository } = &resolved.source else {
+            continue;
+        };
+        if repository.is_some() {
+            continue;
+        }
+        let locked = lockfile
+            .and_then(|l| l.get(&resolved.name))
+            .filter(|p| p.version == resolved.version && p.repository.is_some());
+        if let Some(locked) = locked {
+            resolved.source = gust_resolver::ResolvedSource::Registry {
+                repository: locked.repository.clone(),
+            };
+            continue;
+        }
+        let release = format!("{}@{}", resolved.name, resolved.version);
+        if !known.contains(&release) {
+            lookups.push((resolved.name.clone(), resolved.version.to_string(), release));
+        }
     }
 
-    // Try stripping leading 'v'
-    if let Some(stripped) = tag.strip_prefix('v').or_else(|| tag.strip_prefix('V')) {
-        if let Ok(v) = Version::parse(stripped) {
-            return Some(v);
+    let results: Vec<_> = futures::stream::iter(lookups)
+        .map(|(package, version, release)| async move {
+            let result = match gust_registry::registry_identity(&package) {
+                Ok(id) => client.get_version(&id.scope, &id.name, &version).await,
+                Err(e) => Err(e),
+            };
+            (package, release, result)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let known_before = known.len();
+    for (package, release, result) in results {
+        match result {
+            Ok(found) => match found.repository_url() {
+                Some(url) => {
+                    if let Some(resolved) = packages.get_mut(&package) {
+                        resolved.source = gust_resolver::ResolvedSource::Registry {
+                            repository: Some(url.to_string()),
+                        };
+                    }
+                }
+                None => {
+                    known.insert(release);
+                }
+            },
+            Err(e) => tracing::debug!("No release metadata for {}: {}", package, e),
+        }
+    }
+    if known.len() > known_before {
+        let lines: String = known.iter().map(|r| format!("{}\n", r)).collect();
+        let written = std::fs::create_dir_all(cache.index_dir())
+            .and_then(|_| std::fs::write(&without_repository, lines));
+        if let Err(e) = written {
+            tracing::debug!(
+                "Failed to remember registry releases without a repository: {}",
+                e
+            );
         }
     }
+}
+
+/// Resolved packages that only the root's grouped dependencies need, with
+/// the group that needs them. A package needed by several groups gets the
+/// first by name.
+fn group_only_packages(
+    manifest: &Manifest,
+    packages: &HashMap<String, ResolvedDep>,
+) -> HashMap<String, String> {
+    let reachable = |roots: Vec<&str>| {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut stack = roots;
+        while let Some(name) = stack.pop() {
+            if !seen.insert(name.to_string()) {
+                continue;
+            }
+            if let Some(resolved) = packages.get(name) {
+                stack.extend(resolved.dependencies.iter().map(String::as_str));
+            }
+        }
+        seen
+    };
+
+    let ungrouped = reachable(
+        manifest
+            .dependencies
+            .iter()
+            .filter(|(_, dep)| dep.group.is_none())
+            .map(|(name, _)| name.as_str())
+            .chain(manifest.dev_dependencies.keys().map(String::as_str))
+            .collect(),
+    );
 
-    None
+    let mut grouped: Vec<(&str, &str)> = manifest
+        .dependencies
+        .iter()
+        .filter_map(|(name, dep)| Some((dep.group.as_deref()?, name.as_str())))
+        .collect();
+    grouped.sort();
+
+    let mut groups = HashMap::new();
+    for (group, name) in grouped {
+        for pkg in reachable(vec![name]) {
+            if packages.contains_key(&pkg) && !ungrouped.contains(&pkg) {
+                groups.entry(pkg).or_insert_with(|| group.to_string());
+            }
+        }
+    }
+    groups
 }
 
-/// Result of an installation.
-#[derive(Debug)]
-pub struct InstallResult {
-    /// Number of packages installed
-    pub installed: usize,
+/// Fail if a git package pinned to a version tag doesn't satisfy an
+/// override or constraint on it.
+///
+/// Tags are fetched as Gust.toml names them rather than chosen by version,
+/// so these can't move them; saying so beats silently ignoring them.
+fn check_pinned_tags(
+    packages: &HashMap<String, ResolvedDep>,
+    options: &ResolutionOptions,
+) -> Result<()> {
+    let requirements = options
+        .overrides
+        .iter()
+        .map(|o| ("override", &o.package, &o.version))
+        .chain(
+            options
+                .constraints
+                .iter()
+                .map(|c| ("constraint", &c.package, &c.version)),
+        );
+    for (kind, name, req) in requirements {
+        let Some(gust_resolver::ResolvedSource::Git { tag: Some(tag), .. }) =
+            packages.get(name).map(|p| &p.source)
+        else {
+            continue;
+        };
+        let Some(version) = gust_fetch::parse_tag_version(tag) else {
+            continue;
+        };
+        if !req.matches(&version) {
+            return Err(miette::miette!(
+                help = format!("Point {}'s tag at a version matching {}", name, req),
+                "{} is pinned to tag {}, which doesn't satisfy the {} {}",
+                name,
+                tag,
+                kind,
+                req
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A dependency cycle among `packages`, as the path around it with the
+/// first package repeated at the end: `a -> b -> a`.
+///
+/// Packages are visited in name order, so the same graph always reports
+/// the same cycle.
+fn find_cycle(packages: &HashMap<String, ResolvedDep>) -> Option<Vec<String>> {
+    fn visit<'a>(
+        name: &'a str,
+        packages: &'a HashMap<String, ResolvedDep>,
+        done: &mut HashSet<&'a str>,
+        path: &mut Vec<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|p| *p == name) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|p| p.to_string()).collect();
+            cycle.push(name.to_string());
+            return Some(cycle);
+        }
+        if !done.insert(name) {
+            return None;
+        }
+        let package = packages.get(name)?;
+        path.push(name);
+        let mut deps: Vec<&str> = package.dependencies.iter().map(String::as_str).collect();
+        deps.sort_unstable();
+        for dep in deps {
+            if let Some(cycle) = visit(dep, packages, done, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    let mut names: Vec<&str> = packages.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    let mut done = HashSet::new();
+    names
+        .into_iter()
+        .find_map(|name| visit(name, packages, &mut done, &mut Vec::new()))
+}
+
+/// Remove checkout entries that don't belong to a current package.
+///
+/// Returns the number of entries removed.
+fn prune_checkouts(
+    checkouts_dir: &Path,
+    current: &HashMap<String, FetchResult>,
+) -> std::io::Result<usize> {
+    if !checkouts_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut pruned = 0;
+    for entry in std::fs::read_dir(checkouts_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if current.contains_key(name.to_string_lossy().as_ref()) {
+            continue;
+        }
+
+        // `file_type` doesn't follow symlinks, so dangling links are removed too
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+        pruned += 1;
+    }
+
+    Ok(pruned)
+}
+
+/// Remove git checkouts from the global cache that no known lockfile
+/// references, returning their names.
+///
+/// With [`PruneScope::Project`] only packages `previous` locked and `current`
+/// no longer does are candidates; with [`PruneScope::Global`] every
+/// checkout is. Either way a checkout the lockfile of another project
+/// registered with the cache still references is kept, and if one of those
+/// lockfiles can't be read nothing is removed.
+pub(crate) fn prune_cache(
+    cache: &GlobalCache,
+    project_dir: &Path,
+    previous: Option<&Lockfile>,
+    current: &Lockfile,
+    scope: PruneScope,
+) -> Result<Vec<String>> {
+    let this_project = project_dir.canonicalize().into_diagnostic()?;
+    let mut referenced: HashSet<String> = current
+        .packages
+        .iter()
+        .map(|p| sanitize_name(&p.name))
+        .collect();
+    for project in cache.projects() {
+        let lockfile_path = project.join("Gust.lock");
+        if project == this_project || !lockfile_path.exists() {
+            continue;
+        }
+        let lockfile = Lockfile::load(&lockfile_path)
+            .map_err(|e| miette::miette!("can't read {}: {}", lockfile_path.display(), e))?;
+        referenced.extend(lockfile.packages.iter().map(|p| sanitize_name(&p.name)));
+    }
+
+    let candidates: Vec<String> = match scope {
+        PruneScope::Project => previous
+            .map(|l| l.packages.iter().map(|p| sanitize_name(&p.name)).collect())
+            .unwrap_or_default(),
+        PruneScope::Global => match std::fs::read_dir(cache.git_dir()) {
+            Ok(entries) => entries
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+    };
+
+    let mut pruned = Vec::new();
+    for name in candidates {
+        let path = cache.git_dir().join(&name);
+        if referenced.contains(&name) || !path.is_dir() {
+            continue;
+        }
+        std::fs::remove_dir_all(&path).into_diagnostic()?;
+        pruned.push(name);
+    }
+    pruned.sort();
+    Ok(pruned)
+}
+
+/// What [`relink`] did to a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinkChange {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+/// Recreate the tree at `src` under `dest` with hard links, copying files
+/// that can't be linked, e.g. across file systems.
+fn link_tree(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            link_tree(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            let link = std::fs::read_link(entry.path())?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(link, &target)?;
+            #[cfg(windows)]
+            std::fs::copy(entry.path(), &target)?;
+        } else if std::fs::hard_link(entry.path(), &target).is_err() {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Point `link_path` at `target`, doing nothing if it already does.
+///
+/// An existing link is replaced by renaming a new one over it, so the path
+/// is never missing. A directory in its place is removed first.
+pub(crate) fn relink(link_path: &Path, target: &Path) -> std::io::Result<LinkChange> {
+    let existing = std::fs::symlink_metadata(link_path).ok();
+    if existing
+        .as_ref()
+        .is_some_and(|m| m.file_type().is_symlink())
+        && std::fs::read_link(link_path)? == target
+    {
+        return Ok(LinkChange::Unchanged);
+    }
+
+    let change = match &existing {
+        None => LinkChange::Created,
+        Some(meta) => {
+            if meta.is_dir() {
+                std::fs::remove_dir_all(link_path)?;
+            }
+            LinkChange::Updated
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        let mut staged = link_path.as_os_str().to_owned();
+        staged.push(".gust-link");
+        let staged = PathBuf::from(staged);
+        let _ = std::fs::remove_file(&staged);
+        std::os::unix::fs::symlink(target, &staged)?;
+        std::fs::rename(&staged, link_path)?;
+    }
+
+    #[cfg(windows)]
+    {
+        if existing.is_some() && link_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(link_path)?;
+        }
+        std::os::windows::fs::symlink_dir(target, link_path)?;
+    }
+
+    Ok(change)
+}
+
+/// Content checksums (`blake3:<hex>`) of the git and path checkouts being
+/// installed. A linked path package is hashed as its source is now.
+///
+/// Cached checkouts weren't fetched this run; their checksum is looked up
+/// by commit in `cache`, and only hashed here the first time or when their
+/// files changed.
+fn checkout_checksums(
+    cache: &GlobalCache,
+    resolution: &Resolution,
+    fetch_results: &HashMap<String, FetchResult>,
+) -> Result<HashMap<String, String>> {
+    let mut checksums = HashMap::new();
+    for (name, resolved) in &resolution.packages {
+        if matches!(
+            resolved.source,
+            gust_resolver::ResolvedSource::Registry { .. }
+        ) {
+            continue;
+        }
+        let Some(result) = fetch_results.get(name) else {
+            continue;
+        };
+        // Path packages have no commit, and their files can change
+        let revision = match resolved.source {
+            gust_resolver::ResolvedSource::Git { .. } => fetched_revision(result),
+            _ => None,
+        };
+        let cached = revision
+            .as_deref()
+            .and_then(|r| cache.checkout_checksum(r, &result.path));
+        let checksum = match cached {
+            Some(checksum) if result.checksum.is_empty() => checksum,
+            _ => {
+                let checksum = if result.checksum.is_empty() {
+                    gust_cache::directory_checksum(&result.path).into_diagnostic()?
+                } else {
+                    result.checksum.clone()
+                };
+                if let Some(revision) = &revision {
+                    if let Err(e) =
+                        cache.record_checkout_checksum(revision, &result.path, &checksum)
+                    {
+                        tracing::debug!("Failed to record checksum of {}: {}", name, e);
+                    }
+                }
+                checksum
+            }
+        };
+        checksums.insert(name.clone(), format!("blake3:{}", checksum));
+    }
+    Ok(checksums)
+}
+
+/// The commit a git checkout is at. Checkouts fetched while resolving, or
+/// already in the cache, carry no revision of their own, so it's read from
+/// the checkout.
+fn fetched_revision(result: &FetchResult) -> Option<String> {
+    result
+        .revision
+        .clone()
+        .or_else(|| gust_fetch::checkout_revision(&result.path))
+}
+
+/// Fail if a git checkout no longer matches the checksum `lockfile`
+/// recorded for the same revision, e.g. after a force-pushed tag or a
+/// modified cache.
+fn verify_locked_checksums(
+    lockfile: &Lockfile,
+    resolution: &Resolution,
+    fetch_results: &HashMap<String, FetchResult>,
+    checksums: &HashMap<String, String>,
+) -> Result<()> {
+    for locked in &lockfile.packages {
+        let (Some(expected), Some(actual)) = (&locked.checksum, checksums.get(&locked.name)) else {
+            continue;
+        };
+        let Some(resolved) = resolution.packages.get(&locked.name) else {
+            continue;
+        };
+        let gust_resolver::ResolvedSource::Git { revision, tag, .. } = &resolved.source else {
+            continue;
+        };
+        // A branch moves, so only tags and commits have fixed content
+        if tag.is_none() && revision == "HEAD" {
+            continue;
+        }
+        let revision = fetch_results
+            .get(&locked.name)
+            .and_then(fetched_revision)
+            .unwrap_or_else(|| revision.clone());
+        // A new revision or version is expected to have new content
+        if Some(revision) != locked.revision || resolved.version != locked.version {
+            continue;
+        }
+        if expected != actual {
+            let path = fetch_results
+                .get(&locked.name)
+                .map(|r| r.path.display().to_string())
+                .unwrap_or_default();
+            return Err(miette::miette!(
+                help = format!(
+                    "Remove {} (or run `gust cache clean`) and install again to refetch it",
+                    path
+                ),
+                "Checksum mismatch for {}: Gust.lock has {}, the checkout has {}",
+                locked.name,
+                expected,
+                actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The error for a `--keep-going` install in which `failures` couldn't be
+/// installed.
+fn failures_error(failures: &BTreeMap<String, String>) -> miette::Report {
+    let list: Vec<String> = failures
+        .iter()
+        .map(|(name, error)| format!("  {}: {}", name, error))
+        .collect();
+    miette::miette!(
+        help = "The other packages were installed and locked",
+        "{} package(s) failed to install:\n{}",
+        failures.len(),
+        list.join("\n")
+    )
+}
+
+/// Sanitize a package name for use as a directory name.
+/// Where the checkout of `dep` lives in `cache`: git checkouts by name,
+/// and path dependencies by name and source directory, so path packages of
+/// the same name in different projects don't replace each other or a git
+/// checkout.
+pub(crate) fn checkout_dir(cache: &GlobalCache, name: &str, dep: &Dependency) -> PathBuf {
+    match &dep.path {
+        Some(source) => {
+            let source = std::fs::canonicalize(source).unwrap_or_else(|_| source.clone());
+            let hash = GlobalCache::hash_bytes(source.to_string_lossy().as_bytes());
+            cache
+                .paths_dir()
+                .join(format!("{}-{}", sanitize_name(name), &hash[..16]))
+        }
+        None => cache.git_dir().join(sanitize_name(name)),
+    }
+}
+
+pub(crate) fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// What Gust.lock is written from.
+#[derive(Clone, Copy)]
+struct LockInputs<'a> {
+    manifest: &'a Manifest,
+    resolution: &'a Resolution,
+    fetch_results: &'a HashMap<String, FetchResult>,
+    /// Checkout checksums by package name
+    checksums: &'a HashMap<String, String>,
+    /// The Gust.lock on disk, if any
+    existing_lockfile: Option<&'a Lockfile>,
+    /// Packages that failed to fetch, with why
+    failures: &'a BTreeMap<String, String>,
+}
+
+/// Result of an installation.
+#[derive(Debug)]
+pub struct InstallResult {
+    /// Number of packages installed
+    pub installed: usize,
+    /// What linking into `.build/checkouts` did
+    pub links: LinkStats,
+    /// Where the time went
+    pub timings: InstallTimings,
+}
+
+/// Links in `.build/checkouts` created, repointed and left as they were by
+/// an install.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl LinkStats {
+    /// Number of linked packages.
+    pub fn total(&self) -> usize {
+        self.created + self.updated + self.unchanged
+    }
+}
+
+impl std::fmt::Display for LinkStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} new, {} updated, {} unchanged",
+            self.created, self.updated, self.unchanged
+        )
+    }
+}
+
+/// Wall-clock time spent in each install phase and on each package.
+#[derive(Debug, Clone, Default)]
+pub struct InstallTimings {
+    /// Phases in the order they ran
+    pub phases: Vec<(&'static str, Duration)>,
+    /// Time to fetch each package that wasn't already cached
+    pub fetches: HashMap<String, Duration>,
+    /// Time to parse each package's manifest
+    pub parses: HashMap<String, Duration>,
+}
+
+impl InstallTimings {
+    /// Record a phase that ran from `start` until now, and restart the clock.
+    fn record_phase(&mut self, phase: &'static str, start: &mut Instant) {
+        self.phases.push((phase, start.elapsed()));
+        *start = Instant::now();
+    }
+
+    /// Sum of all phases.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// The `n` slowest package fetches, slowest first.
+    pub fn slowest_fetches(&self, n: usize) -> Vec<(&str, Duration)> {
+        let mut fetches: Vec<(&str, Duration)> = self
+            .fetches
+            .iter()
+            .map(|(name, d)| (name.as_str(), *d))
+            .collect();
+        fetches.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        fetches.truncate(n);
+        fetches
+    }
+
+    /// Human-readable report with the `slowest` slowest fetches.
+    pub fn report(&self, slowest: usize) -> Vec<String> {
+        let mut lines = vec!["Timings:".to_string()];
+        for (phase, duration) in &self.phases {
+            lines.push(format!("  {:<10} {:>8.2}s", phase, duration.as_secs_f64()));
+        }
+        lines.push(format!(
+            "  {:<10} {:>8.2}s",
+            "total",
+            self.total().as_secs_f64()
+        ));
+
+        let fetches = self.slowest_fetches(slowest);
+        if !fetches.is_empty() {
+            lines.push("Slowest fetches:".to_string());
+            for (name, duration) in fetches {
+                let parse = match self.parses.get(name) {
+                    Some(p) => format!(" (parse {:.2}s)", p.as_secs_f64()),
+                    None => String::new(),
+                };
+                lines.push(format!(
+                    "  {} {:.2}s{}",
+                    name,
+                    duration.as_secs_f64(),
+                    parse
+                ));
+            }
+        }
+        lines
+    }
+
+    /// The same data as [`report`](Self::report), as JSON.
+    pub fn to_json(&self, slowest: usize) -> serde_json::Value {
+        let phases: Vec<serde_json::Value> = self
+            .phases
+            .iter()
+            .map(|(phase, d)| serde_json::json!({ "phase": phase, "secs": d.as_secs_f64() }))
+            .collect();
+        let fetches: Vec<serde_json::Value> = self
+            .slowest_fetches(slowest)
+            .into_iter()
+            .map(|(name, d)| {
+                serde_json::json!({
+                    "name": name,
+                    "fetch_secs": d.as_secs_f64(),
+                    "parse_secs": self.parses.get(name).map(Duration::as_secs_f64),
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "phases": phases,
+            "total_secs": self.total().as_secs_f64(),
+            "slowest_fetches": fetches,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::PlainReporter;
+
+    fn resolved(name: &str, deps: &[&str]) -> ResolvedDep {
+        ResolvedDep {
+            name: name.to_string(),
+            version: Version::new(1, 0, 0),
+            source: gust_resolver::ResolvedSource::Registry { repository: None },
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    fn write_package(dir: &Path, name: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("Gust.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"1.0.0\"\n", name),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dependency_cycle_is_an_error() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (alpha, beta) = (tmp.path().join("alpha"), tmp.path().join("beta"));
+        for (dir, name, other, other_dir) in [
+            (&alpha, "alpha", "beta", &beta),
+            (&beta, "beta", "alpha", &alpha),
+        ] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(
+                dir.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"1.0.0\"\n\n[dependencies]\n{} = {{ path = \"{}\" }}\n",
+                    name,
+                    other,
+                    other_dir.display()
+                ),
+            )
+            .unwrap();
+        }
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+
+        let installer = Installer::with_cache(
+            project.clone(),
+            InstallOptions {
+                concurrency: 2,
+                reporter: Some(ReporterKind::Plain),
+                ..Default::default()
+            },
+            GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+        );
+        let err = installer.install().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependency cycle detected: alpha -> beta -> alpha"
+        );
+        assert!(!project.join("Gust.lock").exists());
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_past_max_depth_are_named() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let chain = ["alpha", "beta", "gamma", "delta"];
+        for (i, name) in chain.iter().enumerate() {
+            let dir = tmp.path().join("deps").join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            let mut toml = format!(
+                "[package]\nname = \"{}\"\nversion = \"1.0.0\"\n\n[dependencies]\n",
+                name
+            );
+            if let Some(next) = chain.get(i + 1) {
+                toml.push_str(&format!(
+                    "{} = {{ path = \"{}\" }}\n",
+                    next,
+                    tmp.path().join("deps").join(next).display()
+                ));
+            }
+            std::fs::write(dir.join("Gust.toml"), toml).unwrap();
+        }
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                tmp.path().join("deps").join("alpha").display()
+            ),
+        )
+        .unwrap();
+
+        let installer = |strict_depth, reporter: Arc<dyn Reporter>| Installer {
+            project_dir: project.clone(),
+            cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            fetcher: Fetcher::new(),
+            registry: gust_registry::RegistryClient::new(),
+            reporter,
+            options: InstallOptions {
+                concurrency: 2,
+                max_depth: Some(2),
+                strict_depth,
+                ..Default::default()
+            },
+        };
+
+        let err = installer(true, ReporterKind::Plain.reporter())
+            .install()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependencies deeper than 2 levels were not resolved: gamma"
+        );
+        assert!(!project.join("Gust.lock").exists());
+
+        let out = Arc::new(PlainReporter::new(Vec::new()));
+        installer(false, out.clone()).install().await.unwrap();
+        let out = String::from_utf8(Arc::try_unwrap(out).ok().unwrap().into_inner()).unwrap();
+        assert!(out.contains("warning: Stopped at --max-depth 2; left unresolved: gamma"));
+        assert!(out.contains("Resolved 2 total packages (depth 2)"));
+        assert!(out.contains("warning: Gust.lock was left as it was"));
+        assert!(!project.join("Gust.lock").exists());
+        assert!(installer(false, ReporterKind::Plain.reporter())
+            .lock()
+            .await
+            .is_err());
+        assert!(!project.join("Gust.lock").exists());
+    }
+
+    #[test]
+    fn test_find_cycle_reports_the_path() {
+        let packages: HashMap<String, ResolvedDep> = [
+            resolved("a", &["b"]),
+            resolved("b", &["c"]),
+            resolved("c", &["b", "d"]),
+            resolved("d", &[]),
+        ]
+        .into_iter()
+        .map(|p| (p.name.clone(), p))
+        .collect();
+        assert_eq!(find_cycle(&packages).unwrap(), ["b", "c", "b"]);
+
+        let acyclic: HashMap<String, ResolvedDep> = [
+            resolved("a", &["b", "c"]),
+            resolved("b", &["c"]),
+            resolved("c", &[]),
+        ]
+        .into_iter()
+        .map(|p| (p.name.clone(), p))
+        .collect();
+        assert!(find_cycle(&acyclic).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reinstall_prunes_removed_checkouts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let deps_dir = tmp.path().join("deps");
+
+        let mut deps_toml = String::new();
+        for name in ["alpha", "beta", "gamma"] {
+            write_package(&deps_dir.join(name), name);
+            deps_toml.push_str(&format!(
+                "{} = {{ path = \"{}\" }}\n",
+                name,
+                deps_dir.join(name).display()
+            ));
+        }
+
+        let installer = Installer {
+            project_dir: project.clone(),
+            cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            fetcher: Fetcher::new(),
+            registry: gust_registry::RegistryClient::new(),
+            reporter: ReporterKind::Plain.reporter(),
+            options: InstallOptions {
+                concurrency: 2,
+                ..Default::default()
+            },
+        };
+
+        std::fs::create_dir_all(&project).unwrap();
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n";
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!("{}{}", manifest, deps_toml),
+        )
+        .unwrap();
+        installer.install().await.unwrap();
+
+        let checkouts = project.join(".build").join("checkouts");
+        for name in ["alpha", "beta", "gamma"] {
+            assert!(
+                checkouts.join(name).is_symlink(),
+                "{} should be linked",
+                name
+            );
+        }
+
+        // Drop `beta` from the manifest and reinstall
+        let remaining: String = deps_toml
+            .lines()
+            .filter(|l| !l.starts_with("beta"))
+            .map(|l| format!("{}\n", l))
+            .collect();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!("{}{}", manifest, remaining),
+        )
+        .unwrap();
+        installer.install().await.unwrap();
+
+        assert!(checkouts.join("alpha").is_symlink());
+        assert!(checkouts.join("gamma").is_symlink());
+        assert!(!checkouts.join("beta").exists());
+        assert!(!checkouts.join("beta").is_symlink());
+    }
+
+    #[tokio::test]
+    async fn test_reinstall_leaves_current_links_untouched() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let deps_dir = tmp.path().join("deps");
+
+        let mut manifest =
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n".to_string();
+        for name in ["alpha", "beta"] {
+            write_package(&deps_dir.join(name), name);
+            manifest.push_str(&format!(
+                "{} = {{ path = \"{}\" }}\n",
+                name,
+                deps_dir.join(name).display()
+            ));
+        }
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("Gust.toml"), manifest).unwrap();
+
+        let installer = Installer {
+            project_dir: project.clone(),
+            cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            fetcher: Fetcher::new(),
+            registry: gust_registry::RegistryClient::new(),
+            reporter: ReporterKind::Plain.reporter(),
+            options: InstallOptions::default(),
+        };
+        let first = installer.install().await.unwrap();
+        assert_eq!(
+            first.links,
+            LinkStats {
+                created: 2,
+                updated: 0,
+                unchanged: 0,
+            }
+        );
+
+        let checkouts = project.join(".build").join("checkouts");
+        let mtimes = || {
+            ["", "alpha", "beta"]
+                .iter()
+                .map(|name| {
+                    std::fs::symlink_metadata(checkouts.join(name))
+                        .unwrap()
+                        .modified()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let before = mtimes();
+
+        let second = installer.install().await.unwrap();
+        assert_eq!(
+            second.links,
+            LinkStats {
+                created: 0,
+                updated: 0,
+                unchanged: 2,
+            }
+        );
+        // Neither the links nor the directory holding them were rewritten
+        assert_eq!(mtimes(), before);
+
+        // A link pointing elsewhere is repointed
+        let alpha = checkouts.join("alpha");
+        let target = std::fs::read_link(&alpha).unwrap();
+        std::fs::remove_file(&alpha).unwrap();
+        std::os::unix::fs::symlink(&deps_dir, &alpha).unwrap();
+        let third = installer.install().await.unwrap();
+        assert_eq!(third.links.updated, 1);
+        assert_eq!(third.links.unchanged, 1);
+        assert_eq!(std::fs::read_link(&alpha).unwrap(), target);
+    }
+
+    #[tokio::test]
+    async fn test_optional_dependency_installed_only_with_its_feature() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let deps_dir = tmp.path().join("deps");
+        write_package(&deps_dir.join("alpha"), "alpha");
+        write_package(&deps_dir.join("yams"), "yams");
+
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\nyams = {{ path = \"{}\", optional = true }}\n\n[features]\nyaml = [\"yams\"]\n",
+                deps_dir.join("alpha").display(),
+                deps_dir.join("yams").display()
+            ),
+        )
+        .unwrap();
+
+        let installer = |features: Vec<String>| Installer {
+            project_dir: project.clone(),
+            cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            fetcher: Fetcher::new(),
+            registry: gust_registry::RegistryClient::new(),
+            reporter: ReporterKind::Plain.reporter(),
+            options: InstallOptions {
+                concurrency: 2,
+                features,
+                ..Default::default()
+            },
+        };
+        let checkouts = project.join(".build").join("checkouts");
+
+        let result = installer(Vec::new()).install().await.unwrap();
+        assert_eq!(result.installed, 1);
+        assert!(checkouts.join("alpha").is_symlink());
+        assert!(!checkouts.join("yams").exists());
+
+        let result = installer(vec!["yaml".to_string()]).install().await.unwrap();
+        assert_eq!(result.installed, 2);
+        assert!(checkouts.join("yams").is_symlink());
+    }
+
+    #[tokio::test]
+    async fn test_modified_cached_checkout_fails_verification() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        write_package(&repo, "dep");
+        gust_testkit::tagged_repo(&repo, "1.0.0");
+
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ndep = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                repo.display()
+            ),
+        )
+        .unwrap();
+
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let checkout = cache.git_dir().join("dep");
+        let installer = Installer::with_cache(
+            project.clone(),
+            InstallOptions {
+                concurrency: 2,
+                ..Default::default()
+            },
+            cache,
+        );
+        installer.install().await.unwrap();
+
+        let lockfile = Lockfile::load(&project.join("Gust.lock")).unwrap();
+        let locked = lockfile.get("dep").unwrap();
+        assert!(locked
+            .checksum
+            .as_deref()
+            .is_some_and(|c| c.starts_with("blake3:")));
+
+        // An unchanged checkout still verifies
+        installer.install().await.unwrap();
+
+        std::fs::write(checkout.join("Injected.swift"), "// tampered\n").unwrap();
+        let err = installer.install().await.unwrap_err();
+        assert!(
+            err.to_string().starts_with("Checksum mismatch for dep"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manifest_tag_change_resolves_again() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        write_package(&repo, "dep");
+        gust_testkit::tagged_repo(&repo, "1.0.0");
+        std::fs::write(
+            repo.join("Gust.toml"),
+            "[package]\nname = \"dep\"\nversion = \"1.1.0\"\n",
+        )
+        .unwrap();
+        gust_testkit::git(&repo, &["commit", "-q", "-am", "release"]);
+        gust_testkit::git(&repo, &["tag", "1.1.0"]);
+
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        let write_manifest = |tag: &str| {
+            std::fs::write(
+                project.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ndep = {{ git = \"file://{}\", tag = \"{}\" }}\n",
+                    repo.display(),
+                    tag
+                ),
+            )
+            .unwrap();
+        };
+        let installer = |frozen: bool| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    frozen,
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+        let locked = || {
+            Lockfile::load(&project.join("Gust.lock"))
+                .unwrap()
+                .get("dep")
+                .unwrap()
+                .version
+                .clone()
+        };
+
+        write_manifest("1.0.0");
+        installer(false).install().await.unwrap();
+        assert_eq!(locked(), Version::new(1, 0, 0));
+
+        // --frozen refuses the edited tag rather than installing the old one
+        write_manifest("1.1.0");
+        let err = installer(true).install().await.unwrap_err().to_string();
+        assert!(
+            err.starts_with("Gust.lock is out of date with Gust.toml"),
+            "{}",
+            err
+        );
+        assert_eq!(locked(), Version::new(1, 0, 0));
+
+        installer(false).install().await.unwrap();
+        assert_eq!(locked(), Version::new(1, 1, 0));
+        let linked = project.join(".build/checkouts/dep/Gust.toml");
+        assert!(std::fs::read_to_string(linked)
+            .unwrap()
+            .contains("version = \"1.1.0\""));
+    }
+
+    #[tokio::test]
+    async fn test_grouped_dependency_installed_only_with_its_group() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = |name: &str, extra: &str| {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"1.0.0\"\n{}",
+                    name, extra
+                ),
+            )
+            .unwrap();
+            gust_testkit::tagged_repo(&dir, "1.0.0");
+            format!("file://{}", dir.display())
+        };
+        let bench = repo("bench", "");
+        // A library whose own benchmark group must not reach its consumers
+        let log = repo(
+            "log",
+            &format!(
+                "\n[dependencies]\nbench = {{ git = \"{}\", tag = \"1.0.0\", group = \"bench\" }}\n",
+                bench
+            ),
+        );
+
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nlog = {{ git = \"{}\", tag = \"1.0.0\" }}\nbench = {{ git = \"{}\", tag = \"1.0.0\", group = \"bench\" }}\n",
+                log, bench
+            ),
+        )
+        .unwrap();
+
+        let installer = |groups: &[&str]| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    groups: groups.iter().map(|g| g.to_string()).collect(),
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+        let locked = || {
+            Lockfile::load(&project.join("Gust.lock"))
+                .unwrap()
+                .packages
+                .into_iter()
+                .map(|p| (p.name, p.group))
+                .collect::<Vec<_>>()
+        };
+
+        installer(&[]).install().await.unwrap();
+        assert_eq!(locked(), vec![("log".to_string(), None)]);
+        let checkouts = project.join(".build/checkouts");
+        assert!(!checkouts.join("bench").exists());
+
+        installer(&["bench"]).install().await.unwrap();
+        assert_eq!(
+            locked(),
+            vec![
+                ("bench".to_string(), Some("bench".to_string())),
+                ("log".to_string(), None),
+            ]
+        );
+        assert!(checkouts.join("bench").exists());
+
+        // A default install leaves the group's lock entry alone
+        installer(&[]).install().await.unwrap();
+        assert_eq!(locked().len(), 2);
+        assert!(installer(&["docs"]).install().await.is_err());
+    }
+
+    #[test]
+    fn test_dependency_build_flags_apply_to_that_dependency() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+beta = { git = "https://example.com/beta.git", tag = "1.0.0" }
+
+[dependencies.alpha]
+git = "https://example.com/alpha.git"
+tag = "1.0.0"
+
+[dependencies.alpha.build]
+swift_flags = ["-suppress-warnings"]
+"#,
+        )
+        .unwrap();
+        let manifest = gust_manifest::parse_gust_toml(&project.join("Gust.toml")).unwrap();
+        let installer = Installer::with_cache(
+            project.clone(),
+            InstallOptions::default(),
+            GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+        );
+
+        let mut resolution = Resolution::default();
+        let mut fetch_results = HashMap::new();
+        for name in ["alpha", "beta"] {
+            let checkout = installer.cache.git_dir().join(name);
+            std::fs::create_dir_all(checkout.join("Sources")).unwrap();
+            std::fs::write(
+                checkout.join("Package.swift"),
+                format!("let package = Package(name: \"{}\")\n", name),
+            )
+            .unwrap();
+            std::fs::write(
+                checkout.join("Package@swift-5.9.swift"),
+                format!("let package = Package(name: \"{}\")\n", name),
+            )
+            .unwrap();
+            std::fs::write(checkout.join("Sources/lib.swift"), "").unwrap();
+            resolution.packages.insert(
+                name.to_string(),
+                ResolvedDep {
+                    name: name.to_string(),
+                    version: Version::new(1, 0, 0),
+                    source: gust_resolver::ResolvedSource::Git {
+                        url: format!("https://example.com/{}.git", name),
+                        revision: "HEAD".to_string(),
+                        tag: Some("1.0.0".to_string()),
+                    },
+                    dependencies: Vec::new(),
+                },
+            );
+            fetch_results.insert(
+                name.to_string(),
+                FetchResult {
+                    name: name.to_string(),
+                    path: checkout,
+                    checksum: String::new(),
+                    revision: None,
+                    tag: None,
+                    bytes: None,
+                    duration: Duration::ZERO,
+                },
+            );
+        }
+        installer
+            .link_packages(&manifest, &resolution, &fetch_results, &Edits::default())
+            .unwrap();
+
+        let checkouts = project.join(".build/checkouts");
+        assert_eq!(
+            std::fs::read_link(checkouts.join("alpha")).unwrap(),
+            project.join(".build/build-overlays/alpha")
+        );
+        let alpha = std::fs::read_to_string(checkouts.join("alpha/Package.swift")).unwrap();
+        assert!(alpha.contains("[.unsafeFlags([\"-suppress-warnings\"])]"));
+        let versioned =
+            std::fs::read_to_string(checkouts.join("alpha/Package@swift-5.9.swift")).unwrap();
+        assert!(versioned.contains("[.unsafeFlags([\"-suppress-warnings\"])]"));
+        assert!(checkouts.join("alpha/Sources/lib.swift").exists());
+        let beta = std::fs::read_to_string(checkouts.join("beta/Package.swift")).unwrap();
+        assert!(!beta.contains("unsafeFlags"));
+        // The shared checkout is untouched
+        let cached =
+            std::fs::read_to_string(installer.cache.git_dir().join("alpha/Package.swift")).unwrap();
+        assert!(!cached.contains("unsafeFlags"));
+    }
+
+    #[tokio::test]
+    async fn test_lock_writes_lockfile_without_checkouts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dep = tmp.path().join("alpha");
+        write_package(&dep, "alpha");
+        gust_testkit::tagged_repo(&dep, "1.0.0");
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                dep.display()
+            ),
+        )
+        .unwrap();
+        let installer = |frozen: bool, offline: bool| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    frozen,
+                    offline,
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+
+        // Nothing is cached yet, and there's no lockfile to be frozen to
+        assert!(installer(false, true).lock().await.is_err());
+        assert!(installer(true, false).lock().await.is_err());
+
+        let diff = installer(false, false).lock().await.unwrap().unwrap();
+        assert_eq!(diff.added.len(), 1);
+        let lockfile = Lockfile::load(&project.join("Gust.lock")).unwrap();
+        assert_eq!(lockfile.packages[0].name, "alpha");
+        assert_eq!(lockfile.packages[0].version, Version::new(1, 0, 0));
+        assert!(lockfile.packages[0].checksum.is_some());
+        assert!(!project.join(".build").exists());
+
+        // Once cached, it resolves offline, and frozen agrees with the lock
+        assert!(installer(false, true).lock().await.unwrap().is_none());
+        assert!(installer(true, true).lock().await.unwrap().is_none());
+        assert!(!project.join(".build/checkouts").exists());
+    }
+
+    #[tokio::test]
+    async fn test_keep_going_installs_packages_that_fetch() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut deps = String::new();
+        for name in ["alpha", "gamma"] {
+            let dir = tmp.path().join(name);
+            write_package(&dir, name);
+            gust_testkit::tagged_repo(&dir, "1.0.0");
+            deps.push_str(&format!(
+                "{} = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                name,
+                dir.display()
+            ));
+        }
+        // A repository that no longer exists
+        deps.push_str(&format!(
+            "beta = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+            tmp.path().join("beta").display()
+        ));
+
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                deps
+            ),
+        )
+        .unwrap();
+        let installer = |keep_going: bool| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    keep_going,
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+
+        // Without --keep-going nothing is installed
+        assert!(installer(false).install().await.is_err());
+        assert!(!project.join("Gust.lock").exists());
+
+        let err = installer(true).install().await.unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("1 package(s) failed to install:\n  beta: "),
+            "{}",
+            err
+        );
+        let checkouts = project.join(".build/checkouts");
+        assert!(checkouts.join("alpha").is_symlink());
+        assert!(checkouts.join("gamma").is_symlink());
+        assert!(!checkouts.join("beta").exists());
+        let locked: Vec<String> = Lockfile::load(&project.join("Gust.lock"))
+            .unwrap()
+            .packages
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(locked, vec!["alpha", "gamma"]);
+    }
+
+    #[tokio::test]
+    async fn test_fetched_checkouts_are_stored_by_content() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("dep");
+        write_package(&repo, "dep");
+        gust_testkit::tagged_repo(&repo, "1.0.0");
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ndep = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                repo.display()
+            ),
+        )
+        .unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        Installer::with_cache(
+            project.clone(),
+            InstallOptions {
+                concurrency: 2,
+                ..Default::default()
+            },
+            cache.clone(),
+        )
+        .install()
+        .await
+        .unwrap();
+
+        let stored = gust_cache::PackageMetadata::load(&cache, "dep", "1.0.0").unwrap();
+        let hash = &stored.files["Gust.toml"];
+        let manifest = std::fs::read_to_string(repo.join("Gust.toml")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(cache.get_path(hash).unwrap()).unwrap(),
+            manifest
+        );
+        assert!(!stored.files.keys().any(|f| f.starts_with(".git")));
+
+        // Editing the checkout leaves the stored file alone
+        let checkout = cache.git_dir().join("dep");
+        std::fs::write(checkout.join("Gust.toml"), "# edited\n").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(cache.get_path(hash).unwrap()).unwrap(),
+            manifest
+        );
+
+        // A missing checkout is rebuilt from the store, with the repository
+        // gone and no network, and still matches Gust.lock
+        std::fs::remove_dir_all(&checkout).unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+        for frozen in [false, true] {
+            if checkout.exists() {
+                std::fs::remove_dir_all(&checkout).unwrap();
+            }
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    offline: true,
+                    frozen,
+                    ..Default::default()
+                },
+                cache.clone(),
+            )
+            .install()
+            .await
+            .unwrap();
+            assert_eq!(
+                std::fs::read_to_string(checkout.join("Gust.toml")).unwrap(),
+                manifest
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unfetchable_transitive_dependency_is_left_out() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        // alpha depends on a repository that no longer exists
+        let alpha = tmp.path().join("alpha");
+        std::fs::create_dir_all(&alpha).unwrap();
+        std::fs::write(
+            alpha.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"alpha\"\nversion = \"1.0.0\"\n\n[dependencies]\nbeta = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                tmp.path().join("beta").display()
+            ),
+        )
+        .unwrap();
+        gust_testkit::tagged_repo(&alpha, "1.0.0");
+
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+        let reporter = Arc::new(PlainReporter::new(Vec::new()));
+        let installer = Installer {
+            reporter: reporter.clone(),
+            ..Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+
+        installer.install().await.unwrap();
+        drop(installer);
+        let locked: Vec<String> = Lockfile::load(&project.join("Gust.lock"))
+            .unwrap()
+            .packages
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(locked, vec!["alpha"]);
+        let output =
+            String::from_utf8(Arc::try_unwrap(reporter).ok().unwrap().into_inner()).unwrap();
+        assert!(
+            output.contains("Couldn't fetch beta, leaving it out: "),
+            "{}",
+            output
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checkout_checksums_are_cached_by_commit() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let alpha = tmp.path().join("alpha");
+        write_package(&alpha, "alpha");
+        gust_testkit::tagged_repo(&alpha, "1.0.0");
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let installer = || {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    ..Default::default()
+                },
+                cache.clone(),
+            )
+        };
+
+        // The first install replaces the checkout's files with copies from the
+        // package store after hashing it, so the second records it for good
+        installer().install().await.unwrap();
+        installer().install().await.unwrap();
+        let checksum = || {
+            Lockfile::load(&project.join("Gust.lock")).unwrap().packages[0]
+                .checksum
+                .clone()
+                .unwrap()
+        };
+        let checkout = cache.git_dir().join("alpha");
+        let revision = gust_fetch::checkout_revision(&checkout).unwrap();
+        assert_eq!(
+            cache
+                .checkout_checksum(&revision, &checkout)
+                .map(|c| format!("blake3:{}", c)),
+            Some(checksum())
+        );
+
+        // The cached checkout isn't hashed again: a recorded checksum is
+        // what the next install locks
+        cache
+            .record_checkout_checksum(&revision, &checkout, "cached")
+            .unwrap();
+        std::fs::remove_file(project.join("Gust.lock")).unwrap();
+        installer().install().await.unwrap();
+        assert_eq!(checksum(), "blake3:cached");
+
+        // Unless its files changed
+        std::fs::write(checkout.join("Injected.swift"), "").unwrap();
+        std::fs::remove_file(project.join("Gust.lock")).unwrap();
+        installer().install().await.unwrap();
+        assert_ne!(checksum(), "blake3:cached");
+    }
+
+    #[tokio::test]
+    async fn test_frozen_install_resolves_locked_git_packages_without_revision() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let alpha = tmp.path().join("alpha");
+        write_package(&alpha, "alpha");
+        gust_testkit::tagged_repo(&alpha, "1.0.0");
+
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+        // An older lockfile that recorded no revision
+        std::fs::write(
+            project.join("Gust.lock"),
+            format!(
+                "version = 1\ngenerated-by = \"gust 0.1.0\"\n\n[[package]]\nname = \"alpha\"\nversion = \"1.0.0\"\nsource = \"git\"\ngit = \"file://{}\"\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+        let reporter = Arc::new(PlainReporter::new(Vec::new()));
+        let installer = Installer {
+            reporter: reporter.clone(),
+            ..Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    frozen: true,
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+
+        installer.install().await.unwrap();
+        drop(installer);
+        assert!(project.join(".build/checkouts/alpha/Gust.toml").exists());
+        let output =
+            String::from_utf8(Arc::try_unwrap(reporter).ok().unwrap().into_inner()).unwrap();
+        assert!(
+            output.contains("Gust.lock has no revision for alpha; resolving it again"),
+            "{}",
+            output
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_release_records_repository() {
+        // A registry that serves one release with SSH and HTTPS repository URLs
+        let server = gust_testkit::MockServer::start(|request| {
+            if request.path != "/apple/swift-nio/2.58.0" {
+                return gust_testkit::Response::new(404);
+            }
+            gust_testkit::Response::json(
+                r#"{"id": "apple.swift-nio", "version": "2.58.0", "resources": [],
+                "metadata": {"repositoryURLs": ["git@github.com:apple/swift-nio.git",
+                                                "https://github.com/apple/swift-nio.git"]}}"#,
+            )
+        })
+        .await;
+
+        let mut packages = HashMap::from([(
+            "apple.swift-nio".to_string(),
+            ResolvedDep {
+                name: "apple.swift-nio".to_string(),
+                version: Version::new(2, 58, 0),
+                source: gust_resolver::ResolvedSource::Registry { repository: None },
+                dependencies: Vec::new(),
+            },
+        )]);
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let client = gust_registry::RegistryClient::with_url(server.url());
+        backfill_registry_repositories(&mut packages, None, &client, &cache, 4).await;
+        assert_eq!(server.request_lines(), vec!["GET /apple/swift-nio/2.58.0"]);
+
+        let resolved = packages["apple.swift-nio"].clone();
+        let locked = LockedPackage::from(ResolvedPackage::from(resolved));
+        assert_eq!(
+            locked.repository.as_deref(),
+            Some("https://github.com/apple/swift-nio.git")
+        );
+
+        // Once locked, the registry isn't asked again
+        let lockfile = Lockfile {
+            packages: vec![locked],
+            ..Default::default()
+        };
+        let mut packages = HashMap::from([(
+            "apple.swift-nio".to_string(),
+            ResolvedDep::try_from(ResolvedPackage {
+                repository: None,
+                ..ResolvedPackage::from(lockfile.packages[0].clone())
+            })
+            .unwrap(),
+        )]);
+        backfill_registry_repositories(&mut packages, Some(&lockfile), &client, &cache, 4).await;
+        assert_eq!(server.requests().len(), 1);
+        assert!(matches!(
+            &packages["apple.swift-nio"].source,
+            gust_resolver::ResolvedSource::Registry {
+                repository: Some(_)
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_registry_releases_without_repository_are_asked_about_once() {
+        // Releases without repository URLs, and one the registry doesn't know
+        let server = gust_testkit::MockServer::start(|request| match request.path.as_str() {
+            "/apple/swift-log/1.5.0" | "/apple/swift-nio/2.58.0" => {
+                gust_testkit::Response::json(r#"{"id": "x", "version": "1.0.0", "resources": []}"#)
+            }
+            _ => gust_testkit::Response::new(404),
+        })
+        .await;
+        let registry = |name: &str, version: Version| {
+            (
+                name.to_string(),
+                ResolvedDep {
+                    name: name.to_string(),
+                    version,
+                    source: gust_resolver::ResolvedSource::Registry { repository: None },
+                    dependencies: Vec::new(),
+                },
+            )
+        };
+        let packages = || {
+            HashMap::from([
+                registry("apple.swift-log", Version::new(1, 5, 0)),
+                registry("apple.swift-nio", Version::new(2, 58, 0)),
+                registry("apple.swift-missing", Version::new(1, 0, 0)),
+            ])
+        };
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        let client = gust_registry::RegistryClient::with_url(server.url());
+
+        backfill_registry_repositories(&mut packages(), None, &client, &cache, 4).await;
+        let mut asked = server.request_lines();
+        asked.sort();
+        assert_eq!(
+            asked,
+            vec![
+                "GET /apple/swift-log/1.5.0",
+                "GET /apple/swift-missing/1.0.0",
+                "GET /apple/swift-nio/2.58.0",
+            ]
+        );
+
+        // Only the failed lookup is tried again
+        server.clear();
+        backfill_registry_repositories(&mut packages(), None, &client, &cache, 4).await;
+        assert_eq!(
+            server.request_lines(),
+            vec!["GET /apple/swift-missing/1.0.0"]
+        );
+
+        // Another registry may know the repositories, so it's asked too
+        server.clear();
+        let other = gust_registry::RegistryClient::with_url(format!("{}/mirror", server.url()));
+        backfill_registry_repositories(&mut packages(), None, &other, &cache, 4).await;
+        assert_eq!(server.request_lines().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_install_skips_yanked_registry_releases_unless_pinned() {
+        // A registry listing swift-log 1.4.0 and a yanked 1.5.0, with no
+        // metadata for single releases
+        let server = gust_testkit::MockServer::start(|request| {
+            if request.method == "GET" && request.path == "/apple/swift-log" {
+                gust_testkit::Response::json(
+                    r#"{"releases": {"1.4.0": {},
+                        "1.5.0": {"problem": {"status": "410", "title": "Release yanked"}}}}"#,
+                )
+            } else {
+                gust_testkit::Response::new(404)
+            }
+        })
+        .await;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        let install = |requirement: &str| {
+            std::fs::write(
+                project.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n\"apple.swift-log\" = \"{}\"\n",
+                    requirement
+                ),
+            )
+            .unwrap();
+            let _ = std::fs::remove_file(project.join("Gust.lock"));
+            let reporter = Arc::new(crate::reporter::PlainReporter::new(Vec::new()));
+            let installer = Installer {
+                project_dir: project.clone(),
+                cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+                fetcher: Fetcher::new(),
+                registry: gust_registry::RegistryClient::with_url(server.url()),
+                reporter: reporter.clone(),
+                options: InstallOptions::default(),
+            };
+            let lockfile_path = project.join("Gust.lock");
+            async move {
+                installer.install().await.unwrap();
+                drop(installer);
+                let output = Arc::try_unwrap(reporter).ok().unwrap().into_inner();
+                let lockfile = Lockfile::load(&lockfile_path).unwrap();
+                (
+                    lockfile.get("apple.swift-log").unwrap().version.clone(),
+                    String::from_utf8(output).unwrap(),
+                )
+            }
+        };
+
+        let (version, output) = install("^1.4").await;
+        assert_eq!(version, Version::new(1, 4, 0));
+        assert!(!output.contains("yanked"), "{}", output);
+
+        let (version, output) = install("=1.5.0").await;
+        assert_eq!(version, Version::new(1, 5, 0));
+        assert!(
+            output.contains("apple.swift-log 1.5.0 is pinned but has a problem: Release yanked"),
+            "{}",
+            output
+        );
+    }
+
+    #[tokio::test]
+    async fn test_overrides_and_constraints_layer_config_manifest_and_flags() {
+        let server = gust_testkit::MockServer::start(|request| {
+            if request.method == "GET" && request.path == "/apple/swift-log" {
+                gust_testkit::Response::json(
+                    r#"{"releases": {"1.4.0": {}, "1.5.0": {}, "1.6.0": {}}}"#,
+                )
+            } else {
+                gust_testkit::Response::new(404)
+            }
+        })
+        .await;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n\"apple.swift-log\" = \"^1.4\"\n",
+        )
+        .unwrap();
+        // Overrides and constraints as `--override` and `--constraint` take them
+        let sources = |overrides: &[&str], constraints: &[&str]| {
+            let strings =
+                |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            ResolutionSources::from_flags(&strings(overrides), &strings(constraints)).unwrap()
+        };
+        let lock = |config: ResolutionSources, cli: ResolutionSources| {
+            let _ = std::fs::remove_file(project.join("Gust.lock"));
+            let installer = Installer {
+                project_dir: project.clone(),
+                cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+                fetcher: Fetcher::new(),
+                registry: gust_registry::RegistryClient::with_url(server.url()),
+                reporter: ReporterKind::Plain.reporter(),
+                options: InstallOptions {
+                    resolution_defaults: config,
+                    resolution_flags: cli,
+                    ..Default::default()
+                },
+            };
+            let lockfile_path = project.join("Gust.lock");
+            async move {
+                installer.lock().await.unwrap();
+                Lockfile::load(&lockfile_path)
+                    .unwrap()
+                    .get("apple.swift-log")
+                    .unwrap()
+                    .version
+                    .clone()
+            }
+        };
+        let config = sources(&["apple.swift-log@=1.4.0"], &[]);
+
+        // The config's override, unless a flag overrides it; a constraint
+        // only narrows the choice
+        assert_eq!(
+            lock(config.clone(), sources(&[], &[])).await,
+            Version::new(1, 4, 0)
+        );
+        assert_eq!(
+            lock(config.clone(), sources(&["apple.swift-log@=1.6.0"], &[])).await,
+            Version::new(1, 6, 0)
+        );
+        assert_eq!(
+            lock(sources(&[], &[]), sources(&[], &["apple.swift-log@<1.6"])).await,
+            Version::new(1, 5, 0)
+        );
+
+        // Gust.toml's override beats the config's
+        let manifest = std::fs::read_to_string(project.join("Gust.toml")).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "{}\n[overrides]\n\"apple.swift-log\" = \"=1.5.0\"\n",
+                manifest
+            ),
+        )
+        .unwrap();
+        assert_eq!(lock(config, sources(&[], &[])).await, Version::new(1, 5, 0));
+    }
+
+    #[tokio::test]
+    async fn test_constraint_not_met_by_pinned_tag_fails() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let alpha = tmp.path().join("alpha");
+        write_package(&alpha, "alpha");
+        gust_testkit::tagged_repo(&alpha, "1.0.0");
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ git = \"file://{}\", tag = \"1.0.0\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+        let installer = |constraint: &str| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    reporter: Some(ReporterKind::Plain),
+                    resolution_flags: ResolutionSources::from_flags(&[], &[constraint.to_string()])
+                        .unwrap(),
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+
+        let err = installer("alpha@>=2").install().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "alpha is pinned to tag 1.0.0, which doesn't satisfy the constraint >=2"
+        );
+        assert!(!project.join("Gust.lock").exists());
+        installer("alpha@^1").install().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_member_installs_workspace_dependencies() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().join("repo");
+        let alpha = tmp.path().join("deps").join("alpha");
+        write_package(&alpha, "alpha");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"repo\"\nversion = \"0.1.0\"\n\n[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+        let app = root.join("app");
+        std::fs::create_dir_all(&app).unwrap();
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = { workspace = true }\n";
+        std::fs::write(app.join("Gust.toml"), manifest).unwrap();
+
+        let install = |project: PathBuf| {
+            Installer::with_cache(
+                project,
+                InstallOptions {
+                    concurrency: 2,
+                    reporter: Some(ReporterKind::Plain),
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+        let result = install(app.clone()).install().await.unwrap();
+        assert_eq!(result.installed, 1);
+        let lockfile = Lockfile::load(&app.join("Gust.lock")).unwrap();
+        assert!(lockfile.get("alpha").is_some());
+
+        // Outside the workspace there is nowhere to take the source from
+        let outside = tmp.path().join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("Gust.toml"), manifest).unwrap();
+        let err = install(outside).install().await.unwrap_err();
+        assert!(err.to_string().contains("outside a workspace"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_timings_cover_every_phase_in_order() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let dep = tmp.path().join("deps").join("alpha");
+        write_package(&dep, "alpha");
+
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                dep.display()
+            ),
+        )
+        .unwrap();
+
+        let installer = Installer::with_cache(
+            project,
+            InstallOptions {
+                concurrency: 2,
+                ..Default::default()
+            },
+            GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+        );
+        let timings = installer.install().await.unwrap().timings;
+
+        let phases: Vec<&str> = timings.phases.iter().map(|(p, _)| *p).collect();
+        assert_eq!(phases, ["manifest", "resolve", "fetch", "link", "lockfile"]);
+        assert!(timings.fetches.contains_key("alpha"));
+        assert!(timings.parses.contains_key("alpha"));
+
+        let report = timings.report(5);
+        let position = |label: &str| {
+            report
+                .iter()
+                .position(|l| l.trim_start().starts_with(label))
+                .unwrap()
+        };
+        assert!(position("manifest") < position("resolve"));
+        assert!(position("resolve") < position("fetch"));
+        assert!(position("fetch") < position("link"));
+        assert!(position("link") < position("lockfile"));
+        assert!(position("lockfile") < position("total"));
+        assert!(position("Slowest fetches") < position("alpha"));
+
+        let json = timings.to_json(5);
+        let json_phases: Vec<&str> = json["phases"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["phase"].as_str().unwrap())
+            .collect();
+        assert_eq!(json_phases, phases);
+        assert_eq!(json["slowest_fetches"][0]["name"], "alpha");
+    }
+
+    #[tokio::test]
+    async fn test_custom_target_dir_receives_checkouts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let dep = tmp.path().join("deps").join("alpha");
+        write_package(&dep, "alpha");
+
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                dep.display()
+            ),
+        )
+        .unwrap();
+
+        let installer = Installer::with_cache(
+            project.clone(),
+            InstallOptions {
+                concurrency: 2,
+                target_dir: Some(tmp.path().join("shared-build")),
+                ..Default::default()
+            },
+            GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+        );
+        installer.install().await.unwrap();
+
+        assert!(tmp.path().join("shared-build/checkouts/alpha").is_symlink());
+        assert!(!project.join(".build").exists());
+    }
+
+    #[tokio::test]
+    async fn test_copied_path_dependency_is_locked_with_its_checksum() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let dep = tmp.path().join("deps").join("alpha");
+        write_package(&dep, "alpha");
+
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                dep.display()
+            ),
+        )
+        .unwrap();
+
+        let install = |frozen| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    frozen,
+                    path_mode: PathMode::Copy,
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+        // Another project's git checkout of the same name
+        let git_checkout = tmp.path().join("cache").join("git").join("alpha");
+        std::fs::create_dir_all(&git_checkout).unwrap();
+        std::fs::write(git_checkout.join("Package.swift"), "// git").unwrap();
+        install(false).install().await.unwrap();
+
+        let copy = std::fs::read_link(project.join(".build/checkouts/alpha")).unwrap();
+        assert!(copy.starts_with(tmp.path().join("cache").join("paths")));
+        assert!(copy.is_dir() && !copy.is_symlink());
+        assert!(git_checkout.join("Package.swift").exists());
+        let checksum = |lockfile: &Lockfile| {
+            let alpha = lockfile
+                .packages
+                .iter()
+                .find(|p| p.name == "alpha")
+                .unwrap();
+            assert_eq!(alpha.source, DependencySource::Path);
+            alpha.checksum.clone().unwrap()
+        };
+        let first = checksum(&Lockfile::load(&project.join("Gust.lock")).unwrap());
+        assert_eq!(
+            first,
+            format!("blake3:{}", gust_cache::directory_checksum(&copy).unwrap())
+        );
+
+        // A frozen install takes the path from Gust.toml and snapshots the
+        // edited source again
+        std::fs::write(dep.join("Extra.swift"), "// new\n").unwrap();
+        install(true).install().await.unwrap();
+        assert!(copy.join("Extra.swift").exists());
+    }
+
+    #[tokio::test]
+    async fn test_linked_path_dependencies_are_locked_and_install_frozen() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        let alpha = tmp.path().join("deps").join("alpha");
+        let beta = tmp.path().join("deps").join("beta");
+        write_package(&beta, "beta");
+        std::fs::create_dir_all(&alpha).unwrap();
+        std::fs::write(
+            alpha.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"alpha\"\nversion = \"1.0.0\"\n\n[dependencies]\nbeta = {{ path = \"{}\" }}\n",
+                beta.display()
+            ),
+        )
+        .unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            project.join("Gust.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nalpha = {{ path = \"{}\" }}\n",
+                alpha.display()
+            ),
+        )
+        .unwrap();
+
+        let install = |frozen| {
+            Installer::with_cache(
+                project.clone(),
+                InstallOptions {
+                    concurrency: 2,
+                    frozen,
+                    reporter: Some(ReporterKind::Plain),
+                    ..Default::default()
+                },
+                GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            )
+        };
+        install(false).install().await.unwrap();
+        let checksum = |name: &str| {
+            let lockfile = Lockfile::load(&project.join("Gust.lock")).unwrap();
+            let package = lockfile.get(name).unwrap().clone();
+            assert_eq!(package.source, DependencySource::Path);
+            package.checksum.unwrap()
+        };
+        let (alpha_before, beta_before) = (checksum("alpha"), checksum("beta"));
+        assert!(alpha_before.starts_with("blake3:"));
+
+        // Editing a linked package shows in its checksum
+        std::fs::write(alpha.join("Extra.swift"), "// new\n").unwrap();
+        install(false).install().await.unwrap();
+        assert_ne!(checksum("alpha"), alpha_before);
+        assert_eq!(checksum("beta"), beta_before);
+
+        // beta is only in alpha's manifest, which is where --frozen finds it
+        let checkouts = gust_build::target_dir(&project, None).join("checkouts");
+        std::fs::remove_dir_all(&checkouts).unwrap();
+        install(true).install().await.unwrap();
+        assert!(checkouts.join("beta/Gust.toml").exists());
+
+        // Unless alpha no longer lists it
+        std::fs::write(
+            alpha.join("Gust.toml"),
+            "[package]\nname = \"alpha\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+        let err = install(true).install().await.unwrap_err();
+        assert!(err.to_string().contains("'beta'"), "{}", err);
+    }
+
+    #[test]
+    fn test_prune_removes_only_unreferenced_checkouts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = GlobalCache::open_at(tmp.path().join("cache")).unwrap();
+        for name in ["dropped", "kept", "shared", "orphan"] {
+            std::fs::create_dir_all(cache.git_dir().join(name)).unwrap();
+        }
+        let lockfile = |names: &[&str]| Lockfile {
+            packages: names
+                .iter()
+                .map(|n| LockedPackage::git(*n, Version::new(1, 0, 0), "file:///x", "0000000"))
+                .collect(),
+            ..Default::default()
+        };
+
+        // Another project still locks `shared`
+        let other = tmp.path().join("other");
+        std::fs::create_dir_all(&other).unwrap();
+        lockfile(&["shared"])
+            .save(&other.join("Gust.lock"))
+            .unwrap();
+        cache.register_project(&other).unwrap();
+
+        // This project dropped `dropped` and `shared`
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        cache.register_project(&project).unwrap();
+        let previous = lockfile(&["dropped", "kept", "shared"]);
+        let current = lockfile(&["kept"]);
+
+        let pruned = prune_cache(
+            &cache,
+            &project,
+            Some(&previous),
+            &current,
+            PruneScope::Project,
+        )
+        .unwrap();
+        assert_eq!(pruned, ["dropped"]);
+        assert!(!cache.git_dir().join("dropped").exists());
+        for name in ["kept", "shared", "orphan"] {
+            assert!(cache.git_dir().join(name).exists(), "{} was pruned", name);
+        }
+
+        // Globally, checkouts no project references go too
+        let pruned = prune_cache(&cache, &project, None, &current, PruneScope::Global).unwrap();
+        assert_eq!(pruned, ["orphan"]);
+        assert!(cache.git_dir().join("kept").exists());
+        assert!(cache.git_dir().join("shared").exists());
+    }
+
+    #[tokio::test]
+    async fn test_dev_dependencies_resolved_for_root_only() {
+        let tmp = tempfile::TempDir::new().unwrap();
+
+        // The root depends on `lib` and has `testkit` as a dev-dependency
+        let mut manifest = Manifest::default();
+        manifest.dependencies.insert(
+            "lib".to_string(),
+            Dependency::path("lib", tmp.path().join("lib")),
+        );
+        manifest.dev_dependencies.insert(
+            "testkit".to_string(),
+            Dependency::path("testkit", tmp.path().join("testkit")),
+        );
+
+        let seeds: Vec<String> = root_dependencies(&manifest, true)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(seeds.contains(&"lib".to_string()));
+        assert!(seeds.contains(&"testkit".to_string()));
+        assert_eq!(root_dependencies(&manifest, false).len(), 1);
+
+        // `lib` has its own dev-dependency, which must not be discovered
+        let lib_dir = tmp.path().join("lib");
+        std::fs::create_dir_all(&lib_dir).unwrap();
+        std::fs::write(
+            lib_dir.join("Gust.toml"),
+            r#"
+[package]
+name = "lib"
+version = "1.0.0"
+
+[dependencies]
+core = { path = "../core" }
+
+[dev-dependencies]
+mock = { path = "../mock" }
+"#,
+        )
+        .unwrap();
+
+        let (parsed, discovered) =
+            parse_transitive_deps(vec![("lib".to_string(), lib_dir)], 1).await;
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(discovered, vec!["core".to_string()]);
+
+        // Packages only reachable through dev-dependencies are marked dev-only
+        let packages: HashMap<String, ResolvedDep> = [
+            ("lib".to_string(), resolved("lib", &["core"])),
+            ("core".to_string(), resolved("core", &[])),
+            (
+                "testkit".to_string(),
+                resolved("testkit", &["core", "fixtures"]),
+            ),
+            ("fixtures".to_string(), resolved("fixtures", &[])),
+        ]
+        .into_iter()
+        .collect();
+
+        let dev_only = dev_only_packages(&manifest, &packages);
+        assert_eq!(
+            dev_only,
+            HashSet::from(["testkit".to_string(), "fixtures".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_binary_target_checksum_verified_and_locked() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("app");
+        std::fs::create_dir_all(&project).unwrap();
+
+        let archive = tmp.path().join("Sdk.xcframework.zip");
+        let checksum = gust_testkit::write_xcframework_zip(&archive);
+        let url = format!("file://{}", archive.display());
+
+        let installer = Installer {
+            project_dir: project.clone(),
+            cache: GlobalCache::open_at(tmp.path().join("cache")).unwrap(),
+            fetcher: Fetcher::new(),
+            registry: gust_registry::RegistryClient::new(),
+            reporter: ReporterKind::Plain.reporter(),
+            options: InstallOptions::default(),
+        };
+        let write_manifest = |checksum: &str| {
+            std::fs::write(
+                project.join("Gust.toml"),
+                format!(
+                    "[package]\nname = \"App\"\nversion = \"0.1.0\"\n\n[[target]]\nname = \"Sdk\"\ntype = \"binary\"\nurl = \"{}\"\nchecksum = \"{}\"\n",
+                    url, checksum
+                ),
+            )
+            .unwrap();
+        };
+
+        write_manifest(&"0".repeat(64));
+        let err = installer.install().await.unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{}", err);
+        assert!(!project.join("Gust.lock").exists());
+
+        write_manifest(&checksum);
+        installer.install().await.unwrap();
+
+        let linked = project.join(".build/artifacts/app/Sdk");
+        assert!(linked.is_symlink());
+        assert!(linked.join("Sdk.xcframework/Info.plist").is_file());
+
+        let lockfile = Lockfile::load(&project.join("Gust.lock")).unwrap();
+        assert_eq!(
+            lockfile.artifacts,
+            vec![LockedArtifact {
+                name: "Sdk".to_string(),
+                url,
+                checksum,
+            }]
+        );
+    }
 }
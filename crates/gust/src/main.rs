@@ -2,13 +2,17 @@
 
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::Shell;
+use commands::ui::{CoverageFormat, DepsFormat, ManifestFormat, ManifestOutput, OutputFormat};
 use miette::Result;
 use std::io;
 use std::path::PathBuf;
 
 mod commands;
+mod config;
+mod edits;
 mod install;
 mod package_index;
+mod reporter;
 mod update_checker;
 
 #[derive(Parser)]
@@ -33,10 +37,14 @@ struct GlobalOptions {
     #[arg(short, long, global = true)]
     quiet: bool,
 
-    /// Disable colored output
+    /// Disable colored output, same as `--color never`
     #[arg(long, global = true)]
     no_color: bool,
 
+    /// When to color output
+    #[arg(long, global = true, value_enum, default_value_t = commands::ui::ColorChoice::Auto)]
+    color: commands::ui::ColorChoice,
+
     /// Path to Gust.toml or Package.swift
     #[arg(long, global = true)]
     manifest: Option<PathBuf>,
@@ -44,6 +52,10 @@ struct GlobalOptions {
     /// Number of parallel jobs
     #[arg(short, long, global = true)]
     jobs: Option<usize>,
+
+    /// Directory for checkouts and build products (default: .build)
+    #[arg(long, global = true, env = "GUST_TARGET_DIR")]
+    target_dir: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -55,9 +67,19 @@ enum Commands {
         /// Package type: executable, library
         #[arg(long, default_value = "library")]
         r#type: String,
+        /// Scaffold from a template: a built-in name (vapor, hummingbird),
+        /// git URL or local directory
+        #[arg(long)]
+        template: Option<String>,
         /// Don't create git repository
         #[arg(long)]
         no_git: bool,
+        /// Install dependencies after scaffolding from a template
+        #[arg(long, requires = "template")]
+        install: bool,
+        /// Manifest to write: toml (Gust.toml) or swift (Package.swift)
+        #[arg(long, value_enum, default_value_t = ManifestFormat::Toml, conflicts_with = "template")]
+        manifest_format: ManifestFormat,
     },
 
     /// Initialize a package in the current directory
@@ -68,6 +90,9 @@ enum Commands {
         /// Package type: executable, library
         #[arg(long, default_value = "library")]
         r#type: String,
+        /// Manifest to write: toml (Gust.toml) or swift (Package.swift)
+        #[arg(long, value_enum, default_value_t = ManifestFormat::Toml)]
+        manifest_format: ManifestFormat,
     },
 
     /// Add a dependency
@@ -84,12 +109,34 @@ enum Commands {
         /// Git tag
         #[arg(long)]
         tag: Option<String>,
+        /// Consider prerelease versions when picking the version to add
+        #[arg(long)]
+        allow_prerelease: bool,
         /// Local path
         #[arg(long)]
         path: Option<PathBuf>,
+        /// Features to enable on the dependency (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+        /// Products of the package targets import, instead of its primary
+        /// product (comma-separated)
+        #[arg(long = "product", value_delimiter = ',')]
+        products: Vec<String>,
         /// Add as dev dependency
         #[arg(long)]
         dev: bool,
+        /// Mark as optional, so it's only installed when a feature enables it
+        #[arg(long, conflicts_with = "dev")]
+        optional: bool,
+        /// Feature that enables the optional dependency
+        #[arg(long, requires = "optional")]
+        feature: Option<String>,
+        /// Install right after editing the manifest
+        #[arg(long, overrides_with = "no_install")]
+        install: bool,
+        /// Only edit the manifest (default unless [gust] auto-install is set)
+        #[arg(long, overrides_with = "install")]
+        no_install: bool,
     },
 
     /// Remove a dependency
@@ -105,6 +152,13 @@ enum Commands {
         /// Allow breaking version updates
         #[arg(long)]
         breaking: bool,
+        /// Show what would be updated without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Move the package to exactly this version, up or down
+        /// (`<version>` or `<package>@<version>`)
+        #[arg(long, value_name = "VERSION", conflicts_with = "breaking")]
+        precise: Option<String>,
     },
 
     /// Install dependencies
@@ -112,6 +166,117 @@ enum Commands {
         /// Error if lockfile is out of date
         #[arg(long)]
         frozen: bool,
+
+        /// Skip dev-dependencies (production install)
+        #[arg(long)]
+        no_dev: bool,
+
+        /// Explain why each package version was chosen
+        #[arg(long)]
+        explain: bool,
+
+        /// Print how long each install phase and the slowest fetches took
+        #[arg(long)]
+        timings: bool,
+
+        /// Format of the --timings report
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Features whose optional dependencies to install (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+
+        /// Don't enable the `default` feature
+        #[arg(long)]
+        no_default_features: bool,
+
+        /// Dependency groups to install as well (comma-separated)
+        #[arg(long = "group", value_delimiter = ',')]
+        groups: Vec<String>,
+
+        /// Install what can be installed when packages fail to fetch, then
+        /// report every failure
+        #[arg(long)]
+        keep_going: bool,
+
+        /// How to show progress; `pretty` on a terminal, `plain` otherwise
+        #[arg(long, value_enum, value_name = "NAME")]
+        reporter: Option<reporter::ReporterKind>,
+
+        /// Afterwards, remove cached git checkouts of packages this project
+        /// no longer locks
+        #[arg(long)]
+        prune: bool,
+
+        /// Afterwards, remove every cached git checkout no known project's
+        /// lockfile references
+        #[arg(long)]
+        prune_global: bool,
+
+        /// Link path dependencies into the cache, or copy them to install
+        /// a snapshot
+        #[arg(long, value_enum, value_name = "MODE", default_value_t = install::PathMode::Symlink)]
+        path_mode: install::PathMode,
+
+        /// Levels of transitive dependencies to resolve (default: 20)
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// Fail instead of warning when dependencies lie deeper than
+        /// --max-depth
+        #[arg(long)]
+        strict_depth: bool,
+
+        /// Force a package's version, beating Gust.toml's [overrides]
+        #[arg(long = "override", value_name = "PACKAGE@VERSION")]
+        overrides: Vec<String>,
+
+        /// Add a version requirement on a package, beating Gust.toml's
+        /// [constraints]
+        #[arg(long = "constraint", value_name = "PACKAGE@REQ")]
+        constraints: Vec<String>,
+
+        /// Install every package found under the current directory
+        #[arg(long, conflicts_with_all = ["explain", "timings", "features", "groups", "prune", "prune_global", "path_mode", "max_depth", "strict_depth", "overrides", "constraints"])]
+        recursive: bool,
+    },
+
+    /// Resolve dependencies and write Gust.lock without fetching or
+    /// linking them
+    Resolve {
+        /// Error if Gust.lock is out of date instead of updating it
+        #[arg(long, alias = "locked")]
+        frozen: bool,
+
+        /// Only use packages already in the cache
+        #[arg(long)]
+        offline: bool,
+
+        /// Skip dev-dependencies
+        #[arg(long)]
+        no_dev: bool,
+
+        /// Features whose optional dependencies to resolve (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+
+        /// Don't enable the `default` feature
+        #[arg(long)]
+        no_default_features: bool,
+
+        /// Dependency groups to resolve as well (comma-separated)
+        #[arg(long = "group", value_delimiter = ',')]
+        groups: Vec<String>,
+
+        /// Force a package's version, beating Gust.toml's [overrides]
+        #[arg(long = "override", value_name = "PACKAGE@VERSION")]
+        overrides: Vec<String>,
+
+        /// Add a version requirement on a package, beating Gust.toml's
+        /// [constraints]
+        #[arg(long = "constraint", value_name = "PACKAGE@REQ")]
+        constraints: Vec<String>,
     },
 
     /// Build the package
@@ -125,12 +290,50 @@ enum Commands {
         /// Disable binary artifact caching
         #[arg(long)]
         no_cache: bool,
+        /// Show the build fingerprint and what changed since the last build
+        #[arg(long)]
+        explain_cache: bool,
+        /// Features to enable (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+        /// Don't enable the `default` feature
+        #[arg(long)]
+        no_default_features: bool,
+        /// Dependency groups to include (comma-separated)
+        #[arg(long = "group", value_delimiter = ',')]
+        groups: Vec<String>,
+        /// SDK to build against (e.g. an iPhoneSimulator.sdk path)
+        #[arg(long, alias = "sdk-path", value_name = "PATH")]
+        sdk: Option<PathBuf>,
+        /// Fail the build on any compiler warning
+        #[arg(long)]
+        warnings_as_errors: bool,
+        /// Print the effective manifest, after workspace inheritance,
+        /// overrides and feature selection, instead of building
+        #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "json")]
+        print_manifest: Option<ManifestOutput>,
+        /// Build every workspace member, in dependency order
+        #[arg(
+            long,
+            conflicts_with_all = ["target", "explain_cache", "features", "no_default_features", "groups", "print_manifest"]
+        )]
+        workspace: bool,
+        /// With --workspace, only build members affected by files changed
+        /// since this git ref, and the members depending on them
+        #[arg(long, value_name = "REF", requires = "workspace")]
+        since: Option<String>,
     },
 
     /// Run the executable
     Run {
         /// Executable to run
         target: Option<String>,
+        /// Build and run in release mode
+        #[arg(long, short)]
+        release: bool,
+        /// Environment variable for the executable (repeatable)
+        #[arg(long = "env", value_name = "KEY=VAL", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
         /// Arguments to pass to the executable
         #[arg(last = true)]
         args: Vec<String>,
@@ -143,6 +346,22 @@ enum Commands {
         /// Filter tests by name
         #[arg(long)]
         filter: Option<String>,
+        /// Collect code coverage and print a per-target summary
+        #[arg(long)]
+        coverage: bool,
+        /// Format of the coverage report
+        #[arg(long, value_enum, default_value_t = CoverageFormat::Text, requires = "coverage")]
+        format: CoverageFormat,
+        /// Test every workspace member
+        #[arg(long, conflicts_with_all = ["coverage", "target"])]
+        workspace: bool,
+        /// With --workspace, only test members matching this pattern
+        #[arg(short, long, value_name = "PATTERN", requires = "workspace")]
+        package: Option<String>,
+        /// With --workspace, only test members affected by files changed
+        /// since this git ref, and the members depending on them
+        #[arg(long, value_name = "REF", requires = "workspace")]
+        since: Option<String>,
     },
 
     /// Clean build artifacts
@@ -160,17 +379,52 @@ enum Commands {
         /// Show duplicate versions
         #[arg(long)]
         duplicates: bool,
+        /// Only show what this target depends on
+        #[arg(long)]
+        target: Option<String>,
+    },
+
+    /// List every locked package, or export them as an SBOM
+    #[command(alias = "list")]
+    Deps {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DepsFormat::Text)]
+        format: DepsFormat,
+    },
+
+    /// Use a local checkout of a dependency until `gust unedit`
+    Edit {
+        /// Package name
+        package: String,
+        /// Existing local copy to use instead of cloning into edits/
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+
+    /// Go back to the locked revision of an edited dependency
+    Unedit {
+        /// Package name
+        package: String,
     },
 
     /// Check for outdated dependencies
     Outdated,
 
+    /// Check cached checkouts against the checksums in Gust.lock
+    Verify,
+
     /// Manage global cache
     Cache {
         #[command(subcommand)]
         action: CacheAction,
     },
 
+    /// Read and change settings in ~/.gust/config.toml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
     /// Migrate Package.swift to Gust.toml
     Migrate,
 
@@ -179,8 +433,11 @@ enum Commands {
 
     /// Show package info
     Info {
-        /// Package name
+        /// Package name, as scope.name or a bare name
         package: String,
+        /// Registry scope for a bare package name
+        #[arg(long)]
+        scope: Option<String>,
     },
 
     /// Search for packages
@@ -190,6 +447,9 @@ enum Commands {
         /// Maximum results to show
         #[arg(long, default_value = "10")]
         limit: usize,
+        /// Re-download the package index even if the cached copy is fresh
+        #[arg(long)]
+        refresh: bool,
     },
 
     /// Manage Swift toolchains
@@ -225,12 +485,19 @@ enum Commands {
     /// Generate man page
     #[command(hide = true)]
     Manpage,
+
+    /// Run an end-to-end smoke test of the install and build pipeline
+    #[command(name = "_selftest", hide = true)]
+    Selftest,
 }
 
 #[derive(Subcommand)]
 enum SelfAction {
     /// Update gust to the latest version
     Update,
+    /// Run an end-to-end smoke test of the install and build pipeline
+    #[command(hide = true)]
+    Test,
 }
 
 #[derive(Subcommand)]
@@ -238,7 +505,11 @@ enum CacheAction {
     /// List cached packages
     List,
     /// Show cache statistics
-    Stats,
+    Stats {
+        /// Print a JSON report per cache, with sizes and entry ages
+        #[arg(long)]
+        json: bool,
+    },
     /// Clean cached packages
     Clean {
         /// Remove all cached packages
@@ -250,6 +521,38 @@ enum CacheAction {
     },
     /// Print cache directory path
     Path,
+    /// Move packages from stores left by older Gust versions into the
+    /// current one and remove the old stores
+    Migrate,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective value of a key
+    Get {
+        /// Config key (see `gust config list`)
+        key: String,
+    },
+    /// Set a key in the user config
+    Set {
+        /// Config key
+        key: String,
+        /// New value
+        value: String,
+        /// Write to .gust/config.toml in this project instead
+        #[arg(long)]
+        project: bool,
+    },
+    /// Remove a key from the user config
+    Unset {
+        /// Config key
+        key: String,
+        /// Remove from .gust/config.toml in this project instead
+        #[arg(long)]
+        project: bool,
+    },
+    /// List all keys and their effective values
+    List,
 }
 
 #[derive(Subcommand)]
@@ -275,10 +578,26 @@ enum SwiftAction {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Setup error handling
-    gust_diagnostics::setup();
-
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+
+    // Settings from ~/.gust/config.toml and .gust/config.toml rank below
+    // flags and environment variables
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let config = config::GustConfig::load(&cwd).unwrap_or_else(|e| {
+        eprintln!("warning: ignoring config: {}", e);
+        config::GustConfig::default()
+    });
+    config::configure_crates(&config);
+    cli.global.jobs = config::resolve(
+        cli.global.jobs,
+        config::env_var("GUST_JOBS").and_then(|v| v.parse().ok()),
+        config.jobs,
+    );
+
+    // Setup output styling and error handling
+    let color = commands::ui::color_choice(cli.global.color, cli.global.no_color, config.color);
+    commands::ui::init_colors(color);
+    gust_diagnostics::setup(color.forced());
 
     // Setup logging
     let log_level = match cli.global.verbose {
@@ -292,6 +611,7 @@ async fn main() -> Result<()> {
         tracing_subscriber::fmt()
             .with_max_level(log_level)
             .with_target(false)
+            .with_ansi(color != commands::ui::ColorChoice::Never)
             .init();
     }
 
@@ -299,67 +619,309 @@ async fn main() -> Result<()> {
         Commands::New {
             name,
             r#type,
+            template,
             no_git,
+            install,
+            manifest_format,
+        } => {
+            commands::new_package(
+                &name,
+                &r#type,
+                template.as_deref(),
+                no_git,
+                install,
+                manifest_format,
+            )
+            .await?;
+        }
+        Commands::Init {
+            name,
+            r#type,
+            manifest_format,
+        } => {
+            commands::init(name.as_deref(), &r#type, manifest_format).await?;
+        }
+        Commands::Build {
+            features,
+            no_default_features,
+            groups,
+            print_manifest: Some(format),
+            ..
         } => {
-            commands::new_package(&name, &r#type, no_git).await?;
+            commands::print_manifest(format, &features, no_default_features, &groups)?;
         }
-        Commands::Init { name, r#type } => {
-            commands::init(name.as_deref(), &r#type).await?;
+        Commands::Build {
+            release,
+            no_cache,
+            sdk,
+            warnings_as_errors,
+            workspace: true,
+            since,
+            ..
+        } => {
+            commands::build_workspace(
+                since.as_deref(),
+                &commands::BuildArgs {
+                    release,
+                    jobs: cli.global.jobs,
+                    no_cache,
+                    sdk,
+                    warnings_as_errors,
+                    target_dir: cli.global.target_dir,
+                    ..Default::default()
+                },
+            )
+            .await?;
         }
         Commands::Build {
             release,
             target,
             no_cache,
+            explain_cache,
+            features,
+            no_default_features,
+            groups,
+            sdk,
+            warnings_as_errors,
+            print_manifest: None,
+            workspace: false,
+            since: _,
         } => {
-            commands::build(release, target.as_deref(), cli.global.jobs, no_cache).await?;
+            commands::build(&commands::BuildArgs {
+                release,
+                target,
+                jobs: cli.global.jobs,
+                no_cache,
+                explain_cache,
+                features,
+                no_default_features,
+                groups,
+                sdk,
+                warnings_as_errors,
+                target_dir: cli.global.target_dir,
+            })
+            .await?;
         }
-        Commands::Run { target, args } => {
-            commands::run(target.as_deref(), &args).await?;
+        Commands::Run {
+            target,
+            release,
+            env,
+            args,
+        } => {
+            commands::run(
+                target.as_deref(),
+                release,
+                &env,
+                &args,
+                cli.global.target_dir.as_deref(),
+            )
+            .await?;
         }
-        Commands::Test { target, filter } => {
-            commands::test(target.as_deref(), filter.as_deref()).await?;
+        Commands::Test {
+            target,
+            filter,
+            coverage,
+            format,
+            workspace,
+            package,
+            since,
+        } => {
+            if workspace {
+                commands::test_workspace(
+                    package.as_deref(),
+                    since.as_deref(),
+                    filter.as_deref(),
+                    cli.global.target_dir.as_deref(),
+                )
+                .await?;
+            } else {
+                commands::test(
+                    target.as_deref(),
+                    filter.as_deref(),
+                    coverage.then_some(format),
+                    cli.global.target_dir.as_deref(),
+                )
+                .await?;
+            }
         }
         Commands::Clean { deps } => {
-            commands::clean(deps).await?;
+            commands::clean(deps, cli.global.target_dir.as_deref()).await?;
         }
         Commands::Add {
             package,
             git,
             branch,
             tag,
+            allow_prerelease,
             path,
+            features,
+            products,
             dev,
+            optional,
+            feature,
+            install,
+            no_install,
         } => {
+            // Without a flag, the manifest's [gust] auto-install decides
+            // unless the config turns it on
+            let install = match (install, no_install) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => config.auto_install.filter(|on| *on),
+            };
             commands::add(
                 &package,
-                git.as_deref(),
-                branch.as_deref(),
-                tag.as_deref(),
-                path.as_deref(),
-                dev,
+                &commands::AddArgs {
+                    git,
+                    branch,
+                    tag,
+                    allow_prerelease,
+                    path,
+                    features,
+                    products,
+                    dev,
+                    optional: optional.then_some(feature),
+                    install,
+                    jobs: cli.global.jobs,
+                    target_dir: cli.global.target_dir,
+                },
             )
             .await?;
         }
         Commands::Remove { package } => {
             commands::remove(&package).await?;
         }
-        Commands::Install { frozen } => {
-            commands::install(frozen).await?;
+        Commands::Resolve {
+            frozen,
+            offline,
+            no_dev,
+            features,
+            no_default_features,
+            groups,
+            overrides,
+            constraints,
+        } => {
+            commands::resolve(&commands::ResolveArgs {
+                frozen,
+                offline,
+                no_dev,
+                features,
+                no_default_features,
+                groups,
+                overrides,
+                constraints,
+                jobs: cli.global.jobs,
+            })
+            .await?;
         }
-        Commands::Update { package, breaking } => {
-            commands::update(package.as_deref(), breaking).await?;
+        Commands::Install {
+            frozen,
+            no_dev,
+            explain,
+            timings,
+            format,
+            features,
+            no_default_features,
+            groups,
+            keep_going,
+            reporter,
+            prune,
+            prune_global,
+            path_mode,
+            max_depth,
+            strict_depth,
+            overrides,
+            constraints,
+            recursive,
+        } => {
+            let timings = timings.then_some(format);
+            let prune = match (prune, prune_global) {
+                (_, true) => Some(install::PruneScope::Global),
+                (true, false) => Some(install::PruneScope::Project),
+                (false, false) => None,
+            };
+            if recursive {
+                commands::install_recursive(
+                    frozen,
+                    no_dev,
+                    no_default_features,
+                    keep_going,
+                    reporter,
+                    cli.global.jobs,
+                    cli.global.target_dir.as_deref(),
+                )
+                .await?;
+            } else {
+                commands::install(&commands::InstallArgs {
+                    frozen,
+                    no_dev,
+                    explain,
+                    timings,
+                    features,
+                    no_default_features,
+                    groups,
+                    keep_going,
+                    reporter,
+                    prune,
+                    path_mode,
+                    max_depth,
+                    strict_depth,
+                    overrides,
+                    constraints,
+                    jobs: cli.global.jobs,
+                    target_dir: cli.global.target_dir,
+                })
+                .await?;
+            }
         }
-        Commands::Tree { depth, duplicates } => {
-            commands::tree(depth, duplicates).await?;
+        Commands::Update {
+            package,
+            breaking,
+            dry_run,
+            precise,
+        } => {
+            commands::update(package.as_deref(), breaking, precise.as_deref(), dry_run).await?;
+        }
+        Commands::Tree {
+            depth,
+            duplicates,
+            target,
+        } => {
+            commands::tree(depth, duplicates, target.as_deref()).await?;
+        }
+        Commands::Deps { format } => {
+            commands::deps(format).await?;
+        }
+        Commands::Edit { package, path } => {
+            commands::edit(&package, path.as_deref(), cli.global.target_dir.as_deref()).await?;
+        }
+        Commands::Unedit { package } => {
+            commands::unedit(&package, cli.global.target_dir.as_deref()).await?;
         }
         Commands::Outdated => {
             commands::outdated().await?;
         }
+        Commands::Verify => {
+            commands::verify().await?;
+        }
         Commands::Cache { action } => match action {
             CacheAction::List => commands::cache_list().await?,
-            CacheAction::Stats => commands::cache_stats().await?,
+            CacheAction::Stats { json } => commands::cache_stats(json).await?,
             CacheAction::Clean { all, binary } => commands::cache_clean(all, binary).await?,
             CacheAction::Path => commands::cache_path().await?,
+            CacheAction::Migrate => commands::cache_migrate().await?,
+        },
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key } => commands::config_get(&key).await?,
+            ConfigAction::Set {
+                key,
+                value,
+                project,
+            } => commands::config_set(&key, Some(&value), project).await?,
+            ConfigAction::Unset { key, project } => {
+                commands::config_set(&key, None, project).await?
+            }
+            ConfigAction::List => commands::config_list().await?,
         },
         Commands::Migrate => {
             commands::migrate().await?;
@@ -367,11 +929,15 @@ async fn main() -> Result<()> {
         Commands::Generate => {
             commands::generate().await?;
         }
-        Commands::Info { package } => {
-            commands::info(&package).await?;
+        Commands::Info { package, scope } => {
+            commands::info(&package, scope.as_deref()).await?;
         }
-        Commands::Search { query, limit } => {
-            commands::search(&query, limit).await?;
+        Commands::Search {
+            query,
+            limit,
+            refresh,
+        } => {
+            commands::search(&query, limit, refresh).await?;
         }
         Commands::Swift { action } => match action {
             SwiftAction::List => commands::swift_list().await?,
@@ -390,7 +956,15 @@ async fn main() -> Result<()> {
                 update_checker::self_update().await?;
                 return Ok(()); // Skip update check after self-update
             }
+            SelfAction::Test => {
+                commands::self_test().await?;
+                return Ok(());
+            }
         },
+        Commands::Selftest => {
+            commands::self_test().await?;
+            return Ok(());
+        }
         Commands::Completions { shell } => {
             clap_complete::generate(shell, &mut Cli::command(), "gust", &mut io::stdout());
             return Ok(()); // Skip update check for completions
@@ -411,3 +985,11 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Parse a `KEY=VAL` argument.
+fn parse_env_var(arg: &str) -> std::result::Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VAL, got '{}'", arg)),
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Fetches and caches the package list from Swift Package Index for search.
 
+use gust_cache::GlobalCache;
 use miette::{IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -47,7 +48,9 @@ impl PackageCache {
 
 /// Get the cache file path.
 fn cache_path() -> Option<PathBuf> {
-    dirs::cache_dir().map(|d| d.join("gust").join("package-index.json"))
+    GlobalCache::open()
+        .ok()
+        .map(|c| c.index_dir().join("package-index.json"))
 }
 
 /// Load cached package list.
@@ -96,15 +99,34 @@ fn parse_github_url(url: &str) -> Option<(String, String)> {
 }
 
 /// Fetch the package list from Swift Package Index.
-pub async fn fetch_package_list() -> Result<Vec<String>> {
-    // Check cache first
-    if let Some(cache) = load_cache() {
-        if !cache.is_stale() {
-            return Ok(cache.packages);
+///
+/// A cached list younger than a day is used without touching the network
+/// unless `refresh` is set. If the fetch fails, a stale cached list is
+/// used instead so search keeps working offline.
+pub async fn fetch_package_list(refresh: bool) -> Result<Vec<String>> {
+    let cached = load_cache();
+    if let Some(cache) = &cached {
+        if !refresh && !cache.is_stale() {
+            return Ok(cache.packages.clone());
         }
     }
 
-    // Fetch fresh list
+    match download_package_list().await {
+        Ok(packages) => {
+            let _ = save_cache(&packages);
+            Ok(packages)
+        }
+        Err(e) => match cached {
+            Some(cache) => {
+                tracing::warn!("Using stale package index: {}", e);
+                Ok(cache.packages)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+async fn download_package_list() -> Result<Vec<String>> {
     let client = reqwest::Client::new();
     let resp = client
         .get(PACKAGE_LIST_URL)
@@ -119,68 +141,74 @@ pub async fn fetch_package_list() -> Result<Vec<String>> {
         ));
     }
 
-    let packages: Vec<String> = resp.json().await.into_diagnostic()?;
+    resp.json().await.into_diagnostic()
+}
 
-    // Cache the result
-    let _ = save_cache(&packages);
+/// How well a package matches a search query; higher is better.
+///
+/// Name matches beat owner matches: an exact name, then a name component
+/// (`nio` in `swift-nio`), a name prefix, a component prefix, a substring,
+/// and finally the query's letters appearing in order.
+fn match_score(name: &str, owner: &str, query: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    let segments: Vec<&str> = name.split(['-', '_', '.']).collect();
 
-    Ok(packages)
+    let score = if name == query {
+        100
+    } else if segments.contains(&query) {
+        90
+    } else if name.starts_with(query) {
+        80
+    } else if segments.iter().any(|s| s.starts_with(query)) {
+        70
+    } else if name.contains(query) {
+        50
+    } else if is_subsequence(query, &name) {
+        30
+    } else if owner.to_lowercase().contains(query) {
+        10
+    } else {
+        return None;
+    };
+    Some(score)
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|c| chars.any(|h| h == c))
 }
 
-/// Search for packages matching a query.
-pub async fn search_packages(query: &str, limit: usize) -> Result<Vec<IndexedPackage>> {
-    let packages = fetch_package_list().await?;
-    let query_lower = query.to_lowercase();
+/// Rank package URLs against a query, best match first.
+///
+/// Ties go to the shorter name, then alphabetically.
+pub fn rank_packages(urls: &[String], query: &str) -> Vec<IndexedPackage> {
+    let query = query.trim().to_lowercase();
 
-    let mut matches: Vec<IndexedPackage> = packages
+    let mut scored: Vec<(u32, IndexedPackage)> = urls
         .iter()
         .filter_map(|url| {
             let (owner, name) = parse_github_url(url)?;
-            // Match against package name or owner
-            if name.to_lowercase().contains(&query_lower)
-                || owner.to_lowercase().contains(&query_lower)
-            {
-                Some(IndexedPackage {
+            let score = match_score(&name, &owner, &query)?;
+            Some((
+                score,
+                IndexedPackage {
                     name,
                     owner,
                     url: url.clone(),
-                })
-            } else {
-                None
-            }
+                },
+            ))
         })
         .collect();
 
-    // Sort by relevance: exact name matches first, then name starts with, then contains
-    matches.sort_by(|a, b| {
-        let a_name_lower = a.name.to_lowercase();
-        let b_name_lower = b.name.to_lowercase();
-
-        // Exact match
-        let a_exact = a_name_lower == query_lower;
-        let b_exact = b_name_lower == query_lower;
-        if a_exact != b_exact {
-            return b_exact.cmp(&a_exact);
-        }
-
-        // Starts with
-        let a_starts = a_name_lower.starts_with(&query_lower);
-        let b_starts = b_name_lower.starts_with(&query_lower);
-        if a_starts != b_starts {
-            return b_starts.cmp(&a_starts);
-        }
-
-        // Alphabetical
-        a_name_lower.cmp(&b_name_lower)
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then(a.name.len().cmp(&b.name.len()))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+            .then_with(|| a.owner.cmp(&b.owner))
     });
 
-    Ok(matches.into_iter().take(limit).collect())
-}
-
-/// Get the total number of indexed packages.
-pub async fn package_count() -> Result<usize> {
-    let packages = fetch_package_list().await?;
-    Ok(packages.len())
+    scored.into_iter().map(|(_, pkg)| pkg).collect()
 }
 
 #[cfg(test)]
@@ -197,4 +225,40 @@ mod tests {
         assert_eq!(owner, "vapor");
         assert_eq!(name, "vapor");
     }
+
+    #[test]
+    fn test_rank_packages() {
+        let index: Vec<String> = [
+            "https://github.com/someone/union-find.git",
+            "https://github.com/apple/swift-nio-ssl.git",
+            "https://github.com/nioworks/helpers.git",
+            "https://github.com/apple/swift-nio.git",
+            "https://github.com/example/niobe.git",
+            "https://github.com/example/snowing-io.git",
+            "https://github.com/vapor/vapor.git",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let names: Vec<String> = rank_packages(&index, "nio")
+            .into_iter()
+            .map(|p| format!("{}/{}", p.owner, p.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "apple/swift-nio",
+                "apple/swift-nio-ssl",
+                "example/niobe",
+                "someone/union-find",
+                "example/snowing-io",
+                "nioworks/helpers",
+            ]
+        );
+
+        let exact = rank_packages(&index, "Vapor");
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].name, "vapor");
+    }
 }
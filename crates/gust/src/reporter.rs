@@ -0,0 +1,476 @@
+//! Progress output for `gust install`.
+//!
+//! The installer describes what it is doing as [`Event`]s and leaves drawing
+//! them to a [`Reporter`]: spinners and a progress bar on a terminal, one
+//! line per event in CI logs, or one JSON object per line for tools.
+
+use crate::commands::ui;
+use crate::install::{InstallTimings, LinkStats};
+use console::style;
+use gust_fetch::FetchStatus;
+use indicatif::{MultiProgress, ProgressBar};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How install progress is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReporterKind {
+    /// Spinners and a progress bar
+    Pretty,
+    /// One line of plain text per event, for CI logs
+    Plain,
+    /// One JSON object per event and line
+    Json,
+}
+
+impl ReporterKind {
+    /// `Pretty` when stdout is a terminal, `Plain` otherwise.
+    pub fn detect() -> Self {
+        if console::Term::stdout().is_term() {
+            Self::Pretty
+        } else {
+            Self::Plain
+        }
+    }
+
+    /// A reporter of this kind writing to stdout (the progress bar of
+    /// `Pretty` draws to stderr).
+    pub fn reporter(self) -> Arc<dyn Reporter> {
+        match self {
+            Self::Pretty => Arc::new(PrettyReporter::new()),
+            Self::Plain => Arc::new(PlainReporter::new(std::io::stdout())),
+            Self::Json => Arc::new(JsonReporter::new(std::io::stdout())),
+        }
+    }
+
+    /// A reporter of this kind writing to stderr, leaving stdout to
+    /// machine-readable output such as `--timings --format json`.
+    pub fn stderr_reporter(self) -> Arc<dyn Reporter> {
+        match self {
+            Self::Pretty => Arc::new(PrettyReporter::to_stderr()),
+            Self::Plain => Arc::new(PlainReporter::new(std::io::stderr())),
+            Self::Json => Arc::new(JsonReporter::new(std::io::stderr())),
+        }
+    }
+}
+
+/// Something the installer did or is doing.
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    /// A step started, such as reading the manifest
+    Started(&'a str),
+    /// The current step finished, or a step without a start completed
+    Finished(&'a str),
+    /// The current step was abandoned; an error follows
+    Cancelled,
+    /// A notice for the user
+    Info(&'a str),
+    /// Something the user should look at, though the step went on
+    Warning(&'a str),
+    /// Extra output asked for, such as the resolution trace
+    Detail(&'a str),
+    /// This many packages are about to be fetched
+    FetchStarted { count: usize },
+    /// A package fetch changed state
+    Fetch {
+        name: &'a str,
+        status: &'a FetchStatus,
+    },
+    /// Every package was fetched
+    FetchFinished(&'a str),
+    /// This many packages could not be fetched
+    FetchFailed { errors: usize },
+    /// The install finished
+    Installed { packages: usize, links: LinkStats },
+    /// Where the time went, with the `slowest` slowest fetches
+    Timings {
+        timings: &'a InstallTimings,
+        slowest: usize,
+    },
+}
+
+/// Shows install progress.
+pub trait Reporter: Send + Sync {
+    /// Show one event.
+    fn report(&self, event: Event<'_>);
+}
+
+/// Spinners for each step and a progress bar while fetching.
+pub struct PrettyReporter {
+    mp: MultiProgress,
+    state: Mutex<PrettyState>,
+    /// Print lines to stderr instead of stdout
+    stderr: bool,
+}
+
+#[derive(Default)]
+struct PrettyState {
+    /// Spinner of the running step
+    spinner: Option<ProgressBar>,
+    /// Progress bar while fetching
+    bar: Option<ProgressBar>,
+    /// Packages being fetched, in the order they started
+    active: Vec<String>,
+    /// Latest byte count per package
+    received: HashMap<String, u64>,
+}
+
+impl PrettyReporter {
+    pub fn new() -> Self {
+        Self {
+            mp: MultiProgress::new(),
+            state: Mutex::new(PrettyState::default()),
+            stderr: false,
+        }
+    }
+
+    /// A reporter that prints its lines to stderr.
+    pub fn to_stderr() -> Self {
+        Self {
+            stderr: true,
+            ..Self::new()
+        }
+    }
+
+    /// Print a line outside the progress bars.
+    fn line(&self, line: impl std::fmt::Display) {
+        if self.stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+impl Default for PrettyReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrettyState {
+    /// The fetch bar message; size and throughput only show once some
+    /// fetch has reported bytes.
+    fn fetch_message(&self, elapsed: Duration) -> String {
+        let active = &self.active;
+        let names = if active.len() <= 3 {
+            active.join(", ")
+        } else {
+            format!("{} and {} more", active[..3].join(", "), active.len() - 3)
+        };
+        let total: u64 = self.received.values().sum();
+        if total > 0 {
+            format!(
+                "Fetching: {} ({})",
+                names,
+                ui::transfer_summary(total, elapsed)
+            )
+        } else {
+            format!("Fetching: {}", names)
+        }
+    }
+}
+
+impl Reporter for PrettyReporter {
+    fn report(&self, event: Event<'_>) {
+        let mut state = self.state.lock().unwrap();
+        match event {
+            Event::Started(message) => {
+                let spinner = self.mp.add(ProgressBar::new_spinner());
+                spinner.set_style(ui::spinner_style());
+                spinner.set_message(message.to_string());
+                spinner.enable_steady_tick(Duration::from_millis(100));
+                state.spinner = Some(spinner);
+            }
+            Event::Finished(message) => {
+                let line = format!("{} {}", style("✓").green(), message);
+                match state.spinner.take() {
+                    Some(spinner) => spinner.finish_with_message(line),
+                    None => self.line(line),
+                }
+            }
+            Event::Cancelled => {
+                if let Some(spinner) = state.spinner.take() {
+                    spinner.finish_and_clear();
+                }
+            }
+            Event::Info(message) => self.line(format!("{} {}", style("→").blue().bold(), message)),
+            Event::Warning(message) => {
+                self.line(format!("{} {}", style("!").yellow().bold(), message))
+            }
+            Event::Detail(message) => self.line(message),
+            Event::FetchStarted { count } => {
+                let bar = self.mp.add(ProgressBar::new(count as u64));
+                bar.set_style(ui::bar_style());
+                state.bar = Some(bar);
+                state.active.clear();
+                state.received.clear();
+            }
+            Event::Fetch { name, status } => {
+                match status {
+                    FetchStatus::Started => state.active.push(name.to_string()),
+                    FetchStatus::Progress { bytes, .. } => {
+                        state.received.insert(name.to_string(), *bytes);
+                    }
+                    FetchStatus::Completed | FetchStatus::Failed(_) | FetchStatus::TimedOut => {
+                        state.active.retain(|n| n != name);
+                    }
+                }
+                if let Some(bar) = &state.bar {
+                    match status {
+                        FetchStatus::Started | FetchStatus::Progress { .. } => {
+                            bar.set_message(state.fetch_message(bar.elapsed()))
+                        }
+                        _ => bar.inc(1),
+                    }
+                }
+            }
+            Event::FetchFinished(message) => {
+                if let Some(bar) = state.bar.take() {
+                    bar.finish_with_message(format!("{} {}", style("✓").green(), message));
+                }
+            }
+            Event::FetchFailed { errors } => {
+                if let Some(bar) = state.bar.take() {
+                    bar.abandon_with_message(format!("{} errors during fetch", errors));
+                }
+            }
+            Event::Installed { packages, links } => self.line(format!(
+                "\n{} Installed {} packages {}",
+                style("✓").green().bold(),
+                style(packages).cyan(),
+                ui::dim(format!("(links: {})", links))
+            )),
+            Event::Timings { timings, slowest } => {
+                self.line("");
+                for line in timings.report(slowest) {
+                    self.line(line);
+                }
+            }
+        }
+    }
+}
+
+/// One line of plain text per event.
+///
+/// Byte counts are left out, since they depend on timing and would flood a
+/// log; styling is stripped so lines are the same on every run.
+pub struct PlainReporter<W> {
+    out: Mutex<W>,
+}
+
+impl<W: Write> PlainReporter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+        }
+    }
+
+    /// The writer, with everything reported so far.
+    #[cfg(test)]
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+}
+
+impl<W: Write + Send> Reporter for PlainReporter<W> {
+    fn report(&self, event: Event<'_>) {
+        let line = match event {
+            Event::Started(message)
+            | Event::Finished(message)
+            | Event::Info(message)
+            | Event::Detail(message)
+            | Event::FetchFinished(message) => message.to_string(),
+            Event::Warning(message) => format!("warning: {}", message),
+            Event::Cancelled => return,
+            Event::FetchStarted { count } => format!("Fetching {} packages", count),
+            Event::Fetch { name, status } => match status {
+                FetchStatus::Started => format!("{}: fetching", name),
+                FetchStatus::Completed => format!("{}: fetched", name),
+                FetchStatus::Failed(err) => format!("{}: failed: {}", name, err),
+                FetchStatus::TimedOut => format!("{}: timed out", name),
+                FetchStatus::Progress { .. } => return,
+            },
+            Event::FetchFailed { errors } => format!("{} errors during fetch", errors),
+            Event::Installed { packages, links } => {
+                format!("Installed {} packages (links: {})", packages, links)
+            }
+            Event::Timings { timings, slowest } => timings.report(slowest).join("\n"),
+        };
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", console::strip_ansi_codes(&line));
+    }
+}
+
+/// One JSON object per event and line, with the kind of event in `event`.
+pub struct JsonReporter<W> {
+    out: Mutex<W>,
+    started_at: Instant,
+}
+
+impl<W: Write> JsonReporter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl<W: Write + Send> Reporter for JsonReporter<W> {
+    fn report(&self, event: Event<'_>) {
+        let message = |event: &str, message: &str| {
+            serde_json::json!({
+                "event": event,
+                "message": console::strip_ansi_codes(message),
+            })
+        };
+        let mut value = match event {
+            Event::Started(m) => message("started", m),
+            Event::Finished(m) => message("finished", m),
+            Event::Cancelled => serde_json::json!({ "event": "cancelled" }),
+            Event::Info(m) => message("info", m),
+            Event::Warning(m) => message("warning", m),
+            Event::Detail(m) => message("detail", m),
+            Event::FetchStarted { count } => {
+                serde_json::json!({ "event": "fetch_started", "count": count })
+            }
+            Event::Fetch { name, status } => {
+                let mut value = serde_json::json!({ "event": "fetch", "package": name });
+                let fields = match status {
+                    FetchStatus::Started => serde_json::json!({ "status": "started" }),
+                    FetchStatus::Progress { bytes, total } => {
+                        serde_json::json!({ "status": "progress", "bytes": bytes, "total": total })
+                    }
+                    FetchStatus::Completed => serde_json::json!({ "status": "completed" }),
+                    FetchStatus::Failed(err) => {
+                        serde_json::json!({ "status": "failed", "error": err })
+                    }
+                    FetchStatus::TimedOut => serde_json::json!({ "status": "timed_out" }),
+                };
+                if let (Some(value), serde_json::Value::Object(fields)) =
+                    (value.as_object_mut(), fields)
+                {
+                    value.extend(fields);
+                }
+                value
+            }
+            Event::FetchFinished(m) => message("fetch_finished", m),
+            Event::FetchFailed { errors } => {
+                serde_json::json!({ "event": "fetch_failed", "errors": errors })
+            }
+            Event::Installed { packages, links } => serde_json::json!({
+                "event": "installed",
+                "packages": packages,
+                "links": {
+                    "created": links.created,
+                    "updated": links.updated,
+                    "unchanged": links.unchanged,
+                },
+            }),
+            Event::Timings { timings, slowest } => {
+                let mut value = timings.to_json(slowest);
+                value["event"] = serde_json::json!("timings");
+                value
+            }
+        };
+        value["elapsed_ms"] = serde_json::json!(self.started_at.elapsed().as_millis() as u64);
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_reporter_lines_are_deterministic() {
+        let run = || {
+            let reporter = PlainReporter::new(Vec::new());
+            reporter.report(Event::Started("Reading manifest..."));
+            reporter.report(Event::Finished(&format!(
+                "Read manifest for {}",
+                style("app").cyan().force_styling(true)
+            )));
+            reporter.report(Event::FetchStarted { count: 2 });
+            for name in ["alpha", "beta"] {
+                reporter.report(Event::Fetch {
+                    name,
+                    status: &FetchStatus::Started,
+                });
+            }
+            reporter.report(Event::Fetch {
+                name: "alpha",
+                status: &FetchStatus::Progress {
+                    bytes: 1024,
+                    total: None,
+                },
+            });
+            reporter.report(Event::Fetch {
+                name: "alpha",
+                status: &FetchStatus::Completed,
+            });
+            reporter.report(Event::Fetch {
+                name: "beta",
+                status: &FetchStatus::Failed("not found".to_string()),
+            });
+            reporter.report(Event::FetchFailed { errors: 1 });
+            String::from_utf8(reporter.into_inner()).unwrap()
+        };
+
+        let output = run();
+        assert_eq!(output, run());
+        assert!(!output.contains('\x1b'));
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "Reading manifest...",
+                "Read manifest for app",
+                "Fetching 2 packages",
+                "alpha: fetching",
+                "beta: fetching",
+                "alpha: fetched",
+                "beta: failed: not found",
+                "1 errors during fetch",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_reporter_prints_only_json_lines() {
+        let mut timings = InstallTimings::default();
+        timings.phases.push(("fetch", Duration::from_millis(1500)));
+        timings
+            .fetches
+            .insert("alpha".to_string(), Duration::from_secs(1));
+
+        let reporter = JsonReporter::new(Vec::new());
+        reporter.report(Event::Started("Reading manifest..."));
+        reporter.report(Event::Installed {
+            packages: 1,
+            links: LinkStats {
+                created: 1,
+                ..Default::default()
+            },
+        });
+        reporter.report(Event::Timings {
+            timings: &timings,
+            slowest: 5,
+        });
+        let output = String::from_utf8(reporter.out.into_inner().unwrap()).unwrap();
+
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1]["event"], "installed");
+        assert_eq!(events[1]["packages"], 1);
+        assert_eq!(events[1]["links"]["created"], 1);
+        assert_eq!(events[2]["event"], "timings");
+        assert_eq!(events[2]["slowest_fetches"][0]["name"], "alpha");
+    }
+}